@@ -0,0 +1,435 @@
+use super::error::{ApiError, Result};
+use super::wallet::{find_user_wallet, participant_error, send_account_tx};
+use crate::chains::ChainRegistry;
+use crate::db::models::{
+    TransactionModel, WalletAccountModel, WalletModel, WalletOperation, derivation_path,
+};
+use crate::db::repositories::{
+    AuditRepository, TransactionRepository, WalletAccountRepository, WalletRepository,
+};
+use crate::participants::{ParticipantPool, account_address, key_quorum, wallet_infos};
+use crate::prices::{Asset, FiatValue, Prices, native_decimals};
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct CreateAccountRequest {
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct AccountBalanceResponse {
+    pub account_id: i32,
+    pub address: String,
+    /// Decimal balance in wei
+    pub balance: String,
+    /// Value of the balance, when the price feed quotes the chain currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
+}
+
+#[derive(Serialize)]
+pub struct AccountTransactionResponse {
+    #[serde(flatten)]
+    pub transaction: TransactionModel,
+    /// Value of the amount sent, at the current price rather than the one it was sent at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/{id}/accounts")
+            .route(web::get().to(list_accounts))
+            .route(web::post().to(create_account)),
+    )
+    .service(web::resource("/{id}/accounts/{account_id}").route(web::get().to(get_account)))
+    .service(
+        web::resource("/{id}/accounts/{account_id}/balance").route(web::get().to(account_balance)),
+    )
+    .service(
+        web::resource("/{id}/accounts/{account_id}/transactions")
+            .route(web::get().to(account_transactions)),
+    )
+    .service(
+        web::resource("/{id}/accounts/{account_id}/tx").route(web::post().to(send_account_tx)),
+    );
+}
+
+/// Account of the wallet, accounts of other wallets aren't found
+pub(super) async fn find_wallet_account(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    account_id: i32,
+) -> Result<WalletAccountModel> {
+    let account = WalletAccountRepository::new_with_connection(db)
+        .find_by_id(account_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the account"))?;
+
+    match account {
+        Some(account) if account.wallet_id == wallet.id => Ok(account),
+        _ => Err(ApiError::not_found(
+            "account_not_found",
+            "Account not found",
+        )),
+    }
+}
+
+/// Wallet of the user and its account named in the path
+async fn find_user_account(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    path: (i32, i32),
+) -> Result<(WalletModel, WalletAccountModel)> {
+    let user_id = request_user_id(req)?;
+    let (wallet_id, account_id) = path;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(db),
+        wallet_id,
+        user_id,
+    )
+    .await?;
+
+    let account = find_wallet_account(db, &wallet, account_id).await?;
+
+    Ok((wallet, account))
+}
+
+/// Address of the wallet's child key at `derivation_index`, read from the participants'
+/// shares without a signing round
+async fn derive_address(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    derivation_index: i32,
+) -> Result<String> {
+    let results = wallet_infos(participants, wallet, &derivation_path(derivation_index)).await;
+
+    let infos = results
+        .iter()
+        .filter_map(|res| res.as_ref().ok())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // Keys generated without a chain code are rejected by every participant
+    if infos.len() < key_quorum(wallet) {
+        return Err(participant_error(
+            &results,
+            "account_derivation_failed",
+            "Failed to derive the account key",
+        ));
+    }
+
+    account_address(wallet, &infos).map_err(|err| {
+        log::error!(
+            "Failed to derive account {derivation_index} of wallet {}: {err}",
+            wallet.id
+        );
+        ApiError::internal("Participants disagree on the account key").with_code("share_mismatch")
+    })
+}
+
+/// Creates a named sub-account at the next derivation index of an HD wallet, it shares the
+/// key shares of the wallet
+pub async fn create_account(
+    req: HttpRequest,
+    data: web::Json<CreateAccountRequest>,
+    db: web::Data<DatabaseConnection>,
+    participants: web::Data<dyn ParticipantPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let name = data.name.trim();
+
+    if name.is_empty() {
+        return Err(ApiError::bad_request("Account name must not be empty"));
+    }
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::CreateAccount)?;
+
+    // Balances and nonces of accounts are read from EVM providers
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::unprocessable(
+            "accounts_unsupported",
+            "Sub-accounts are only supported on EVM wallets",
+        ));
+    }
+
+    let repository = WalletAccountRepository::new_with_connection(&db);
+
+    let derivation_index = repository
+        .next_index(wallet.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to create the account"))?;
+
+    let address = derive_address(participants.get_ref(), &wallet, derivation_index).await?;
+
+    // Also rejects a concurrent request that derived the same index
+    let account = repository
+        .create(wallet.id, name.to_string(), derivation_index, address)
+        .await
+        .map_err(|_| ApiError::conflict("Account already exists").with_code("account_exists"))?;
+
+    let audit = AuditRepository::new_with_connection(&db)
+        .record(
+            &format!("user:{user_id}"),
+            "wallet.account_created",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({
+                "account_id": account.id,
+                "derivation_index": account.derivation_index,
+                "address": account.address,
+            })),
+        )
+        .await;
+
+    if let Err(err) = audit {
+        log::error!(
+            "Failed to audit the creation of account {}: {err}",
+            account.id
+        );
+    }
+
+    Ok(HttpResponse::Created().json(account))
+}
+
+pub async fn list_accounts(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    let accounts = WalletAccountRepository::new_with_connection(&db)
+        .find_by_wallet_id(wallet.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the accounts"))?;
+
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+pub async fn get_account(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (_, account) = find_user_account(&req, &db, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(account))
+}
+
+/// Native balance of the account address, read from the chain provider
+pub async fn account_balance(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    prices: web::Data<Prices>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (wallet, account) = find_user_account(&req, &db, path.into_inner()).await?;
+
+    let provider = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let address: Address = account
+        .address
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid account address"))?;
+
+    let balance = provider.get_balance(address).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to read the account balance")
+    })?;
+
+    let price = prices.price(&Asset::Native(wallet.chain.clone())).await;
+
+    Ok(HttpResponse::Ok().json(AccountBalanceResponse {
+        account_id: account.id,
+        address: account.address,
+        balance: balance.to_string(),
+        fiat: prices.value(price, balance, native_decimals(&wallet.chain)),
+    }))
+}
+
+/// Transactions sent from the account, latest first
+pub async fn account_transactions(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    prices: web::Data<Prices>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (wallet, account) = find_user_account(&req, &db, path.into_inner()).await?;
+
+    let transactions = TransactionRepository::new_with_connection(&db)
+        .find_by_account_id(account.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the transactions"))?;
+
+    let chain = |transaction: &TransactionModel| {
+        transaction
+            .chain
+            .clone()
+            .unwrap_or_else(|| wallet.chain.clone())
+    };
+
+    let assets: Vec<Asset> = transactions
+        .iter()
+        .map(|transaction| Asset::Native(chain(transaction)))
+        .collect();
+
+    let quotes = prices.prices(&assets).await;
+
+    let response: Vec<AccountTransactionResponse> = transactions
+        .into_iter()
+        .map(|transaction| {
+            let chain = chain(&transaction);
+
+            let fiat = transaction
+                .value
+                .as_deref()
+                .and_then(|value| value.parse::<U256>().ok())
+                .and_then(|value| {
+                    let price = quotes.get(&Asset::Native(chain.clone())).copied();
+                    prices.value(price, value, native_decimals(&chain))
+                });
+
+            AccountTransactionResponse { transaction, fiat }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_accounts_have_indexes_and_nonces_of_their_own() {
+        use super::*;
+        use crate::db::models::{
+            Chain, TransactionActiveModel, TransactionStatus, UserActiveModel, WalletActiveModel,
+            WalletState,
+        };
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let accounts = WalletAccountRepository::new_with_connection(&db);
+
+        assert_eq!(accounts.next_index(wallet.id).await.unwrap(), 0);
+
+        let savings = accounts
+            .create(wallet.id, "savings".to_string(), 0, "0x01".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(accounts.next_index(wallet.id).await.unwrap(), 1);
+        assert!(
+            accounts
+                .create(wallet.id, "savings".to_string(), 1, "0x02".to_string())
+                .await
+                .is_err()
+        );
+        assert!(
+            accounts
+                .create(wallet.id, "payroll".to_string(), 0, "0x02".to_string())
+                .await
+                .is_err()
+        );
+
+        let transactions = TransactionRepository::new_with_connection(&db);
+
+        for (account_id, nonce) in [(None, 4), (Some(savings.id), 1)] {
+            transactions
+                .create(TransactionActiveModel {
+                    user_id: Set(user.id),
+                    wallet_id: Set(wallet.id),
+                    status: Set(TransactionStatus::Pending),
+                    nonce: Set(Some(nonce)),
+                    account_id: Set(account_id),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            transactions
+                .find_held_nonces(wallet.id, None, 0)
+                .await
+                .unwrap(),
+            vec![4]
+        );
+        assert_eq!(
+            transactions
+                .find_held_nonces(wallet.id, Some(savings.id), 0)
+                .await
+                .unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            transactions
+                .find_by_account_id(savings.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use super::error::{ApiError, Result};
+use crate::chains::{encode_base58, parse_pubkey};
+use crate::db::models::{AddressActiveModel, AddressModel, Chain};
+use crate::db::repositories::AddressRepository;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::Address;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, IntoActiveModel, Set};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CreateAddressRequest {
+    pub name: String,
+    pub chain: Chain,
+    pub address: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAddressRequest {
+    pub name: String,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").get(list_addresses).post(create_address))
+        .service(
+            web::resource("/{id}")
+                .get(get_address)
+                .put(update_address)
+                .delete(delete_address),
+        );
+}
+
+/// Canonical form of the address used for whitelist lookups, checksummed on EVM chains
+fn normalize_address(chain: &Chain, address: &str) -> Result<String> {
+    let address = address.trim();
+
+    if address.is_empty() {
+        return Err(ApiError::bad_request("Invalid address"));
+    }
+
+    match chain {
+        Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon => {
+            address
+                .parse::<Address>()
+                .map(|address| address.to_string())
+                .map_err(|_| ApiError::bad_request("Invalid address"))
+        }
+        Chain::Solana => parse_pubkey(address)
+            .map(|pubkey| encode_base58(&pubkey))
+            .ok_or_else(|| ApiError::bad_request("Invalid address")),
+        Chain::Bitcoin => Ok(address.to_string()),
+    }
+}
+
+fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err(ApiError::bad_request("Name is required"));
+    }
+
+    Ok(name.to_string())
+}
+
+async fn find_user_address(
+    repository: &AddressRepository<'_>,
+    address_id: i32,
+    user_id: i32,
+) -> Result<AddressModel> {
+    let address = repository
+        .find_by_id(address_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the address"))?;
+
+    match address {
+        Some(address) if address.user_id == user_id => Ok(address),
+        _ => Err(ApiError::not_found(
+            "address_not_found",
+            "Address not found",
+        )),
+    }
+}
+
+pub async fn list_addresses(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let addresses = AddressRepository::new(&db)
+        .find_by_user_id(user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve addresses"))?;
+
+    Ok(HttpResponse::Ok().json(addresses))
+}
+
+pub async fn create_address(
+    req: HttpRequest,
+    data: web::Json<CreateAddressRequest>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let name = validate_name(&data.name)?;
+    let address = normalize_address(&data.chain, &data.address)?;
+
+    let repository = AddressRepository::new(&db);
+
+    let exists = repository
+        .contains(user_id, &data.chain, &address)
+        .await
+        .map_err(|_| ApiError::internal("Failed to create address"))?;
+
+    if exists {
+        return Err(ApiError::conflict("Address already in the address book"));
+    }
+
+    let address = repository
+        .create(AddressActiveModel {
+            user_id: Set(user_id),
+            name: Set(name),
+            chain: Set(data.chain.clone()),
+            address: Set(address),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to create address"))?;
+
+    Ok(HttpResponse::Created().json(address))
+}
+
+pub async fn get_address(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let address =
+        find_user_address(&AddressRepository::new(&db), path.into_inner(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(address))
+}
+
+/// Renames the entry, the address itself can't change so the whitelist stays auditable
+pub async fn update_address(
+    req: HttpRequest,
+    data: web::Json<UpdateAddressRequest>,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let name = validate_name(&data.name)?;
+
+    let repository = AddressRepository::new(&db);
+
+    let address = find_user_address(&repository, path.into_inner(), user_id).await?;
+
+    let mut model = address.into_active_model();
+    model.name = Set(name);
+    model.updated_at = Set(Some(Utc::now()));
+
+    let address = repository
+        .update(model)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update address"))?;
+
+    Ok(HttpResponse::Ok().json(address))
+}
+
+pub async fn delete_address(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = AddressRepository::new(&db);
+
+    let address = find_user_address(&repository, path.into_inner(), user_id).await?;
+
+    repository
+        .delete(address.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete address"))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_address_checksums_evm_addresses() {
+        assert_eq!(
+            normalize_address(
+                &Chain::Ethereum,
+                " 0x7e5f4552091a69125d5dfcb7b8c2659029395bdf "
+            )
+            .unwrap(),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        assert!(normalize_address(&Chain::Ethereum, "0x1234").is_err());
+        assert!(normalize_address(&Chain::Bitcoin, " ").is_err());
+        assert!(normalize_address(&Chain::Solana, "0x1234").is_err());
+    }
+}
@@ -0,0 +1,482 @@
+use super::error::{ApiError, Result};
+use super::exports;
+use super::quotas::{UserLimits, user_limits};
+use super::wallet::wallet_error;
+use super::withdrawals;
+use crate::config::app_config::{QuotaConfig, WalletExportConfig};
+use crate::db::models::{
+    ExportState, JobKind, OperationKind, OperationState, WalletOperation, WalletState,
+};
+use crate::db::repositories::{
+    AuditRepository, JobRepository, OperationRepository, UserLimitRepository, UserRepository,
+    WalletExportRepository, WalletRepository, WithdrawalRepository,
+};
+use crate::jobs::{KeygenRetry, Reconciler, WalletHealthMonitor, enqueue};
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Utc};
+use sea_orm::{DatabaseConnection, IntoActiveModel, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize)]
+pub struct WalletStateResponse {
+    pub id: i32,
+    pub state: WalletState,
+}
+
+#[derive(Serialize)]
+pub struct RotateNamespaceResponse {
+    pub id: i32,
+    pub namespace_rotated_at: Option<DateTime<Utc>>,
+}
+
+/// Limits of a user, `null` ones fall back to the configured defaults
+#[derive(Deserialize)]
+pub struct UpdateUserLimitsRequest {
+    pub max_wallets: Option<u32>,
+    pub max_daily_transactions: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct UserLimitsResponse {
+    pub user_id: i32,
+    #[serde(flatten)]
+    pub limits: UserLimits,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/wallets/{id}/rotate-namespace").route(web::post().to(rotate_namespace)),
+    )
+    .service(web::resource("/wallets/{id}/freeze").route(web::post().to(freeze_wallet)))
+    .service(web::resource("/wallets/{id}/unfreeze").route(web::post().to(unfreeze_wallet)))
+    .service(web::resource("/wallets/health").route(web::get().to(wallet_health_report)))
+    .service(web::resource("/reconciliation").route(web::get().to(reconciliation_report)))
+    .service(web::resource("/operations/{id}/retry").route(web::post().to(retry_operation)))
+    .service(web::resource("/exports/{id}").route(web::get().to(get_export)))
+    .service(web::resource("/exports/{id}/approve").route(web::post().to(approve_export)))
+    .service(web::resource("/withdrawals/{id}/cancel").route(web::post().to(cancel_withdrawal)))
+    .service(
+        web::resource("/users/{id}/limits")
+            .route(web::get().to(get_user_limits))
+            .route(web::put().to(update_user_limits)),
+    );
+}
+
+/// Approval of an export signed by an approver, see `exports::approval_message`
+#[derive(Deserialize)]
+pub struct ApproveExportRequest {
+    /// Name of the approver in `WALLET_EXPORT_APPROVERS`
+    pub approver: String,
+    /// Hex 64 byte ECDSA signature of the approval message
+    pub signature: String,
+}
+
+/// Export awaiting approval, for approvers to review before approving it
+pub async fn get_export(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let export = WalletExportRepository::new_with_connection(&db)
+        .find_by_id(path.into_inner())
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the export"))?
+        .ok_or_else(|| ApiError::not_found("export_not_found", "Export not found"))?;
+
+    Ok(HttpResponse::Ok().json(export))
+}
+
+/// Records the approval of an operator signed with its key, each approver counts once
+/// however many admin requests it sends. The participants verify the signatures again
+pub async fn approve_export(
+    path: web::Path<i32>,
+    data: web::Json<ApproveExportRequest>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<WalletExportConfig>,
+) -> Result<HttpResponse> {
+    if !config.enabled {
+        return Err(ApiError::forbidden("Key export is disabled"));
+    }
+
+    let approver = config
+        .approvers
+        .iter()
+        .find(|approver| approver.name == data.approver)
+        .ok_or_else(|| ApiError::unauthorized("Invalid approver signature"))?;
+
+    let signature = hex::decode(data.signature.trim_start_matches("0x"))
+        .map_err(|_| ApiError::bad_request("Expected a hex ECDSA signature"))?;
+
+    let repository = WalletExportRepository::new_with_connection(&db);
+
+    let export = repository
+        .find_by_id(path.into_inner())
+        .await
+        .map_err(|_| ApiError::internal("Failed to approve the export"))?
+        .ok_or_else(|| ApiError::not_found("export_not_found", "Export not found"))?;
+
+    if export.state != ExportState::Pending {
+        return Err(ApiError::conflict("Export is not pending approval"));
+    }
+
+    if !exports::verifies_approval(&export, &approver.public_key, &signature) {
+        return Err(ApiError::unauthorized("Invalid approver signature"));
+    }
+
+    if export.approved_by(&approver.name) {
+        return Err(ApiError::conflict(
+            "Export was already approved by this approver",
+        ));
+    }
+
+    let export = repository
+        .approve(
+            &export,
+            &approver.name,
+            &approver.public_key,
+            &signature,
+            config.approvals,
+        )
+        .await
+        .map_err(|_| ApiError::conflict("Export was changed by another request"))?;
+
+    AuditRepository::new_with_connection(&db)
+        .record(
+            &format!("approver:{}", approver.name),
+            "wallet.export_approved",
+            "wallet",
+            Some(export.wallet_id.to_string()),
+            Some(serde_json::json!({
+                "export_id": export.id,
+                "approvals": export.approvals,
+                "state": export.state,
+            })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to approve the export"))?;
+
+    log::warn!(
+        "Export {} approved by {} ({}/{})",
+        export.id,
+        approver.name,
+        export.approvals,
+        config.approvals
+    );
+
+    Ok(HttpResponse::Ok().json(export))
+}
+
+/// Queues a failed keygen for the job runner, which runs it again for a new wallet.
+/// Signings are retried by the client, the operation doesn't keep what was signed
+pub async fn retry_operation(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let repository = OperationRepository::new_with_connection(&db);
+
+    let operation = repository
+        .find_by_id(path.into_inner())
+        .await
+        .map_err(|_| ApiError::internal("Failed to retry operation"))?
+        .ok_or_else(|| ApiError::not_found("operation_not_found", "Operation not found"))?;
+
+    if operation.kind != OperationKind::Keygen {
+        return Err(ApiError::conflict("Only keygen operations can be retried"));
+    }
+
+    if operation.state != OperationState::Failed {
+        return Err(ApiError::conflict("Operation has not failed"));
+    }
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to retry operation"))?;
+
+    let operation = OperationRepository::new_with_transaction(&txn)
+        .retry(&operation)
+        .await
+        .map_err(|_| ApiError::conflict("Operation was changed by another request"))?;
+
+    let retry = KeygenRetry {
+        operation_id: operation.id,
+    };
+
+    enqueue(
+        &JobRepository::new_with_transaction(&txn),
+        JobKind::OperationKeygen,
+        &retry,
+    )
+    .await
+    .map_err(|_| ApiError::internal("Failed to retry operation"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to retry operation"))?;
+
+    AuditRepository::new_with_connection(&db)
+        .record(
+            "admin",
+            "operation.retried",
+            "operation",
+            Some(operation.id.to_string()),
+            Some(serde_json::json!({ "attempts": operation.attempts })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to retry operation"))?;
+
+    log::info!("Operation {} queued for retry", operation.id);
+
+    Ok(HttpResponse::Accepted().json(operation))
+}
+
+/// Wallets that needed attention in the last reconciliation run
+pub async fn reconciliation_report(reconciler: web::Data<Reconciler>) -> Result<HttpResponse> {
+    let report = reconciler
+        .report()
+        .ok_or_else(|| ApiError::not_found("report_not_found", "Reconciliation has not run yet"))?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Wallets whose shares failed the last health check
+pub async fn wallet_health_report(monitor: web::Data<WalletHealthMonitor>) -> Result<HttpResponse> {
+    let report = monitor.report().ok_or_else(|| {
+        ApiError::not_found("report_not_found", "Wallet health check has not run yet")
+    })?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+async fn ensure_user_exists(db: &DatabaseConnection, user_id: i32) -> Result<()> {
+    UserRepository::new(db)
+        .find_by_id(user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the user"))?
+        .ok_or_else(|| ApiError::not_found("user_not_found", "User not found"))?;
+
+    Ok(())
+}
+
+/// Limits the user is held to, the overrides and the defaults combined
+pub async fn get_user_limits(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+    quotas: web::Data<QuotaConfig>,
+) -> Result<HttpResponse> {
+    let user_id = path.into_inner();
+
+    ensure_user_exists(&db, user_id).await?;
+
+    let limits = user_limits(&db, &quotas, user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the user limits"))?;
+
+    Ok(HttpResponse::Ok().json(UserLimitsResponse { user_id, limits }))
+}
+
+/// Overrides the default limits of the user, e.g. for a paying plan
+pub async fn update_user_limits(
+    path: web::Path<i32>,
+    data: web::Json<UpdateUserLimitsRequest>,
+    db: web::Data<DatabaseConnection>,
+    quotas: web::Data<QuotaConfig>,
+) -> Result<HttpResponse> {
+    let user_id = path.into_inner();
+
+    let limit = |limit: Option<u32>| {
+        limit
+            .map(i32::try_from)
+            .transpose()
+            .map_err(|_| ApiError::bad_request("Limit is too large"))
+    };
+
+    let max_wallets = limit(data.max_wallets)?;
+    let max_daily_transactions = limit(data.max_daily_transactions)?;
+
+    ensure_user_exists(&db, user_id).await?;
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update the user limits"))?;
+
+    let overrides = UserLimitRepository::new_with_transaction(&txn)
+        .upsert(user_id, max_wallets, max_daily_transactions)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update the user limits"))?;
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            "admin",
+            "user.limits_updated",
+            "user",
+            Some(user_id.to_string()),
+            Some(serde_json::json!({
+                "max_wallets": overrides.max_wallets,
+                "max_daily_transactions": overrides.max_daily_transactions,
+            })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to update the user limits"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update the user limits"))?;
+
+    log::info!("Updated the limits of user {user_id}");
+
+    Ok(HttpResponse::Ok().json(UserLimitsResponse {
+        user_id,
+        limits: UserLimits::resolve(&quotas, Some(&overrides)),
+    }))
+}
+
+/// Moves every future ceremony of the wallet to fresh relay rooms, e.g. after a relay compromise
+pub async fn rotate_namespace(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let wallet_id = path.into_inner();
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to rotate namespace"))?;
+
+    let repository = WalletRepository::new_with_transaction(&txn);
+
+    let wallet = repository
+        .find_by_id(wallet_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to rotate namespace"))?
+        .ok_or_else(ApiError::wallet_not_found)?;
+
+    wallet
+        .state
+        .ensure_allows(WalletOperation::RotateNamespace)?;
+
+    let previous_namespace = wallet.namespace.clone();
+
+    let mut model = wallet.into_active_model();
+    model.namespace = Set(Uuid::new_v4().simple().to_string());
+    model.namespace_rotated_at = Set(Some(Utc::now()));
+
+    let wallet = repository
+        .update(model)
+        .await
+        .map_err(|_| ApiError::internal("Failed to rotate namespace"))?;
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            "admin",
+            "wallet.namespace_rotated",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({ "previous_namespace": previous_namespace })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to rotate namespace"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to rotate namespace"))?;
+
+    log::info!("Rotated room namespace of wallet {}", wallet.id);
+
+    Ok(HttpResponse::Ok().json(RotateNamespaceResponse {
+        id: wallet.id,
+        namespace_rotated_at: wallet.namespace_rotated_at,
+    }))
+}
+
+/// Blocks signing on the wallet until it is unfrozen
+/// Cancels a pending withdrawal of any wallet, e.g. when its owner reports a stolen account
+pub async fn cancel_withdrawal(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let withdrawal = WithdrawalRepository::new_with_connection(&db)
+        .find_by_id(path.into_inner())
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the withdrawal"))?
+        .ok_or_else(|| ApiError::not_found("withdrawal_not_found", "Withdrawal not found"))?;
+
+    let withdrawal = withdrawals::cancel(&db, &withdrawal, "admin").await?;
+
+    Ok(HttpResponse::Ok().json(withdrawal))
+}
+
+pub async fn freeze_wallet(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    change_state(path.into_inner(), &db, WalletState::Frozen, "wallet.frozen").await
+}
+
+pub async fn unfreeze_wallet(
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    change_state(
+        path.into_inner(),
+        &db,
+        WalletState::Active,
+        "wallet.unfrozen",
+    )
+    .await
+}
+
+async fn change_state(
+    wallet_id: i32,
+    db: &DatabaseConnection,
+    next: WalletState,
+    action: &str,
+) -> Result<HttpResponse> {
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update wallet"))?;
+
+    let repository = WalletRepository::new_with_transaction(&txn);
+
+    let wallet = repository
+        .find_by_id(wallet_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update wallet"))?
+        .ok_or_else(ApiError::wallet_not_found)?;
+
+    // Unfreezing must not revive wallets that are being archived or deleted
+    if next == WalletState::Active && wallet.state != WalletState::Frozen {
+        return Err(ApiError::conflict("Wallet is not frozen"));
+    }
+
+    let previous_state = wallet.state;
+
+    let wallet = repository
+        .transition(&wallet, next)
+        .await
+        .map_err(|err| wallet_error(err, "Failed to update wallet"))?;
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            "admin",
+            action,
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({ "previous_state": previous_state })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to update wallet"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update wallet"))?;
+
+    log::info!("Wallet {} moved to {}", wallet.id, wallet.state);
+
+    Ok(HttpResponse::Ok().json(WalletStateResponse {
+        id: wallet.id,
+        state: wallet.state,
+    }))
+}
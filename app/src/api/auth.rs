@@ -1,19 +1,25 @@
-use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized, ErrorUnprocessableEntity};
-use actix_web::{Error, HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, http::StatusCode, http::header, web};
+use chrono::{DateTime, Duration, Utc};
+use std::net::IpAddr;
 
 use sea_orm::DbConn;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 use crate::auth::hash_password;
-use crate::db::models::UserActiveModel;
+use crate::config::app_config::LoginConfig;
+use crate::db::models::{LoginFailureModel, SessionActiveModel, UserActiveModel};
+use crate::siem::{self, Outcome, SecurityEvent};
 
 use crate::utils::validators::user::{validate_no_spaces, validate_password};
 
 use sea_orm::ActiveValue::Set;
 
-use crate::auth::{generate_claims, generate_token, verify_password};
-use crate::db::repositories::UserRepository;
+use super::error::{ApiError, Result};
+use crate::auth::{Claims, generate_claims, generate_token, needs_rehash, verify_password};
+use crate::db::repositories::{
+    AuditRepository, LoginFailureRepository, SessionRepository, UserRepository,
+};
 use crate::utils::validate::validate_req;
 
 #[derive(Deserialize, Validate)]
@@ -32,31 +38,290 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .route("/signup", web::post().to(signup));
 }
 
-async fn login(db: web::Data<DbConn>, req: web::Json<LoginRequest>) -> Result<HttpResponse, Error> {
+async fn login(
+    http_req: HttpRequest,
+    db: web::Data<DbConn>,
+    config: web::Data<LoginConfig>,
+    req: web::Json<LoginRequest>,
+) -> Result<HttpResponse> {
     validate_req(&req)?;
 
+    let client = http_req.peer_addr().map(|addr| addr.ip());
+    let subjects = login_subjects(&config, &req.username, client);
+
+    ensure_not_locked(db.get_ref(), &subjects).await?;
+
     let user_repository = UserRepository::new(db.get_ref());
 
-    let user = match user_repository
+    let user = user_repository
         .find_by_username(&req.username)
         .await
-        .map_err(|e| ErrorInternalServerError(format!("Database error: {}", e)))?
-    {
-        Some(user) => user,
-        None => return Err(ErrorUnauthorized("Account not registered".to_string())),
+        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+
+    let user = match user {
+        Some(user) if verify_password(&req.password, &user.password)? => user,
+        user => {
+            let rejection = match user {
+                Some(_) => {
+                    ApiError::unauthorized("Invalid credentials").with_code("invalid_credentials")
+                }
+                None => ApiError::unauthorized("Account not registered")
+                    .with_code("account_not_registered"),
+            };
+
+            record_failure(db.get_ref(), &config, &subjects, &req.username, client).await;
+
+            return Err(rejection);
+        }
     };
 
-    let is_valid = verify_password(&req.password, &user.password)?;
-    if !is_valid {
-        return Err(ErrorUnauthorized("Invalid credentials"));
+    // The password is only known now, hashes of weaker costs are replaced with it
+    if needs_rehash(&user.password) {
+        match hash_password(&req.password) {
+            Ok(password) => match user_repository.update_password(&user, password).await {
+                Ok(_) => log::info!("Rehashed the password of {}", user.username),
+                Err(err) => {
+                    log::error!("Failed to rehash the password of {}: {err}", user.username)
+                }
+            },
+            Err(err) => log::error!("Failed to rehash the password of {}: {err}", user.username),
+        }
+    }
+
+    // Failures of the client address are kept, its own account must not reset them
+    if let Err(err) = LoginFailureRepository::new(db.get_ref())
+        .clear(&subjects[0].key)
+        .await
+    {
+        log::error!(
+            "Failed to clear the login failures of {}: {err}",
+            user.username
+        );
     }
 
     let claims = generate_claims(&user);
     let token = generate_token(&claims)?;
 
+    create_session(db.get_ref(), &http_req, &claims, client).await?;
+
     Ok(HttpResponse::Ok().json(LoginResponse { token }))
 }
 
+/// Records the session of a new token, tokens without one are refused
+pub(super) async fn create_session(
+    db: &DbConn,
+    req: &HttpRequest,
+    claims: &Claims,
+    client: Option<IpAddr>,
+) -> Result<()> {
+    let now = Utc::now();
+
+    let device = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|agent| agent.to_str().ok())
+        .map(|agent| agent.chars().take(255).collect());
+
+    let expires_at = DateTime::from_timestamp(claims.exp as i64, 0)
+        .ok_or_else(|| ApiError::internal("Failed to create the session"))?;
+
+    SessionRepository::new(db)
+        .create(SessionActiveModel {
+            id: Set(claims.jti.clone()),
+            user_id: Set(claims.user_id),
+            device: Set(device),
+            ip: Set(client.map(|ip| ip.to_string())),
+            created_at: Set(now),
+            last_used_at: Set(now),
+            expires_at: Set(expires_at),
+            revoked_at: Set(None),
+        })
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to create the session: {e}")))?;
+
+    siem::emit(
+        SecurityEvent::new("auth.login", Outcome::Success)
+            .actor(format!("user:{}", claims.user_id))
+            .user(claims.user_id)
+            .resource("session", &claims.jti)
+            .source_ip(client),
+    );
+
+    Ok(())
+}
+
+/// Username or client address failed logins are counted against
+struct LoginSubject {
+    key: String,
+    max_failures: u32,
+}
+
+/// The username first, then the client address when known
+fn login_subjects(
+    config: &LoginConfig,
+    username: &str,
+    client: Option<IpAddr>,
+) -> Vec<LoginSubject> {
+    let username = LoginSubject {
+        key: format!("username:{username}"),
+        max_failures: config.max_failures,
+    };
+
+    let client = client.map(|client| LoginSubject {
+        key: format!("ip:{client}"),
+        max_failures: config.max_ip_failures,
+    });
+
+    std::iter::once(username).chain(client).collect()
+}
+
+/// Lockout after `failures` failed logins of a subject locked after `max_failures`, doubling
+/// with every failure past them. A threshold of 0 disables the lockout
+fn lockout(config: &LoginConfig, max_failures: u32, failures: u32) -> Option<Duration> {
+    if max_failures == 0 || failures < max_failures {
+        return None;
+    }
+
+    let doublings = (failures - max_failures).min(32);
+    let seconds = config
+        .lockout
+        .saturating_mul(1u64 << doublings)
+        .min(config.max_lockout);
+
+    Some(Duration::seconds(seconds as i64))
+}
+
+/// Failures of the subject with one more, the previous ones are forgotten once they are
+/// older than the longest lockout
+fn next_failure(
+    config: &LoginConfig,
+    subject: &LoginSubject,
+    previous: Option<LoginFailureModel>,
+    now: DateTime<Utc>,
+) -> LoginFailureModel {
+    let failures = match previous {
+        Some(previous)
+            if now - previous.last_failure_at < Duration::seconds(config.max_lockout as i64) =>
+        {
+            previous.failures as u32 + 1
+        }
+        _ => 1,
+    };
+
+    LoginFailureModel {
+        subject: subject.key.clone(),
+        failures: failures as i32,
+        last_failure_at: now,
+        locked_until: lockout(config, subject.max_failures, failures).map(|lockout| now + lockout),
+    }
+}
+
+/// Refuses logins of a locked username or from a locked client address, before the password
+/// is checked so guessing it right while locked gains nothing
+async fn ensure_not_locked(db: &DbConn, subjects: &[LoginSubject]) -> Result<()> {
+    let repository = LoginFailureRepository::new(db);
+    let now = Utc::now();
+
+    for subject in subjects {
+        let locked_until = repository
+            .find_by_subject(&subject.key)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?
+            .and_then(|failure| failure.locked_until)
+            .filter(|locked_until| *locked_until > now);
+
+        if let Some(locked_until) = locked_until {
+            return Err(ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "login_locked",
+                "Too many failed logins, try again later",
+            )
+            .with_retry_after((locked_until - now).num_seconds().max(1) as u64));
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts a failed login against every subject and audits it, along with the lockouts it
+/// starts. Storage errors are logged, the login is rejected either way
+async fn record_failure(
+    db: &DbConn,
+    config: &LoginConfig,
+    subjects: &[LoginSubject],
+    username: &str,
+    client: Option<IpAddr>,
+) {
+    let repository = LoginFailureRepository::new(db);
+    let audit = AuditRepository::new_with_connection(db);
+    let actor = client.map_or_else(|| "anonymous".to_string(), |client| format!("ip:{client}"));
+    let now = Utc::now();
+
+    for subject in subjects {
+        let failure = match repository.find_by_subject(&subject.key).await {
+            Ok(previous) => next_failure(config, subject, previous, now),
+            Err(err) => {
+                log::error!(
+                    "Failed to read the login failures of {}: {err}",
+                    subject.key
+                );
+                continue;
+            }
+        };
+
+        let failure = match repository.save(failure).await {
+            Ok(failure) => failure,
+            Err(err) => {
+                log::error!(
+                    "Failed to record the login failure of {}: {err}",
+                    subject.key
+                );
+                continue;
+            }
+        };
+
+        if let Some(locked_until) = failure.locked_until {
+            log::warn!(
+                "Locked logins of {} until {locked_until} after {} failures",
+                subject.key,
+                failure.failures
+            );
+
+            let recorded = audit
+                .record(
+                    &actor,
+                    "auth.login_locked",
+                    "user",
+                    Some(username.to_string()),
+                    Some(serde_json::json!({
+                        "subject": subject.key,
+                        "failures": failure.failures,
+                        "locked_until": locked_until,
+                    })),
+                )
+                .await;
+
+            if let Err(err) = recorded {
+                log::error!("Failed to audit the lockout of {}: {err}", subject.key);
+            }
+        }
+    }
+
+    let recorded = audit
+        .record(
+            &actor,
+            "auth.login_failed",
+            "user",
+            Some(username.to_string()),
+            None,
+        )
+        .await;
+
+    if let Err(err) = recorded {
+        log::error!("Failed to audit the failed login of {username}: {err}");
+    }
+}
+
 #[derive(Deserialize, Serialize, Validate)]
 pub struct CreateUserRequest {
     #[validate(length(
@@ -77,7 +342,7 @@ pub struct CreateUserRequest {
 pub async fn signup(
     db: web::Data<DbConn>,
     user: web::Json<CreateUserRequest>,
-) -> Result<HttpResponse, Error> {
+) -> Result<HttpResponse> {
     validate_req(&user)?;
 
     let repo = UserRepository::new(db.get_ref());
@@ -85,25 +350,25 @@ pub async fn signup(
     if (repo
         .find_by_username(&user.username)
         .await
-        .map_err(ErrorInternalServerError)?)
+        .map_err(|e| ApiError::internal(e.to_string()))?)
     .is_some()
     {
-        return Err(ErrorUnprocessableEntity(format!(
-            "Username {} already exists",
-            user.username
-        )));
+        return Err(ApiError::unprocessable(
+            "username_taken",
+            format!("Username {} already exists", user.username),
+        ));
     }
 
     if (repo
         .find_by_email(&user.email)
         .await
-        .map_err(ErrorInternalServerError)?)
+        .map_err(|e| ApiError::internal(e.to_string()))?)
     .is_some()
     {
-        return Err(ErrorUnprocessableEntity(format!(
-            "Email {} already exists",
-            user.email
-        )));
+        return Err(ApiError::unprocessable(
+            "email_taken",
+            format!("Email {} already exists", user.email),
+        ));
     }
 
     let user_model = UserActiveModel {
@@ -116,7 +381,122 @@ pub async fn signup(
     let created_user = repo
         .create(user_model)
         .await
-        .map_err(|e| ErrorInternalServerError(format!("Failed to create user: {}", e)))?;
+        .map_err(|e| ApiError::internal(format!("Failed to create user: {}", e)))?;
 
     Ok(HttpResponse::Created().json(created_user))
 }
+
+/// Public keys of the issued tokens, for other services to verify them without the
+/// signing key. Cached for the default `SECRETS_REFRESH_INTERVAL`
+pub async fn jwks() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(crate::auth::jwks())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LoginConfig {
+        LoginConfig {
+            max_failures: 3,
+            max_ip_failures: 10,
+            lockout: 60,
+            max_lockout: 600,
+        }
+    }
+
+    #[test]
+    fn test_lockout_doubles_past_the_threshold() {
+        let config = config();
+
+        assert_eq!(lockout(&config, 3, 2), None);
+        assert_eq!(lockout(&config, 3, 3), Some(Duration::seconds(60)));
+        assert_eq!(lockout(&config, 3, 4), Some(Duration::seconds(120)));
+        assert_eq!(lockout(&config, 3, 7), Some(Duration::seconds(600)));
+        assert_eq!(lockout(&config, 3, u32::MAX), Some(Duration::seconds(600)));
+        assert_eq!(lockout(&config, 0, 100), None);
+    }
+
+    #[test]
+    fn test_old_failures_are_forgotten() {
+        let config = config();
+        let subjects = login_subjects(&config, "alice", Some([10, 0, 0, 1].into()));
+        let now = Utc::now();
+
+        assert_eq!(
+            subjects
+                .iter()
+                .map(|subject| subject.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["username:alice", "ip:10.0.0.1"]
+        );
+
+        let previous = LoginFailureModel {
+            subject: "username:alice".to_string(),
+            failures: 2,
+            last_failure_at: now - Duration::seconds(30),
+            locked_until: None,
+        };
+
+        let failure = next_failure(&config, &subjects[0], Some(previous.clone()), now);
+
+        assert_eq!(failure.failures, 3);
+        assert_eq!(failure.locked_until, Some(now + Duration::seconds(60)));
+
+        let previous = LoginFailureModel {
+            last_failure_at: now - Duration::seconds(600),
+            ..previous
+        };
+
+        let failure = next_failure(&config, &subjects[0], Some(previous), now);
+
+        assert_eq!(failure.failures, 1);
+        assert_eq!(failure.locked_until, None);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_failures_lock_the_username() {
+        use sea_orm::{ConnectOptions, Database};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let config = config();
+        let subjects = login_subjects(&config, "alice", None);
+
+        for _ in 0..2 {
+            record_failure(&db, &config, &subjects, "alice", None).await;
+        }
+
+        ensure_not_locked(&db, &subjects).await.unwrap();
+
+        record_failure(&db, &config, &subjects, "alice", None).await;
+
+        let error = ensure_not_locked(&db, &subjects).await.unwrap_err();
+
+        assert_eq!(error.problem().status, 429);
+        assert_eq!(error.problem().code, "login_locked");
+
+        // Other usernames aren't affected
+        ensure_not_locked(&db, &login_subjects(&config, "bob", None))
+            .await
+            .unwrap();
+
+        LoginFailureRepository::new(&db)
+            .clear("username:alice")
+            .await
+            .unwrap();
+
+        ensure_not_locked(&db, &subjects).await.unwrap();
+    }
+}
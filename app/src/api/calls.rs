@@ -0,0 +1,291 @@
+use super::error::{ApiError, Result};
+use super::wallet::find_user_wallet;
+use crate::chains::{CallOutcome, ChainRegistry, multicall};
+use crate::db::repositories::WalletRepository;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::dyn_abi::{DynSolValue, FunctionExt, JsonAbiExt, Specifier};
+use alloy::json_abi::Function;
+use alloy::primitives::{Address, Bytes, hex};
+use alloy::sol_types::decode_revert_reason;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Calls accepted by a single request
+const MAX_CALLS: usize = 100;
+
+#[derive(Deserialize)]
+pub struct ContractCall {
+    pub contract: Address,
+    /// Human readable signature with the returned types, e.g.
+    /// `balanceOf(address) returns (uint256)`
+    pub function: String,
+    /// One per input, in the notation of Solidity literals, e.g. `"0x..."`, `"100"`,
+    /// `"[1,2]"` or `"(true,0x...)"`
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MulticallRequest {
+    pub calls: Vec<ContractCall>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CallResponse {
+    pub success: bool,
+    /// Decoded outputs of the function, integers as decimal strings and bytes as hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Vec<Value>>,
+    /// Revert reason of a failed call, or why its output couldn't be decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/call").route(web::post().to(call_contracts)));
+}
+
+/// Function of the call and its ABI-encoded call data
+fn encode_call(call: &ContractCall) -> std::result::Result<(Function, Bytes), String> {
+    let function = Function::parse(&call.function)
+        .map_err(|err| format!("Invalid function {}: {err}", call.function))?;
+
+    if function.inputs.len() != call.args.len() {
+        return Err(format!(
+            "{} takes {} arguments, {} given",
+            function.name,
+            function.inputs.len(),
+            call.args.len()
+        ));
+    }
+
+    let values = function
+        .inputs
+        .iter()
+        .zip(&call.args)
+        .map(|(param, arg)| {
+            param
+                .resolve()
+                .and_then(|ty| ty.coerce_str(arg))
+                .map_err(|err| format!("Invalid {} argument {arg}: {err}", param.ty))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let data = function
+        .abi_encode_input(&values)
+        .map_err(|err| format!("Failed to encode {}: {err}", function.name))?;
+
+    Ok((function, data.into()))
+}
+
+/// JSON of a decoded value, integers are strings since JSON numbers can't hold 256 bits
+fn json_value(value: &DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(value) => Value::Bool(*value),
+        DynSolValue::Int(value, _) => Value::String(value.to_string()),
+        DynSolValue::Uint(value, _) => Value::String(value.to_string()),
+        DynSolValue::FixedBytes(word, size) => Value::String(hex::encode_prefixed(&word[..*size])),
+        DynSolValue::Address(address) => Value::String(address.to_checksum(None)),
+        DynSolValue::Function(function) => Value::String(hex::encode_prefixed(function)),
+        DynSolValue::Bytes(bytes) => Value::String(hex::encode_prefixed(bytes)),
+        DynSolValue::String(value) => Value::String(value.clone()),
+        DynSolValue::Array(values)
+        | DynSolValue::FixedArray(values)
+        | DynSolValue::Tuple(values) => Value::Array(values.iter().map(json_value).collect()),
+    }
+}
+
+fn call_response(function: &Function, outcome: &CallOutcome) -> CallResponse {
+    if !outcome.success {
+        let error =
+            decode_revert_reason(&outcome.data).unwrap_or_else(|| match outcome.data.is_empty() {
+                true => "Call reverted".to_string(),
+                false => format!("Call reverted with {}", outcome.data),
+            });
+
+        return CallResponse {
+            success: false,
+            result: None,
+            error: Some(error),
+        };
+    }
+
+    match function.abi_decode_output(&outcome.data) {
+        Ok(values) => CallResponse {
+            success: true,
+            result: Some(values.iter().map(json_value).collect()),
+            error: None,
+        },
+        // E.g. a contract without the function, or a signature with the wrong returned types
+        Err(err) => CallResponse {
+            success: false,
+            result: None,
+            error: Some(format!("Failed to decode the output: {err}")),
+        },
+    }
+}
+
+/// Runs read-only calls of contracts on the chain of the wallet in a single Multicall3
+/// `eth_call`, returning the decoded outputs of each in request order
+pub async fn call_contracts(
+    req: HttpRequest,
+    data: web::Json<MulticallRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::bad_request(
+            "Contract calls are only supported on EVM chains",
+        ));
+    }
+
+    if data.calls.is_empty() || data.calls.len() > MAX_CALLS {
+        return Err(ApiError::bad_request(format!(
+            "A request makes 1 to {MAX_CALLS} calls"
+        )));
+    }
+
+    let provider = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?
+        .provider
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let (functions, calls): (Vec<_>, Vec<_>) = data
+        .calls
+        .iter()
+        .enumerate()
+        .map(|(index, call)| {
+            let (function, call_data) = encode_call(call)
+                .map_err(|err| ApiError::bad_request(format!("Call {index}: {err}")))?;
+
+            Ok((function, (call.contract, call_data)))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+
+    let outcomes = multicall(provider, calls).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to run the calls")
+    })?;
+
+    let response = functions
+        .iter()
+        .zip(&outcomes)
+        .map(|(function, outcome)| call_response(function, outcome))
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::U256;
+    use alloy::sol_types::{Revert, SolError};
+
+    fn balance_of() -> ContractCall {
+        ContractCall {
+            contract: Address::ZERO,
+            function: "function balanceOf(address owner) view returns (uint256)".to_string(),
+            args: vec!["0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_calls_are_encoded_from_their_signature() {
+        let (function, data) = encode_call(&balance_of()).unwrap();
+
+        assert_eq!(function.name, "balanceOf");
+        assert_eq!(&data[..4], &hex!("70a08231"));
+        assert_eq!(data.len(), 36);
+
+        let missing = ContractCall {
+            args: Vec::new(),
+            ..balance_of()
+        };
+
+        assert!(encode_call(&missing).is_err());
+
+        let invalid = ContractCall {
+            args: vec!["vitalik.eth".to_string()],
+            ..balance_of()
+        };
+
+        assert!(encode_call(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_outputs_are_decoded_to_json() {
+        let (function, _) = encode_call(&balance_of()).unwrap();
+
+        let output = DynSolValue::Uint(U256::from(10u64.pow(18)), 256).abi_encode();
+
+        assert_eq!(
+            call_response(
+                &function,
+                &CallOutcome {
+                    success: true,
+                    data: output.into(),
+                }
+            ),
+            CallResponse {
+                success: true,
+                result: Some(vec![Value::String("1000000000000000000".to_string())]),
+                error: None,
+            }
+        );
+
+        let revert = Revert::from("Paused").abi_encode();
+
+        let failed = call_response(
+            &function,
+            &CallOutcome {
+                success: false,
+                data: revert.into(),
+            },
+        );
+
+        assert!(!failed.success);
+        assert!(failed.error.unwrap().contains("Paused"));
+
+        // Empty output, e.g. no contract at the address
+        let undecoded = call_response(
+            &function,
+            &CallOutcome {
+                success: true,
+                data: Bytes::new(),
+            },
+        );
+
+        assert!(!undecoded.success);
+    }
+
+    #[test]
+    fn test_nested_values_are_json_arrays() {
+        let value = DynSolValue::Tuple(vec![
+            DynSolValue::Bool(true),
+            DynSolValue::Array(vec![DynSolValue::Bytes(vec![0xab, 0xcd])]),
+            DynSolValue::FixedBytes(alloy::primitives::B256::repeat_byte(0x11), 2),
+        ]);
+
+        assert_eq!(
+            json_value(&value),
+            serde_json::json!([true, ["0xabcd"], "0x1111"])
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use super::error::{ApiError, Result};
+use crate::config::app_config::CosignerConfig;
+use crate::participants::cosigner::{self, JoinError};
+use crate::siem::{self, Outcome, SecurityEvent};
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// What the device needs to run its part of a ceremony on its own participant
+#[derive(Serialize)]
+pub struct JoinedCeremonyResponse {
+    pub execution_id: Uuid,
+    pub kind: &'static str,
+    pub relay_url: String,
+    /// Base64 protobuf message of the kind, a `mpc.CreateWalletMessage`, `mpc.SignMessage`
+    /// or `mpc.SignBatchMessage`, with the room token of the ceremony
+    pub message: String,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/ceremonies").route(web::get().to(list_ceremonies)))
+        .service(
+            web::resource("/ceremonies/{execution_id}/join").route(web::post().to(join_ceremony)),
+        );
+}
+
+fn ensure_enabled(config: &CosignerConfig) -> Result<&str> {
+    match (config.enabled, config.relay_url.as_deref()) {
+        (true, Some(relay_url)) => Ok(relay_url),
+        _ => Err(ApiError::forbidden("Co-signed wallets are disabled")),
+    }
+}
+
+/// Ceremonies of the user's co-signed wallets waiting for the device, polled by the device
+/// while the keygen or signing request runs
+pub async fn list_ceremonies(
+    req: HttpRequest,
+    config: web::Data<CosignerConfig>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    ensure_enabled(&config)?;
+
+    Ok(HttpResponse::Ok().json(cosigner::pending(user_id)))
+}
+
+/// Hands the ceremony over to the device. Each ceremony is joined once, the participant of a
+/// second device would find the rooms taken
+pub async fn join_ceremony(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    config: web::Data<CosignerConfig>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let relay_url = ensure_enabled(&config)?;
+
+    let execution_id = path.into_inner();
+
+    let joined = cosigner::join(user_id, &execution_id);
+
+    let mut event = SecurityEvent::new("cosigner.joined", Outcome::of(&joined))
+        .user(user_id)
+        .resource("ceremony", execution_id);
+
+    if let Ok(message) = &joined {
+        event = event
+            .wallet(message.wallet_id())
+            .details(serde_json::json!({ "kind": message.kind() }));
+    }
+
+    siem::emit(event);
+
+    let message = joined.map_err(|err| match err {
+        JoinError::NotFound => ApiError::not_found("ceremony_not_found", "Ceremony not found"),
+        JoinError::AlreadyJoined => {
+            ApiError::conflict("Ceremony was already joined").with_code("ceremony_joined")
+        }
+    })?;
+
+    log::info!(
+        "Device of user {user_id} joined the {} ceremony {execution_id} of wallet {}",
+        message.kind(),
+        message.wallet_id()
+    );
+
+    Ok(HttpResponse::Ok().json(JoinedCeremonyResponse {
+        execution_id,
+        kind: message.kind(),
+        relay_url: relay_url.to_string(),
+        message: BASE64.encode(message.encode()),
+    }))
+}
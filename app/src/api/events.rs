@@ -0,0 +1,76 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder, web};
+use actix_web_lab::sse::{self, Sse};
+use chrono::Utc;
+use futures::Stream;
+use std::time::Duration;
+
+use super::error::{ApiError, Result};
+use crate::auth::Claims;
+use crate::events::{EventBus, Subscription};
+
+/// Comment sent while no event happens so proxies keep the stream open
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Streams the wallet and transaction events of the user until its token expires, the
+/// client then reconnects with a fresh token
+pub async fn events(req: HttpRequest, bus: web::Data<EventBus>) -> Result<HttpResponse> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or_else(|| ApiError::unauthorized("User not authorized"))?;
+
+    let expires_in = (claims.exp as i64 - Utc::now().timestamp()).max(0) as u64;
+
+    let stream = subscription_to_stream(
+        bus.subscribe(claims.user_id),
+        Duration::from_secs(expires_in),
+    );
+
+    Ok(Sse::from_stream(stream)
+        .with_keep_alive(KEEP_ALIVE)
+        .with_retry_duration(Duration::from_secs(5))
+        .respond_to(&req))
+}
+
+fn subscription_to_stream(
+    mut subscription: Subscription,
+    expires_in: Duration,
+) -> impl Stream<Item = std::result::Result<sse::Event, actix_web::Error>> {
+    async_stream::stream! {
+        let expiry = tokio::time::sleep(expires_in);
+        tokio::pin!(expiry);
+
+        loop {
+            let event = tokio::select! {
+                event = subscription.recv() => event,
+                _ = &mut expiry => break,
+            };
+
+            match event {
+                Ok(event) => match sse::Data::new_json(&event) {
+                    Ok(data) => yield Ok(sse::Event::Data(data.event(event.name.clone()))),
+                    Err(err) => log::error!("Failed to serialize event {}: {err}", event.name),
+                },
+                // Missed events are found by fetching the wallets and transactions again
+                Err(missed) => {
+                    yield Ok(sse::Event::Data(sse::Data::new(missed.to_string()).event("lagged")))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[actix_web::test]
+    async fn test_stream_ends_when_token_expires() {
+        let stream =
+            subscription_to_stream(EventBus::new().subscribe(-1), Duration::from_millis(10));
+
+        assert!(Box::pin(stream).next().await.is_none());
+    }
+}
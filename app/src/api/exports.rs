@@ -0,0 +1,515 @@
+use super::error::{ApiError, Result};
+use super::wallet::find_user_wallet;
+use crate::config::app_config::WalletExportConfig;
+use crate::db::models::{ExportState, WalletExportModel, WalletOperation};
+use crate::db::repositories::{AuditRepository, WalletExportRepository, WalletRepository};
+use crate::participants::{ParticipantPool, SIGNING_THRESHOLD, run_export};
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use proto::mpc::{ExportApproval, ShareBackupMessage};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+
+static APPROVAL_DOMAIN: &[u8] = b"mpc-waas-export-approval-v1";
+
+#[derive(Deserialize)]
+pub struct CreateExportRequest {
+    pub wallet_id: i32,
+    /// Hex SEC1 secp256k1 public key the shares are encrypted to, its private key never
+    /// leaves the owner. Participants only export to keys the operators pinned after
+    /// checking the owner holds them, a key registered here could be swapped by the server
+    pub public_key: String,
+}
+
+/// Share of a participant as ECIES over secp256k1 with ChaCha20-Poly1305, the wallet id
+/// big-endian as associated data
+#[derive(Serialize)]
+pub struct ExportedShare {
+    pub participant: usize,
+    pub ephemeral_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Serialize)]
+pub struct CompletedExportResponse {
+    pub id: i32,
+    pub wallet_id: i32,
+    pub shares: Vec<ExportedShare>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::post().to(create_export)))
+        .service(web::resource("/{id}").route(web::get().to(get_export)))
+        .service(web::resource("/{id}/complete").route(web::post().to(complete_export)));
+}
+
+/// Parses an SEC1 encoded public key, participants reject the ones off the curve
+fn export_public_key(public_key: &str) -> Result<Vec<u8>> {
+    hex::decode(public_key.trim_start_matches("0x"))
+        .ok()
+        .filter(|key| {
+            matches!(key.as_slice(), [0x02 | 0x03, rest @ ..] if rest.len() == 32)
+                || matches!(key.as_slice(), [0x04, rest @ ..] if rest.len() == 64)
+        })
+        .ok_or_else(|| ApiError::bad_request("Expected a hex SEC1 secp256k1 public key"))
+}
+
+/// Message the approvers sign, the participants verify the same before exporting a share
+pub fn approval_message(export: &WalletExportModel) -> Result<Vec<u8>> {
+    let public_key = hex::decode(&export.public_key)
+        .map_err(|_| ApiError::internal("Invalid export public key"))?;
+
+    Ok([
+        APPROVAL_DOMAIN,
+        &export.id.to_be_bytes(),
+        &export.wallet_id.to_be_bytes(),
+        &public_key,
+    ]
+    .concat())
+}
+
+/// Whether `signature`, a 64 byte ECDSA signature, approves the export under `approver_key`
+pub fn verifies_approval(
+    export: &WalletExportModel,
+    approver_key: &[u8],
+    signature: &[u8],
+) -> bool {
+    let (Ok(message), Ok(key), Ok(signature)) = (
+        approval_message(export),
+        VerifyingKey::from_sec1_bytes(approver_key),
+        Signature::from_slice(signature),
+    ) else {
+        return false;
+    };
+
+    key.verify(&message, &signature).is_ok()
+}
+
+/// Approvals recorded for the export, passed to the participants
+fn export_approvals(export: &WalletExportModel) -> Result<Vec<ExportApproval>> {
+    #[derive(Deserialize)]
+    struct Signed {
+        approver_key: String,
+        signature: String,
+    }
+
+    let signed: Vec<Signed> = serde_json::from_value(export.signatures.clone())
+        .map_err(|_| ApiError::internal("Failed to complete the export"))?;
+
+    signed
+        .into_iter()
+        .map(|signed| {
+            Ok(ExportApproval {
+                approver_key: hex::decode(signed.approver_key)
+                    .map_err(|_| ApiError::internal("Failed to complete the export"))?,
+                signature: hex::decode(signed.signature)
+                    .map_err(|_| ApiError::internal("Failed to complete the export"))?,
+            })
+        })
+        .collect()
+}
+
+fn ensure_enabled(config: &WalletExportConfig) -> Result<()> {
+    match config.enabled {
+        true => Ok(()),
+        false => Err(ApiError::forbidden("Key export is disabled")),
+    }
+}
+
+/// Requests the export of the private key of a wallet, to be approved by the operators
+pub async fn create_export(
+    req: HttpRequest,
+    data: web::Json<CreateExportRequest>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<WalletExportConfig>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    ensure_enabled(&config)?;
+
+    let public_key = export_public_key(&data.public_key)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        data.wallet_id,
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::Export)?;
+
+    // The device already holds the other share, the server's alone reveals nothing
+    if wallet.cosigner {
+        return Err(ApiError::unprocessable(
+            "cosigned_wallet",
+            "Co-signed wallets can't be exported, the server holds a single share",
+        ));
+    }
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to request the export"))?;
+
+    let export = WalletExportRepository::new_with_transaction(&txn)
+        .create(user_id, wallet.id, hex::encode(public_key))
+        .await
+        .map_err(|_| ApiError::internal("Failed to request the export"))?;
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            &format!("user:{user_id}"),
+            "wallet.export_requested",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({ "export_id": export.id })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to request the export"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to request the export"))?;
+
+    log::warn!(
+        "Export {} of wallet {} requested by user {user_id}",
+        export.id,
+        wallet.id
+    );
+
+    Ok(HttpResponse::Accepted().json(export))
+}
+
+async fn find_user_export(
+    db: &DatabaseConnection,
+    export_id: i32,
+    user_id: i32,
+) -> Result<WalletExportModel> {
+    let export = WalletExportRepository::new_with_connection(db)
+        .find_by_id(export_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the export"))?;
+
+    match export {
+        Some(export) if export.user_id == user_id => Ok(export),
+        _ => Err(ApiError::not_found("export_not_found", "Export not found")),
+    }
+}
+
+pub async fn get_export(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let export = find_user_export(&db, path.into_inner(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(export))
+}
+
+/// Hands out the encrypted shares of an approved export, once
+pub async fn complete_export(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+    participants: web::Data<dyn ParticipantPool>,
+    config: web::Data<WalletExportConfig>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    ensure_enabled(&config)?;
+
+    let export = find_user_export(&db, path.into_inner(), user_id).await?;
+
+    if export.state != ExportState::Approved {
+        return Err(ApiError::conflict("Export is not approved").with_code("export_not_approved"));
+    }
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        export.wallet_id,
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::Export)?;
+
+    let public_key = hex::decode(&export.public_key)
+        .map_err(|_| ApiError::internal("Failed to complete the export"))?;
+
+    let approvals = export_approvals(&export)?;
+
+    let results = run_export(
+        participants.get_ref(),
+        wallet.id,
+        export.id,
+        &public_key,
+        approvals,
+    )
+    .await;
+
+    let shares = exported_shares(results)?;
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to complete the export"))?;
+
+    // The shares are discarded when another request completed the export first
+    let export = WalletExportRepository::new_with_transaction(&txn)
+        .complete(&export)
+        .await
+        .map_err(|_| ApiError::conflict("Export was already completed"))?;
+
+    let participants = shares
+        .iter()
+        .map(|share| share.participant)
+        .collect::<Vec<_>>();
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            &format!("user:{user_id}"),
+            "wallet.exported",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({
+                "export_id": export.id,
+                "approvers": export.approvers,
+                "participants": participants,
+            })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to complete the export"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to complete the export"))?;
+
+    log::warn!(
+        "Exported the shares of wallet {} to user {user_id}",
+        wallet.id
+    );
+
+    Ok(HttpResponse::Ok().json(CompletedExportResponse {
+        id: export.id,
+        wallet_id: wallet.id,
+        shares,
+    }))
+}
+
+/// Shares returned by the participants, enough of them to reconstruct the key
+fn exported_shares(results: Vec<Result<ShareBackupMessage, Status>>) -> Result<Vec<ExportedShare>> {
+    let shares = results
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, res)| {
+            let share = res.ok()?;
+
+            Some(ExportedShare {
+                participant: index + 1,
+                ephemeral_key: hex::encode(share.ephemeral_key),
+                nonce: hex::encode(share.nonce),
+                ciphertext: hex::encode(share.ciphertext),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if shares.len() < SIGNING_THRESHOLD {
+        return Err(
+            ApiError::internal("Not enough participants returned their share")
+                .with_code("export_failed"),
+        );
+    }
+
+    Ok(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_public_key_requires_sec1() {
+        let compressed = format!("02{}", "11".repeat(32));
+
+        assert_eq!(export_public_key(&compressed).unwrap().len(), 33);
+        assert_eq!(
+            export_public_key(&format!("0x04{}", "11".repeat(64)))
+                .unwrap()
+                .len(),
+            65
+        );
+        assert!(export_public_key(&format!("04{}", "11".repeat(32))).is_err());
+        assert!(export_public_key("not hex").is_err());
+    }
+
+    #[test]
+    fn test_approval_is_signed_over_the_export() {
+        use k256::ecdsa::SigningKey;
+        use k256::ecdsa::signature::Signer;
+
+        let approver = SigningKey::from_slice(&[1; 32]).unwrap();
+        let approver_key = approver.verifying_key().to_sec1_bytes();
+
+        let export = WalletExportModel {
+            id: 1,
+            user_id: 1,
+            wallet_id: 7,
+            public_key: "02".repeat(33),
+            state: ExportState::Pending,
+            approvals: 0,
+            approvers: serde_json::json!([]),
+            signatures: serde_json::json!([]),
+            created_at: None,
+            updated_at: None,
+        };
+
+        let message = approval_message(&export).unwrap();
+
+        // The participants verify the message they build themselves
+        assert_eq!(
+            message,
+            participant::backup::approval_message(1, 7, &[2; 33])
+        );
+
+        let signature: Signature = approver.sign(&message);
+
+        assert!(verifies_approval(
+            &export,
+            &approver_key,
+            &signature.to_bytes()
+        ));
+
+        let other = WalletExportModel {
+            wallet_id: 8,
+            ..export.clone()
+        };
+
+        assert!(!verifies_approval(
+            &other,
+            &approver_key,
+            &signature.to_bytes()
+        ));
+        assert!(!verifies_approval(&export, &approver_key, &[0; 64]));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_export_is_approved_by_distinct_approvers() {
+        use crate::db::models::{Chain, UserActiveModel, WalletActiveModel, WalletState};
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let repository = WalletExportRepository::new_with_connection(&db);
+
+        let pending = repository
+            .create(user.id, wallet.id, "02".repeat(33))
+            .await
+            .unwrap();
+
+        let first = repository
+            .approve(&pending, "bob", &[2; 33], &[1; 64], 2)
+            .await
+            .unwrap();
+
+        assert_eq!(first.state, ExportState::Pending);
+        assert!(first.approved_by("bob"));
+
+        // Recorded against the approvals it read, the first approval already moved them
+        assert!(
+            repository
+                .approve(&pending, "carol", &[3; 33], &[1; 64], 2)
+                .await
+                .is_err()
+        );
+
+        let second = repository
+            .approve(&first, "carol", &[3; 33], &[1; 64], 2)
+            .await
+            .unwrap();
+
+        assert_eq!(second.state, ExportState::Approved);
+        assert_eq!(
+            repository.find_by_id(pending.id).await.unwrap(),
+            Some(WalletExportModel {
+                approvers: serde_json::json!(["bob", "carol"]),
+                signatures: serde_json::json!([
+                    { "approver_key": "02".repeat(33), "signature": "01".repeat(64) },
+                    { "approver_key": "03".repeat(33), "signature": "01".repeat(64) },
+                ]),
+                ..second.clone()
+            })
+        );
+
+        let completed = repository.complete(&second).await.unwrap();
+
+        assert_eq!(completed.state, ExportState::Completed);
+        assert!(repository.complete(&second).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_exported_shares_need_the_signing_threshold() {
+        use crate::participants::mock::MockParticipants;
+
+        let participants = MockParticipants::new(3)
+            .with_share(1, 7, 0)
+            .with_share(2, 7, 1)
+            .failing(3, Status::internal("vault sealed"));
+
+        let shares =
+            exported_shares(run_export(&participants, 7, 1, &[2; 33], Vec::new()).await).unwrap();
+
+        assert_eq!(
+            shares
+                .iter()
+                .map(|share| share.participant)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let participants = participants.failing(2, Status::internal("vault sealed"));
+
+        let error = exported_shares(run_export(&participants, 7, 1, &[2; 33], Vec::new()).await)
+            .err()
+            .unwrap();
+
+        assert_eq!(error.problem().code, "export_failed");
+    }
+}
@@ -0,0 +1,450 @@
+use actix_web::{HttpMessage, HttpRequest, web};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Guard, Object, Schema, SimpleObject,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use chrono::{DateTime, Utc};
+use sea_orm::DbConn;
+use serde::Serialize;
+
+use super::error::{ApiError, Result};
+use crate::auth::Claims;
+use crate::db::models::{TransactionModel, UserModel, WalletModel};
+use crate::db::repositories::{TransactionRepository, UserRepository, WalletRepository};
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Most transactions a wallet lists in one request
+const MAX_TRANSACTIONS: i32 = 100;
+
+/// Read-only schema over the data of the authenticated user, nested queries are bounded
+/// so a single request can't walk the whole database
+pub fn schema(db: DbConn) -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(db)
+        .limit_depth(8)
+        .limit_complexity(2000)
+        .finish()
+}
+
+pub async fn graphql(
+    req: HttpRequest,
+    schema: web::Data<ApiSchema>,
+    request: GraphQLRequest,
+) -> Result<GraphQLResponse> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or_else(|| ApiError::unauthorized("User not authorized"))?;
+
+    Ok(schema
+        .execute(request.into_inner().data(claims))
+        .await
+        .into())
+}
+
+/// Resolves only for the user the field belongs to
+struct Owner(i32);
+
+impl Guard for Owner {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        match ctx.data_opt::<Claims>() {
+            Some(claims) if claims.user_id == self.0 => Ok(()),
+            _ => Err("Forbidden".into()),
+        }
+    }
+}
+
+fn claims<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Claims> {
+    ctx.data::<Claims>()
+        .map_err(|_| async_graphql::Error::new("User not authorized"))
+}
+
+fn database_error(err: anyhow::Error) -> async_graphql::Error {
+    log::error!("GraphQL query failed: {err}");
+    async_graphql::Error::new("Database error")
+}
+
+/// Name enums are serialized with in the REST API
+fn name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The authenticated user
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let user = UserRepository::new(ctx.data::<DbConn>()?)
+            .find_by_id(claims(ctx)?.user_id)
+            .await
+            .map_err(database_error)?;
+
+        Ok(user.map(User))
+    }
+
+    /// Wallets of the authenticated user
+    async fn wallets(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Wallet>> {
+        user_wallets(ctx, claims(ctx)?.user_id).await
+    }
+
+    /// Wallet of the authenticated user, null for the wallets of others
+    async fn wallet(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<Wallet>> {
+        let user_id = claims(ctx)?.user_id;
+
+        let wallet = WalletRepository::new_with_connection(ctx.data::<DbConn>()?)
+            .find_by_id(id)
+            .await
+            .map_err(database_error)?;
+
+        Ok(wallet
+            .filter(|wallet| wallet.user_id == user_id)
+            .map(Wallet))
+    }
+
+    /// Transaction of the authenticated user, null for the transactions of others
+    async fn transaction(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> async_graphql::Result<Option<Transaction>> {
+        let user_id = claims(ctx)?.user_id;
+
+        let transaction = TransactionRepository::new_with_connection(ctx.data::<DbConn>()?)
+            .find_by_id(id)
+            .await
+            .map_err(database_error)?;
+
+        Ok(transaction
+            .filter(|transaction| transaction.user_id == user_id)
+            .map(Transaction))
+    }
+}
+
+async fn user_wallets(ctx: &Context<'_>, user_id: i32) -> async_graphql::Result<Vec<Wallet>> {
+    let wallets = WalletRepository::new_with_connection(ctx.data::<DbConn>()?)
+        .find_by_user_id(user_id)
+        .await
+        .map_err(database_error)?;
+
+    Ok(wallets.into_iter().map(Wallet).collect())
+}
+
+pub struct User(UserModel);
+
+#[Object]
+impl User {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    #[graphql(guard = "Owner(self.0.id)")]
+    async fn email(&self) -> &str {
+        &self.0.email
+    }
+
+    async fn created_on(&self) -> Option<DateTime<Utc>> {
+        self.0.created_on
+    }
+
+    #[graphql(guard = "Owner(self.0.id)")]
+    async fn wallets(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Wallet>> {
+        user_wallets(ctx, self.0.id).await
+    }
+}
+
+pub struct Wallet(WalletModel);
+
+#[Object]
+impl Wallet {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn chain(&self) -> String {
+        name(&self.0.chain)
+    }
+
+    async fn state(&self) -> String {
+        name(&self.0.state)
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
+
+    async fn address_type(&self) -> Option<String> {
+        self.0.address_type.as_ref().map(name)
+    }
+
+    async fn whitelist_only(&self) -> bool {
+        self.0.whitelist_only
+    }
+
+    async fn auto_bump_gas(&self) -> bool {
+        self.0.auto_bump_gas
+    }
+
+    async fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.0.created_at
+    }
+
+    async fn archived_at(&self) -> Option<DateTime<Utc>> {
+        self.0.archived_at
+    }
+
+    /// Latest transactions first, at most 100
+    #[graphql(guard = "Owner(self.0.user_id)")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] first: i32,
+    ) -> async_graphql::Result<Vec<Transaction>> {
+        let transactions = TransactionRepository::new_with_connection(ctx.data::<DbConn>()?)
+            .find_by_wallet_id(self.0.id, first.clamp(0, MAX_TRANSACTIONS) as u64)
+            .await
+            .map_err(database_error)?;
+
+        Ok(transactions.into_iter().map(Transaction).collect())
+    }
+}
+
+/// Inclusion of a transaction in a block
+#[derive(SimpleObject)]
+pub struct Inclusion {
+    block_number: i64,
+    block_hash: Option<String>,
+    confirmations: Option<i64>,
+}
+
+pub struct Transaction(TransactionModel);
+
+#[Object]
+impl Transaction {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn wallet_id(&self) -> i32 {
+        self.0.wallet_id
+    }
+
+    async fn account_id(&self) -> Option<i32> {
+        self.0.account_id
+    }
+
+    async fn status(&self) -> String {
+        name(&self.0.status)
+    }
+
+    async fn chain(&self) -> Option<String> {
+        self.0.chain.as_ref().map(name)
+    }
+
+    async fn tx_hash(&self) -> Option<&str> {
+        self.0.tx_hash.as_deref()
+    }
+
+    async fn to_address(&self) -> Option<&str> {
+        self.0.to_address.as_deref()
+    }
+
+    /// Decimal amount in the smallest unit of the chain
+    async fn value(&self) -> Option<&str> {
+        self.0.value.as_deref()
+    }
+
+    async fn contract_address(&self) -> Option<&str> {
+        self.0.contract_address.as_deref()
+    }
+
+    async fn token_contract(&self) -> Option<&str> {
+        self.0.token_contract.as_deref()
+    }
+
+    async fn token_id(&self) -> Option<&str> {
+        self.0.token_id.as_deref()
+    }
+
+    async fn nonce(&self) -> Option<i64> {
+        self.0.nonce
+    }
+
+    async fn inclusion(&self) -> Option<Inclusion> {
+        self.0.block_number.map(|block_number| Inclusion {
+            block_number,
+            block_hash: self.0.block_hash.clone(),
+            confirmations: self.0.confirmations,
+        })
+    }
+
+    async fn error(&self) -> Option<&str> {
+        self.0.error.as_deref()
+    }
+
+    async fn screening_verdict(&self) -> Option<String> {
+        self.0.screening_verdict.as_ref().map(name)
+    }
+
+    async fn screening_reason(&self) -> Option<&str> {
+        self.0.screening_reason.as_deref()
+    }
+
+    async fn stuck_at(&self) -> Option<DateTime<Utc>> {
+        self.0.stuck_at
+    }
+
+    async fn replaces_id(&self) -> Option<i32> {
+        self.0.replaces_id
+    }
+
+    async fn memo(&self) -> Option<&str> {
+        self.0.memo.as_deref()
+    }
+
+    /// Key/value pairs the client sent with the transaction
+    async fn metadata(&self) -> Option<async_graphql::Json<&serde_json::Value>> {
+        self.0.metadata.as_ref().map(async_graphql::Json)
+    }
+
+    /// Signed transaction, to broadcast it elsewhere
+    #[graphql(guard = "Owner(self.0.user_id)")]
+    async fn raw_tx(&self) -> Option<&str> {
+        self.0.raw_tx.as_deref()
+    }
+
+    async fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.0.updated_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_nested_query_covers_only_own_data() {
+        use super::*;
+        use crate::auth::generate_claims;
+        use crate::db::models::{
+            Chain, TransactionActiveModel, TransactionStatus, UserActiveModel, WalletActiveModel,
+            WalletState,
+        };
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let mut users = Vec::new();
+
+        for username in ["alice", "bob"] {
+            let user = UserRepository::new(&db)
+                .create(UserActiveModel {
+                    username: Set(username.to_string()),
+                    password: Set(String::new()),
+                    email: Set(format!("{username}@example.com")),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            users.push(user);
+        }
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(users[0].id),
+                name: Set("savings".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut transactions = Vec::new();
+
+        for value in ["1", "2"] {
+            let transaction = TransactionRepository::new_with_connection(&db)
+                .create(TransactionActiveModel {
+                    user_id: Set(users[0].id),
+                    wallet_id: Set(wallet.id),
+                    status: Set(TransactionStatus::Pending),
+                    value: Set(Some(value.to_string())),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            transactions.push(transaction);
+        }
+
+        let schema = schema(db);
+
+        let response = schema
+            .execute(
+                async_graphql::Request::new(
+                    "{ me { email wallets { name transactions(first: 1) { value } } } }",
+                )
+                .data(generate_claims(&users[0])),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({
+                "me": {
+                    "email": "alice@example.com",
+                    "wallets": [{ "name": "savings", "transactions": [{ "value": "2" }] }],
+                },
+            })
+        );
+
+        // Others don't see the wallet nor its transactions
+        let response = schema
+            .execute(
+                async_graphql::Request::new(format!(
+                    "{{ wallet(id: {}) {{ id }} transaction(id: {}) {{ id }} }}",
+                    wallet.id, transactions[0].id
+                ))
+                .data(generate_claims(&users[1])),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "wallet": null, "transaction": null })
+        );
+
+        // Nor does a request without claims
+        let response = schema.execute("{ wallets { id } }").await;
+
+        assert_eq!(response.errors[0].message, "User not authorized");
+    }
+}
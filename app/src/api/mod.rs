@@ -1,41 +1,131 @@
-use crate::middleware::AuthMiddleware;
+use crate::chains::ChainRegistry;
+use crate::middleware::{
+    AdminMiddleware, AuthMiddleware, RateLimitMiddleware, RateLimiter, SessionStore,
+};
+use crate::participants::ParticipantPool;
 use actix_web::web::ServiceConfig;
 use actix_web::{HttpResponse, web};
-use alloy::providers::Provider;
 use sea_orm::DbConn;
 use std::sync::Arc;
-use tonic::transport::Channel;
 
+mod accounts;
+mod addresses;
+mod admin;
 mod auth;
+mod calls;
+mod cosigner;
+pub mod error;
+mod events;
+mod exports;
+mod graphql;
+mod nfts;
+mod oidc;
+mod operations;
+mod psbt;
+mod quotas;
+mod recurring;
+mod safe;
+pub mod status;
+mod sweep;
+mod tokens;
+mod user_operations;
 mod users;
 mod wallet;
+mod withdrawals;
+
+pub use operations::finish_operation;
+pub use wallet::{TransactionRequest, keygen_wallet, replace_transaction, send_wallet_tx};
+pub use withdrawals::release_withdrawal;
 
 pub fn configure_routes(
     cfg: &mut ServiceConfig,
     db: DbConn,
-    participants: Vec<Channel>,
-    provider: Arc<dyn Provider + Send + Sync>,
+    participants: Arc<dyn ParticipantPool>,
+    chains: Arc<ChainRegistry>,
+    status: web::Data<status::StatusService>,
+    status_limiter: Arc<RateLimiter>,
+    admin_api_key: Option<String>,
 ) {
+    let sessions_data = web::Data::from(Arc::new(db.clone()) as Arc<dyn SessionStore>);
     let db_data = web::Data::new(db);
-    let participants_data = web::Data::new(participants);
-    let provider_data = web::Data::from(provider);
+    let participants_data = web::Data::from(participants);
+    let chains_data = web::Data::from(chains);
+    let schema_data = web::Data::new(graphql::schema(db_data.get_ref().clone()));
 
     cfg.app_data(db_data)
+        .app_data(sessions_data)
         .app_data(participants_data)
-        .app_data(provider_data)
+        .app_data(chains_data)
+        .app_data(status)
+        .app_data(schema_data)
         .route("/health", web::get().to(health_check))
+        .route("/.well-known/jwks.json", web::get().to(auth::jwks))
+        .service(
+            web::resource("/status")
+                .wrap(RateLimitMiddleware::new(status_limiter))
+                .get(status::status),
+        )
         .service(
             web::scope("/api")
-                .service(web::scope("/auth").configure(auth::configure))
+                .service(
+                    web::scope("/auth")
+                        .configure(auth::configure)
+                        .configure(oidc::configure),
+                )
+                .service(
+                    web::resource("/events")
+                        .wrap(AuthMiddleware::new())
+                        .route(web::get().to(events::events)),
+                )
+                .service(
+                    web::resource("/graphql")
+                        .wrap(AuthMiddleware::new())
+                        .route(web::post().to(graphql::graphql)),
+                )
                 .service(
                     web::scope("/users")
                         .wrap(AuthMiddleware::new())
                         .configure(users::configure_protected),
                 )
+                .service(
+                    web::scope("/addresses")
+                        .wrap(AuthMiddleware::new())
+                        .configure(addresses::configure),
+                )
+                .service(
+                    web::scope("/operations")
+                        .wrap(AuthMiddleware::new())
+                        .configure(operations::configure),
+                )
+                .service(
+                    web::scope("/exports")
+                        .wrap(AuthMiddleware::new())
+                        .configure(exports::configure),
+                )
+                .service(
+                    web::scope("/cosigner")
+                        .wrap(AuthMiddleware::new())
+                        .configure(cosigner::configure),
+                )
                 .service(
                     web::scope("/wallet")
                         .wrap(AuthMiddleware::new())
-                        .configure(wallet::configure),
+                        .configure(wallet::configure)
+                        .configure(accounts::configure)
+                        .configure(recurring::configure)
+                        .configure(sweep::configure)
+                        .configure(calls::configure)
+                        .configure(nfts::configure)
+                        .configure(tokens::configure)
+                        .configure(psbt::configure)
+                        .configure(user_operations::configure)
+                        .configure(safe::configure)
+                        .configure(withdrawals::configure),
+                )
+                .service(
+                    web::scope("/admin")
+                        .wrap(AdminMiddleware::new(admin_api_key))
+                        .configure(admin::configure),
                 ),
         );
 }
@@ -0,0 +1,177 @@
+use super::error::{ApiError, Result};
+use super::quotas::ensure_signing_velocity;
+use super::wallet::{
+    Annotation, Recipient, TokenCall, TransactionRequest, Transfer, ensure_whitelisted,
+    estimate_gas_limit, find_user_wallet, resolve_transaction, send_contract_call,
+};
+use crate::chains::{ChainRegistry, NftStandard, NftTransfer};
+use crate::db::models::WalletOperation;
+use crate::db::repositories::WalletRepository;
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, U256};
+use alloy::rpc::types::{TransactionInput, TransactionRequest as CallRequest};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct NftTransferRequest {
+    pub to: Recipient,
+    pub standard: NftStandard,
+    pub contract: Address,
+    pub token_id: U256,
+    /// ERC-1155 only, defaults to a single token
+    #[serde(default)]
+    pub amount: Option<U256>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/nft/transfer").route(web::post().to(transfer_nft)));
+}
+
+/// Number of tokens sent, ERC-721 tokens are unique so only one can move at a time
+fn nft_amount(standard: NftStandard, amount: Option<U256>) -> Result<U256> {
+    match (standard, amount) {
+        (_, None) => Ok(U256::from(1)),
+        (_, Some(amount)) if amount.is_zero() => {
+            Err(ApiError::bad_request("Amount must be positive"))
+        }
+        (NftStandard::Erc721, Some(amount)) if amount != U256::from(1) => Err(
+            ApiError::bad_request("ERC-721 tokens can only be sent one at a time"),
+        ),
+        (_, Some(amount)) => Ok(amount),
+    }
+}
+
+/// Sends an ERC-721 or ERC-1155 token of the wallet with `safeTransferFrom`, the provider
+/// must report the wallet as its owner before the signing round
+pub async fn transfer_nft(
+    req: HttpRequest,
+    data: web::Json<NftTransferRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let wallet_id = path.into_inner();
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let from: Address = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to send from"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    let nft = NftTransfer {
+        standard: data.standard,
+        contract: data.contract,
+        token_id: data.token_id,
+        amount: nft_amount(data.standard, data.amount)?,
+    };
+
+    let recipient = resolve_transaction(
+        &wallet,
+        network,
+        &TransactionRequest {
+            to: Some(data.to.clone()),
+            value: U256::ZERO,
+            data: None,
+            memo: None,
+            metadata: None,
+        },
+    )
+    .await?;
+
+    let to = recipient
+        .to
+        .ok_or_else(|| ApiError::bad_request("Recipient is required"))?;
+
+    ensure_whitelisted(&db, &wallet, [to.to_string()]).await?;
+
+    let owned = nft
+        .is_owned_by(provider.as_ref(), from)
+        .await
+        .map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to check the token owner")
+        })?;
+
+    if !owned {
+        return Err(ApiError::unprocessable(
+            "token_not_owned",
+            "Wallet does not own the token",
+        ));
+    }
+
+    let call_data = nft.call_data(from, to);
+
+    let call = CallRequest {
+        from: Some(from),
+        to: Some(nft.contract.into()),
+        input: TransactionInput::new(call_data.clone()),
+        ..Default::default()
+    };
+
+    let transfer = Transfer {
+        to: Some(nft.contract),
+        value: U256::ZERO,
+        data: call_data,
+        gas_limit: estimate_gas_limit(provider.as_ref(), call).await?,
+        ens_name: recipient.ens_name,
+        token: Some(TokenCall {
+            recipient: to,
+            token_id: Some(nft.token_id),
+        }),
+        annotation: Annotation::default(),
+    };
+
+    send_contract_call(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        transfer,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nft_amount_defaults_to_one_token() {
+        assert_eq!(
+            nft_amount(NftStandard::Erc721, None).unwrap(),
+            U256::from(1)
+        );
+        assert_eq!(
+            nft_amount(NftStandard::Erc1155, Some(U256::from(5))).unwrap(),
+            U256::from(5)
+        );
+        assert!(nft_amount(NftStandard::Erc721, Some(U256::from(2))).is_err());
+        assert!(nft_amount(NftStandard::Erc1155, Some(U256::ZERO)).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+use actix_web::{HttpRequest, HttpResponse, http::StatusCode, http::header, web};
+use chrono::{Duration, Utc};
+use sea_orm::ActiveValue::Set;
+use sea_orm::DbConn;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::auth::{LoginResponse, create_session};
+use super::error::{ApiError, Result};
+use crate::auth::{
+    Authorization, IdTokenClaims, OidcClient, generate_claims, generate_token, hash_password,
+};
+use crate::config::app_config::OidcConfig;
+use crate::db::models::{OidcLoginModel, UserActiveModel, UserModel};
+use crate::db::repositories::{
+    AuditRepository, OidcLoginRepository, UserIdentityRepository, UserRepository,
+};
+
+/// Time a user has to log in at the provider before the callback is refused
+const LOGIN_TIMEOUT: Duration = Duration::minutes(10);
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set by the provider instead of the code when the login was refused
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/oidc/start", web::get().to(start))
+        .route("/oidc/callback", web::get().to(callback));
+}
+
+fn oidc_client(oidc: &Option<OidcClient>) -> Result<&OidcClient> {
+    oidc.as_ref()
+        .ok_or_else(|| ApiError::not_found("oidc_disabled", "OpenID Connect login is disabled"))
+}
+
+/// Redirects the user to the login page of the provider
+pub async fn start(
+    db: web::Data<DbConn>,
+    oidc: web::Data<Option<OidcClient>>,
+) -> Result<HttpResponse> {
+    let oidc = oidc_client(&oidc)?;
+    let now = Utc::now();
+
+    let repository = OidcLoginRepository::new(db.get_ref());
+
+    if let Err(err) = repository.delete_started_before(now - LOGIN_TIMEOUT).await {
+        log::error!("Failed to forget the abandoned OIDC logins: {err}");
+    }
+
+    let authorization = Authorization::new();
+
+    let url = oidc
+        .authorization_url(&authorization)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to discover the OIDC provider: {err}");
+            ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                "oidc_unavailable",
+                "Identity provider is unavailable",
+            )
+        })?;
+
+    repository
+        .create(OidcLoginModel {
+            state: authorization.state,
+            nonce: authorization.nonce,
+            code_verifier: authorization.code_verifier,
+            created_at: now,
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to start the login"))?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .finish())
+}
+
+/// Logs in the user the provider redirected back, issuing the same tokens as a password login
+pub async fn callback(
+    req: HttpRequest,
+    query: web::Query<CallbackQuery>,
+    db: web::Data<DbConn>,
+    oidc: web::Data<Option<OidcClient>>,
+) -> Result<HttpResponse> {
+    let oidc = oidc_client(&oidc)?;
+
+    if let Some(error) = &query.error {
+        let reason = query.error_description.as_ref().unwrap_or(error);
+
+        return Err(ApiError::unauthorized(format!(
+            "Identity provider refused the login: {reason}"
+        ))
+        .with_code("oidc_denied"));
+    }
+
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return Err(ApiError::bad_request("Expected a code and a state"));
+    };
+
+    // Taken whatever happens next, a state is only ever used once
+    let login = OidcLoginRepository::new(db.get_ref())
+        .take(state)
+        .await
+        .map_err(|_| ApiError::internal("Failed to complete the login"))?
+        .filter(|login| login.created_at > Utc::now() - LOGIN_TIMEOUT)
+        .ok_or_else(|| {
+            ApiError::bad_request("Unknown or expired login").with_code("oidc_invalid_state")
+        })?;
+
+    let authorization = Authorization {
+        state: login.state,
+        nonce: login.nonce,
+        code_verifier: login.code_verifier,
+    };
+
+    let id_token = oidc
+        .authenticate(code, &authorization)
+        .await
+        .map_err(|err| {
+            log::warn!("OIDC login failed: {err}");
+            ApiError::unauthorized("Identity provider login failed").with_code("oidc_failed")
+        })?;
+
+    let user = oidc_user(db.get_ref(), oidc.config(), &id_token).await?;
+
+    let claims = generate_claims(&user);
+    let token = generate_token(&claims)?;
+
+    let client = req.peer_addr().map(|addr| addr.ip());
+    create_session(db.get_ref(), &req, &claims, client).await?;
+
+    Ok(HttpResponse::Ok().json(LoginResponse { token }))
+}
+
+/// Local user of the subject, linked on its first login to the user of its email when
+/// allowed or else to a new user
+async fn oidc_user(
+    db: &DbConn,
+    config: &OidcConfig,
+    id_token: &IdTokenClaims,
+) -> Result<UserModel> {
+    let identities = UserIdentityRepository::new(db);
+    let users = UserRepository::new(db);
+
+    let identity = identities
+        .find_by_subject(&id_token.iss, &id_token.sub)
+        .await
+        .map_err(|e| ApiError::internal(format!("Database error: {e}")))?;
+
+    if let Some(identity) = identity {
+        return users
+            .find_by_id(identity.user_id)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::internal("Linked user not found"));
+    }
+
+    let Some(email) = id_token.email.clone() else {
+        return Err(ApiError::forbidden("The identity provider shared no email")
+            .with_code("oidc_email_required"));
+    };
+
+    let existing = users
+        .find_by_email(&email)
+        .await
+        .map_err(|e| ApiError::internal(format!("Database error: {e}")))?;
+
+    let (user, created) = match existing {
+        Some(user) if config.link_by_email && id_token.email_verified => (user, false),
+        Some(_) => {
+            return Err(ApiError::unprocessable(
+                "email_taken",
+                format!(
+                    "Email {email} is registered to a user that didn't log in with the identity provider"
+                ),
+            ));
+        }
+        None => (create_user(&users, id_token, email).await?, true),
+    };
+
+    identities
+        .link(user.id, &id_token.iss, &id_token.sub)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to link the identity: {e}")))?;
+
+    if let Err(err) = AuditRepository::new_with_connection(db)
+        .record(
+            &format!("user:{}", user.id),
+            "auth.oidc_linked",
+            "user",
+            Some(user.id.to_string()),
+            Some(serde_json::json!({
+                "issuer": id_token.iss,
+                "subject": id_token.sub,
+                "created": created,
+            })),
+        )
+        .await
+    {
+        log::error!("Failed to audit the OIDC link of user {}: {err}", user.id);
+    }
+
+    Ok(user)
+}
+
+/// User of a first OIDC login, with a random password it can't log in with
+async fn create_user(
+    users: &UserRepository<'_>,
+    id_token: &IdTokenClaims,
+    email: String,
+) -> Result<UserModel> {
+    let base = base_username(id_token.preferred_username.as_deref(), &email);
+
+    let mut username = base.clone();
+
+    // A suffix for the names taken by a local user or another provider
+    for _ in 0..5 {
+        let taken = users
+            .find_by_username(&username)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database error: {e}")))?;
+
+        if taken.is_none() {
+            return users
+                .create(UserActiveModel {
+                    username: Set(username),
+                    password: Set(hash_password(&Uuid::new_v4().to_string())?),
+                    email: Set(email),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to create user: {e}")));
+        }
+
+        username = format!("{base}-{}", &Uuid::new_v4().simple().to_string()[..6]);
+    }
+
+    Err(ApiError::internal("Failed to pick a username"))
+}
+
+/// Preferred username of the provider, or the local part of the email, reduced to the
+/// characters usernames are made of
+fn base_username(preferred_username: Option<&str>, email: &str) -> String {
+    let name = preferred_username
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default());
+
+    let name = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "._-".contains(*c))
+        .take(40)
+        .collect::<String>();
+
+    match name.len() {
+        0..3 => format!("user-{name}"),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_username() {
+        assert_eq!(base_username(Some("alice"), "a@example.com"), "alice");
+        assert_eq!(
+            base_username(Some(" "), "bob.smith@example.com"),
+            "bob.smith"
+        );
+        assert_eq!(base_username(Some("Jane Doe"), "j@example.com"), "JaneDoe");
+        assert_eq!(base_username(None, "jo@example.com"), "user-jo");
+        assert_eq!(base_username(None, "@example.com"), "user-");
+        assert_eq!(base_username(Some(&"x".repeat(60)), "").len(), 40);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_oidc_user_is_linked_once() {
+        use sea_orm::{ConnectOptions, Database};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let local = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let id_token = |sub: &str, email: &str| -> IdTokenClaims {
+            serde_json::from_value(serde_json::json!({
+                "iss": "https://idp.example.com/",
+                "sub": sub,
+                "email": email,
+                "email_verified": true,
+                "preferred_username": "alice",
+            }))
+            .unwrap()
+        };
+
+        let mut config = OidcConfig {
+            issuer: "https://idp.example.com/".to_string(),
+            client_id: "waas".to_string(),
+            client_secret: None,
+            redirect_url: "https://waas.example.com/api/auth/oidc/callback".to_string(),
+            scopes: "openid email".to_string(),
+            link_by_email: false,
+            timeout: 10,
+        };
+
+        // A new subject gets a user of its own, the username of the local one suffixed
+        let created = oidc_user(&db, &config, &id_token("1", "other@example.com"))
+            .await
+            .unwrap();
+
+        assert_ne!(created.id, local.id);
+        assert!(created.username.starts_with("alice-"));
+        assert_eq!(
+            oidc_user(&db, &config, &id_token("1", "changed@example.com"))
+                .await
+                .unwrap()
+                .id,
+            created.id
+        );
+
+        // The email of a local user is only linked when allowed
+        let error = oidc_user(&db, &config, &id_token("2", "alice@example.com"))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.problem().code, "email_taken");
+
+        config.link_by_email = true;
+
+        let linked = oidc_user(&db, &config, &id_token("2", "alice@example.com"))
+            .await
+            .unwrap();
+
+        assert_eq!(linked.id, local.id);
+    }
+}
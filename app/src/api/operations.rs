@@ -0,0 +1,57 @@
+use super::error::{ApiError, Result};
+use crate::db::models::OperationModel;
+use crate::db::repositories::OperationRepository;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use sea_orm::DatabaseConnection;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}").route(web::get().to(get_operation)));
+}
+
+/// Records how the running attempt of an operation ended, `error` is the problem it failed
+/// with. Failing to record it is only logged, the outcome of the ceremony stands
+pub async fn finish_operation(
+    repository: &OperationRepository<'_>,
+    operation: &OperationModel,
+    error: Option<&ApiError>,
+) {
+    let finished = match error {
+        None => repository.succeed(operation).await,
+        Some(error) => {
+            let problem = error.problem();
+
+            repository
+                .fail(operation, problem.code, &problem.detail)
+                .await
+        }
+    };
+
+    if let Err(err) = finished {
+        log::error!(
+            "Failed to record the outcome of operation {}: {err}",
+            operation.id
+        );
+    }
+}
+
+pub async fn get_operation(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let operation = OperationRepository::new_with_connection(&db)
+        .find_by_id(path.into_inner())
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the operation"))?;
+
+    match operation {
+        Some(operation) if operation.user_id == user_id => Ok(HttpResponse::Ok().json(operation)),
+        _ => Err(ApiError::not_found(
+            "operation_not_found",
+            "Operation not found",
+        )),
+    }
+}
@@ -0,0 +1,224 @@
+use super::error::{ApiError, Result};
+use super::quotas::ensure_signing_velocity;
+use super::wallet::{
+    SendFailure, SigningData, ensure_whitelisted, fail_transaction, find_user_wallet,
+    screen_transaction, sign_with_participants,
+};
+use crate::chains::{ChainRegistry, Psbt, script_address};
+use crate::db::models::{AddressType, TransactionActiveModel, TransactionStatus, WalletOperation};
+use crate::db::repositories::{StatusDetails, TransactionRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sea_orm::{DatabaseConnection, Set};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct SignPsbtRequest {
+    /// Base64 encoded PSBT
+    pub psbt: String,
+}
+
+#[derive(Serialize)]
+pub struct SignPsbtResponse {
+    pub id: i32,
+    pub psbt: String,
+    /// Indexes of the inputs signed by the wallet
+    pub signed_inputs: Vec<usize>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/psbt/sign").route(web::post().to(sign_psbt)));
+}
+
+/// Signs the Taproot key path inputs of the PSBT that spend from the wallet, finalizing
+/// and broadcasting the transaction is left to the coordinator that built it
+pub async fn sign_psbt(
+    req: HttpRequest,
+    data: web::Json<SignPsbtRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let wallet_id = path.into_inner();
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    // ECDSA participants hash the data they sign, only Schnorr signs a sighash as is
+    if wallet.address_type != Some(AddressType::P2tr) {
+        return Err(ApiError::bad_request(
+            "PSBT signing requires a Taproot wallet",
+        ));
+    }
+
+    let address = wallet
+        .address
+        .clone()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to sign for"))?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let mut psbt = BASE64
+        .decode(data.psbt.trim())
+        .map_err(|_| ApiError::bad_request("PSBT is not valid base64"))
+        .and_then(|bytes| {
+            Psbt::parse(&bytes).map_err(|err| ApiError::bad_request(format!("Invalid PSBT: {err}")))
+        })?;
+
+    let mut sighashes = Vec::new();
+
+    for index in 0..psbt.input_count() {
+        let utxo = psbt
+            .witness_utxo(index)
+            .map_err(|err| ApiError::bad_request(format!("Invalid input {index}: {err}")))?;
+
+        // Inputs of other signers are left untouched
+        if utxo.and_then(|utxo| script_address(&utxo.script_pubkey)) != Some(address.clone()) {
+            continue;
+        }
+
+        let sighash = psbt
+            .sighash_type(index)
+            .and_then(|hash_type| Ok((hash_type, psbt.taproot_sighash(index, hash_type)?)))
+            .map_err(|err| ApiError::bad_request(format!("Invalid input {index}: {err}")))?;
+
+        sighashes.push((index, sighash));
+    }
+
+    if sighashes.is_empty() {
+        return Err(ApiError::bad_request(
+            "No input of the PSBT spends from the wallet",
+        ));
+    }
+
+    // Change back to the wallet is neither whitelisted nor screened
+    let mut recipients = Vec::new();
+    let mut value = 0u64;
+
+    for output in psbt.outputs() {
+        match script_address(&output.script_pubkey) {
+            Some(recipient) if recipient == address => {}
+            Some(recipient) => {
+                value = value.saturating_add(output.value);
+                recipients.push(recipient);
+            }
+            None if output.value > 0 && wallet.whitelist_only => {
+                return Err(ApiError::forbidden("Output script has no address"));
+            }
+            None => value = value.saturating_add(output.value),
+        }
+    }
+
+    ensure_whitelisted(&db, &wallet, recipients.clone()).await?;
+
+    let transaction_repository = TransactionRepository::new_with_connection(&db);
+
+    let mut transaction_model = transaction_repository
+        .create(TransactionActiveModel {
+            user_id: Set(wallet.user_id),
+            wallet_id: Set(wallet.id),
+            status: Set(TransactionStatus::Pending),
+            chain: Set(Some(wallet.chain.clone())),
+            value: Set(Some(value.to_string())),
+            to_address: Set(recipients.first().cloned()),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to create transaction"))?;
+
+    for recipient in &recipients {
+        transaction_model = match screen_transaction(
+            &transaction_repository,
+            &screener,
+            &wallet,
+            transaction_model,
+            recipient,
+        )
+        .await
+        {
+            Ok(transaction_model) => transaction_model,
+            Err(failure) => return Err(failure.into()),
+        };
+    }
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signing,
+            StatusDetails::default(),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to sign transaction"))?;
+
+    let mut signed_inputs = Vec::new();
+
+    for (index, (hash_type, sighash)) in sighashes {
+        let signed = sign_with_participants(
+            &db,
+            participants.get_ref(),
+            &wallet,
+            network,
+            &transaction_model,
+            SigningData::Input {
+                data: sighash.to_vec(),
+                index: index as u32,
+            },
+        )
+        .await;
+
+        let (r, s) = match signed {
+            Ok((r, s, _)) => (r, s),
+            Err(failure) => return Err(failure.into()),
+        };
+
+        let Ok(signature) = <[u8; 64]>::try_from([r, s].concat()) else {
+            let failure = SendFailure::Internal("Invalid signature returned by participants");
+
+            fail_transaction(
+                &transaction_repository,
+                &transaction_model,
+                failure.message(),
+            )
+            .await;
+
+            return Err(failure.into());
+        };
+
+        psbt.set_tap_key_sig(index, &signature, hash_type);
+        signed_inputs.push(index);
+    }
+
+    let signed_psbt = BASE64.encode(psbt.serialize());
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signed,
+            StatusDetails {
+                raw_tx: Some(signed_psbt.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to sign transaction"))?;
+
+    Ok(HttpResponse::Ok().json(SignPsbtResponse {
+        id: transaction_model.id,
+        psbt: signed_psbt,
+        signed_inputs,
+    }))
+}
@@ -0,0 +1,411 @@
+use super::error::{ApiError, Result};
+use crate::config::app_config::QuotaConfig;
+use crate::db::models::{TransactionModel, UserLimitModel, WalletModel};
+use crate::db::repositories::{TransactionRepository, UserLimitRepository, WalletRepository};
+use actix_web::{HttpRequest, http::StatusCode, web};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+
+/// Window the daily transaction limit counts over
+const DAY: Duration = Duration::hours(24);
+
+/// Window the hourly signing limit of a wallet counts over
+const HOUR: Duration = Duration::hours(1);
+
+/// Limits applying to a user, 0 disables a limit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct UserLimits {
+    pub max_wallets: u32,
+    pub max_daily_transactions: u32,
+}
+
+impl UserLimits {
+    /// The limits an admin set for the user, the configured defaults for the others
+    pub fn resolve(config: &QuotaConfig, overrides: Option<&UserLimitModel>) -> Self {
+        let max_wallets = overrides.and_then(|limits| limits.max_wallets);
+        let max_daily_transactions = overrides.and_then(|limits| limits.max_daily_transactions);
+
+        Self {
+            max_wallets: max_wallets.map_or(config.max_wallets, |limit| limit as u32),
+            max_daily_transactions: max_daily_transactions
+                .map_or(config.max_daily_transactions, |limit| limit as u32),
+        }
+    }
+}
+
+pub async fn user_limits(
+    db: &DatabaseConnection,
+    config: &QuotaConfig,
+    user_id: i32,
+) -> anyhow::Result<UserLimits> {
+    let overrides = UserLimitRepository::new_with_connection(db)
+        .find_by_user_id(user_id)
+        .await?;
+
+    Ok(UserLimits::resolve(config, overrides.as_ref()))
+}
+
+/// Rejects the creation of a wallet by a user already holding as many as allowed
+pub(super) async fn ensure_wallet_quota(
+    db: &DatabaseConnection,
+    config: &QuotaConfig,
+    user_id: i32,
+) -> Result<()> {
+    let limits = user_limits(db, config, user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the wallet limit"))?;
+
+    if limits.max_wallets == 0 {
+        return Ok(());
+    }
+
+    let wallets = WalletRepository::new_with_connection(db)
+        .find_by_user_id(user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the wallet limit"))?;
+
+    if wallets.len() >= limits.max_wallets as usize {
+        return Err(ApiError::forbidden(format!(
+            "Wallet limit of {} reached, delete a wallet or ask for a higher limit",
+            limits.max_wallets
+        ))
+        .with_code("wallet_limit_exceeded")
+        .with("limit", limits.max_wallets));
+    }
+
+    Ok(())
+}
+
+/// Rejects `count` more transactions of a user that would exceed its daily limit, with the
+/// limits of the app data since the send handlers already take as many extractors as allowed
+pub(super) async fn ensure_transaction_quota(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    user_id: i32,
+    count: usize,
+) -> Result<()> {
+    let config = req
+        .app_data::<web::Data<QuotaConfig>>()
+        .ok_or_else(|| ApiError::internal("Failed to check the transaction limit"))?;
+
+    ensure_daily_transactions(db, config, user_id, count).await
+}
+
+/// Rejects `count` more transactions of a user that would exceed its daily limit, retrying
+/// once enough of the transactions of the last 24 hours got older than that
+pub(super) async fn ensure_daily_transactions(
+    db: &DatabaseConnection,
+    config: &QuotaConfig,
+    user_id: i32,
+    count: usize,
+) -> Result<()> {
+    let limits = user_limits(db, config, user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the transaction limit"))?;
+
+    if limits.max_daily_transactions == 0 {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+
+    let sent = TransactionRepository::new_with_connection(db)
+        .find_sent_since(user_id, now - DAY)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the transaction limit"))?;
+
+    let limit = limits.max_daily_transactions as usize;
+
+    if sent.len() + count <= limit {
+        return Ok(());
+    }
+
+    match retry_after(&sent, sent.len() + count - limit, DAY, now) {
+        Some(seconds) => Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "transaction_limit_exceeded",
+            format!("Daily transaction limit of {limit} reached"),
+        )
+        .with("limit", limit)
+        .with("sent", sent.len())
+        .with_retry_after(seconds)),
+        None => Err(ApiError::forbidden(format!(
+            "{count} transactions exceed the daily transaction limit of {limit}"
+        ))
+        .with_code("transaction_limit_exceeded")
+        .with("limit", limit)),
+    }
+}
+
+/// Rejects `count` more signatures of the wallet that would exceed the hourly or daily
+/// limit of its policy, so runaway automation or a leaked key can't drain it in a burst
+pub(super) async fn ensure_signing_velocity(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    count: usize,
+) -> Result<()> {
+    let limits = [
+        (wallet.max_signatures_per_hour, HOUR, "hourly"),
+        (wallet.max_signatures_per_day, DAY, "daily"),
+    ];
+
+    if limits.iter().all(|(limit, _, _)| limit.is_none()) {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+
+    let sent = TransactionRepository::new_with_connection(db)
+        .find_wallet_sent_since(wallet.id, now - DAY)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the signing limit"))?;
+
+    for (limit, window, name) in limits {
+        let Some(limit) = limit else {
+            continue;
+        };
+
+        let limit = limit.max(0) as usize;
+        let sent = within(&sent, window, now);
+
+        if sent.len() + count <= limit {
+            continue;
+        }
+
+        return Err(
+            match retry_after(sent, sent.len() + count - limit, window, now) {
+                Some(seconds) => ApiError::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "signing_limit_exceeded",
+                    format!("The {name} signing limit of {limit} of the wallet is reached"),
+                )
+                .with("limit", limit)
+                .with("sent", sent.len())
+                .with_retry_after(seconds),
+                None => ApiError::forbidden(format!(
+                    "{count} transactions exceed the {name} signing limit of {limit} of the wallet"
+                ))
+                .with_code("signing_limit_exceeded")
+                .with("limit", limit),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// The transactions, oldest first, sent in the `window` before `now`
+fn within(sent: &[TransactionModel], window: Duration, now: DateTime<Utc>) -> &[TransactionModel] {
+    let start = sent.partition_point(|transaction| {
+        transaction
+            .created_at
+            .is_none_or(|created_at| created_at < now - window)
+    });
+
+    &sent[start..]
+}
+
+/// Seconds until the `excess` oldest transactions left the window, `None` when the
+/// transactions requested exceed the limit on their own
+fn retry_after(
+    sent: &[TransactionModel],
+    excess: usize,
+    window: Duration,
+    now: DateTime<Utc>,
+) -> Option<u64> {
+    let created_at = sent.get(excess.checked_sub(1)?)?.created_at?;
+
+    Some((created_at + window - now).num_seconds().max(1) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent_at(created_at: DateTime<Utc>) -> TransactionModel {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "user_id": 1,
+            "wallet_id": 1,
+            "created_at": created_at,
+            "status": "pending",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_limits_fall_back_to_the_defaults() {
+        let config = QuotaConfig {
+            max_wallets: 10,
+            max_daily_transactions: 100,
+        };
+
+        assert_eq!(
+            UserLimits::resolve(&config, None),
+            UserLimits {
+                max_wallets: 10,
+                max_daily_transactions: 100,
+            }
+        );
+
+        let overrides = UserLimitModel {
+            user_id: 1,
+            max_wallets: None,
+            max_daily_transactions: Some(0),
+            updated_at: None,
+        };
+
+        assert_eq!(
+            UserLimits::resolve(&config, Some(&overrides)),
+            UserLimits {
+                max_wallets: 10,
+                max_daily_transactions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_retry_after_the_excess_leaves_the_window() {
+        let now = Utc::now();
+
+        let sent = [
+            sent_at(now - Duration::hours(23)),
+            sent_at(now - Duration::hours(2)),
+        ];
+
+        assert_eq!(retry_after(&sent, 1, DAY, now), Some(3600));
+        assert_eq!(retry_after(&sent, 2, DAY, now), Some(22 * 3600));
+
+        // More transactions than the limit allows at all
+        assert_eq!(retry_after(&sent, 3, DAY, now), None);
+    }
+
+    #[test]
+    fn test_signing_windows_count_the_recent_transactions() {
+        let now = Utc::now();
+
+        let sent = [
+            sent_at(now - Duration::hours(5)),
+            sent_at(now - Duration::minutes(50)),
+            sent_at(now - Duration::minutes(10)),
+        ];
+
+        assert_eq!(within(&sent, HOUR, now).len(), 2);
+        assert_eq!(within(&sent, DAY, now).len(), 3);
+
+        // The hourly limit frees up once the transaction of 50 minutes ago is an hour old
+        assert_eq!(
+            retry_after(within(&sent, HOUR, now), 1, HOUR, now),
+            Some(600)
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_quotas_apply_the_user_overrides() {
+        use crate::db::models::{
+            Chain, TransactionActiveModel, TransactionStatus, UserActiveModel, WalletActiveModel,
+            WalletState,
+        };
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        TransactionRepository::new_with_connection(&db)
+            .create(TransactionActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Confirmed),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let config = QuotaConfig {
+            max_wallets: 2,
+            max_daily_transactions: 2,
+        };
+
+        ensure_wallet_quota(&db, &config, user.id).await.unwrap();
+        ensure_daily_transactions(&db, &config, user.id, 1)
+            .await
+            .unwrap();
+
+        let error = ensure_daily_transactions(&db, &config, user.id, 2)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.problem().status, 429);
+        assert_eq!(error.problem().code, "transaction_limit_exceeded");
+
+        UserLimitRepository::new_with_connection(&db)
+            .upsert(user.id, Some(1), Some(0))
+            .await
+            .unwrap();
+
+        let error = ensure_wallet_quota(&db, &config, user.id)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.problem().status, 403);
+        assert_eq!(error.problem().code, "wallet_limit_exceeded");
+
+        // No limit at all
+        ensure_daily_transactions(&db, &config, user.id, 10)
+            .await
+            .unwrap();
+
+        ensure_signing_velocity(&db, &wallet, 10).await.unwrap();
+
+        let limited = WalletModel {
+            max_signatures_per_hour: Some(2),
+            max_signatures_per_day: Some(5),
+            cosigner: false,
+            ..wallet
+        };
+
+        ensure_signing_velocity(&db, &limited, 1).await.unwrap();
+
+        let error = ensure_signing_velocity(&db, &limited, 2).await.unwrap_err();
+
+        assert_eq!(error.problem().status, 429);
+        assert_eq!(error.problem().code, "signing_limit_exceeded");
+
+        // A batch larger than the limit itself never fits
+        let error = ensure_signing_velocity(&db, &limited, 6).await.unwrap_err();
+
+        assert_eq!(error.problem().status, 403);
+    }
+}
@@ -0,0 +1,267 @@
+use super::error::{ApiError, Result};
+use super::wallet::{Annotation, Recipient, TransactionRequest, find_user_wallet};
+use crate::db::models::{
+    Chain, RecurringPaymentActiveModel, RecurringPaymentModel, RecurringPaymentState, WalletModel,
+    WalletOperation,
+};
+use crate::db::repositories::{AuditRepository, RecurringPaymentRepository, WalletRepository};
+use crate::jobs::{next_run, parse_schedule};
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::U256;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, Set};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct RecurringPaymentRequest {
+    /// Hex address or ENS name on EVM chains, base58 account on Solana
+    pub to: String,
+    /// Amount of every run in the smallest unit of the chain
+    pub value: U256,
+    /// Five field cron expression in UTC, e.g. "0 9 1 * *"
+    pub schedule: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/{id}/recurring")
+            .route(web::get().to(list_recurring_payments))
+            .route(web::post().to(create_recurring_payment)),
+    )
+    .service(
+        web::resource("/{id}/recurring/{payment_id}")
+            .route(web::delete().to(delete_recurring_payment)),
+    )
+    .service(
+        web::resource("/{id}/recurring/{payment_id}/pause")
+            .route(web::post().to(pause_recurring_payment)),
+    )
+    .service(
+        web::resource("/{id}/recurring/{payment_id}/resume")
+            .route(web::post().to(resume_recurring_payment)),
+    );
+}
+
+/// Wallet of the user and its recurring payment named in the path
+async fn find_user_payment(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    path: (i32, i32),
+) -> Result<(WalletModel, RecurringPaymentModel)> {
+    let user_id = request_user_id(req)?;
+    let (wallet_id, payment_id) = path;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(db),
+        wallet_id,
+        user_id,
+    )
+    .await?;
+
+    let payment = RecurringPaymentRepository::new_with_connection(db)
+        .find_by_id(payment_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the recurring payment"))?;
+
+    match payment {
+        Some(payment) if payment.wallet_id == wallet.id => Ok((wallet, payment)),
+        _ => Err(ApiError::not_found(
+            "recurring_payment_not_found",
+            "Recurring payment not found",
+        )),
+    }
+}
+
+async fn audit(db: &DatabaseConnection, action: &str, payment: &RecurringPaymentModel) {
+    let audit = AuditRepository::new_with_connection(db)
+        .record(
+            &format!("user:{}", payment.user_id),
+            action,
+            "wallet",
+            Some(payment.wallet_id.to_string()),
+            Some(serde_json::json!({
+                "recurring_payment_id": payment.id,
+                "recipient": payment.recipient,
+                "value": payment.value,
+                "schedule": payment.schedule,
+            })),
+        )
+        .await;
+
+    if let Err(err) = audit {
+        log::error!(
+            "Failed to audit {action} of recurring payment {}: {err}",
+            payment.id
+        );
+    }
+}
+
+pub async fn create_recurring_payment(
+    req: HttpRequest,
+    data: web::Json<RecurringPaymentRequest>,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    // Runs are sent through the same path as `/tx`, which has no Bitcoin transfers
+    if wallet.chain == Chain::Bitcoin {
+        return Err(ApiError::unprocessable(
+            "recurring_unsupported",
+            "Recurring payments are only supported on EVM and Solana wallets",
+        ));
+    }
+
+    let recipient = Recipient::try_from(data.to.clone()).map_err(ApiError::bad_request)?;
+
+    match (&recipient, wallet.chain == Chain::Solana) {
+        (Recipient::Pubkey(_), true) | (Recipient::Address(_) | Recipient::Name(_), false) => {}
+        _ => {
+            return Err(ApiError::bad_request(
+                "Recipient doesn't match the chain of the wallet",
+            ));
+        }
+    }
+
+    if data.value.is_zero() {
+        return Err(ApiError::bad_request("Value must be greater than zero"));
+    }
+
+    let schedule =
+        parse_schedule(&data.schedule).map_err(|err| ApiError::bad_request(err.to_string()))?;
+
+    let next_run_at =
+        next_run(&schedule, Utc::now()).map_err(|err| ApiError::bad_request(err.to_string()))?;
+
+    let annotation = Annotation::from_request(&TransactionRequest {
+        to: Some(recipient),
+        value: data.value,
+        data: None,
+        memo: data.memo.clone(),
+        metadata: None,
+    })?;
+
+    let payment = RecurringPaymentRepository::new_with_connection(&db)
+        .create(RecurringPaymentActiveModel {
+            user_id: Set(user_id),
+            wallet_id: Set(wallet.id),
+            recipient: Set(data.to.trim().to_string()),
+            value: Set(data.value.to_string()),
+            schedule: Set(data
+                .schedule
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")),
+            memo: Set(annotation.memo),
+            state: Set(RecurringPaymentState::Active),
+            next_run_at: Set(Some(next_run_at)),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to create the recurring payment"))?;
+
+    audit(&db, "wallet.recurring_payment_created", &payment).await;
+
+    Ok(HttpResponse::Created().json(payment))
+}
+
+pub async fn list_recurring_payments(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    let payments = RecurringPaymentRepository::new_with_connection(&db)
+        .find_by_wallet_id(wallet.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the recurring payments"))?;
+
+    Ok(HttpResponse::Ok().json(payments))
+}
+
+pub async fn pause_recurring_payment(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (_, payment) = find_user_payment(&req, &db, path.into_inner()).await?;
+
+    if payment.state == RecurringPaymentState::Paused {
+        return Ok(HttpResponse::Ok().json(payment));
+    }
+
+    let payment = RecurringPaymentRepository::new_with_connection(&db)
+        .pause(&payment)
+        .await
+        .map_err(|_| ApiError::internal("Failed to pause the recurring payment"))?;
+
+    audit(&db, "wallet.recurring_payment_paused", &payment).await;
+
+    Ok(HttpResponse::Ok().json(payment))
+}
+
+/// Resumes the payment from the next occurrence of its schedule, the runs missed while it
+/// was paused aren't sent
+pub async fn resume_recurring_payment(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (wallet, payment) = find_user_payment(&req, &db, path.into_inner()).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    if payment.state == RecurringPaymentState::Active {
+        return Ok(HttpResponse::Ok().json(payment));
+    }
+
+    let next_run_at = parse_schedule(&payment.schedule)
+        .and_then(|schedule| next_run(&schedule, Utc::now()))
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+
+    let payment = RecurringPaymentRepository::new_with_connection(&db)
+        .resume(&payment, next_run_at)
+        .await
+        .map_err(|_| ApiError::internal("Failed to resume the recurring payment"))?;
+
+    audit(&db, "wallet.recurring_payment_resumed", &payment).await;
+
+    Ok(HttpResponse::Ok().json(payment))
+}
+
+pub async fn delete_recurring_payment(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (_, payment) = find_user_payment(&req, &db, path.into_inner()).await?;
+
+    RecurringPaymentRepository::new_with_connection(&db)
+        .delete(payment.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete the recurring payment"))?;
+
+    audit(&db, "wallet.recurring_payment_deleted", &payment).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
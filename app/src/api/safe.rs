@@ -0,0 +1,318 @@
+use super::error::{ApiError, Result};
+use super::quotas::ensure_signing_velocity;
+use super::wallet::{
+    SendFailure, ensure_whitelisted, fail_transaction, find_user_wallet, sign_owner_digest,
+};
+use crate::chains::{ChainEntry, ChainRegistry, SafeTransaction, safe_nonce};
+use crate::db::models::{TransactionModel, TransactionStatus, WalletModel, WalletOperation};
+use crate::db::repositories::{StatusDetails, TransactionRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, B256, Bytes, U256};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+/// Transaction of a Safe the wallet is an owner of
+#[derive(Deserialize)]
+pub struct SafeTransactionRequest {
+    pub to: Address,
+    #[serde(default)]
+    pub value: U256,
+    #[serde(default)]
+    pub data: Bytes,
+    /// 0 for a call, 1 for a delegatecall
+    #[serde(default)]
+    pub operation: u8,
+    #[serde(default)]
+    pub safe_tx_gas: U256,
+    #[serde(default)]
+    pub base_gas: U256,
+    #[serde(default)]
+    pub gas_price: U256,
+    #[serde(default)]
+    pub gas_token: Address,
+    #[serde(default)]
+    pub refund_receiver: Address,
+    /// Current nonce of the Safe when omitted
+    pub nonce: Option<U256>,
+}
+
+#[derive(Serialize)]
+pub struct SafeSignatureResponse {
+    pub id: i32,
+    pub safe_tx_hash: B256,
+    pub signature: Bytes,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/{id}/safe/{safe}/transactions").route(web::post().to(propose_safe_tx)),
+    )
+    .service(
+        web::resource("/{id}/safe/{safe}/transactions/{safe_tx_hash}/confirm")
+            .route(web::post().to(confirm_safe_tx)),
+    );
+}
+
+/// Wallet and network of a Safe owner, with the owner address the Safe knows it by
+async fn safe_owner<'a>(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    chains: &'a ChainRegistry,
+    wallet_id: i32,
+) -> Result<(WalletModel, &'a ChainEntry, Address)> {
+    let user_id = request_user_id(req)?;
+
+    let wallet_repository = WalletRepository::new_with_connection(db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(db, &wallet, 1).await?;
+
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::bad_request(
+            "Safes are only supported on EVM chains",
+        ));
+    }
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    if network.safe.is_none() {
+        return Err(ApiError::bad_request(
+            "No Safe transaction service configured for this chain",
+        ));
+    }
+
+    let owner = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to sign for"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    Ok((wallet, network, owner))
+}
+
+/// Applies the wallet policy to the Safe transaction before any owner signature
+async fn ensure_safe_allowed(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    transaction: &SafeTransaction,
+) -> Result<()> {
+    match transaction.operation {
+        0 => {}
+        // The target runs in the Safe's context, the address book can't vouch for it
+        1 if wallet.whitelist_only => {
+            return Err(ApiError::forbidden(
+                "Delegate calls are not allowed on whitelist-only wallets",
+            ));
+        }
+        1 => {}
+        _ => return Err(ApiError::bad_request("Invalid Safe operation")),
+    }
+
+    ensure_whitelisted(db, wallet, [transaction.to.to_string()]).await
+}
+
+/// Signs the Safe transaction hash with the wallet as one of the owners, the transaction is
+/// recorded as `signed` since the Safe executes it once enough owners confirmed
+async fn sign_safe_transaction(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction: &SafeTransaction,
+) -> Result<(TransactionModel, B256, Vec<u8>), SendFailure> {
+    let safe_tx_hash = transaction.hash(network.config.chain_id);
+
+    let (transaction_model, signature) = sign_owner_digest(
+        db,
+        participants,
+        screener,
+        wallet,
+        network,
+        Some((transaction.to, transaction.value)),
+        safe_tx_hash,
+    )
+    .await?;
+
+    let transaction_model = TransactionRepository::new_with_connection(db)
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signed,
+            StatusDetails {
+                tx_hash: Some(safe_tx_hash.to_string()),
+                raw_tx: Some(format!("0x{}", hex::encode(&signature))),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    Ok((transaction_model, safe_tx_hash, signature))
+}
+
+/// Proposes a transaction to the Safe transaction service with the wallet's signature
+pub async fn propose_safe_tx(
+    req: HttpRequest,
+    data: web::Json<SafeTransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<(i32, Address)>,
+) -> Result<HttpResponse> {
+    let (wallet_id, safe) = path.into_inner();
+
+    let (wallet, network, owner) = safe_owner(&req, &db, &chains, wallet_id).await?;
+
+    let nonce = match data.nonce {
+        Some(nonce) => nonce,
+        None => {
+            let provider = network
+                .provider
+                .as_ref()
+                .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+            safe_nonce(provider.as_ref(), safe).await.map_err(|err| {
+                log::error!("{err}");
+                ApiError::internal("Failed to fetch the Safe nonce")
+            })?
+        }
+    };
+
+    let transaction = SafeTransaction {
+        safe,
+        to: data.to,
+        value: data.value,
+        data: data.data.clone(),
+        operation: data.operation,
+        safe_tx_gas: data.safe_tx_gas,
+        base_gas: data.base_gas,
+        gas_price: data.gas_price,
+        gas_token: data.gas_token,
+        refund_receiver: data.refund_receiver,
+        nonce,
+    };
+
+    ensure_safe_allowed(&db, &wallet, &transaction).await?;
+
+    let signed = sign_safe_transaction(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        &transaction,
+    )
+    .await;
+
+    let (transaction_model, safe_tx_hash, signature) = match signed {
+        Ok(signed) => signed,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    let service = network
+        .safe
+        .as_ref()
+        .ok_or_else(|| ApiError::internal("Safe transaction service not configured"))?;
+
+    if let Err(err) = service
+        .propose(&transaction, safe_tx_hash, owner, &signature)
+        .await
+    {
+        log::error!("{err}");
+        fail_transaction(
+            &TransactionRepository::new_with_connection(&db),
+            &transaction_model,
+            &err.to_string(),
+        )
+        .await;
+        return Err(ApiError::internal("Failed to propose Safe transaction"));
+    }
+
+    Ok(HttpResponse::Ok().json(SafeSignatureResponse {
+        id: transaction_model.id,
+        safe_tx_hash,
+        signature: signature.into(),
+    }))
+}
+
+/// Confirms a transaction another owner proposed, the hash is recomputed from the
+/// transaction the service returns so the signature covers what was screened
+pub async fn confirm_safe_tx(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<(i32, Address, B256)>,
+) -> Result<HttpResponse> {
+    let (wallet_id, safe, safe_tx_hash) = path.into_inner();
+
+    let (wallet, network, _) = safe_owner(&req, &db, &chains, wallet_id).await?;
+
+    let service = network
+        .safe
+        .as_ref()
+        .ok_or_else(|| ApiError::internal("Safe transaction service not configured"))?;
+
+    let transaction = service
+        .transaction(safe_tx_hash)
+        .await
+        .map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to fetch Safe transaction")
+        })?
+        .filter(|transaction| transaction.safe == safe)
+        .ok_or_else(|| {
+            ApiError::not_found("safe_transaction_not_found", "Safe transaction not found")
+        })?;
+
+    if transaction.hash(network.config.chain_id) != safe_tx_hash {
+        return Err(ApiError::internal(
+            "Safe transaction does not match its hash",
+        ));
+    }
+
+    ensure_safe_allowed(&db, &wallet, &transaction).await?;
+
+    let signed = sign_safe_transaction(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        &transaction,
+    )
+    .await;
+
+    let (transaction_model, safe_tx_hash, signature) = match signed {
+        Ok(signed) => signed,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    if let Err(err) = service.confirm(safe_tx_hash, &signature).await {
+        log::error!("{err}");
+        fail_transaction(
+            &TransactionRepository::new_with_connection(&db),
+            &transaction_model,
+            &err.to_string(),
+        )
+        .await;
+        return Err(ApiError::internal("Failed to confirm Safe transaction"));
+    }
+
+    Ok(HttpResponse::Ok().json(SafeSignatureResponse {
+        id: transaction_model.id,
+        safe_tx_hash,
+        signature: signature.into(),
+    }))
+}
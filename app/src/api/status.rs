@@ -0,0 +1,193 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Utc};
+use futures::lock::Mutex;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::health::{HealthChecker, HealthReport, HealthStatus};
+
+// Threshold equal to 2 participants for now
+static SIGNING_THRESHOLD: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Operational,
+    Degraded,
+    Outage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusComponents {
+    pub api: ComponentStatus,
+    pub signing: ComponentStatus,
+    pub broadcasts: ComponentStatus,
+}
+
+/// Public summary of the service health, without any internal topology
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusPage {
+    pub status: ComponentStatus,
+    pub components: StatusComponents,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&HealthReport> for StatusPage {
+    fn from(report: &HealthReport) -> Self {
+        let api = match report.database {
+            HealthStatus::Up => ComponentStatus::Operational,
+            HealthStatus::Down => ComponentStatus::Outage,
+        };
+
+        let participants_up = report.participants_up();
+        // Standbys stand in for the participants that are down, assuming they copy those
+        let signers_up = (participants_up + report.standbys_up()).min(report.participants.len());
+        let signing = if api == ComponentStatus::Outage || signers_up < SIGNING_THRESHOLD {
+            ComponentStatus::Outage
+        } else if participants_up < report.participants.len() {
+            ComponentStatus::Degraded
+        } else {
+            ComponentStatus::Operational
+        };
+
+        let broadcasts = match report.provider {
+            HealthStatus::Up => ComponentStatus::Operational,
+            HealthStatus::Down => ComponentStatus::Outage,
+        };
+
+        let components = [api, signing, broadcasts];
+        let status = if components
+            .iter()
+            .all(|c| *c == ComponentStatus::Operational)
+        {
+            ComponentStatus::Operational
+        } else if components.iter().all(|c| *c == ComponentStatus::Outage) {
+            ComponentStatus::Outage
+        } else {
+            ComponentStatus::Degraded
+        };
+
+        StatusPage {
+            status,
+            components: StatusComponents {
+                api,
+                signing,
+                broadcasts,
+            },
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Serves the status page from a cache so public traffic never fans out to the dependencies
+pub struct StatusService {
+    checker: HealthChecker,
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, StatusPage)>>,
+}
+
+impl StatusService {
+    pub fn new(checker: HealthChecker, ttl: Duration) -> Self {
+        Self {
+            checker,
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn status(&self) -> StatusPage {
+        // Holding the lock while refreshing lets concurrent requests share a single check
+        let mut cached = self.cached.lock().await;
+
+        if let Some((checked_at, page)) = cached.as_ref()
+            && checked_at.elapsed() < self.ttl
+        {
+            return page.clone();
+        }
+
+        let page = StatusPage::from(&self.checker.check().await);
+        *cached = Some((Instant::now(), page.clone()));
+
+        page
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+pub async fn status(service: web::Data<StatusService>) -> HttpResponse {
+    let page = service.status().await;
+
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(service.ttl().as_secs() as u32),
+        ]))
+        .json(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(database: HealthStatus, provider: HealthStatus, up: usize) -> HealthReport {
+        HealthReport {
+            database,
+            provider,
+            participants: (0..3)
+                .map(|i| {
+                    if i < up {
+                        HealthStatus::Up
+                    } else {
+                        HealthStatus::Down
+                    }
+                })
+                .collect(),
+            standbys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_all_up_is_operational() {
+        let page = StatusPage::from(&report(HealthStatus::Up, HealthStatus::Up, 3));
+
+        assert_eq!(page.status, ComponentStatus::Operational);
+        assert_eq!(page.components.signing, ComponentStatus::Operational);
+    }
+
+    #[test]
+    fn test_signing_degraded_above_threshold() {
+        let page = StatusPage::from(&report(HealthStatus::Up, HealthStatus::Up, 2));
+
+        assert_eq!(page.status, ComponentStatus::Degraded);
+        assert_eq!(page.components.signing, ComponentStatus::Degraded);
+    }
+
+    #[test]
+    fn test_signing_outage_below_threshold() {
+        let page = StatusPage::from(&report(HealthStatus::Up, HealthStatus::Down, 1));
+
+        assert_eq!(page.components.signing, ComponentStatus::Outage);
+        assert_eq!(page.components.broadcasts, ComponentStatus::Outage);
+        assert_eq!(page.status, ComponentStatus::Degraded);
+    }
+
+    #[test]
+    fn test_standby_keeps_signing_up() {
+        let mut report = report(HealthStatus::Up, HealthStatus::Up, 1);
+        report.standbys = vec![HealthStatus::Up, HealthStatus::Down];
+
+        let page = StatusPage::from(&report);
+
+        assert_eq!(page.components.signing, ComponentStatus::Degraded);
+    }
+
+    #[test]
+    fn test_database_down_is_outage() {
+        let page = StatusPage::from(&report(HealthStatus::Down, HealthStatus::Down, 3));
+
+        assert_eq!(page.status, ComponentStatus::Outage);
+    }
+}
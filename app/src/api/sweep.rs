@@ -0,0 +1,241 @@
+use super::error::{ApiError, Result};
+use super::quotas::{ensure_signing_velocity, ensure_transaction_quota};
+use super::wallet::{
+    Recipient, TransactionRequest, TransactionResponse, find_user_wallet, send_evm_tx,
+    send_solana_tx, transfer_fee,
+};
+use super::withdrawals::{hold_withdrawal, withdrawal_release_at};
+use crate::chains::{ChainEntry, ChainRegistry, LAMPORTS_PER_SIGNATURE};
+use crate::db::models::{Chain, WalletModel, WalletOperation, WithdrawalModel};
+use crate::db::repositories::WalletRepository;
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct SweepRequest {
+    /// Hex address or ENS name on EVM chains, base58 account on Solana
+    pub to: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Balance of the wallet split into the fee of the sweep and the value it sends, decimal
+/// strings in the smallest unit of the chain
+#[derive(Serialize)]
+pub struct SweepResponse {
+    pub balance: String,
+    pub fee: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<TransactionResponse>,
+    /// Set instead of the transaction when the withdrawal delay of the wallet holds the sweep
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawal: Option<WithdrawalModel>,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/sweep").route(web::post().to(sweep_wallet)));
+}
+
+/// Value of a sweep of `balance` once `fee` is paid, there is nothing to sweep when the
+/// balance doesn't cover more than the fee
+fn sweep_value(balance: U256, fee: U256) -> Result<U256> {
+    balance
+        .checked_sub(fee)
+        .filter(|value| !value.is_zero())
+        .ok_or_else(|| {
+            ApiError::unprocessable(
+                "insufficient_funds",
+                "The balance of the wallet doesn't cover the fee of a sweep",
+            )
+            .with("balance", balance.to_string())
+            .with("fee", fee.to_string())
+        })
+}
+
+/// Native balance and transfer fee of an EVM wallet. The balance is the one of the latest
+/// block, transactions still pending spend from it too
+async fn evm_balance_and_fee(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    to: &Recipient,
+) -> Result<(U256, U256)> {
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let address: Address = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to sweep"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    let balance = provider.get_balance(address).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to read the wallet balance")
+    })?;
+
+    // The L1 data fee of rollups is priced on a transaction sending the whole balance, the
+    // value actually sent is no larger
+    let fee = transfer_fee(wallet, network, to, balance).await?;
+
+    Ok((balance, fee))
+}
+
+/// Balance in lamports of a Solana wallet and the fee of its single signature transfer
+async fn solana_balance_and_fee(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+) -> Result<(U256, U256)> {
+    let client = network
+        .solana
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let address = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to sweep"))?;
+
+    let balance = client.balance(address).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to read the wallet balance")
+    })?;
+
+    Ok((U256::from(balance), U256::from(LAMPORTS_PER_SIGNATURE)))
+}
+
+/// Sends the whole native balance of the wallet, less the fee of the transfer, to `to`.
+/// Subject to the same state, quota, signing limit, delay, policy and screening checks as
+/// `send_tx`
+pub async fn sweep_wallet(
+    req: HttpRequest,
+    data: web::Json<SweepRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_transaction_quota(&req, &db, user_id, 1).await?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let to = Recipient::try_from(data.to.clone()).map_err(ApiError::bad_request)?;
+
+    let (balance, fee) = match wallet.chain {
+        Chain::Solana => solana_balance_and_fee(&wallet, network).await?,
+        // A Bitcoin sweep spends every UTXO of the address into one output, its fee depends
+        // on how many there are and the app doesn't track the UTXO set yet
+        Chain::Bitcoin => {
+            return Err(ApiError::unprocessable(
+                "sweep_unsupported",
+                "Sweeps are only supported on EVM and Solana wallets",
+            ));
+        }
+        _ => evm_balance_and_fee(&wallet, network, &to).await?,
+    };
+
+    let value = sweep_value(balance, fee)?;
+
+    let request = TransactionRequest {
+        to: Some(to),
+        value,
+        data: None,
+        memo: data.memo.clone(),
+        metadata: data.metadata.clone(),
+    };
+
+    let mut response = SweepResponse {
+        balance: balance.to_string(),
+        fee: fee.to_string(),
+        value: value.to_string(),
+        transaction: None,
+        withdrawal: None,
+    };
+
+    if let Some(release_at) = withdrawal_release_at(&wallet, value) {
+        response.withdrawal =
+            Some(hold_withdrawal(&db, &wallet, None, &request, release_at).await?);
+
+        return Ok(HttpResponse::Accepted().json(response));
+    }
+
+    let transaction = match wallet.chain {
+        Chain::Solana => {
+            send_solana_tx(
+                &db,
+                participants.get_ref(),
+                &screener,
+                &wallet,
+                network,
+                &request,
+            )
+            .await?
+        }
+        _ => {
+            send_evm_tx(
+                &db,
+                participants.get_ref(),
+                &screener,
+                &wallet,
+                None,
+                network,
+                &request,
+            )
+            .await?
+        }
+    };
+
+    response.transaction = Some(transaction);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_sends_the_balance_less_the_fee() {
+        let fee = U256::from(21_000_000_000_000u64);
+
+        assert_eq!(
+            sweep_value(U256::from(10u64.pow(18)), fee).unwrap(),
+            U256::from(999_979_000_000_000_000u64)
+        );
+
+        // Nothing is left once the fee is paid
+        let error = sweep_value(fee, fee).unwrap_err();
+
+        assert_eq!(error.problem().status, 422);
+        assert_eq!(error.problem().code, "insufficient_funds");
+
+        assert!(sweep_value(U256::ZERO, fee).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use super::error::{ApiError, Result};
+use super::quotas::ensure_signing_velocity;
+use super::wallet::{
+    Annotation, TokenCall, Transfer, ensure_transfers_allowed, estimate_gas_limit,
+    find_user_wallet, send_contract_call,
+};
+use crate::chains::{
+    ChainEntry, ChainRegistry, ProviderPool, TokenBalance, allowance, approve_call_data,
+    token_balance,
+};
+use crate::db::models::{WalletModel, WalletOperation};
+use crate::db::repositories::{AddressRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+use crate::prices::{Asset, FiatValue, Prices};
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, U256};
+use alloy::rpc::types::{TransactionInput, TransactionRequest as CallRequest};
+use futures::future::join_all;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct AllowanceQuery {
+    pub token: Address,
+}
+
+#[derive(Deserialize)]
+pub struct AllowanceRequest {
+    pub token: Address,
+    pub spender: Address,
+    pub amount: U256,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeAllowanceRequest {
+    pub token: Address,
+    pub spender: Address,
+}
+
+#[derive(Serialize)]
+pub struct AllowanceResponse {
+    pub spender: Address,
+    /// Name of the spender in the address book
+    pub name: String,
+    pub allowance: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenBalanceResponse {
+    pub token: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    /// Balance in the smallest unit of the token
+    pub balance: String,
+    /// Balance in whole tokens
+    pub formatted: String,
+    /// Value of the balance, when the price feed quotes the token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
+}
+
+impl From<TokenBalance> for TokenBalanceResponse {
+    fn from(balance: TokenBalance) -> Self {
+        Self {
+            token: balance.token,
+            formatted: balance.formatted(),
+            balance: balance.balance.to_string(),
+            symbol: balance.symbol,
+            decimals: balance.decimals,
+            fiat: None,
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/tokens").route(web::get().to(list_tokens)))
+        .service(
+            web::resource("/{id}/allowances")
+                .route(web::get().to(list_allowances))
+                .route(web::put().to(set_allowance)),
+        )
+        .service(
+            web::resource("/{id}/allowances/increase").route(web::post().to(increase_allowance)),
+        )
+        .service(web::resource("/{id}/allowances/revoke").route(web::post().to(revoke_allowance)));
+}
+
+/// Wallet and network of an ERC-20 holder, with the address its allowances are granted from
+async fn token_owner<'a>(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+    chains: &'a ChainRegistry,
+    wallet_id: i32,
+) -> Result<(WalletModel, &'a ChainEntry, Address)> {
+    let user_id = request_user_id(req)?;
+
+    let wallet_repository = WalletRepository::new_with_connection(db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::bad_request(
+            "Tokens are only supported on EVM chains",
+        ));
+    }
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let owner = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to hold tokens"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    Ok((wallet, network, owner))
+}
+
+fn token_provider(network: &ChainEntry) -> Result<&ProviderPool> {
+    network
+        .provider
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))
+}
+
+/// `approve` transaction replacing the allowance of the spender, checked against the policy
+async fn approval(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    owner: Address,
+    token: Address,
+    spender: Address,
+    amount: U256,
+) -> Result<Transfer> {
+    let call_data = approve_call_data(spender, amount);
+
+    let call = CallRequest {
+        from: Some(owner),
+        to: Some(token.into()),
+        input: TransactionInput::new(call_data.clone()),
+        ..Default::default()
+    };
+
+    let transfer = Transfer {
+        to: Some(token),
+        value: U256::ZERO,
+        data: call_data,
+        gas_limit: estimate_gas_limit(token_provider(network)?, call).await?,
+        ens_name: None,
+        token: Some(TokenCall {
+            recipient: spender,
+            token_id: None,
+        }),
+        annotation: Annotation::default(),
+    };
+
+    // Revoking only lowers the exposure, the spender needn't be in the address book
+    if !amount.is_zero() {
+        ensure_transfers_allowed(db, wallet, std::slice::from_ref(&transfer)).await?;
+    }
+
+    Ok(transfer)
+}
+
+/// Balances of the wallet in the ERC-20 tokens configured for its chain
+pub async fn list_tokens(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    prices: web::Data<Prices>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let (wallet, network, owner) = token_owner(&req, &db, &chains, path.into_inner()).await?;
+
+    let provider = token_provider(network)?;
+
+    let assets: Vec<Asset> = network
+        .tokens
+        .iter()
+        .map(|token| Asset::Token(wallet.chain.clone(), *token))
+        .collect();
+
+    let quotes = prices.prices(&assets).await;
+
+    let balances = join_all(
+        network
+            .tokens
+            .iter()
+            .map(|token| token_balance(provider, *token, owner)),
+    )
+    .await;
+
+    let mut response = Vec::new();
+
+    for balance in balances {
+        let balance = balance.map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to read token balance")
+        })?;
+
+        let price = quotes
+            .get(&Asset::Token(wallet.chain.clone(), balance.token))
+            .copied();
+        let fiat = prices.value(price, balance.balance, balance.decimals);
+
+        response.push(TokenBalanceResponse {
+            fiat,
+            ..TokenBalanceResponse::from(balance)
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Current ERC-20 allowances of the wallet to the spenders saved in the address book
+pub async fn list_allowances(
+    req: HttpRequest,
+    query: web::Query<AllowanceQuery>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let (wallet, network, owner) = token_owner(&req, &db, &chains, path.into_inner()).await?;
+
+    let provider = token_provider(network)?;
+
+    let spenders = AddressRepository::new(&db)
+        .find_by_user_id(wallet.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load the address book"))?
+        .into_iter()
+        .filter(|entry| entry.chain == wallet.chain)
+        .filter_map(|entry| Some((entry.address.parse::<Address>().ok()?, entry.name)))
+        .collect::<Vec<_>>();
+
+    let allowances = join_all(
+        spenders
+            .iter()
+            .map(|(spender, _)| allowance(provider, query.token, owner, *spender)),
+    )
+    .await;
+
+    let mut response = Vec::new();
+
+    for ((spender, name), allowance) in spenders.into_iter().zip(allowances) {
+        let allowance = allowance.map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to read allowance")
+        })?;
+
+        response.push(AllowanceResponse {
+            spender,
+            name,
+            allowance: allowance.to_string(),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Sets the allowance of the spender to the amount, whatever it was before
+pub async fn set_allowance(
+    req: HttpRequest,
+    data: web::Json<AllowanceRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let (wallet, network, owner) = token_owner(&req, &db, &chains, path.into_inner()).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let transfer = approval(
+        &db,
+        &wallet,
+        network,
+        owner,
+        data.token,
+        data.spender,
+        data.amount,
+    )
+    .await?;
+
+    send_contract_call(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        transfer,
+    )
+    .await
+}
+
+/// Raises the allowance of the spender by the amount, read from the token first since
+/// ERC-20 only defines `approve`
+pub async fn increase_allowance(
+    req: HttpRequest,
+    data: web::Json<AllowanceRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let (wallet, network, owner) = token_owner(&req, &db, &chains, path.into_inner()).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let current = allowance(token_provider(network)?, data.token, owner, data.spender)
+        .await
+        .map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to read allowance")
+        })?;
+
+    let amount = current
+        .checked_add(data.amount)
+        .ok_or_else(|| ApiError::bad_request("Allowance would exceed the uint256 range"))?;
+
+    let transfer = approval(
+        &db,
+        &wallet,
+        network,
+        owner,
+        data.token,
+        data.spender,
+        amount,
+    )
+    .await?;
+
+    send_contract_call(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        transfer,
+    )
+    .await
+}
+
+/// Sets the allowance of the spender back to zero
+pub async fn revoke_allowance(
+    req: HttpRequest,
+    data: web::Json<RevokeAllowanceRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let (wallet, network, owner) = token_owner(&req, &db, &chains, path.into_inner()).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let transfer = approval(
+        &db,
+        &wallet,
+        network,
+        owner,
+        data.token,
+        data.spender,
+        U256::ZERO,
+    )
+    .await?;
+
+    send_contract_call(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        transfer,
+    )
+    .await
+}
@@ -0,0 +1,213 @@
+use super::error::{ApiError, Result};
+use super::quotas::ensure_signing_velocity;
+use super::wallet::{ensure_whitelisted, fail_transaction, find_user_wallet, sign_owner_digest};
+use crate::chains::{ChainRegistry, ENTRY_POINT, UserOperation, account_nonce};
+use crate::db::models::{TransactionStatus, WalletOperation};
+use crate::db::repositories::{StatusDetails, TransactionRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::{Address, B256, Bytes, U256, eip191_hash_message};
+use alloy::providers::Provider;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+/// ERC-4337 v0.7 user operation of a smart account owned by the wallet
+#[derive(Deserialize)]
+pub struct UserOperationRequest {
+    pub sender: Address,
+    /// Next EntryPoint nonce of the account when omitted
+    pub nonce: Option<U256>,
+    pub factory: Option<Address>,
+    pub factory_data: Option<Bytes>,
+    pub call_data: Bytes,
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+    /// Current network fees when omitted
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub paymaster: Option<Address>,
+    pub paymaster_verification_gas_limit: Option<U256>,
+    pub paymaster_post_op_gas_limit: Option<U256>,
+    pub paymaster_data: Option<Bytes>,
+    /// Submits the signed operation to the chain's bundler
+    #[serde(default)]
+    pub submit: bool,
+}
+
+#[derive(Serialize)]
+pub struct UserOperationResponse {
+    pub id: i32,
+    pub user_op_hash: B256,
+    pub user_operation: UserOperation,
+    pub submitted: bool,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/userop").route(web::post().to(sign_user_operation)));
+}
+
+/// Signs the user operation hash with the wallet as the owner of the smart account, the
+/// signature is the 65-byte `r || s || v` over its EIP-191 message hash that
+/// `SimpleAccount`-style accounts recover
+///
+/// Submitted operations stay `signed`, the bundler tracks their inclusion.
+pub async fn sign_user_operation(
+    req: HttpRequest,
+    data: web::Json<UserOperationRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let wallet_id = path.into_inner();
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::bad_request(
+            "User operations are only supported on EVM chains",
+        ));
+    }
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let bundler = match (&network.bundler, data.submit) {
+        (Some(bundler), true) => Some(bundler),
+        (None, true) => {
+            return Err(ApiError::bad_request(
+                "No bundler configured for this chain",
+            ));
+        }
+        (_, false) => None,
+    };
+
+    let nonce = match data.nonce {
+        Some(nonce) => nonce,
+        None => account_nonce(provider.as_ref(), ENTRY_POINT, data.sender)
+            .await
+            .map_err(|err| {
+                log::error!("{err}");
+                ApiError::internal("Failed to fetch the account nonce")
+            })?,
+    };
+
+    let (max_fee_per_gas, max_priority_fee_per_gas) =
+        match (data.max_fee_per_gas, data.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(priority_fee)) => (max_fee, priority_fee),
+            (max_fee, priority_fee) => {
+                let fees = provider.estimate_eip1559_fees().await.map_err(|err| {
+                    log::error!("{err}");
+                    ApiError::internal("Failed to estimate fees")
+                })?;
+
+                (
+                    max_fee.unwrap_or(U256::from(fees.max_fee_per_gas)),
+                    priority_fee.unwrap_or(U256::from(fees.max_priority_fee_per_gas)),
+                )
+            }
+        };
+
+    let mut operation = UserOperation {
+        sender: data.sender,
+        nonce,
+        factory: data.factory,
+        factory_data: data.factory_data.clone(),
+        call_data: data.call_data.clone(),
+        call_gas_limit: data.call_gas_limit,
+        verification_gas_limit: data.verification_gas_limit,
+        pre_verification_gas: data.pre_verification_gas,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        paymaster: data.paymaster,
+        paymaster_verification_gas_limit: data.paymaster_verification_gas_limit,
+        paymaster_post_op_gas_limit: data.paymaster_post_op_gas_limit,
+        paymaster_data: data.paymaster_data.clone(),
+        signature: Bytes::new(),
+    };
+
+    let user_op_hash = operation
+        .hash(ENTRY_POINT, network.config.chain_id)
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+
+    // Only `execute` calls name the account's recipient
+    let target = operation.execute_target();
+
+    if target.is_none() && wallet.whitelist_only {
+        return Err(ApiError::forbidden(
+            "Call data of the user operation has no known recipient",
+        ));
+    }
+
+    ensure_whitelisted(&db, &wallet, target.map(|(dest, _)| dest.to_string())).await?;
+
+    let signed = sign_owner_digest(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        network,
+        target,
+        eip191_hash_message(user_op_hash),
+    )
+    .await;
+
+    let (transaction_model, signature) = match signed {
+        Ok(signed) => signed,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    let transaction_repository = TransactionRepository::new_with_connection(&db);
+
+    operation.signature = signature.into();
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signed,
+            StatusDetails {
+                tx_hash: Some(user_op_hash.to_string()),
+                raw_tx: serde_json::to_string(&operation).ok(),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to sign transaction"))?;
+
+    if let Some(bundler) = bundler
+        && let Err(err) = bundler.send_user_operation(&operation, ENTRY_POINT).await
+    {
+        log::error!("{err}");
+        fail_transaction(
+            &transaction_repository,
+            &transaction_model,
+            &err.to_string(),
+        )
+        .await;
+        return Err(ApiError::internal("Failed to submit user operation"));
+    }
+
+    Ok(HttpResponse::Ok().json(UserOperationResponse {
+        id: transaction_model.id,
+        user_op_hash,
+        user_operation: operation,
+        submitted: bundler.is_some(),
+    }))
+}
@@ -1,15 +1,86 @@
-use crate::utils::request::request_user_id;
-use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
-use actix_web::{Error, HttpRequest, HttpResponse, web};
+use super::error::{ApiError, Result};
+use crate::utils::request::{request_session_id, request_user_id};
+use actix_web::{HttpRequest, HttpResponse, web};
+use chrono::Utc;
 use sea_orm::DbConn;
+use serde::Serialize;
 
-use crate::db::repositories::UserRepository;
+use crate::db::models::SessionModel;
+use crate::db::repositories::{AuditRepository, SessionRepository, UserRepository};
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    pub session: SessionModel,
+    /// Whether the request was authenticated with the token of the session
+    pub current: bool,
+}
 
 pub fn configure_protected(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("/{id}").get(get_user).delete(delete_user));
+    cfg.service(web::resource("/me/sessions").get(list_sessions))
+        .service(web::resource("/me/sessions/{id}").delete(revoke_session))
+        .service(web::resource("/{id}").get(get_user).delete(delete_user));
+}
+
+/// Active sessions of the user, the most recently used first
+pub async fn list_sessions(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let current = request_session_id(&req)?;
+
+    let sessions = SessionRepository::new(db.get_ref())
+        .find_active_by_user_id(user_id, Utc::now())
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the sessions"))?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: session.id == current,
+            session,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+/// Revokes a session of the user, its token is refused from the next request on
+pub async fn revoke_session(
+    req: HttpRequest,
+    path: web::Path<String>,
+    db: web::Data<DbConn>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let session_id = path.into_inner();
+
+    let revoked = SessionRepository::new(db.get_ref())
+        .revoke(user_id, &session_id, Utc::now())
+        .await
+        .map_err(|_| ApiError::internal("Failed to revoke the session"))?;
+
+    if !revoked {
+        return Err(ApiError::not_found(
+            "session_not_found",
+            "Session not found",
+        ));
+    }
+
+    if let Err(err) = AuditRepository::new_with_connection(db.get_ref())
+        .record(
+            &format!("user:{user_id}"),
+            "session.revoked",
+            "session",
+            Some(session_id.clone()),
+            None,
+        )
+        .await
+    {
+        log::error!("Failed to audit the revocation of session {session_id}: {err}");
+    }
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
-pub async fn get_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpResponse, Error> {
+pub async fn get_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpResponse> {
     let user_id = request_user_id(&req)?;
 
     let repo = UserRepository::new(db.get_ref());
@@ -17,15 +88,18 @@ pub async fn get_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpRes
     let user = repo
         .find_by_id(user_id)
         .await
-        .map_err(|e| ErrorNotFound(format!("Failed to retrieve user: {}", e)))?;
+        .map_err(|e| ApiError::internal(format!("Failed to retrieve user: {}", e)))?;
 
     match user {
         Some(user) => Ok(HttpResponse::Ok().json(user)),
-        None => Err(ErrorNotFound(format!("User with ID {} not found", user_id))),
+        None => Err(ApiError::not_found(
+            "user_not_found",
+            format!("User with ID {} not found", user_id),
+        )),
     }
 }
 
-pub async fn delete_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpResponse, Error> {
+pub async fn delete_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<HttpResponse> {
     let user_id = request_user_id(&req)?;
 
     let repo = UserRepository::new(db.get_ref());
@@ -33,19 +107,22 @@ pub async fn delete_user(req: HttpRequest, db: web::Data<DbConn>) -> Result<Http
     let user = repo
         .find_by_id(user_id)
         .await
-        .map_err(|err| ErrorInternalServerError(format!("Database error: {err}")))?;
+        .map_err(|err| ApiError::internal(format!("Database error: {err}")))?;
 
     if user.is_none() {
-        return Err(ErrorNotFound(format!("User with ID {user_id} not found")));
+        return Err(ApiError::not_found(
+            "user_not_found",
+            format!("User with ID {user_id} not found"),
+        ));
     }
 
     let res = repo
         .delete(user_id)
         .await
-        .map_err(|err| ErrorInternalServerError(format!("Failed to delete user: {err}")))?;
+        .map_err(|err| ApiError::internal(format!("Failed to delete user: {err}")))?;
 
     match res.rows_affected {
-        0 => Err(ErrorInternalServerError("Failed to delete user")),
+        0 => Err(ApiError::internal("Failed to delete user")),
         _ => Ok(HttpResponse::NoContent().finish()),
     }
 }
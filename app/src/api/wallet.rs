@@ -1,32 +1,306 @@
-use crate::db::models::{Chain, TransactionActiveModel, WalletActiveModel, WalletModel};
-use crate::db::repositories::{TransactionRepository, WalletRepository};
-use crate::utils::request::request_user_id;
-use actix_web::{
-    HttpRequest, HttpResponse, Result,
-    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
-    web,
+use super::accounts::find_wallet_account;
+use super::error::{ApiError, Result};
+use super::operations::finish_operation;
+use super::quotas::{
+    ensure_daily_transactions, ensure_signing_velocity, ensure_transaction_quota,
+    ensure_wallet_quota,
+};
+use super::withdrawals::{ensure_not_delayed, hold_withdrawal, withdrawal_release_at};
+use crate::chains::{
+    ChainEntry, ChainRegistry, Simulation, SolanaClient, encode_base58, extended_public_key,
+    l1_fee, parse_pubkey, quote, resolve_name, signed_transaction, simulate, transfer_message,
+};
+use crate::config::app_config::{
+    CosignerConfig, QuotaConfig, WalletImportConfig, WithdrawalConfig,
+};
+use crate::db::models::{
+    AddressType, Chain, JobKind, MpcFailureActiveModel, OperationKind, TransactionActiveModel,
+    TransactionModel, TransactionStatus, WalletAccountModel, WalletActiveModel, WalletModel,
+    WalletOperation, WalletState, WalletStateError,
 };
-use alloy::primitives::{Address, U256, Uint};
+use crate::db::repositories::{
+    AddressRepository, AuditRepository, JobRepository, MpcFailureRepository, OperationRepository,
+    SignatureDetails, StatusDetails, TransactionRepository, WalletAccountRepository,
+    WalletRepository, WalletTagRepository,
+};
+use crate::jobs::{WalletPurge, enqueue};
+use crate::participants::cosigner::{self, CeremonyMessage};
+use crate::participants::progress::{ParticipantProgress, keygen_progress};
+use crate::participants::{
+    ParticipantPool, RetryPolicy, SIGNING_THRESHOLD, Signer, cosigning_party, error_code,
+    error_detail, extended_key, key_quorum, keygen_address, may_hold_share, rooms, run_import,
+    run_keygen, select_signers, signing_parties, wallet_infos,
+};
+use crate::screening::Screener;
+use crate::siem::{self, Outcome, SecurityEvent};
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::StatusCode, web};
+use alloy::primitives::{Address, B256, Bytes, Signature, TxKind, U256, Uint, keccak256};
 use alloy::providers::Provider;
-use alloy_rlp::{Encodable, RlpDecodable, RlpEncodable};
+use alloy::rpc::types::{TransactionInput, TransactionRequest as CallRequest};
+use alloy::transports::RpcError;
+use alloy_rlp::{Decodable, Encodable, RlpDecodable, RlpEncodable};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use futures::future::join_all;
-use proto::mpc::participant_client::ParticipantClient;
-use proto::mpc::{CreateWalletMessage, DeleteWalletMessage, SignMessage};
-use sea_orm::{DatabaseConnection, Set, TransactionTrait};
+use proto::mpc::{ErrorCode, SignBatchMessage, SignMessage, WalletCreatedMessage};
+use sea_orm::{DatabaseConnection, IntoActiveModel, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
-use tonic::transport::Channel;
+use std::collections::HashMap;
+use tonic::{Code, Status};
 use uuid::Uuid;
+use zeroize::Zeroizing;
+
+/// Transactions accepted by a single batch request
+const MAX_BATCH_SIZE: usize = 100;
 
 #[derive(Deserialize)]
 pub struct CreateWalletRequest {
     pub name: String,
     pub chain: Chain,
+    /// Bitcoin only, defaults to P2WPKH
+    #[serde(default)]
+    pub address_type: Option<AddressType>,
+    /// Shares the key with the user's device instead of the participants, which joins the
+    /// keygen while the request runs
+    #[serde(default)]
+    pub cosigner: bool,
 }
 
 #[derive(Deserialize)]
+pub struct ImportWalletRequest {
+    pub name: String,
+    pub chain: Chain,
+    /// Hex private key of the account, the 32-byte seed on Solana. Keys of a mnemonic are
+    /// derived by the client
+    pub private_key: String,
+    /// Bitcoin only, defaults to P2WPKH
+    #[serde(default)]
+    pub address_type: Option<AddressType>,
+}
+
+/// Stored as is by withdrawals held for the delay of the wallet policy
+#[derive(Deserialize, Serialize)]
 pub struct TransactionRequest {
-    pub to: Address,
+    /// `null` deploys the init code of `data` as a new contract
+    #[serde(default)]
+    pub to: Option<Recipient>,
     pub value: Uint<256, 4>,
+    #[serde(default)]
+    pub data: Option<Bytes>,
+    /// Free text stored with the transaction
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// Key/value pairs stored with the transaction, e.g. the id of an invoice it settles
+    #[serde(default)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Longest memo of a transaction, in characters
+const MAX_MEMO_LENGTH: usize = 256;
+/// Most keys of the metadata of a transaction
+const MAX_METADATA_KEYS: usize = 50;
+/// Longest metadata key, in characters
+const MAX_METADATA_KEY_LENGTH: usize = 40;
+/// Largest metadata of a transaction, in bytes of JSON
+const MAX_METADATA_SIZE: usize = 4096;
+
+/// Memo and metadata of a transaction request, returned with the transaction for the
+/// client to reconcile it
+#[derive(Debug, Clone, Default)]
+pub(super) struct Annotation {
+    pub(super) memo: Option<String>,
+    metadata: Option<serde_json::Value>,
+}
+
+impl Annotation {
+    pub(super) fn from_request(data: &TransactionRequest) -> Result<Self> {
+        let memo = data
+            .memo
+            .as_deref()
+            .map(str::trim)
+            .filter(|memo| !memo.is_empty());
+
+        if memo.is_some_and(|memo| memo.chars().count() > MAX_MEMO_LENGTH) {
+            return Err(ApiError::bad_request(format!(
+                "Memo exceeds {MAX_MEMO_LENGTH} characters"
+            )));
+        }
+
+        let metadata = data
+            .metadata
+            .as_ref()
+            .filter(|metadata| !metadata.is_empty());
+
+        if let Some(metadata) = metadata {
+            if metadata.len() > MAX_METADATA_KEYS {
+                return Err(ApiError::bad_request(format!(
+                    "Metadata exceeds {MAX_METADATA_KEYS} keys"
+                )));
+            }
+
+            if metadata
+                .keys()
+                .any(|key| key.is_empty() || key.chars().count() > MAX_METADATA_KEY_LENGTH)
+            {
+                return Err(ApiError::bad_request(format!(
+                    "Metadata keys must be 1 to {MAX_METADATA_KEY_LENGTH} characters"
+                )));
+            }
+
+            if serde_json::to_vec(metadata).map_or(0, |json| json.len()) > MAX_METADATA_SIZE {
+                return Err(ApiError::bad_request(format!(
+                    "Metadata exceeds {MAX_METADATA_SIZE} bytes"
+                )));
+            }
+        }
+
+        Ok(Self {
+            memo: memo.map(str::to_string),
+            metadata: metadata.cloned().map(serde_json::Value::Object),
+        })
+    }
+}
+
+/// Transaction recipient, a hex address or an ENS name resolved before the transaction
+/// is built, or the base58 public key of a Solana account
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recipient {
+    Address(Address),
+    Name(String),
+    Pubkey([u8; 32]),
+}
+
+impl TryFrom<String> for Recipient {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.trim();
+
+        if let Some(hex) = value.strip_prefix("0x") {
+            let address: Address = value
+                .parse()
+                .map_err(|_| format!("Invalid address {value}"))?;
+
+            // All-lowercase and all-uppercase addresses carry no EIP-55 checksum
+            let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+                && hex.chars().any(|c| c.is_ascii_uppercase());
+
+            if mixed_case && address.to_checksum(None) != value {
+                return Err(format!("Invalid EIP-55 checksum for address {value}"));
+            }
+
+            return Ok(Recipient::Address(address));
+        }
+
+        if !value.contains('.') {
+            return parse_pubkey(value)
+                .map(Recipient::Pubkey)
+                .ok_or_else(|| format!("Recipient {value} is neither an address nor an ENS name"));
+        }
+
+        if !value.split('.').all(|label| !label.is_empty()) {
+            return Err(format!("Invalid ENS name {value}"));
+        }
+
+        Ok(Recipient::Name(value.to_lowercase()))
+    }
+}
+
+impl From<Recipient> for String {
+    fn from(recipient: Recipient) -> Self {
+        match recipient {
+            Recipient::Address(address) => address.to_checksum(None),
+            Recipient::Name(name) => name,
+            Recipient::Pubkey(pubkey) => encode_base58(&pubkey),
+        }
+    }
+}
+
+/// Transaction request with its recipient resolved to an address
+pub(super) struct Transfer {
+    /// `None` for a contract deployment
+    pub(super) to: Option<Address>,
+    pub(super) value: U256,
+    /// Init code of the deployed contract
+    pub(super) data: Bytes,
+    pub(super) gas_limit: u64,
+    /// ENS name the recipient was resolved from
+    pub(super) ens_name: Option<String>,
+    /// Set when the transaction calls a token contract, `to` is then the contract
+    pub(super) token: Option<TokenCall>,
+    pub(super) annotation: Annotation,
+}
+
+/// Account a token contract call is made for, screened and recorded in place of the contract
+pub(super) struct TokenCall {
+    /// Recipient of an NFT or spender of an allowance
+    pub(super) recipient: Address,
+    pub(super) token_id: Option<U256>,
+}
+
+impl Transfer {
+    fn kind(&self) -> TxKind {
+        self.to.map_or(TxKind::Create, TxKind::Call)
+    }
+
+    /// Account receiving the transferred value or token
+    fn recipient(&self) -> Option<Address> {
+        self.token.as_ref().map(|token| token.recipient).or(self.to)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WalletPolicyRequest {
+    pub whitelist_only: bool,
+    /// Kept as is when omitted
+    #[serde(default)]
+    pub auto_bump_gas: Option<bool>,
+    /// Transfers of more value, in the smallest unit of the chain, are held for
+    /// `withdrawal_delay` seconds before signing. Kept as is when omitted
+    #[serde(default)]
+    pub withdrawal_threshold: Option<U256>,
+    /// `0` signs every transfer right away. Kept as is when omitted
+    #[serde(default)]
+    pub withdrawal_delay: Option<u32>,
+    /// Transactions of the wallet signed in any hour, `0` lifts the limit. Kept as is when
+    /// omitted
+    #[serde(default)]
+    pub max_signatures_per_hour: Option<u32>,
+    /// Transactions of the wallet signed in any 24 hours, `0` lifts the limit. Kept as is
+    /// when omitted
+    #[serde(default)]
+    pub max_signatures_per_day: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct WalletListQuery {
+    /// Only the wallets carrying the tag
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WalletTagsRequest {
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TaggedWalletResponse {
+    #[serde(flatten)]
+    pub wallet: WalletModel,
+    pub tags: Vec<String>,
+}
+
+/// Most tags of a wallet
+const MAX_WALLET_TAGS: usize = 20;
+/// Longest tag, in characters
+const MAX_TAG_LENGTH: usize = 32;
+
+#[derive(Deserialize)]
+pub struct BatchTransactionRequest {
+    pub transactions: Vec<TransactionRequest>,
 }
 
 #[derive(Serialize)]
@@ -42,11 +316,54 @@ pub struct WalletResponse {
 pub struct TransactionResponse {
     pub id: i32,
     pub hash: String,
+    pub status: TransactionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Address the deployed contract will have once the transaction is mined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ens_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchItemResponse {
+    pub id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ens_name: Option<String>,
+    pub value: String,
+    pub status: TransactionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
+pub struct BatchResponse {
+    pub items: Vec<BatchItemResponse>,
+}
+
+#[derive(Serialize)]
+pub struct BatchSimulationResponse {
+    pub index: usize,
+    pub simulation: Simulation,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MpcFailureResponse {
+    pub reporter: usize,
+    pub faulty_parties: Vec<u32>,
+    pub round: String,
+    pub reason: String,
 }
 
 impl From<WalletModel> for WalletResponse {
@@ -60,297 +377,4038 @@ impl From<WalletModel> for WalletResponse {
     }
 }
 
+impl From<&WalletStateError> for ApiError {
+    fn from(err: &WalletStateError) -> Self {
+        ApiError::conflict(err.to_string()).with_code("wallet_state_conflict")
+    }
+}
+
+impl From<WalletStateError> for ApiError {
+    fn from(err: WalletStateError) -> Self {
+        (&err).into()
+    }
+}
+
+impl ResponseError for WalletStateError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::CONFLICT
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError::from(self).error_response()
+    }
+}
+
+/// Keeps state errors from the repository as `409`, everything else is a `500` with `message`
+pub fn wallet_error(err: anyhow::Error, message: &'static str) -> ApiError {
+    match err.downcast::<WalletStateError>() {
+        Ok(err) => err.into(),
+        Err(err) => {
+            log::error!("{message}: {err}");
+            ApiError::internal(message)
+        }
+    }
+}
+
+/// First failed call whose participant reported the error
+fn find_error<T>(results: &[Result<T, Status>], error: ErrorCode) -> Option<&Status> {
+    results
+        .iter()
+        .filter_map(|res| res.as_ref().err())
+        .find(|status| error_code(status) == error)
+}
+
+/// Maps failed participant calls to an error from the `ErrorDetail` they carry, the most
+/// actionable failure wins and anything unexpected is a `500` with `code`
+pub(super) fn participant_error<T>(
+    results: &[Result<T, Status>],
+    code: &'static str,
+    message: &str,
+) -> ApiError {
+    if find_error(results, ErrorCode::Busy).is_some() {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "participants_busy",
+            "Participants are busy, try again later",
+        )
+        .with_retry_after(5);
+    }
+
+    if find_error(results, ErrorCode::Unavailable).is_some() {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "participants_unavailable",
+            "Not enough participants available, try again later",
+        )
+        .with_retry_after(5);
+    }
+
+    if find_error(results, ErrorCode::StorageFailed).is_some() {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "participant_storage_failed",
+            "Participant key storage is unavailable, try again later",
+        )
+        .with_retry_after(5);
+    }
+
+    if find_error(results, ErrorCode::Timeout).is_some() {
+        return ApiError::new(
+            StatusCode::GATEWAY_TIMEOUT,
+            "participants_timeout",
+            "Participants timed out, try again later",
+        );
+    }
+
+    // A participant's own signing policy refused the transaction
+    if let Some(status) = find_error(results, ErrorCode::PolicyViolation) {
+        return ApiError::forbidden(status.message()).with_code("participant_policy_violation");
+    }
+
+    // Keygens never replace a stored share of the wallet
+    if find_error(results, ErrorCode::ShareExists).is_some() {
+        return ApiError::conflict("A participant already holds a share of the wallet")
+            .with_code("share_exists");
+    }
+
+    // Participants only sign an execution id and a transaction once
+    if let Some(status) = find_error(results, ErrorCode::Replayed) {
+        return ApiError::conflict(status.message()).with_code("participant_replay_rejected");
+    }
+
+    // Requests the participants can't serve, e.g. a scheme unsupported on the chain
+    if let Some(status) = find_error(results, ErrorCode::InvalidRequest) {
+        return ApiError::unprocessable("participant_rejected_request", status.message());
+    }
+
+    if find_error(results, ErrorCode::ShareNotFound).is_some() {
+        return ApiError::internal("A participant holds no share of the wallet")
+            .with_code("share_not_found");
+    }
+
+    if find_error(results, ErrorCode::ShareMismatch).is_some() {
+        return ApiError::internal("A participant's share doesn't match the wallet")
+            .with_code("share_mismatch");
+    }
+
+    // The share was corrupted or replaced in Vault, the participant refuses to use it
+    if find_error(results, ErrorCode::ShareTampered).is_some() {
+        return ApiError::internal("A participant's share failed its integrity check")
+            .with_code("share_integrity_failed");
+    }
+
+    ApiError::internal(message).with_code(code)
+}
+
+/// Code of a transaction the node rejects, balances too low for the value and the gas
+/// are told apart from reverts
+fn rejection_code(reason: &str) -> &'static str {
+    let reason = reason.to_lowercase();
+
+    if reason.contains("insufficient funds") || reason.contains("insufficient balance") {
+        "insufficient_funds"
+    } else {
+        "transaction_would_fail"
+    }
+}
+
+/// Identifiable aborts reported by the participants, `reporters` holds the signing index of
+/// the participant behind each result, the index faulty parties are named by
+fn aborts<T>(results: &[Result<T, Status>], reporters: &[usize]) -> Vec<MpcFailureResponse> {
+    results
+        .iter()
+        .zip(reporters)
+        .filter_map(|(res, &reporter)| match res {
+            Err(status) if status.code() == Code::Aborted => {
+                let details = error_detail(status)?.abort?;
+
+                Some(MpcFailureResponse {
+                    reporter,
+                    faulty_parties: details.faulty_parties,
+                    round: details.round,
+                    reason: details.reason,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn record_aborts(
+    db: &DatabaseConnection,
+    wallet_id: i32,
+    execution_id: &Uuid,
+    operation: &str,
+    failures: &[MpcFailureResponse],
+) {
+    let repository = MpcFailureRepository::new_with_connection(db);
+
+    for failure in failures {
+        log::error!(
+            "Participant {} reported an abort by parties {:?} in {} - wallet_id: {}",
+            failure.reporter,
+            failure.faulty_parties,
+            failure.round,
+            wallet_id
+        );
+
+        // The response already carries the failure, losing the record is not fatal
+        let res = repository
+            .create(MpcFailureActiveModel {
+                wallet_id: Set(wallet_id),
+                execution_id: Set(execution_id.to_string()),
+                operation: Set(operation.to_string()),
+                reporter: Set(failure.reporter as i32),
+                faulty_parties: Set(serde_json::json!(failure.faulty_parties)),
+                round: Set(failure.round.clone()),
+                reason: Set(failure.reason.clone()),
+                ..Default::default()
+            })
+            .await;
+
+        if let Err(err) = res {
+            log::error!("Failed to record MPC failure: {err}");
+        }
+    }
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("").route(web::post().to(create_wallet)))
-        .service(web::resource("/{id}").route(web::delete().to(delete_wallet)))
-        .service(web::resource("/{id}/tx").route(web::post().to(send_tx)));
+    cfg.service(
+        web::resource("")
+            .route(web::get().to(list_wallets))
+            .route(web::post().to(create_wallet)),
+    )
+    .service(web::resource("/import").route(web::post().to(import_wallet)))
+    .service(web::resource("/{id}").route(web::delete().to(delete_wallet)))
+    .service(web::resource("/{id}/keygen").route(web::get().to(keygen_status)))
+    .service(web::resource("/{id}/xpub").route(web::get().to(wallet_xpub)))
+    .service(web::resource("/{id}/archive").route(web::post().to(archive_wallet)))
+    .service(web::resource("/{id}/restore").route(web::post().to(restore_wallet)))
+    .service(web::resource("/{id}/policy").route(web::put().to(update_policy)))
+    .service(web::resource("/{id}/tags").route(web::put().to(update_tags)))
+    .service(web::resource("/{id}/tx").route(web::post().to(send_tx)))
+    .service(web::resource("/{id}/tx/batch").route(web::post().to(send_batch_tx)))
+    .service(web::resource("/{id}/tx/simulate").route(web::post().to(simulate_tx)))
+    .service(web::resource("/{id}/tx/quote").route(web::get().to(quote_tx)))
+    .service(web::resource("/{id}/tx/{tx_id}/broadcast").route(web::post().to(broadcast_tx)));
+}
+
+/// Address type stored on a new wallet of `chain`, only Bitcoin wallets have one
+fn wallet_address_type(
+    chain: &Chain,
+    requested: Option<AddressType>,
+) -> Result<Option<AddressType>> {
+    match (chain, requested) {
+        (Chain::Bitcoin, requested) => Ok(Some(requested.unwrap_or(AddressType::P2wpkh))),
+        (_, None) => Ok(None),
+        (_, Some(_)) => Err(ApiError::bad_request(
+            "Address types are only supported on Bitcoin",
+        )),
+    }
 }
 
 pub async fn create_wallet(
     req: HttpRequest,
     data: web::Json<CreateWalletRequest>,
     db: web::Data<DatabaseConnection>,
-    participants: web::Data<Vec<Channel>>,
+    participants: web::Data<dyn ParticipantPool>,
+    quotas: web::Data<QuotaConfig>,
+    cosigners: web::Data<CosignerConfig>,
 ) -> Result<HttpResponse> {
     let user_id = request_user_id(&req)?;
 
-    // Revert transaction on keygen failure
-    // TODO: Add a clean up mechanism for partially created wallets
-    let txn = db
-        .begin()
-        .await
-        .map_err(|_| ErrorInternalServerError("Failed to create wallet"))?;
+    if data.cosigner && !cosigners.enabled {
+        return Err(ApiError::forbidden("Co-signed wallets are disabled"));
+    }
 
-    let repository = WalletRepository::new_with_transaction(&txn);
+    let address_type = wallet_address_type(&data.chain, data.address_type)?;
+
+    ensure_wallet_quota(&db, &quotas, user_id).await?;
+
+    // The wallet is visible as `creating` while keygen runs so it can't be used yet
+    let repository = WalletRepository::new_with_connection(&db);
 
     let wallet = repository
         .create(WalletActiveModel {
             user_id: Set(user_id),
             name: Set(data.name.clone()),
             chain: Set(data.chain.clone()),
+            namespace: Set(Uuid::new_v4().simple().to_string()),
+            state: Set(WalletState::Creating),
+            address_type: Set(address_type),
+            cosigner: Set(data.cosigner),
             ..Default::default()
         })
         .await
-        .map_err(|_| ErrorInternalServerError("Failed to create wallet"))?;
-
-    // Must be unique for all participants
-    let execution_id = Uuid::new_v4();
+        .map_err(|_| ApiError::internal("Failed to create wallet"))?;
 
-    let futures = participants.iter().map(|p| {
-        let mut client = ParticipantClient::new(p.clone());
-        let request_clone = tonic::Request::new(CreateWalletMessage {
-            wallet_id: wallet.id,
-            chain: data.chain.clone().into(),
-            execution_id: execution_id.as_bytes().to_vec(),
-        });
+    let operations = OperationRepository::new_with_connection(&db);
 
-        async move {
-            client.new_wallet(request_clone).await.map_err(|err| {
-                log::error!("Failed to create wallet on participant: {err}");
-                ErrorInternalServerError("Failed to create wallet")
-            })
-        }
-    });
+    let operation = operations
+        .start(user_id, wallet.id, None, OperationKind::Keygen)
+        .await
+        .map_err(|_| ApiError::internal("Failed to create wallet"))?;
 
-    let is_created = join_all(futures).await.iter().all(|res| res.is_ok());
+    let created = keygen_wallet(&db, participants.get_ref(), &wallet).await;
 
-    if is_created {
-        txn.commit()
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to create wallet"))?;
+    finish_operation(&operations, &operation, created.as_ref().err()).await;
 
-        Ok(HttpResponse::Created().json(wallet))
-    } else {
-        txn.rollback()
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to create wallet"))?;
+    let wallet = created.map_err(|err| err.with("operation_id", operation.id))?;
 
-        Ok(HttpResponse::InternalServerError().finish())
-    }
+    Ok(HttpResponse::Created().json(wallet))
 }
 
-pub async fn delete_wallet(
+/// Shares an existing private key between the participants as a new wallet, for users
+/// moving their wallets into MPC custody. The key is only kept until it was dealt
+pub async fn import_wallet(
     req: HttpRequest,
-    path: web::Path<i32>,
+    data: web::Json<ImportWalletRequest>,
     db: web::Data<DatabaseConnection>,
-    participants: web::Data<Vec<Channel>>,
+    participants: web::Data<dyn ParticipantPool>,
+    import: web::Data<WalletImportConfig>,
+    quotas: web::Data<QuotaConfig>,
 ) -> Result<HttpResponse> {
     let user_id = request_user_id(&req)?;
 
-    let wallet_id = path.into_inner();
+    let data = data.into_inner();
 
-    // Revert transaction on keygen failure
-    // to be sure no dangling wallets exist
-    let txn = db
-        .begin()
-        .await
-        .map_err(|_| ErrorInternalServerError("Failed to delete wallet"))?;
+    // The request body is dropped with the handler, the decoded key is scrubbed
+    let private_key = Zeroizing::new(data.private_key);
 
-    let repository = WalletRepository::new_with_transaction(&txn);
+    if !import.enabled {
+        return Err(ApiError::forbidden("Key import is disabled"));
+    }
+
+    let private_key = hex::decode(private_key.trim_start_matches("0x"))
+        .ok()
+        .filter(|key| key.len() == 32)
+        .map(Zeroizing::new)
+        .ok_or_else(|| ApiError::bad_request("Expected a 32-byte hex private key"))?;
+
+    let address_type = wallet_address_type(&data.chain, data.address_type)?;
+
+    ensure_wallet_quota(&db, &quotas, user_id).await?;
+
+    let repository = WalletRepository::new_with_connection(&db);
 
     let wallet = repository
-        .find_by_id(wallet_id)
+        .create(WalletActiveModel {
+            user_id: Set(user_id),
+            name: Set(data.name),
+            chain: Set(data.chain),
+            namespace: Set(Uuid::new_v4().simple().to_string()),
+            state: Set(WalletState::Creating),
+            address_type: Set(address_type),
+            ..Default::default()
+        })
         .await
-        .map_err(|_| ErrorInternalServerError("Failed to delete wallet"))?;
+        .map_err(|_| ApiError::internal("Failed to import wallet"))?;
 
-    let wallet = match wallet {
-        Some(w) if w.user_id == user_id => Ok(w),
-        _ => Err(ErrorNotFound("Wallet not found")),
-    }?;
+    let operations = OperationRepository::new_with_connection(&db);
 
-    let futures = participants.iter().map(|p| {
-        let mut client = ParticipantClient::new(p.clone());
-        let request_clone = tonic::Request::new(DeleteWalletMessage {
-            wallet_id: wallet.id,
-        });
+    let operation = operations
+        .start(user_id, wallet.id, None, OperationKind::Import)
+        .await
+        .map_err(|_| ApiError::internal("Failed to import wallet"))?;
 
-        async move { client.delete_wallet(request_clone).await }
-    });
+    let imported = import_key(&db, participants.get_ref(), &wallet, &private_key).await;
 
-    let is_deleted = join_all(futures).await.iter().all(|res| res.is_ok());
+    drop(private_key);
 
-    if is_deleted {
-        repository
-            .delete(wallet_id)
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to delete wallet"))?;
+    finish_operation(&operations, &operation, imported.as_ref().err()).await;
 
-        txn.commit()
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to delete wallet"))?;
+    let wallet = imported.map_err(|err| err.with("operation_id", operation.id))?;
 
-        Ok(HttpResponse::NoContent().finish())
-    } else {
-        txn.rollback()
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to delete wallet"))?;
+    let audit = AuditRepository::new_with_connection(&db)
+        .record(
+            &format!("user:{user_id}"),
+            "wallet.imported",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({ "address": wallet.address })),
+        )
+        .await;
 
-        Ok(HttpResponse::InternalServerError().finish())
+    if let Err(err) = audit {
+        log::error!("Failed to audit the import of wallet {}: {err}", wallet.id);
     }
-}
 
-#[derive(Debug, RlpEncodable, RlpDecodable)]
-struct RawTransaction {
-    nonce: u64,
-    gas_price: u64,
-    gas_limit: u64,
-    to: Address,
-    value: U256,
-    data: Vec<u8>,
+    Ok(HttpResponse::Created().json(wallet))
 }
 
-#[derive(Debug, RlpEncodable, RlpDecodable)]
-struct SignedTransaction {
-    nonce: u64,
-    gas_price: u64,
-    gas_limit: u64,
-    to: Address,
-    value: U256,
-    data: Vec<u8>,
-    v: u32,
-    r: U256,
-    s: U256,
+/// Runs the keygen of a `creating` wallet and activates it. A failed keygen moves the wallet
+/// to `deleting` and queues the purge of its shares
+pub async fn keygen_wallet(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+) -> Result<WalletModel> {
+    let results = run_keygen(participants, wallet).await;
+
+    settle_keygen(
+        db,
+        wallet,
+        &results,
+        "keygen_failed",
+        "Failed to create wallet",
+    )
+    .await
 }
 
-pub async fn send_tx(
-    req: HttpRequest,
-    data: web::Json<TransactionRequest>,
-    db: web::Data<DatabaseConnection>,
-    provider: web::Data<dyn Provider + Send + Sync>,
-    participants: web::Data<Vec<Channel>>,
-    path: web::Path<i32>,
-) -> Result<HttpResponse> {
-    let user_id = request_user_id(&req)?;
-    let wallet_id = path.into_inner();
+/// Shares the private key as the key of a `creating` wallet, settled like a keygen
+pub async fn import_key(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    private_key: &[u8],
+) -> Result<WalletModel> {
+    let results = run_import(participants, wallet, private_key).await;
 
-    let txn = db.begin().await.map_err(|_| ErrorInternalServerError(""))?;
+    settle_keygen(
+        db,
+        wallet,
+        &results,
+        "import_failed",
+        "Failed to import wallet",
+    )
+    .await
+}
 
-    let wallet_repository = WalletRepository::new_with_transaction(&txn);
-    let transaction_repository = TransactionRepository::new_with_transaction(&txn);
+/// Activates the wallet once every participant returned the same key, otherwise moves it to
+/// `deleting` and fails with `code`
+async fn settle_keygen(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    results: &[Result<WalletCreatedMessage, Status>],
+    code: &'static str,
+    message: &'static str,
+) -> Result<WalletModel> {
+    let repository = WalletRepository::new_with_connection(db);
 
-    let wallet = wallet_repository
-        .find_by_id(wallet_id)
-        .await
-        .map_err(|_| ErrorInternalServerError("Failed to retrive the wallet"))?;
+    let is_created = results.iter().all(|res| res.is_ok());
 
-    let wallet = match wallet {
-        Some(w) if w.user_id == user_id => Ok(w),
-        _ => Err(ErrorNotFound("Wallet not found")),
-    }?;
+    if is_created {
+        match keygen_address(&wallet.chain, wallet.address_type, results) {
+            Ok(address) => {
+                return repository
+                    .activate(wallet, address)
+                    .await
+                    .map_err(|err| wallet_error(err, message));
+            }
+            Err(err) => log::error!("Discarding keygen of wallet {}: {err}", wallet.id),
+        }
+    }
 
-    let transaction_model = transaction_repository
-        .create(TransactionActiveModel {
-            user_id: Set(user_id),
-            wallet_id: Set(wallet_id),
-            ..Default::default()
-        })
+    // Participants may hold a share even after a timeout, the purge job deletes them and
+    // wallets left in `deleting` are swept by the wallet purger
+    let wallet = repository
+        .transition(wallet, WalletState::Deleting)
         .await
-        .map_err(|_| ErrorInternalServerError(""))?;
+        .map_err(|err| wallet_error(err, message))?;
 
-    let tx_data = match wallet.chain {
-        Chain::Ethereum => {
-            // TODO: Fetch nonce from provider to avoid replay attacks
-            // TODO: Allow custom gas price, gas limit, data
-            let unsigned_tx = RawTransaction {
-                nonce: 10,
-                gas_price: 1000000000u64,
-                gas_limit: 21000u64,
-                to: data.to,
-                value: U256::from(data.value),
-                data: Vec::new(),
-            };
-
-            let mut rlp_buf = Vec::new();
+    if results.iter().any(may_hold_share) {
+        let purge = WalletPurge {
+            wallet_id: wallet.id,
+        };
 
-            unsigned_tx.encode(&mut rlp_buf);
+        let jobs = JobRepository::new_with_connection(db);
 
-            Ok(rlp_buf)
+        if let Err(err) = enqueue(&jobs, JobKind::WalletPurge, &purge).await {
+            log::error!("Failed to queue the purge of wallet {}: {err}", wallet.id);
         }
-        _ => Err(ErrorBadRequest("Chain not supported")),
-    }?;
+    } else {
+        repository
+            .transition(&wallet, WalletState::Deleted)
+            .await
+            .map_err(|err| wallet_error(err, message))?;
+    }
 
-    // Must be unique for all participants
-    let execution_id = Uuid::new_v4();
+    Err(participant_error(results, code, message))
+}
 
-    // Threshold equal to 2 participants for now
-    let futures = participants.iter().take(2).map(|p| {
-        let mut client = ParticipantClient::new(p.clone());
-        let request_clone = tonic::Request::new(SignMessage {
-            tx_id: transaction_model.id,
-            wallet_id,
-            execution_id: execution_id.as_bytes().to_vec(),
-            chain: wallet.chain.clone().into(),
-            data: tx_data.clone(),
-        });
-
-        async move { client.sign_tx(request_clone).await }
-    });
+#[derive(Debug, Serialize)]
+pub struct KeygenStatusResponse {
+    pub wallet_id: i32,
+    pub state: WalletState,
+    /// Progress of each participant while the keygen runs, empty otherwise
+    pub participants: Vec<ParticipantProgress>,
+}
 
-    let results = join_all(futures).await;
+/// Progress of the keygen of a `creating` wallet, as streamed by the participants
+pub async fn keygen_status(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
 
-    let is_signed = results.iter().all(|res| res.is_ok());
+    let repository = WalletRepository::new_with_connection(&db);
 
-    let mut signature = None;
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
 
-    if is_signed && let Some(Ok(response)) = results.first() {
-        let s = response.get_ref();
-        signature = Some((s.r.clone(), s.s.clone(), s.v));
-    }
+    Ok(HttpResponse::Ok().json(KeygenStatusResponse {
+        wallet_id: wallet.id,
+        state: wallet.state,
+        participants: keygen_progress(wallet.id),
+    }))
+}
 
-    if let Some((r, s, v)) = signature {
-        txn.commit()
-            .await
-            .map_err(|_| ErrorInternalServerError("Failed to sign transaction"))?;
-
-        let tx_hash = match wallet.chain {
-            Chain::Ethereum => {
-                // TODO: Fetch nonce from provider to avoid replay attacks
-                // TODO: Allow custom gas price, gas limit, data
-                let signed_tx = SignedTransaction {
-                    nonce: 10,
-                    gas_price: 1000000000u64,
-                    gas_limit: 21000u64,
-                    to: data.to,
-                    value: U256::from(data.value),
-                    data: Vec::new(),
-                    v,
-                    r: U256::from_be_slice(&r),
-                    s: U256::from_be_slice(&s),
-                };
-
-                let mut rlp_buf = Vec::new();
-
-                signed_tx.encode(&mut rlp_buf);
-
-                let tx = provider
-                    .send_raw_transaction(&rlp_buf)
-                    .await
-                    .map_err(|err| {
-                        log::error!("{err}");
-                        ErrorInternalServerError("Failed to send transaction")
-                    })?;
+#[derive(Debug, Serialize)]
+pub struct XpubResponse {
+    pub wallet_id: i32,
+    /// BIP-32 extended public key of the wallet key, the master key of its derivations
+    pub xpub: String,
+    /// Hex SEC1 compressed public key
+    pub public_key: String,
+    pub chain_code: String,
+}
+
+/// Extended public key of an HD wallet for deriving addresses watch-only, read from the
+/// shares of the participants without a signing round
+pub async fn wallet_xpub(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+    participants: web::Data<dyn ParticipantPool>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
+
+    if wallet.state == WalletState::Creating {
+        return Err(WalletStateError::Creating.into());
+    }
+
+    let infos = wallet_infos(participants.get_ref(), &wallet, &[])
+        .await
+        .into_iter()
+        .filter_map(|res| res.ok())
+        .collect::<Vec<_>>();
+
+    // A single participant can't make up the chain code of the wallet, unless it's the only
+    // one holding a share
+    if infos.len() < key_quorum(&wallet) {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "participants_unavailable",
+            "Not enough participants available, try again later",
+        )
+        .with_retry_after(5));
+    }
+
+    let key = extended_key(&infos)
+        .map_err(|err| {
+            log::error!(
+                "Failed to read the extended key of wallet {}: {err}",
+                wallet.id
+            );
+            ApiError::internal("Participants disagree on the wallet key")
+                .with_code("share_mismatch")
+        })?
+        .ok_or_else(|| {
+            ApiError::unprocessable(
+                "not_hd_wallet",
+                "Wallet key has no chain code, it is not an HD secp256k1 ECDSA key",
+            )
+        })?;
+
+    Ok(HttpResponse::Ok().json(XpubResponse {
+        wallet_id: wallet.id,
+        xpub: extended_public_key(&key.public_key, &key.chain_code),
+        public_key: hex::encode(key.public_key),
+        chain_code: hex::encode(key.chain_code),
+    }))
+}
+
+/// Soft deletes the wallet, it can be restored until the retention window passes
+pub async fn delete_wallet(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    archive(&req, path.into_inner(), &db).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn archive_wallet(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let wallet = archive(&req, path.into_inner(), &db).await?;
+
+    Ok(HttpResponse::Ok().json(wallet))
+}
+
+pub async fn restore_wallet(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
+
+    if wallet.state != WalletState::Archiving {
+        return Err(ApiError::conflict("Wallet is not archived"));
+    }
+
+    let wallet = repository
+        .restore(&wallet)
+        .await
+        .map_err(|err| wallet_error(err, "Failed to restore wallet"))?;
+
+    Ok(HttpResponse::Ok().json(wallet))
+}
+
+/// Changes the transfer policy of the wallet, recorded in the audit log
+pub async fn update_policy(
+    req: HttpRequest,
+    data: web::Json<WalletPolicyRequest>,
+    db: web::Data<DatabaseConnection>,
+    withdrawals: web::Data<WithdrawalConfig>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    if data
+        .withdrawal_delay
+        .is_some_and(|delay| delay > withdrawals.max_delay)
+    {
+        return Err(ApiError::bad_request(format!(
+            "Withdrawal delay exceeds {} seconds",
+            withdrawals.max_delay
+        )));
+    }
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update policy"))?;
+
+    let repository = WalletRepository::new_with_transaction(&txn);
+
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
+
+    let previous = wallet.whitelist_only;
+    let previous_bump = wallet.auto_bump_gas;
+    let previous_threshold = wallet.withdrawal_threshold.clone();
+    let previous_delay = wallet.withdrawal_delay;
+    let previous_hourly = wallet.max_signatures_per_hour;
+    let previous_daily = wallet.max_signatures_per_day;
+
+    let mut model = wallet.into_active_model();
+    model.whitelist_only = Set(data.whitelist_only);
+    model.auto_bump_gas = Set(data.auto_bump_gas.unwrap_or(previous_bump));
+
+    if let Some(threshold) = data.withdrawal_threshold {
+        model.withdrawal_threshold = Set(Some(threshold.to_string()));
+    }
+
+    if let Some(delay) = data.withdrawal_delay {
+        model.withdrawal_delay = Set(i32::try_from(delay).unwrap_or(i32::MAX));
+    }
+
+    if let Some(limit) = data.max_signatures_per_hour {
+        model.max_signatures_per_hour = Set(signing_limit(limit));
+    }
+
+    if let Some(limit) = data.max_signatures_per_day {
+        model.max_signatures_per_day = Set(signing_limit(limit));
+    }
+
+    let wallet = repository
+        .update(model)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update policy"))?;
+
+    AuditRepository::new_with_transaction(&txn)
+        .record(
+            &format!("user:{user_id}"),
+            "wallet.policy_updated",
+            "wallet",
+            Some(wallet.id.to_string()),
+            Some(serde_json::json!({
+                "whitelist_only": { "from": previous, "to": wallet.whitelist_only },
+                "auto_bump_gas": { "from": previous_bump, "to": wallet.auto_bump_gas },
+                "withdrawal_threshold": {
+                    "from": previous_threshold,
+                    "to": wallet.withdrawal_threshold,
+                },
+                "withdrawal_delay": { "from": previous_delay, "to": wallet.withdrawal_delay },
+                "max_signatures_per_hour": {
+                    "from": previous_hourly,
+                    "to": wallet.max_signatures_per_hour,
+                },
+                "max_signatures_per_day": {
+                    "from": previous_daily,
+                    "to": wallet.max_signatures_per_day,
+                },
+            })),
+        )
+        .await
+        .map_err(|_| ApiError::internal("Failed to update policy"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update policy"))?;
+
+    Ok(HttpResponse::Ok().json(wallet))
+}
+
+/// Stored signing limit of the policy request, `0` lifts it
+fn signing_limit(limit: u32) -> Option<i32> {
+    (limit > 0).then(|| i32::try_from(limit).unwrap_or(i32::MAX))
+}
+
+/// Lowercases the tag, tags are letters, digits, `-`, `_`, `:` and `.` so they fit in a
+/// query string as they are
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_LENGTH
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'));
+
+    match valid {
+        true => Ok(tag),
+        false => Err(ApiError::bad_request(format!(
+            "Invalid tag {tag}, tags are 1 to {MAX_TAG_LENGTH} letters, digits, '-', '_', ':' or '.'"
+        ))),
+    }
+}
+
+/// Tags to store on a wallet, sorted and without duplicates
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut tags = tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .collect::<Result<Vec<_>>>()?;
+
+    tags.sort();
+    tags.dedup();
+
+    if tags.len() > MAX_WALLET_TAGS {
+        return Err(ApiError::bad_request(format!(
+            "Wallets have at most {MAX_WALLET_TAGS} tags"
+        )));
+    }
+
+    Ok(tags)
+}
+
+/// Wallets of the user with their tags, those carrying `tag` when set
+pub async fn list_wallets(
+    req: HttpRequest,
+    query: web::Query<WalletListQuery>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = WalletRepository::new_with_connection(&db);
+
+    let wallets = match query.tag.as_deref() {
+        Some(tag) => {
+            repository
+                .find_by_user_id_and_tag(user_id, &normalize_tag(tag)?)
+                .await
+        }
+        None => repository.find_by_user_id(user_id).await,
+    }
+    .map_err(|_| ApiError::internal("Failed to retrieve the wallets"))?;
+
+    let wallet_ids: Vec<i32> = wallets.iter().map(|wallet| wallet.id).collect();
+
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+
+    for tag in WalletTagRepository::new_with_connection(&db)
+        .find_by_wallet_ids(&wallet_ids)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the wallets"))?
+    {
+        tags.entry(tag.wallet_id).or_default().push(tag.tag);
+    }
+
+    let mut response: Vec<TaggedWalletResponse> = wallets
+        .into_iter()
+        .map(|wallet| TaggedWalletResponse {
+            tags: tags.remove(&wallet.id).unwrap_or_default(),
+            wallet,
+        })
+        .collect();
+
+    response.sort_by_key(|item| item.wallet.id);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Replaces the tags of the wallet
+pub async fn update_tags(
+    req: HttpRequest,
+    data: web::Json<WalletTagsRequest>,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let tags = normalize_tags(&data.tags)?;
+
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update tags"))?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_transaction(&txn),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    WalletTagRepository::new_with_transaction(&txn)
+        .replace(wallet.id, &tags)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update tags"))?;
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to update tags"))?;
+
+    Ok(HttpResponse::Ok().json(TaggedWalletResponse { wallet, tags }))
+}
+
+/// Rejects recipients missing from the owner's address book on `whitelist_only` wallets
+pub(super) async fn ensure_whitelisted(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    recipients: impl IntoIterator<Item = String>,
+) -> Result<()> {
+    if !wallet.whitelist_only {
+        return Ok(());
+    }
+
+    let repository = AddressRepository::new(db);
+
+    for recipient in recipients {
+        let known = repository
+            .contains(wallet.user_id, &wallet.chain, &recipient)
+            .await
+            .map_err(|_| ApiError::internal("Failed to check the address book"))?;
+
+        if !known {
+            return Err(ApiError::forbidden(format!(
+                "Recipient {recipient} is not in the address book"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the recipients of the transfers against the address book, whitelist-only wallets
+/// can't deploy contracts as a deployment has no recipient to check
+pub(super) async fn ensure_transfers_allowed(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    transfers: &[Transfer],
+) -> Result<()> {
+    if wallet.whitelist_only && transfers.iter().any(|transfer| transfer.to.is_none()) {
+        return Err(ApiError::forbidden(
+            "Contract deployments are not allowed on whitelist-only wallets",
+        ));
+    }
+
+    ensure_whitelisted(
+        db,
+        wallet,
+        transfers
+            .iter()
+            .filter_map(Transfer::recipient)
+            .map(|to| to.to_string()),
+    )
+    .await
+}
+
+async fn archive(
+    req: &HttpRequest,
+    wallet_id: i32,
+    db: &DatabaseConnection,
+) -> Result<WalletModel> {
+    let user_id = request_user_id(req)?;
+
+    let repository = WalletRepository::new_with_connection(db);
+
+    let wallet = find_user_wallet(&repository, wallet_id, user_id).await?;
+
+    repository
+        .archive(&wallet)
+        .await
+        .map_err(|err| wallet_error(err, "Failed to archive wallet"))
+}
+
+pub(super) async fn find_user_wallet(
+    repository: &WalletRepository<'_>,
+    wallet_id: i32,
+    user_id: i32,
+) -> Result<WalletModel> {
+    let wallet = repository
+        .find_by_id(wallet_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrive the wallet"))?;
+
+    match wallet {
+        Some(w) if w.user_id == user_id && w.state != WalletState::Deleted => {
+            logging::set_wallet_id(w.id);
+
+            Ok(w)
+        }
+        _ => Err(ApiError::wallet_not_found()),
+    }
+}
+
+#[derive(Debug, RlpEncodable, RlpDecodable)]
+struct RawTransaction {
+    nonce: u64,
+    gas_price: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    data: Bytes,
+}
+
+impl RawTransaction {
+    /// EIP-155 payload the wallet signs, the transaction fields followed by the chain id and
+    /// two empty values
+    fn signing_payload(&self, chain_id: u64) -> Vec<u8> {
+        let payload = SigningPayload {
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            to: self.to,
+            value: self.value,
+            data: self.data.clone(),
+            chain_id,
+            r: 0,
+            s: 0,
+        };
+
+        let mut buf = Vec::new();
+
+        payload.encode(&mut buf);
+
+        buf
+    }
+
+    /// Keccak hash of the signing payload
+    fn signing_hash(&self, chain_id: u64) -> B256 {
+        keccak256(self.signing_payload(chain_id))
+    }
+}
+
+#[derive(Debug, RlpEncodable)]
+struct SigningPayload {
+    nonce: u64,
+    gas_price: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    data: Bytes,
+    chain_id: u64,
+    r: u8,
+    s: u8,
+}
+
+#[derive(Debug, RlpEncodable, RlpDecodable)]
+struct SignedTransaction {
+    nonce: u64,
+    gas_price: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    data: Bytes,
+    v: u32,
+    r: U256,
+    s: U256,
+}
+
+/// Gas of a plain transfer, which runs no contract code
+const TRANSFER_GAS_LIMIT: u64 = 21000;
+
+/// Resolves the ENS name of the recipient with the chain provider, deployments get their
+/// gas limit estimated instead
+pub(super) async fn resolve_transaction(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    data: &TransactionRequest,
+) -> Result<Transfer> {
+    let annotation = Annotation::from_request(data)?;
+
+    let name = match &data.to {
+        None => return resolve_deployment(wallet, network, data, annotation).await,
+        Some(_) if data.data.is_some() => {
+            return Err(ApiError::bad_request(
+                "Data is only supported for contract deployments",
+            ));
+        }
+        Some(Recipient::Address(address)) => {
+            return Ok(Transfer {
+                to: Some(*address),
+                value: data.value,
+                data: Bytes::new(),
+                gas_limit: TRANSFER_GAS_LIMIT,
+                ens_name: None,
+                token: None,
+                annotation,
+            });
+        }
+        Some(Recipient::Name(name)) => name,
+        Some(Recipient::Pubkey(_)) => {
+            return Err(ApiError::bad_request(
+                "Solana recipients are not supported on this chain",
+            ));
+        }
+    };
+
+    // The ENS registry only lives on Ethereum
+    let provider = network
+        .provider
+        .as_ref()
+        .filter(|_| network.config.chain == Chain::Ethereum)
+        .ok_or_else(|| ApiError::bad_request("ENS names are not supported on this chain"))?;
+
+    let address = resolve_name(provider.as_ref(), name)
+        .await
+        .map_err(|err| {
+            log::error!("{err}");
+            ApiError::internal("Failed to resolve ENS name")
+        })?
+        .ok_or_else(|| {
+            ApiError::bad_request(format!("ENS name {name} does not resolve to an address"))
+        })?;
+
+    Ok(Transfer {
+        to: Some(address),
+        value: data.value,
+        data: Bytes::new(),
+        gas_limit: TRANSFER_GAS_LIMIT,
+        ens_name: Some(name.clone()),
+        token: None,
+        annotation,
+    })
+}
+
+/// Sender of a deployment, the contract address is derived from it and the nonce
+fn deployer(wallet: &WalletModel) -> Result<Address> {
+    wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to deploy from"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))
+}
+
+/// Estimates the gas of the init code from the wallet address, the constructor can run
+/// arbitrary logic so there is no fixed limit like for transfers
+async fn resolve_deployment(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    data: &TransactionRequest,
+    annotation: Annotation,
+) -> Result<Transfer> {
+    let init_code = data
+        .data
+        .clone()
+        .filter(|init_code| !init_code.is_empty())
+        .ok_or_else(|| ApiError::bad_request("Contract deployments require init code in data"))?;
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let call = CallRequest {
+        from: Some(deployer(wallet)?),
+        to: Some(TxKind::Create),
+        value: Some(data.value),
+        input: TransactionInput::new(init_code.clone()),
+        ..Default::default()
+    };
+
+    Ok(Transfer {
+        to: None,
+        value: data.value,
+        data: init_code,
+        gas_limit: estimate_gas_limit(provider.as_ref(), call).await?,
+        ens_name: None,
+        token: None,
+        annotation,
+    })
+}
+
+/// Gas limit of a call running contract code, estimated by the node
+pub(super) async fn estimate_gas_limit(
+    provider: &(dyn Provider + Send + Sync),
+    call: CallRequest,
+) -> Result<u64> {
+    match provider.estimate_gas(call).await {
+        Ok(gas_limit) => Ok(gas_limit),
+        // The node couldn't estimate the call, e.g. the contract reverts
+        Err(RpcError::ErrorResp(payload)) => Err(ApiError::unprocessable(
+            rejection_code(&payload.message),
+            format!("Transaction would fail: {}", payload.message),
+        )),
+        Err(err) => {
+            log::error!("{err}");
+            Err(ApiError::internal("Failed to estimate gas"))
+        }
+    }
+}
+
+fn unsigned_transaction(
+    wallet: &WalletModel,
+    data: &Transfer,
+    nonce: u64,
+) -> Result<RawTransaction> {
+    match wallet.chain {
+        ref chain if chain.is_evm() => {
+            // TODO: Allow custom gas price
+            Ok(RawTransaction {
+                nonce,
+                gas_price: 1000000000u64,
+                gas_limit: data.gas_limit,
+                to: data.kind(),
+                value: data.value,
+                data: data.data.clone(),
+            })
+        }
+        _ => Err(ApiError::bad_request("Chain not supported")),
+    }
+}
+
+/// Highest fee a transfer of `value` to `to` can be charged, its gas at the gas price of the
+/// wallet transactions plus the L1 data fee on OP Stack rollups
+pub(super) async fn transfer_fee(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    to: &Recipient,
+    value: U256,
+) -> Result<U256> {
+    let request = TransactionRequest {
+        to: Some(to.clone()),
+        value,
+        data: None,
+        memo: None,
+        metadata: None,
+    };
+
+    let transfer = resolve_transaction(wallet, network, &request).await?;
+
+    let unsigned_tx = unsigned_transaction(wallet, &transfer, 0)?;
+
+    let fee = U256::from(unsigned_tx.gas_limit) * U256::from(unsigned_tx.gas_price);
+
+    if !wallet.chain.is_op_stack() {
+        return Ok(fee);
+    }
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    // Priced with the largest nonce, the transaction sent is no larger
+    let mut tx_data = Vec::new();
+
+    RawTransaction {
+        nonce: u64::MAX,
+        ..unsigned_tx
+    }
+    .encode(&mut tx_data);
+
+    let l1_fee = l1_fee(provider.as_ref(), tx_data).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to quote the L1 data fee")
+    })?;
+
+    Ok(fee + l1_fee)
+}
+
+/// Dry-runs the transaction from the wallet address, wallets without a stored address are
+/// refused rather than signed for unsimulated
+async fn simulate_transaction(
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    tx: &RawTransaction,
+) -> Result<Simulation> {
+    let address = wallet.address.as_ref().ok_or_else(|| {
+        ApiError::unprocessable(
+            "missing_address",
+            "Wallet has no address to simulate the transaction from",
+        )
+    })?;
+
+    let from: Address = address
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let call = CallRequest {
+        from: Some(from),
+        to: Some(tx.to),
+        value: Some(tx.value),
+        gas: Some(tx.gas_limit),
+        gas_price: Some(tx.gas_price.into()),
+        input: TransactionInput::new(tx.data.clone()),
+        ..Default::default()
+    };
+
+    simulate(provider.as_ref(), call).await.map_err(|err| {
+        log::error!("{err}");
+        ApiError::internal("Failed to simulate transaction")
+    })
+}
+
+/// Reports whether the transaction would succeed without signing or broadcasting it
+pub async fn simulate_tx(
+    req: HttpRequest,
+    data: web::Json<TransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let transfer = resolve_transaction(&wallet, network, &data).await?;
+
+    // The nonce is not part of the simulated call
+    let unsigned_tx = unsigned_transaction(&wallet, &transfer, 0)?;
+
+    let simulation = simulate_transaction(&wallet, network, &unsigned_tx).await?;
+
+    Ok(HttpResponse::Ok().json(simulation))
+}
+
+/// Gas, fees and total cost of the transaction `send_tx` would sign, for a confirmation
+/// screen
+pub async fn quote_tx(
+    req: HttpRequest,
+    data: web::Query<TransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&repository, path.into_inner(), user_id).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let from: Address = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to quote from"))?
+        .parse()
+        .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+    let transfer = resolve_transaction(&wallet, network, &data).await?;
+
+    // Quoted with the gas limit and price `send_tx` signs
+    let unsigned_tx = unsigned_transaction(&wallet, &transfer, 0)?;
+
+    // OP Stack rollups price the unsigned transaction posted to L1, the nonce barely
+    // changes its size
+    let l1_data = wallet.chain.is_op_stack().then(|| {
+        let mut tx_data = Vec::new();
+
+        unsigned_tx.encode(&mut tx_data);
+
+        tx_data
+    });
+
+    let quote = quote(
+        provider.as_ref(),
+        from,
+        unsigned_tx.gas_limit,
+        unsigned_tx.gas_price,
+        unsigned_tx.value,
+        l1_data,
+    )
+    .await;
+
+    match quote {
+        Ok(quote) => Ok(HttpResponse::Ok().json(quote)),
+        Err(err) => {
+            log::error!("{err}");
+            Err(ApiError::internal("Failed to quote transaction"))
+        }
+    }
+}
+
+/// Why a transaction was not broadcast, it is already marked `failed` unless `Unsent`
+#[derive(Clone)]
+pub(super) enum SendFailure {
+    /// A participant identified the faulty parties of the signing round
+    Aborted(Vec<MpcFailureResponse>),
+    /// Participants failed the signing round, e.g. busy or timed out
+    Signing(Vec<Status>),
+    /// Screening blocked the recipient, with the reason of the provider
+    Blocked(String),
+    /// The provider could not be reached after signing, the transaction stays `signed` so
+    /// its broadcast can be retried
+    Unsent(i32),
+    Internal(&'static str),
+    /// Failure of the recorded `sign` operation with this id
+    Operation(i32, Box<SendFailure>),
+}
+
+impl SendFailure {
+    pub(super) fn message(&self) -> &'static str {
+        match self {
+            SendFailure::Aborted(_) => "Transaction signing aborted by a faulty participant",
+            SendFailure::Signing(_) => "Failed to sign transaction",
+            SendFailure::Blocked(_) => "Recipient blocked by screening",
+            SendFailure::Unsent(_) => "Transaction signed but not broadcast",
+            SendFailure::Internal(message) => message,
+            SendFailure::Operation(_, failure) => failure.message(),
+        }
+    }
+}
+
+impl From<SendFailure> for ApiError {
+    fn from(failure: SendFailure) -> Self {
+        let error = failure.message();
+
+        match failure {
+            SendFailure::Aborted(failures) => ApiError::internal(error)
+                .with_code("signing_aborted")
+                .with("failures", failures),
+            SendFailure::Signing(errors) => {
+                let results: Vec<Result<(), Status>> = errors.into_iter().map(Err).collect();
+
+                participant_error(&results, "signing_failed", error)
+            }
+            SendFailure::Blocked(reason) => {
+                ApiError::forbidden(format!("{error}: {reason}")).with_code("recipient_blocked")
+            }
+            // `id` is the signed transaction whose broadcast can be retried
+            SendFailure::Unsent(id) => {
+                ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "transaction_unsent", error)
+                    .with("id", id)
+            }
+            SendFailure::Internal(message) => ApiError::internal(message),
+            SendFailure::Operation(id, failure) => {
+                ApiError::from(*failure).with("operation_id", id)
+            }
+        }
+    }
+}
+
+/// Nonces of `count` new transactions of the wallet or its account, from the node's pending
+/// count up, skipping the nonces held by transactions that may not have reached the node yet.
+/// The nonces of failed transactions are free again, so a failure leaves no gap
+async fn allocate_nonces(
+    repository: &TransactionRepository<'_>,
+    wallet: &WalletModel,
+    account_id: Option<i32>,
+    network: &ChainEntry,
+    count: usize,
+) -> Result<Vec<u64>> {
+    let pending = match (&wallet.address, &network.provider) {
+        (Some(address), Some(provider)) => {
+            let address: Address = address
+                .parse()
+                .map_err(|_| ApiError::internal("Invalid wallet address"))?;
+
+            provider
+                .get_transaction_count(address)
+                .pending()
+                .await
+                .map_err(|err| {
+                    log::error!("{err}");
+                    ApiError::internal("Failed to allocate nonce")
+                })?
+        }
+        _ => 0,
+    };
+
+    let held = repository
+        .find_held_nonces(wallet.id, account_id, pending as i64)
+        .await
+        .map_err(|_| ApiError::internal("Failed to allocate nonce"))?;
+
+    Ok(free_nonces(pending, &held, count))
+}
+
+/// First `count` nonces from `from` that aren't `held`
+fn free_nonces(from: u64, held: &[i64], count: usize) -> Vec<u64> {
+    (from..)
+        .filter(|nonce| !held.contains(&(*nonce as i64)))
+        .take(count)
+        .collect()
+}
+
+/// Creates the `pending` transactions with the next free nonces, in request order.
+/// Transactions of an account are sent with the account view of the wallet and its nonces
+async fn create_transactions(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    account_id: Option<i32>,
+    network: &ChainEntry,
+    transfers: &[Transfer],
+) -> Result<Vec<(TransactionModel, RawTransaction)>> {
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| ApiError::internal("Failed to allocate nonce"))?;
+
+    // The wallet row stays locked until the transactions holding the nonces are committed
+    WalletRepository::new_with_transaction(&txn)
+        .lock(wallet.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to allocate nonce"))?;
+
+    let repository = TransactionRepository::new_with_transaction(&txn);
+
+    let nonces = allocate_nonces(&repository, wallet, account_id, network, transfers.len()).await?;
+
+    let mut transactions = Vec::new();
+
+    for (nonce, data) in nonces.into_iter().zip(transfers) {
+        let unsigned_tx = unsigned_transaction(wallet, data, nonce)?;
+
+        let contract_address = match data.to {
+            Some(_) => None,
+            None => Some(deployer(wallet)?.create(nonce)),
+        };
+
+        // Every step is persisted so failed and pending transactions stay visible
+        let transaction_model = repository
+            .create(TransactionActiveModel {
+                user_id: Set(wallet.user_id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Pending),
+                chain: Set(Some(wallet.chain.clone())),
+                value: Set(Some(data.value.to_string())),
+                to_address: Set(data.recipient().map(|to| to.to_string())),
+                contract_address: Set(contract_address.map(|address| address.to_string())),
+                token_contract: Set(data
+                    .token
+                    .as_ref()
+                    .and(data.to)
+                    .map(|contract| contract.to_string())),
+                token_id: Set(data
+                    .token
+                    .as_ref()
+                    .and_then(|token| token.token_id)
+                    .map(|token_id| token_id.to_string())),
+                nonce: Set(Some(nonce as i64)),
+                account_id: Set(account_id),
+                memo: Set(data.annotation.memo.clone()),
+                metadata: Set(data.annotation.metadata.clone()),
+                ..Default::default()
+            })
+            .await
+            .map_err(|_| ApiError::internal("Failed to create transaction"))?;
+
+        transactions.push((transaction_model, unsigned_tx));
+    }
+
+    txn.commit()
+        .await
+        .map_err(|_| ApiError::internal("Failed to create transaction"))?;
+
+    Ok(transactions)
+}
+
+/// Screens the recipient of the pending transaction and records the verdict on it, a
+/// blocked recipient or an unavailable provider fails the transaction before signing
+pub(super) async fn screen_transaction(
+    repository: &TransactionRepository<'_>,
+    screener: &Screener,
+    wallet: &WalletModel,
+    transaction_model: TransactionModel,
+    recipient: &str,
+) -> Result<TransactionModel, SendFailure> {
+    let screening = match screener.screen(&wallet.chain, recipient).await {
+        Ok(screening) => screening,
+        Err(err) => {
+            log::error!(
+                "Failed to screen transaction {}: {err}",
+                transaction_model.id
+            );
+
+            let failure = SendFailure::Internal("Failed to screen recipient");
+
+            fail_transaction(repository, &transaction_model, failure.message()).await;
+
+            return Err(failure);
+        }
+    };
+
+    let transaction_model = repository
+        .update_screening(
+            &transaction_model,
+            screening.verdict,
+            screening.reason.clone(),
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to screen recipient"))?;
+
+    if screening.is_blocked() {
+        let failure = SendFailure::Blocked(screening.reason.unwrap_or_default());
+
+        fail_transaction(repository, &transaction_model, failure.message()).await;
+
+        return Err(failure);
+    }
+
+    Ok(transaction_model)
+}
+
+/// Signature of a signing round, as `(r, s, v)`
+type RoundSignature = (Vec<u8>, Vec<u8>, u32);
+
+/// Data signed by a signing round
+pub(super) enum SigningData {
+    /// Signed as is with FROST, ECDSA participants hash it first
+    Raw(Vec<u8>),
+    /// Raw data of one input of a transaction signing several of them
+    Input { data: Vec<u8>, index: u32 },
+    /// 32-byte digest ECDSA participants sign as is
+    Digest(B256),
+    /// EIP-155 hash of the unsigned transaction, sent with its payload so participants can
+    /// check it against their signing policy
+    Transaction { digest: B256, payload: Vec<u8> },
+}
+
+impl SigningData {
+    /// `data`, `prehashed`, `payload` and `item` of the participants' request
+    fn into_parts(self) -> (Vec<u8>, bool, Vec<u8>, u32) {
+        match self {
+            SigningData::Raw(data) => (data, false, Vec::new(), 0),
+            SigningData::Input { data, index } => (data, false, Vec::new(), index),
+            SigningData::Digest(digest) => (digest.to_vec(), true, Vec::new(), 0),
+            SigningData::Transaction { digest, payload } => (digest.to_vec(), true, payload, 0),
+        }
+    }
+}
+
+/// First healthy participants holding a share and enough to sign, sorted by keygen index so
+/// results line up with the signing indexes
+/// Participants the app calls to sign and the keygen indexes of all signers of the round,
+/// which takes the user's device along with the co-signing participant for a co-signed wallet
+async fn round_signers(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+) -> Option<(Vec<Signer>, Vec<u32>)> {
+    if wallet.cosigner {
+        let (signer, device) = cosigning_party(participants, wallet.id).await?;
+
+        let mut indexes = vec![signer.keygen_index, device];
+        indexes.sort_unstable();
+
+        return Some((vec![signer], indexes));
+    }
+
+    let available = signing_parties(participants, wallet.id).await;
+
+    let mut signers = select_signers(available, SIGNING_THRESHOLD)?;
+
+    signers.sort_by_key(|signer| signer.keygen_index);
+
+    let indexes = signers.iter().map(|signer| signer.keygen_index).collect();
+
+    Some((signers, indexes))
+}
+
+/// Signing index of each signer, its position among the keygen indexes of the round
+fn signing_indexes(signers: &[Signer], indexes: &[u32]) -> Vec<usize> {
+    signers
+        .iter()
+        .map(|signer| {
+            indexes
+                .iter()
+                .position(|index| *index == signer.keygen_index)
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Failure of a signing round that didn't complete, identified aborts are recorded
+async fn round_failure<T>(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    execution_id: &Uuid,
+    reporters: &[usize],
+    results: Vec<Result<T, Status>>,
+) -> SendFailure {
+    let failures = aborts(&results, reporters);
+
+    if failures.is_empty() {
+        SendFailure::Signing(results.into_iter().filter_map(Result::err).collect())
+    } else {
+        record_aborts(db, wallet.id, execution_id, "signing", &failures).await;
+
+        SendFailure::Aborted(failures)
+    }
+}
+
+/// Records `signing` as a `sign` operation of the transaction, its failure names the operation.
+/// Every signing round is reported to the SIEM
+async fn sign_operation<T>(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    transaction_id: Option<i32>,
+    signing: impl Future<Output = Result<T, SendFailure>>,
+) -> Result<T, SendFailure> {
+    let operations = OperationRepository::new_with_connection(db);
+
+    // The signing goes ahead unrecorded rather than failing on the operations table
+    let operation = operations
+        .start(
+            wallet.user_id,
+            wallet.id,
+            transaction_id,
+            OperationKind::Sign,
+        )
+        .await
+        .inspect_err(|err| log::error!("Failed to record signing operation: {err}"))
+        .ok();
+
+    let signed = signing.await;
+
+    let error = signed
+        .as_ref()
+        .err()
+        .map(|failure| ApiError::from(failure.clone()));
+
+    let mut event = SecurityEvent::new("wallet.signing", Outcome::of(&signed))
+        .user(wallet.user_id)
+        .wallet(wallet.id)
+        .details(serde_json::json!({
+            "chain": wallet.chain,
+            "error": error.as_ref().map(|error| error.problem().code),
+        }));
+
+    if let Some(transaction_id) = transaction_id {
+        event = event.resource("transaction", transaction_id);
+    }
+
+    siem::emit(event);
+
+    let Some(operation) = operation else {
+        return signed;
+    };
+
+    finish_operation(&operations, &operation, error.as_ref()).await;
+
+    signed.map_err(|failure| SendFailure::Operation(operation.id, Box::new(failure)))
+}
+
+/// Runs the participants' signing round of `data` for the `signing` transaction and
+/// returns its `(r, s, v)`, the transaction is failed when the round doesn't complete
+///
+/// The round is run by the first healthy participants holding a share, any threshold of
+/// them can sign.
+pub(super) async fn sign_with_participants(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction_model: &TransactionModel,
+    data: SigningData,
+) -> Result<RoundSignature, SendFailure> {
+    sign_operation(
+        db,
+        wallet,
+        Some(transaction_model.id),
+        run_signing(db, participants, wallet, network, transaction_model, data),
+    )
+    .await
+}
+
+/// Derivation path of the key signing the transaction, the one of its account or else the
+/// empty path of the wallet key. `None` when the account can't be read
+async fn signing_path(
+    db: &DatabaseConnection,
+    transaction_model: &TransactionModel,
+) -> Option<Vec<u32>> {
+    let Some(account_id) = transaction_model.account_id else {
+        return Some(Vec::new());
+    };
+
+    WalletAccountRepository::new_with_connection(db)
+        .find_by_id(account_id)
+        .await
+        .inspect_err(|err| log::error!("Failed to read account {account_id}: {err}"))
+        .ok()
+        .flatten()
+        .map(|account| account.derivation_path())
+}
+
+async fn run_signing(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction_model: &TransactionModel,
+    data: SigningData,
+) -> Result<RoundSignature, SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    // Must be unique for all participants
+    let execution_id = Uuid::new_v4();
+    let room_token = rooms::room_token(&execution_id);
+
+    let Some(derivation_path) = signing_path(db, transaction_model).await else {
+        let failure = SendFailure::Internal("Failed to read the account of the transaction");
+
+        fail_transaction(
+            &transaction_repository,
+            transaction_model,
+            failure.message(),
+        )
+        .await;
+
+        return Err(failure);
+    };
+
+    let Some((signers, signer_indexes)) = round_signers(participants, wallet).await else {
+        let failure = SendFailure::Signing(vec![Status::unavailable(
+            "Not enough healthy participants to sign",
+        )]);
+
+        fail_transaction(
+            &transaction_repository,
+            transaction_model,
+            failure.message(),
+        )
+        .await;
+
+        return Err(failure);
+    };
+
+    let (tx_data, prehashed, payload, item) = data.into_parts();
+
+    let message = SignMessage {
+        tx_id: transaction_model.id,
+        wallet_id: wallet.id,
+        execution_id: execution_id.as_bytes().to_vec(),
+        chain: wallet.chain.clone().into(),
+        data: tx_data,
+        namespace: wallet.namespace.clone(),
+        room_token,
+        chain_id: network.config.chain_id,
+        scheme: wallet.signature_scheme().into(),
+        prehashed,
+        signers: signer_indexes,
+        payload,
+        item,
+        derivation_path,
+    };
+
+    // The device of a co-signed wallet signs along with the co-signing participant
+    let _ceremony = wallet.cosigner.then(|| {
+        cosigner::open(
+            wallet.user_id,
+            execution_id,
+            CeremonyMessage::Signing(message.clone()),
+        )
+    });
+
+    let policy = RetryPolicy::current();
+
+    let futures = signers.iter().map(|signer| {
+        let message = &message;
+
+        async move {
+            policy
+                .run(|| participants.sign(signer.participant, message.clone()))
+                .await
+        }
+    });
+
+    let results = join_all(futures).await;
+
+    let is_signed = results.iter().all(|res| res.is_ok());
+
+    let signatures = results
+        .iter()
+        .filter_map(|res| res.as_ref().ok())
+        .map(|s| (s.r.clone(), s.s.clone(), s.v))
+        .collect::<Vec<_>>();
+
+    let signature = signatures.first().cloned().filter(|_| is_signed);
+
+    let Some((r, s, v)) = signature else {
+        let reporters = signing_indexes(&signers, &message.signers);
+        let failure = round_failure(db, wallet, &execution_id, &reporters, results).await;
+
+        fail_transaction(
+            &transaction_repository,
+            transaction_model,
+            failure.message(),
+        )
+        .await;
+
+        return Err(failure);
+    };
+
+    // Each participant combines the same signature, a different one means a participant
+    // signed something else or is faulty
+    if signatures
+        .iter()
+        .any(|other| other != &(r.clone(), s.clone(), v))
+    {
+        let failure = SendFailure::Internal("Participants returned different signatures");
+
+        fail_transaction(
+            &transaction_repository,
+            transaction_model,
+            failure.message(),
+        )
+        .await;
+
+        return Err(failure);
+    }
+
+    Ok((r, s, v))
+}
+
+/// Signatures every participant agreed on, `None` when a batch differs or misses items
+fn agreed_signatures(
+    batches: &[Vec<RoundSignature>],
+    expected: usize,
+) -> Option<&Vec<RoundSignature>> {
+    let first = batches.first()?;
+
+    (first.len() == expected && batches.iter().all(|batch| batch == first)).then_some(first)
+}
+
+/// Signs the EIP-155 `payloads` of the batch `transactions` in one participants' session and
+/// returns their `(r, s, v)` in order, every transaction is failed when the session doesn't
+/// complete. The session is one `sign` operation of the first transaction
+async fn sign_batch_with_participants(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transactions: &[TransactionModel],
+    payloads: Vec<Vec<u8>>,
+) -> Result<Vec<RoundSignature>, SendFailure> {
+    sign_operation(
+        db,
+        wallet,
+        transactions.first().map(|transaction| transaction.id),
+        run_batch_signing(db, participants, wallet, network, transactions, payloads),
+    )
+    .await
+}
+
+async fn run_batch_signing(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transactions: &[TransactionModel],
+    payloads: Vec<Vec<u8>>,
+) -> Result<Vec<RoundSignature>, SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let fail_all = |failure: SendFailure| async {
+        for transaction_model in transactions {
+            fail_transaction(
+                &transaction_repository,
+                transaction_model,
+                failure.message(),
+            )
+            .await;
+        }
+
+        Err(failure)
+    };
+
+    let execution_id = Uuid::new_v4();
+    let room_token = rooms::room_token(&execution_id);
+
+    let Some((signers, signer_indexes)) = round_signers(participants, wallet).await else {
+        return fail_all(SendFailure::Signing(vec![Status::unavailable(
+            "Not enough healthy participants to sign",
+        )]))
+        .await;
+    };
+
+    // The first transaction names the session rooms, its id is unique to the batch
+    let message = SignBatchMessage {
+        batch_id: transactions.first().map_or(0, |transaction| transaction.id),
+        wallet_id: wallet.id,
+        execution_id: execution_id.as_bytes().to_vec(),
+        chain: wallet.chain.clone().into(),
+        data: payloads
+            .iter()
+            .map(|payload| keccak256(payload).to_vec())
+            .collect(),
+        namespace: wallet.namespace.clone(),
+        room_token,
+        chain_id: network.config.chain_id,
+        scheme: wallet.signature_scheme().into(),
+        prehashed: true,
+        signers: signer_indexes,
+        payloads,
+        tx_ids: transactions
+            .iter()
+            .map(|transaction| transaction.id)
+            .collect(),
+    };
+
+    let _ceremony = wallet.cosigner.then(|| {
+        cosigner::open(
+            wallet.user_id,
+            execution_id,
+            CeremonyMessage::BatchSigning(message.clone()),
+        )
+    });
+
+    let policy = RetryPolicy::current();
+
+    let futures = signers.iter().map(|signer| {
+        let message = &message;
+
+        async move {
+            policy
+                .run(|| participants.sign_batch(signer.participant, message.clone()))
+                .await
+        }
+    });
+
+    let results = join_all(futures).await;
+
+    if results.iter().any(|res| res.is_err()) {
+        let reporters = signing_indexes(&signers, &message.signers);
+        let failure = round_failure(db, wallet, &execution_id, &reporters, results).await;
+
+        return fail_all(failure).await;
+    }
+
+    let batches = results
+        .iter()
+        .filter_map(|res| res.as_ref().ok())
+        .map(|response| {
+            response
+                .signatures
+                .iter()
+                .map(|s| (s.r.clone(), s.s.clone(), s.v))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    match agreed_signatures(&batches, transactions.len()) {
+        Some(signatures) => Ok(signatures.clone()),
+        None => {
+            fail_all(SendFailure::Internal(
+                "Participants returned different signatures",
+            ))
+            .await
+        }
+    }
+}
+
+/// Signature of `digest` by `address` with the parity recovering it, `s` is flipped to the
+/// lower half that EVM chains accept. `None` when the signature isn't the address' one
+fn recover_signature(address: Address, digest: &B256, r: &[u8], s: &[u8]) -> Option<Signature> {
+    if r.len() != 32 || s.len() != 32 {
+        return None;
+    }
+
+    let signature = Signature::new(U256::from_be_slice(r), U256::from_be_slice(s), false);
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    [false, true]
+        .into_iter()
+        .map(|parity| signature.with_parity(parity))
+        .find(|signature| {
+            signature
+                .recover_address_from_prehash(digest)
+                .is_ok_and(|signer| signer == address)
+        })
+}
+
+/// Checks the participants' signature of `digest` recovers the wallet address, the
+/// transaction is failed otherwise so nothing is broadcast for another key
+async fn verify_signature(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    transaction_model: &TransactionModel,
+    digest: &B256,
+    r: &[u8],
+    s: &[u8],
+) -> Result<Signature, SendFailure> {
+    let signature = wallet
+        .address
+        .as_deref()
+        .and_then(|address| address.parse::<Address>().ok())
+        .and_then(|address| recover_signature(address, digest, r, s));
+
+    match signature {
+        Some(signature) => Ok(signature),
+        None => {
+            let failure = SendFailure::Internal("Signature does not match the wallet address");
+
+            fail_transaction(
+                &TransactionRepository::new_with_connection(db),
+                transaction_model,
+                failure.message(),
+            )
+            .await;
+
+            Err(failure)
+        }
+    }
+}
+
+/// Signs the pending transaction with the participants and broadcasts it
+async fn sign_and_broadcast(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction_model: &TransactionModel,
+    unsigned_tx: &RawTransaction,
+) -> Result<(TransactionModel, B256), SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let transaction_model = transaction_repository
+        .update_status(
+            transaction_model,
+            TransactionStatus::Signing,
+            StatusDetails::default(),
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    let payload = unsigned_tx.signing_payload(network.config.chain_id);
+
+    let (r, s, _) = sign_with_participants(
+        db,
+        participants,
+        wallet,
+        network,
+        &transaction_model,
+        SigningData::Transaction {
+            digest: keccak256(&payload),
+            payload,
+        },
+    )
+    .await?;
+
+    let (transaction_model, raw_tx) =
+        store_signed(db, wallet, network, &transaction_model, unsigned_tx, &r, &s).await?;
+
+    broadcast(db, network, &transaction_model, &raw_tx).await
+}
+
+/// Verifies the participants' `(r, s)` of the `signing` transaction and marks it `signed`
+/// with its raw transaction, returned for the broadcast
+async fn store_signed(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction_model: &TransactionModel,
+    unsigned_tx: &RawTransaction,
+    r: &[u8],
+    s: &[u8],
+) -> Result<(TransactionModel, Vec<u8>), SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let chain_id = network.config.chain_id;
+    let digest = unsigned_tx.signing_hash(chain_id);
+
+    let signature = verify_signature(db, wallet, transaction_model, &digest, r, s).await?;
+
+    let v = u32::try_from(chain_id * 2 + 35 + u64::from(signature.v()))
+        .map_err(|_| SendFailure::Internal("Chain id is too large"))?;
+
+    let signed_tx = SignedTransaction {
+        nonce: unsigned_tx.nonce,
+        gas_price: unsigned_tx.gas_price,
+        gas_limit: unsigned_tx.gas_limit,
+        to: unsigned_tx.to,
+        value: unsigned_tx.value,
+        data: unsigned_tx.data.clone(),
+        v,
+        r: signature.r(),
+        s: signature.s(),
+    };
+
+    let mut rlp_buf = Vec::new();
+
+    signed_tx.encode(&mut rlp_buf);
+
+    let transaction_model = transaction_repository
+        .update_status(
+            transaction_model,
+            TransactionStatus::Signed,
+            StatusDetails {
+                raw_tx: Some(format!("0x{}", hex::encode(&rlp_buf))),
+                signature: Some(SignatureDetails {
+                    r: B256::from(signature.r()).to_string(),
+                    s: B256::from(signature.s()).to_string(),
+                    v: i64::from(v),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    Ok((transaction_model, rlp_buf))
+}
+
+/// Broadcasts the `signed` transaction, it stays `signed` when the provider can't be reached
+/// so the stored raw transaction can be broadcast again without another signing round
+async fn broadcast(
+    db: &DatabaseConnection,
+    network: &ChainEntry,
+    transaction_model: &TransactionModel,
+    raw_tx: &[u8],
+) -> Result<(TransactionModel, B256), SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or(SendFailure::Internal("Chain not supported"))?;
+
+    let tx_hash = match provider.send_raw_transaction(raw_tx).await {
+        Ok(tx) => *tx.tx_hash(),
+        Err(RpcError::Transport(err)) => {
+            log::error!("{err}");
+            return Err(SendFailure::Unsent(transaction_model.id));
+        }
+        Err(err) => {
+            // A broadcast that timed out before may have reached the node anyway
+            let tx_hash = keccak256(raw_tx);
+
+            if let Ok(Some(_)) = provider.get_transaction_by_hash(tx_hash).await {
+                tx_hash
+            } else {
+                log::error!("{err}");
+                fail_transaction(&transaction_repository, transaction_model, &err.to_string())
+                    .await;
+                return Err(SendFailure::Internal("Failed to send transaction"));
+            }
+        }
+    };
+
+    // Confirmations are tracked by the receipt poller
+    let transaction_model = transaction_repository
+        .update_status(
+            transaction_model,
+            TransactionStatus::Broadcast,
+            StatusDetails {
+                tx_hash: Some(tx_hash.to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to send transaction"))?;
+
+    Ok((transaction_model, tx_hash))
+}
+
+/// Retries the broadcast of an EVM transaction left `signed`, e.g. after a provider outage,
+/// with its stored raw transaction
+pub async fn broadcast_tx(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let (wallet_id, transaction_id) = path.into_inner();
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+    let transaction_repository = TransactionRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    if !wallet.chain.is_evm() {
+        return Err(ApiError::bad_request(
+            "Broadcast retries are only supported on EVM chains",
+        ));
+    }
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let transaction_model = transaction_repository
+        .find_by_id(transaction_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrive the transaction"))?
+        .filter(|transaction| transaction.wallet_id == wallet.id)
+        .ok_or_else(|| ApiError::not_found("transaction_not_found", "Transaction not found"))?;
+
+    if transaction_model.status != TransactionStatus::Signed {
+        return Err(ApiError::conflict(
+            "Only signed transactions can be broadcast",
+        ));
+    }
+
+    let raw_tx = transaction_model
+        .raw_tx
+        .as_deref()
+        .and_then(|raw_tx| hex::decode(raw_tx.trim_start_matches("0x")).ok())
+        .ok_or_else(|| ApiError::conflict("Transaction has no raw transaction"))?;
+
+    let (transaction_model, tx_hash) =
+        match broadcast(&db, network, &transaction_model, &raw_tx).await {
+            Ok(sent) => sent,
+            Err(failure) => return Err(failure.into()),
+        };
+
+    Ok(HttpResponse::Ok().json(TransactionResponse {
+        id: transaction_model.id,
+        hash: tx_hash.to_string(),
+        status: transaction_model.status,
+        to: transaction_model.to_address,
+        contract_address: transaction_model.contract_address,
+        ens_name: None,
+        explorer_url: explorer_url(network, &tx_hash),
+    }))
+}
+
+/// Gas price of a replacement, `bump_percent` above the stuck one and rounded up so it is
+/// always raised
+fn bumped_gas_price(gas_price: u64, bump_percent: u64) -> u64 {
+    let bumped = (u128::from(gas_price) * u128::from(100 + bump_percent)).div_ceil(100);
+
+    u64::try_from(bumped)
+        .unwrap_or(u64::MAX)
+        .max(gas_price.saturating_add(1))
+}
+
+/// Replaces the stuck EVM `transaction` with one of the same nonce and payload whose gas
+/// price is bumped, or the current gas price of the chain when that is higher. The
+/// replacement is a new transaction pointing at the stuck one, failed attempts stay recorded
+pub async fn replace_transaction(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transaction: &TransactionModel,
+    bump_percent: u64,
+) -> anyhow::Result<TransactionModel> {
+    let raw_tx = transaction
+        .raw_tx
+        .as_deref()
+        .and_then(|raw_tx| hex::decode(raw_tx.trim_start_matches("0x")).ok())
+        .ok_or_else(|| anyhow::anyhow!("Transaction has no raw transaction"))?;
+
+    let signed_tx = SignedTransaction::decode(&mut raw_tx.as_slice())?;
+
+    // The replacement of an account transaction is sent from the account too
+    let wallet = &match transaction.account_id {
+        Some(account_id) => WalletAccountRepository::new_with_connection(db)
+            .find_by_id(account_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Account {account_id} not found"))?
+            .wallet(wallet),
+        None => wallet.clone(),
+    };
+
+    let provider = network
+        .provider
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Chain not supported"))?;
+
+    let current = u64::try_from(provider.get_gas_price().await?).unwrap_or(u64::MAX);
+
+    let unsigned_tx = RawTransaction {
+        nonce: signed_tx.nonce,
+        gas_price: bumped_gas_price(signed_tx.gas_price, bump_percent).max(current),
+        gas_limit: signed_tx.gas_limit,
+        to: signed_tx.to,
+        value: signed_tx.value,
+        data: signed_tx.data,
+    };
+
+    // The recipient and payload are unchanged, so is the screening verdict
+    let replacement = TransactionRepository::new_with_connection(db)
+        .create(TransactionActiveModel {
+            user_id: Set(transaction.user_id),
+            wallet_id: Set(transaction.wallet_id),
+            status: Set(TransactionStatus::Pending),
+            chain: Set(transaction.chain.clone()),
+            value: Set(transaction.value.clone()),
+            to_address: Set(transaction.to_address.clone()),
+            contract_address: Set(transaction.contract_address.clone()),
+            token_contract: Set(transaction.token_contract.clone()),
+            token_id: Set(transaction.token_id.clone()),
+            nonce: Set(transaction.nonce),
+            screening_verdict: Set(transaction.screening_verdict),
+            screening_reason: Set(transaction.screening_reason.clone()),
+            replaces_id: Set(Some(transaction.id)),
+            account_id: Set(transaction.account_id),
+            memo: Set(transaction.memo.clone()),
+            metadata: Set(transaction.metadata.clone()),
+            ..Default::default()
+        })
+        .await?;
+
+    log::info!(
+        "Replacing transaction {} with {} at a gas price of {}",
+        transaction.id,
+        replacement.id,
+        unsigned_tx.gas_price
+    );
+
+    match sign_and_broadcast(
+        db,
+        participants,
+        wallet,
+        network,
+        &replacement,
+        &unsigned_tx,
+    )
+    .await
+    {
+        Ok((replacement, _)) => Ok(replacement),
+        Err(failure) => Err(anyhow::anyhow!(failure.message())),
+    }
+}
+
+fn explorer_url(network: &ChainEntry, tx_hash: &impl std::fmt::Display) -> Option<String> {
+    network
+        .config
+        .explorer_url
+        .as_ref()
+        .map(|url| format!("{}/tx/{tx_hash}", url.trim_end_matches('/')))
+}
+
+pub async fn send_tx(
+    req: HttpRequest,
+    data: web::Json<TransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let wallet_id = path.into_inner();
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, wallet_id, user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_transaction_quota(&req, &db, user_id, 1).await?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    if let Some(release_at) = withdrawal_release_at(&wallet, data.value) {
+        let withdrawal = hold_withdrawal(&db, &wallet, None, &data, release_at).await?;
+
+        return Ok(HttpResponse::Accepted().json(withdrawal));
+    }
+
+    let response = match wallet.chain {
+        Chain::Solana => {
+            send_solana_tx(
+                &db,
+                participants.get_ref(),
+                &screener,
+                &wallet,
+                network,
+                &data,
+            )
+            .await?
+        }
+        _ => {
+            send_evm_tx(
+                &db,
+                participants.get_ref(),
+                &screener,
+                &wallet,
+                None,
+                network,
+                &data,
+            )
+            .await?
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Sends a transaction of the wallet outside of a request, such as a recurring payment run,
+/// subject to the same state, quota, policy and screening checks as `send_tx`. Transfers the
+/// withdrawal delay would hold are rejected rather than held
+pub async fn send_wallet_tx(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    quotas: &QuotaConfig,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    data: &TransactionRequest,
+) -> Result<TransactionResponse> {
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_daily_transactions(db, quotas, wallet.user_id, 1).await?;
+
+    ensure_signing_velocity(db, wallet, 1).await?;
+
+    ensure_not_delayed(wallet, std::slice::from_ref(data))?;
+
+    match wallet.chain {
+        Chain::Solana => send_solana_tx(db, participants, screener, wallet, network, data).await,
+        _ => send_evm_tx(db, participants, screener, wallet, None, network, data).await,
+    }
+}
+
+/// Sends a transaction from a sub-account of the wallet, signed with the child key of the
+/// account and with nonces of its own
+pub async fn send_account_tx(
+    req: HttpRequest,
+    data: web::Json<TransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let (wallet_id, account_id) = path.into_inner();
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        wallet_id,
+        user_id,
+    )
+    .await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    let account = find_wallet_account(&db, &wallet, account_id).await?;
+
+    ensure_transaction_quota(&req, &db, user_id, 1).await?;
+
+    ensure_signing_velocity(&db, &wallet, 1).await?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    if let Some(release_at) = withdrawal_release_at(&wallet, data.value) {
+        let withdrawal = hold_withdrawal(&db, &wallet, Some(account.id), &data, release_at).await?;
+
+        return Ok(HttpResponse::Accepted().json(withdrawal));
+    }
+
+    let response = send_evm_tx(
+        &db,
+        participants.get_ref(),
+        &screener,
+        &wallet,
+        Some(&account),
+        network,
+        &data,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Simulates, screens, signs and broadcasts a transaction of an EVM wallet, sent from the
+/// `account` of the wallet when set
+pub(super) async fn send_evm_tx(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    wallet: &WalletModel,
+    account: Option<&WalletAccountModel>,
+    network: &ChainEntry,
+    data: &TransactionRequest,
+) -> Result<TransactionResponse> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let wallet = &account.map_or_else(|| wallet.clone(), |account| account.wallet(wallet));
+
+    let transfer = resolve_transaction(wallet, network, data).await?;
+
+    ensure_transfers_allowed(db, wallet, std::slice::from_ref(&transfer)).await?;
+
+    // Rejected before the signing round
+    let unsigned_tx = unsigned_transaction(wallet, &transfer, 0)?;
+
+    let simulation = simulate_transaction(wallet, network, &unsigned_tx).await?;
+
+    if !simulation.success {
+        let code = rejection_code(simulation.revert_reason.as_deref().unwrap_or_default());
+
+        return Err(
+            ApiError::unprocessable(code, "Transaction would fail").with("simulation", simulation)
+        );
+    }
+
+    let mut transactions = create_transactions(
+        db,
+        wallet,
+        account.map(|account| account.id),
+        network,
+        std::slice::from_ref(&transfer),
+    )
+    .await?;
+
+    let (transaction_model, unsigned_tx) = transactions.remove(0);
+
+    // Deployments have no recipient to screen
+    let transaction_model = match unsigned_tx.to {
+        TxKind::Create => transaction_model,
+        TxKind::Call(to) => match screen_transaction(
+            &transaction_repository,
+            screener,
+            wallet,
+            transaction_model,
+            &to.to_string(),
+        )
+        .await
+        {
+            Ok(transaction_model) => transaction_model,
+            Err(failure) => return Err(failure.into()),
+        },
+    };
+
+    let sent = sign_and_broadcast(
+        db,
+        participants,
+        wallet,
+        network,
+        &transaction_model,
+        &unsigned_tx,
+    )
+    .await;
+
+    let (transaction_model, tx_hash) = match sent {
+        Ok(sent) => sent,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    Ok(TransactionResponse {
+        id: transaction_model.id,
+        hash: tx_hash.to_string(),
+        status: transaction_model.status,
+        to: transfer.to.map(|to| to.to_string()),
+        contract_address: transaction_model.contract_address,
+        ens_name: transfer.ens_name,
+        explorer_url: explorer_url(network, &tx_hash),
+    })
+}
+
+/// Sends a System Program transfer from a Solana wallet, the recent blockhash of the
+/// message takes the place of the nonce
+pub(super) async fn send_solana_tx(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    data: &TransactionRequest,
+) -> Result<TransactionResponse> {
+    let client = network
+        .solana
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("Chain not supported"))?;
+
+    let from = wallet
+        .address
+        .as_deref()
+        .ok_or_else(|| ApiError::bad_request("Wallet has no address to send from"))
+        .and_then(|address| {
+            parse_pubkey(address).ok_or_else(|| ApiError::internal("Invalid wallet address"))
+        })?;
+
+    let Some(Recipient::Pubkey(to)) = data.to else {
+        return Err(ApiError::bad_request("Recipient is not a Solana address"));
+    };
+
+    if data.data.is_some() {
+        return Err(ApiError::bad_request("Data is not supported on Solana"));
+    }
+
+    let lamports = u64::try_from(data.value)
+        .map_err(|_| ApiError::bad_request("Value exceeds the lamports range"))?;
+
+    let recipient = encode_base58(&to);
+
+    let annotation = Annotation::from_request(data)?;
+
+    ensure_whitelisted(db, wallet, [recipient.clone()]).await?;
+
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let transaction_model = transaction_repository
+        .create(TransactionActiveModel {
+            user_id: Set(wallet.user_id),
+            wallet_id: Set(wallet.id),
+            status: Set(TransactionStatus::Pending),
+            chain: Set(Some(wallet.chain.clone())),
+            value: Set(Some(lamports.to_string())),
+            to_address: Set(Some(recipient.clone())),
+            memo: Set(annotation.memo),
+            metadata: Set(annotation.metadata),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to create transaction"))?;
+
+    let transaction_model = match screen_transaction(
+        &transaction_repository,
+        screener,
+        wallet,
+        transaction_model,
+        &recipient,
+    )
+    .await
+    {
+        Ok(transaction_model) => transaction_model,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    let sent = sign_and_submit(
+        db,
+        participants,
+        wallet,
+        network,
+        client,
+        &transaction_model,
+        SolanaTransfer { from, to, lamports },
+    )
+    .await;
+
+    let (transaction_model, signature) = match sent {
+        Ok(sent) => sent,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    Ok(TransactionResponse {
+        id: transaction_model.id,
+        explorer_url: explorer_url(network, &signature),
+        hash: signature,
+        status: transaction_model.status,
+        to: Some(recipient),
+        contract_address: None,
+        ens_name: None,
+    })
+}
+
+/// Accounts and amount of a Solana transfer, the message is built once the blockhash is known
+struct SolanaTransfer {
+    from: [u8; 32],
+    to: [u8; 32],
+    lamports: u64,
+}
+
+/// Signs the transfer message of the pending transaction with FROST and submits it to the
+/// cluster, the transaction hash is the base58 signature
+async fn sign_and_submit(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    client: &SolanaClient,
+    transaction_model: &TransactionModel,
+    transfer: SolanaTransfer,
+) -> Result<(TransactionModel, String), SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let blockhash = match client.latest_blockhash().await {
+        Ok(blockhash) => blockhash,
+        Err(err) => {
+            log::error!("{err}");
+
+            let failure = SendFailure::Internal("Failed to fetch a recent blockhash");
+
+            fail_transaction(
+                &transaction_repository,
+                transaction_model,
+                failure.message(),
+            )
+            .await;
+
+            return Err(failure);
+        }
+    };
+
+    let message = transfer_message(&transfer.from, &transfer.to, transfer.lamports, &blockhash);
+
+    let transaction_model = transaction_repository
+        .update_status(
+            transaction_model,
+            TransactionStatus::Signing,
+            StatusDetails::default(),
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    let (r, s, _) = sign_with_participants(
+        db,
+        participants,
+        wallet,
+        network,
+        &transaction_model,
+        SigningData::Raw(message.clone()),
+    )
+    .await?;
+
+    let Ok(signature) = <[u8; 64]>::try_from([r, s].concat()) else {
+        let failure = SendFailure::Internal("Invalid signature returned by participants");
+
+        fail_transaction(
+            &transaction_repository,
+            &transaction_model,
+            failure.message(),
+        )
+        .await;
+
+        return Err(failure);
+    };
+
+    let raw_tx = signed_transaction(&message, &signature);
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signed,
+            StatusDetails {
+                raw_tx: Some(BASE64.encode(&raw_tx)),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    if let Err(err) = client.send_transaction(&raw_tx).await {
+        log::error!("{err}");
+        fail_transaction(
+            &transaction_repository,
+            &transaction_model,
+            &err.to_string(),
+        )
+        .await;
+        return Err(SendFailure::Internal("Failed to send transaction"));
+    }
+
+    // Finalization is tracked by the receipt poller
+    let tx_hash = encode_base58(&signature);
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Broadcast,
+            StatusDetails {
+                tx_hash: Some(tx_hash.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to send transaction"))?;
+
+    Ok((transaction_model, tx_hash))
+}
+
+/// Signs and broadcasts a call of a token contract, the account it is made for is screened
+/// instead of the contract
+pub(super) async fn send_contract_call(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    transfer: Transfer,
+) -> Result<HttpResponse> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let recipient = transfer
+        .recipient()
+        .ok_or_else(|| ApiError::internal("Contract call without recipient"))?;
+
+    let mut transactions =
+        create_transactions(db, wallet, None, network, std::slice::from_ref(&transfer)).await?;
+
+    let (transaction_model, unsigned_tx) = transactions.remove(0);
+
+    let transaction_model = match screen_transaction(
+        &transaction_repository,
+        screener,
+        wallet,
+        transaction_model,
+        &recipient.to_string(),
+    )
+    .await
+    {
+        Ok(transaction_model) => transaction_model,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    let sent = sign_and_broadcast(
+        db,
+        participants,
+        wallet,
+        network,
+        &transaction_model,
+        &unsigned_tx,
+    )
+    .await;
+
+    let (transaction_model, tx_hash) = match sent {
+        Ok(sent) => sent,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    Ok(HttpResponse::Ok().json(TransactionResponse {
+        id: transaction_model.id,
+        hash: tx_hash.to_string(),
+        status: transaction_model.status,
+        to: Some(recipient.to_string()),
+        contract_address: None,
+        ens_name: transfer.ens_name,
+        explorer_url: explorer_url(network, &tx_hash),
+    }))
+}
+
+/// Signs `digest` as is on behalf of a contract the wallet owns, recorded as a transaction of
+/// `value` to `recipient` that is screened first. Returns the `signing` transaction and the
+/// 65-byte `r || s || v` signature with a `v` of 27 or 28
+pub(super) async fn sign_owner_digest(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    recipient: Option<(Address, U256)>,
+    digest: B256,
+) -> Result<(TransactionModel, Vec<u8>), SendFailure> {
+    let transaction_repository = TransactionRepository::new_with_connection(db);
+
+    let mut transaction_model = transaction_repository
+        .create(TransactionActiveModel {
+            user_id: Set(wallet.user_id),
+            wallet_id: Set(wallet.id),
+            status: Set(TransactionStatus::Pending),
+            chain: Set(Some(wallet.chain.clone())),
+            value: Set(Some(
+                recipient
+                    .map(|(_, value)| value)
+                    .unwrap_or_default()
+                    .to_string(),
+            )),
+            to_address: Set(recipient.map(|(to, _)| to.to_string())),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to create transaction"))?;
+
+    if let Some((to, _)) = recipient {
+        transaction_model = screen_transaction(
+            &transaction_repository,
+            screener,
+            wallet,
+            transaction_model,
+            &to.to_string(),
+        )
+        .await?;
+    }
+
+    let transaction_model = transaction_repository
+        .update_status(
+            &transaction_model,
+            TransactionStatus::Signing,
+            StatusDetails::default(),
+        )
+        .await
+        .map_err(|_| SendFailure::Internal("Failed to sign transaction"))?;
+
+    let (r, s, _) = sign_with_participants(
+        db,
+        participants,
+        wallet,
+        network,
+        &transaction_model,
+        SigningData::Digest(digest),
+    )
+    .await?;
+
+    // Contracts expect a v of 27 or 28 rather than the EIP-155 one of the chain
+    let signature = verify_signature(db, wallet, &transaction_model, &digest, &r, &s).await?;
+
+    Ok((transaction_model, signature.as_bytes().to_vec()))
+}
+
+/// Sends every payout of the batch with the next free nonces. They are signed in one
+/// participants' session and broadcast one after the other, a failure stops the batch and
+/// fails the payouts after it, whose nonces the next transactions reuse
+pub async fn send_batch_tx(
+    req: HttpRequest,
+    data: web::Json<BatchTransactionRequest>,
+    db: web::Data<DatabaseConnection>,
+    chains: web::Data<ChainRegistry>,
+    participants: web::Data<dyn ParticipantPool>,
+    screener: web::Data<Screener>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    if data.transactions.is_empty() || data.transactions.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::bad_request(format!(
+            "A batch holds between 1 and {MAX_BATCH_SIZE} transactions"
+        )));
+    }
+
+    let wallet_repository = WalletRepository::new_with_connection(&db);
+    let transaction_repository = TransactionRepository::new_with_connection(&db);
+
+    let wallet = find_user_wallet(&wallet_repository, path.into_inner(), user_id).await?;
+
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_transaction_quota(&req, &db, user_id, data.transactions.len()).await?;
+
+    ensure_signing_velocity(&db, &wallet, data.transactions.len()).await?;
+
+    ensure_not_delayed(&wallet, &data.transactions)?;
+
+    let network = chains
+        .get(&wallet.chain)
+        .ok_or_else(|| ApiError::bad_request("Chain not configured"))?;
+
+    let mut transfers = Vec::new();
+
+    for item in &data.transactions {
+        transfers.push(resolve_transaction(&wallet, network, item).await?);
+    }
+
+    ensure_transfers_allowed(&db, &wallet, &transfers).await?;
 
-                let res = tx.get_receipt().await.map_err(|err| {
-                    log::error!("{err}");
-                    ErrorInternalServerError("Failed to send transaction")
-                })?;
+    // Each payout is simulated on its own, the batch is rejected before any signing round
+    let mut failed_simulations = Vec::new();
 
-                Ok(res.transaction_hash)
-            }
-            _ => Err(ErrorBadRequest("Chain not supported")),
-        }?;
+    for (index, item) in transfers.iter().enumerate() {
+        let unsigned_tx = unsigned_transaction(&wallet, item, 0)?;
+
+        let simulation = simulate_transaction(&wallet, network, &unsigned_tx).await?;
+
+        if !simulation.success {
+            failed_simulations.push(BatchSimulationResponse { index, simulation });
+        }
+    }
+
+    if !failed_simulations.is_empty() {
+        return Err(ApiError::unprocessable(
+            "transaction_would_fail",
+            "Some transactions would fail",
+        )
+        .with("failures", failed_simulations));
+    }
+
+    let transactions = create_transactions(&db, &wallet, None, network, &transfers).await?;
+
+    // Every recipient is screened first, a single block rejects the whole batch
+    let mut screened = Vec::new();
+    let mut rejected = None;
+
+    for (transaction_model, unsigned_tx) in transactions {
+        // Deployments have no recipient to screen
+        let TxKind::Call(to) = unsigned_tx.to else {
+            screened.push((transaction_model, unsigned_tx));
+            continue;
+        };
+
+        if rejected.is_some() {
+            screened.push((transaction_model, unsigned_tx));
+            continue;
+        }
+
+        match screen_transaction(
+            &transaction_repository,
+            &screener,
+            &wallet,
+            transaction_model,
+            &to.to_string(),
+        )
+        .await
+        {
+            Ok(transaction_model) => screened.push((transaction_model, unsigned_tx)),
+            Err(failure) => rejected = Some(failure),
+        }
+    }
+
+    if let Some(failure) = rejected {
+        for (transaction_model, _) in &screened {
+            fail_transaction(
+                &transaction_repository,
+                transaction_model,
+                "Skipped after another transaction of the batch failed screening",
+            )
+            .await;
+        }
+
+        return Err(failure.into());
+    }
+
+    let mut signing = Vec::new();
+
+    for (transaction_model, unsigned_tx) in screened {
+        let transaction_model = transaction_repository
+            .update_status(
+                &transaction_model,
+                TransactionStatus::Signing,
+                StatusDetails::default(),
+            )
+            .await
+            .map_err(|_| ApiError::internal("Failed to sign transaction"))?;
 
-        Ok(HttpResponse::Ok().json(TransactionResponse {
+        signing.push((transaction_model, unsigned_tx));
+    }
+
+    let (models, unsigned_txs): (Vec<_>, Vec<_>) = signing.into_iter().unzip();
+
+    let payloads = unsigned_txs
+        .iter()
+        .map(|unsigned_tx| unsigned_tx.signing_payload(network.config.chain_id))
+        .collect();
+
+    let signed = sign_batch_with_participants(
+        &db,
+        participants.get_ref(),
+        &wallet,
+        network,
+        &models,
+        payloads,
+    )
+    .await;
+
+    let mut items = Vec::new();
+    let mut stopped = false;
+
+    for (index, ((transaction_model, unsigned_tx), transfer)) in models
+        .into_iter()
+        .zip(unsigned_txs)
+        .zip(transfers)
+        .enumerate()
+    {
+        let mut item = BatchItemResponse {
             id: transaction_model.id,
-            hash: tx_hash.to_string(),
+            to: transfer.to,
+            contract_address: transaction_model
+                .contract_address
+                .as_deref()
+                .and_then(|address| address.parse().ok()),
+            ens_name: transfer.ens_name,
+            value: unsigned_tx.value.to_string(),
+            status: TransactionStatus::Failed,
+            hash: None,
+            explorer_url: None,
+            error: None,
+        };
+
+        // A failed session already failed every transaction of the batch
+        let signatures = match &signed {
+            Ok(signatures) => signatures,
+            Err(failure) => {
+                item.error = Some(failure.message().to_string());
+                items.push(item);
+                continue;
+            }
+        };
+
+        if stopped {
+            let error = "Skipped after an earlier transaction of the batch failed";
+
+            fail_transaction(&transaction_repository, &transaction_model, error).await;
+            item.error = Some(error.to_string());
+        } else {
+            let (r, s, _) = &signatures[index];
+
+            let sent = match store_signed(
+                &db,
+                &wallet,
+                network,
+                &transaction_model,
+                &unsigned_tx,
+                r,
+                s,
+            )
+            .await
+            {
+                Ok((transaction_model, raw_tx)) => {
+                    broadcast(&db, network, &transaction_model, &raw_tx).await
+                }
+                Err(failure) => Err(failure),
+            };
+
+            match sent {
+                Ok((transaction_model, tx_hash)) => {
+                    item.status = transaction_model.status;
+                    item.hash = Some(tx_hash.to_string());
+                    item.explorer_url = explorer_url(network, &tx_hash);
+                }
+                Err(failure) => {
+                    stopped = true;
+
+                    if let SendFailure::Unsent(_) = failure {
+                        item.status = TransactionStatus::Signed;
+                    }
+
+                    item.error = Some(failure.message().to_string());
+                }
+            }
+        }
+
+        items.push(item);
+    }
+
+    Ok(HttpResponse::Ok().json(BatchResponse { items }))
+}
+
+pub(super) async fn fail_transaction(
+    repository: &TransactionRepository<'_>,
+    transaction: &TransactionModel,
+    error: &str,
+) {
+    let res = repository
+        .update_status(
+            transaction,
+            TransactionStatus::Failed,
+            StatusDetails {
+                error: Some(error.to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    if let Err(err) = res {
+        log::error!(
+            "Failed to mark transaction {} as failed: {err}",
+            transaction.id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use proto::mpc::{AbortDetails, ErrorDetail};
+
+    /// Migrated in-memory database on a single connection, holding a `creating` wallet
+    #[cfg(feature = "sqlite")]
+    async fn creating_wallet() -> (DatabaseConnection, WalletModel) {
+        use crate::db::models::UserActiveModel;
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set(Uuid::new_v4().simple().to_string()),
+                state: Set(WalletState::Creating),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        (db, wallet)
+    }
+
+    #[test]
+    fn test_tags_are_normalized() {
+        let tags = [" Treasury ", "ops:eu", "treasury"].map(String::from);
+
+        assert_eq!(normalize_tags(&tags).unwrap(), ["ops:eu", "treasury"]);
+        assert!(normalize_tag("").is_err());
+        assert!(normalize_tag("cold storage").is_err());
+        assert!(normalize_tag(&"a".repeat(MAX_TAG_LENGTH + 1)).is_err());
+
+        let tags: Vec<String> = (0..=MAX_WALLET_TAGS).map(|i| format!("tag-{i}")).collect();
+
+        assert!(normalize_tags(&tags).is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_wallets_are_listed_by_tag() {
+        let (db, wallet) = creating_wallet().await;
+
+        let tags = WalletTagRepository::new_with_connection(&db);
+        let wallets = WalletRepository::new_with_connection(&db);
+
+        tags.replace(wallet.id, &["ops".to_string(), "treasury".to_string()])
+            .await
+            .unwrap();
+
+        let tagged = wallets
+            .find_by_user_id_and_tag(wallet.user_id, "treasury")
+            .await
+            .unwrap();
+
+        assert_eq!(tagged, std::slice::from_ref(&wallet));
+
+        // Replacing drops the tags left out
+        tags.replace(wallet.id, &["ops".to_string()]).await.unwrap();
+
+        assert!(
+            wallets
+                .find_by_user_id_and_tag(wallet.user_id, "treasury")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            tags.find_by_wallet_ids(&[wallet.id])
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|tag| tag.tag)
+                .collect::<Vec<_>>(),
+            ["ops"]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_keygen_wallet_activates_the_wallet() {
+        use crate::participants::mock::MockParticipants;
+
+        let (db, wallet) = creating_wallet().await;
+        let participants = MockParticipants::new(3);
+
+        let wallet = keygen_wallet(&db, &participants, &wallet).await.unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+        assert_eq!(
+            wallet.address.as_deref(),
+            Some("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf")
+        );
+        assert!((1..=3).all(|participant| participants.has_share_of(participant, wallet.id)));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_keygen_wallet_queues_the_purge_of_a_failed_keygen() {
+        use crate::db::models::{JobEntity, JobKind};
+        use crate::participants::mock::MockParticipants;
+        use sea_orm::EntityTrait;
+
+        let (db, wallet) = creating_wallet().await;
+        let participants = MockParticipants::new(3).failing(3, Status::internal("vault sealed"));
+
+        let error = keygen_wallet(&db, &participants, &wallet)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.problem().code, "keygen_failed");
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .find_by_id(wallet.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(wallet.state, WalletState::Deleting);
+
+        let jobs = JobEntity::find().all(&db).await.unwrap();
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, JobKind::WalletPurge);
+        assert_eq!(
+            jobs[0].payload,
+            serde_json::json!({ "wallet_id": wallet.id })
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_import_key_activates_the_wallet() {
+        use crate::participants::mock::MockParticipants;
+
+        let (db, wallet) = creating_wallet().await;
+        let participants = MockParticipants::new(3);
+
+        let wallet = import_key(&db, &participants, &wallet, &[1; 32])
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+        assert!((1..=3).all(|participant| participants.has_share_of(participant, wallet.id)));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_solana_key_import_end_to_end() {
+        use crate::chains::encode_base58;
+        use crate::testing::Harness;
+
+        let harness = Harness::start().await;
+        let participants = harness.participants.as_ref();
+
+        let user = harness.user("alice").await;
+        let wallet = harness.creating_wallet(&user, Chain::Solana).await;
+
+        // First test vector of RFC 8032
+        let seed = hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+            .unwrap();
+        let public_key =
+            hex::decode("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+                .unwrap();
+
+        let wallet = import_key(&harness.db, participants, &wallet, &seed)
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+        assert_eq!(wallet.address, Some(encode_base58(&public_key)));
+
+        for participant in 1..=participants.count() {
+            let share = participants
+                .has_share(participant, wallet.id)
+                .await
+                .unwrap();
+            assert!(share.present);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_solana_wallet_keygen_and_signing_end_to_end() {
+        use crate::testing::Harness;
+
+        let harness = Harness::start().await;
+        let participants = harness.participants.as_ref();
+
+        let user = harness.user("alice").await;
+        let wallet = harness.creating_wallet(&user, Chain::Solana).await;
+
+        let wallet = keygen_wallet(&harness.db, participants, &wallet)
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+        assert!(wallet.address.is_some());
+
+        for participant in 1..=participants.count() {
+            let share = participants
+                .has_share(participant, wallet.id)
+                .await
+                .unwrap();
+            assert!(share.present);
+        }
+
+        let network = solana_network();
+
+        let transaction = TransactionRepository::new_with_connection(&harness.db)
+            .create(TransactionActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Pending),
+                chain: Set(Some(Chain::Solana)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let (r, s, _) = sign_with_participants(
+            &harness.db,
+            participants,
+            &wallet,
+            &network,
+            &transaction,
+            SigningData::Raw(b"end to end".to_vec()),
+        )
+        .await
+        .unwrap_or_else(|failure| panic!("{}", failure.message()));
+
+        assert_eq!((r.len(), s.len()), (32, 32));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_ethereum_wallet_keygen_and_signing_end_to_end() {
+        use crate::testing::Harness;
+
+        let harness = Harness::start().await;
+        let participants = harness.participants.as_ref();
+
+        let user = harness.user("alice").await;
+        let wallet = harness.creating_wallet(&user, Chain::Ethereum).await;
+
+        // Keygen and aux info of CGGMP21, on the pregenerated primes of the harness
+        let wallet = keygen_wallet(&harness.db, participants, &wallet)
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+
+        let address: Address = wallet.address.as_deref().unwrap().parse().unwrap();
+
+        let transaction = TransactionRepository::new_with_connection(&harness.db)
+            .create(TransactionActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Pending),
+                chain: Set(Some(Chain::Ethereum)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let digest = keccak256(b"end to end");
+
+        let (r, s, _) = sign_with_participants(
+            &harness.db,
+            participants,
+            &wallet,
+            &ethereum_network(),
+            &transaction,
+            SigningData::Digest(digest),
+        )
+        .await
+        .unwrap_or_else(|failure| panic!("{}", failure.message()));
+
+        assert!(recover_signature(address, &digest, &r, &s).is_some());
+    }
+
+    /// Solana network signed for without an RPC endpoint
+    #[cfg(feature = "sqlite")]
+    fn solana_network() -> ChainEntry {
+        use crate::config::app_config::ChainConfig;
+
+        ChainEntry {
+            config: ChainConfig {
+                chain: Chain::Solana,
+                chain_id: 0,
+                rpc_urls: vec![],
+                explorer_url: None,
+                confirmations: 1,
+                bundler_url: None,
+                safe_service_url: None,
+                tokens: vec![],
+                ws_url: None,
+            },
+            provider: None,
+            solana: None,
+            bundler: None,
+            safe: None,
+            tokens: vec![],
+        }
+    }
+
+    /// Ethereum mainnet signed for without an RPC endpoint
+    #[cfg(feature = "sqlite")]
+    fn ethereum_network() -> ChainEntry {
+        let mut network = solana_network();
+        network.config.chain = Chain::Ethereum;
+        network.config.chain_id = 1;
+        network
+    }
+
+    /// Joins the next ceremony of the user on the device and runs it, as the app of the
+    /// device does with the message of the join API
+    #[cfg(feature = "sqlite")]
+    async fn join_on_device(
+        device: &mut proto::mpc::participant_client::ParticipantClient<tonic::transport::Channel>,
+        user_id: i32,
+    ) {
+        let ceremony = loop {
+            match cosigner::pending(user_id)
+                .into_iter()
+                .find(|ceremony| !ceremony.joined)
+            {
+                Some(ceremony) => break ceremony,
+                None => actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await,
+            }
+        };
+
+        let message = cosigner::join(user_id, &ceremony.execution_id)
+            .unwrap()
+            .encode();
+
+        match ceremony.kind {
+            "keygen" => {
+                let message = proto::mpc::CreateWalletMessage::decode(message.as_slice()).unwrap();
+                let mut events = device.new_wallet(message).await.unwrap().into_inner();
+
+                while events.message().await.unwrap().is_some() {}
+            }
+            "signing" => {
+                let message = SignMessage::decode(message.as_slice()).unwrap();
+
+                device.sign_tx(message).await.unwrap();
+            }
+            kind => panic!("Unexpected {kind} ceremony"),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_cosigned_solana_wallet_end_to_end() {
+        use crate::testing::Harness;
+        use sea_orm::ActiveModelTrait;
+
+        let harness = Harness::start().await;
+        let participants = harness.participants.as_ref();
+        let mut device = harness.device();
+
+        let user = harness.user("alice").await;
+        let wallet = harness.creating_wallet(&user, Chain::Solana).await;
+
+        let mut model = wallet.into_active_model();
+        model.cosigner = Set(true);
+        let wallet = model.update(&harness.db).await.unwrap();
+
+        let (created, ()) = futures::future::join(
+            keygen_wallet(&harness.db, participants, &wallet),
+            join_on_device(&mut device, user.id),
+        )
+        .await;
+        let wallet = created.unwrap();
+
+        assert_eq!(wallet.state, WalletState::Active);
+        assert!(wallet.address.is_some());
+
+        // The server holds a single share of the 2-of-2 key
+        let shares = join_all((1..=participants.count()).map(|participant| async move {
+            participants
+                .has_share(participant, wallet.id)
+                .await
+                .unwrap()
+                .present
         }))
-    } else {
-        txn.rollback()
+        .await;
+        assert_eq!(shares, [true, false, false]);
+        assert!(cosigner::pending(user.id).is_empty());
+
+        let transaction = TransactionRepository::new_with_connection(&harness.db)
+            .create(TransactionActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Pending),
+                chain: Set(Some(Chain::Solana)),
+                ..Default::default()
+            })
             .await
-            .map_err(|_| ErrorInternalServerError(""))?;
+            .unwrap();
+
+        let (signed, ()) = futures::future::join(
+            sign_with_participants(
+                &harness.db,
+                participants,
+                &wallet,
+                &solana_network(),
+                &transaction,
+                SigningData::Raw(b"co-signed".to_vec()),
+            ),
+            join_on_device(&mut device, user.id),
+        )
+        .await;
+        let (r, s, _) = signed.unwrap_or_else(|failure| panic!("{}", failure.message()));
+
+        assert_eq!((r.len(), s.len()), (32, 32));
+    }
+
+    #[test]
+    fn test_aborts_decodes_details() {
+        let details = AbortDetails {
+            faulty_parties: vec![1],
+            round: "round2".to_string(),
+            reason: "ψ, ψˆ, or ψ' proofs are invalid".to_string(),
+        };
+
+        let detail = ErrorDetail {
+            code: ErrorCode::Aborted.into(),
+            party: Some(1),
+            abort: Some(details),
+            ..Default::default()
+        };
+
+        let results: Vec<Result<(), Status>> = vec![
+            Err(Status::with_details(
+                Code::Aborted,
+                "aborted",
+                detail.encode_to_vec().into(),
+            )),
+            Err(Status::internal("Transaction signing failed")),
+        ];
+
+        let failures = aborts(&results, &[2, 0]);
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].reporter, 2);
+        assert_eq!(failures[0].faulty_parties, vec![1]);
+        assert_eq!(failures[0].round, "round2");
+    }
+
+    #[test]
+    fn test_participant_error_codes() {
+        let busy: Vec<Result<(), Status>> = vec![Err(Status::resource_exhausted("busy"))];
+        let stalled: Vec<Result<(), Status>> = vec![Err(Status::deadline_exceeded("stalled"))];
+        let failed: Vec<Result<(), Status>> = vec![Err(Status::internal("failed"))];
+        let down: Vec<Result<(), Status>> = vec![Err(Status::unavailable("down"))];
+
+        let busy = participant_error(&busy, "signing_failed", "");
+        let stalled = participant_error(&stalled, "signing_failed", "");
+        let failed = participant_error(&failed, "signing_failed", "");
+        let down = participant_error(&down, "signing_failed", "");
+
+        assert_eq!(busy.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(busy.problem().code, "participants_busy");
+        assert_eq!(stalled.status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(failed.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(failed.problem().code, "signing_failed");
+        assert_eq!(down.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(down.problem().code, "participants_unavailable");
+
+        // Reported as `INTERNAL`, the details tell a storage outage from a protocol failure
+        let detail = ErrorDetail {
+            code: ErrorCode::StorageFailed.into(),
+            retryable: true,
+            ..Default::default()
+        };
+        let storage: Vec<Result<(), Status>> = vec![Err(Status::with_details(
+            Code::Internal,
+            "Wallet not found",
+            detail.encode_to_vec().into(),
+        ))];
+
+        let storage = participant_error(&storage, "signing_failed", "");
+
+        assert_eq!(storage.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(storage.problem().code, "participant_storage_failed");
+
+        let refused: Vec<Result<(), Status>> = vec![Err(Status::permission_denied(
+            "Recipient not allowed by the signing policy",
+        ))];
+
+        let refused = participant_error(&refused, "signing_failed", "");
+
+        assert_eq!(refused.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(refused.problem().code, "participant_policy_violation");
+
+        let replayed: Vec<Result<(), Status>> =
+            vec![Err(Status::already_exists("Already received"))];
+
+        assert_eq!(
+            participant_error(&replayed, "signing_failed", "")
+                .problem()
+                .code,
+            "participant_replay_rejected"
+        );
+
+        // The details tell a stored share from a replay, both are `ALREADY_EXISTS`
+        let detail = ErrorDetail {
+            code: ErrorCode::ShareExists.into(),
+            ..Default::default()
+        };
+        let stored: Vec<Result<(), Status>> = vec![Err(Status::with_details(
+            Code::AlreadyExists,
+            "A share of the wallet is already stored",
+            detail.encode_to_vec().into(),
+        ))];
 
-        Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-            error: "Failed to sign transaction".to_string(),
+        let stored = participant_error(&stored, "keygen_failed", "");
+
+        assert_eq!(stored.status_code(), StatusCode::CONFLICT);
+        assert_eq!(stored.problem().code, "share_exists");
+
+        let detail = ErrorDetail {
+            code: ErrorCode::ShareTampered.into(),
+            ..Default::default()
+        };
+        let tampered: Vec<Result<(), Status>> = vec![
+            Ok(()),
+            Err(Status::with_details(
+                Code::DataLoss,
+                "Stored share failed its integrity check",
+                detail.encode_to_vec().into(),
+            )),
+        ];
+
+        let tampered = participant_error(&tampered, "signing_failed", "");
+
+        assert_eq!(tampered.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(tampered.problem().code, "share_integrity_failed");
+    }
+
+    #[test]
+    fn test_rejection_code() {
+        assert_eq!(
+            rejection_code("insufficient funds for gas * price + value"),
+            "insufficient_funds"
+        );
+        assert_eq!(
+            rejection_code("execution reverted: ERC20: Insufficient balance"),
+            "insufficient_funds"
+        );
+        assert_eq!(
+            rejection_code("execution reverted"),
+            "transaction_would_fail"
+        );
+    }
+
+    #[test]
+    fn test_batch_request_accepts_decimal_values() {
+        let request: BatchTransactionRequest = serde_json::from_value(serde_json::json!({
+            "transactions": [
+                { "to": "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf", "value": "1000" },
+                { "to": "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf", "value": 2000 },
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(request.transactions.len(), 2);
+        assert_eq!(request.transactions[1].value, Uint::from(2000));
+    }
+
+    #[test]
+    fn test_annotation_limits_memo_and_metadata() {
+        let request = |memo: &str, metadata: serde_json::Value| -> TransactionRequest {
+            serde_json::from_value(serde_json::json!({
+                "to": "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf",
+                "value": "1000",
+                "memo": memo,
+                "metadata": metadata,
+            }))
+            .unwrap()
+        };
+
+        let annotation = Annotation::from_request(&request(
+            " Invoice 42 ",
+            serde_json::json!({ "invoice_id": "inv_42", "lines": [1, 2] }),
+        ))
+        .unwrap();
+
+        assert_eq!(annotation.memo.as_deref(), Some("Invoice 42"));
+        assert_eq!(
+            annotation.metadata,
+            Some(serde_json::json!({ "invoice_id": "inv_42", "lines": [1, 2] }))
+        );
+
+        // Blank memos and empty metadata aren't stored
+        let annotation = Annotation::from_request(&request(" ", serde_json::json!({}))).unwrap();
+
+        assert_eq!((annotation.memo, annotation.metadata), (None, None));
+
+        assert!(
+            Annotation::from_request(&request(&"a".repeat(257), serde_json::Value::Null)).is_err()
+        );
+        assert!(
+            Annotation::from_request(&request("", serde_json::json!({ "": "empty key" }))).is_err()
+        );
+        assert!(
+            Annotation::from_request(&request(
+                "",
+                serde_json::json!({ "notes": "a".repeat(MAX_METADATA_SIZE) })
+            ))
+            .is_err()
+        );
+
+        // Metadata must be an object of key/value pairs
+        assert!(
+            serde_json::from_value::<TransactionRequest>(serde_json::json!({
+                "to": null,
+                "value": "0",
+                "metadata": ["invoice_id"],
+            }))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_deployment_request_has_no_recipient() {
+        let request: TransactionRequest = serde_json::from_value(serde_json::json!({
+            "to": null,
+            "value": "0",
+            "data": "0x6080604052",
         }))
+        .unwrap();
+
+        assert!(request.to.is_none());
+        assert_eq!(request.data.unwrap().len(), 5);
+
+        let transfer = Transfer {
+            to: None,
+            value: U256::ZERO,
+            data: Bytes::from_static(&[0x60, 0x80]),
+            gas_limit: 100_000,
+            ens_name: None,
+            token: None,
+            annotation: Annotation::default(),
+        };
+
+        let mut tx_data = Vec::new();
+
+        RawTransaction {
+            nonce: 0,
+            gas_price: 1,
+            gas_limit: transfer.gas_limit,
+            to: transfer.kind(),
+            value: transfer.value,
+            data: transfer.data,
+        }
+        .encode(&mut tx_data);
+
+        // An empty string takes the place of the recipient, the init code is a string too
+        assert_eq!(&tx_data[7..], [0x80, 0x80, 0x82, 0x60, 0x80]);
+
+        let deployer: Address = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            deployer.create(0).to_string(),
+            "0xcd234A471b72ba2F1Ccf0A70FCABA648a5eeCD8d"
+        );
+    }
+
+    #[test]
+    fn test_signature_recovers_wallet_address() {
+        // Example transaction of EIP-155, signed with the key 0x4646...46
+        let unsigned_tx = RawTransaction {
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21000,
+            to: TxKind::Call(
+                "0x3535353535353535353535353535353535353535"
+                    .parse()
+                    .unwrap(),
+            ),
+            value: U256::from(10).pow(U256::from(18)),
+            data: Bytes::new(),
+        };
+
+        let digest = unsigned_tx.signing_hash(1);
+
+        assert_eq!(
+            digest.to_string(),
+            "0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let address: Address = "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"
+            .parse()
+            .unwrap();
+
+        let r = U256::from_str_radix(
+            "18515461264373351373200002665853028612451056578545711640558177340181847433846",
+            10,
+        )
+        .unwrap();
+        let s = U256::from_str_radix(
+            "46948507304638947509940763649030358759909902576025900602547168820602576006531",
+            10,
+        )
+        .unwrap();
+
+        let signature = recover_signature(
+            address,
+            &digest,
+            &r.to_be_bytes::<32>(),
+            &s.to_be_bytes::<32>(),
+        )
+        .unwrap();
+
+        // v of 37 on chain 1
+        assert!(!signature.v());
+        assert_eq!(signature.s(), s);
+
+        // The high s of the same signature is flipped back with the other parity
+        let order = U256::from_str_radix(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        )
+        .unwrap();
+        let high_s = order - s;
+        let flipped = recover_signature(
+            address,
+            &digest,
+            &r.to_be_bytes::<32>(),
+            &high_s.to_be_bytes::<32>(),
+        )
+        .unwrap();
+
+        assert_eq!(flipped, signature);
+
+        // Another key or transaction doesn't recover the wallet
+        assert!(
+            recover_signature(
+                Address::repeat_byte(1),
+                &digest,
+                &r.to_be_bytes::<32>(),
+                &s.to_be_bytes::<32>()
+            )
+            .is_none()
+        );
+        assert!(
+            recover_signature(
+                address,
+                &unsigned_tx.signing_hash(10),
+                &r.to_be_bytes::<32>(),
+                &s.to_be_bytes::<32>()
+            )
+            .is_none()
+        );
+        assert!(recover_signature(address, &digest, &[1; 31], &[1; 32]).is_none());
+    }
+
+    #[test]
+    fn test_agreed_signatures_need_identical_batches() {
+        let batch = vec![(vec![1], vec![2], 27), (vec![3], vec![4], 28)];
+
+        assert_eq!(
+            agreed_signatures(&[batch.clone(), batch.clone()], 2),
+            Some(&batch)
+        );
+
+        // A participant signing something else or skipping an item fails the batch
+        let other = vec![(vec![1], vec![2], 27), (vec![5], vec![4], 28)];
+
+        assert_eq!(agreed_signatures(&[batch.clone(), other], 2), None);
+        assert_eq!(
+            agreed_signatures(&[batch.clone(), batch[..1].to_vec()], 2),
+            None
+        );
+        assert_eq!(agreed_signatures(&[batch], 3), None);
+        assert_eq!(agreed_signatures(&[], 0), None);
+    }
+
+    #[test]
+    fn test_free_nonces_reuse_failed_nonces() {
+        assert_eq!(free_nonces(5, &[], 3), vec![5, 6, 7]);
+        // 6 failed and is free again, 5 and 7 are still held
+        assert_eq!(free_nonces(5, &[5, 7, 8], 3), vec![6, 9, 10]);
+    }
+
+    #[test]
+    fn test_bumped_gas_price_always_raises() {
+        assert_eq!(bumped_gas_price(1_000_000_000, 10), 1_100_000_000);
+        assert_eq!(bumped_gas_price(15, 10), 17);
+        assert_eq!(bumped_gas_price(1, 0), 2);
+        assert_eq!(bumped_gas_price(u64::MAX, 10), u64::MAX);
+    }
+
+    #[test]
+    fn test_recipient_validates_checksum_and_accepts_names() {
+        let address = Recipient::try_from("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".to_string());
+
+        assert!(matches!(address, Ok(Recipient::Address(_))));
+
+        // Unchecksummed addresses are accepted, a wrong checksum is not
+        assert!(
+            Recipient::try_from("0x7e5f4552091a69125d5dfcb7b8c2659029395bdf".to_string()).is_ok()
+        );
+        assert!(
+            Recipient::try_from("0x7E5F4552091A69125d5DfCb7b8C2659029395BDF".to_string()).is_err()
+        );
+
+        assert_eq!(
+            Recipient::try_from("Vitalik.eth".to_string()),
+            Ok(Recipient::Name("vitalik.eth".to_string()))
+        );
+        assert!(Recipient::try_from("vitalik".to_string()).is_err());
+        assert!(Recipient::try_from("vitalik..eth".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_wallet_address_type_defaults_on_bitcoin() {
+        assert_eq!(
+            wallet_address_type(&Chain::Bitcoin, None).unwrap(),
+            Some(AddressType::P2wpkh)
+        );
+        assert_eq!(
+            wallet_address_type(&Chain::Bitcoin, Some(AddressType::P2tr)).unwrap(),
+            Some(AddressType::P2tr)
+        );
+        assert_eq!(wallet_address_type(&Chain::Ethereum, None).unwrap(), None);
+        assert!(wallet_address_type(&Chain::Solana, Some(AddressType::P2tr)).is_err());
+    }
+
+    #[test]
+    fn test_signing_failure_keeps_participant_codes() {
+        let failure = SendFailure::Signing(vec![Status::resource_exhausted("busy")]);
+
+        assert_eq!(
+            ApiError::from(failure).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_unsent_failure_names_the_signed_transaction() {
+        let failure = SendFailure::Unsent(7);
+
+        assert_eq!(failure.message(), "Transaction signed but not broadcast");
+
+        let err = ApiError::from(failure);
+
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.problem().extensions["id"], 7);
     }
 }
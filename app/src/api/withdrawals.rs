@@ -0,0 +1,323 @@
+use super::accounts::find_wallet_account;
+use super::error::{ApiError, Result};
+use super::quotas::{ensure_daily_transactions, ensure_signing_velocity};
+use super::wallet::{
+    Annotation, TransactionRequest, TransactionResponse, find_user_wallet, send_evm_tx,
+    send_solana_tx,
+};
+use crate::chains::ChainEntry;
+use crate::config::app_config::QuotaConfig;
+use crate::db::models::{
+    Chain, WalletModel, WalletOperation, WithdrawalActiveModel, WithdrawalModel, WithdrawalState,
+};
+use crate::db::repositories::{AuditRepository, WalletRepository, WithdrawalRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+use crate::utils::request::request_user_id;
+use actix_web::{HttpRequest, HttpResponse, web};
+use alloy::primitives::U256;
+use chrono::{DateTime, Utc};
+use sea_orm::{DatabaseConnection, Set};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/withdrawals").route(web::get().to(list_withdrawals)))
+        .service(
+            web::resource("/{id}/withdrawals/{withdrawal_id}/cancel")
+                .route(web::post().to(cancel_withdrawal)),
+        );
+}
+
+/// End of the window a transfer of `value` is held for by the withdrawal delay of the wallet,
+/// `None` when it is signed right away
+pub(super) fn withdrawal_release_at(wallet: &WalletModel, value: U256) -> Option<DateTime<Utc>> {
+    let threshold: U256 = wallet.withdrawal_threshold.as_deref()?.parse().ok()?;
+
+    if wallet.withdrawal_delay <= 0 || value <= threshold {
+        return None;
+    }
+
+    Some(Utc::now() + chrono::Duration::seconds(wallet.withdrawal_delay.into()))
+}
+
+/// Rejects transfers the withdrawal delay would hold, for the paths that sign right away
+pub(super) fn ensure_not_delayed(
+    wallet: &WalletModel,
+    requests: &[TransactionRequest],
+) -> Result<()> {
+    if requests
+        .iter()
+        .any(|data| withdrawal_release_at(wallet, data.value).is_some())
+    {
+        return Err(ApiError::unprocessable(
+            "withdrawal_delayed",
+            "Transfers above the withdrawal threshold are only sent one at a time through /tx",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Holds the transfer until `release_at`, the memo and metadata are checked now rather than
+/// once the window closed
+pub(super) async fn hold_withdrawal(
+    db: &DatabaseConnection,
+    wallet: &WalletModel,
+    account_id: Option<i32>,
+    data: &TransactionRequest,
+    release_at: DateTime<Utc>,
+) -> Result<WithdrawalModel> {
+    Annotation::from_request(data)?;
+
+    let request = serde_json::to_value(data)
+        .map_err(|_| ApiError::internal("Failed to hold the withdrawal"))?;
+
+    WithdrawalRepository::new_with_connection(db)
+        .create(WithdrawalActiveModel {
+            user_id: Set(wallet.user_id),
+            wallet_id: Set(wallet.id),
+            account_id: Set(account_id),
+            to_address: Set(data.to.clone().map(String::from)),
+            value: Set(data.value.to_string()),
+            request: Set(request),
+            state: Set(WithdrawalState::Pending),
+            release_at: Set(release_at),
+            ..Default::default()
+        })
+        .await
+        .map_err(|_| ApiError::internal("Failed to hold the withdrawal"))
+}
+
+/// Sends the released withdrawal, subject to the same state, quota, signing limit, policy and
+/// screening checks as `send_tx` at the time of its release
+pub async fn release_withdrawal(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    screener: &Screener,
+    quotas: &QuotaConfig,
+    wallet: &WalletModel,
+    network: &ChainEntry,
+    withdrawal: &WithdrawalModel,
+) -> Result<TransactionResponse> {
+    wallet.state.ensure_allows(WalletOperation::Sign)?;
+
+    ensure_daily_transactions(db, quotas, wallet.user_id, 1).await?;
+
+    ensure_signing_velocity(db, wallet, 1).await?;
+
+    let data: TransactionRequest = serde_json::from_value(withdrawal.request.clone())
+        .map_err(|_| ApiError::internal("Invalid withdrawal request"))?;
+
+    let account = match withdrawal.account_id {
+        Some(account_id) => Some(find_wallet_account(db, wallet, account_id).await?),
+        None => None,
+    };
+
+    match wallet.chain {
+        Chain::Solana => send_solana_tx(db, participants, screener, wallet, network, &data).await,
+        _ => {
+            send_evm_tx(
+                db,
+                participants,
+                screener,
+                wallet,
+                account.as_ref(),
+                network,
+                &data,
+            )
+            .await
+        }
+    }
+}
+
+/// Cancels the pending withdrawal on behalf of `actor`, `user:<id>` or `admin`, recorded in
+/// the audit log
+pub(super) async fn cancel(
+    db: &DatabaseConnection,
+    withdrawal: &WithdrawalModel,
+    actor: &str,
+) -> Result<WithdrawalModel> {
+    if withdrawal.state != WithdrawalState::Pending {
+        return Err(ApiError::conflict("Withdrawal is no longer pending"));
+    }
+
+    let withdrawal = WithdrawalRepository::new_with_connection(db)
+        .cancel(withdrawal, actor)
+        .await
+        .map_err(|_| ApiError::internal("Failed to cancel the withdrawal"))?
+        .ok_or_else(|| ApiError::conflict("Withdrawal is no longer pending"))?;
+
+    let audit = AuditRepository::new_with_connection(db)
+        .record(
+            actor,
+            "wallet.withdrawal_cancelled",
+            "wallet",
+            Some(withdrawal.wallet_id.to_string()),
+            Some(serde_json::json!({
+                "withdrawal_id": withdrawal.id,
+                "to": withdrawal.to_address,
+                "value": withdrawal.value,
+            })),
+        )
+        .await;
+
+    if let Err(err) = audit {
+        log::error!(
+            "Failed to audit the cancellation of withdrawal {}: {err}",
+            withdrawal.id
+        );
+    }
+
+    Ok(withdrawal)
+}
+
+pub async fn list_withdrawals(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        path.into_inner(),
+        user_id,
+    )
+    .await?;
+
+    let withdrawals = WithdrawalRepository::new_with_connection(&db)
+        .find_by_wallet_id(wallet.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the withdrawals"))?;
+
+    Ok(HttpResponse::Ok().json(withdrawals))
+}
+
+pub async fn cancel_withdrawal(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let user_id = request_user_id(&req)?;
+    let (wallet_id, withdrawal_id) = path.into_inner();
+
+    let wallet = find_user_wallet(
+        &WalletRepository::new_with_connection(&db),
+        wallet_id,
+        user_id,
+    )
+    .await?;
+
+    let withdrawal = WithdrawalRepository::new_with_connection(&db)
+        .find_by_id(withdrawal_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve the withdrawal"))?
+        .filter(|withdrawal| withdrawal.wallet_id == wallet.id)
+        .ok_or_else(|| ApiError::not_found("withdrawal_not_found", "Withdrawal not found"))?;
+
+    let withdrawal = cancel(&db, &withdrawal, &format!("user:{user_id}")).await?;
+
+    Ok(HttpResponse::Ok().json(withdrawal))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_withdrawals_above_threshold_are_held_until_cancelled() {
+        use crate::db::models::{UserActiveModel, WalletActiveModel, WalletState};
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set(uuid::Uuid::new_v4().simple().to_string()),
+                state: Set(WalletState::Active),
+                withdrawal_threshold: Set(Some("1000".to_string())),
+                withdrawal_delay: Set(3600),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(withdrawal_release_at(&wallet, U256::from(1000)), None);
+
+        let release_at = withdrawal_release_at(&wallet, U256::from(1001)).unwrap();
+
+        let data: TransactionRequest = serde_json::from_value(serde_json::json!({
+            "to": "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045",
+            "value": "1001",
+            "memo": "payroll",
+        }))
+        .unwrap();
+
+        assert!(ensure_not_delayed(&wallet, std::slice::from_ref(&data)).is_err());
+
+        let withdrawal = hold_withdrawal(&db, &wallet, None, &data, release_at)
+            .await
+            .unwrap();
+
+        // The stored request is sent as it was made once released
+        let stored: TransactionRequest =
+            serde_json::from_value(withdrawal.request.clone()).unwrap();
+
+        assert_eq!((stored.to, stored.value), (data.to, data.value));
+        assert_eq!(stored.memo.as_deref(), Some("payroll"));
+
+        let repository = WithdrawalRepository::new_with_connection(&db);
+
+        assert!(
+            repository
+                .find_due(Utc::now(), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            repository
+                .find_due(release_at + chrono::Duration::seconds(1), 10)
+                .await
+                .unwrap(),
+            std::slice::from_ref(&withdrawal)
+        );
+
+        let cancelled = cancel(&db, &withdrawal, "user:1").await.unwrap();
+
+        assert_eq!(cancelled.state, WithdrawalState::Cancelled);
+        assert_eq!(cancelled.cancelled_by.as_deref(), Some("user:1"));
+
+        // Neither a second cancellation nor the releaser acts on it any more
+        assert!(cancel(&db, &withdrawal, "admin").await.is_err());
+        assert_eq!(repository.release(&withdrawal).await.unwrap(), None);
+        assert!(
+            repository
+                .find_due(release_at + chrono::Duration::seconds(1), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,13 +1,29 @@
-use actix_web::Error;
-use actix_web::error::ErrorInternalServerError;
-use actix_web::error::ErrorUnauthorized;
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
+use argon2::password_hash::rand_core::OsRng;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation, decode, encode};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, decode_header,
+    encode,
+};
 use once_cell::sync::Lazy;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey, EncodeRsaPrivateKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{RsaPrivateKey, RsaPublicKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
 use uuid::Uuid;
 
+use crate::api::error::ApiError;
 use crate::db::models::UserModel;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -16,14 +32,296 @@ pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub jti: String,
+    pub iss: String,
+    pub aud: String,
     pub user_id: i32,
     pub username: String,
 }
 
-pub static JWT_SECRET: Lazy<String> = Lazy::new(|| {
-    std::env::var("JWT_SECRET").unwrap_or_else(|_| "default_jwt_secret_for_development_only".into())
+/// Public half of a signing key, published in the JWKS and looked up by the `kid` of a token
+#[derive(Clone)]
+struct VerifyingKey {
+    jwk: Jwk,
+    algorithm: Algorithm,
+    decoding: DecodingKey,
+}
+
+impl VerifyingKey {
+    /// ES256 key of a P-256 public key
+    fn ec(public_key: &p256::PublicKey) -> Result<Self> {
+        let point = public_key.to_encoded_point(false);
+        let (Some(x), Some(y)) = (point.x(), point.y()) else {
+            return Err(anyhow!("Invalid P-256 public key"));
+        };
+
+        let x = URL_SAFE_NO_PAD.encode(x);
+        let y = URL_SAFE_NO_PAD.encode(y);
+        let kid = thumbprint(&format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#
+        ));
+
+        Self::new(
+            kid,
+            Algorithm::ES256,
+            AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x,
+                y,
+            }),
+        )
+    }
+
+    /// RS256 key of an RSA public key
+    fn rsa(public_key: &RsaPublicKey) -> Result<Self> {
+        if public_key.size() < 256 {
+            return Err(anyhow!("RSA keys need at least 2048 bits"));
+        }
+
+        let n = URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be());
+        let e = URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be());
+        let kid = thumbprint(&format!(r#"{{"e":"{e}","kty":"RSA","n":"{n}"}}"#));
+
+        Self::new(
+            kid,
+            Algorithm::RS256,
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n,
+                e,
+            }),
+        )
+    }
+
+    fn new(kid: String, algorithm: Algorithm, parameters: AlgorithmParameters) -> Result<Self> {
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::ES256 => KeyAlgorithm::ES256,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                key_id: Some(kid),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(Self {
+            decoding: DecodingKey::from_jwk(&jwk)?,
+            algorithm,
+            jwk,
+        })
+    }
+
+    /// Public key of a PEM private or public key
+    fn from_pem(pem: &str) -> Result<Self> {
+        if let Ok(signing_key) = SigningKey::from_pem(pem) {
+            return Ok(signing_key.verifying);
+        }
+
+        if let Ok(public_key) = p256::PublicKey::from_public_key_pem(pem) {
+            return Self::ec(&public_key);
+        }
+
+        match RsaPublicKey::from_public_key_pem(pem).or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        {
+            Ok(public_key) => Self::rsa(&public_key),
+            Err(_) => Err(anyhow!("Expected a PEM P-256 or RSA key")),
+        }
+    }
+
+    fn kid(&self) -> &str {
+        self.jwk.common.key_id.as_deref().unwrap_or_default()
+    }
+}
+
+/// RFC 7638 thumbprint of the required members of a JWK, the `kid` of its key so every
+/// instance signing with the key agrees on it
+fn thumbprint(members: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(members.as_bytes()))
+}
+
+/// Key new tokens are signed with
+struct SigningKey {
+    encoding: EncodingKey,
+    verifying: VerifyingKey,
+}
+
+impl SigningKey {
+    /// Key of a PEM P-256 (PKCS#8 or SEC1) or RSA (PKCS#8 or PKCS#1) private key
+    fn from_pem(pem: &str) -> Result<Self> {
+        if let Ok(secret_key) =
+            p256::SecretKey::from_pkcs8_pem(pem).or_else(|_| p256::SecretKey::from_sec1_pem(pem))
+        {
+            return Self::ec(&secret_key);
+        }
+
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|_| anyhow!("Expected a PEM P-256 or RSA private key"))?;
+
+        Ok(Self {
+            encoding: EncodingKey::from_rsa_der(private_key.to_pkcs1_der()?.as_bytes()),
+            verifying: VerifyingKey::rsa(&private_key.to_public_key())?,
+        })
+    }
+
+    /// ES256 key of a P-256 private key
+    fn ec(secret_key: &p256::SecretKey) -> Result<Self> {
+        Ok(Self {
+            encoding: EncodingKey::from_ec_der(secret_key.to_pkcs8_der()?.as_bytes()),
+            verifying: VerifyingKey::ec(&secret_key.public_key())?,
+        })
+    }
+}
+
+/// Every PEM block of `pems`, e.g. keys concatenated in one variable
+fn pem_blocks(pems: &str) -> Vec<&str> {
+    let starts = pems
+        .match_indices("-----BEGIN ")
+        .map(|(start, _)| start)
+        .collect::<Vec<_>>();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            pems[start..starts.get(index + 1).copied().unwrap_or(pems.len())].trim()
+        })
+        .collect()
+}
+
+/// Signing key, plus the one it replaced and the retired keys still accepted so tokens
+/// issued before a rotation stay valid
+struct JwtKeys {
+    current: SigningKey,
+    rotated: Option<VerifyingKey>,
+    retired: Vec<VerifyingKey>,
+}
+
+impl JwtKeys {
+    fn new(current: SigningKey, retired: Vec<VerifyingKey>) -> Self {
+        Self {
+            current,
+            rotated: None,
+            retired,
+        }
+    }
+
+    fn rotate(&mut self, signing_key: SigningKey) -> bool {
+        if signing_key.verifying.kid() == self.current.verifying.kid() {
+            return false;
+        }
+
+        self.rotated = Some(std::mem::replace(&mut self.current, signing_key).verifying);
+
+        true
+    }
+
+    fn verifying_keys(&self) -> impl Iterator<Item = &VerifyingKey> {
+        std::iter::once(&self.current.verifying)
+            .chain(&self.rotated)
+            .chain(&self.retired)
+    }
+
+    fn encode(&self, claims: &Claims) -> jsonwebtoken::errors::Result<String> {
+        let key = &self.current.verifying;
+
+        let header = Header {
+            kid: Some(key.kid().to_string()),
+            ..Header::new(key.algorithm)
+        };
+
+        encode(&header, claims, &self.current.encoding)
+    }
+
+    fn decode(
+        &self,
+        token: &str,
+        issuer: &JwtIssuer,
+    ) -> jsonwebtoken::errors::Result<TokenData<Claims>> {
+        let kid = decode_header(token)?.kid.ok_or(ErrorKind::InvalidToken)?;
+
+        let key = self
+            .verifying_keys()
+            .find(|key| key.kid() == kid)
+            .ok_or(ErrorKind::InvalidSignature)?;
+
+        let mut validation = Validation::new(key.algorithm);
+        validation.set_issuer(&[&issuer.issuer]);
+        validation.set_audience(&[&issuer.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        decode::<Claims>(token, &key.decoding, &validation)
+    }
+
+    fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.verifying_keys().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+/// Until keys are set, tokens are signed with a P-256 key generated at startup, for
+/// development only as its tokens don't outlive the process
+static JWT_KEYS: Lazy<RwLock<JwtKeys>> = Lazy::new(|| {
+    let signing_key = SigningKey::ec(&p256::SecretKey::random(&mut OsRng))
+        .expect("Invalid generated signing key");
+
+    RwLock::new(JwtKeys::new(signing_key, Vec::new()))
 });
 
+/// Signs new tokens with the PEM `signing_key`, accepting the tokens of the PEM keys in
+/// `previous_keys` as well. Tokens signed with any other key become invalid
+pub fn set_jwt_keys(signing_key: &str, previous_keys: &str) -> Result<()> {
+    let signing_key = SigningKey::from_pem(signing_key).context("Invalid JWT_SIGNING_KEY")?;
+
+    let retired = pem_blocks(previous_keys)
+        .into_iter()
+        .map(VerifyingKey::from_pem)
+        .collect::<Result<Vec<_>>>()
+        .context("Invalid JWT_PREVIOUS_KEYS")?;
+
+    *JWT_KEYS.write().unwrap_or_else(|e| e.into_inner()) = JwtKeys::new(signing_key, retired);
+
+    Ok(())
+}
+
+/// Signs new tokens with the PEM `signing_key` while still accepting tokens of the current
+/// one, returns false when the key did not change
+pub fn rotate_jwt_key(signing_key: &str) -> Result<bool> {
+    let signing_key = SigningKey::from_pem(signing_key)?;
+
+    Ok(JWT_KEYS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .rotate(signing_key))
+}
+
+/// Public keys tokens are verified with, for other services to verify them without a secret
+pub fn jwks() -> JwkSet {
+    JWT_KEYS.read().unwrap_or_else(|e| e.into_inner()).jwks()
+}
+
+/// Issuer and audience of the tokens of this deployment, tokens of others are rejected
+struct JwtIssuer {
+    issuer: String,
+    audience: String,
+}
+
+static JWT_ISSUER: Lazy<RwLock<JwtIssuer>> = Lazy::new(|| {
+    RwLock::new(JwtIssuer {
+        issuer: "mpc-waas".to_string(),
+        audience: "mpc-waas".to_string(),
+    })
+});
+
+/// Sets the `iss` and `aud` of new tokens, the only ones accepted from then on
+pub fn set_jwt_issuer(issuer: String, audience: String) {
+    *JWT_ISSUER.write().unwrap_or_else(|e| e.into_inner()) = JwtIssuer { issuer, audience };
+}
+
 pub fn generate_claims(user: &UserModel) -> Claims {
     let expiration = Utc::now()
         .checked_add_signed(Duration::days(1))
@@ -33,38 +331,40 @@ pub fn generate_claims(user: &UserModel) -> Claims {
     let iat = Utc::now().timestamp() as usize;
     let jti = Uuid::new_v4().to_string();
 
+    let issuer = JWT_ISSUER.read().unwrap_or_else(|e| e.into_inner());
+
     Claims {
         sub: user.id.to_string(),
         exp: expiration,
         iat,
         jti,
+        iss: issuer.issuer.clone(),
+        aud: issuer.audience.clone(),
         user_id: user.id,
         username: user.username.clone(),
     }
 }
 
-pub fn generate_token(claims: &Claims) -> Result<String, Error> {
-    encode(
-        &Header::default(),
-        claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
-    .map_err(|e| {
-        log::error!("Error generating token: {}", e);
-        ErrorInternalServerError(e)
-    })
+pub fn generate_token(claims: &Claims) -> Result<String, ApiError> {
+    JWT_KEYS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .encode(claims)
+        .map_err(|e| {
+            log::error!("Error generating token: {}", e);
+            ApiError::internal(e.to_string())
+        })
 }
 
-pub async fn validate_token(token: &str) -> Result<TokenData<Claims>, Error> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|e| {
-        log::debug!("JWT validation error: {}", e);
-        ErrorUnauthorized::<String>("Invalid token".to_string())
-    })?;
+pub async fn validate_token(token: &str) -> Result<TokenData<Claims>, ApiError> {
+    let token_data = JWT_KEYS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .decode(token, &JWT_ISSUER.read().unwrap_or_else(|e| e.into_inner()))
+        .map_err(|e| {
+            log::debug!("JWT validation error: {}", e);
+            ApiError::unauthorized("Invalid token").with_code("invalid_token")
+        })?;
 
     Ok(token_data)
 }
@@ -72,6 +372,7 @@ pub async fn validate_token(token: &str) -> Result<TokenData<Claims>, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use p256::pkcs8::{EncodePublicKey, LineEnding};
 
     #[tokio::test]
     async fn test_token_roundtrip() {
@@ -96,4 +397,161 @@ mod tests {
         assert_eq!(original_claims.username, recovered_claims.username);
         assert_eq!(original_claims.jti, recovered_claims.jti);
     }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "1".to_string(),
+            exp: (Utc::now() + Duration::hours(1)).timestamp() as usize,
+            iat: Utc::now().timestamp() as usize,
+            jti: Uuid::new_v4().to_string(),
+            iss: "mpc-waas".to_string(),
+            aud: "mpc-waas".to_string(),
+            user_id: 1,
+            username: "testuser".to_string(),
+        }
+    }
+
+    fn issuer() -> JwtIssuer {
+        JwtIssuer {
+            issuer: "mpc-waas".to_string(),
+            audience: "mpc-waas".to_string(),
+        }
+    }
+
+    fn keys(pem: &str) -> JwtKeys {
+        JwtKeys::new(SigningKey::from_pem(pem).unwrap(), Vec::new())
+    }
+
+    #[test]
+    fn test_rotation_keeps_previous_key() {
+        let mut keys = keys(&OTHER_KEY);
+        let old_token = keys.encode(&claims()).unwrap();
+
+        assert!(keys.rotate(SigningKey::from_pem(&EC_KEY).unwrap()));
+        assert!(!keys.rotate(SigningKey::from_pem(&EC_KEY).unwrap()));
+        let new_token = keys.encode(&claims()).unwrap();
+
+        assert!(keys.decode(&old_token, &issuer()).is_ok());
+        assert!(keys.decode(&new_token, &issuer()).is_ok());
+        assert_eq!(keys.jwks().keys.len(), 2);
+
+        keys.rotate(SigningKey::from_pem(&RSA_KEY).unwrap());
+        assert!(keys.decode(&old_token, &issuer()).is_err());
+        assert!(keys.decode(&new_token, &issuer()).is_ok());
+    }
+
+    #[test]
+    fn test_rsa_key_signs_rs256() {
+        let keys = keys(&RSA_KEY);
+        let token = keys.encode(&claims()).unwrap();
+
+        let header = decode_header(&token).unwrap();
+
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid.as_deref(), Some(keys.current.verifying.kid()));
+        assert!(keys.decode(&token, &issuer()).is_ok());
+    }
+
+    #[test]
+    fn test_previous_public_key_verifies() {
+        let token = keys(&EC_KEY).encode(&claims()).unwrap();
+
+        let keys = JwtKeys::new(
+            SigningKey::from_pem(&OTHER_KEY).unwrap(),
+            pem_blocks(&format!("{}\n{}\n", *EC_PUBLIC_KEY, *RSA_KEY))
+                .into_iter()
+                .map(|pem| VerifyingKey::from_pem(pem).unwrap())
+                .collect(),
+        );
+
+        assert!(keys.decode(&token, &issuer()).is_ok());
+        assert_eq!(keys.jwks().keys.len(), 3);
+    }
+
+    #[test]
+    fn test_unknown_kid_is_rejected() {
+        let keys = keys(&OTHER_KEY);
+        let token = keys.encode(&claims()).unwrap();
+
+        // Same key under a kid it isn't published with
+        let header = Header {
+            kid: Some("unknown".to_string()),
+            ..Header::new(Algorithm::ES256)
+        };
+        let unknown = encode(&header, &claims(), &keys.current.encoding).unwrap();
+
+        let header = Header::new(Algorithm::ES256);
+        let missing = encode(&header, &claims(), &keys.current.encoding).unwrap();
+
+        assert!(keys.decode(&token, &issuer()).is_ok());
+        assert!(keys.decode(&unknown, &issuer()).is_err());
+        assert!(keys.decode(&missing, &issuer()).is_err());
+    }
+
+    #[test]
+    fn test_other_issuer_or_audience_is_rejected() {
+        let keys = keys(&OTHER_KEY);
+        let token = keys.encode(&claims()).unwrap();
+
+        let other_issuer = JwtIssuer {
+            issuer: "other".to_string(),
+            ..issuer()
+        };
+        let other_audience = JwtIssuer {
+            audience: "other".to_string(),
+            ..issuer()
+        };
+
+        assert!(keys.decode(&token, &issuer()).is_ok());
+        assert!(keys.decode(&token, &other_issuer).is_err());
+        assert!(keys.decode(&token, &other_audience).is_err());
+    }
+
+    #[test]
+    fn test_kid_is_the_jwk_thumbprint() {
+        let jwks = keys(&EC_KEY).jwks();
+        let jwk = serde_json::to_value(&jwks.keys[0]).unwrap();
+
+        assert_eq!(jwk["kty"], "EC");
+        assert_eq!(jwk["crv"], "P-256");
+        assert_eq!(jwk["alg"], "ES256");
+        assert_eq!(jwk["use"], "sig");
+
+        let members = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":{},"y":{}}}"#,
+            jwk["x"], jwk["y"]
+        );
+        assert_eq!(jwk["kid"], thumbprint(&members));
+    }
+
+    static EC_KEY: Lazy<String> = Lazy::new(|| {
+        p256::SecretKey::random(&mut OsRng)
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string()
+    });
+
+    static EC_PUBLIC_KEY: Lazy<String> = Lazy::new(|| {
+        p256::SecretKey::from_pkcs8_pem(&EC_KEY)
+            .unwrap()
+            .public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap()
+    });
+
+    static RSA_KEY: Lazy<String> = Lazy::new(|| {
+        RsaPrivateKey::new(&mut OsRng, 2048)
+            .unwrap()
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string()
+    });
+
+    /// Signing key of its own, distinct from the other keys of the tests
+    static OTHER_KEY: Lazy<String> = Lazy::new(|| {
+        p256::SecretKey::random(&mut OsRng)
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string()
+    });
 }
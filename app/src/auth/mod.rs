@@ -1,5 +1,10 @@
 mod jwt;
+mod oidc;
 mod password;
 
-pub use jwt::{Claims, generate_claims, generate_token, validate_token};
-pub use password::{hash_password, verify_password};
+pub use jwt::{
+    Claims, generate_claims, generate_token, jwks, rotate_jwt_key, set_jwt_issuer, set_jwt_keys,
+    validate_token,
+};
+pub use oidc::{Authorization, IdTokenClaims, OidcClient};
+pub use password::{hash_password, needs_rehash, set_password_params, verify_password};
@@ -0,0 +1,448 @@
+use alloy::transports::http::reqwest::{Client, Url};
+use anyhow::{Result, anyhow};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::config::app_config::{OidcConfig, ProxyConfig};
+use crate::utils::http::http_client;
+
+/// Endpoints of the provider from its discovery document
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of a verified ID token a user is looked up or created from
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    nonce: Option<String>,
+}
+
+/// Random values of an authorization request, checked again by its callback
+pub struct Authorization {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+impl Authorization {
+    pub fn new() -> Self {
+        Self {
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+        }
+    }
+
+    /// S256 PKCE challenge of the verifier, RFC 7636
+    fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+/// 256 random bits, URL-safe
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Authorization code flow against an OpenID Connect provider. The discovery document and
+/// the keys are fetched on first use, the keys again when a token is signed with a new one
+pub struct OidcClient {
+    client: Client,
+    config: OidcConfig,
+    metadata: RwLock<Option<Arc<ProviderMetadata>>>,
+    jwks: RwLock<Option<Arc<JwkSet>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: &OidcConfig, proxy: &ProxyConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client(proxy)?,
+            config: config.clone(),
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        })
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    async fn metadata(&self) -> Result<Arc<ProviderMetadata>> {
+        if let Some(metadata) = self
+            .metadata
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+        {
+            return Ok(metadata);
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+
+        let metadata: ProviderMetadata = self.get(&url).await?;
+
+        // Tokens are checked against the configured issuer, the document must agree on it
+        if metadata.issuer != self.config.issuer {
+            return Err(anyhow!(
+                "Provider issuer {} doesn't match OIDC_ISSUER",
+                metadata.issuer
+            ));
+        }
+
+        let metadata = Arc::new(metadata);
+        *self.metadata.write().unwrap_or_else(|e| e.into_inner()) = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    async fn jwks(&self, refresh: bool) -> Result<Arc<JwkSet>> {
+        if !refresh && let Some(jwks) = self.jwks.read().unwrap_or_else(|e| e.into_inner()).clone()
+        {
+            return Ok(jwks);
+        }
+
+        let jwks = Arc::new(self.get::<JwkSet>(&self.metadata().await?.jwks_uri).await?);
+        *self.jwks.write().unwrap_or_else(|e| e.into_inner()) = Some(jwks.clone());
+
+        Ok(jwks)
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T> {
+        let response = self
+            .client
+            .get(url)
+            .timeout(Duration::from_secs(self.config.timeout))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Provider returned {} for {url}", response.status()));
+        }
+
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+
+    /// Address of the provider login page the user is sent to
+    pub async fn authorization_url(&self, authorization: &Authorization) -> Result<String> {
+        let metadata = self.metadata().await?;
+
+        authorization_url(
+            &metadata.authorization_endpoint,
+            &self.config,
+            authorization,
+        )
+    }
+
+    /// Exchanges the code of the callback for the ID token and verifies it
+    pub async fn authenticate(
+        &self,
+        code: &str,
+        authorization: &Authorization,
+    ) -> Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", &authorization.code_verifier),
+        ];
+
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response = self
+            .client
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .timeout(Duration::from_secs(self.config.timeout))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Token endpoint returned {}", response.status()));
+        }
+
+        let token: TokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+
+        let verify = |jwks: &JwkSet| {
+            verify_id_token(
+                jwks,
+                &metadata.issuer,
+                &self.config.client_id,
+                &token.id_token,
+                &authorization.nonce,
+            )
+        };
+
+        let claims = match verify(&*self.jwks(false).await?) {
+            Err(VerifyError::UnknownKey) => verify(&*self.jwks(true).await?),
+            res => res,
+        };
+
+        claims.map_err(|err| match err {
+            VerifyError::UnknownKey => anyhow!("ID token is signed with an unknown key"),
+            VerifyError::Invalid(reason) => anyhow!("Invalid ID token: {reason}"),
+        })
+    }
+}
+
+fn authorization_url(
+    endpoint: &str,
+    config: &OidcConfig,
+    authorization: &Authorization,
+) -> Result<String> {
+    let url = Url::parse_with_params(
+        endpoint,
+        [
+            ("response_type", "code"),
+            ("client_id", &config.client_id),
+            ("redirect_uri", &config.redirect_url),
+            ("scope", &config.scopes),
+            ("state", &authorization.state),
+            ("nonce", &authorization.nonce),
+            ("code_challenge", &authorization.code_challenge()),
+            ("code_challenge_method", "S256"),
+        ],
+    )?;
+
+    Ok(url.into())
+}
+
+#[derive(Debug)]
+enum VerifyError {
+    /// No key of the provider has the `kid` of the token, it may have rotated its keys
+    UnknownKey,
+    Invalid(String),
+}
+
+/// Claims of an ID token signed by a key of the provider for this client, bound to the
+/// login by its nonce
+fn verify_id_token(
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+    id_token: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, VerifyError> {
+    let header = decode_header(id_token).map_err(|e| VerifyError::Invalid(e.to_string()))?;
+
+    // Only the asymmetric algorithms of the JWKS, never a secret
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(VerifyError::Invalid(format!(
+            "{:?} isn't allowed",
+            header.alg
+        )));
+    }
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(VerifyError::UnknownKey)?;
+
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| VerifyError::Invalid(e.to_string()))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| VerifyError::Invalid(e.to_string()))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(VerifyError::Invalid("nonce mismatch".to_string()));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use once_cell::sync::Lazy;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use p256::pkcs8::{EncodePrivateKey, LineEnding};
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            issuer: "https://idp.example.com/".to_string(),
+            client_id: "waas".to_string(),
+            client_secret: None,
+            redirect_url: "https://waas.example.com/api/auth/oidc/callback".to_string(),
+            scopes: "openid email".to_string(),
+            link_by_email: false,
+            timeout: 10,
+        }
+    }
+
+    fn jwks() -> JwkSet {
+        let point = EC_KEY.public_key().to_encoded_point(false);
+
+        serde_json::from_value(serde_json::json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "kid": "key-1",
+                "use": "sig",
+                "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+                "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn id_token(kid: &str, claims: serde_json::Value) -> String {
+        let header = Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(Algorithm::ES256)
+        };
+
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_ec_pem(EC_KEY.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes())
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": "https://idp.example.com/",
+            "aud": "waas",
+            "sub": "auth0|123",
+            "exp": Utc::now().timestamp() + 300,
+            "email": "alice@example.com",
+            "email_verified": true,
+            "nonce": "nonce",
+        })
+    }
+
+    fn verify(id_token: &str) -> Result<IdTokenClaims, VerifyError> {
+        verify_id_token(
+            &jwks(),
+            "https://idp.example.com/",
+            "waas",
+            id_token,
+            "nonce",
+        )
+    }
+
+    #[test]
+    fn test_authorization_url_carries_pkce_and_nonce() {
+        let authorization = Authorization::new();
+
+        let url = authorization_url(
+            "https://idp.example.com/authorize?audience=api",
+            &config(),
+            &authorization,
+        )
+        .unwrap();
+
+        let url = Url::parse(&url).unwrap();
+        let params = url
+            .query_pairs()
+            .into_owned()
+            .collect::<std::collections::HashMap<_, _>>();
+
+        assert_eq!(params["audience"], "api");
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], "waas");
+        assert_eq!(params["scope"], "openid email");
+        assert_eq!(params["state"], authorization.state);
+        assert_eq!(params["nonce"], authorization.nonce);
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(
+            params["code_challenge"],
+            URL_SAFE_NO_PAD.encode(Sha256::digest(authorization.code_verifier.as_bytes()))
+        );
+        assert_ne!(authorization.state, Authorization::new().state);
+    }
+
+    #[test]
+    fn test_id_token_is_verified() {
+        let claims = verify(&id_token("key-1", claims())).unwrap();
+
+        assert_eq!(claims.sub, "auth0|123");
+        assert_eq!(claims.email.as_deref(), Some("alice@example.com"));
+        assert!(claims.email_verified);
+    }
+
+    #[test]
+    fn test_id_token_of_another_login_or_client_is_rejected() {
+        for (name, value) in [
+            ("nonce", serde_json::json!("replayed")),
+            ("aud", serde_json::json!("other-client")),
+            ("iss", serde_json::json!("https://evil.example.com/")),
+            ("exp", serde_json::json!(Utc::now().timestamp() - 300)),
+        ] {
+            let mut claims = claims();
+            claims[name] = value;
+
+            assert!(
+                matches!(
+                    verify(&id_token("key-1", claims)),
+                    Err(VerifyError::Invalid(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_id_token_of_unknown_key_or_secret_is_rejected() {
+        assert!(matches!(
+            verify(&id_token("key-2", claims())),
+            Err(VerifyError::UnknownKey)
+        ));
+
+        let header = Header {
+            kid: Some("key-1".to_string()),
+            ..Header::new(Algorithm::HS256)
+        };
+        let token = encode(&header, &claims(), &EncodingKey::from_secret(b"secret")).unwrap();
+
+        assert!(matches!(verify(&token), Err(VerifyError::Invalid(_))));
+    }
+
+    static EC_KEY: Lazy<p256::SecretKey> = Lazy::new(|| p256::SecretKey::random(&mut OsRng));
+}
@@ -1,37 +1,81 @@
-use actix_web::{Error, error::ErrorInternalServerError};
+use crate::api::error::ApiError;
 use argon2::{
-    Argon2,
+    ARGON2ID_IDENT, Algorithm, Argon2, Params, Version,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
 
-pub fn hash_password(password: &str) -> Result<String, Error> {
+static PASSWORD_PARAMS: Lazy<RwLock<Params>> = Lazy::new(|| RwLock::new(Params::default()));
+
+/// Sets the Argon2id costs new password hashes are computed with, hashes of weaker costs are
+/// replaced on the next login of their user
+pub fn set_password_params(params: Params) {
+    *PASSWORD_PARAMS.write().unwrap_or_else(|e| e.into_inner()) = params;
+}
+
+fn password_params() -> Params {
+    PASSWORD_PARAMS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+pub fn hash_password(password: &str) -> Result<String, ApiError> {
+    hash_password_with(password, password_params())
+}
+
+fn hash_password_with(password: &str, params: Params) -> Result<String, ApiError> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
     let password_hash = argon2
         .hash_password(password.as_bytes(), &salt)
         .map_err(|e| {
             log::error!("Error hashing password: {}", e);
-            ErrorInternalServerError(e)
+            ApiError::internal(e.to_string())
         })?
         .to_string();
 
     Ok(password_hash)
 }
 
-pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, Error> {
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, ApiError> {
     let parsed_hash = PasswordHash::new(password_hash).map_err(|e| {
         log::error!("Error parsing hash: {}", e);
-        ErrorInternalServerError(e)
+        ApiError::internal(e.to_string())
     })?;
 
+    // The costs are read from the hash
     Ok(Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok())
 }
 
+/// Whether the hash was computed with another algorithm than Argon2id or with lower costs
+/// than the current ones, so it should be replaced once the password is known
+pub fn needs_rehash(password_hash: &str) -> bool {
+    is_weaker(password_hash, &password_params())
+}
+
+fn is_weaker(password_hash: &str, current: &Params) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+
+    let Ok(params) = Params::try_from(&parsed_hash) else {
+        return true;
+    };
+
+    parsed_hash.algorithm != ARGON2ID_IDENT
+        || parsed_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() < current.m_cost()
+        || params.t_cost() < current.t_cost()
+        || params.p_cost() < current.p_cost()
+}
+
 #[cfg(test)]
 pub mod tests {
-    use super::{hash_password, verify_password};
+    use super::*;
 
     #[test]
     fn test_verify_password_correct() {
@@ -53,4 +97,29 @@ pub mod tests {
         assert!(verify_result.is_ok());
         assert!(!verify_result.unwrap());
     }
+
+    #[test]
+    fn test_weaker_hashes_need_a_rehash() {
+        let weak = Params::new(8 * 1024, 1, 1, None).unwrap();
+        let strong = Params::new(16 * 1024, 2, 1, None).unwrap();
+
+        let hash = hash_password_with("correct_password", weak.clone()).unwrap();
+
+        assert!(verify_password("correct_password", &hash).unwrap());
+        assert!(is_weaker(&hash, &strong));
+        assert!(!is_weaker(&hash, &weak));
+
+        let hash = hash_password_with("correct_password", strong.clone()).unwrap();
+
+        // Lowering the costs doesn't downgrade stronger hashes
+        assert!(!is_weaker(&hash, &weak));
+        assert!(!is_weaker(&hash, &strong));
+
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, strong.clone())
+            .hash_password(b"correct_password", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+
+        assert!(is_weaker(&argon2i, &strong));
+    }
 }
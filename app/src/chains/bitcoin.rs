@@ -0,0 +1,189 @@
+use sha2::{Digest, Sha256};
+
+use super::solana::encode_base58;
+
+static CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Checksum constants of bech32 (BIP-173) for witness version 0 and bech32m (BIP-350) after
+static BECH32_CONST: u32 = 1;
+static BECH32M_CONST: u32 = 0x2bc830a3;
+
+// Addresses are derived for mainnet
+static HRP: &str = "bc";
+static P2PKH_VERSION: u8 = 0x00;
+static P2SH_VERSION: u8 = 0x05;
+static XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+
+fn polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+    let mut checksum = 1u32;
+
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ u32::from(*value);
+
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+
+    checksum
+}
+
+/// Regroups bytes into padded 5-bit words
+fn to_words(bytes: &[u8]) -> Vec<u8> {
+    let mut words = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0u32);
+
+    for byte in bytes {
+        acc = (acc << 8) | u32::from(*byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            words.push(((acc >> bits) & 31) as u8);
+        }
+    }
+
+    if bits > 0 {
+        words.push(((acc << (5 - bits)) & 31) as u8);
+    }
+
+    words
+}
+
+/// Bech32 address of a SegWit output, bech32m from witness version 1 on
+fn segwit_address(version: u8, program: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend(to_words(program));
+
+    let mut values: Vec<u8> = HRP.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(HRP.bytes().map(|c| c & 31));
+    values.extend(&data);
+    values.extend([0; 6]);
+
+    let constant = if version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    let checksum = polymod(&values) ^ constant;
+
+    data.extend((0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8));
+
+    let encoded: String = data
+        .iter()
+        .map(|word| CHARSET[*word as usize] as char)
+        .collect();
+
+    format!("{HRP}1{encoded}")
+}
+
+/// Bech32m address of a SegWit v1 (Taproot) output paying to the x-only `output_key`
+pub fn taproot_address(output_key: &[u8; 32]) -> String {
+    segwit_address(1, output_key)
+}
+
+fn base58check(version: &[u8], data: &[u8]) -> String {
+    let mut payload = version.to_vec();
+    payload.extend_from_slice(data);
+
+    let checksum = Sha256::digest(Sha256::digest(&payload));
+    payload.extend_from_slice(&checksum[..4]);
+
+    encode_base58(&payload)
+}
+
+/// BIP-32 `xpub` of a master key, from its compressed public key and chain code
+pub fn extended_public_key(public_key: &[u8; 33], chain_code: &[u8; 32]) -> String {
+    // Depth, parent fingerprint and child number are all zero for a master key
+    let mut data = vec![0u8; 9];
+    data.extend_from_slice(chain_code);
+    data.extend_from_slice(public_key);
+
+    base58check(&XPUB_VERSION, &data)
+}
+
+/// Address an output script pays to, `None` for scripts without one such as `OP_RETURN`
+pub fn script_address(script: &[u8]) -> Option<String> {
+    match script {
+        // OP_n <program>, witness programs are 2 to 40 bytes
+        [op @ (0x00 | 0x51..=0x60), len, program @ ..]
+            if usize::from(*len) == program.len() && (2..=40).contains(&program.len()) =>
+        {
+            let version = if *op == 0 { 0 } else { op - 0x50 };
+
+            Some(segwit_address(version, program))
+        }
+        // OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+        [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] if hash.len() == 20 => {
+            Some(base58check(&[P2PKH_VERSION], hash))
+        }
+        // OP_HASH160 <hash> OP_EQUAL
+        [0xa9, 0x14, hash @ .., 0x87] if hash.len() == 20 => {
+            Some(base58check(&[P2SH_VERSION], hash))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taproot_address() {
+        // First receiving address of the BIP-86 test vectors
+        let output_key: [u8; 32] =
+            hex::decode("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        assert_eq!(
+            taproot_address(&output_key),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn test_extended_public_key() {
+        // Master key of the first BIP-32 test vector
+        let public_key: [u8; 33] =
+            hex::decode("0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let chain_code: [u8; 32] =
+            hex::decode("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        assert_eq!(
+            extended_public_key(&public_key, &chain_code),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+    }
+
+    #[test]
+    fn test_script_address() {
+        // Addresses of the BIP-173 test vectors and of the hash160 of the secp256k1 generator
+        let p2wpkh = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let p2pkh = hex::decode("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+
+        assert_eq!(
+            script_address(&p2wpkh).as_deref(),
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        assert_eq!(
+            script_address(&p2pkh).as_deref(),
+            Some("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH")
+        );
+        assert_eq!(script_address(&[0x6a, 0x01, 0x00]), None);
+    }
+}
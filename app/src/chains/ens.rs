@@ -0,0 +1,81 @@
+use alloy::primitives::{Address, B256, address, keccak256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use anyhow::Result;
+
+/// ENS registry, deployed at the same address on mainnet and the public testnets
+const ENS_REGISTRY: Address = address!("0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e");
+
+sol! {
+    function resolver(bytes32 node) external view returns (address);
+    function addr(bytes32 node) external view returns (address);
+}
+
+/// EIP-137 namehash of a lowercase, dot-separated name
+fn namehash(name: &str) -> B256 {
+    name.rsplit('.')
+        .filter(|label| !label.is_empty())
+        .fold(B256::ZERO, |node, label| {
+            keccak256([node.as_slice(), keccak256(label).as_slice()].concat())
+        })
+}
+
+/// Address an ENS name points to, `None` when it has no resolver or no address record
+///
+/// Names are only lowercased, not normalized with UTS-46.
+pub async fn resolve_name(
+    provider: &(dyn Provider + Send + Sync),
+    name: &str,
+) -> Result<Option<Address>> {
+    let node = namehash(&name.to_lowercase());
+
+    let resolver = call_address(provider, ENS_REGISTRY, resolverCall { node }).await?;
+
+    if resolver.is_zero() {
+        return Ok(None);
+    }
+
+    let address = call_address(provider, resolver, addrCall { node }).await?;
+
+    Ok((!address.is_zero()).then_some(address))
+}
+
+async fn call_address<C: SolCall<Return = Address>>(
+    provider: &(dyn Provider + Send + Sync),
+    to: Address,
+    call: C,
+) -> Result<Address> {
+    let input = call.abi_encode();
+
+    let output = provider
+        .call(TransactionRequest::default().to(to).input(input.into()))
+        .await?;
+
+    // Accounts without code, e.g. no registry on a local chain, return empty data
+    if output.is_empty() {
+        return Ok(Address::ZERO);
+    }
+
+    Ok(C::abi_decode_returns(&output)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::b256;
+
+    #[test]
+    fn test_namehash() {
+        assert_eq!(namehash(""), B256::ZERO);
+        assert_eq!(
+            namehash("eth"),
+            b256!("0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae")
+        );
+        assert_eq!(
+            namehash("foo.eth"),
+            b256!("0xde9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f")
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use alloy::primitives::utils::format_units;
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use anyhow::Result;
+
+sol! {
+    interface IERC20 {
+        function allowance(address owner, address spender) external view returns (uint256);
+        function approve(address spender, uint256 value) external returns (bool);
+        function balanceOf(address account) external view returns (uint256);
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+    }
+}
+
+/// Balance of the wallet in one ERC-20 token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalance {
+    pub token: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    pub balance: U256,
+}
+
+impl TokenBalance {
+    /// Balance in whole tokens, e.g. "1.500000" for 1500000 of a 6 decimals token
+    pub fn formatted(&self) -> String {
+        format_units(self.balance, self.decimals).unwrap_or_else(|_| self.balance.to_string())
+    }
+}
+
+async fn view<C: SolCall>(
+    provider: &(dyn Provider + Send + Sync),
+    token: Address,
+    call: C,
+) -> Result<C::Return> {
+    let output = provider
+        .call(
+            TransactionRequest::default()
+                .to(token)
+                .input(call.abi_encode().into()),
+        )
+        .await?;
+
+    Ok(C::abi_decode_returns(&output)?)
+}
+
+/// `approve` call of the token contract, replacing the current allowance of the spender
+pub fn approve_call_data(spender: Address, amount: U256) -> Bytes {
+    IERC20::approveCall {
+        spender,
+        value: amount,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Amount of the owner's tokens the spender may still transfer
+pub async fn allowance(
+    provider: &(dyn Provider + Send + Sync),
+    token: Address,
+    owner: Address,
+    spender: Address,
+) -> Result<U256> {
+    view(provider, token, IERC20::allowanceCall { owner, spender }).await
+}
+
+/// Balance, symbol and decimals of the token held by the owner
+pub async fn token_balance(
+    provider: &(dyn Provider + Send + Sync),
+    token: Address,
+    owner: Address,
+) -> Result<TokenBalance> {
+    let (balance, symbol, decimals) = futures::try_join!(
+        view(provider, token, IERC20::balanceOfCall { account: owner }),
+        view(provider, token, IERC20::symbolCall {}),
+        view(provider, token, IERC20::decimalsCall {}),
+    )?;
+
+    Ok(TokenBalance {
+        token,
+        symbol,
+        decimals,
+        balance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approve_call_data() {
+        let call_data = approve_call_data(Address::repeat_byte(1), U256::MAX);
+
+        // approve(address,uint256)
+        assert_eq!(call_data[..4], [0x09, 0x5e, 0xa7, 0xb3]);
+        assert_eq!(
+            IERC20::approveCall::abi_decode(&call_data).unwrap().value,
+            U256::MAX
+        );
+    }
+
+    #[test]
+    fn test_token_balance_formatted() {
+        let balance = TokenBalance {
+            token: Address::ZERO,
+            symbol: "USDC".to_string(),
+            decimals: 6,
+            balance: U256::from(1_500_000),
+        };
+
+        assert_eq!(balance.formatted(), "1.500000");
+    }
+}
@@ -0,0 +1,135 @@
+use alloy::primitives::Address;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::app_config::{ChainConfig, ProxyConfig};
+use crate::db::models::Chain;
+use crate::utils::http::http_client;
+
+mod bitcoin;
+mod ens;
+mod erc20;
+mod multicall;
+mod nft;
+mod pool;
+mod psbt;
+mod quote;
+mod safe;
+mod simulation;
+mod solana;
+mod user_operation;
+
+pub use bitcoin::{extended_public_key, script_address, taproot_address};
+pub use ens::resolve_name;
+pub use erc20::{TokenBalance, allowance, approve_call_data, token_balance};
+pub use multicall::{CallOutcome, multicall};
+pub use nft::{NftStandard, NftTransfer};
+pub use pool::ProviderPool;
+pub use psbt::Psbt;
+pub use quote::{l1_fee, quote};
+pub use safe::{SafeClient, SafeTransaction, safe_nonce};
+pub use simulation::{Simulation, simulate};
+pub use solana::{
+    LAMPORTS_PER_SIGNATURE, SolanaClient, encode_base58, parse_pubkey, signed_transaction,
+    transfer_message,
+};
+pub use user_operation::{BundlerClient, ENTRY_POINT, UserOperation, account_nonce};
+
+pub struct ChainEntry {
+    pub config: ChainConfig,
+    /// JSON-RPC provider of EVM chains, failing over between the RPC endpoints
+    pub provider: Option<Arc<ProviderPool>>,
+    /// JSON-RPC client of Solana
+    pub solana: Option<SolanaClient>,
+    /// ERC-4337 bundler of EVM chains
+    pub bundler: Option<BundlerClient>,
+    /// Safe transaction service of EVM chains
+    pub safe: Option<SafeClient>,
+    /// ERC-20 contracts whose balances are listed for wallets of EVM chains
+    pub tokens: Vec<Address>,
+}
+
+/// Networks the app can transact on, built once from the chain configuration
+pub struct ChainRegistry {
+    chains: HashMap<Chain, ChainEntry>,
+}
+
+impl ChainRegistry {
+    pub fn new(configs: &[ChainConfig], proxy: &ProxyConfig) -> Result<Self> {
+        let client = http_client(proxy)?;
+
+        let mut chains = HashMap::new();
+
+        for config in configs {
+            let url = config
+                .rpc_urls
+                .first()
+                .ok_or_else(|| anyhow!("No RPC URL configured for {:?}", config.chain))?;
+
+            let provider = if config.chain.is_evm() {
+                Some(Arc::new(ProviderPool::new(
+                    client.clone(),
+                    &config.rpc_urls,
+                )?))
+            } else {
+                None
+            };
+
+            let solana =
+                (config.chain == Chain::Solana).then(|| SolanaClient::new(client.clone(), url));
+
+            let bundler = config
+                .bundler_url
+                .as_deref()
+                .filter(|_| config.chain.is_evm())
+                .map(|url| BundlerClient::new(client.clone(), url));
+
+            let safe = config
+                .safe_service_url
+                .as_deref()
+                .filter(|_| config.chain.is_evm())
+                .map(|url| SafeClient::new(client.clone(), url));
+
+            let tokens = config
+                .tokens
+                .iter()
+                .filter(|_| config.chain.is_evm())
+                .map(|token| {
+                    token.parse().map_err(|_| {
+                        anyhow!("Invalid token address {token} for {:?}", config.chain)
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            chains.insert(
+                config.chain.clone(),
+                ChainEntry {
+                    config: config.clone(),
+                    provider,
+                    solana,
+                    bundler,
+                    safe,
+                    tokens,
+                },
+            );
+        }
+
+        Ok(Self { chains })
+    }
+
+    pub fn get(&self, chain: &Chain) -> Option<&ChainEntry> {
+        self.chains.get(chain)
+    }
+
+    /// Chains with a configured network
+    pub fn chains(&self) -> impl Iterator<Item = &Chain> {
+        self.chains.keys()
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &Arc<ProviderPool>> {
+        self.chains
+            .values()
+            .filter_map(|entry| entry.provider.as_ref())
+    }
+}
@@ -0,0 +1,65 @@
+use alloy::primitives::{Address, Bytes, address};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use anyhow::Result;
+
+/// Multicall3, deployed at the same address on every major EVM chain
+pub const MULTICALL3: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
+sol! {
+    struct Call3 {
+        address target;
+        bool allowFailure;
+        bytes callData;
+    }
+
+    struct Result3 {
+        bool success;
+        bytes returnData;
+    }
+
+    function aggregate3(Call3[] calldata calls) external payable returns (Result3[] memory returnData);
+}
+
+/// Outcome of one call of a multicall, the revert data when it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallOutcome {
+    pub success: bool,
+    pub data: Bytes,
+}
+
+/// Runs the `(contract, call data)` calls in a single `eth_call` of Multicall3 at the
+/// latest block, a reverting call fails on its own rather than the batch
+pub async fn multicall(
+    provider: &(dyn Provider + Send + Sync),
+    calls: Vec<(Address, Bytes)>,
+) -> Result<Vec<CallOutcome>> {
+    let calls = calls
+        .into_iter()
+        .map(|(target, call_data)| Call3 {
+            target,
+            allowFailure: true,
+            callData: call_data,
+        })
+        .collect();
+
+    let output = provider
+        .call(
+            TransactionRequest::default()
+                .to(MULTICALL3)
+                .input(aggregate3Call { calls }.abi_encode().into()),
+        )
+        .await?;
+
+    let results = aggregate3Call::abi_decode_returns(&output)?;
+
+    Ok(results
+        .into_iter()
+        .map(|result| CallOutcome {
+            success: result.success,
+            data: result.returnData,
+        })
+        .collect())
+}
@@ -0,0 +1,137 @@
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy::transports::RpcError;
+use anyhow::Result;
+use serde::Deserialize;
+
+sol! {
+    interface IERC721 {
+        function ownerOf(uint256 tokenId) external view returns (address owner);
+        function safeTransferFrom(address from, address to, uint256 tokenId) external;
+    }
+
+    interface IERC1155 {
+        function balanceOf(address account, uint256 id) external view returns (uint256);
+        function safeTransferFrom(address from, address to, uint256 id, uint256 value, bytes data) external;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NftStandard {
+    Erc721,
+    Erc1155,
+}
+
+/// Token of an ERC-721 or ERC-1155 contract moved out of the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftTransfer {
+    pub standard: NftStandard,
+    pub contract: Address,
+    pub token_id: U256,
+    /// Always 1 for ERC-721 tokens
+    pub amount: U256,
+}
+
+impl NftTransfer {
+    /// `safeTransferFrom` call of the token contract, receivers that are contracts must
+    /// accept the token
+    pub fn call_data(&self, from: Address, to: Address) -> Bytes {
+        match self.standard {
+            NftStandard::Erc721 => IERC721::safeTransferFromCall {
+                from,
+                to,
+                tokenId: self.token_id,
+            }
+            .abi_encode(),
+            NftStandard::Erc1155 => IERC1155::safeTransferFromCall {
+                from,
+                to,
+                id: self.token_id,
+                value: self.amount,
+                data: Bytes::new(),
+            }
+            .abi_encode(),
+        }
+        .into()
+    }
+
+    /// Whether the owner holds the token, a reverting `ownerOf` means it doesn't exist
+    pub async fn is_owned_by(
+        &self,
+        provider: &(dyn Provider + Send + Sync),
+        owner: Address,
+    ) -> Result<bool> {
+        let input = match self.standard {
+            NftStandard::Erc721 => IERC721::ownerOfCall {
+                tokenId: self.token_id,
+            }
+            .abi_encode(),
+            NftStandard::Erc1155 => IERC1155::balanceOfCall {
+                account: owner,
+                id: self.token_id,
+            }
+            .abi_encode(),
+        };
+
+        let output = match provider
+            .call(
+                TransactionRequest::default()
+                    .to(self.contract)
+                    .input(input.into()),
+            )
+            .await
+        {
+            Ok(output) => output,
+            Err(RpcError::ErrorResp(_)) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(match self.standard {
+            NftStandard::Erc721 => IERC721::ownerOfCall::abi_decode_returns(&output)? == owner,
+            NftStandard::Erc1155 => {
+                IERC1155::balanceOfCall::abi_decode_returns(&output)? >= self.amount
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nft_transfer_call_data() {
+        let transfer = NftTransfer {
+            standard: NftStandard::Erc721,
+            contract: Address::repeat_byte(1),
+            token_id: U256::from(7),
+            amount: U256::from(1),
+        };
+
+        let from = Address::repeat_byte(2);
+        let to = Address::repeat_byte(3);
+
+        // safeTransferFrom(address,address,uint256)
+        assert_eq!(transfer.call_data(from, to)[..4], [0x42, 0x84, 0x2e, 0x0e]);
+
+        let transfer = NftTransfer {
+            standard: NftStandard::Erc1155,
+            ..transfer
+        };
+
+        // safeTransferFrom(address,address,uint256,uint256,bytes)
+        let call_data = transfer.call_data(from, to);
+
+        assert_eq!(call_data[..4], [0xf2, 0x42, 0x43, 0x2a]);
+        assert_eq!(
+            IERC1155::safeTransferFromCall::abi_decode(&call_data)
+                .unwrap()
+                .value,
+            U256::from(1)
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use actix_web::rt::time::timeout;
+use alloy::providers::{Provider, RootProvider};
+use alloy::rpc::client::RpcClient;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::http::Http;
+use alloy::transports::http::reqwest::Client;
+use alloy::transports::{TransportError, TransportErrorKind, TransportFut, TransportResult};
+use anyhow::{Result, anyhow};
+use futures::future::join_all;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::Service;
+
+/// Methods that change chain state, always sent to the first healthy endpoint
+const WRITE_METHODS: [&str; 2] = ["eth_sendRawTransaction", "eth_sendTransaction"];
+
+/// Time an endpoint has to answer a health probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Endpoint {
+    /// Host of the URL, RPC URLs often carry an API key so the full URL is never logged
+    host: String,
+    transport: Http<Client>,
+    healthy: AtomicBool,
+}
+
+/// Order endpoints are tried in, healthy ones first from `start` of the rotation, endpoints
+/// marked down are only tried once no healthy endpoint answered
+fn try_order(healthy: &[bool], start: usize) -> Vec<usize> {
+    let count = healthy.len();
+
+    let (up, down): (Vec<usize>, Vec<usize>) = (0..count)
+        .map(|offset| (start + offset) % count)
+        .partition(|index| healthy[*index]);
+
+    up.into_iter().chain(down).collect()
+}
+
+/// Transport failing over between the endpoints of a chain, reads are spread round-robin
+#[derive(Clone)]
+struct FailoverTransport {
+    endpoints: Arc<Vec<Endpoint>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl FailoverTransport {
+    async fn send(self, request: RequestPacket) -> TransportResult<ResponsePacket> {
+        let write = request
+            .method_names()
+            .any(|method| WRITE_METHODS.contains(&method));
+
+        // Broadcasts stick to the preferred endpoint so its mempool sees every nonce
+        let start = if write {
+            0
+        } else {
+            self.next.fetch_add(1, Ordering::Relaxed)
+        };
+
+        let healthy = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.healthy.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+
+        let mut last_error = None;
+
+        // JSON-RPC errors are responses of a working endpoint, only transport failures
+        // move on to the next endpoint
+        for index in try_order(&healthy, start) {
+            let endpoint = &self.endpoints[index];
+
+            match endpoint.transport.clone().call(request.clone()).await {
+                Ok(response) => {
+                    endpoint.healthy.store(true, Ordering::Relaxed);
+
+                    return Ok(response);
+                }
+                Err(err) => {
+                    if endpoint.healthy.swap(false, Ordering::Relaxed) {
+                        log::warn!("RPC endpoint {} is down: {err}", endpoint.host);
+                    }
+
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| TransportErrorKind::custom_str("No RPC endpoint")))
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().send(request))
+    }
+}
+
+/// Provider of a chain backed by all of its RPC endpoints, failing over when one of them
+/// stops answering
+pub struct ProviderPool {
+    root: RootProvider,
+    transport: FailoverTransport,
+}
+
+impl ProviderPool {
+    pub fn new(client: Client, urls: &[String]) -> Result<Self> {
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                let url: alloy::transports::http::reqwest::Url = url.parse()?;
+
+                Ok(Endpoint {
+                    host: url.host_str().unwrap_or_default().to_string(),
+                    transport: Http::with_client(client.clone(), url),
+                    healthy: AtomicBool::new(true),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if endpoints.is_empty() {
+            return Err(anyhow!("No RPC endpoint"));
+        }
+
+        let transport = FailoverTransport {
+            endpoints: Arc::new(endpoints),
+            next: Arc::new(AtomicUsize::new(0)),
+        };
+
+        Ok(Self {
+            root: RootProvider::new(RpcClient::new(transport.clone(), false)),
+            transport,
+        })
+    }
+
+    /// Probes every endpoint with `eth_blockNumber` so endpoints marked down come back once
+    /// they answer again, returns the number of healthy endpoints
+    pub async fn check(&self) -> usize {
+        let probes = self.transport.endpoints.iter().map(|endpoint| async move {
+            let provider = RootProvider::<alloy::network::Ethereum>::new(RpcClient::new(
+                endpoint.transport.clone(),
+                false,
+            ));
+
+            let healthy = matches!(
+                timeout(PROBE_TIMEOUT, provider.get_block_number()).await,
+                Ok(Ok(_))
+            );
+
+            if healthy != endpoint.healthy.swap(healthy, Ordering::Relaxed) {
+                if healthy {
+                    log::info!("RPC endpoint {} is up", endpoint.host);
+                } else {
+                    log::warn!("RPC endpoint {} is down", endpoint.host);
+                }
+            }
+
+            healthy
+        });
+
+        join_all(probes)
+            .await
+            .into_iter()
+            .filter(|healthy| *healthy)
+            .count()
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.transport.endpoints.len()
+    }
+}
+
+impl Provider for ProviderPool {
+    fn root(&self) -> &RootProvider {
+        &self.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_order_rotates_healthy_endpoints_first() {
+        assert_eq!(try_order(&[true, true, true], 0), vec![0, 1, 2]);
+        assert_eq!(try_order(&[true, true, true], 4), vec![1, 2, 0]);
+
+        // Down endpoints are kept as a last resort
+        assert_eq!(try_order(&[false, true, true], 0), vec![1, 2, 0]);
+        assert_eq!(try_order(&[false, false], 1), vec![1, 0]);
+    }
+}
@@ -0,0 +1,477 @@
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+
+static MAGIC: &[u8] = b"psbt\xff";
+
+// Key types of BIP-174 and BIP-371 used to sign Taproot key path inputs
+static GLOBAL_UNSIGNED_TX: u8 = 0x00;
+static IN_WITNESS_UTXO: u8 = 0x01;
+static IN_SIGHASH_TYPE: u8 = 0x03;
+static IN_TAP_KEY_SIG: u8 = 0x13;
+
+static SIGHASH_DEFAULT: u8 = 0x00;
+static SIGHASH_ALL: u8 = 0x01;
+static SIGHASH_SINGLE: u8 = 0x03;
+static SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Key-value pairs of a PSBT map in their original order, unknown keys are kept as is
+type Map = Vec<(Vec<u8>, Vec<u8>)>;
+
+struct TxIn {
+    /// Previous transaction id and output index, as serialized
+    prevout: [u8; 36],
+    sequence: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+impl TxOut {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.value.to_le_bytes());
+        write_compact_size(buf, self.script_pubkey.len() as u64);
+        buf.extend_from_slice(&self.script_pubkey);
+    }
+}
+
+struct UnsignedTx {
+    version: u32,
+    inputs: Vec<TxIn>,
+    outputs: Vec<TxOut>,
+    lock_time: u32,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(anyhow!("Unexpected end of data"));
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn compact_size(&mut self) -> Result<u64> {
+        Ok(match self.take(1)?[0] {
+            0xfd => u64::from(u16::from_le_bytes(self.take(2)?.try_into()?)),
+            0xfe => u64::from(self.u32()?),
+            0xff => self.u64()?,
+            len => u64::from(len),
+        })
+    }
+
+    fn var_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.compact_size()?;
+
+        self.take(usize::try_from(len)?)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if !self.bytes.is_empty() {
+            return Err(anyhow!("Unexpected trailing data"));
+        }
+
+        Ok(())
+    }
+}
+
+fn write_compact_size(buf: &mut Vec<u8>, len: u64) {
+    match len {
+        0..0xfd => buf.push(len as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xff);
+            buf.extend_from_slice(&len.to_le_bytes());
+        }
+    }
+}
+
+fn read_map(reader: &mut Reader) -> Result<Map> {
+    let mut map: Map = Vec::new();
+
+    loop {
+        let key = reader.var_bytes()?;
+
+        // A zero-length key terminates the map
+        if key.is_empty() {
+            return Ok(map);
+        }
+
+        if map.iter().any(|(existing, _)| existing == key) {
+            return Err(anyhow!("Duplicate key in PSBT map"));
+        }
+
+        let value = reader.var_bytes()?;
+
+        map.push((key.to_vec(), value.to_vec()));
+    }
+}
+
+fn write_map(buf: &mut Vec<u8>, map: &Map) {
+    for (key, value) in map {
+        write_compact_size(buf, key.len() as u64);
+        buf.extend_from_slice(key);
+        write_compact_size(buf, value.len() as u64);
+        buf.extend_from_slice(value);
+    }
+
+    buf.push(0x00);
+}
+
+fn find<'a>(map: &'a Map, key: &[u8]) -> Option<&'a [u8]> {
+    map.iter()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, value)| value.as_slice())
+}
+
+fn parse_unsigned_tx(bytes: &[u8]) -> Result<UnsignedTx> {
+    let mut reader = Reader { bytes };
+
+    let version = reader.u32()?;
+
+    // Zero inputs would be the SegWit marker, the unsigned transaction carries no witness
+    let input_count = reader.compact_size()?;
+
+    if input_count == 0 {
+        return Err(anyhow!("Unsigned transaction has no input"));
+    }
+
+    let mut inputs = Vec::new();
+
+    for _ in 0..input_count {
+        let prevout = reader.take(36)?.try_into()?;
+
+        if !reader.var_bytes()?.is_empty() {
+            return Err(anyhow!("Unsigned transaction has a non-empty scriptSig"));
+        }
+
+        inputs.push(TxIn {
+            prevout,
+            sequence: reader.u32()?,
+        });
+    }
+
+    let mut outputs = Vec::new();
+
+    for _ in 0..reader.compact_size()? {
+        outputs.push(TxOut {
+            value: reader.u64()?,
+            script_pubkey: reader.var_bytes()?.to_vec(),
+        });
+    }
+
+    let lock_time = reader.u32()?;
+
+    reader.finish()?;
+
+    Ok(UnsignedTx {
+        version,
+        inputs,
+        outputs,
+        lock_time,
+    })
+}
+
+fn tagged_hash(tag: &[u8], message: &[u8]) -> [u8; 32] {
+    let tag = Sha256::digest(tag);
+
+    Sha256::new()
+        .chain_update(tag)
+        .chain_update(tag)
+        .chain_update(message)
+        .finalize()
+        .into()
+}
+
+/// Version 0 Partially Signed Bitcoin Transaction (BIP-174)
+pub struct Psbt {
+    global: Map,
+    tx: UnsignedTx,
+    inputs: Vec<Map>,
+    outputs: Vec<Map>,
+}
+
+impl Psbt {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("Missing PSBT magic bytes"));
+        }
+
+        let global = read_map(&mut reader)?;
+
+        // Version 2 PSBTs (BIP-370) have no global unsigned transaction
+        let tx = find(&global, &[GLOBAL_UNSIGNED_TX])
+            .ok_or_else(|| anyhow!("Missing unsigned transaction"))
+            .and_then(parse_unsigned_tx)?;
+
+        let inputs = (0..tx.inputs.len())
+            .map(|_| read_map(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+        let outputs = (0..tx.outputs.len())
+            .map(|_| read_map(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+
+        reader.finish()?;
+
+        Ok(Self {
+            global,
+            tx,
+            inputs,
+            outputs,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+
+        write_map(&mut buf, &self.global);
+
+        for map in self.inputs.iter().chain(&self.outputs) {
+            write_map(&mut buf, map);
+        }
+
+        buf
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.tx.inputs.len()
+    }
+
+    pub fn outputs(&self) -> &[TxOut] {
+        &self.tx.outputs
+    }
+
+    /// Output spent by the input, as provided by the coordinator
+    pub fn witness_utxo(&self, index: usize) -> Result<Option<TxOut>> {
+        let Some(value) = find(&self.inputs[index], &[IN_WITNESS_UTXO]) else {
+            return Ok(None);
+        };
+
+        let mut reader = Reader { bytes: value };
+
+        let utxo = TxOut {
+            value: reader.u64()?,
+            script_pubkey: reader.var_bytes()?.to_vec(),
+        };
+
+        reader.finish()?;
+
+        Ok(Some(utxo))
+    }
+
+    /// Sighash type requested for the input, `SIGHASH_DEFAULT` when unset
+    pub fn sighash_type(&self, index: usize) -> Result<u8> {
+        let Some(value) = find(&self.inputs[index], &[IN_SIGHASH_TYPE]) else {
+            return Ok(SIGHASH_DEFAULT);
+        };
+
+        let hash_type = u32::from_le_bytes(value.try_into()?);
+
+        u8::try_from(hash_type).map_err(|_| anyhow!("Invalid sighash type {hash_type}"))
+    }
+
+    /// BIP-341 signature hash of a key path spend of the input, without annex
+    pub fn taproot_sighash(&self, index: usize, hash_type: u8) -> Result<[u8; 32]> {
+        if !matches!(hash_type, 0x00..=0x03 | 0x81..=0x83) {
+            return Err(anyhow!("Invalid sighash type {hash_type}"));
+        }
+
+        let output_type = if hash_type == SIGHASH_DEFAULT {
+            SIGHASH_ALL
+        } else {
+            hash_type & 0x03
+        };
+        let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+
+        let input = &self.tx.inputs[index];
+
+        // Leading epoch byte
+        let mut message = vec![0x00, hash_type];
+
+        message.extend_from_slice(&self.tx.version.to_le_bytes());
+        message.extend_from_slice(&self.tx.lock_time.to_le_bytes());
+
+        if !anyone_can_pay {
+            let utxos = (0..self.input_count())
+                .map(|i| {
+                    self.witness_utxo(i)?
+                        .ok_or_else(|| anyhow!("Input {i} has no witness UTXO"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut prevouts = Sha256::new();
+            let mut amounts = Sha256::new();
+            let mut script_pubkeys = Sha256::new();
+            let mut sequences = Sha256::new();
+
+            for (input, utxo) in self.tx.inputs.iter().zip(&utxos) {
+                prevouts.update(input.prevout);
+                amounts.update(utxo.value.to_le_bytes());
+
+                let mut script = Vec::new();
+                write_compact_size(&mut script, utxo.script_pubkey.len() as u64);
+                script.extend_from_slice(&utxo.script_pubkey);
+                script_pubkeys.update(script);
+
+                sequences.update(input.sequence.to_le_bytes());
+            }
+
+            message.extend_from_slice(&prevouts.finalize());
+            message.extend_from_slice(&amounts.finalize());
+            message.extend_from_slice(&script_pubkeys.finalize());
+            message.extend_from_slice(&sequences.finalize());
+        }
+
+        // SIGHASH_NONE commits to no output
+        if output_type == SIGHASH_ALL {
+            let mut outputs = Vec::new();
+
+            for output in &self.tx.outputs {
+                output.encode(&mut outputs);
+            }
+
+            message.extend_from_slice(&Sha256::digest(outputs));
+        }
+
+        // Key path spend without annex
+        message.push(0x00);
+
+        if anyone_can_pay {
+            let utxo = self
+                .witness_utxo(index)?
+                .ok_or_else(|| anyhow!("Input {index} has no witness UTXO"))?;
+
+            message.extend_from_slice(&input.prevout);
+            utxo.encode(&mut message);
+            message.extend_from_slice(&input.sequence.to_le_bytes());
+        } else {
+            message.extend_from_slice(&(index as u32).to_le_bytes());
+        }
+
+        if output_type == SIGHASH_SINGLE {
+            let output = self
+                .tx
+                .outputs
+                .get(index)
+                .ok_or_else(|| anyhow!("No output matches input {index} for SIGHASH_SINGLE"))?;
+
+            let mut encoded = Vec::new();
+            output.encode(&mut encoded);
+
+            message.extend_from_slice(&Sha256::digest(encoded));
+        }
+
+        Ok(tagged_hash(b"TapSighash", &message))
+    }
+
+    /// Records the BIP-340 signature of the input's key path spend, replacing any previous one
+    pub fn set_tap_key_sig(&mut self, index: usize, signature: &[u8; 64], hash_type: u8) {
+        let mut value = signature.to_vec();
+
+        // The default sighash type is implied by a 64-byte signature
+        if hash_type != SIGHASH_DEFAULT {
+            value.push(hash_type);
+        }
+
+        let map = &mut self.inputs[index];
+
+        map.retain(|(key, _)| key.as_slice() != [IN_TAP_KEY_SIG]);
+        map.push((vec![IN_TAP_KEY_SIG], value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn psbt(utxo_script: &[u8]) -> Vec<u8> {
+        let mut tx = 2u32.to_le_bytes().to_vec();
+        tx.push(1);
+        tx.extend_from_slice(&[0xaa; 36]);
+        tx.push(0);
+        tx.extend_from_slice(&0xffff_fffdu32.to_le_bytes());
+        tx.push(1);
+        TxOut {
+            value: 900,
+            script_pubkey: vec![0x6a],
+        }
+        .encode(&mut tx);
+        tx.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut utxo = Vec::new();
+        TxOut {
+            value: 1000,
+            script_pubkey: utxo_script.to_vec(),
+        }
+        .encode(&mut utxo);
+
+        let mut buf = MAGIC.to_vec();
+        write_map(&mut buf, &vec![(vec![GLOBAL_UNSIGNED_TX], tx)]);
+        write_map(&mut buf, &vec![(vec![IN_WITNESS_UTXO], utxo)]);
+        write_map(&mut buf, &Vec::new());
+        buf
+    }
+
+    #[test]
+    fn test_psbt_round_trip_and_signature() {
+        let script = [&[0x51, 0x20][..], &[7; 32]].concat();
+        let bytes = psbt(&script);
+
+        let mut psbt = Psbt::parse(&bytes).unwrap();
+
+        assert_eq!(psbt.serialize(), bytes);
+        assert_eq!(psbt.input_count(), 1);
+        assert_eq!(psbt.witness_utxo(0).unwrap().unwrap().script_pubkey, script);
+        assert_eq!(psbt.sighash_type(0).unwrap(), SIGHASH_DEFAULT);
+
+        // Every sighash type commits to different data
+        let default = psbt.taproot_sighash(0, SIGHASH_DEFAULT).unwrap();
+        assert_ne!(default, psbt.taproot_sighash(0, SIGHASH_ALL).unwrap());
+        assert_ne!(default, psbt.taproot_sighash(0, 0x82).unwrap());
+        assert!(psbt.taproot_sighash(0, 0x04).is_err());
+
+        psbt.set_tap_key_sig(0, &[9; 64], SIGHASH_DEFAULT);
+        psbt.set_tap_key_sig(0, &[9; 64], SIGHASH_ALL);
+
+        let signed = Psbt::parse(&psbt.serialize()).unwrap();
+        let signature = find(&signed.inputs[0], &[IN_TAP_KEY_SIG]).unwrap();
+
+        assert_eq!(signature.len(), 65);
+        assert_eq!(signature[64], SIGHASH_ALL);
+    }
+
+    #[test]
+    fn test_psbt_rejects_invalid_data() {
+        let bytes = psbt(&[0x51, 0x20]);
+
+        assert!(Psbt::parse(&bytes[1..]).is_err());
+        assert!(Psbt::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Psbt::parse(&[bytes.as_slice(), &[0x00]].concat()).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use alloy::eips::BlockNumberOrTag;
+use alloy::primitives::{Address, U256, address};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy::transports::{TransportErrorKind, TransportResult};
+use serde::Serialize;
+
+/// OP Stack predeploy quoting the L1 data fee of a transaction
+const GAS_PRICE_ORACLE: Address = address!("0x420000000000000000000000000000000000000F");
+
+sol! {
+    function getL1Fee(bytes memory _data) external view returns (uint256);
+}
+
+/// Expected cost of a transaction at the gas price it is signed with, amounts are decimal
+/// strings in wei
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeeQuote {
+    pub gas_limit: u64,
+    /// Legacy gas price the transaction is signed with
+    pub gas_price: String,
+    pub base_fee_per_gas: String,
+    /// Part of the gas price left to the block producer over the current base fee
+    pub max_priority_fee_per_gas: String,
+    /// Fee for posting the transaction data to L1, only on OP Stack rollups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_fee: Option<String>,
+    /// Value plus the highest fee the transaction can be charged
+    pub total_cost: String,
+    pub balance: String,
+    pub sufficient_balance: bool,
+}
+
+/// Quotes the legacy transaction from `from` with `gas_limit` at `gas_price`
+///
+/// `l1_data` is the unsigned transaction priced by the gas price oracle of OP Stack rollups,
+/// Arbitrum already includes its L1 cost in the gas estimate.
+pub async fn quote(
+    provider: &(dyn Provider + Send + Sync),
+    from: Address,
+    gas_limit: u64,
+    gas_price: u64,
+    value: U256,
+    l1_data: Option<Vec<u8>>,
+) -> TransportResult<FeeQuote> {
+    let balance = provider.get_balance(from).await?;
+
+    let base_fee = provider
+        .get_block_by_number(BlockNumberOrTag::Latest)
+        .await?
+        .and_then(|block| block.header.base_fee_per_gas)
+        .unwrap_or_default();
+
+    let l1_fee = match l1_data {
+        Some(data) => Some(l1_fee(provider, data).await?),
+        None => None,
+    };
+
+    Ok(fee_quote(
+        gas_limit, gas_price, base_fee, l1_fee, value, balance,
+    ))
+}
+
+/// L1 data fee of the unsigned transaction `data` on OP Stack rollups
+pub async fn l1_fee(
+    provider: &(dyn Provider + Send + Sync),
+    data: Vec<u8>,
+) -> TransportResult<U256> {
+    let input = getL1FeeCall { _data: data.into() }.abi_encode();
+
+    let output = provider
+        .call(
+            TransactionRequest::default()
+                .to(GAS_PRICE_ORACLE)
+                .input(input.into()),
+        )
+        .await?;
+
+    getL1FeeCall::abi_decode_returns(&output)
+        .map_err(|err| TransportErrorKind::custom_str(&format!("Invalid L1 fee: {err}")))
+}
+
+fn fee_quote(
+    gas_limit: u64,
+    gas_price: u64,
+    base_fee: u64,
+    l1_fee: Option<U256>,
+    value: U256,
+    balance: U256,
+) -> FeeQuote {
+    let total_cost = U256::from(gas_limit).saturating_mul(U256::from(gas_price))
+        + l1_fee.unwrap_or_default()
+        + value;
+
+    FeeQuote {
+        gas_limit,
+        gas_price: gas_price.to_string(),
+        base_fee_per_gas: base_fee.to_string(),
+        max_priority_fee_per_gas: gas_price.saturating_sub(base_fee).to_string(),
+        l1_fee: l1_fee.map(|fee| fee.to_string()),
+        total_cost: total_cost.to_string(),
+        balance: balance.to_string(),
+        sufficient_balance: balance >= total_cost,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_quote_total_cost() {
+        let value = U256::from(1_000_000_000_000_000u64);
+        let balance = U256::from(10u64.pow(18));
+
+        let quote = fee_quote(21_000, 3_000_000_000, 1_000_000_000, None, value, balance);
+
+        assert_eq!(quote.total_cost, "1063000000000000");
+        assert_eq!(quote.max_priority_fee_per_gas, "2000000000");
+        assert_eq!(quote.l1_fee, None);
+        assert!(quote.sufficient_balance);
+
+        let quote = fee_quote(21_000, 3_000_000_000, 1_000_000_000, None, value, value);
+
+        assert!(!quote.sufficient_balance);
+
+        let l1_fee = U256::from(5_000_000_000_000u64);
+        let quote = fee_quote(
+            21_000,
+            3_000_000_000,
+            1_000_000_000,
+            Some(l1_fee),
+            value,
+            balance,
+        );
+
+        assert_eq!(quote.l1_fee.as_deref(), Some("5000000000000"));
+        assert_eq!(quote.total_cost, "1068000000000000");
+    }
+
+    #[test]
+    fn test_fee_quote_below_base_fee_leaves_no_priority_fee() {
+        let quote = fee_quote(21_000, 1, 2, None, U256::ZERO, U256::ZERO);
+
+        assert_eq!(quote.max_priority_fee_per_gas, "0");
+    }
+}
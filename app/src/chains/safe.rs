@@ -0,0 +1,254 @@
+use alloy::primitives::{Address, B256, Bytes, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::{Eip712Domain, SolCall, SolStruct};
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Value, json};
+
+sol! {
+    struct SafeTx {
+        address to;
+        uint256 value;
+        bytes data;
+        uint8 operation;
+        uint256 safeTxGas;
+        uint256 baseGas;
+        uint256 gasPrice;
+        address gasToken;
+        address refundReceiver;
+        uint256 nonce;
+    }
+
+    function nonce() external view returns (uint256);
+}
+
+/// Amounts of the transaction service come back as decimal strings or plain numbers
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(amount) => amount.parse().map_err(serde::de::Error::custom),
+        Value::Number(amount) => amount.to_string().parse().map_err(serde::de::Error::custom),
+        value => Err(serde::de::Error::custom(format!("Invalid amount {value}"))),
+    }
+}
+
+fn optional_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    Ok(Option::<Bytes>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Transaction of a Safe multisig, as hashed by the Safe and stored by its transaction service
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTransaction {
+    pub safe: Address,
+    pub to: Address,
+    #[serde(deserialize_with = "amount")]
+    pub value: U256,
+    #[serde(deserialize_with = "optional_bytes")]
+    pub data: Bytes,
+    /// 0 for a call, 1 for a delegatecall
+    pub operation: u8,
+    #[serde(deserialize_with = "amount")]
+    pub safe_tx_gas: U256,
+    #[serde(deserialize_with = "amount")]
+    pub base_gas: U256,
+    #[serde(deserialize_with = "amount")]
+    pub gas_price: U256,
+    pub gas_token: Address,
+    pub refund_receiver: Address,
+    #[serde(deserialize_with = "amount")]
+    pub nonce: U256,
+}
+
+impl SafeTransaction {
+    /// EIP-712 hash the owners sign, the domain of Safes since v1.3.0 has no name or version
+    pub fn hash(&self, chain_id: u64) -> B256 {
+        let domain = Eip712Domain::new(
+            None,
+            None,
+            Some(U256::from(chain_id)),
+            Some(self.safe),
+            None,
+        );
+
+        SafeTx {
+            to: self.to,
+            value: self.value,
+            data: self.data.clone(),
+            operation: self.operation,
+            safeTxGas: self.safe_tx_gas,
+            baseGas: self.base_gas,
+            gasPrice: self.gas_price,
+            gasToken: self.gas_token,
+            refundReceiver: self.refund_receiver,
+            nonce: self.nonce,
+        }
+        .eip712_signing_hash(&domain)
+    }
+}
+
+/// Current nonce of the Safe, the next transaction must use it
+pub async fn safe_nonce(provider: &(dyn Provider + Send + Sync), safe: Address) -> Result<U256> {
+    let output = provider
+        .call(
+            TransactionRequest::default()
+                .to(safe)
+                .input(nonceCall {}.abi_encode().into()),
+        )
+        .await?;
+
+    Ok(nonceCall::abi_decode_returns(&output)?)
+}
+
+/// Client of the Safe transaction service, which collects owner signatures off-chain
+pub struct SafeClient {
+    client: Client,
+    url: String,
+}
+
+impl SafeClient {
+    pub fn new(client: Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}{path}", self.url))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+
+            return Err(anyhow!(
+                "Safe transaction service returned {status}: {}",
+                response.text().await.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Proposes the transaction with the first owner signature
+    pub async fn propose(
+        &self,
+        transaction: &SafeTransaction,
+        safe_tx_hash: B256,
+        sender: Address,
+        signature: &[u8],
+    ) -> Result<()> {
+        let body = json!({
+            "to": transaction.to,
+            "value": transaction.value.to_string(),
+            "data": (!transaction.data.is_empty()).then_some(&transaction.data),
+            "operation": transaction.operation,
+            "safeTxGas": transaction.safe_tx_gas.to_string(),
+            "baseGas": transaction.base_gas.to_string(),
+            "gasPrice": transaction.gas_price.to_string(),
+            "gasToken": transaction.gas_token,
+            "refundReceiver": transaction.refund_receiver,
+            "nonce": transaction.nonce.to_string(),
+            "contractTransactionHash": safe_tx_hash,
+            "sender": sender,
+            "signature": Bytes::copy_from_slice(signature),
+        });
+
+        self.post(
+            &format!("/api/v1/safes/{}/multisig-transactions/", transaction.safe),
+            body,
+        )
+        .await
+    }
+
+    /// Transaction proposed under the hash, `None` when the service doesn't know it
+    pub async fn transaction(&self, safe_tx_hash: B256) -> Result<Option<SafeTransaction>> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/api/v1/multisig-transactions/{safe_tx_hash}/",
+                self.url
+            ))
+            .send()
+            .await?;
+
+        if response.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Safe transaction service returned {}",
+                response.status()
+            ));
+        }
+
+        Ok(Some(serde_json::from_slice(&response.bytes().await?)?))
+    }
+
+    /// Adds the owner signature to the proposed transaction
+    pub async fn confirm(&self, safe_tx_hash: B256, signature: &[u8]) -> Result<()> {
+        self.post(
+            &format!("/api/v1/multisig-transactions/{safe_tx_hash}/confirmations/"),
+            json!({ "signature": Bytes::copy_from_slice(signature) }),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{b256, keccak256};
+    use alloy::sol_types::SolValue;
+
+    #[test]
+    fn test_safe_transaction_hash() {
+        // SAFE_TX_TYPEHASH and DOMAIN_SEPARATOR_TYPEHASH of the Safe contracts
+        assert_eq!(
+            keccak256(SafeTx::eip712_encode_type().as_bytes()),
+            b256!("0xbb8310d486368db6bd6f849402fdd73ad53d316b5a4b2644ad6efe0f941286d8")
+        );
+        let safe = Address::repeat_byte(1);
+        let domain = Eip712Domain::new(None, None, Some(U256::from(1)), Some(safe), None);
+
+        assert_eq!(
+            domain.hash_struct(),
+            keccak256(
+                (
+                    b256!("0x47e79534a245952e8b16893a336b85a3d9ea9fa8c573f3d803afb92a79469218"),
+                    U256::from(1),
+                    safe,
+                )
+                    .abi_encode()
+            )
+        );
+
+        let transaction: SafeTransaction = serde_json::from_value(json!({
+            "safe": "0x0000000000000000000000000000000000000001",
+            "to": "0x0000000000000000000000000000000000000002",
+            "value": "1000",
+            "data": null,
+            "operation": 0,
+            "safeTxGas": 0,
+            "baseGas": "0",
+            "gasPrice": "0",
+            "gasToken": "0x0000000000000000000000000000000000000000",
+            "refundReceiver": "0x0000000000000000000000000000000000000000",
+            "nonce": 7,
+        }))
+        .unwrap();
+
+        assert_eq!(transaction.value, U256::from(1000));
+        assert_eq!(transaction.nonce, U256::from(7));
+        assert!(transaction.data.is_empty());
+        assert_ne!(transaction.hash(1), transaction.hash(10));
+    }
+}
@@ -0,0 +1,87 @@
+use alloy::providers::Provider;
+use alloy::rpc::json_rpc::ErrorPayload;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::decode_revert_reason;
+use alloy::transports::RpcError;
+use anyhow::Result;
+use serde::Serialize;
+
+/// Outcome of executing a transaction on top of the latest block without broadcasting it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Simulation {
+    pub success: bool,
+    /// Gas estimated by the node, only set when the transaction succeeds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+}
+
+/// Dry-runs the transaction with `eth_call` and estimates its gas when it succeeds
+///
+/// Reverts, out of gas and insufficient funds are reported in the simulation, only
+/// failures to reach the node are errors.
+pub async fn simulate(
+    provider: &(dyn Provider + Send + Sync),
+    tx: TransactionRequest,
+) -> Result<Simulation> {
+    match provider.call(tx.clone()).await {
+        Ok(_) => {
+            let gas_used = provider.estimate_gas(tx).await?;
+
+            Ok(Simulation {
+                success: true,
+                gas_used: Some(gas_used),
+                revert_reason: None,
+            })
+        }
+        Err(RpcError::ErrorResp(payload)) => Ok(Simulation {
+            success: false,
+            gas_used: None,
+            revert_reason: Some(revert_reason(&payload)),
+        }),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Decoded `Error(string)` or `Panic(uint256)` of the revert, the node message otherwise
+fn revert_reason(payload: &ErrorPayload) -> String {
+    payload
+        .as_revert_data()
+        .and_then(|data| decode_revert_reason(&data))
+        .unwrap_or_else(|| payload.message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(message: &str, data: Option<&str>) -> ErrorPayload {
+        serde_json::from_value(serde_json::json!({
+            "code": 3,
+            "message": message,
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_revert_reason_decodes_error_string() {
+        // Error("Insufficient balance")
+        let data = "0x08c379a0\
+            0000000000000000000000000000000000000000000000000000000000000020\
+            0000000000000000000000000000000000000000000000000000000000000014\
+            496e73756666696369656e742062616c616e6365000000000000000000000000";
+
+        let reason = revert_reason(&payload("execution reverted", Some(data)));
+
+        assert!(reason.contains("Insufficient balance"), "{reason}");
+    }
+
+    #[test]
+    fn test_revert_reason_falls_back_to_message() {
+        let reason = revert_reason(&payload("insufficient funds for gas * price + value", None));
+
+        assert_eq!(reason, "insufficient funds for gas * price + value");
+    }
+}
@@ -0,0 +1,316 @@
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+static ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+// The System Program id is the all-zero key
+static SYSTEM_PROGRAM: [u8; 32] = [0; 32];
+
+// Index of the System Program `Transfer` instruction
+static TRANSFER_INSTRUCTION: u32 = 2;
+
+/// Base fee of a transaction per signature, transfers have the sender as their only signer
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+pub fn encode_base58(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+
+    // Little-endian base 58 digits of the big-endian number after the leading zeros
+    let mut digits: Vec<u8> = Vec::new();
+
+    for byte in &bytes[zeros..] {
+        let mut carry = u32::from(*byte);
+
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    // Every leading zero byte is written as a leading '1'
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|digit| ALPHABET[*digit as usize] as char),
+        )
+        .collect()
+}
+
+pub fn decode_base58(encoded: &str) -> Option<Vec<u8>> {
+    let zeros = encoded.bytes().take_while(|c| *c == b'1').count();
+
+    // Little-endian bytes of the number after the leading '1's
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for c in encoded.bytes().skip(zeros) {
+        let mut carry = ALPHABET.iter().position(|a| *a == c)? as u32;
+
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    Some(
+        std::iter::repeat_n(0, zeros)
+            .chain(bytes.into_iter().rev())
+            .collect(),
+    )
+}
+
+/// Ed25519 public key of a Solana account from its base58 address
+pub fn parse_pubkey(address: &str) -> Option<[u8; 32]> {
+    decode_base58(address)?.try_into().ok()
+}
+
+/// Compact-u16 length prefix of the Solana wire format
+fn push_length(buf: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+
+        if len == 0 {
+            buf.push(byte);
+            return;
+        }
+
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Legacy message of a System Program transfer of `lamports` from `from`, which pays the
+/// fee and is the only signer, to `to`
+pub fn transfer_message(
+    from: &[u8; 32],
+    to: &[u8; 32],
+    lamports: u64,
+    recent_blockhash: &[u8; 32],
+) -> Vec<u8> {
+    // A self-transfer references the same account twice, listed once
+    let accounts: Vec<&[u8; 32]> = if from == to {
+        vec![from, &SYSTEM_PROGRAM]
+    } else {
+        vec![from, to, &SYSTEM_PROGRAM]
+    };
+
+    let program_index = (accounts.len() - 1) as u8;
+    let to_index = if from == to { 0 } else { 1 };
+
+    // One writable signer, no readonly signer and the readonly System Program
+    let mut message = vec![1, 0, 1];
+
+    push_length(&mut message, accounts.len());
+
+    for account in accounts {
+        message.extend_from_slice(account);
+    }
+
+    message.extend_from_slice(recent_blockhash);
+
+    let mut data = TRANSFER_INSTRUCTION.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+
+    push_length(&mut message, 1);
+    message.push(program_index);
+    push_length(&mut message, 2);
+    message.extend_from_slice(&[0, to_index]);
+    push_length(&mut message, data.len());
+    message.extend_from_slice(&data);
+
+    message
+}
+
+/// Wire transaction of a message signed by its single signer
+pub fn signed_transaction(message: &[u8], signature: &[u8; 64]) -> Vec<u8> {
+    let mut transaction = Vec::with_capacity(1 + signature.len() + message.len());
+
+    push_length(&mut transaction, 1);
+    transaction.extend_from_slice(signature);
+    transaction.extend_from_slice(message);
+
+    transaction
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Contextual<T> {
+    value: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LatestBlockhash {
+    blockhash: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureStatus {
+    pub slot: u64,
+    /// Blocks confirmed on top of the transaction's, `None` once the cluster rooted it
+    pub confirmations: Option<u64>,
+    /// Error of the executed transaction, the fee is still charged
+    pub err: Option<Value>,
+}
+
+/// JSON-RPC client of a Solana cluster
+pub struct SolanaClient {
+    client: Client,
+    url: String,
+}
+
+impl SolanaClient {
+    pub fn new(client: Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Solana RPC returned {}", response.status()));
+        }
+
+        let response: RpcResponse<T> = serde_json::from_slice(&response.bytes().await?)?;
+
+        if let Some(error) = response.error {
+            return Err(anyhow!(
+                "Solana RPC error {}: {}",
+                error.code,
+                error.message
+            ));
+        }
+
+        response
+            .result
+            .ok_or_else(|| anyhow!("Solana RPC returned no result for {method}"))
+    }
+
+    /// Blockhash the transaction is built against, it expires after about 150 slots
+    pub async fn latest_blockhash(&self) -> Result<[u8; 32]> {
+        let response: Contextual<LatestBlockhash> = self
+            .call("getLatestBlockhash", json!([{ "commitment": "finalized" }]))
+            .await?;
+
+        parse_pubkey(&response.value.blockhash)
+            .ok_or_else(|| anyhow!("Invalid blockhash {}", response.value.blockhash))
+    }
+
+    /// Balance of the account in lamports
+    pub async fn balance(&self, address: &str) -> Result<u64> {
+        let response: Contextual<u64> = self
+            .call(
+                "getBalance",
+                json!([address, { "commitment": "confirmed" }]),
+            )
+            .await?;
+
+        Ok(response.value)
+    }
+
+    /// Submits the signed transaction, returning its base58 signature
+    pub async fn send_transaction(&self, transaction: &[u8]) -> Result<String> {
+        self.call(
+            "sendTransaction",
+            json!([BASE64.encode(transaction), { "encoding": "base64" }]),
+        )
+        .await
+    }
+
+    /// Status of the transaction, `None` while the cluster doesn't know it
+    pub async fn signature_status(&self, signature: &str) -> Result<Option<SignatureStatus>> {
+        let response: Contextual<Vec<Option<SignatureStatus>>> = self
+            .call(
+                "getSignatureStatuses",
+                json!([[signature], { "searchTransactionHistory": true }]),
+            )
+            .await?;
+
+        Ok(response.value.into_iter().next().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base58() {
+        assert_eq!(encode_base58(b"Hello World"), "JxF12TrwUP45BMd");
+        assert_eq!(encode_base58(&SYSTEM_PROGRAM), "1".repeat(32));
+        assert_eq!(
+            decode_base58("JxF12TrwUP45BMd").as_deref(),
+            Some(b"Hello World".as_slice())
+        );
+        assert_eq!(parse_pubkey(&"1".repeat(32)), Some(SYSTEM_PROGRAM));
+        assert_eq!(decode_base58("0OIl"), None);
+    }
+
+    #[test]
+    fn test_transfer_message() {
+        let (from, to, blockhash) = ([1; 32], [2; 32], [3; 32]);
+
+        let message = transfer_message(&from, &to, 5, &blockhash);
+
+        assert_eq!(&message[..4], &[1, 0, 1, 3]);
+        assert_eq!(&message[4..36], &from);
+        assert_eq!(&message[36..68], &to);
+        assert_eq!(&message[68..100], &SYSTEM_PROGRAM);
+        assert_eq!(&message[100..132], &blockhash);
+        assert_eq!(
+            &message[132..],
+            &[1, 2, 2, 0, 1, 12, 2, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let transaction = signed_transaction(&message, &[4; 64]);
+
+        assert_eq!(transaction[0], 1);
+        assert_eq!(&transaction[65..], &message);
+    }
+}
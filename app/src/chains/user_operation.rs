@@ -0,0 +1,266 @@
+use alloy::primitives::{Address, B256, Bytes, U256, address, keccak256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolValue};
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// EntryPoint v0.7, deployed at the same address on every supported chain
+pub const ENTRY_POINT: Address = address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+sol! {
+    function getNonce(address sender, uint192 key) external view returns (uint256 nonce);
+    function execute(address dest, uint256 value, bytes func) external;
+}
+
+/// ERC-4337 v0.7 user operation in the unpacked form bundlers accept
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory_data: Option<Bytes>,
+    pub call_data: Bytes,
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_verification_gas_limit: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_post_op_gas_limit: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_data: Option<Bytes>,
+    pub signature: Bytes,
+}
+
+/// Two 128-bit values packed into one word, `high` first
+fn pack_u128(high: U256, low: U256) -> Result<[u8; 32]> {
+    let mut word = [0u8; 32];
+
+    for (half, value) in word.chunks_mut(16).zip([high, low]) {
+        let bytes = value.to_be_bytes::<32>();
+
+        if bytes[..16].iter().any(|byte| *byte != 0) {
+            return Err(anyhow!("Gas value {value} exceeds 128 bits"));
+        }
+
+        half.copy_from_slice(&bytes[16..]);
+    }
+
+    Ok(word)
+}
+
+impl UserOperation {
+    fn init_code(&self) -> Vec<u8> {
+        match self.factory {
+            Some(factory) => [
+                factory.as_slice(),
+                self.factory_data
+                    .as_ref()
+                    .map_or(&[][..], |data| data.as_ref()),
+            ]
+            .concat(),
+            None => Vec::new(),
+        }
+    }
+
+    fn paymaster_and_data(&self) -> Result<Vec<u8>> {
+        let Some(paymaster) = self.paymaster else {
+            return Ok(Vec::new());
+        };
+
+        let gas_limits = pack_u128(
+            self.paymaster_verification_gas_limit.unwrap_or_default(),
+            self.paymaster_post_op_gas_limit.unwrap_or_default(),
+        )?;
+
+        Ok([
+            paymaster.as_slice(),
+            &gas_limits,
+            self.paymaster_data
+                .as_ref()
+                .map_or(&[][..], |data| data.as_ref()),
+        ]
+        .concat())
+    }
+
+    /// Hash the EntryPoint hands to the account's `validateUserOp`, binding the operation to
+    /// the EntryPoint and the chain
+    pub fn hash(&self, entry_point: Address, chain_id: u64) -> Result<B256> {
+        let packed = (
+            self.sender,
+            self.nonce,
+            keccak256(self.init_code()),
+            keccak256(&self.call_data),
+            B256::from(pack_u128(self.verification_gas_limit, self.call_gas_limit)?),
+            self.pre_verification_gas,
+            B256::from(pack_u128(
+                self.max_priority_fee_per_gas,
+                self.max_fee_per_gas,
+            )?),
+            keccak256(self.paymaster_and_data()?),
+        )
+            .abi_encode();
+
+        Ok(keccak256(
+            (keccak256(packed), entry_point, U256::from(chain_id)).abi_encode(),
+        ))
+    }
+
+    /// Target of a `SimpleAccount`-style `execute(dest, value, func)` call, `None` for any
+    /// other call data
+    pub fn execute_target(&self) -> Option<(Address, U256)> {
+        executeCall::abi_decode(&self.call_data)
+            .ok()
+            .map(|call| (call.dest, call.value))
+    }
+}
+
+/// Next nonce of the smart account on the default key
+pub async fn account_nonce(
+    provider: &(dyn Provider + Send + Sync),
+    entry_point: Address,
+    sender: Address,
+) -> Result<U256> {
+    let input = getNonceCall {
+        sender,
+        key: Default::default(),
+    }
+    .abi_encode();
+
+    let output = provider
+        .call(
+            TransactionRequest::default()
+                .to(entry_point)
+                .input(input.into()),
+        )
+        .await?;
+
+    Ok(getNonceCall::abi_decode_returns(&output)?)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// ERC-4337 bundler JSON-RPC client
+pub struct BundlerClient {
+    client: Client,
+    url: String,
+}
+
+impl BundlerClient {
+    pub fn new(client: Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+        }
+    }
+
+    /// Submits the signed operation to the bundler's mempool, returning its hash
+    pub async fn send_user_operation(
+        &self,
+        operation: &UserOperation,
+        entry_point: Address,
+    ) -> Result<B256> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendUserOperation",
+            "params": [operation, entry_point],
+        });
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Bundler returned {}", response.status()));
+        }
+
+        let response: RpcResponse<B256> = serde_json::from_slice(&response.bytes().await?)?;
+
+        if let Some(error) = response.error {
+            return Err(anyhow!("Bundler error {}: {}", error.code, error.message));
+        }
+
+        response
+            .result
+            .ok_or_else(|| anyhow!("Bundler returned no user operation hash"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_operation_hash() {
+        let operation = UserOperation {
+            sender: Address::repeat_byte(1),
+            call_gas_limit: U256::from(100_000),
+            verification_gas_limit: U256::from(200_000),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pack_u128(U256::from(2), U256::from(1)).unwrap()[15..17],
+            [2, 0]
+        );
+        assert!(pack_u128(U256::MAX, U256::ZERO).is_err());
+        assert!(operation.init_code().is_empty());
+
+        let hash = operation.hash(ENTRY_POINT, 1).unwrap();
+
+        assert_ne!(hash, operation.hash(ENTRY_POINT, 10).unwrap());
+        assert_ne!(hash, operation.hash(Address::ZERO, 1).unwrap());
+
+        let sponsored = UserOperation {
+            paymaster: Some(Address::repeat_byte(2)),
+            ..operation.clone()
+        };
+
+        assert_eq!(sponsored.paymaster_and_data().unwrap().len(), 52);
+        assert_ne!(hash, sponsored.hash(ENTRY_POINT, 1).unwrap());
+    }
+
+    #[test]
+    fn test_execute_target() {
+        let dest = Address::repeat_byte(3);
+        let operation = UserOperation {
+            call_data: executeCall {
+                dest,
+                value: U256::from(5),
+                func: Bytes::new(),
+            }
+            .abi_encode()
+            .into(),
+            ..Default::default()
+        };
+
+        assert_eq!(operation.execute_target(), Some((dest, U256::from(5))));
+        assert_eq!(UserOperation::default().execute_target(), None);
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
+use vaultrs::kv2;
+
+use super::app_config::VaultSecretsConfig;
+
+/// KV v2 secret holding the app secrets, e.g. `{"JWT_SIGNING_KEY": "..", "DATABASE_URL": ".."}`
+pub struct VaultSecrets {
+    client: VaultClient,
+    mount: String,
+    path: String,
+}
+
+impl VaultSecrets {
+    pub fn new(config: &VaultSecretsConfig) -> Result<Self> {
+        let client = VaultClient::new(
+            VaultClientSettingsBuilder::default()
+                .address(&config.address)
+                .token(&config.token)
+                .build()?,
+        )?;
+
+        Ok(Self {
+            client,
+            mount: config.mount.clone(),
+            path: config.path.clone(),
+        })
+    }
+
+    pub async fn fetch(&self) -> Result<HashMap<String, String>> {
+        Ok(kv2::read(&self.client, &self.mount, &self.path).await?)
+    }
+}
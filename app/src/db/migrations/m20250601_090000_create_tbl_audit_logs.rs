@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // No foreign keys: audit entries must outlive the users and wallets they describe
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblAuditLogs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblAuditLogs::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblAuditLogs::Actor).string().not_null())
+                    .col(ColumnDef::new(TblAuditLogs::Action).string().not_null())
+                    .col(
+                        ColumnDef::new(TblAuditLogs::ResourceType)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblAuditLogs::ResourceId).string())
+                    .col(ColumnDef::new(TblAuditLogs::Details).json())
+                    .col(
+                        ColumnDef::new(TblAuditLogs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_log_resource")
+                    .table(TblAuditLogs::Table)
+                    .col(TblAuditLogs::ResourceType)
+                    .col(TblAuditLogs::ResourceId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblAuditLogs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblAuditLogs {
+    Table,
+    Id,
+    Actor,
+    Action,
+    ResourceType,
+    ResourceId,
+    Details,
+    CreatedAt,
+}
@@ -0,0 +1,47 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing wallets keep the empty namespace, which maps to the legacy room names
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblWallets::Table)
+                    .add_column(
+                        ColumnDef::new(WalletNamespace::Namespace)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .add_column(
+                        ColumnDef::new(WalletNamespace::NamespaceRotatedAt)
+                            .timestamp_with_time_zone(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblWallets::Table)
+                    .drop_column(WalletNamespace::Namespace)
+                    .drop_column(WalletNamespace::NamespaceRotatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum WalletNamespace {
+    Namespace,
+    NamespaceRotatedAt,
+}
@@ -0,0 +1,41 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Wallets created before the state machine only exist once keygen succeeded
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblWallets::Table)
+                    .add_column(
+                        ColumnDef::new(WalletState::State)
+                            .string()
+                            .not_null()
+                            .default("active"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblWallets::Table)
+                    .drop_column(WalletState::State)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum WalletState {
+    State,
+}
@@ -0,0 +1,96 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblMpcFailures::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblMpcFailures::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TblMpcFailures::WalletId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblMpcFailures::ExecutionId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblMpcFailures::Operation)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblMpcFailures::Reporter)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblMpcFailures::FaultyParties)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblMpcFailures::Round).string().not_null())
+                    .col(ColumnDef::new(TblMpcFailures::Reason).string().not_null())
+                    .col(
+                        ColumnDef::new(TblMpcFailures::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mpc_failure_wallet_id")
+                            .from(TblMpcFailures::Table, TblMpcFailures::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mpc_failure_wallet_id")
+                    .table(TblMpcFailures::Table)
+                    .col(TblMpcFailures::WalletId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblMpcFailures::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblMpcFailures {
+    Table,
+    Id,
+    WalletId,
+    ExecutionId,
+    Operation,
+    Reporter,
+    FaultyParties,
+    Round,
+    Reason,
+    CreatedAt,
+}
@@ -0,0 +1,78 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Transactions were only persisted once signed before statuses were tracked
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(TransactionStatus::Status)
+                            .string()
+                            .not_null()
+                            .default("signed"),
+                    )
+                    .add_column(ColumnDef::new(TransactionStatus::TxHash).string())
+                    .add_column(ColumnDef::new(TransactionStatus::RawTx).text())
+                    .add_column(ColumnDef::new(TransactionStatus::Chain).string())
+                    .add_column(ColumnDef::new(TransactionStatus::Value).string())
+                    .add_column(ColumnDef::new(TransactionStatus::ToAddress).string())
+                    .add_column(ColumnDef::new(TransactionStatus::Error).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_transaction_status")
+                    .table(TblTransactions::Table)
+                    .col(TransactionStatus::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_transaction_status")
+                    .table(TblTransactions::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .drop_column(TransactionStatus::Status)
+                    .drop_column(TransactionStatus::TxHash)
+                    .drop_column(TransactionStatus::RawTx)
+                    .drop_column(TransactionStatus::Chain)
+                    .drop_column(TransactionStatus::Value)
+                    .drop_column(TransactionStatus::ToAddress)
+                    .drop_column(TransactionStatus::Error)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionStatus {
+    Status,
+    TxHash,
+    RawTx,
+    Chain,
+    Value,
+    ToAddress,
+    Error,
+}
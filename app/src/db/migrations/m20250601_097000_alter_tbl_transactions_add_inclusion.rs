@@ -0,0 +1,45 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use super::{add_columns, drop_columns};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(TransactionInclusion::BlockNumber)
+                .big_integer()
+                .to_owned(),
+            ColumnDef::new(TransactionInclusion::BlockHash)
+                .string()
+                .to_owned(),
+            ColumnDef::new(TransactionInclusion::Confirmations)
+                .big_integer()
+                .to_owned(),
+        ];
+
+        add_columns(manager, TblTransactions::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblTransactions::Table,
+            [
+                TransactionInclusion::BlockNumber,
+                TransactionInclusion::BlockHash,
+                TransactionInclusion::Confirmations,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionInclusion {
+    BlockNumber,
+    BlockHash,
+    Confirmations,
+}
@@ -0,0 +1,80 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblAddresses::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblAddresses::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblAddresses::UserId).integer().not_null())
+                    .col(ColumnDef::new(TblAddresses::Name).string().not_null())
+                    .col(ColumnDef::new(TblAddresses::Chain).string().not_null())
+                    .col(ColumnDef::new(TblAddresses::Address).string().not_null())
+                    .col(
+                        ColumnDef::new(TblAddresses::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblAddresses::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_address_user_id")
+                            .from(TblAddresses::Table, TblAddresses::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_address_user_chain_address")
+                    .table(TblAddresses::Table)
+                    .col(TblAddresses::UserId)
+                    .col(TblAddresses::Chain)
+                    .col(TblAddresses::Address)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblAddresses::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblAddresses {
+    Table,
+    Id,
+    UserId,
+    Name,
+    Chain,
+    Address,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,40 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use super::{add_columns, drop_columns};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(TransactionScreening::ScreeningVerdict)
+                .string()
+                .to_owned(),
+            ColumnDef::new(TransactionScreening::ScreeningReason)
+                .string()
+                .to_owned(),
+        ];
+
+        add_columns(manager, TblTransactions::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblTransactions::Table,
+            [
+                TransactionScreening::ScreeningVerdict,
+                TransactionScreening::ScreeningReason,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionScreening {
+    ScreeningVerdict,
+    ScreeningReason,
+}
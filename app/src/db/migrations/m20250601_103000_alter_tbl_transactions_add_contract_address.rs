@@ -0,0 +1,37 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(TransactionContractAddress::ContractAddress).string(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .drop_column(TransactionContractAddress::ContractAddress)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionContractAddress {
+    ContractAddress,
+}
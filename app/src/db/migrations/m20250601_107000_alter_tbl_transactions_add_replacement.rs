@@ -0,0 +1,40 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use super::{add_columns, drop_columns};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(TransactionReplacement::StuckAt)
+                .timestamp_with_time_zone()
+                .to_owned(),
+            ColumnDef::new(TransactionReplacement::ReplacesId)
+                .integer()
+                .to_owned(),
+        ];
+
+        add_columns(manager, TblTransactions::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblTransactions::Table,
+            [
+                TransactionReplacement::StuckAt,
+                TransactionReplacement::ReplacesId,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionReplacement {
+    StuckAt,
+    ReplacesId,
+}
@@ -0,0 +1,106 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblOperations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblOperations::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblOperations::UserId).integer().not_null())
+                    .col(ColumnDef::new(TblOperations::WalletId).integer().not_null())
+                    .col(ColumnDef::new(TblOperations::TransactionId).integer())
+                    .col(ColumnDef::new(TblOperations::Kind).string().not_null())
+                    .col(ColumnDef::new(TblOperations::State).string().not_null())
+                    .col(
+                        ColumnDef::new(TblOperations::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(TblOperations::ErrorCode).string())
+                    .col(ColumnDef::new(TblOperations::Error).string())
+                    .col(
+                        ColumnDef::new(TblOperations::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblOperations::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_operation_user_id")
+                            .from(TblOperations::Table, TblOperations::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_operation_wallet_id")
+                            .from(TblOperations::Table, TblOperations::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_operation_transaction_id")
+                            .from(TblOperations::Table, TblOperations::TransactionId)
+                            .to(TblTransactions::Table, TblTransactions::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_operation_state")
+                    .table(TblOperations::Table)
+                    .col(TblOperations::State)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblOperations::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblOperations {
+    Table,
+    Id,
+    UserId,
+    WalletId,
+    TransactionId,
+    Kind,
+    State,
+    Attempts,
+    ErrorCode,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
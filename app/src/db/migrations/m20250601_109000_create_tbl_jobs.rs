@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblJobs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblJobs::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblJobs::Kind).string().not_null())
+                    .col(ColumnDef::new(TblJobs::Payload).json().not_null())
+                    .col(ColumnDef::new(TblJobs::State).string().not_null())
+                    .col(
+                        ColumnDef::new(TblJobs::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(TblJobs::MaxAttempts).integer().not_null())
+                    .col(
+                        ColumnDef::new(TblJobs::RunAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblJobs::LeasedUntil).timestamp_with_time_zone())
+                    .col(ColumnDef::new(TblJobs::LeaseOwner).string())
+                    .col(ColumnDef::new(TblJobs::LastError).string())
+                    .col(
+                        ColumnDef::new(TblJobs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblJobs::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_job_state_run_at")
+                    .table(TblJobs::Table)
+                    .col(TblJobs::State)
+                    .col(TblJobs::RunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblJobs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblJobs {
+    Table,
+    Id,
+    Kind,
+    Payload,
+    State,
+    Attempts,
+    MaxAttempts,
+    RunAt,
+    LeasedUntil,
+    LeaseOwner,
+    LastError,
+    CreatedAt,
+    UpdatedAt,
+}
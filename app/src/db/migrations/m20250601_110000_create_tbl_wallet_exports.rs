@@ -0,0 +1,106 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblWalletExports::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblWalletExports::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::WalletId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::PublicKey)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblWalletExports::State).string().not_null())
+                    .col(
+                        ColumnDef::new(TblWalletExports::Approvals)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::Approvers)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::Signatures)
+                            .json()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletExports::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_wallet_export_user_id")
+                            .from(TblWalletExports::Table, TblWalletExports::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_wallet_export_wallet_id")
+                            .from(TblWalletExports::Table, TblWalletExports::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblWalletExports::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblWalletExports {
+    Table,
+    Id,
+    UserId,
+    WalletId,
+    PublicKey,
+    State,
+    Approvals,
+    Approvers,
+    Signatures,
+    CreatedAt,
+    UpdatedAt,
+}
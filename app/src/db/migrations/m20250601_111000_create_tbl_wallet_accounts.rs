@@ -0,0 +1,93 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblWalletAccounts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::WalletId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblWalletAccounts::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::DerivationIndex)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::Address)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWalletAccounts::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_wallet_account_wallet_id")
+                            .from(TblWalletAccounts::Table, TblWalletAccounts::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_wallet_account_index")
+                            .col(TblWalletAccounts::WalletId)
+                            .col(TblWalletAccounts::DerivationIndex)
+                            .unique(),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_wallet_account_name")
+                            .col(TblWalletAccounts::WalletId)
+                            .col(TblWalletAccounts::Name)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblWalletAccounts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblWalletAccounts {
+    Table,
+    Id,
+    WalletId,
+    Name,
+    DerivationIndex,
+    Address,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,55 @@
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Transactions of the wallet key itself have no account
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .add_column(ColumnDef::new(TransactionAccount::AccountId).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_transaction_account_id")
+                    .table(TblTransactions::Table)
+                    .col(TransactionAccount::AccountId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_transaction_account_id")
+                    .table(TblTransactions::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TblTransactions::Table)
+                    .drop_column(TransactionAccount::AccountId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TransactionAccount {
+    AccountId,
+}
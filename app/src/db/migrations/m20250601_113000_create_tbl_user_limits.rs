@@ -0,0 +1,59 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblUserLimits::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblUserLimits::UserId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblUserLimits::MaxWallets).integer().null())
+                    .col(
+                        ColumnDef::new(TblUserLimits::MaxDailyTransactions)
+                            .integer()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblUserLimits::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_limit_user_id")
+                            .from(TblUserLimits::Table, TblUserLimits::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblUserLimits::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblUserLimits {
+    Table,
+    UserId,
+    MaxWallets,
+    MaxDailyTransactions,
+    UpdatedAt,
+}
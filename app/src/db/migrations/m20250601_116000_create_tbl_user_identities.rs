@@ -0,0 +1,77 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblUserIdentities::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblUserIdentities::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TblUserIdentities::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblUserIdentities::Issuer)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblUserIdentities::Subject)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblUserIdentities::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_identity_user_id")
+                            .from(TblUserIdentities::Table, TblUserIdentities::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_user_identity_subject")
+                            .col(TblUserIdentities::Issuer)
+                            .col(TblUserIdentities::Subject)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblUserIdentities::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblUserIdentities {
+    Table,
+    Id,
+    UserId,
+    Issuer,
+    Subject,
+    CreatedAt,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblOidcLogins::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblOidcLogins::State)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblOidcLogins::Nonce).string().not_null())
+                    .col(
+                        ColumnDef::new(TblOidcLogins::CodeVerifier)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblOidcLogins::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblOidcLogins::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblOidcLogins {
+    Table,
+    State,
+    Nonce,
+    CodeVerifier,
+    CreatedAt,
+}
@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblOutboxEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblOutboxEvents::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblOutboxEvents::Name).string().not_null())
+                    .col(ColumnDef::new(TblOutboxEvents::Payload).json().not_null())
+                    .col(
+                        ColumnDef::new(TblOutboxEvents::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(TblOutboxEvents::LastError).string())
+                    .col(
+                        ColumnDef::new(TblOutboxEvents::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblOutboxEvents::PublishedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_outbox_event_published_at")
+                    .table(TblOutboxEvents::Table)
+                    .col(TblOutboxEvents::PublishedAt)
+                    .col(TblOutboxEvents::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblOutboxEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblOutboxEvents {
+    Table,
+    Id,
+    Name,
+    Payload,
+    Attempts,
+    LastError,
+    CreatedAt,
+    PublishedAt,
+}
@@ -0,0 +1,74 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblWalletTags::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblWalletTags::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblWalletTags::WalletId).integer().not_null())
+                    .col(ColumnDef::new(TblWalletTags::Tag).string().not_null())
+                    .col(
+                        ColumnDef::new(TblWalletTags::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_wallet_tag_wallet_id")
+                            .from(TblWalletTags::Table, TblWalletTags::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_wallet_tag_wallet_tag")
+                            .col(TblWalletTags::WalletId)
+                            .col(TblWalletTags::Tag)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Wallets are listed by tag
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wallet_tag_tag")
+                    .table(TblWalletTags::Table)
+                    .col(TblWalletTags::Tag)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblWalletTags::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblWalletTags {
+    Table,
+    Id,
+    WalletId,
+    Tag,
+    CreatedAt,
+}
@@ -0,0 +1,138 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblRecurringPayments::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::WalletId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Recipient)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Value)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Schedule)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblRecurringPayments::Memo).text())
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::State)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblRecurringPayments::NextRunAt).timestamp_with_time_zone())
+                    .col(ColumnDef::new(TblRecurringPayments::LastRunAt).timestamp_with_time_zone())
+                    .col(ColumnDef::new(TblRecurringPayments::LastTransactionId).integer())
+                    .col(ColumnDef::new(TblRecurringPayments::LastError).text())
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Runs)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::Failures)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblRecurringPayments::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_recurring_payment_user_id")
+                            .from(TblRecurringPayments::Table, TblRecurringPayments::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_recurring_payment_wallet_id")
+                            .from(TblRecurringPayments::Table, TblRecurringPayments::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The dispatcher polls for the due payments
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_recurring_payment_next_run_at")
+                    .table(TblRecurringPayments::Table)
+                    .col(TblRecurringPayments::NextRunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblRecurringPayments::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblRecurringPayments {
+    Table,
+    Id,
+    UserId,
+    WalletId,
+    Recipient,
+    Value,
+    Schedule,
+    Memo,
+    State,
+    NextRunAt,
+    LastRunAt,
+    LastTransactionId,
+    LastError,
+    Runs,
+    Failures,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,42 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use super::{add_columns, drop_columns};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(WalletWithdrawalPolicy::WithdrawalThreshold)
+                .string()
+                .to_owned(),
+            ColumnDef::new(WalletWithdrawalPolicy::WithdrawalDelay)
+                .integer()
+                .not_null()
+                .default(0)
+                .to_owned(),
+        ];
+
+        add_columns(manager, TblWallets::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblWallets::Table,
+            [
+                WalletWithdrawalPolicy::WithdrawalThreshold,
+                WalletWithdrawalPolicy::WithdrawalDelay,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum WalletWithdrawalPolicy {
+    WithdrawalThreshold,
+    WithdrawalDelay,
+}
@@ -0,0 +1,109 @@
+use super::m20250517_093000_create_tbl_users::TblUsers;
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TblWithdrawals::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TblWithdrawals::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TblWithdrawals::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(TblWithdrawals::WalletId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblWithdrawals::AccountId).integer())
+                    .col(ColumnDef::new(TblWithdrawals::ToAddress).string())
+                    .col(ColumnDef::new(TblWithdrawals::Value).string().not_null())
+                    .col(ColumnDef::new(TblWithdrawals::Request).json().not_null())
+                    .col(ColumnDef::new(TblWithdrawals::State).string().not_null())
+                    .col(
+                        ColumnDef::new(TblWithdrawals::ReleaseAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TblWithdrawals::TransactionId).integer())
+                    .col(ColumnDef::new(TblWithdrawals::CancelledBy).string())
+                    .col(ColumnDef::new(TblWithdrawals::Error).text())
+                    .col(
+                        ColumnDef::new(TblWithdrawals::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TblWithdrawals::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_withdrawal_user_id")
+                            .from(TblWithdrawals::Table, TblWithdrawals::UserId)
+                            .to(TblUsers::Table, TblUsers::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_withdrawal_wallet_id")
+                            .from(TblWithdrawals::Table, TblWithdrawals::WalletId)
+                            .to(TblWallets::Table, TblWallets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The releaser polls for the pending withdrawals past their window
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_withdrawal_state_release_at")
+                    .table(TblWithdrawals::Table)
+                    .col(TblWithdrawals::State)
+                    .col(TblWithdrawals::ReleaseAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TblWithdrawals::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TblWithdrawals {
+    Table,
+    Id,
+    UserId,
+    WalletId,
+    AccountId,
+    ToAddress,
+    Value,
+    Request,
+    State,
+    ReleaseAt,
+    TransactionId,
+    CancelledBy,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,37 @@
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use super::{add_columns, drop_columns};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            WalletSigningLimits::MaxSignaturesPerHour,
+            WalletSigningLimits::MaxSignaturesPerDay,
+        ]
+        .map(|column| ColumnDef::new(column).integer().to_owned());
+
+        add_columns(manager, TblWallets::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblWallets::Table,
+            [
+                WalletSigningLimits::MaxSignaturesPerHour,
+                WalletSigningLimits::MaxSignaturesPerDay,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum WalletSigningLimits {
+    MaxSignaturesPerHour,
+    MaxSignaturesPerDay,
+}
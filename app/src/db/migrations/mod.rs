@@ -3,6 +3,45 @@ pub use sea_orm_migration::prelude::*;
 mod m20250517_093000_create_tbl_users;
 mod m20250517_094000_create_tbl_wallets;
 mod m20250517_095000_create_tbl_transactions;
+mod m20250601_090000_create_tbl_audit_logs;
+mod m20250601_091000_alter_tbl_wallets_add_namespace;
+mod m20250601_092000_alter_tbl_wallets_add_state;
+mod m20250601_093000_create_tbl_mpc_failures;
+mod m20250601_094000_alter_tbl_transactions_add_status;
+mod m20250601_095000_alter_tbl_wallets_add_archived_at;
+mod m20250601_096000_alter_tbl_wallets_add_address;
+mod m20250601_097000_alter_tbl_transactions_add_inclusion;
+mod m20250601_098000_alter_tbl_transactions_add_nonce;
+mod m20250601_099000_create_tbl_addresses;
+mod m20250601_100000_alter_tbl_wallets_add_whitelist_only;
+mod m20250601_101000_alter_tbl_transactions_add_screening;
+mod m20250601_102000_alter_tbl_wallets_add_address_type;
+mod m20250601_103000_alter_tbl_transactions_add_contract_address;
+mod m20250601_104000_alter_tbl_transactions_add_token;
+mod m20250601_105000_alter_tbl_transactions_add_signature;
+mod m20250601_106000_alter_tbl_wallets_add_auto_bump_gas;
+mod m20250601_107000_alter_tbl_transactions_add_replacement;
+mod m20250601_108000_create_tbl_operations;
+mod m20250601_109000_create_tbl_jobs;
+mod m20250601_110000_create_tbl_wallet_exports;
+mod m20250601_111000_create_tbl_wallet_accounts;
+mod m20250601_112000_alter_tbl_transactions_add_account_id;
+mod m20250601_113000_create_tbl_user_limits;
+mod m20250601_114000_create_tbl_login_failures;
+mod m20250601_115000_create_tbl_sessions;
+mod m20250601_116000_create_tbl_user_identities;
+mod m20250601_117000_create_tbl_oidc_logins;
+mod m20250601_118000_create_tbl_outbox_events;
+mod m20250601_119000_alter_tbl_transactions_add_memo;
+mod m20250601_120000_create_tbl_wallet_tags;
+mod m20250601_121000_create_tbl_recurring_payments;
+mod m20250601_122000_alter_tbl_wallets_add_withdrawal_delay;
+mod m20250601_123000_create_tbl_withdrawals;
+mod m20250601_124000_alter_tbl_wallets_add_signing_limits;
+mod m20250601_125000_alter_tbl_wallets_add_cosigner;
+mod sqlite;
+
+use sqlite::PerBackend;
 
 pub struct Migrator;
 
@@ -13,6 +52,108 @@ impl MigratorTrait for Migrator {
             Box::new(m20250517_093000_create_tbl_users::Migration),
             Box::new(m20250517_094000_create_tbl_wallets::Migration),
             Box::new(m20250517_095000_create_tbl_transactions::Migration),
+            Box::new(m20250601_090000_create_tbl_audit_logs::Migration),
+            Box::new(PerBackend {
+                postgres: m20250601_091000_alter_tbl_wallets_add_namespace::Migration,
+                sqlite: sqlite::WalletsAddNamespace,
+            }),
+            Box::new(m20250601_092000_alter_tbl_wallets_add_state::Migration),
+            Box::new(m20250601_093000_create_tbl_mpc_failures::Migration),
+            Box::new(PerBackend {
+                postgres: m20250601_094000_alter_tbl_transactions_add_status::Migration,
+                sqlite: sqlite::TransactionsAddStatus,
+            }),
+            Box::new(m20250601_095000_alter_tbl_wallets_add_archived_at::Migration),
+            Box::new(m20250601_096000_alter_tbl_wallets_add_address::Migration),
+            Box::new(m20250601_097000_alter_tbl_transactions_add_inclusion::Migration),
+            Box::new(m20250601_098000_alter_tbl_transactions_add_nonce::Migration),
+            Box::new(m20250601_099000_create_tbl_addresses::Migration),
+            Box::new(m20250601_100000_alter_tbl_wallets_add_whitelist_only::Migration),
+            Box::new(m20250601_101000_alter_tbl_transactions_add_screening::Migration),
+            Box::new(m20250601_102000_alter_tbl_wallets_add_address_type::Migration),
+            Box::new(m20250601_103000_alter_tbl_transactions_add_contract_address::Migration),
+            Box::new(m20250601_104000_alter_tbl_transactions_add_token::Migration),
+            Box::new(m20250601_105000_alter_tbl_transactions_add_signature::Migration),
+            Box::new(m20250601_106000_alter_tbl_wallets_add_auto_bump_gas::Migration),
+            Box::new(m20250601_107000_alter_tbl_transactions_add_replacement::Migration),
+            Box::new(m20250601_108000_create_tbl_operations::Migration),
+            Box::new(m20250601_109000_create_tbl_jobs::Migration),
+            Box::new(m20250601_110000_create_tbl_wallet_exports::Migration),
+            Box::new(m20250601_111000_create_tbl_wallet_accounts::Migration),
+            Box::new(m20250601_112000_alter_tbl_transactions_add_account_id::Migration),
+            Box::new(m20250601_113000_create_tbl_user_limits::Migration),
+            Box::new(m20250601_114000_create_tbl_login_failures::Migration),
+            Box::new(m20250601_115000_create_tbl_sessions::Migration),
+            Box::new(m20250601_116000_create_tbl_user_identities::Migration),
+            Box::new(m20250601_117000_create_tbl_oidc_logins::Migration),
+            Box::new(m20250601_118000_create_tbl_outbox_events::Migration),
+            Box::new(m20250601_119000_alter_tbl_transactions_add_memo::Migration),
+            Box::new(m20250601_120000_create_tbl_wallet_tags::Migration),
+            Box::new(m20250601_121000_create_tbl_recurring_payments::Migration),
+            Box::new(m20250601_122000_alter_tbl_wallets_add_withdrawal_delay::Migration),
+            Box::new(m20250601_123000_create_tbl_withdrawals::Migration),
+            Box::new(m20250601_124000_alter_tbl_wallets_add_signing_limits::Migration),
+            Box::new(m20250601_125000_alter_tbl_wallets_add_cosigner::Migration),
         ]
     }
 }
+
+// SQLite only supports a single change per ALTER TABLE, so columns are altered one at a time
+
+/// Adds `columns` to `table`
+async fn add_columns(
+    manager: &SchemaManager<'_>,
+    table: impl IntoIden,
+    columns: impl IntoIterator<Item = ColumnDef>,
+) -> Result<(), DbErr> {
+    let table = table.into_iden();
+
+    for column in columns {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table.clone())
+                    .add_column(column)
+                    .to_owned(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Drops `columns` from `table`
+async fn drop_columns<C: IntoIden>(
+    manager: &SchemaManager<'_>,
+    table: impl IntoIden,
+    columns: impl IntoIterator<Item = C>,
+) -> Result<(), DbErr> {
+    let table = table.into_iden();
+
+    for column in columns {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table.clone())
+                    .drop_column(column)
+                    .to_owned(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use sea_orm::Database;
+
+    #[tokio::test]
+    async fn test_migrations_run_on_sqlite() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+
+        Migrator::up(&db, None).await.unwrap();
+        Migrator::down(&db, None).await.unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! SQLite versions of the migrations that alter several columns in a single statement,
+//! which SQLite refuses
+
+use super::m20250517_094000_create_tbl_wallets::TblWallets;
+use super::m20250517_095000_create_tbl_transactions::TblTransactions;
+use super::m20250601_091000_alter_tbl_wallets_add_namespace::WalletNamespace;
+use super::m20250601_094000_alter_tbl_transactions_add_status::TransactionStatus;
+use super::{add_columns, drop_columns};
+use sea_orm::DbBackend;
+use sea_orm_migration::prelude::*;
+
+/// Migration run as `postgres` on PostgreSQL and as `sqlite` on SQLite, recorded under the
+/// name of `postgres`
+pub struct PerBackend<P, S> {
+    pub postgres: P,
+    pub sqlite: S,
+}
+
+impl<P: MigrationName, S> MigrationName for PerBackend<P, S> {
+    fn name(&self) -> &str {
+        self.postgres.name()
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: MigrationTrait, S: MigrationTrait> MigrationTrait for PerBackend<P, S> {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match manager.get_database_backend() {
+            DbBackend::Sqlite => self.sqlite.up(manager).await,
+            _ => self.postgres.up(manager).await,
+        }
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match manager.get_database_backend() {
+            DbBackend::Sqlite => self.sqlite.down(manager).await,
+            _ => self.postgres.down(manager).await,
+        }
+    }
+}
+
+#[derive(DeriveMigrationName)]
+pub struct WalletsAddNamespace;
+
+#[async_trait::async_trait]
+impl MigrationTrait for WalletsAddNamespace {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(WalletNamespace::Namespace)
+                .string()
+                .not_null()
+                .default("")
+                .to_owned(),
+            ColumnDef::new(WalletNamespace::NamespaceRotatedAt)
+                .timestamp_with_time_zone()
+                .to_owned(),
+        ];
+
+        add_columns(manager, TblWallets::Table, columns).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_columns(
+            manager,
+            TblWallets::Table,
+            [
+                WalletNamespace::Namespace,
+                WalletNamespace::NamespaceRotatedAt,
+            ],
+        )
+        .await
+    }
+}
+
+#[derive(DeriveMigrationName)]
+pub struct TransactionsAddStatus;
+
+#[async_trait::async_trait]
+impl MigrationTrait for TransactionsAddStatus {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(TransactionStatus::Status)
+                .string()
+                .not_null()
+                .default("signed")
+                .to_owned(),
+            ColumnDef::new(TransactionStatus::TxHash)
+                .string()
+                .to_owned(),
+            ColumnDef::new(TransactionStatus::RawTx).text().to_owned(),
+            ColumnDef::new(TransactionStatus::Chain).string().to_owned(),
+            ColumnDef::new(TransactionStatus::Value).string().to_owned(),
+            ColumnDef::new(TransactionStatus::ToAddress)
+                .string()
+                .to_owned(),
+            ColumnDef::new(TransactionStatus::Error).text().to_owned(),
+        ];
+
+        add_columns(manager, TblTransactions::Table, columns).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_transaction_status")
+                    .table(TblTransactions::Table)
+                    .col(TransactionStatus::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_transaction_status")
+                    .table(TblTransactions::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        drop_columns(
+            manager,
+            TblTransactions::Table,
+            [
+                TransactionStatus::Status,
+                TransactionStatus::TxHash,
+                TransactionStatus::RawTx,
+                TransactionStatus::Chain,
+                TransactionStatus::Value,
+                TransactionStatus::ToAddress,
+                TransactionStatus::Error,
+            ],
+        )
+        .await
+    }
+}
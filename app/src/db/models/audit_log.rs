@@ -0,0 +1,23 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_audit_logs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub actor: String,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+    pub details: Option<Json>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,61 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum JobKind {
+    /// Runs an operation queued again by an operator, see `jobs::operations`
+    #[sea_orm(string_value = "operation.keygen")]
+    #[serde(rename = "operation.keygen")]
+    OperationKeygen,
+    /// Deletes the participant shares of a `deleting` wallet, see `jobs::purge`
+    #[sea_orm(string_value = "wallet.purge")]
+    #[serde(rename = "wallet.purge")]
+    WalletPurge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for `run_at`, including between attempts
+    #[sea_orm(string_value = "queued")]
+    Queued,
+    /// Leased by a runner until `leased_until`, then up for grabs again
+    #[sea_orm(string_value = "running")]
+    Running,
+    #[sea_orm(string_value = "succeeded")]
+    Succeeded,
+    /// Every attempt failed, `last_error` is the one of the last attempt
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+/// Background work queued for the job runners, see `jobs::queue`
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_jobs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub kind: JobKind,
+    pub payload: Json,
+    pub state: JobState,
+    /// Leases taken so far, also the version a runner claims the job with
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub leased_until: Option<DateTime<Utc>>,
+    /// Runner holding the lease
+    pub lease_owner: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
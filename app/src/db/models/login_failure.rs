@@ -0,0 +1,23 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// Failed logins of a username (`username:{name}`) or client address (`ip:{address}`)
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_login_failures")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub subject: String,
+    /// Failures since the last successful login or since they were forgotten
+    pub failures: i32,
+    pub last_failure_at: DateTime<Utc>,
+    /// Logins are refused until then, whatever the password
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
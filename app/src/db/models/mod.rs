@@ -1,12 +1,90 @@
+mod address;
+mod audit_log;
+mod job;
+mod login_failure;
+mod mpc_failure;
+mod oidc_login;
+mod operation;
+mod outbox_event;
+mod recurring_payment;
+mod session;
 mod transaction;
 mod user;
+mod user_identity;
+mod user_limit;
 mod wallet;
+mod wallet_account;
+mod wallet_export;
+mod wallet_tag;
+mod withdrawal;
 
-pub use transaction::{ActiveModel as TransactionActiveModel, Model as TransactionModel};
+pub use address::{
+    ActiveModel as AddressActiveModel, Column as AddressColumn, Entity as AddressEntity,
+    Model as AddressModel,
+};
+pub use audit_log::{ActiveModel as AuditLogActiveModel, Model as AuditLogModel};
+pub use job::{
+    ActiveModel as JobActiveModel, Column as JobColumn, Entity as JobEntity, JobKind, JobState,
+    Model as JobModel,
+};
+pub use login_failure::{
+    ActiveModel as LoginFailureActiveModel, Column as LoginFailureColumn,
+    Entity as LoginFailureEntity, Model as LoginFailureModel,
+};
+pub use mpc_failure::{ActiveModel as MpcFailureActiveModel, Model as MpcFailureModel};
+pub use oidc_login::{
+    ActiveModel as OidcLoginActiveModel, Column as OidcLoginColumn, Entity as OidcLoginEntity,
+    Model as OidcLoginModel,
+};
+pub use operation::{
+    ActiveModel as OperationActiveModel, Column as OperationColumn, Entity as OperationEntity,
+    Model as OperationModel, OperationKind, OperationState, OperationStateError,
+};
+pub use outbox_event::{
+    ActiveModel as OutboxEventActiveModel, Column as OutboxEventColumn,
+    Entity as OutboxEventEntity, Model as OutboxEventModel,
+};
+pub use recurring_payment::{
+    ActiveModel as RecurringPaymentActiveModel, Column as RecurringPaymentColumn,
+    Entity as RecurringPaymentEntity, Model as RecurringPaymentModel, RecurringPaymentState,
+};
+pub use session::{
+    ActiveModel as SessionActiveModel, Column as SessionColumn, Entity as SessionEntity,
+    Model as SessionModel,
+};
+pub use transaction::{
+    ActiveModel as TransactionActiveModel, Column as TransactionColumn,
+    Entity as TransactionEntity, Model as TransactionModel, ScreeningVerdict, TransactionStatus,
+    TransactionStatusError,
+};
 pub use user::{
     ActiveModel as UserActiveModel, Column as UserColumn, Entity as UserEntity, Model as UserModel,
 };
+pub use user_identity::{
+    ActiveModel as UserIdentityActiveModel, Column as UserIdentityColumn,
+    Entity as UserIdentityEntity, Model as UserIdentityModel,
+};
+pub use user_limit::{
+    ActiveModel as UserLimitActiveModel, Column as UserLimitColumn, Entity as UserLimitEntity,
+    Model as UserLimitModel,
+};
 pub use wallet::{
-    ActiveModel as WalletActiveModel, Chain, Column as WalletColumn, Entity as WalletEntity,
-    Model as WalletModel,
+    ActiveModel as WalletActiveModel, AddressType, Chain, Column as WalletColumn,
+    Entity as WalletEntity, Model as WalletModel, WalletOperation, WalletState, WalletStateError,
+};
+pub use wallet_account::{
+    ActiveModel as WalletAccountActiveModel, Column as WalletAccountColumn,
+    Entity as WalletAccountEntity, Model as WalletAccountModel, derivation_path,
+};
+pub use wallet_export::{
+    ActiveModel as WalletExportActiveModel, Column as WalletExportColumn,
+    Entity as WalletExportEntity, ExportState, Model as WalletExportModel,
+};
+pub use wallet_tag::{
+    ActiveModel as WalletTagActiveModel, Column as WalletTagColumn, Entity as WalletTagEntity,
+    Model as WalletTagModel,
+};
+pub use withdrawal::{
+    ActiveModel as WithdrawalActiveModel, Column as WithdrawalColumn, Entity as WithdrawalEntity,
+    Model as WithdrawalModel, WithdrawalState,
 };
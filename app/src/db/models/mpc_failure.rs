@@ -0,0 +1,40 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// Identifiable abort reported by a participant during an MPC ceremony
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_mpc_failures")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub wallet_id: i32,
+    pub execution_id: String,
+    pub operation: String,
+    // Signing index of the participant that detected the abort
+    pub reporter: i32,
+    pub faulty_parties: Json,
+    pub round: String,
+    pub reason: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
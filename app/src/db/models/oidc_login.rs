@@ -0,0 +1,24 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// OpenID Connect login started and awaiting its callback, consumed by it
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_oidc_logins")]
+pub struct Model {
+    /// `state` of the authorization request, tying the callback to it
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub state: String,
+    /// Expected `nonce` claim of the ID token
+    pub nonce: String,
+    /// PKCE verifier of the code challenge sent to the provider
+    pub code_verifier: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,154 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// MPC ceremony an operation runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    #[sea_orm(string_value = "keygen")]
+    Keygen,
+    #[sea_orm(string_value = "sign")]
+    Sign,
+    #[sea_orm(string_value = "refresh")]
+    Refresh,
+    #[sea_orm(string_value = "reshare")]
+    Reshare,
+    /// Sharing of a private key imported by the user, see `api::wallet::import_wallet`
+    #[sea_orm(string_value = "import")]
+    Import,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    /// Waiting for the operation worker, e.g. after an admin retry
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "running")]
+    Running,
+    #[sea_orm(string_value = "succeeded")]
+    Succeeded,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OperationStateError {
+    #[error("Operation cannot move from {from:?} to {to:?}")]
+    InvalidTransition {
+        from: OperationState,
+        to: OperationState,
+    },
+    #[error("Operation state was changed by another request")]
+    Conflict,
+}
+
+impl OperationState {
+    pub fn transition_to(
+        &self,
+        next: OperationState,
+    ) -> Result<OperationState, OperationStateError> {
+        use OperationState::*;
+
+        let valid = matches!(
+            (self, next),
+            (Pending, Running) | (Running, Succeeded) | (Running, Failed) | (Failed, Pending)
+        );
+
+        if valid {
+            Ok(next)
+        } else {
+            Err(OperationStateError::InvalidTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
+
+/// Keygen or signing of a wallet, kept with the reason it failed so clients can poll it and
+/// operators retry it
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_operations")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    /// Wallet of the last attempt, a retried keygen runs for a new wallet
+    pub wallet_id: i32,
+    /// Signed transaction of `sign` operations
+    pub transaction_id: Option<i32>,
+    pub kind: OperationKind,
+    pub state: OperationState,
+    pub attempts: i32,
+    /// `code` of the problem the last attempt failed with, e.g. `participants_busy`
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_transitions() {
+        let state = OperationState::Running;
+
+        let failed = state.transition_to(OperationState::Failed).unwrap();
+        let retried = failed.transition_to(OperationState::Pending).unwrap();
+        let running = retried.transition_to(OperationState::Running).unwrap();
+
+        assert_eq!(
+            running.transition_to(OperationState::Succeeded),
+            Ok(OperationState::Succeeded)
+        );
+        assert!(
+            OperationState::Succeeded
+                .transition_to(OperationState::Pending)
+                .is_err()
+        );
+        assert!(
+            OperationState::Pending
+                .transition_to(OperationState::Failed)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// Event recorded with the state change it describes, delivered by `jobs::outbox`
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_outbox_events")]
+pub struct Model {
+    /// Increasing in the order events were recorded, also the id consumers deduplicate by
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub name: String,
+    pub payload: Json,
+    /// Failed deliveries so far
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the message bus acknowledged the event, `None` while it is pending
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
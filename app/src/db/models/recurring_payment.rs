@@ -0,0 +1,65 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum RecurringPaymentState {
+    #[sea_orm(string_value = "active")]
+    Active,
+    /// Paused by the owner or after `RECURRING_MAX_FAILURES` failed runs, until resumed
+    #[sea_orm(string_value = "paused")]
+    Paused,
+}
+
+/// Transfer of `value` to `recipient` sent from the wallet on every occurrence of the cron
+/// `schedule`, in UTC
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_recurring_payments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    pub wallet_id: i32,
+    /// Address, ENS name or Solana account as the owner gave it, ENS names are resolved on
+    /// every run
+    pub recipient: String,
+    /// Decimal amount in the smallest unit of the chain
+    pub value: String,
+    /// Five field cron expression, e.g. "0 9 1 * *" for 09:00 UTC on the first of the month
+    pub schedule: String,
+    pub memo: Option<String>,
+    pub state: RecurringPaymentState,
+    /// Unset while paused, a run is claimed by moving it to the following occurrence
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Transaction of the last run, failed runs may have none
+    pub last_transaction_id: Option<i32>,
+    pub last_error: Option<String>,
+    pub runs: i32,
+    /// Consecutive failed runs, reset by a successful one
+    pub failures: i32,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// Login of a user, identified by the `jti` of the token it issued
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_sessions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: i32,
+    /// User agent of the client that logged in
+    pub device: Option<String>,
+    /// Address of the client that logged in
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Last authenticated request, updated at most once a minute
+    pub last_used_at: DateTime<Utc>,
+    /// Expiry of the token
+    pub expires_at: DateTime<Utc>,
+    /// The token is refused from then on, even before it expires
+    #[serde(skip_serializing)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Model {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
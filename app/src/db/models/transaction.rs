@@ -3,6 +3,81 @@ use sea_orm::{
     sqlx::types::chrono::{DateTime, Utc},
 };
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::wallet::Chain;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "signing")]
+    Signing,
+    #[sea_orm(string_value = "signed")]
+    Signed,
+    #[sea_orm(string_value = "broadcast")]
+    Broadcast,
+    #[sea_orm(string_value = "confirmed")]
+    Confirmed,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+/// Outcome of the recipient screening run before a transaction is signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum ScreeningVerdict {
+    #[sea_orm(string_value = "clear")]
+    Clear,
+    #[sea_orm(string_value = "blocked")]
+    Blocked,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TransactionStatusError {
+    #[error("Transaction cannot move from {from:?} to {to:?}")]
+    InvalidTransition {
+        from: TransactionStatus,
+        to: TransactionStatus,
+    },
+    #[error("Transaction status was changed by another request")]
+    Conflict,
+}
+
+impl TransactionStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Confirmed | TransactionStatus::Failed
+        )
+    }
+
+    pub fn transition_to(
+        &self,
+        next: TransactionStatus,
+    ) -> Result<TransactionStatus, TransactionStatusError> {
+        use TransactionStatus::*;
+
+        let valid = match (self, next) {
+            (Pending, Signing) | (Signing, Signed) | (Signed, Broadcast) => true,
+            (Broadcast, Confirmed) => true,
+            (current, Failed) => !current.is_terminal(),
+            _ => false,
+        };
+
+        if valid {
+            Ok(next)
+        } else {
+            Err(TransactionStatusError::InvalidTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "tbl_transactions")]
@@ -13,6 +88,43 @@ pub struct Model {
     pub wallet_id: i32,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
+    pub status: TransactionStatus,
+    pub tx_hash: Option<String>,
+    // Hex encoded signed transaction, kept to rebroadcast or investigate failures
+    pub raw_tx: Option<String>,
+    // Hex encoded signature of an EVM transaction, its `v` is the EIP-155 one of the chain
+    pub signature_r: Option<String>,
+    pub signature_s: Option<String>,
+    pub signature_v: Option<i64>,
+    pub chain: Option<Chain>,
+    // Decimal amount in the smallest unit of the chain currency
+    pub value: Option<String>,
+    pub to_address: Option<String>,
+    // Address of the contract a deployment creates, derived from the sender and the nonce
+    pub contract_address: Option<String>,
+    // ERC-721 or ERC-1155 token sent by the transaction, `to_address` is its recipient
+    pub token_contract: Option<String>,
+    pub token_id: Option<String>,
+    pub error: Option<String>,
+    // Block the transaction was last seen in, cleared when a reorg drops it
+    pub block_number: Option<i64>,
+    pub block_hash: Option<String>,
+    pub confirmations: Option<i64>,
+    // Allocated by the app when the transaction is created, see `find_held_nonces`
+    pub nonce: Option<i64>,
+    // Unset until the recipient is screened, the reason names the provider that blocked it
+    pub screening_verdict: Option<ScreeningVerdict>,
+    pub screening_reason: Option<String>,
+    // Set once the transaction is still unconfirmed after `STUCK_TX_AFTER`
+    pub stuck_at: Option<DateTime<Utc>>,
+    // Stuck transaction this one replaces with the same nonce and a bumped gas price
+    pub replaces_id: Option<i32>,
+    // Sub-account of the wallet sending the transaction, unset for the wallet key itself.
+    // Each account has nonces of its own
+    pub account_id: Option<i32>,
+    // Free text and string key/value metadata of the client, for its reconciliation
+    pub memo: Option<String>,
+    pub metadata: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -44,3 +156,39 @@ impl Related<super::wallet::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_transitions() {
+        let status = TransactionStatus::Pending;
+
+        let status = status.transition_to(TransactionStatus::Signing).unwrap();
+        let status = status.transition_to(TransactionStatus::Signed).unwrap();
+        let status = status.transition_to(TransactionStatus::Broadcast).unwrap();
+        let status = status.transition_to(TransactionStatus::Confirmed).unwrap();
+
+        assert_eq!(status, TransactionStatus::Confirmed);
+        assert!(status.transition_to(TransactionStatus::Failed).is_err());
+    }
+
+    #[test]
+    fn test_any_pending_status_can_fail() {
+        for status in [
+            TransactionStatus::Pending,
+            TransactionStatus::Signing,
+            TransactionStatus::Signed,
+            TransactionStatus::Broadcast,
+        ] {
+            assert!(status.transition_to(TransactionStatus::Failed).is_ok());
+        }
+
+        assert!(
+            TransactionStatus::Pending
+                .transition_to(TransactionStatus::Broadcast)
+                .is_err()
+        );
+    }
+}
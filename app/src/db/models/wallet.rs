@@ -1,17 +1,47 @@
 use proto::mpc::Chain as ProtoChain;
+use proto::mpc::SignatureScheme;
 use sea_orm::{
     entity::prelude::*,
     sqlx::types::chrono::{DateTime, Utc},
 };
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
 pub enum Chain {
     #[sea_orm(string_value = "ethereum")]
     Ethereum,
     #[sea_orm(string_value = "bitcoin")]
     Bitcoin,
+    #[sea_orm(string_value = "solana")]
+    Solana,
+    #[sea_orm(string_value = "optimism")]
+    Optimism,
+    #[sea_orm(string_value = "arbitrum")]
+    Arbitrum,
+    #[sea_orm(string_value = "base")]
+    Base,
+    #[sea_orm(string_value = "polygon")]
+    Polygon,
+}
+
+impl Chain {
+    /// Whether transactions are built, signed and broadcast through an EVM JSON-RPC provider
+    pub fn is_evm(&self) -> bool {
+        match self {
+            Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon => {
+                true
+            }
+            Chain::Bitcoin | Chain::Solana => false,
+        }
+    }
+
+    /// Whether the chain is an OP Stack rollup, charging an L1 data fee on top of L2 gas
+    pub fn is_op_stack(&self) -> bool {
+        matches!(self, Chain::Optimism | Chain::Base)
+    }
 }
 
 impl From<Chain> for i32 {
@@ -19,6 +49,138 @@ impl From<Chain> for i32 {
         match val {
             Chain::Ethereum => ProtoChain::Ethereum as i32,
             Chain::Bitcoin => ProtoChain::Bitcoin as i32,
+            Chain::Solana => ProtoChain::Solana as i32,
+            Chain::Optimism => ProtoChain::Optimism as i32,
+            Chain::Arbitrum => ProtoChain::Arbitrum as i32,
+            Chain::Base => ProtoChain::Base as i32,
+            Chain::Polygon => ProtoChain::Polygon as i32,
+        }
+    }
+}
+
+/// Script type of Bitcoin wallets, deciding how their transactions are signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    /// Native SegWit, ECDSA signatures with CGGMP21
+    #[sea_orm(string_value = "p2wpkh")]
+    P2wpkh,
+    /// Taproot key path spends, BIP-340 Schnorr signatures with FROST
+    #[sea_orm(string_value = "p2tr")]
+    P2tr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum WalletState {
+    #[sea_orm(string_value = "creating")]
+    Creating,
+    #[sea_orm(string_value = "active")]
+    Active,
+    #[sea_orm(string_value = "frozen")]
+    Frozen,
+    #[sea_orm(string_value = "archiving")]
+    Archiving,
+    #[sea_orm(string_value = "deleting")]
+    Deleting,
+    #[sea_orm(string_value = "deleted")]
+    Deleted,
+}
+
+/// Operations that use the wallet without changing its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletOperation {
+    Sign,
+    RotateNamespace,
+    Export,
+    CreateAccount,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WalletStateError {
+    #[error("Wallet is still being created")]
+    Creating,
+    #[error("Wallet is frozen")]
+    Frozen,
+    #[error("Wallet is being archived")]
+    Archiving,
+    #[error("Wallet is being deleted")]
+    Deleting,
+    #[error("Wallet has been deleted")]
+    Deleted,
+    #[error("Wallet cannot move from {from} to {to}")]
+    InvalidTransition { from: WalletState, to: WalletState },
+    #[error("Wallet state was changed by another request")]
+    Conflict,
+}
+
+impl fmt::Display for WalletState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WalletState::Creating => "creating",
+            WalletState::Active => "active",
+            WalletState::Frozen => "frozen",
+            WalletState::Archiving => "archiving",
+            WalletState::Deleting => "deleting",
+            WalletState::Deleted => "deleted",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl WalletState {
+    pub fn can_transition_to(&self, next: WalletState) -> bool {
+        use WalletState::*;
+
+        matches!(
+            (self, next),
+            (Creating, Active)
+                | (Creating, Deleting)
+                | (Active, Frozen)
+                | (Active, Archiving)
+                | (Active, Deleting)
+                | (Frozen, Active)
+                | (Frozen, Deleting)
+                | (Archiving, Active)
+                | (Archiving, Deleting)
+                | (Deleting, Deleted)
+        )
+    }
+
+    /// Single place where wallet transitions are validated
+    pub fn transition_to(&self, next: WalletState) -> Result<WalletState, WalletStateError> {
+        if self.can_transition_to(next) {
+            return Ok(next);
+        }
+
+        match self {
+            WalletState::Active | WalletState::Frozen => Err(WalletStateError::InvalidTransition {
+                from: *self,
+                to: next,
+            }),
+            _ => Err(self.blocked()),
+        }
+    }
+
+    pub fn ensure_allows(&self, operation: WalletOperation) -> Result<(), WalletStateError> {
+        match (self, operation) {
+            (WalletState::Active, _) => Ok(()),
+            // Rotating rooms is part of responding to an incident, which is when wallets get frozen
+            (WalletState::Frozen, WalletOperation::RotateNamespace) => Ok(()),
+            _ => Err(self.blocked()),
+        }
+    }
+
+    fn blocked(&self) -> WalletStateError {
+        match self {
+            WalletState::Creating => WalletStateError::Creating,
+            WalletState::Active | WalletState::Frozen => WalletStateError::Frozen,
+            WalletState::Archiving => WalletStateError::Archiving,
+            WalletState::Deleting => WalletStateError::Deleting,
+            WalletState::Deleted => WalletStateError::Deleted,
         }
     }
 }
@@ -33,6 +195,50 @@ pub struct Model {
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub chain: Chain,
+
+    #[serde(skip_serializing)]
+    pub namespace: String,
+
+    pub namespace_rotated_at: Option<DateTime<Utc>>,
+    pub state: WalletState,
+
+    // Set while the wallet is archived, shares are purged once the retention window passes
+    pub archived_at: Option<DateTime<Utc>>,
+
+    // Derived from the shared public key returned by keygen, unset for P2WPKH wallets
+    pub address: Option<String>,
+
+    // Policy restricting transfers to recipients in the owner's address book
+    pub whitelist_only: bool,
+
+    // Policy letting the stuck transaction monitor replace transactions with bumped fees
+    pub auto_bump_gas: bool,
+
+    // Policy holding transfers of more than this value, in the smallest unit of the chain,
+    // for `withdrawal_delay` seconds before they are signed
+    pub withdrawal_threshold: Option<String>,
+    pub withdrawal_delay: i32,
+
+    // Policy capping how many transactions of the wallet are signed in any hour and day
+    pub max_signatures_per_hour: Option<i32>,
+    pub max_signatures_per_day: Option<i32>,
+
+    // Set on Bitcoin wallets only
+    pub address_type: Option<AddressType>,
+
+    // Key shared between the co-signing participant and the user's device, 2-of-2, so the
+    // server alone can't sign
+    pub cosigner: bool,
+}
+
+impl Model {
+    /// Scheme the participants hold shares for and sign with, Solana ignores it
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        match self.address_type {
+            Some(AddressType::P2tr) => SignatureScheme::Schnorr,
+            Some(AddressType::P2wpkh) | None => SignatureScheme::Ecdsa,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -52,3 +258,65 @@ impl Related<super::user::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_transitions() {
+        let state = WalletState::Creating;
+
+        let state = state.transition_to(WalletState::Active).unwrap();
+        let state = state.transition_to(WalletState::Frozen).unwrap();
+        let state = state.transition_to(WalletState::Deleting).unwrap();
+        let state = state.transition_to(WalletState::Deleted).unwrap();
+
+        assert_eq!(state, WalletState::Deleted);
+    }
+
+    #[test]
+    fn test_rejects_invalid_transitions() {
+        assert_eq!(
+            WalletState::Creating.transition_to(WalletState::Frozen),
+            Err(WalletStateError::Creating)
+        );
+        assert_eq!(
+            WalletState::Deleted.transition_to(WalletState::Active),
+            Err(WalletStateError::Deleted)
+        );
+        assert_eq!(
+            WalletState::Active.transition_to(WalletState::Deleted),
+            Err(WalletStateError::InvalidTransition {
+                from: WalletState::Active,
+                to: WalletState::Deleted,
+            })
+        );
+    }
+
+    #[test]
+    fn test_signing_requires_active_wallet() {
+        assert!(
+            WalletState::Active
+                .ensure_allows(WalletOperation::Sign)
+                .is_ok()
+        );
+        assert_eq!(
+            WalletState::Creating.ensure_allows(WalletOperation::Sign),
+            Err(WalletStateError::Creating)
+        );
+        assert_eq!(
+            WalletState::Frozen.ensure_allows(WalletOperation::Sign),
+            Err(WalletStateError::Frozen)
+        );
+        assert!(
+            WalletState::Frozen
+                .ensure_allows(WalletOperation::RotateNamespace)
+                .is_ok()
+        );
+        assert_eq!(
+            WalletState::Frozen.ensure_allows(WalletOperation::Export),
+            Err(WalletStateError::Frozen)
+        );
+    }
+}
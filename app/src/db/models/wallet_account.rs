@@ -0,0 +1,60 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+/// Named sub-account of an HD wallet, signing with the child key at its derivation index.
+/// Accounts share the key shares of their wallet, only the derivation path differs
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_wallet_accounts")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub wallet_id: i32,
+    pub name: String,
+    /// Non-hardened child index of the account key under the wallet key
+    pub derivation_index: i32,
+    /// Derived from the child public key the participants agreed on
+    pub address: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// BIP-32 path of the account key at `derivation_index`, `m/<index>` of the wallet key so
+/// the xpub of the wallet derives the same addresses
+pub fn derivation_path(derivation_index: i32) -> Vec<u32> {
+    vec![derivation_index as u32]
+}
+
+impl Model {
+    pub fn derivation_path(&self) -> Vec<u32> {
+        derivation_path(self.derivation_index)
+    }
+
+    /// The wallet as the transactions of the account see it, sending from the account address
+    pub fn wallet(&self, wallet: &super::wallet::Model) -> super::wallet::Model {
+        super::wallet::Model {
+            address: Some(self.address.clone()),
+            ..wallet.clone()
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum ExportState {
+    /// Waiting for `WALLET_EXPORT_APPROVALS` distinct approvers
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    /// Approved, the owner can collect the encrypted shares once
+    #[sea_orm(string_value = "approved")]
+    Approved,
+    #[sea_orm(string_value = "completed")]
+    Completed,
+}
+
+/// Export of the private key of a wallet requested by its owner. The participants encrypt
+/// their shares to `public_key` and the owner reconstructs the key offline
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_wallet_exports")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    pub wallet_id: i32,
+    /// Hex SEC1 secp256k1 key of the owner the shares are encrypted to
+    pub public_key: String,
+    pub state: ExportState,
+    /// Approvals so far, also the version an approval is recorded with
+    pub approvals: i32,
+    /// Names of the approvers, in approval order
+    pub approvers: Json,
+    /// Hex `approver_key` and `signature` of each approval, checked again by the participants
+    pub signatures: Json,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn approved_by(&self, approver: &str) -> bool {
+        self.approvers
+            .as_array()
+            .is_some_and(|approvers| approvers.iter().any(|name| name == approver))
+    }
+}
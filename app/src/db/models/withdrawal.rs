@@ -0,0 +1,129 @@
+use sea_orm::{
+    entity::prelude::*,
+    sqlx::types::chrono::{DateTime, Utc},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalState {
+    /// Waiting out the delay of the wallet policy, cancellable until `release_at`
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+    /// Handed over to signing, `transaction_id` is set once the transaction was created
+    #[sea_orm(string_value = "released")]
+    Released,
+    /// Rejected when released, e.g. the wallet was frozen during the window
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WithdrawalStateError {
+    #[error("Withdrawal cannot move from {from:?} to {to:?}")]
+    InvalidTransition {
+        from: WithdrawalState,
+        to: WithdrawalState,
+    },
+}
+
+impl WithdrawalState {
+    /// Pending withdrawals are cancelled or released, only released ones can fail
+    pub fn transition_to(
+        &self,
+        next: WithdrawalState,
+    ) -> Result<WithdrawalState, WithdrawalStateError> {
+        use WithdrawalState::*;
+
+        let valid = matches!(
+            (self, next),
+            (Pending, Cancelled) | (Pending, Released) | (Released, Failed)
+        );
+
+        if valid {
+            Ok(next)
+        } else {
+            Err(WithdrawalStateError::InvalidTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
+
+/// Transfer above the withdrawal threshold of its wallet, held for the delay of the policy
+/// before it is signed
+#[derive(Debug, Clone, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "tbl_withdrawals")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    pub wallet_id: i32,
+    /// Sub-account the transfer is sent from
+    pub account_id: Option<i32>,
+    /// Recipient as requested, unset for a contract deployment
+    pub to_address: Option<String>,
+    /// Decimal amount in the smallest unit of the chain
+    pub value: String,
+    /// Transaction request sent once released
+    #[serde(skip_serializing)]
+    pub request: Json,
+    pub state: WithdrawalState,
+    pub release_at: DateTime<Utc>,
+    pub transaction_id: Option<i32>,
+    /// `user:<id>` or `admin` for a cancelled withdrawal
+    pub cancelled_by: Option<String>,
+    pub error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id"
+    )]
+    Wallet,
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_transitions() {
+        let released = WithdrawalState::Pending
+            .transition_to(WithdrawalState::Released)
+            .unwrap();
+
+        assert_eq!(
+            released.transition_to(WithdrawalState::Failed),
+            Ok(WithdrawalState::Failed)
+        );
+        assert!(
+            WithdrawalState::Released
+                .transition_to(WithdrawalState::Cancelled)
+                .is_err()
+        );
+        assert!(
+            WithdrawalState::Cancelled
+                .transition_to(WithdrawalState::Released)
+                .is_err()
+        );
+    }
+}
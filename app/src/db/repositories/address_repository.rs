@@ -0,0 +1,52 @@
+use crate::db::models::{AddressActiveModel, AddressColumn, AddressEntity, AddressModel, Chain};
+use anyhow::Result;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DeleteResult, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+
+pub struct AddressRepository<'a> {
+    db: &'a DatabaseConnection,
+}
+
+impl<'a> AddressRepository<'a> {
+    pub fn new(db: &'a DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<AddressModel>> {
+        Ok(AddressEntity::find_by_id(id).one(self.db).await?)
+    }
+
+    pub async fn find_by_user_id(&self, user_id: i32) -> Result<Vec<AddressModel>> {
+        Ok(AddressEntity::find()
+            .filter(AddressColumn::UserId.eq(user_id))
+            .order_by_asc(AddressColumn::Name)
+            .all(self.db)
+            .await?)
+    }
+
+    /// Whether the user saved `address` for `chain`, the address must be checksummed
+    pub async fn contains(&self, user_id: i32, chain: &Chain, address: &str) -> Result<bool> {
+        let count = AddressEntity::find()
+            .filter(AddressColumn::UserId.eq(user_id))
+            .filter(AddressColumn::Chain.eq(chain.clone()))
+            .filter(AddressColumn::Address.eq(address))
+            .count(self.db)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn create(&self, model: AddressActiveModel) -> Result<AddressModel> {
+        Ok(model.insert(self.db).await?)
+    }
+
+    pub async fn update(&self, model: AddressActiveModel) -> Result<AddressModel> {
+        Ok(model.update(self.db).await?)
+    }
+
+    pub async fn delete(&self, id: i32) -> Result<DeleteResult> {
+        Ok(AddressEntity::delete_by_id(id).exec(self.db).await?)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::db::models::{AuditLogActiveModel, AuditLogModel};
+use crate::siem::{self, SecurityEvent};
+use anyhow::Result;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DatabaseTransaction, Set};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct AuditRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> AuditRepository<'a> {
+    #[allow(dead_code)]
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Records the entry and exports it to the SIEM, whether or not the transaction it was
+    /// recorded in commits
+    pub async fn record(
+        &self,
+        actor: &str,
+        action: &str,
+        resource_type: &str,
+        resource_id: Option<String>,
+        details: Option<serde_json::Value>,
+    ) -> Result<AuditLogModel> {
+        let model = AuditLogActiveModel {
+            actor: Set(actor.to_string()),
+            action: Set(action.to_string()),
+            resource_type: Set(resource_type.to_string()),
+            resource_id: Set(resource_id),
+            details: Set(details),
+            ..Default::default()
+        };
+
+        let entry = match &self.executor {
+            DbExecutor::Connection(db) => model.insert(*db).await?,
+            DbExecutor::Transaction(txn) => model.insert(*txn).await?,
+        };
+
+        siem::emit(SecurityEvent::audit(&entry));
+
+        Ok(entry)
+    }
+}
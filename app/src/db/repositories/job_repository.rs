@@ -0,0 +1,173 @@
+use crate::db::models::{JobActiveModel, JobColumn, JobEntity, JobKind, JobModel, JobState};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use std::time::Duration;
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct JobRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> JobRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Queues a job due right away
+    pub async fn enqueue(
+        &self,
+        kind: JobKind,
+        payload: serde_json::Value,
+        max_attempts: i32,
+    ) -> Result<JobModel> {
+        let model = JobActiveModel {
+            kind: Set(kind),
+            payload: Set(payload),
+            state: Set(JobState::Queued),
+            max_attempts: Set(max_attempts),
+            run_at: Set(Utc::now()),
+            ..Default::default()
+        };
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    /// Leases the job due the longest for `owner`, either queued or abandoned by a runner
+    /// whose lease expired. `None` when nothing is due or another runner got there first
+    pub async fn lease(&self, owner: &str, lease: Duration) -> Result<Option<JobModel>> {
+        let now = Utc::now();
+
+        let query = JobEntity::find()
+            .filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(JobColumn::State.eq(JobState::Queued))
+                            .add(JobColumn::RunAt.lte(now)),
+                    )
+                    .add(
+                        Condition::all()
+                            .add(JobColumn::State.eq(JobState::Running))
+                            .add(JobColumn::LeasedUntil.lt(now)),
+                    ),
+            )
+            .order_by_asc(JobColumn::RunAt)
+            .order_by_asc(JobColumn::Id);
+
+        let job = match &self.executor {
+            DbExecutor::Connection(db) => query.one(*db).await?,
+            DbExecutor::Transaction(txn) => query.one(*txn).await?,
+        };
+
+        let Some(job) = job else {
+            return Ok(None);
+        };
+
+        let leased_until = now + chrono::Duration::from_std(lease)?;
+
+        // `attempts` versions the job, only one runner claims each attempt
+        let update = JobEntity::update_many()
+            .col_expr(JobColumn::State, Expr::value(JobState::Running))
+            .col_expr(JobColumn::Attempts, Expr::col(JobColumn::Attempts).add(1))
+            .col_expr(JobColumn::LeaseOwner, Expr::value(owner))
+            .col_expr(JobColumn::LeasedUntil, Expr::value(leased_until))
+            .col_expr(JobColumn::UpdatedAt, Expr::value(now))
+            .filter(JobColumn::Id.eq(job.id))
+            .filter(JobColumn::State.eq(job.state))
+            .filter(JobColumn::Attempts.eq(job.attempts));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(JobModel {
+            state: JobState::Running,
+            attempts: job.attempts + 1,
+            lease_owner: Some(owner.to_string()),
+            leased_until: Some(leased_until),
+            updated_at: Some(now),
+            ..job
+        }))
+    }
+
+    pub async fn complete(&self, job: &JobModel) -> Result<()> {
+        self.release(job, JobState::Succeeded, None, None).await
+    }
+
+    /// Fails the leased attempt, queueing the job again at `retry_at` when given
+    pub async fn fail(
+        &self,
+        job: &JobModel,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let state = match retry_at {
+            Some(_) => JobState::Queued,
+            None => JobState::Failed,
+        };
+
+        self.release(job, state, Some(error), retry_at).await
+    }
+
+    /// Ends the lease of the attempt, unless the lease expired and another runner took over
+    async fn release(
+        &self,
+        job: &JobModel,
+        state: JobState,
+        error: Option<&str>,
+        run_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let lease_owner: Option<String> = None;
+        let leased_until: Option<DateTime<Utc>> = None;
+
+        let mut update = JobEntity::update_many()
+            .col_expr(JobColumn::State, Expr::value(state))
+            .col_expr(JobColumn::LeaseOwner, Expr::value(lease_owner))
+            .col_expr(JobColumn::LeasedUntil, Expr::value(leased_until))
+            .col_expr(JobColumn::LastError, Expr::value(error))
+            .col_expr(JobColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(JobColumn::Id.eq(job.id))
+            .filter(JobColumn::State.eq(JobState::Running))
+            .filter(JobColumn::Attempts.eq(job.attempts));
+
+        if let Some(run_at) = run_at {
+            update = update.col_expr(JobColumn::RunAt, Expr::value(run_at));
+        }
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            anyhow::bail!("Job {} was leased again by another runner", job.id);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use crate::db::models::{
+    LoginFailureActiveModel, LoginFailureColumn, LoginFailureEntity, LoginFailureModel,
+};
+use anyhow::Result;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{DatabaseConnection, EntityTrait, Set};
+
+pub struct LoginFailureRepository<'a> {
+    db: &'a DatabaseConnection,
+}
+
+impl<'a> LoginFailureRepository<'a> {
+    pub fn new(db: &'a DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn find_by_subject(&self, subject: &str) -> Result<Option<LoginFailureModel>> {
+        Ok(LoginFailureEntity::find_by_id(subject).one(self.db).await?)
+    }
+
+    /// Stores the failures of the subject, replacing the ones recorded before
+    pub async fn save(&self, failure: LoginFailureModel) -> Result<LoginFailureModel> {
+        let model = LoginFailureActiveModel {
+            subject: Set(failure.subject.clone()),
+            failures: Set(failure.failures),
+            last_failure_at: Set(failure.last_failure_at),
+            locked_until: Set(failure.locked_until),
+        };
+
+        LoginFailureEntity::insert(model)
+            .on_conflict(
+                OnConflict::column(LoginFailureColumn::Subject)
+                    .update_columns([
+                        LoginFailureColumn::Failures,
+                        LoginFailureColumn::LastFailureAt,
+                        LoginFailureColumn::LockedUntil,
+                    ])
+                    .to_owned(),
+            )
+            .exec(self.db)
+            .await?;
+
+        Ok(failure)
+    }
+
+    /// Forgets the failures of the subject after a successful login
+    pub async fn clear(&self, subject: &str) -> Result<()> {
+        LoginFailureEntity::delete_by_id(subject)
+            .exec(self.db)
+            .await?;
+
+        Ok(())
+    }
+}
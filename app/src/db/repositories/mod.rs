@@ -1,7 +1,41 @@
+mod address_repository;
+mod audit_repository;
+mod job_repository;
+mod login_failure_repository;
+mod mpc_failure_repository;
+mod oidc_login_repository;
+mod operation_repository;
+mod outbox_repository;
+mod recurring_payment_repository;
+mod session_repository;
 mod transaction_repository;
+mod user_identity_repository;
+mod user_limit_repository;
 mod user_repository;
+mod wallet_account_repository;
+mod wallet_export_repository;
 mod wallet_repository;
+mod wallet_tag_repository;
+mod withdrawal_repository;
 
-pub use transaction_repository::TransactionRepository;
+pub use address_repository::AddressRepository;
+pub use audit_repository::AuditRepository;
+pub use job_repository::JobRepository;
+pub use login_failure_repository::LoginFailureRepository;
+pub use mpc_failure_repository::MpcFailureRepository;
+pub use oidc_login_repository::OidcLoginRepository;
+pub use operation_repository::OperationRepository;
+pub use outbox_repository::OutboxRepository;
+pub use recurring_payment_repository::RecurringPaymentRepository;
+pub use session_repository::SessionRepository;
+pub use transaction_repository::{
+    Inclusion, SignatureDetails, StatusDetails, TransactionRepository,
+};
+pub use user_identity_repository::UserIdentityRepository;
+pub use user_limit_repository::UserLimitRepository;
 pub use user_repository::UserRepository;
+pub use wallet_account_repository::WalletAccountRepository;
+pub use wallet_export_repository::WalletExportRepository;
 pub use wallet_repository::WalletRepository;
+pub use wallet_tag_repository::WalletTagRepository;
+pub use withdrawal_repository::WithdrawalRepository;
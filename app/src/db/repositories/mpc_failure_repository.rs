@@ -0,0 +1,35 @@
+use crate::db::models::{MpcFailureActiveModel, MpcFailureModel};
+use anyhow::Result;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DatabaseTransaction};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    #[allow(dead_code)]
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct MpcFailureRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> MpcFailureRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    pub async fn create(&self, model: MpcFailureActiveModel) -> Result<MpcFailureModel> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::db::models::{OidcLoginActiveModel, OidcLoginColumn, OidcLoginEntity, OidcLoginModel};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+pub struct OidcLoginRepository<'a> {
+    db: &'a DatabaseConnection,
+}
+
+impl<'a> OidcLoginRepository<'a> {
+    pub fn new(db: &'a DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, login: OidcLoginModel) -> Result<OidcLoginModel> {
+        let model = OidcLoginActiveModel {
+            state: Set(login.state),
+            nonce: Set(login.nonce),
+            code_verifier: Set(login.code_verifier),
+            created_at: Set(login.created_at),
+        };
+
+        Ok(model.insert(self.db).await?)
+    }
+
+    /// Removes the login of the state, returns it to the one caller that removed it
+    pub async fn take(&self, state: &str) -> Result<Option<OidcLoginModel>> {
+        let Some(login) = OidcLoginEntity::find_by_id(state).one(self.db).await? else {
+            return Ok(None);
+        };
+
+        let res = OidcLoginEntity::delete_by_id(state).exec(self.db).await?;
+
+        Ok((res.rows_affected > 0).then_some(login))
+    }
+
+    /// Forgets the logins started before `before`, their callback never came
+    pub async fn delete_started_before(&self, before: DateTime<Utc>) -> Result<u64> {
+        let res = OidcLoginEntity::delete_many()
+            .filter(OidcLoginColumn::CreatedAt.lt(before))
+            .exec(self.db)
+            .await?;
+
+        Ok(res.rows_affected)
+    }
+}
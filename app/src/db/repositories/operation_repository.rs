@@ -0,0 +1,165 @@
+use crate::db::models::{
+    OperationActiveModel, OperationColumn, OperationEntity, OperationKind, OperationModel,
+    OperationState, OperationStateError,
+};
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, Set,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct OperationRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> OperationRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Records an operation already running its first attempt
+    pub async fn start(
+        &self,
+        user_id: i32,
+        wallet_id: i32,
+        transaction_id: Option<i32>,
+        kind: OperationKind,
+    ) -> Result<OperationModel> {
+        let model = OperationActiveModel {
+            user_id: Set(user_id),
+            wallet_id: Set(wallet_id),
+            transaction_id: Set(transaction_id),
+            kind: Set(kind),
+            state: Set(OperationState::Running),
+            attempts: Set(1),
+            ..Default::default()
+        };
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<OperationModel>> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(OperationEntity::find_by_id(id).one(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(OperationEntity::find_by_id(id).one(*txn).await?),
+        }
+    }
+
+    /// Starts another attempt of a pending operation on the wallet
+    pub async fn resume(
+        &self,
+        operation: &OperationModel,
+        wallet_id: i32,
+    ) -> Result<OperationModel> {
+        let error: Option<String> = None;
+
+        let operation = self
+            .transition(operation, OperationState::Running, |update| {
+                update
+                    .col_expr(OperationColumn::WalletId, Expr::value(wallet_id))
+                    .col_expr(
+                        OperationColumn::Attempts,
+                        Expr::col(OperationColumn::Attempts).add(1),
+                    )
+                    .col_expr(OperationColumn::ErrorCode, Expr::value(error.clone()))
+                    .col_expr(OperationColumn::Error, Expr::value(error))
+            })
+            .await?;
+
+        Ok(OperationModel {
+            wallet_id,
+            attempts: operation.attempts + 1,
+            error_code: None,
+            error: None,
+            ..operation
+        })
+    }
+
+    pub async fn succeed(&self, operation: &OperationModel) -> Result<OperationModel> {
+        self.transition(operation, OperationState::Succeeded, |update| update)
+            .await
+    }
+
+    /// Fails the running attempt with the `code` and detail of its problem
+    pub async fn fail(
+        &self,
+        operation: &OperationModel,
+        code: &str,
+        error: &str,
+    ) -> Result<OperationModel> {
+        let operation = self
+            .transition(operation, OperationState::Failed, |update| {
+                update
+                    .col_expr(OperationColumn::ErrorCode, Expr::value(code))
+                    .col_expr(OperationColumn::Error, Expr::value(error))
+            })
+            .await?;
+
+        Ok(OperationModel {
+            error_code: Some(code.to_string()),
+            error: Some(error.to_string()),
+            ..operation
+        })
+    }
+
+    /// Moves a failed operation back to pending, for a job to run it again
+    pub async fn retry(&self, operation: &OperationModel) -> Result<OperationModel> {
+        self.transition(operation, OperationState::Pending, |update| update)
+            .await
+    }
+
+    /// Moves an operation still in `operation.state` to `next`, with the extra columns set
+    /// by `columns`
+    async fn transition(
+        &self,
+        operation: &OperationModel,
+        next: OperationState,
+        columns: impl FnOnce(
+            sea_orm::UpdateMany<OperationEntity>,
+        ) -> sea_orm::UpdateMany<OperationEntity>,
+    ) -> Result<OperationModel> {
+        let state = operation.state.transition_to(next)?;
+        let now = Utc::now();
+
+        let update = columns(
+            OperationEntity::update_many()
+                .col_expr(OperationColumn::State, Expr::value(state))
+                .col_expr(OperationColumn::UpdatedAt, Expr::value(now))
+                .filter(OperationColumn::Id.eq(operation.id))
+                .filter(OperationColumn::State.eq(operation.state)),
+        );
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Err(OperationStateError::Conflict.into());
+        }
+
+        Ok(OperationModel {
+            state,
+            updated_at: Some(now),
+            ..operation.clone()
+        })
+    }
+}
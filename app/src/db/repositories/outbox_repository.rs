@@ -0,0 +1,165 @@
+use crate::db::models::{
+    OutboxEventActiveModel, OutboxEventColumn, OutboxEventEntity, OutboxEventModel,
+};
+use crate::events::Event;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait, UpdateMany,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct OutboxRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> OutboxRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    pub async fn record(&self, event: &Event) -> Result<OutboxEventModel> {
+        let model = OutboxEventActiveModel {
+            name: Set(event.name.to_string()),
+            payload: Set(serde_json::to_value(event)?),
+            ..Default::default()
+        };
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    /// Oldest events not published yet, in the order they were recorded
+    pub async fn find_pending(&self, limit: u64) -> Result<Vec<OutboxEventModel>> {
+        let query = OutboxEventEntity::find()
+            .filter(OutboxEventColumn::PublishedAt.is_null())
+            .order_by_asc(OutboxEventColumn::Id)
+            .limit(limit);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Events recorded after the event `id`, in the order they were recorded
+    pub async fn find_after(&self, id: i32, limit: u64) -> Result<Vec<OutboxEventModel>> {
+        let query = OutboxEventEntity::find()
+            .filter(OutboxEventColumn::Id.gt(id))
+            .order_by_asc(OutboxEventColumn::Id)
+            .limit(limit);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Id of the last event recorded, 0 when there is none
+    pub async fn last_id(&self) -> Result<i32> {
+        let query = OutboxEventEntity::find().order_by_desc(OutboxEventColumn::Id);
+
+        let last = match &self.executor {
+            DbExecutor::Connection(db) => query.one(*db).await?,
+            DbExecutor::Transaction(txn) => query.one(*txn).await?,
+        };
+
+        Ok(last.map_or(0, |event| event.id))
+    }
+
+    pub async fn mark_published(&self, id: i32, now: DateTime<Utc>) -> Result<()> {
+        let update = OutboxEventEntity::update_many()
+            .col_expr(OutboxEventColumn::PublishedAt, Expr::value(now))
+            .filter(OutboxEventColumn::Id.eq(id));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        Ok(())
+    }
+
+    pub async fn mark_failed(&self, id: i32, error: &str) -> Result<()> {
+        let update = OutboxEventEntity::update_many()
+            .col_expr(
+                OutboxEventColumn::Attempts,
+                Expr::col(OutboxEventColumn::Attempts).add(1),
+            )
+            .col_expr(OutboxEventColumn::LastError, Expr::value(error))
+            .filter(OutboxEventColumn::Id.eq(id));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        Ok(())
+    }
+
+    /// Forgets the events published before `cutoff`, returning how many were deleted
+    pub async fn delete_published_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let delete =
+            OutboxEventEntity::delete_many().filter(OutboxEventColumn::PublishedAt.lt(cutoff));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => delete.exec(*db).await?,
+            DbExecutor::Transaction(txn) => delete.exec(*txn).await?,
+        };
+
+        Ok(result.rows_affected)
+    }
+
+    /// Forgets the events recorded before `cutoff`, returning how many were deleted. Without
+    /// a message bus events are only relayed to the subscribers of `/api/events`
+    pub async fn delete_recorded_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let delete =
+            OutboxEventEntity::delete_many().filter(OutboxEventColumn::CreatedAt.lt(cutoff));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => delete.exec(*db).await?,
+            DbExecutor::Transaction(txn) => delete.exec(*txn).await?,
+        };
+
+        Ok(result.rows_affected)
+    }
+}
+
+/// Runs the state change of `update`, recording `event` in the outbox in the same database
+/// transaction when a row changed. Returns the rows changed
+pub async fn exec_recorded<C, E>(db: &C, update: UpdateMany<E>, event: &Event) -> Result<u64>
+where
+    C: ConnectionTrait + TransactionTrait,
+    E: EntityTrait,
+{
+    // A savepoint when `db` is already a transaction
+    let txn = db.begin().await?;
+
+    let rows_affected = update.exec(&txn).await?.rows_affected;
+
+    if rows_affected > 0 {
+        OutboxRepository::new_with_transaction(&txn)
+            .record(event)
+            .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(rows_affected)
+}
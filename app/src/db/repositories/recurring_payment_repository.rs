@@ -0,0 +1,243 @@
+use super::outbox_repository::exec_recorded;
+use crate::db::models::{
+    RecurringPaymentActiveModel, RecurringPaymentColumn, RecurringPaymentEntity,
+    RecurringPaymentModel, RecurringPaymentState,
+};
+use crate::events::Event;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    #[allow(dead_code)]
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct RecurringPaymentRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> RecurringPaymentRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    pub async fn create(
+        &self,
+        model: RecurringPaymentActiveModel,
+    ) -> Result<RecurringPaymentModel> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<RecurringPaymentModel>> {
+        let query = RecurringPaymentEntity::find_by_id(id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.one(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.one(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_wallet_id(&self, wallet_id: i32) -> Result<Vec<RecurringPaymentModel>> {
+        let query = RecurringPaymentEntity::find()
+            .filter(RecurringPaymentColumn::WalletId.eq(wallet_id))
+            .order_by_asc(RecurringPaymentColumn::Id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Active payments due by `now`, the longest overdue first
+    pub async fn find_due(
+        &self,
+        now: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<Vec<RecurringPaymentModel>> {
+        let query = RecurringPaymentEntity::find()
+            .filter(RecurringPaymentColumn::State.eq(RecurringPaymentState::Active))
+            .filter(RecurringPaymentColumn::NextRunAt.lte(now))
+            .order_by_asc(RecurringPaymentColumn::NextRunAt)
+            .limit(limit);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Claims the due run of the payment by moving it to `next`, false when another
+    /// dispatcher claimed it first or the payment was paused meanwhile
+    pub async fn claim(
+        &self,
+        payment: &RecurringPaymentModel,
+        next: DateTime<Utc>,
+    ) -> Result<bool> {
+        let update = RecurringPaymentEntity::update_many()
+            .col_expr(RecurringPaymentColumn::NextRunAt, Expr::value(next))
+            .col_expr(RecurringPaymentColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(RecurringPaymentColumn::Id.eq(payment.id))
+            .filter(RecurringPaymentColumn::State.eq(RecurringPaymentState::Active))
+            .filter(RecurringPaymentColumn::NextRunAt.eq(payment.next_run_at));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Records the transaction of a run, clearing the failures before it
+    pub async fn record_run(
+        &self,
+        payment: &RecurringPaymentModel,
+        transaction_id: i32,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        let update = RecurringPaymentEntity::update_many()
+            .col_expr(
+                RecurringPaymentColumn::Runs,
+                Expr::col(RecurringPaymentColumn::Runs).add(1),
+            )
+            .col_expr(RecurringPaymentColumn::Failures, Expr::value(0))
+            .col_expr(RecurringPaymentColumn::LastRunAt, Expr::value(at))
+            .col_expr(
+                RecurringPaymentColumn::LastTransactionId,
+                Expr::value(transaction_id),
+            )
+            .col_expr(
+                RecurringPaymentColumn::LastError,
+                Expr::value(None::<String>),
+            )
+            .col_expr(RecurringPaymentColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(RecurringPaymentColumn::Id.eq(payment.id));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        Ok(())
+    }
+
+    /// Records a failed run and notifies the owner, pausing the payment once it failed
+    /// `max_failures` times in a row. Zero never pauses
+    pub async fn record_failure(
+        &self,
+        payment: &RecurringPaymentModel,
+        error: &str,
+        at: DateTime<Utc>,
+        max_failures: i32,
+    ) -> Result<RecurringPaymentModel> {
+        let failures = payment.failures + 1;
+        let paused = max_failures > 0 && failures >= max_failures;
+
+        let updated = RecurringPaymentModel {
+            state: match paused {
+                true => RecurringPaymentState::Paused,
+                false => payment.state,
+            },
+            next_run_at: match paused {
+                true => None,
+                false => payment.next_run_at,
+            },
+            last_run_at: Some(at),
+            last_transaction_id: None,
+            last_error: Some(error.to_string()),
+            failures,
+            updated_at: Some(Utc::now()),
+            ..payment.clone()
+        };
+
+        let update = RecurringPaymentEntity::update_many()
+            .col_expr(RecurringPaymentColumn::State, Expr::value(updated.state))
+            .col_expr(
+                RecurringPaymentColumn::NextRunAt,
+                Expr::value(updated.next_run_at),
+            )
+            .col_expr(RecurringPaymentColumn::Failures, Expr::value(failures))
+            .col_expr(RecurringPaymentColumn::LastRunAt, Expr::value(at))
+            .col_expr(
+                RecurringPaymentColumn::LastTransactionId,
+                Expr::value(None::<i32>),
+            )
+            .col_expr(
+                RecurringPaymentColumn::LastError,
+                Expr::value(updated.last_error.clone()),
+            )
+            .col_expr(
+                RecurringPaymentColumn::UpdatedAt,
+                Expr::value(updated.updated_at),
+            )
+            .filter(RecurringPaymentColumn::Id.eq(payment.id));
+
+        let event = Event::recurring_payment(&updated, paused);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => exec_recorded(*db, update, &event).await?,
+            DbExecutor::Transaction(txn) => exec_recorded(*txn, update, &event).await?,
+        };
+
+        Ok(updated)
+    }
+
+    pub async fn pause(&self, payment: &RecurringPaymentModel) -> Result<RecurringPaymentModel> {
+        let mut model: RecurringPaymentActiveModel = payment.clone().into();
+        model.state = Set(RecurringPaymentState::Paused);
+        model.next_run_at = Set(None);
+        model.updated_at = Set(Some(Utc::now()));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.update(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.update(*txn).await?),
+        }
+    }
+
+    /// Resumes the payment from its `next_run_at` occurrence with its failures forgiven
+    pub async fn resume(
+        &self,
+        payment: &RecurringPaymentModel,
+        next_run_at: DateTime<Utc>,
+    ) -> Result<RecurringPaymentModel> {
+        let mut model: RecurringPaymentActiveModel = payment.clone().into();
+        model.state = Set(RecurringPaymentState::Active);
+        model.next_run_at = Set(Some(next_run_at));
+        model.failures = Set(0);
+        model.updated_at = Set(Some(Utc::now()));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.update(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.update(*txn).await?),
+        }
+    }
+
+    pub async fn delete(&self, id: i32) -> Result<()> {
+        let delete = RecurringPaymentEntity::delete_by_id(id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => delete.exec(*db).await?,
+            DbExecutor::Transaction(txn) => delete.exec(*txn).await?,
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use crate::db::models::{SessionActiveModel, SessionColumn, SessionEntity, SessionModel};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+
+pub struct SessionRepository<'a> {
+    db: &'a DatabaseConnection,
+}
+
+impl<'a> SessionRepository<'a> {
+    pub fn new(db: &'a DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, session: SessionActiveModel) -> Result<SessionModel> {
+        Ok(session.insert(self.db).await?)
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<SessionModel>> {
+        Ok(SessionEntity::find_by_id(id).one(self.db).await?)
+    }
+
+    /// Sessions of the user neither revoked nor expired, the most recently used first
+    pub async fn find_active_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<SessionModel>> {
+        Ok(SessionEntity::find()
+            .filter(SessionColumn::UserId.eq(user_id))
+            .filter(SessionColumn::RevokedAt.is_null())
+            .filter(SessionColumn::ExpiresAt.gt(now))
+            .order_by_desc(SessionColumn::LastUsedAt)
+            .all(self.db)
+            .await?)
+    }
+
+    pub async fn touch(&self, id: &str, now: DateTime<Utc>) -> Result<()> {
+        SessionEntity::update_many()
+            .col_expr(SessionColumn::LastUsedAt, Expr::value(now))
+            .filter(SessionColumn::Id.eq(id))
+            .exec(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revokes an active session of the user, returns false when there is none
+    pub async fn revoke(&self, user_id: i32, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let res = SessionEntity::update_many()
+            .col_expr(SessionColumn::RevokedAt, Expr::value(now))
+            .filter(SessionColumn::Id.eq(id))
+            .filter(SessionColumn::UserId.eq(user_id))
+            .filter(SessionColumn::RevokedAt.is_null())
+            .filter(SessionColumn::ExpiresAt.gt(now))
+            .exec(self.db)
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+}
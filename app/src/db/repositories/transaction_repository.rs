@@ -1,9 +1,43 @@
-use crate::db::models::{TransactionActiveModel, TransactionModel};
+use super::outbox_repository::exec_recorded;
+use crate::db::models::{
+    Chain, ScreeningVerdict, TransactionActiveModel, TransactionColumn, TransactionEntity,
+    TransactionModel, TransactionStatus, TransactionStatusError,
+};
+use crate::events::Event;
 use anyhow::Result;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, DatabaseTransaction};
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+
+/// Fields recorded alongside a status change, `None` keeps the stored value
+#[derive(Debug, Default)]
+pub struct StatusDetails {
+    pub tx_hash: Option<String>,
+    pub raw_tx: Option<String>,
+    pub signature: Option<SignatureDetails>,
+    pub error: Option<String>,
+}
+
+/// Signature of an EVM transaction, `r` and `s` are hex encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureDetails {
+    pub r: String,
+    pub s: String,
+    pub v: i64,
+}
+
+/// Block a broadcast transaction was found in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inclusion {
+    pub block_number: u64,
+    pub block_hash: String,
+    pub confirmations: u64,
+}
 
 pub enum DbExecutor<'a> {
-    #[allow(dead_code)]
     Connection(&'a DatabaseConnection),
     Transaction(&'a DatabaseTransaction),
 }
@@ -13,7 +47,6 @@ pub struct TransactionRepository<'a> {
 }
 
 impl<'a> TransactionRepository<'a> {
-    #[allow(dead_code)]
     pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
         Self {
             executor: DbExecutor::Connection(db),
@@ -32,4 +65,327 @@ impl<'a> TransactionRepository<'a> {
             DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
         }
     }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<TransactionModel>> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(TransactionEntity::find_by_id(id).one(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(TransactionEntity::find_by_id(id).one(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_status(&self, status: TransactionStatus) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find().filter(TransactionColumn::Status.eq(status));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Transactions in `status` on one of `chains`
+    pub async fn find_by_status_on(
+        &self,
+        status: TransactionStatus,
+        chains: &[Chain],
+    ) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::Status.eq(status))
+            .filter(TransactionColumn::Chain.is_in(chains.iter().cloned()));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Nonces from `from` held by transactions of the wallet or its `account` that have not
+    /// failed, failed transactions either never reached the chain or are counted by the node
+    pub async fn find_held_nonces(
+        &self,
+        wallet_id: i32,
+        account_id: Option<i32>,
+        from: i64,
+    ) -> Result<Vec<i64>> {
+        let query = TransactionEntity::find()
+            .select_only()
+            .column(TransactionColumn::Nonce)
+            .filter(TransactionColumn::WalletId.eq(wallet_id))
+            .filter(sender(account_id))
+            .filter(TransactionColumn::Status.ne(TransactionStatus::Failed))
+            .filter(TransactionColumn::Nonce.gte(from))
+            .order_by_asc(TransactionColumn::Nonce)
+            .into_tuple::<i64>();
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Transactions of the wallet or its account sharing `nonce`, i.e. a transaction and its
+    /// replacements
+    pub async fn find_by_nonce(
+        &self,
+        wallet_id: i32,
+        account_id: Option<i32>,
+        nonce: i64,
+    ) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::WalletId.eq(wallet_id))
+            .filter(sender(account_id))
+            .filter(TransactionColumn::Nonce.eq(nonce))
+            .order_by_asc(TransactionColumn::Id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Latest `limit` transactions of the wallet, latest first
+    pub async fn find_by_wallet_id(
+        &self,
+        wallet_id: i32,
+        limit: u64,
+    ) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::WalletId.eq(wallet_id))
+            .order_by_desc(TransactionColumn::Id)
+            .limit(limit);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Transactions sent from the account, latest first
+    pub async fn find_by_account_id(&self, account_id: i32) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::AccountId.eq(account_id))
+            .order_by_desc(TransactionColumn::Id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Transactions the user sent since `since`, oldest first. Replacements re-send a
+    /// transaction and are left out
+    pub async fn find_sent_since(
+        &self,
+        user_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::UserId.eq(user_id))
+            .filter(TransactionColumn::ReplacesId.is_null())
+            .filter(TransactionColumn::CreatedAt.gte(since))
+            .order_by_asc(TransactionColumn::CreatedAt);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Transactions of the wallet sent since `since`, oldest first and without replacements
+    pub async fn find_wallet_sent_since(
+        &self,
+        wallet_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<TransactionModel>> {
+        let query = TransactionEntity::find()
+            .filter(TransactionColumn::WalletId.eq(wallet_id))
+            .filter(TransactionColumn::ReplacesId.is_null())
+            .filter(TransactionColumn::CreatedAt.gte(since))
+            .order_by_asc(TransactionColumn::CreatedAt);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Flags a transaction still in `transaction.status` as stuck
+    pub async fn mark_stuck(&self, transaction: &TransactionModel) -> Result<TransactionModel> {
+        let now = Utc::now();
+
+        let update = TransactionEntity::update_many()
+            .col_expr(TransactionColumn::StuckAt, Expr::value(now))
+            .filter(TransactionColumn::Id.eq(transaction.id))
+            .filter(TransactionColumn::Status.eq(transaction.status));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Err(TransactionStatusError::Conflict.into());
+        }
+
+        Ok(TransactionModel {
+            stuck_at: Some(now),
+            ..transaction.clone()
+        })
+    }
+
+    /// Records the block a transaction still in `transaction.status` was found in,
+    /// `None` clears it after a reorg
+    pub async fn update_inclusion(
+        &self,
+        transaction: &TransactionModel,
+        inclusion: Option<Inclusion>,
+    ) -> Result<TransactionModel> {
+        let now = Utc::now();
+
+        let block_number = inclusion.as_ref().map(|i| i.block_number as i64);
+        let block_hash = inclusion.as_ref().map(|i| i.block_hash.clone());
+        let confirmations = inclusion.as_ref().map(|i| i.confirmations as i64);
+
+        let update = TransactionEntity::update_many()
+            .col_expr(TransactionColumn::BlockNumber, Expr::value(block_number))
+            .col_expr(
+                TransactionColumn::BlockHash,
+                Expr::value(block_hash.clone()),
+            )
+            .col_expr(TransactionColumn::Confirmations, Expr::value(confirmations))
+            .col_expr(TransactionColumn::UpdatedAt, Expr::value(now))
+            .filter(TransactionColumn::Id.eq(transaction.id))
+            .filter(TransactionColumn::Status.eq(transaction.status));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Err(TransactionStatusError::Conflict.into());
+        }
+
+        Ok(TransactionModel {
+            updated_at: Some(now),
+            block_number,
+            block_hash,
+            confirmations,
+            ..transaction.clone()
+        })
+    }
+
+    /// Records the screening verdict of a transaction still in `transaction.status`
+    pub async fn update_screening(
+        &self,
+        transaction: &TransactionModel,
+        verdict: ScreeningVerdict,
+        reason: Option<String>,
+    ) -> Result<TransactionModel> {
+        let now = Utc::now();
+
+        let update = TransactionEntity::update_many()
+            .col_expr(TransactionColumn::ScreeningVerdict, Expr::value(verdict))
+            .col_expr(
+                TransactionColumn::ScreeningReason,
+                Expr::value(reason.clone()),
+            )
+            .col_expr(TransactionColumn::UpdatedAt, Expr::value(now))
+            .filter(TransactionColumn::Id.eq(transaction.id))
+            .filter(TransactionColumn::Status.eq(transaction.status));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Err(TransactionStatusError::Conflict.into());
+        }
+
+        Ok(TransactionModel {
+            updated_at: Some(now),
+            screening_verdict: Some(verdict),
+            screening_reason: reason,
+            ..transaction.clone()
+        })
+    }
+
+    /// Moves the transaction to `next`, failing if the transition is invalid or the
+    /// stored status no longer matches `transaction.status`
+    pub async fn update_status(
+        &self,
+        transaction: &TransactionModel,
+        next: TransactionStatus,
+        details: StatusDetails,
+    ) -> Result<TransactionModel> {
+        let status = transaction.status.transition_to(next)?;
+        let now = Utc::now();
+
+        let mut update = TransactionEntity::update_many()
+            .col_expr(TransactionColumn::Status, Expr::value(status))
+            .col_expr(TransactionColumn::UpdatedAt, Expr::value(now))
+            .filter(TransactionColumn::Id.eq(transaction.id))
+            .filter(TransactionColumn::Status.eq(transaction.status));
+
+        if let Some(tx_hash) = &details.tx_hash {
+            update = update.col_expr(TransactionColumn::TxHash, Expr::value(tx_hash.clone()));
+        }
+
+        if let Some(raw_tx) = &details.raw_tx {
+            update = update.col_expr(TransactionColumn::RawTx, Expr::value(raw_tx.clone()));
+        }
+
+        if let Some(signature) = &details.signature {
+            update = update
+                .col_expr(
+                    TransactionColumn::SignatureR,
+                    Expr::value(signature.r.clone()),
+                )
+                .col_expr(
+                    TransactionColumn::SignatureS,
+                    Expr::value(signature.s.clone()),
+                )
+                .col_expr(TransactionColumn::SignatureV, Expr::value(signature.v));
+        }
+
+        if let Some(error) = &details.error {
+            update = update.col_expr(TransactionColumn::Error, Expr::value(error.clone()));
+        }
+
+        let mut updated = TransactionModel {
+            status,
+            updated_at: Some(now),
+            tx_hash: details.tx_hash.or_else(|| transaction.tx_hash.clone()),
+            raw_tx: details.raw_tx.or_else(|| transaction.raw_tx.clone()),
+            error: details.error.or_else(|| transaction.error.clone()),
+            ..transaction.clone()
+        };
+
+        if let Some(signature) = details.signature {
+            updated.signature_r = Some(signature.r);
+            updated.signature_s = Some(signature.s);
+            updated.signature_v = Some(signature.v);
+        }
+
+        let event = Event::transaction(&updated);
+
+        let rows_affected = match &self.executor {
+            DbExecutor::Connection(db) => exec_recorded(*db, update, &event).await?,
+            DbExecutor::Transaction(txn) => exec_recorded(*txn, update, &event).await?,
+        };
+
+        if rows_affected == 0 {
+            return Err(TransactionStatusError::Conflict.into());
+        }
+
+        Ok(updated)
+    }
+}
+
+/// Transactions sent by the key of the account, or of the wallet itself without one
+fn sender(account_id: Option<i32>) -> SimpleExpr {
+    match account_id {
+        Some(account_id) => TransactionColumn::AccountId.eq(account_id),
+        None => TransactionColumn::AccountId.is_null(),
+    }
 }
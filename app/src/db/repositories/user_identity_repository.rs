@@ -0,0 +1,44 @@
+use crate::db::models::{
+    UserIdentityActiveModel, UserIdentityColumn, UserIdentityEntity, UserIdentityModel,
+};
+use anyhow::Result;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+pub struct UserIdentityRepository<'a> {
+    db: &'a DatabaseConnection,
+}
+
+impl<'a> UserIdentityRepository<'a> {
+    pub fn new(db: &'a DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn find_by_subject(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentityModel>> {
+        Ok(UserIdentityEntity::find()
+            .filter(UserIdentityColumn::Issuer.eq(issuer))
+            .filter(UserIdentityColumn::Subject.eq(subject))
+            .one(self.db)
+            .await?)
+    }
+
+    /// Links the subject to the user, failing when it is linked already
+    pub async fn link(
+        &self,
+        user_id: i32,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<UserIdentityModel> {
+        let identity = UserIdentityActiveModel {
+            user_id: Set(user_id),
+            issuer: Set(issuer.to_string()),
+            subject: Set(subject.to_string()),
+            ..Default::default()
+        };
+
+        Ok(identity.insert(self.db).await?)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::db::models::{UserLimitActiveModel, UserLimitColumn, UserLimitEntity, UserLimitModel};
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{DatabaseConnection, DatabaseTransaction, EntityTrait, Set};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct UserLimitRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> UserLimitRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    pub async fn find_by_user_id(&self, user_id: i32) -> Result<Option<UserLimitModel>> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(UserLimitEntity::find_by_id(user_id).one(*db).await?),
+            DbExecutor::Transaction(txn) => {
+                Ok(UserLimitEntity::find_by_id(user_id).one(*txn).await?)
+            }
+        }
+    }
+
+    /// Records the limits of the user, replacing the ones set before
+    pub async fn upsert(
+        &self,
+        user_id: i32,
+        max_wallets: Option<i32>,
+        max_daily_transactions: Option<i32>,
+    ) -> Result<UserLimitModel> {
+        let model = UserLimitActiveModel {
+            user_id: Set(user_id),
+            max_wallets: Set(max_wallets),
+            max_daily_transactions: Set(max_daily_transactions),
+            updated_at: Set(Some(Utc::now())),
+        };
+
+        let query = UserLimitEntity::insert(model).on_conflict(
+            OnConflict::column(UserLimitColumn::UserId)
+                .update_columns([
+                    UserLimitColumn::MaxWallets,
+                    UserLimitColumn::MaxDailyTransactions,
+                    UserLimitColumn::UpdatedAt,
+                ])
+                .to_owned(),
+        );
+
+        match &self.executor {
+            DbExecutor::Connection(db) => query.exec(*db).await?,
+            DbExecutor::Transaction(txn) => query.exec(*txn).await?,
+        };
+
+        self.find_by_user_id(user_id)
+            .await?
+            .ok_or_else(|| anyhow!("User limits not found after update"))
+    }
+}
@@ -1,7 +1,11 @@
 use crate::db::models::{UserActiveModel, UserColumn, UserEntity, UserModel};
 use anyhow::Result;
+use chrono::Utc;
 use sea_orm::DeleteResult;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    Set,
+};
 
 pub struct UserRepository<'a> {
     db: &'a DatabaseConnection,
@@ -34,6 +38,14 @@ impl<'a> UserRepository<'a> {
         Ok(model.insert(self.db).await?)
     }
 
+    pub async fn update_password(&self, user: &UserModel, password: String) -> Result<UserModel> {
+        let mut model = user.clone().into_active_model();
+        model.password = Set(password);
+        model.updated_on = Set(Some(Utc::now()));
+
+        Ok(model.update(self.db).await?)
+    }
+
     pub async fn delete(&self, id: i32) -> Result<DeleteResult> {
         Ok(UserEntity::delete_by_id(id).exec(self.db).await?)
     }
@@ -0,0 +1,90 @@
+use crate::db::models::{
+    WalletAccountActiveModel, WalletAccountColumn, WalletAccountEntity, WalletAccountModel,
+};
+use anyhow::Result;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    #[allow(dead_code)]
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct WalletAccountRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> WalletAccountRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Records an account, failing when the wallet already has one with the name or index
+    pub async fn create(
+        &self,
+        wallet_id: i32,
+        name: String,
+        derivation_index: i32,
+        address: String,
+    ) -> Result<WalletAccountModel> {
+        let model = WalletAccountActiveModel {
+            wallet_id: Set(wallet_id),
+            name: Set(name),
+            derivation_index: Set(derivation_index),
+            address: Set(address),
+            ..Default::default()
+        };
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<WalletAccountModel>> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(WalletAccountEntity::find_by_id(id).one(*db).await?),
+            DbExecutor::Transaction(txn) => {
+                Ok(WalletAccountEntity::find_by_id(id).one(*txn).await?)
+            }
+        }
+    }
+
+    /// Accounts of the wallet in derivation order
+    pub async fn find_by_wallet_id(&self, wallet_id: i32) -> Result<Vec<WalletAccountModel>> {
+        let query = WalletAccountEntity::find()
+            .filter(WalletAccountColumn::WalletId.eq(wallet_id))
+            .order_by_asc(WalletAccountColumn::DerivationIndex);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Index the next account of the wallet is derived at, indexes are never reused
+    pub async fn next_index(&self, wallet_id: i32) -> Result<i32> {
+        let query = WalletAccountEntity::find()
+            .filter(WalletAccountColumn::WalletId.eq(wallet_id))
+            .order_by_desc(WalletAccountColumn::DerivationIndex);
+
+        let last = match &self.executor {
+            DbExecutor::Connection(db) => query.one(*db).await?,
+            DbExecutor::Transaction(txn) => query.one(*txn).await?,
+        };
+
+        Ok(last.map_or(0, |account| account.derivation_index + 1))
+    }
+}
@@ -0,0 +1,164 @@
+use crate::db::models::{
+    ExportState, WalletExportActiveModel, WalletExportColumn, WalletExportEntity, WalletExportModel,
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, Set,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct WalletExportRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> WalletExportRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Records an export waiting for its approvals
+    pub async fn create(
+        &self,
+        user_id: i32,
+        wallet_id: i32,
+        public_key: String,
+    ) -> Result<WalletExportModel> {
+        let model = WalletExportActiveModel {
+            user_id: Set(user_id),
+            wallet_id: Set(wallet_id),
+            public_key: Set(public_key),
+            state: Set(ExportState::Pending),
+            approvals: Set(0),
+            approvers: Set(serde_json::json!([])),
+            signatures: Set(serde_json::json!([])),
+            ..Default::default()
+        };
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.insert(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<WalletExportModel>> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(WalletExportEntity::find_by_id(id).one(*db).await?),
+            DbExecutor::Transaction(txn) => {
+                Ok(WalletExportEntity::find_by_id(id).one(*txn).await?)
+            }
+        }
+    }
+
+    /// Adds the approval of `approver`, signed by its key, to a pending export, approving it
+    /// with the `required`th one. Fails when another approval was recorded in the meantime
+    pub async fn approve(
+        &self,
+        export: &WalletExportModel,
+        approver: &str,
+        approver_key: &[u8],
+        signature: &[u8],
+        required: usize,
+    ) -> Result<WalletExportModel> {
+        let mut approvers = export.approvers.as_array().cloned().unwrap_or_default();
+        approvers.push(approver.into());
+
+        let mut signatures = export.signatures.as_array().cloned().unwrap_or_default();
+        signatures.push(serde_json::json!({
+            "approver_key": hex::encode(approver_key),
+            "signature": hex::encode(signature),
+        }));
+
+        let approvers = serde_json::Value::Array(approvers);
+        let signatures = serde_json::Value::Array(signatures);
+        let approvals = export.approvals + 1;
+
+        let state = match approvals as usize >= required {
+            true => ExportState::Approved,
+            false => ExportState::Pending,
+        };
+
+        let update = WalletExportEntity::update_many()
+            .col_expr(WalletExportColumn::Approvals, Expr::value(approvals))
+            .col_expr(
+                WalletExportColumn::Approvers,
+                Expr::value(approvers.clone()),
+            )
+            .col_expr(
+                WalletExportColumn::Signatures,
+                Expr::value(signatures.clone()),
+            )
+            .col_expr(WalletExportColumn::State, Expr::value(state))
+            .filter(WalletExportColumn::Id.eq(export.id))
+            .filter(WalletExportColumn::State.eq(ExportState::Pending))
+            .filter(WalletExportColumn::Approvals.eq(export.approvals));
+
+        let now = self.update(export, update).await?;
+
+        Ok(WalletExportModel {
+            approvals,
+            approvers,
+            signatures,
+            state,
+            updated_at: Some(now),
+            ..export.clone()
+        })
+    }
+
+    /// Completes an approved export, only one request gets to hand out the shares
+    pub async fn complete(&self, export: &WalletExportModel) -> Result<WalletExportModel> {
+        let update = WalletExportEntity::update_many()
+            .col_expr(
+                WalletExportColumn::State,
+                Expr::value(ExportState::Completed),
+            )
+            .filter(WalletExportColumn::Id.eq(export.id))
+            .filter(WalletExportColumn::State.eq(ExportState::Approved));
+
+        let now = self.update(export, update).await?;
+
+        Ok(WalletExportModel {
+            state: ExportState::Completed,
+            updated_at: Some(now),
+            ..export.clone()
+        })
+    }
+
+    /// Runs the update of a single export, returning when it was updated
+    async fn update(
+        &self,
+        export: &WalletExportModel,
+        update: sea_orm::UpdateMany<WalletExportEntity>,
+    ) -> Result<DateTime<Utc>> {
+        let now = Utc::now();
+        let update = update.col_expr(WalletExportColumn::UpdatedAt, Expr::value(now));
+
+        let result = match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        if result.rows_affected == 0 {
+            return Err(anyhow!(
+                "Export {} was changed by another request",
+                export.id
+            ));
+        }
+
+        Ok(now)
+    }
+}
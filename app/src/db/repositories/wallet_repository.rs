@@ -1,9 +1,15 @@
-use crate::db::models::{WalletActiveModel, WalletColumn, WalletEntity, WalletModel};
+use super::outbox_repository::exec_recorded;
+use crate::db::models::{
+    WalletActiveModel, WalletColumn, WalletEntity, WalletModel, WalletState, WalletStateError,
+    WalletTagColumn, WalletTagEntity,
+};
+use crate::events::Event;
 use anyhow::Result;
-use sea_orm::DeleteResult;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::{Expr, Query};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
-    QueryFilter,
+    QueryFilter, QuerySelect,
 };
 
 pub enum DbExecutor<'a> {
@@ -37,20 +43,57 @@ impl<'a> WalletRepository<'a> {
         }
     }
 
-    #[allow(dead_code)]
+    /// Wallet locked for update until the transaction ends, so the nonces of its transactions
+    /// are allocated one request at a time across every app instance
+    pub async fn lock(&self, id: i32) -> Result<Option<WalletModel>> {
+        let query = WalletEntity::find_by_id(id).lock_exclusive();
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.one(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.one(*txn).await?),
+        }
+    }
+
     pub async fn find_by_user_id(&self, user_id: i32) -> Result<Vec<WalletModel>> {
         match &self.executor {
             DbExecutor::Connection(db) => Ok(WalletEntity::find()
                 .filter(WalletColumn::UserId.eq(user_id))
+                .filter(WalletColumn::State.ne(WalletState::Deleted))
                 .all(*db)
                 .await?),
             DbExecutor::Transaction(txn) => Ok(WalletEntity::find()
                 .filter(WalletColumn::UserId.eq(user_id))
+                .filter(WalletColumn::State.ne(WalletState::Deleted))
                 .all(*txn)
                 .await?),
         }
     }
 
+    /// Wallets of the user carrying the tag
+    pub async fn find_by_user_id_and_tag(
+        &self,
+        user_id: i32,
+        tag: &str,
+    ) -> Result<Vec<WalletModel>> {
+        let query = WalletEntity::find()
+            .filter(WalletColumn::UserId.eq(user_id))
+            .filter(WalletColumn::State.ne(WalletState::Deleted))
+            .filter(
+                WalletColumn::Id.in_subquery(
+                    Query::select()
+                        .column(WalletTagColumn::WalletId)
+                        .from(WalletTagEntity)
+                        .and_where(WalletTagColumn::Tag.eq(tag))
+                        .to_owned(),
+                ),
+            );
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
     pub async fn create(&self, model: WalletActiveModel) -> Result<WalletModel> {
         match &self.executor {
             DbExecutor::Connection(db) => Ok(model.insert(*db).await?),
@@ -58,10 +101,100 @@ impl<'a> WalletRepository<'a> {
         }
     }
 
-    pub async fn delete(&self, id: i32) -> Result<DeleteResult> {
+    pub async fn update(&self, model: WalletActiveModel) -> Result<WalletModel> {
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(model.update(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(model.update(*txn).await?),
+        }
+    }
+
+    pub async fn find_by_state(&self, state: WalletState) -> Result<Vec<WalletModel>> {
+        let query = WalletEntity::find().filter(WalletColumn::State.eq(state));
+
         match &self.executor {
-            DbExecutor::Connection(db) => Ok(WalletEntity::delete_by_id(id).exec(*db).await?),
-            DbExecutor::Transaction(txn) => Ok(WalletEntity::delete_by_id(id).exec(*txn).await?),
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
         }
     }
+
+    pub async fn find_archived_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<WalletModel>> {
+        let query = WalletEntity::find()
+            .filter(WalletColumn::State.eq(WalletState::Archiving))
+            .filter(WalletColumn::ArchivedAt.lt(cutoff));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Moves the wallet to `next`, failing if the transition is invalid or the
+    /// stored state no longer matches `wallet.state`
+    pub async fn transition(&self, wallet: &WalletModel, next: WalletState) -> Result<WalletModel> {
+        self.update_state(wallet, next, wallet.archived_at, wallet.address.clone())
+            .await
+    }
+
+    /// Activates a wallet whose keygen completed, storing the address of its shared key
+    pub async fn activate(
+        &self,
+        wallet: &WalletModel,
+        address: Option<String>,
+    ) -> Result<WalletModel> {
+        self.update_state(wallet, WalletState::Active, wallet.archived_at, address)
+            .await
+    }
+
+    /// Archives the wallet, starting its retention window
+    pub async fn archive(&self, wallet: &WalletModel) -> Result<WalletModel> {
+        self.update_state(
+            wallet,
+            WalletState::Archiving,
+            Some(Utc::now()),
+            wallet.address.clone(),
+        )
+        .await
+    }
+
+    pub async fn restore(&self, wallet: &WalletModel) -> Result<WalletModel> {
+        self.update_state(wallet, WalletState::Active, None, wallet.address.clone())
+            .await
+    }
+
+    async fn update_state(
+        &self,
+        wallet: &WalletModel,
+        next: WalletState,
+        archived_at: Option<DateTime<Utc>>,
+        address: Option<String>,
+    ) -> Result<WalletModel> {
+        let state = wallet.state.transition_to(next)?;
+
+        let update = WalletEntity::update_many()
+            .col_expr(WalletColumn::State, Expr::value(state))
+            .col_expr(WalletColumn::ArchivedAt, Expr::value(archived_at))
+            .col_expr(WalletColumn::Address, Expr::value(address.clone()))
+            .filter(WalletColumn::Id.eq(wallet.id))
+            .filter(WalletColumn::State.eq(wallet.state));
+
+        let updated = WalletModel {
+            state,
+            archived_at,
+            address,
+            ..wallet.clone()
+        };
+
+        let event = Event::wallet(&updated, wallet.state);
+
+        let rows_affected = match &self.executor {
+            DbExecutor::Connection(db) => exec_recorded(*db, update, &event).await?,
+            DbExecutor::Transaction(txn) => exec_recorded(*txn, update, &event).await?,
+        };
+
+        if rows_affected == 0 {
+            return Err(WalletStateError::Conflict.into());
+        }
+
+        Ok(updated)
+    }
 }
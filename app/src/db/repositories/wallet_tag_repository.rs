@@ -0,0 +1,66 @@
+use crate::db::models::{WalletTagActiveModel, WalletTagColumn, WalletTagEntity, WalletTagModel};
+use anyhow::Result;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct WalletTagRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+impl<'a> WalletTagRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    /// Tags of the wallets in alphabetical order
+    pub async fn find_by_wallet_ids(&self, wallet_ids: &[i32]) -> Result<Vec<WalletTagModel>> {
+        let query = WalletTagEntity::find()
+            .filter(WalletTagColumn::WalletId.is_in(wallet_ids.iter().copied()))
+            .order_by_asc(WalletTagColumn::Tag);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Replaces the tags of the wallet, atomic only with a transaction
+    pub async fn replace(&self, wallet_id: i32, tags: &[String]) -> Result<()> {
+        let delete = WalletTagEntity::delete_many().filter(WalletTagColumn::WalletId.eq(wallet_id));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => delete.exec(*db).await?,
+            DbExecutor::Transaction(txn) => delete.exec(*txn).await?,
+        };
+
+        for tag in tags {
+            let model = WalletTagActiveModel {
+                wallet_id: Set(wallet_id),
+                tag: Set(tag.clone()),
+                ..Default::default()
+            };
+
+            match &self.executor {
+                DbExecutor::Connection(db) => model.insert(*db).await?,
+                DbExecutor::Transaction(txn) => model.insert(*txn).await?,
+            };
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+use super::outbox_repository::{OutboxRepository, exec_recorded};
+use crate::db::models::{
+    WithdrawalActiveModel, WithdrawalColumn, WithdrawalEntity, WithdrawalModel, WithdrawalState,
+};
+use crate::events::Event;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+
+pub enum DbExecutor<'a> {
+    Connection(&'a DatabaseConnection),
+    #[allow(dead_code)]
+    Transaction(&'a DatabaseTransaction),
+}
+
+pub struct WithdrawalRepository<'a> {
+    executor: DbExecutor<'a>,
+}
+
+/// Inserts the withdrawal, recording its `withdrawal.pending` event in the outbox in the
+/// same database transaction
+async fn insert_recorded<C>(db: &C, model: WithdrawalActiveModel) -> Result<WithdrawalModel>
+where
+    C: ConnectionTrait + TransactionTrait,
+{
+    let txn = db.begin().await?;
+
+    let withdrawal = model.insert(&txn).await?;
+
+    OutboxRepository::new_with_transaction(&txn)
+        .record(&Event::withdrawal(&withdrawal))
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(withdrawal)
+}
+
+impl<'a> WithdrawalRepository<'a> {
+    pub fn new_with_connection(db: &'a DatabaseConnection) -> Self {
+        Self {
+            executor: DbExecutor::Connection(db),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn new_with_transaction(txn: &'a DatabaseTransaction) -> Self {
+        Self {
+            executor: DbExecutor::Transaction(txn),
+        }
+    }
+
+    pub async fn create(&self, model: WithdrawalActiveModel) -> Result<WithdrawalModel> {
+        match &self.executor {
+            DbExecutor::Connection(db) => insert_recorded(*db, model).await,
+            DbExecutor::Transaction(txn) => insert_recorded(*txn, model).await,
+        }
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<WithdrawalModel>> {
+        let query = WithdrawalEntity::find_by_id(id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.one(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.one(*txn).await?),
+        }
+    }
+
+    /// Withdrawals of the wallet, latest first
+    pub async fn find_by_wallet_id(&self, wallet_id: i32) -> Result<Vec<WithdrawalModel>> {
+        let query = WithdrawalEntity::find()
+            .filter(WithdrawalColumn::WalletId.eq(wallet_id))
+            .order_by_desc(WithdrawalColumn::Id);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Pending withdrawals whose window closed by `now`, the oldest first
+    pub async fn find_due(&self, now: DateTime<Utc>, limit: u64) -> Result<Vec<WithdrawalModel>> {
+        let query = WithdrawalEntity::find()
+            .filter(WithdrawalColumn::State.eq(WithdrawalState::Pending))
+            .filter(WithdrawalColumn::ReleaseAt.lte(now))
+            .order_by_asc(WithdrawalColumn::ReleaseAt)
+            .limit(limit);
+
+        match &self.executor {
+            DbExecutor::Connection(db) => Ok(query.all(*db).await?),
+            DbExecutor::Transaction(txn) => Ok(query.all(*txn).await?),
+        }
+    }
+
+    /// Cancels the pending withdrawal, `None` when it was released or cancelled first
+    pub async fn cancel(
+        &self,
+        withdrawal: &WithdrawalModel,
+        cancelled_by: &str,
+    ) -> Result<Option<WithdrawalModel>> {
+        let updated = WithdrawalModel {
+            state: WithdrawalState::Cancelled,
+            cancelled_by: Some(cancelled_by.to_string()),
+            ..withdrawal.clone()
+        };
+
+        self.update(withdrawal, updated).await
+    }
+
+    /// Claims the pending withdrawal for signing, `None` when another releaser claimed it
+    /// first or it was cancelled meanwhile
+    pub async fn release(&self, withdrawal: &WithdrawalModel) -> Result<Option<WithdrawalModel>> {
+        let updated = WithdrawalModel {
+            state: WithdrawalState::Released,
+            ..withdrawal.clone()
+        };
+
+        self.update(withdrawal, updated).await
+    }
+
+    pub async fn record_transaction(
+        &self,
+        withdrawal: &WithdrawalModel,
+        transaction_id: i32,
+    ) -> Result<()> {
+        let update = WithdrawalEntity::update_many()
+            .col_expr(WithdrawalColumn::TransactionId, Expr::value(transaction_id))
+            .col_expr(WithdrawalColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(WithdrawalColumn::Id.eq(withdrawal.id));
+
+        match &self.executor {
+            DbExecutor::Connection(db) => update.exec(*db).await?,
+            DbExecutor::Transaction(txn) => update.exec(*txn).await?,
+        };
+
+        Ok(())
+    }
+
+    /// Fails the released withdrawal that couldn't be sent
+    pub async fn fail(
+        &self,
+        withdrawal: &WithdrawalModel,
+        error: &str,
+    ) -> Result<Option<WithdrawalModel>> {
+        let updated = WithdrawalModel {
+            state: WithdrawalState::Failed,
+            error: Some(error.to_string()),
+            ..withdrawal.clone()
+        };
+
+        self.update(withdrawal, updated).await
+    }
+
+    /// Moves the withdrawal to the state of `updated` unless its state changed meanwhile,
+    /// fails when its state can't move there
+    async fn update(
+        &self,
+        withdrawal: &WithdrawalModel,
+        updated: WithdrawalModel,
+    ) -> Result<Option<WithdrawalModel>> {
+        withdrawal.state.transition_to(updated.state)?;
+
+        let updated = WithdrawalModel {
+            updated_at: Some(Utc::now()),
+            ..updated
+        };
+
+        let update = WithdrawalEntity::update_many()
+            .col_expr(WithdrawalColumn::State, Expr::value(updated.state))
+            .col_expr(
+                WithdrawalColumn::CancelledBy,
+                Expr::value(updated.cancelled_by.clone()),
+            )
+            .col_expr(WithdrawalColumn::Error, Expr::value(updated.error.clone()))
+            .col_expr(WithdrawalColumn::UpdatedAt, Expr::value(updated.updated_at))
+            .filter(WithdrawalColumn::Id.eq(withdrawal.id))
+            .filter(WithdrawalColumn::State.eq(withdrawal.state));
+
+        let event = Event::withdrawal(&updated);
+
+        let rows_affected = match &self.executor {
+            DbExecutor::Connection(db) => exec_recorded(*db, update, &event).await?,
+            DbExecutor::Transaction(txn) => exec_recorded(*txn, update, &event).await?,
+        };
+
+        if rows_affected == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(updated))
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::models::{Chain, UserActiveModel, WalletActiveModel, WalletState};
+    use crate::db::repositories::{UserRepository, WalletRepository};
+    use sea_orm::{ConnectOptions, Database, Set};
+    use sea_orm_migration::MigratorTrait;
+
+    /// Migrated in-memory database on a single connection, holding a pending withdrawal
+    async fn pending_withdrawal() -> (DatabaseConnection, WithdrawalModel) {
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set(uuid::Uuid::new_v4().simple().to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let withdrawal = WithdrawalRepository::new_with_connection(&db)
+            .create(WithdrawalActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                to_address: Set(Some(
+                    "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".to_string(),
+                )),
+                value: Set("1001".to_string()),
+                request: Set(serde_json::json!({})),
+                state: Set(WithdrawalState::Pending),
+                release_at: Set(Utc::now()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        (db, withdrawal)
+    }
+
+    /// Names of the events recorded in the outbox, oldest first
+    async fn recorded(db: &DatabaseConnection) -> Vec<String> {
+        OutboxRepository::new_with_connection(db)
+            .find_after(0, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.name)
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_released_withdrawal_records_its_transaction_and_failure() {
+        let (db, withdrawal) = pending_withdrawal().await;
+        let repository = WithdrawalRepository::new_with_connection(&db);
+
+        let released = repository.release(&withdrawal).await.unwrap().unwrap();
+
+        assert_eq!(released.state, WithdrawalState::Released);
+
+        repository.record_transaction(&released, 7).await.unwrap();
+
+        let failed = repository
+            .fail(&released, "Wallet is frozen")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(failed.state, WithdrawalState::Failed);
+
+        let stored = repository.find_by_id(withdrawal.id).await.unwrap().unwrap();
+
+        assert_eq!(stored.state, WithdrawalState::Failed);
+        assert_eq!(stored.transaction_id, Some(7));
+        assert_eq!(stored.error.as_deref(), Some("Wallet is frozen"));
+        assert_eq!(
+            recorded(&db).await,
+            [
+                "withdrawal.pending",
+                "withdrawal.released",
+                "withdrawal.failed"
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_withdrawal_is_released_once() {
+        let (db, withdrawal) = pending_withdrawal().await;
+        let repository = WithdrawalRepository::new_with_connection(&db);
+
+        assert!(repository.release(&withdrawal).await.unwrap().is_some());
+
+        // A second releaser and a late cancellation still hold it as pending, neither acts
+        assert_eq!(repository.release(&withdrawal).await.unwrap(), None);
+        assert_eq!(repository.cancel(&withdrawal, "admin").await.unwrap(), None);
+
+        let stored = repository.find_by_id(withdrawal.id).await.unwrap().unwrap();
+
+        assert_eq!(stored.state, WithdrawalState::Released);
+        assert_eq!(stored.cancelled_by, None);
+        assert_eq!(
+            recorded(&db).await,
+            ["withdrawal.pending", "withdrawal.released"]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_cancelled_withdrawal_is_neither_released_nor_failed() {
+        let (db, withdrawal) = pending_withdrawal().await;
+        let repository = WithdrawalRepository::new_with_connection(&db);
+
+        let cancelled = repository
+            .cancel(&withdrawal, "user:1")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cancelled.cancelled_by.as_deref(), Some("user:1"));
+        assert_eq!(repository.release(&withdrawal).await.unwrap(), None);
+        assert!(repository.fail(&cancelled, "Too late").await.is_err());
+
+        let stored = repository.find_by_id(withdrawal.id).await.unwrap().unwrap();
+
+        assert_eq!(stored.state, WithdrawalState::Cancelled);
+        assert_eq!(stored.error, None);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_transitions_are_rejected() {
+        let (db, withdrawal) = pending_withdrawal().await;
+        let repository = WithdrawalRepository::new_with_connection(&db);
+
+        // Only a released withdrawal was handed to signing and can fail
+        let err = repository.fail(&withdrawal, "Failed").await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Withdrawal cannot move from Pending to Failed"
+        );
+
+        let released = repository.release(&withdrawal).await.unwrap().unwrap();
+
+        assert!(repository.cancel(&released, "admin").await.is_err());
+        assert!(repository.release(&released).await.is_err());
+
+        let stored = repository.find_by_id(withdrawal.id).await.unwrap().unwrap();
+
+        assert_eq!(stored.state, WithdrawalState::Released);
+        assert_eq!(
+            recorded(&db).await,
+            ["withdrawal.pending", "withdrawal.released"]
+        );
+    }
+}
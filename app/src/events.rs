@@ -0,0 +1,225 @@
+//! Events pushed to the users subscribed to `/api/events` as wallets and transactions change
+//! state, from the API handlers and the background jobs alike. Every event is recorded in the
+//! outbox with its state change and only reaches the subscribers once committed, relayed by
+//! `jobs::outbox::EventRelay`
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::db::models::{
+    RecurringPaymentModel, TransactionModel, TransactionStatus, WalletModel, WalletState,
+    WithdrawalModel, WithdrawalState,
+};
+
+/// Events held for the subscribers of the process, slower ones miss the oldest
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub user_id: i32,
+    /// Name of the event, `wallet.<state>`, `transaction.<status>`, `withdrawal.<state>` or
+    /// `recurring_payment.<failed|paused>`, a wallet whose keygen completed is `wallet.created`.
+    /// Stored apart from the payload in the outbox
+    #[serde(skip)]
+    pub name: String,
+    pub wallet_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurring_payment_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawal_id: Option<i32>,
+    /// Why the run of a recurring payment or the release of a withdrawal failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl Event {
+    /// Status the transaction moved to
+    pub fn transaction(transaction: &TransactionModel) -> Self {
+        let name = match transaction.status {
+            TransactionStatus::Pending => "transaction.pending",
+            TransactionStatus::Signing => "transaction.signing",
+            TransactionStatus::Signed => "transaction.signed",
+            TransactionStatus::Broadcast => "transaction.broadcast",
+            TransactionStatus::Confirmed => "transaction.confirmed",
+            TransactionStatus::Failed => "transaction.failed",
+        };
+
+        Self {
+            user_id: transaction.user_id,
+            name: name.to_string(),
+            wallet_id: transaction.wallet_id,
+            transaction_id: Some(transaction.id),
+            tx_hash: transaction.tx_hash.clone(),
+            recurring_payment_id: None,
+            withdrawal_id: None,
+            error: None,
+            at: transaction.updated_at.unwrap_or_else(Utc::now),
+        }
+    }
+
+    /// State the wallet moved to from `previous`
+    pub fn wallet(wallet: &WalletModel, previous: WalletState) -> Self {
+        let name = match wallet.state {
+            WalletState::Active if previous == WalletState::Creating => "wallet.created",
+            WalletState::Creating => "wallet.creating",
+            WalletState::Active => "wallet.active",
+            WalletState::Frozen => "wallet.frozen",
+            WalletState::Archiving => "wallet.archiving",
+            WalletState::Deleting => "wallet.deleting",
+            WalletState::Deleted => "wallet.deleted",
+        };
+
+        Self {
+            user_id: wallet.user_id,
+            name: name.to_string(),
+            wallet_id: wallet.id,
+            transaction_id: None,
+            tx_hash: None,
+            recurring_payment_id: None,
+            withdrawal_id: None,
+            error: None,
+            at: Utc::now(),
+        }
+    }
+
+    /// Failed run of the recurring payment, `paused` when it won't run again until resumed
+    pub fn recurring_payment(payment: &RecurringPaymentModel, paused: bool) -> Self {
+        let name = match paused {
+            true => "recurring_payment.paused",
+            false => "recurring_payment.failed",
+        };
+
+        Self {
+            user_id: payment.user_id,
+            name: name.to_string(),
+            wallet_id: payment.wallet_id,
+            transaction_id: payment.last_transaction_id,
+            tx_hash: None,
+            recurring_payment_id: Some(payment.id),
+            withdrawal_id: None,
+            error: payment.last_error.clone(),
+            at: payment.last_run_at.unwrap_or_else(Utc::now),
+        }
+    }
+
+    /// State the delayed withdrawal moved to, `withdrawal.pending` once it is held
+    pub fn withdrawal(withdrawal: &WithdrawalModel) -> Self {
+        let name = match withdrawal.state {
+            WithdrawalState::Pending => "withdrawal.pending",
+            WithdrawalState::Cancelled => "withdrawal.cancelled",
+            WithdrawalState::Released => "withdrawal.released",
+            WithdrawalState::Failed => "withdrawal.failed",
+        };
+
+        Self {
+            user_id: withdrawal.user_id,
+            name: name.to_string(),
+            wallet_id: withdrawal.wallet_id,
+            transaction_id: withdrawal.transaction_id,
+            tx_hash: None,
+            recurring_payment_id: None,
+            withdrawal_id: Some(withdrawal.id),
+            error: withdrawal.error.clone(),
+            at: withdrawal.updated_at.unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// Committed events of every instance, relayed from the outbox to the subscribers of the
+/// process. Shared through the app state
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    /// Pushes the event to the subscribers of its user
+    pub fn publish(&self, event: Event) {
+        // Fails only when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    /// Events of a user from now on
+    pub fn subscribe(&self, user_id: i32) -> Subscription {
+        Subscription {
+            user_id,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Subscription {
+    user_id: i32,
+    receiver: broadcast::Receiver<Event>,
+}
+
+impl Subscription {
+    /// Next event of the user, `Err` when it fell behind and events may have been missed
+    pub async fn recv(&mut self) -> Result<Event, u64> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.user_id == self.user_id => return Ok(event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => return Err(missed),
+                // The bus lives as long as the server, nothing more is coming
+                Err(RecvError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(user_id: i32, wallet_id: i32) -> Event {
+        Event {
+            user_id,
+            name: "wallet.created".to_string(),
+            wallet_id,
+            transaction_id: None,
+            tx_hash: None,
+            recurring_payment_id: None,
+            withdrawal_id: None,
+            error: None,
+            at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_receives_only_own_events() {
+        let bus = EventBus::new();
+        let mut alice = bus.subscribe(-1);
+
+        bus.publish(event(-2, -2));
+        bus.publish(event(-1, -3));
+
+        let event = alice.recv().await.unwrap();
+
+        assert_eq!(
+            (event.name.as_str(), event.wallet_id),
+            ("wallet.created", -3)
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "user_id": -1, "wallet_id": -3, "at": event.at })
+        );
+    }
+}
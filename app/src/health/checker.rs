@@ -0,0 +1,127 @@
+use actix_web::rt::time::timeout;
+use alloy::providers::Provider;
+use futures::future::join_all;
+use sea_orm::DbConn;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic_health::pb::health_check_response::ServingStatus;
+
+use crate::chains::ChainRegistry;
+use crate::participants::ParticipantPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+impl HealthStatus {
+    fn from_ok(ok: bool) -> Self {
+        if ok { Self::Up } else { Self::Down }
+    }
+}
+
+/// Result of probing every dependency the app needs to serve requests
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub database: HealthStatus,
+    pub provider: HealthStatus,
+    pub participants: Vec<HealthStatus>,
+    pub standbys: Vec<HealthStatus>,
+}
+
+impl HealthReport {
+    pub fn participants_up(&self) -> usize {
+        self.participants
+            .iter()
+            .filter(|status| **status == HealthStatus::Up)
+            .count()
+    }
+
+    pub fn standbys_up(&self) -> usize {
+        self.standbys
+            .iter()
+            .filter(|status| **status == HealthStatus::Up)
+            .count()
+    }
+}
+
+pub struct HealthChecker {
+    db: DbConn,
+    chains: Arc<ChainRegistry>,
+    participants: Arc<dyn ParticipantPool>,
+    timeout: Duration,
+}
+
+impl HealthChecker {
+    pub fn new(
+        db: DbConn,
+        chains: Arc<ChainRegistry>,
+        participants: Arc<dyn ParticipantPool>,
+    ) -> Self {
+        Self {
+            db,
+            chains,
+            participants,
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    pub async fn check(&self) -> HealthReport {
+        let (database, provider, mut participants) = futures::future::join3(
+            self.check_database(),
+            self.check_provider(),
+            self.check_participants(),
+        )
+        .await;
+
+        let standbys = participants.split_off(self.participants.primaries());
+
+        HealthReport {
+            database,
+            provider,
+            participants,
+            standbys,
+        }
+    }
+
+    async fn check_database(&self) -> HealthStatus {
+        let res = timeout(self.timeout, self.db.ping()).await;
+
+        HealthStatus::from_ok(matches!(res, Ok(Ok(()))))
+    }
+
+    /// Up only when the provider of every configured EVM chain responds
+    async fn check_provider(&self) -> HealthStatus {
+        let futures = self
+            .chains
+            .providers()
+            .map(|provider| timeout(self.timeout, provider.get_block_number()));
+
+        let results = join_all(futures).await;
+
+        HealthStatus::from_ok(results.iter().all(|res| matches!(res, Ok(Ok(_)))))
+    }
+
+    /// Queries the `grpc.health.v1` service of the participants and standbys, participants only
+    /// serve once Vault and the relay are up
+    async fn check_participants(&self) -> Vec<HealthStatus> {
+        let futures = (1..=self.participants.count()).map(|participant| async move {
+            match timeout(self.timeout, self.participants.health(participant)).await {
+                Ok(Ok(status)) => HealthStatus::from_ok(status == ServingStatus::Serving),
+                Ok(Err(err)) => {
+                    log::warn!("Participant {participant} health check failed: {err}");
+                    HealthStatus::Down
+                }
+                Err(_) => {
+                    log::warn!("Participant {participant} health check timed out");
+                    HealthStatus::Down
+                }
+            }
+        });
+
+        join_all(futures).await
+    }
+}
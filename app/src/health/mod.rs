@@ -0,0 +1,3 @@
+mod checker;
+
+pub use checker::{HealthChecker, HealthReport, HealthStatus};
@@ -0,0 +1,396 @@
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use proto::mpc::{ErrorCode, WalletInfoResponse};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tonic::Status;
+
+use crate::db::models::{WalletModel, WalletState};
+use crate::db::repositories::WalletRepository;
+use crate::participants::{
+    ParticipantPool, error_code, key_quorum, share_holders, verify_shares, wallet_infos,
+};
+use crate::siem::{self, Outcome, SecurityEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareHealth {
+    /// The share is of the key of the wallet address
+    Healthy,
+    /// The participant holds no share of the wallet
+    Missing,
+    /// The share is of another key than the wallet address, or outside of its key parties
+    WrongKey,
+    /// The share failed the integrity check of the participant
+    Tampered,
+    /// The participant can't read the share, e.g. of another curve than the wallet's
+    Unreadable,
+    /// The participant could not be asked, the share is unchecked
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareCheck {
+    pub participant: usize,
+    pub health: ShareHealth,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnhealthyWallet {
+    pub wallet_id: i32,
+    pub user_id: i32,
+    pub state: WalletState,
+    pub shares: Vec<ShareCheck>,
+    /// Why the readable shares disagree with each other or with the wallet address
+    pub inconsistency: Option<String>,
+    /// Whether enough healthy shares are left to sign
+    pub signable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletHealthReport {
+    pub checked_at: DateTime<Utc>,
+    pub checked: usize,
+    /// Wallets left unchecked because some participant could not be asked
+    pub unchecked: usize,
+    pub wallets: Vec<UnhealthyWallet>,
+}
+
+#[derive(Debug)]
+enum Checked {
+    Healthy,
+    Unchecked,
+    Unhealthy(UnhealthyWallet),
+}
+
+fn share_health(wallet: &WalletModel, result: &Result<WalletInfoResponse, Status>) -> ShareHealth {
+    match result {
+        Ok(info) if verify_shares(wallet, std::slice::from_ref(info)).is_ok() => {
+            ShareHealth::Healthy
+        }
+        Ok(_) => ShareHealth::WrongKey,
+        Err(status) => match error_code(status) {
+            ErrorCode::ShareNotFound => ShareHealth::Missing,
+            ErrorCode::ShareTampered => ShareHealth::Tampered,
+            ErrorCode::ShareMismatch => ShareHealth::Unreadable,
+            _ => ShareHealth::Unreachable,
+        },
+    }
+}
+
+/// Asks the share holders of the wallet for their key and compares it with the address
+async fn check_wallet(participants: &dyn ParticipantPool, wallet: &WalletModel) -> Checked {
+    let results = wallet_infos(participants, wallet, &[]).await;
+
+    let shares: Vec<ShareCheck> = share_holders(participants, wallet)
+        .zip(&results)
+        .map(|(participant, result)| ShareCheck {
+            participant,
+            health: share_health(wallet, result),
+        })
+        .collect();
+
+    // Single shares can't tell keys apart for addresses that don't commit to the key
+    let infos: Vec<WalletInfoResponse> = results.into_iter().filter_map(Result::ok).collect();
+
+    let inconsistency = match infos.len() {
+        0 | 1 => None,
+        _ => verify_shares(wallet, &infos)
+            .err()
+            .map(|err| err.to_string()),
+    };
+
+    let healthy = shares
+        .iter()
+        .filter(|share| share.health == ShareHealth::Healthy)
+        .count();
+
+    if inconsistency.is_none() {
+        if healthy == shares.len() {
+            return Checked::Healthy;
+        }
+
+        if shares.iter().all(|share| {
+            matches!(
+                share.health,
+                ShareHealth::Healthy | ShareHealth::Unreachable
+            )
+        }) {
+            return Checked::Unchecked;
+        }
+    }
+
+    Checked::Unhealthy(UnhealthyWallet {
+        wallet_id: wallet.id,
+        user_id: wallet.user_id,
+        state: wallet.state,
+        signable: inconsistency.is_none() && healthy >= key_quorum(wallet),
+        shares,
+        inconsistency,
+    })
+}
+
+/// Checks the shares of every usable wallet against its address on each participant, so a
+/// lost or corrupted share is found before a signing request of the user fails on it
+pub struct WalletHealthMonitor {
+    db: DatabaseConnection,
+    participants: Arc<dyn ParticipantPool>,
+    interval: Duration,
+    concurrency: usize,
+    report: RwLock<Option<WalletHealthReport>>,
+}
+
+impl WalletHealthMonitor {
+    pub fn new(
+        db: DatabaseConnection,
+        participants: Arc<dyn ParticipantPool>,
+        interval: Duration,
+        concurrency: usize,
+    ) -> Self {
+        Self {
+            db,
+            participants,
+            interval,
+            concurrency,
+            report: RwLock::new(None),
+        }
+    }
+
+    /// Last completed run, `None` until the first run finishes
+    pub fn report(&self) -> Option<WalletHealthReport> {
+        self.report
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            match self.check().await {
+                Ok(report) => {
+                    self.emit_flagged(&report);
+
+                    *self.report.write().unwrap_or_else(|e| e.into_inner()) = Some(report);
+                }
+                Err(err) => log::error!("Wallet health check failed: {err}"),
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn check(&self) -> anyhow::Result<WalletHealthReport> {
+        let repository = WalletRepository::new_with_connection(&self.db);
+
+        let mut wallets = Vec::new();
+
+        for state in [WalletState::Active, WalletState::Frozen] {
+            wallets.extend(repository.find_by_state(state).await?);
+        }
+
+        let checked_at = Utc::now();
+        let checked = wallets.len();
+
+        let results: Vec<Checked> = futures::stream::iter(&wallets)
+            .map(|wallet| check_wallet(self.participants.as_ref(), wallet))
+            .buffer_unordered(self.concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut unchecked = 0;
+        let mut unhealthy = Vec::new();
+
+        for result in results {
+            match result {
+                Checked::Healthy => {}
+                Checked::Unchecked => unchecked += 1,
+                Checked::Unhealthy(wallet) => unhealthy.push(wallet),
+            }
+        }
+
+        unhealthy.sort_by_key(|wallet| wallet.wallet_id);
+
+        log::info!(
+            "Checked the shares of {checked} wallets, {} unhealthy, {unchecked} unchecked",
+            unhealthy.len()
+        );
+
+        Ok(WalletHealthReport {
+            checked_at,
+            checked,
+            unchecked,
+            wallets: unhealthy,
+        })
+    }
+
+    /// Emits the wallets flagged since the previous run, a wallet stays flagged until its
+    /// shares are repaired
+    fn emit_flagged(&self, report: &WalletHealthReport) {
+        let flagged: HashSet<i32> = self
+            .report()
+            .map(|previous| previous.wallets.iter().map(|w| w.wallet_id).collect())
+            .unwrap_or_default();
+
+        for wallet in report
+            .wallets
+            .iter()
+            .filter(|wallet| !flagged.contains(&wallet.wallet_id))
+        {
+            log::error!(
+                "Wallet {} has unhealthy shares: {:?}",
+                wallet.wallet_id,
+                wallet.shares
+            );
+
+            siem::emit(
+                SecurityEvent::new("wallet.shares_unhealthy", Outcome::Failure)
+                    .user(wallet.user_id)
+                    .wallet(wallet.wallet_id)
+                    .details(serde_json::json!({
+                        "shares": wallet.shares,
+                        "inconsistency": wallet.inconsistency,
+                        "signable": wallet.signable,
+                    })),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::Chain;
+    use crate::participants::mock::MockParticipants;
+    use prost::Message;
+    use proto::mpc::ErrorDetail;
+    use tonic::Code;
+
+    fn wallet(address: &str) -> WalletModel {
+        WalletModel {
+            id: 1,
+            user_id: 1,
+            name: "wallet".to_string(),
+            created_at: None,
+            updated_at: None,
+            chain: Chain::Ethereum,
+            namespace: String::new(),
+            namespace_rotated_at: None,
+            state: WalletState::Active,
+            archived_at: None,
+            address: Some(address.to_string()),
+            whitelist_only: false,
+            auto_bump_gas: false,
+            withdrawal_threshold: None,
+            withdrawal_delay: 0,
+            max_signatures_per_hour: None,
+            max_signatures_per_day: None,
+            cosigner: false,
+            address_type: None,
+        }
+    }
+
+    fn health(checked: Checked) -> Vec<ShareHealth> {
+        match checked {
+            Checked::Unhealthy(wallet) => wallet.shares.iter().map(|s| s.health).collect(),
+            checked => panic!("Expected an unhealthy wallet, got {checked:?}"),
+        }
+    }
+
+    #[test]
+    fn test_share_health_of_participant_errors() {
+        let wallet = wallet("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+
+        let tampered = Status::with_details(
+            Code::DataLoss,
+            "Share failed its integrity check",
+            ErrorDetail {
+                code: ErrorCode::ShareTampered.into(),
+                ..Default::default()
+            }
+            .encode_to_vec()
+            .into(),
+        );
+
+        assert_eq!(
+            share_health(&wallet, &Err(Status::not_found("Wallet not found"))),
+            ShareHealth::Missing
+        );
+        assert_eq!(share_health(&wallet, &Err(tampered)), ShareHealth::Tampered);
+        assert_eq!(
+            share_health(&wallet, &Err(Status::unavailable("down"))),
+            ShareHealth::Unreachable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_share_is_flagged() {
+        let participants = MockParticipants::new(3)
+            .with_share(1, 1, 0)
+            .with_share(2, 1, 1);
+
+        let checked = check_wallet(
+            &participants,
+            &wallet("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"),
+        )
+        .await;
+
+        let Checked::Unhealthy(unhealthy) = checked else {
+            panic!("Expected an unhealthy wallet, got {checked:?}");
+        };
+
+        assert_eq!(
+            unhealthy
+                .shares
+                .iter()
+                .map(|s| s.health)
+                .collect::<Vec<_>>(),
+            [
+                ShareHealth::Healthy,
+                ShareHealth::Healthy,
+                ShareHealth::Missing
+            ]
+        );
+        assert!(unhealthy.signable);
+    }
+
+    #[tokio::test]
+    async fn test_shares_of_another_key_are_flagged() {
+        let participants = MockParticipants::new(3)
+            .with_share(1, 1, 0)
+            .with_share(2, 1, 1)
+            .with_share(3, 1, 2);
+
+        let checked = check_wallet(
+            &participants,
+            &wallet("0x0000000000000000000000000000000000000001"),
+        )
+        .await;
+
+        assert_eq!(health(checked), [ShareHealth::WrongKey; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_participant_leaves_wallet_unchecked() {
+        let wallet = wallet("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+
+        let participants = MockParticipants::new(3)
+            .with_share(1, 1, 0)
+            .with_share(2, 1, 1)
+            .with_share(3, 1, 2);
+
+        assert!(matches!(
+            check_wallet(&participants, &wallet).await,
+            Checked::Healthy
+        ));
+
+        let participants = participants.down(2);
+
+        assert!(matches!(
+            check_wallet(&participants, &wallet).await,
+            Checked::Unchecked
+        ));
+    }
+}
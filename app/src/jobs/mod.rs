@@ -0,0 +1,25 @@
+mod health;
+mod operations;
+mod outbox;
+mod providers;
+mod purge;
+mod queue;
+mod receipts;
+mod reconcile;
+mod recurring;
+mod secrets;
+mod stuck;
+mod withdrawals;
+
+pub use health::WalletHealthMonitor;
+pub use operations::KeygenRetry;
+pub use outbox::{EventRelay, OutboxPublisher, message_bus};
+pub use providers::ProviderMonitor;
+pub use purge::{WalletPurge, WalletPurger};
+pub use queue::{JobPolicy, JobRunner, enqueue};
+pub use receipts::ReceiptPoller;
+pub use reconcile::Reconciler;
+pub use recurring::{RecurringDispatcher, next_run, parse_schedule};
+pub use secrets::SecretRotator;
+pub use stuck::StuckMonitor;
+pub use withdrawals::WithdrawalReleaser;
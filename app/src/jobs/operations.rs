@@ -0,0 +1,97 @@
+use sea_orm::{DatabaseConnection, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::{finish_operation, keygen_wallet};
+use crate::db::models::{OperationModel, OperationState, WalletActiveModel, WalletState};
+use crate::db::repositories::{OperationRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+
+/// Payload of an `operation.keygen` job, queued when an operator retries a failed keygen.
+/// That keygen deleted its wallet, so the retry creates the wallet anew with the same owner,
+/// name and chain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeygenRetry {
+    pub operation_id: i32,
+}
+
+impl KeygenRetry {
+    /// Runs the keygen again. Its outcome is recorded on the operation, so the job only
+    /// fails when the keygen couldn't be started
+    pub async fn run(
+        &self,
+        db: &DatabaseConnection,
+        participants: &dyn ParticipantPool,
+    ) -> anyhow::Result<()> {
+        let repository = OperationRepository::new_with_connection(db);
+
+        let Some(operation) = repository.find_by_id(self.operation_id).await? else {
+            anyhow::bail!("Operation {} is gone", self.operation_id);
+        };
+
+        // Already run by an earlier attempt whose lease expired
+        if operation.state != OperationState::Pending {
+            log::info!(
+                "Operation {} is {:?}, nothing to retry",
+                operation.id,
+                operation.state
+            );
+            return Ok(());
+        }
+
+        retry_keygen(db, participants, &repository, operation).await
+    }
+}
+
+async fn retry_keygen(
+    db: &DatabaseConnection,
+    participants: &dyn ParticipantPool,
+    repository: &OperationRepository<'_>,
+    operation: OperationModel,
+) -> anyhow::Result<()> {
+    let wallets = WalletRepository::new_with_connection(db);
+
+    let Some(previous) = wallets.find_by_id(operation.wallet_id).await? else {
+        anyhow::bail!(
+            "Wallet {} of operation {} is gone",
+            operation.wallet_id,
+            operation.id
+        );
+    };
+
+    let wallet = wallets
+        .create(WalletActiveModel {
+            user_id: Set(previous.user_id),
+            name: Set(previous.name.clone()),
+            chain: Set(previous.chain.clone()),
+            namespace: Set(Uuid::new_v4().simple().to_string()),
+            state: Set(WalletState::Creating),
+            address_type: Set(previous.address_type),
+            ..Default::default()
+        })
+        .await?;
+
+    // Fails when another instance already picked the operation up
+    let operation = match repository.resume(&operation, wallet.id).await {
+        Ok(operation) => operation,
+        Err(err) => {
+            wallets.transition(&wallet, WalletState::Deleting).await?;
+            return Err(err);
+        }
+    };
+
+    let created = keygen_wallet(db, participants, &wallet).await;
+
+    finish_operation(repository, &operation, created.as_ref().err()).await;
+
+    match created {
+        Ok(wallet) => log::info!(
+            "Keygen operation {} created wallet {}",
+            operation.id,
+            wallet.id
+        ),
+        Err(err) => log::warn!("Keygen operation {} failed again: {err}", operation.id),
+    }
+
+    Ok(())
+}
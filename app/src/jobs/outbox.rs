@@ -0,0 +1,590 @@
+use anyhow::Result;
+use chrono::Utc;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use rskafka::BackoffConfig;
+use rskafka::client::ClientBuilder;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::record::Record;
+use sea_orm::DatabaseConnection;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+use crate::config::app_config::{OutboxBus, OutboxConfig};
+use crate::db::models::OutboxEventModel;
+use crate::db::repositories::OutboxRepository;
+use crate::events::{Event, EventBus};
+
+/// Time a message bus has to acknowledge an event before the publisher retries it
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time the relay waits for an id taken by a transaction still open before relaying the
+/// events after it, a transaction that rolled back leaves its ids unused for good
+const GAP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Destination of the outbox events, an event is published once the call succeeded
+///
+/// Events may be delivered more than once, e.g. when the acknowledgement was lost or when
+/// several instances publish, consumers deduplicate them by the `id` they carry.
+pub trait MessageBus: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn publish<'a>(&'a self, event: &'a OutboxEventModel) -> BoxFuture<'a, Result<()>>;
+}
+
+pub fn message_bus(config: &OutboxConfig) -> Option<Box<dyn MessageBus>> {
+    let topic = config.topic.clone();
+
+    match config.bus.clone()? {
+        OutboxBus::Kafka { brokers, partition } => Some(Box::new(KafkaBus {
+            brokers,
+            topic,
+            partition,
+            client: OnceCell::new(),
+        })),
+        OutboxBus::Nats { url } => Some(Box::new(NatsBus {
+            url,
+            subject: topic,
+            jetstream: OnceCell::new(),
+        })),
+    }
+}
+
+/// Produces the events to a partition of a Kafka topic, keyed by their id
+struct KafkaBus {
+    brokers: Vec<String>,
+    topic: String,
+    partition: i32,
+    client: OnceCell<PartitionClient>,
+}
+
+impl KafkaBus {
+    async fn client(&self) -> Result<&PartitionClient> {
+        self.client
+            .get_or_try_init(|| async {
+                let client = ClientBuilder::new(self.brokers.clone())
+                    .backoff_config(BackoffConfig {
+                        deadline: Some(PUBLISH_TIMEOUT),
+                        ..Default::default()
+                    })
+                    .build()
+                    .await?;
+
+                let partition = client
+                    .partition_client(
+                        self.topic.clone(),
+                        self.partition,
+                        UnknownTopicHandling::Error,
+                    )
+                    .await?;
+
+                Ok(partition)
+            })
+            .await
+    }
+}
+
+impl MessageBus for KafkaBus {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn publish<'a>(&'a self, event: &'a OutboxEventModel) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let record = Record {
+                key: Some(event.id.to_string().into_bytes()),
+                value: Some(serde_json::to_vec(&event.payload)?),
+                headers: BTreeMap::from([("event".to_string(), event.name.clone().into_bytes())]),
+                timestamp: event.created_at.unwrap_or_else(Utc::now),
+            };
+
+            self.client()
+                .await?
+                .produce(vec![record], Compression::NoCompression)
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Publishes the events to JetStream on `<subject>.<event name>`, the id as `Nats-Msg-Id`
+/// lets the stream drop the duplicates of its deduplication window
+struct NatsBus {
+    url: String,
+    subject: String,
+    jetstream: OnceCell<async_nats::jetstream::Context>,
+}
+
+impl MessageBus for NatsBus {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn publish<'a>(&'a self, event: &'a OutboxEventModel) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let jetstream = self
+                .jetstream
+                .get_or_try_init(|| async {
+                    let client = async_nats::ConnectOptions::new()
+                        .connection_timeout(PUBLISH_TIMEOUT)
+                        .connect(self.url.as_str())
+                        .await?;
+
+                    Ok::<_, anyhow::Error>(async_nats::jetstream::new(client))
+                })
+                .await?;
+
+            let mut headers = async_nats::HeaderMap::new();
+            headers.insert(async_nats::header::NATS_MESSAGE_ID, event.id.to_string());
+
+            let ack = jetstream
+                .publish_with_headers(
+                    format!("{}.{}", self.subject, event.name),
+                    headers,
+                    serde_json::to_vec(&event.payload)?.into(),
+                )
+                .await?;
+
+            ack.await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Relays the committed outbox events to the subscribers of `/api/events` of the instance,
+/// those recorded by every instance as they all read the outbox. Events recorded before the
+/// relay started are left out
+pub struct EventRelay {
+    db: DatabaseConnection,
+    bus: Arc<EventBus>,
+    interval: Duration,
+    batch_size: u64,
+}
+
+impl EventRelay {
+    pub fn new(db: DatabaseConnection, bus: Arc<EventBus>, config: &OutboxConfig) -> Self {
+        Self {
+            db,
+            bus,
+            interval: Duration::from_secs(config.relay_interval),
+            batch_size: config.batch_size,
+        }
+    }
+
+    pub async fn run(self) {
+        let mut cursor = loop {
+            match OutboxRepository::new_with_connection(&self.db)
+                .last_id()
+                .await
+            {
+                Ok(id) => break id,
+                Err(err) => log::error!("Failed to read the last outbox event: {err}"),
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        };
+
+        loop {
+            match self.relay(cursor).await {
+                Ok(relayed) => cursor = relayed,
+                Err(err) => log::error!("Event relay failed: {err}"),
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Relays the events after `cursor` up to an id not committed yet, returning the id of
+    /// the last event relayed
+    async fn relay(&self, mut cursor: i32) -> Result<i32> {
+        let repository = OutboxRepository::new_with_connection(&self.db);
+        let timeout = chrono::Duration::from_std(GAP_TIMEOUT)?;
+
+        loop {
+            let recorded = repository.find_after(cursor, self.batch_size).await?;
+            let full = recorded.len() as u64 == self.batch_size;
+            let now = Utc::now();
+
+            for event in recorded {
+                // The missing ids may still be committed
+                if event.id != cursor + 1
+                    && event
+                        .created_at
+                        .is_some_and(|created_at| now - created_at < timeout)
+                {
+                    return Ok(cursor);
+                }
+
+                cursor = event.id;
+
+                match serde_json::from_value::<Event>(event.payload) {
+                    Ok(payload) => self.bus.publish(Event {
+                        name: event.name,
+                        ..payload
+                    }),
+                    Err(err) => log::error!("Failed to read outbox event {}: {err}", event.id),
+                }
+            }
+
+            if !full {
+                return Ok(cursor);
+            }
+        }
+    }
+}
+
+/// Delivers the outbox events in the order they were recorded, an event the bus refused
+/// holds back the later ones until it is published. Without a bus it only deletes the
+/// events past their retention
+pub struct OutboxPublisher {
+    db: DatabaseConnection,
+    bus: Option<Box<dyn MessageBus>>,
+    interval: Duration,
+    batch_size: u64,
+    retention: Duration,
+}
+
+impl OutboxPublisher {
+    pub fn new(
+        db: DatabaseConnection,
+        bus: Option<Box<dyn MessageBus>>,
+        config: &OutboxConfig,
+    ) -> Self {
+        Self {
+            db,
+            bus,
+            interval: Duration::from_secs(config.poll_interval),
+            batch_size: config.batch_size,
+            retention: Duration::from_secs(config.retention_days * 24 * 60 * 60),
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.publish_pending().await {
+                // Keep draining while a full batch went out
+                Ok(published) if published == self.batch_size => continue,
+                Ok(_) => {}
+                Err(err) => log::error!("Outbox publishing failed: {err}"),
+            }
+
+            if let Err(err) = self.delete_expired().await {
+                log::error!("Failed to delete the expired outbox events: {err}");
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Publishes the pending events of a batch, returning how many were published
+    async fn publish_pending(&self) -> Result<u64> {
+        let Some(bus) = &self.bus else {
+            return Ok(0);
+        };
+
+        let repository = OutboxRepository::new_with_connection(&self.db);
+        let mut published = 0;
+
+        for event in repository.find_pending(self.batch_size).await? {
+            let result = tokio::time::timeout(PUBLISH_TIMEOUT, bus.publish(&event))
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out")));
+
+            if let Err(err) = result {
+                repository.mark_failed(event.id, &err.to_string()).await?;
+
+                anyhow::bail!(
+                    "{} refused event {} ({}): {err}",
+                    bus.name(),
+                    event.id,
+                    event.name
+                );
+            }
+
+            repository.mark_published(event.id, Utc::now()).await?;
+            published += 1;
+        }
+
+        Ok(published)
+    }
+
+    async fn delete_expired(&self) -> Result<()> {
+        let cutoff = Utc::now() - chrono::Duration::from_std(self.retention)?;
+
+        let repository = OutboxRepository::new_with_connection(&self.db);
+
+        let deleted = match self.bus {
+            Some(_) => repository.delete_published_before(cutoff).await?,
+            None => repository.delete_recorded_before(cutoff).await?,
+        };
+
+        if deleted > 0 {
+            log::info!("Deleted {deleted} outbox events");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_events_are_published_in_order() {
+        use super::*;
+        use crate::db::models::{
+            Chain, TransactionActiveModel, TransactionStatus, UserActiveModel, WalletActiveModel,
+            WalletState,
+        };
+        use crate::db::repositories::{
+            StatusDetails, TransactionRepository, UserRepository, WalletRepository,
+        };
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// Records the published events, refusing them while `refuse` is set
+        #[derive(Default)]
+        struct FakeBus {
+            published: Mutex<Vec<String>>,
+            refuse: AtomicBool,
+        }
+
+        impl MessageBus for &'static FakeBus {
+            fn name(&self) -> &'static str {
+                "fake"
+            }
+
+            fn publish<'a>(&'a self, event: &'a OutboxEventModel) -> BoxFuture<'a, Result<()>> {
+                async move {
+                    if self.refuse.load(Ordering::SeqCst) {
+                        anyhow::bail!("Unavailable");
+                    }
+
+                    self.published.lock().unwrap().push(event.name.clone());
+                    Ok(())
+                }
+                .boxed()
+            }
+        }
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallets = WalletRepository::new_with_connection(&db);
+
+        let wallet = wallets
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Creating),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        wallets.activate(&wallet, None).await.unwrap();
+
+        let transactions = TransactionRepository::new_with_connection(&db);
+
+        let transaction = transactions
+            .create(TransactionActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                status: Set(TransactionStatus::Pending),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let transaction = transactions
+            .update_status(
+                &transaction,
+                TransactionStatus::Signing,
+                StatusDetails::default(),
+            )
+            .await
+            .unwrap();
+
+        // A refused status change records nothing
+        assert!(
+            transactions
+                .update_status(
+                    &transaction,
+                    TransactionStatus::Confirmed,
+                    StatusDetails::default()
+                )
+                .await
+                .is_err()
+        );
+
+        let bus: &'static FakeBus = Box::leak(Box::default());
+        bus.refuse.store(true, Ordering::SeqCst);
+
+        let config = OutboxConfig {
+            bus: None,
+            topic: "events".to_string(),
+            poll_interval: 1,
+            relay_interval: 1,
+            batch_size: 10,
+            retention_days: 0,
+        };
+
+        let publisher = OutboxPublisher::new(db.clone(), Some(Box::new(bus)), &config);
+
+        assert!(publisher.publish_pending().await.is_err());
+
+        let outbox = OutboxRepository::new_with_connection(&db);
+        let pending = outbox.find_pending(10).await.unwrap();
+
+        assert_eq!(pending.len(), 2);
+        assert_eq!((pending[0].attempts, pending[1].attempts), (1, 0));
+        assert_eq!(pending[0].payload["wallet_id"], wallet.id);
+
+        bus.refuse.store(false, Ordering::SeqCst);
+
+        assert_eq!(publisher.publish_pending().await.unwrap(), 2);
+        assert_eq!(
+            *bus.published.lock().unwrap(),
+            ["wallet.created", "transaction.signing"]
+        );
+        assert!(outbox.find_pending(10).await.unwrap().is_empty());
+
+        publisher.delete_expired().await.unwrap();
+
+        assert!(outbox.find_pending(10).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_only_committed_events_are_relayed() {
+        use super::*;
+        use crate::db::models::{
+            Chain, OutboxEventActiveModel, UserActiveModel, WalletActiveModel, WalletState,
+        };
+        use crate::db::repositories::{UserRepository, WalletRepository};
+        use sea_orm::{ActiveModelTrait, ConnectOptions, Database, Set, TransactionTrait};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set("namespace".to_string()),
+                state: Set(WalletState::Creating),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let bus = Arc::new(EventBus::new());
+        let mut subscription = bus.subscribe(user.id);
+
+        let config = OutboxConfig {
+            bus: None,
+            topic: "events".to_string(),
+            poll_interval: 1,
+            relay_interval: 1,
+            batch_size: 10,
+            retention_days: 0,
+        };
+
+        let relay = EventRelay::new(db.clone(), bus.clone(), &config);
+        let cursor = OutboxRepository::new_with_connection(&db)
+            .last_id()
+            .await
+            .unwrap();
+
+        // The change of a transaction that rolled back is never relayed
+        let txn = db.begin().await.unwrap();
+        WalletRepository::new_with_transaction(&txn)
+            .activate(&wallet, None)
+            .await
+            .unwrap();
+        txn.rollback().await.unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .activate(&wallet, None)
+            .await
+            .unwrap();
+        WalletRepository::new_with_connection(&db)
+            .transition(&wallet, WalletState::Frozen)
+            .await
+            .unwrap();
+
+        let cursor = relay.relay(cursor).await.unwrap();
+
+        let names = [
+            subscription.recv().await.unwrap().name,
+            subscription.recv().await.unwrap().name,
+        ];
+        assert_eq!(names, ["wallet.created", "wallet.frozen"]);
+
+        // An id left unused, by a transaction still open or rolled back, is waited for
+        let event = crate::events::Event::wallet(&wallet, WalletState::Active);
+        let recorded = OutboxEventActiveModel {
+            id: Set(cursor + 2),
+            name: Set("wallet.archiving".to_string()),
+            payload: Set(serde_json::to_value(&event).unwrap()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(relay.relay(cursor).await.unwrap(), cursor);
+
+        // Until the events after it are old enough
+        let mut aged: OutboxEventActiveModel = recorded.into();
+        aged.created_at = Set(Some(Utc::now() - chrono::Duration::minutes(1)));
+        aged.update(&db).await.unwrap();
+
+        assert_eq!(relay.relay(cursor).await.unwrap(), cursor + 2);
+        assert_eq!(subscription.recv().await.unwrap().name, "wallet.archiving");
+    }
+}
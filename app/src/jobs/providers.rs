@@ -0,0 +1,45 @@
+use futures::future::join_all;
+use sea_orm::Iterable;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::chains::ChainRegistry;
+use crate::db::models::Chain;
+
+/// Probes the RPC endpoints of every EVM chain, bringing endpoints the pools marked down
+/// back into rotation once they answer again
+pub struct ProviderMonitor {
+    chains: Arc<ChainRegistry>,
+    interval: Duration,
+}
+
+impl ProviderMonitor {
+    pub fn new(chains: Arc<ChainRegistry>, interval: Duration) -> Self {
+        Self { chains, interval }
+    }
+
+    pub async fn run(self) {
+        loop {
+            self.check().await;
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn check(&self) {
+        let probes = Chain::iter().filter_map(|chain| {
+            let pool = self.chains.get(&chain)?.provider.as_ref()?;
+
+            Some(async move {
+                if pool.check().await == 0 {
+                    log::error!(
+                        "None of the {} RPC endpoints of {chain:?} is answering",
+                        pool.endpoint_count()
+                    );
+                }
+            })
+        });
+
+        join_all(probes).await;
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::models::{WalletModel, WalletState};
+use crate::db::repositories::WalletRepository;
+use crate::participants::{ParticipantPool, purge_shares};
+
+/// Payload of a `wallet.purge` job, queued when a failed keygen deleted its wallet
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletPurge {
+    pub wallet_id: i32,
+}
+
+impl WalletPurge {
+    /// Deletes the shares of the `deleting` wallet, failing while a participant still holds
+    /// one so the job is retried
+    pub async fn run(
+        &self,
+        db: &DatabaseConnection,
+        participants: &dyn ParticipantPool,
+    ) -> anyhow::Result<()> {
+        let repository = WalletRepository::new_with_connection(db);
+
+        let Some(wallet) = repository.find_by_id(self.wallet_id).await? else {
+            anyhow::bail!("Wallet {} is gone", self.wallet_id);
+        };
+
+        if wallet.state != WalletState::Deleting {
+            log::info!(
+                "Wallet {} is {:?}, nothing to purge",
+                wallet.id,
+                wallet.state
+            );
+            return Ok(());
+        }
+
+        if !purge_shares(participants, wallet.id).await {
+            anyhow::bail!("A participant still holds a share of wallet {}", wallet.id);
+        }
+
+        repository.transition(&wallet, WalletState::Deleted).await?;
+
+        log::info!("Purged shares of wallet {}", wallet.id);
+
+        Ok(())
+    }
+}
+
+/// Purges the participant shares of wallets archived longer than the retention window,
+/// and retries deletions that previously failed on some participant
+pub struct WalletPurger {
+    db: DatabaseConnection,
+    participants: Arc<dyn ParticipantPool>,
+    retention: chrono::Duration,
+    interval: Duration,
+}
+
+impl WalletPurger {
+    pub fn new(
+        db: DatabaseConnection,
+        participants: Arc<dyn ParticipantPool>,
+        retention: chrono::Duration,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            db,
+            participants,
+            retention,
+            interval,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.purge().await {
+                log::error!("Wallet purge failed: {err}");
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let repository = WalletRepository::new_with_connection(&self.db);
+
+        let cutoff = Utc::now()
+            .checked_sub_signed(self.retention)
+            .ok_or_else(|| anyhow::anyhow!("Retention of {} is out of range", self.retention))?;
+
+        let mut wallets = repository.find_archived_before(cutoff).await?;
+        wallets.extend(repository.find_by_state(WalletState::Deleting).await?);
+
+        for wallet in wallets {
+            if let Err(err) = self.purge_wallet(&repository, wallet).await {
+                log::error!("Failed to purge wallet: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn purge_wallet(
+        &self,
+        repository: &WalletRepository<'_>,
+        wallet: WalletModel,
+    ) -> anyhow::Result<()> {
+        let wallet = match wallet.state {
+            WalletState::Deleting => wallet,
+            _ => {
+                repository
+                    .transition(&wallet, WalletState::Deleting)
+                    .await?
+            }
+        };
+
+        if !purge_shares(self.participants.as_ref(), wallet.id).await {
+            log::warn!("Wallet {} left in deleting, retrying later", wallet.id);
+            return Ok(());
+        }
+
+        repository.transition(&wallet, WalletState::Deleted).await?;
+
+        log::info!("Purged shares of wallet {}", wallet.id);
+
+        Ok(())
+    }
+}
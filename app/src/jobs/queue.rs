@@ -0,0 +1,235 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::operations::KeygenRetry;
+use super::purge::WalletPurge;
+use crate::config::app_config::JobsConfig;
+use crate::db::models::{JobKind, JobModel};
+use crate::db::repositories::JobRepository;
+use crate::participants::ParticipantPool;
+
+static POLICY: OnceCell<JobPolicy> = OnceCell::new();
+
+/// Attempts of the queued jobs and the delay between them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for JobPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl From<&JobsConfig> for JobPolicy {
+    fn from(config: &JobsConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            backoff: Duration::from_secs(config.backoff),
+            max_backoff: Duration::from_secs(config.max_backoff),
+        }
+    }
+}
+
+impl JobPolicy {
+    /// Sets the policy of every job queued from now on, the default one applies until then
+    pub fn install(self) {
+        if POLICY.set(self).is_err() {
+            log::warn!("Job policy is already installed");
+        }
+    }
+
+    pub fn current() -> Self {
+        POLICY.get().copied().unwrap_or_default()
+    }
+
+    /// Delay before the retry following `attempt`
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    /// When the failed attempt of the job runs again, `None` once it used all its attempts
+    fn retry_at(&self, job: &JobModel, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if job.attempts >= job.max_attempts {
+            return None;
+        }
+
+        let delay = chrono::Duration::from_std(self.delay(job.attempts.max(1) as u32)).ok()?;
+
+        Some(now + delay)
+    }
+}
+
+/// Queues a job for the job runners, with the attempts of the current policy
+pub async fn enqueue(
+    repository: &JobRepository<'_>,
+    kind: JobKind,
+    payload: &impl Serialize,
+) -> anyhow::Result<JobModel> {
+    let max_attempts = JobPolicy::current().max_attempts.min(i32::MAX as u32) as i32;
+
+    let job = repository
+        .enqueue(kind, serde_json::to_value(payload)?, max_attempts)
+        .await?;
+
+    log::info!("Queued job {} ({:?})", job.id, job.kind);
+
+    Ok(job)
+}
+
+/// Runs the queued jobs one at a time. Every instance runs one, a job is leased by a single
+/// runner and taken over by another once the lease expires, e.g. when its runner crashed
+pub struct JobRunner {
+    db: DatabaseConnection,
+    participants: Arc<dyn ParticipantPool>,
+    owner: String,
+    interval: Duration,
+    lease: Duration,
+}
+
+impl JobRunner {
+    pub fn new(
+        db: DatabaseConnection,
+        participants: Arc<dyn ParticipantPool>,
+        interval: Duration,
+        lease: Duration,
+    ) -> Self {
+        Self {
+            db,
+            participants,
+            owner: Uuid::new_v4().simple().to_string(),
+            interval,
+            lease,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.run_next().await {
+                // Keep draining while jobs are due
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(err) => log::error!("Job run failed: {err}"),
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Leases and runs the next due job, `false` when none is due
+    async fn run_next(&self) -> anyhow::Result<bool> {
+        let repository = JobRepository::new_with_connection(&self.db);
+
+        let Some(job) = repository.lease(&self.owner, self.lease).await? else {
+            return Ok(false);
+        };
+
+        // The lease of the last attempt expired, its runner is presumed dead
+        let result = if job.attempts > job.max_attempts {
+            Err(anyhow::anyhow!("Lease of the last attempt expired"))
+        } else {
+            self.execute(&job).await
+        };
+
+        match result {
+            Ok(()) => {
+                repository.complete(&job).await?;
+                log::info!("Job {} ({:?}) succeeded", job.id, job.kind);
+            }
+            Err(err) => {
+                let retry_at = JobPolicy::current().retry_at(&job, Utc::now());
+
+                match retry_at {
+                    Some(retry_at) => log::warn!(
+                        "Job {} ({:?}) failed attempt {}, retrying at {retry_at}: {err}",
+                        job.id,
+                        job.kind,
+                        job.attempts
+                    ),
+                    None => log::error!(
+                        "Job {} ({:?}) failed after {} attempts: {err}",
+                        job.id,
+                        job.kind,
+                        job.attempts
+                    ),
+                }
+
+                repository.fail(&job, &err.to_string(), retry_at).await?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn execute(&self, job: &JobModel) -> anyhow::Result<()> {
+        let payload = job.payload.clone();
+
+        match job.kind {
+            JobKind::OperationKeygen => {
+                let retry: KeygenRetry = serde_json::from_value(payload)?;
+                retry.run(&self.db, self.participants.as_ref()).await
+            }
+            JobKind::WalletPurge => {
+                let purge: WalletPurge = serde_json::from_value(payload)?;
+                purge.run(&self.db, self.participants.as_ref()).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(attempts: i32, max_attempts: i32) -> JobModel {
+        JobModel {
+            id: 1,
+            kind: JobKind::WalletPurge,
+            payload: serde_json::json!({ "wallet_id": 1 }),
+            state: crate::db::models::JobState::Running,
+            attempts,
+            max_attempts,
+            run_at: Utc::now(),
+            leased_until: None,
+            lease_owner: None,
+            last_error: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_the_cap() {
+        let policy = JobPolicy::default();
+
+        assert_eq!(policy.delay(1), Duration::from_secs(30));
+        assert_eq!(policy.delay(3), Duration::from_secs(120));
+        assert_eq!(policy.delay(20), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_retry_at_stops_after_the_last_attempt() {
+        let policy = JobPolicy::default();
+        let now = Utc::now();
+
+        assert_eq!(
+            policy.retry_at(&job(2, 3), now),
+            Some(now + chrono::Duration::seconds(60))
+        );
+        assert_eq!(policy.retry_at(&job(3, 3), now), None);
+    }
+}
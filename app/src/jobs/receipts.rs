@@ -0,0 +1,479 @@
+use alloy::primitives::B256;
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use anyhow::Result;
+use futures::StreamExt;
+use sea_orm::{DatabaseConnection, Iterable};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::chains::{ChainRegistry, SolanaClient};
+use crate::db::models::{Chain, TransactionModel, TransactionStatus};
+use crate::db::repositories::{Inclusion, StatusDetails, TransactionRepository};
+
+/// Block a receipt places the transaction in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Included {
+    block_number: u64,
+    block_hash: String,
+    success: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Progress {
+    /// Not in any block, the node may have dropped it
+    Missing,
+    /// Included, but not yet buried under the confirmation depth
+    Confirming(Inclusion),
+    /// Buried under the confirmation depth, `success` is the receipt status
+    Final { inclusion: Inclusion, success: bool },
+}
+
+fn progress(included: Option<Included>, head: u64, depth: u64) -> Progress {
+    let Some(included) = included else {
+        return Progress::Missing;
+    };
+
+    // The including block is the first confirmation
+    let confirmations = head.saturating_sub(included.block_number) + 1;
+
+    let inclusion = Inclusion {
+        block_number: included.block_number,
+        block_hash: included.block_hash,
+        confirmations,
+    };
+
+    if confirmations >= depth {
+        Progress::Final {
+            inclusion,
+            success: included.success,
+        }
+    } else {
+        Progress::Confirming(inclusion)
+    }
+}
+
+/// Chains the interval poll covers, the configured ones without a live new heads
+/// subscription: chains without a WebSocket endpoint and those whose socket dropped
+fn polled_chains<'a>(
+    configured: impl IntoIterator<Item = &'a Chain>,
+    subscribed: &HashSet<Chain>,
+) -> Vec<Chain> {
+    configured
+        .into_iter()
+        .filter(|chain| !subscribed.contains(chain))
+        .cloned()
+        .collect()
+}
+
+/// Wait before subscribing again to the new heads of a chain after the socket dropped
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Follows broadcast transactions until they are buried under their chain's confirmation
+/// depth, re-checking inclusion on every poll so reorged transactions go back to waiting
+///
+/// Chains with a WebSocket endpoint are checked on every new head instead of the interval,
+/// they fall back to the interval while the subscription is down.
+pub struct ReceiptPoller {
+    db: DatabaseConnection,
+    chains: Arc<ChainRegistry>,
+    interval: Duration,
+    /// Chains whose new heads subscription is live
+    subscribed: Mutex<HashSet<Chain>>,
+}
+
+impl ReceiptPoller {
+    pub fn new(db: DatabaseConnection, chains: Arc<ChainRegistry>, interval: Duration) -> Self {
+        Self {
+            db,
+            chains,
+            interval,
+            subscribed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub async fn run(self) {
+        let poller = Arc::new(self);
+
+        for chain in Chain::iter() {
+            let Some(url) = poller
+                .chains
+                .get(&chain)
+                .and_then(|network| network.config.ws_url.clone())
+            else {
+                continue;
+            };
+
+            actix_web::rt::spawn(poller.clone().follow_heads(chain, url));
+        }
+
+        loop {
+            if let Err(err) = poller.poll(&poller.polled_chains()).await {
+                log::error!("Receipt polling failed: {err}");
+            }
+
+            actix_web::rt::time::sleep(poller.interval).await;
+        }
+    }
+
+    fn polled_chains(&self) -> Vec<Chain> {
+        match self.subscribed.lock() {
+            Ok(subscribed) => polled_chains(self.chains.chains(), &subscribed),
+            Err(_) => polled_chains(self.chains.chains(), &HashSet::new()),
+        }
+    }
+
+    fn set_subscribed(&self, chain: &Chain, live: bool) {
+        if let Ok(mut subscribed) = self.subscribed.lock() {
+            if live {
+                subscribed.insert(chain.clone());
+            } else {
+                subscribed.remove(chain);
+            }
+        }
+    }
+
+    /// Keeps a new heads subscription open, reconnecting whenever the socket drops
+    async fn follow_heads(self: Arc<Self>, chain: Chain, url: String) {
+        loop {
+            match self.subscribe_heads(&chain, &url).await {
+                Ok(()) => log::warn!("New heads subscription of {chain:?} ended"),
+                Err(err) => log::warn!("New heads subscription of {chain:?} failed: {err}"),
+            }
+
+            self.set_subscribed(&chain, false);
+
+            actix_web::rt::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn subscribe_heads(&self, chain: &Chain, url: &str) -> Result<()> {
+        let provider = ProviderBuilder::new()
+            .connect_ws(WsConnect::new(url))
+            .await?;
+
+        let mut heads = provider.subscribe_blocks().await?.into_stream();
+
+        self.set_subscribed(chain, true);
+
+        log::info!("Tracking {chain:?} receipts on new heads");
+
+        while heads.next().await.is_some() {
+            if let Err(err) = self.poll(std::slice::from_ref(chain)).await {
+                log::error!("Receipt polling of {chain:?} failed: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn poll(&self, chains: &[Chain]) -> Result<()> {
+        if chains.is_empty() {
+            return Ok(());
+        }
+
+        let repository = TransactionRepository::new_with_connection(&self.db);
+
+        for transaction in repository
+            .find_by_status_on(TransactionStatus::Broadcast, chains)
+            .await?
+        {
+            let id = transaction.id;
+
+            if let Err(err) = self.track(&repository, transaction).await {
+                log::error!("Failed to track transaction {id}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn track(
+        &self,
+        repository: &TransactionRepository<'_>,
+        transaction: TransactionModel,
+    ) -> Result<()> {
+        let (Some(chain), Some(tx_hash)) = (&transaction.chain, &transaction.tx_hash) else {
+            return Ok(());
+        };
+
+        let Some(network) = self.chains.get(chain) else {
+            return Ok(());
+        };
+
+        if let Some(client) = &network.solana {
+            return track_signature(
+                repository,
+                client,
+                &transaction,
+                tx_hash,
+                network.config.confirmations,
+            )
+            .await;
+        }
+
+        let Some(provider) = &network.provider else {
+            return Ok(());
+        };
+
+        let hash: B256 = tx_hash.parse()?;
+
+        let receipt = provider.get_transaction_receipt(hash).await?;
+        let head = provider.get_block_number().await?;
+
+        let included = receipt.and_then(|receipt| {
+            Some(Included {
+                block_number: receipt.block_number?,
+                block_hash: receipt.block_hash?.to_string(),
+                success: receipt.status(),
+            })
+        });
+
+        match progress(included, head, network.config.confirmations) {
+            Progress::Missing => {
+                if let Some(block_hash) = &transaction.block_hash {
+                    log::warn!(
+                        "Transaction {} was dropped from block {block_hash} by a reorg",
+                        transaction.id
+                    );
+
+                    repository.update_inclusion(&transaction, None).await?;
+                }
+
+                rebroadcast(provider.as_ref(), hash, &transaction).await;
+            }
+            Progress::Confirming(inclusion) => {
+                let moved = transaction
+                    .block_hash
+                    .as_ref()
+                    .is_some_and(|block_hash| *block_hash != inclusion.block_hash);
+
+                if moved {
+                    log::warn!(
+                        "Transaction {} was reorged into block {}",
+                        transaction.id,
+                        inclusion.block_hash
+                    );
+                }
+
+                if moved || transaction.confirmations != Some(inclusion.confirmations as i64) {
+                    repository
+                        .update_inclusion(&transaction, Some(inclusion))
+                        .await?;
+                }
+            }
+            Progress::Final { inclusion, success } => {
+                let transaction = repository
+                    .update_inclusion(&transaction, Some(inclusion))
+                    .await?;
+
+                fail_replaced(repository, &transaction).await?;
+
+                if success {
+                    repository
+                        .update_status(
+                            &transaction,
+                            TransactionStatus::Confirmed,
+                            StatusDetails::default(),
+                        )
+                        .await?;
+                } else {
+                    repository
+                        .update_status(
+                            &transaction,
+                            TransactionStatus::Failed,
+                            StatusDetails {
+                                error: Some("Transaction reverted".to_string()),
+                                ..Default::default()
+                            },
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails the other transactions sharing the nonce of a mined one, of a stuck transaction
+/// and its replacements only one can ever be mined
+async fn fail_replaced(
+    repository: &TransactionRepository<'_>,
+    transaction: &TransactionModel,
+) -> Result<()> {
+    let Some(nonce) = transaction.nonce else {
+        return Ok(());
+    };
+
+    for other in repository
+        .find_by_nonce(transaction.wallet_id, transaction.account_id, nonce)
+        .await?
+    {
+        if other.id == transaction.id || other.status.is_terminal() {
+            continue;
+        }
+
+        repository
+            .update_status(
+                &other,
+                TransactionStatus::Failed,
+                StatusDetails {
+                    error: Some(format!("Nonce was used by transaction {}", transaction.id)),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Finalizes a Solana transaction once enough blocks are confirmed on top of its slot,
+/// unknown signatures are left alone as their blockhash expires the transaction anyway
+async fn track_signature(
+    repository: &TransactionRepository<'_>,
+    client: &SolanaClient,
+    transaction: &TransactionModel,
+    tx_hash: &str,
+    depth: u64,
+) -> Result<()> {
+    let Some(status) = client.signature_status(tx_hash).await? else {
+        return Ok(());
+    };
+
+    if let Some(err) = status.err {
+        repository
+            .update_status(
+                transaction,
+                TransactionStatus::Failed,
+                StatusDetails {
+                    error: Some(format!("Transaction failed: {err}")),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        return Ok(());
+    }
+
+    // A rooted slot reports no confirmations, it can no longer be rolled back
+    let finalized = status
+        .confirmations
+        .is_none_or(|confirmations| confirmations + 1 >= depth);
+
+    if finalized {
+        log::info!(
+            "Transaction {} finalized in slot {}",
+            transaction.id,
+            status.slot
+        );
+
+        repository
+            .update_status(
+                transaction,
+                TransactionStatus::Confirmed,
+                StatusDetails::default(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Sends the signed transaction again when the node no longer knows it, e.g. after a
+/// reorg or a mempool eviction
+async fn rebroadcast(
+    provider: &(dyn Provider + Send + Sync),
+    hash: B256,
+    transaction: &TransactionModel,
+) {
+    if let Ok(Some(_)) = provider.get_transaction_by_hash(hash).await {
+        return;
+    }
+
+    let Some(raw_tx) = transaction
+        .raw_tx
+        .as_deref()
+        .and_then(|raw_tx| hex::decode(raw_tx.trim_start_matches("0x")).ok())
+    else {
+        return;
+    };
+
+    match provider.send_raw_transaction(&raw_tx).await {
+        Ok(_) => log::info!("Rebroadcast transaction {}", transaction.id),
+        Err(err) => log::warn!(
+            "Failed to rebroadcast transaction {}: {err}",
+            transaction.id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn included(block_number: u64, success: bool) -> Option<Included> {
+        Some(Included {
+            block_number,
+            block_hash: format!("0x{block_number:064x}"),
+            success,
+        })
+    }
+
+    #[test]
+    fn test_progress_counts_confirmations() {
+        match progress(included(100, true), 104, 12) {
+            Progress::Confirming(inclusion) => assert_eq!(inclusion.confirmations, 5),
+            other => panic!("unexpected progress {other:?}"),
+        }
+
+        assert!(matches!(
+            progress(included(100, true), 111, 12),
+            Progress::Final { success: true, .. }
+        ));
+    }
+
+    #[test]
+    fn test_progress_reverted_and_missing() {
+        assert!(matches!(
+            progress(included(100, false), 100, 1),
+            Progress::Final { success: false, .. }
+        ));
+        assert_eq!(progress(None, 100, 1), Progress::Missing);
+    }
+
+    #[test]
+    fn test_polled_chains_skip_live_subscriptions() {
+        let configured = [Chain::Ethereum, Chain::Base, Chain::Solana];
+        let subscribed = HashSet::from([Chain::Ethereum]);
+
+        assert_eq!(
+            polled_chains(&configured, &subscribed),
+            vec![Chain::Base, Chain::Solana]
+        );
+    }
+
+    #[test]
+    fn test_polled_chains_fall_back_when_subscriptions_drop() {
+        let configured = [Chain::Ethereum, Chain::Base];
+
+        assert_eq!(
+            polled_chains(&configured, &HashSet::new()),
+            configured.to_vec()
+        );
+        assert_eq!(
+            polled_chains(&configured, &HashSet::from([Chain::Ethereum, Chain::Base])),
+            Vec::<Chain>::new()
+        );
+    }
+
+    #[test]
+    fn test_progress_lagging_node() {
+        // Receipts can come from a node ahead of the one answering the block number
+        match progress(included(100, true), 99, 12) {
+            Progress::Confirming(inclusion) => assert_eq!(inclusion.confirmations, 1),
+            other => panic!("unexpected progress {other:?}"),
+        }
+    }
+}
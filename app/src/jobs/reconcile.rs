@@ -0,0 +1,308 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::db::models::{WalletModel, WalletState};
+use crate::db::repositories::WalletRepository;
+use crate::participants::{
+    ParticipantPool, has_shares, keygen_address, purge_shares, run_keygen, verify_shares,
+    wallet_infos,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileAction {
+    /// Keygen had completed on every participant, the wallet was activated
+    Activated,
+    /// A new keygen succeeded and the wallet was activated
+    KeygenRetried,
+    /// The leftover shares were deleted and the wallet tombstoned
+    CleanedUp,
+    /// Some shares could not be deleted, the purge job retries them
+    CleanupPending,
+    /// A usable wallet is missing shares, it needs an operator (e.g. a backup import)
+    MissingShares,
+    /// The participants hold shares of different keys or of another key than the wallet's
+    InconsistentShares,
+    /// Some participant could not be asked, nothing was changed
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciledWallet {
+    pub wallet_id: i32,
+    pub state: WalletState,
+    /// Share presence per participant, `None` when the participant could not be asked
+    pub shares: Vec<Option<bool>>,
+    pub action: ReconcileAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciliationReport {
+    pub checked_at: DateTime<Utc>,
+    pub checked: usize,
+    pub wallets: Vec<ReconciledWallet>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Plan {
+    Activate,
+    RetryKeygen,
+    CleanUp,
+    Report(ReconcileAction),
+}
+
+fn plan(state: WalletState, shares: &[Option<bool>], retry_keygen: bool) -> Option<Plan> {
+    if shares.iter().any(Option::is_none) {
+        return Some(Plan::Report(ReconcileAction::Unreachable));
+    }
+
+    let complete = shares.iter().all(|share| *share == Some(true));
+
+    match state {
+        WalletState::Creating if complete => Some(Plan::Activate),
+        WalletState::Creating if retry_keygen => Some(Plan::RetryKeygen),
+        WalletState::Creating => Some(Plan::CleanUp),
+        _ if complete => None,
+        _ => Some(Plan::Report(ReconcileAction::MissingShares)),
+    }
+}
+
+/// Finds wallets whose shares are not held by every participant, finishing or cleaning up
+/// interrupted keygens and reporting usable wallets that lost a share
+pub struct Reconciler {
+    db: DatabaseConnection,
+    participants: Arc<dyn ParticipantPool>,
+    interval: Duration,
+    grace_period: Duration,
+    retry_keygen: bool,
+    report: RwLock<Option<ReconciliationReport>>,
+}
+
+impl Reconciler {
+    pub fn new(
+        db: DatabaseConnection,
+        participants: Arc<dyn ParticipantPool>,
+        interval: Duration,
+        grace_period: Duration,
+        retry_keygen: bool,
+    ) -> Self {
+        Self {
+            db,
+            participants,
+            interval,
+            grace_period,
+            retry_keygen,
+            report: RwLock::new(None),
+        }
+    }
+
+    /// Last completed run, `None` until the first run finishes
+    pub fn report(&self) -> Option<ReconciliationReport> {
+        self.report
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            match self.reconcile().await {
+                Ok(report) => {
+                    *self.report.write().unwrap_or_else(|e| e.into_inner()) = Some(report);
+                }
+                Err(err) => log::error!("Wallet reconciliation failed: {err}"),
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn reconcile(&self) -> anyhow::Result<ReconciliationReport> {
+        let repository = WalletRepository::new_with_connection(&self.db);
+
+        // Younger `creating` wallets may still have their keygen running
+        let cutoff = Utc::now() - chrono::Duration::from_std(self.grace_period)?;
+
+        let mut wallets: Vec<WalletModel> = repository
+            .find_by_state(WalletState::Creating)
+            .await?
+            .into_iter()
+            .filter(|wallet| wallet.created_at.is_some_and(|at| at < cutoff))
+            .collect();
+
+        for state in [
+            WalletState::Active,
+            WalletState::Frozen,
+            WalletState::Archiving,
+        ] {
+            wallets.extend(repository.find_by_state(state).await?);
+        }
+
+        let checked_at = Utc::now();
+        let checked = wallets.len();
+        let mut reconciled = Vec::new();
+
+        for wallet in wallets {
+            match self.reconcile_wallet(&repository, wallet).await {
+                Ok(Some(entry)) => reconciled.push(entry),
+                Ok(None) => {}
+                Err(err) => log::error!("Failed to reconcile wallet: {err}"),
+            }
+        }
+
+        log::info!(
+            "Reconciled {checked} wallets, {} need attention",
+            reconciled.len()
+        );
+
+        Ok(ReconciliationReport {
+            checked_at,
+            checked,
+            wallets: reconciled,
+        })
+    }
+
+    async fn reconcile_wallet(
+        &self,
+        repository: &WalletRepository<'_>,
+        wallet: WalletModel,
+    ) -> anyhow::Result<Option<ReconciledWallet>> {
+        let shares: Vec<Option<bool>> = has_shares(self.participants.as_ref(), &wallet)
+            .await
+            .into_iter()
+            .map(Result::ok)
+            .collect();
+
+        let Some(plan) = plan(wallet.state, &shares, self.retry_keygen) else {
+            return Ok(self.verify_wallet(wallet, shares).await);
+        };
+
+        let (wallet, action) = match plan {
+            Plan::Activate => (
+                repository.transition(&wallet, WalletState::Active).await?,
+                ReconcileAction::Activated,
+            ),
+            // No device is around to take part in the keygen of a co-signed wallet
+            Plan::RetryKeygen if wallet.cosigner => self.clean_up(repository, wallet).await?,
+            Plan::RetryKeygen => {
+                let results = run_keygen(self.participants.as_ref(), &wallet).await;
+
+                let address = results
+                    .iter()
+                    .all(|res| res.is_ok())
+                    .then(|| keygen_address(&wallet.chain, wallet.address_type, &results).ok())
+                    .flatten();
+
+                match address {
+                    Some(address) => (
+                        repository.activate(&wallet, address).await?,
+                        ReconcileAction::KeygenRetried,
+                    ),
+                    None => self.clean_up(repository, wallet).await?,
+                }
+            }
+            Plan::CleanUp => self.clean_up(repository, wallet).await?,
+            Plan::Report(action) => (wallet, action),
+        };
+
+        log::warn!("Reconciled wallet {}: {action:?}", wallet.id);
+
+        Ok(Some(ReconciledWallet {
+            wallet_id: wallet.id,
+            state: wallet.state,
+            shares,
+            action,
+        }))
+    }
+
+    /// Compares the keys of the shares of a complete wallet, a failed lookup leaves the
+    /// wallet unchecked until the next run
+    async fn verify_wallet(
+        &self,
+        wallet: WalletModel,
+        shares: Vec<Option<bool>>,
+    ) -> Option<ReconciledWallet> {
+        let infos = wallet_infos(self.participants.as_ref(), &wallet, &[])
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        let err = verify_shares(&wallet, &infos).err()?;
+
+        log::error!("Wallet {} has inconsistent shares: {err}", wallet.id);
+
+        Some(ReconciledWallet {
+            wallet_id: wallet.id,
+            state: wallet.state,
+            shares,
+            action: ReconcileAction::InconsistentShares,
+        })
+    }
+
+    async fn clean_up(
+        &self,
+        repository: &WalletRepository<'_>,
+        wallet: WalletModel,
+    ) -> anyhow::Result<(WalletModel, ReconcileAction)> {
+        let wallet = repository
+            .transition(&wallet, WalletState::Deleting)
+            .await?;
+
+        if !purge_shares(self.participants.as_ref(), wallet.id).await {
+            return Ok((wallet, ReconcileAction::CleanupPending));
+        }
+
+        let wallet = repository.transition(&wallet, WalletState::Deleted).await?;
+
+        Ok((wallet, ReconcileAction::CleanedUp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_creating() {
+        let complete = [Some(true); 3];
+        let partial = [Some(true), Some(false), Some(true)];
+
+        assert_eq!(
+            plan(WalletState::Creating, &complete, false),
+            Some(Plan::Activate)
+        );
+        assert_eq!(
+            plan(WalletState::Creating, &partial, false),
+            Some(Plan::CleanUp)
+        );
+        assert_eq!(
+            plan(WalletState::Creating, &partial, true),
+            Some(Plan::RetryKeygen)
+        );
+    }
+
+    #[test]
+    fn test_plan_usable_wallets_are_only_reported() {
+        let partial = [Some(true), Some(true), Some(false)];
+
+        assert_eq!(plan(WalletState::Active, &[Some(true); 3], true), None);
+        assert_eq!(
+            plan(WalletState::Frozen, &partial, true),
+            Some(Plan::Report(ReconcileAction::MissingShares))
+        );
+    }
+
+    #[test]
+    fn test_plan_unreachable_participant_changes_nothing() {
+        let shares = [Some(false), None, Some(false)];
+
+        assert_eq!(
+            plan(WalletState::Creating, &shares, false),
+            Some(Plan::Report(ReconcileAction::Unreachable))
+        );
+    }
+}
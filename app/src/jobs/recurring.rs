@@ -0,0 +1,328 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use croner::parser::{CronParser, Seconds, Year};
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::{TransactionRequest, send_wallet_tx};
+use crate::chains::ChainRegistry;
+use crate::config::app_config::{QuotaConfig, RecurringConfig};
+use crate::db::models::RecurringPaymentModel;
+use crate::db::repositories::{RecurringPaymentRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+
+/// Most payments run per poll, the rest wait for the next one
+const BATCH_SIZE: u64 = 100;
+
+/// Five field cron expression, minute to day of week, without seconds or years
+pub fn parse_schedule(expression: &str) -> Result<Cron> {
+    if expression.split_whitespace().count() != 5 {
+        return Err(anyhow!("Schedule must have five fields"));
+    }
+
+    CronParser::builder()
+        .seconds(Seconds::Disallowed)
+        .year(Year::Disallowed)
+        .build()
+        .parse(expression)
+        .map_err(|err| anyhow!("Invalid schedule: {err}"))
+}
+
+/// First occurrence of the schedule strictly after `after`, in UTC
+pub fn next_run(schedule: &Cron, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    schedule
+        .find_next_occurrence(&after, false)
+        .map_err(|err| anyhow!("Schedule has no next occurrence: {err}"))
+}
+
+/// Transaction of a run of the payment, the run tagged in its metadata
+fn transaction_request(payment: &RecurringPaymentModel) -> Result<TransactionRequest> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "to": payment.recipient,
+        "value": payment.value,
+        "memo": payment.memo,
+        "metadata": {
+            "recurring_payment_id": payment.id,
+            "run": payment.runs + 1,
+        },
+    }))?)
+}
+
+/// Sends the transfers of the recurring payments as they fall due. A run is claimed by
+/// moving the payment to its next occurrence after now, so runs missed while the service was
+/// down are skipped rather than sent in a burst
+pub struct RecurringDispatcher {
+    db: DatabaseConnection,
+    chains: Arc<ChainRegistry>,
+    participants: Arc<dyn ParticipantPool>,
+    screener: Arc<Screener>,
+    quotas: QuotaConfig,
+    interval: Duration,
+    max_failures: i32,
+}
+
+impl RecurringDispatcher {
+    pub fn new(
+        db: DatabaseConnection,
+        chains: Arc<ChainRegistry>,
+        participants: Arc<dyn ParticipantPool>,
+        screener: Arc<Screener>,
+        quotas: QuotaConfig,
+        config: &RecurringConfig,
+    ) -> Self {
+        Self {
+            db,
+            chains,
+            participants,
+            screener,
+            quotas,
+            interval: Duration::from_secs(config.poll_interval),
+            max_failures: config.max_failures,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.dispatch_due().await {
+                log::error!("Recurring payment dispatch failed: {err}");
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn dispatch_due(&self) -> Result<()> {
+        let repository = RecurringPaymentRepository::new_with_connection(&self.db);
+
+        let now = Utc::now();
+
+        for payment in repository.find_due(now, BATCH_SIZE).await? {
+            let id = payment.id;
+
+            if let Err(err) = self.dispatch(&repository, payment, now).await {
+                log::error!("Failed to run recurring payment {id}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn dispatch(
+        &self,
+        repository: &RecurringPaymentRepository<'_>,
+        payment: RecurringPaymentModel,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let next = next_run(&parse_schedule(&payment.schedule)?, now)?;
+
+        if !repository.claim(&payment, next).await? {
+            return Ok(());
+        }
+
+        let payment = RecurringPaymentModel {
+            next_run_at: Some(next),
+            ..payment
+        };
+
+        match self.send(&payment).await {
+            Ok(transaction_id) => {
+                repository.record_run(&payment, transaction_id, now).await?;
+
+                log::info!(
+                    "Recurring payment {} sent transaction {transaction_id}",
+                    payment.id
+                );
+            }
+            Err(err) => {
+                let updated = repository
+                    .record_failure(&payment, &err, now, self.max_failures)
+                    .await?;
+
+                log::warn!(
+                    "Recurring payment {} failed ({} in a row): {err}",
+                    payment.id,
+                    updated.failures
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Id of the transaction of the run, or why it wasn't sent
+    async fn send(&self, payment: &RecurringPaymentModel) -> Result<i32, String> {
+        let wallet = WalletRepository::new_with_connection(&self.db)
+            .find_by_id(payment.wallet_id)
+            .await
+            .map_err(|_| "Failed to retrieve the wallet".to_string())?
+            .ok_or_else(|| "Wallet not found".to_string())?;
+
+        let network = self
+            .chains
+            .get(&wallet.chain)
+            .ok_or_else(|| "Chain not configured".to_string())?;
+
+        let request = transaction_request(payment).map_err(|err| err.to_string())?;
+
+        let response = send_wallet_tx(
+            &self.db,
+            self.participants.as_ref(),
+            &self.screener,
+            &self.quotas,
+            &wallet,
+            network,
+            &request,
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+
+        Ok(response.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_next_run_of_schedule() {
+        let monthly = parse_schedule("0 9 1 * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            next_run(&monthly, after).unwrap(),
+            Utc.with_ymd_and_hms(2025, 2, 1, 9, 0, 0).unwrap()
+        );
+
+        // A run due at `after` itself is the one being claimed
+        let due = Utc.with_ymd_and_hms(2025, 2, 1, 9, 0, 0).unwrap();
+
+        assert_eq!(
+            next_run(&monthly, due).unwrap(),
+            Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap()
+        );
+
+        assert!(parse_schedule("0 0 9 1 * *").is_err());
+        assert!(parse_schedule("0 9 1 *").is_err());
+        assert!(parse_schedule("61 9 * * *").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_runs_are_claimed_once_and_failures_pause() {
+        use crate::db::models::{
+            Chain, RecurringPaymentActiveModel, RecurringPaymentState, UserActiveModel,
+            WalletActiveModel, WalletState,
+        };
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::new(&db)
+            .create(UserActiveModel {
+                username: Set("alice".to_string()),
+                password: Set(String::new()),
+                email: Set("alice@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let wallet = WalletRepository::new_with_connection(&db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(Chain::Ethereum),
+                namespace: Set(uuid::Uuid::new_v4().simple().to_string()),
+                state: Set(WalletState::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let repository = RecurringPaymentRepository::new_with_connection(&db);
+        let now = Utc::now();
+
+        let payment = repository
+            .create(RecurringPaymentActiveModel {
+                user_id: Set(user.id),
+                wallet_id: Set(wallet.id),
+                recipient: Set("vitalik.eth".to_string()),
+                value: Set("1000".to_string()),
+                schedule: Set("0 9 * * *".to_string()),
+                state: Set(RecurringPaymentState::Active),
+                next_run_at: Set(Some(now - chrono::Duration::minutes(1))),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.find_due(now, BATCH_SIZE).await.unwrap(),
+            std::slice::from_ref(&payment)
+        );
+
+        let next = now + chrono::Duration::days(1);
+
+        // A dispatcher holding the same due run loses the claim
+        assert!(repository.claim(&payment, next).await.unwrap());
+        assert!(!repository.claim(&payment, next).await.unwrap());
+        assert!(
+            repository
+                .find_due(now, BATCH_SIZE)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let payment = RecurringPaymentModel {
+            next_run_at: Some(next),
+            ..payment
+        };
+
+        let failed = repository
+            .record_failure(&payment, "Insufficient funds", now, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            (failed.state, failed.failures),
+            (RecurringPaymentState::Active, 1)
+        );
+
+        let paused = repository
+            .record_failure(&failed, "Insufficient funds", now, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(paused.state, RecurringPaymentState::Paused);
+        assert_eq!(paused.next_run_at, None);
+
+        let stored = repository.find_by_id(payment.id).await.unwrap().unwrap();
+
+        assert_eq!(
+            (stored.state, stored.failures, stored.last_error.as_deref()),
+            (RecurringPaymentState::Paused, 2, Some("Insufficient funds"))
+        );
+        assert!(
+            repository
+                .find_due(next + chrono::Duration::days(1), BATCH_SIZE)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
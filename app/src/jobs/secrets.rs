@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use crate::auth::rotate_jwt_key;
+use crate::config::secrets::VaultSecrets;
+
+/// Picks up a rotated `JWT_SIGNING_KEY` from Vault without restarting the app,
+/// other secrets such as `DATABASE_URL` are only read at startup
+pub struct SecretRotator {
+    vault: VaultSecrets,
+    interval: Duration,
+}
+
+impl SecretRotator {
+    pub fn new(vault: VaultSecrets, interval: Duration) -> Self {
+        Self { vault, interval }
+    }
+
+    pub async fn run(self) {
+        loop {
+            actix_web::rt::time::sleep(self.interval).await;
+
+            match self.vault.fetch().await {
+                Ok(mut secrets) => {
+                    if let Some(key) = secrets.remove("JWT_SIGNING_KEY") {
+                        match rotate_jwt_key(&key) {
+                            Ok(true) => log::info!("Rotated JWT signing key"),
+                            Ok(false) => {}
+                            Err(err) => log::error!("Ignored invalid JWT signing key: {err}"),
+                        }
+                    }
+                }
+                Err(err) => log::error!("Failed to refresh secrets from Vault: {err}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::replace_transaction;
+use crate::chains::ChainRegistry;
+use crate::db::models::{TransactionModel, TransactionStatus, WalletOperation};
+use crate::db::repositories::{TransactionRepository, WalletRepository};
+use crate::participants::ParticipantPool;
+
+/// Whether the stuck `transaction` gets another replacement, `attempts` are the
+/// transactions sharing its nonce. A live replacement is waited for instead and failed
+/// replacements still count towards `max_bumps`
+fn replacement_due(
+    transaction: &TransactionModel,
+    attempts: &[TransactionModel],
+    max_bumps: usize,
+) -> bool {
+    let replaced = attempts.iter().any(|attempt| {
+        attempt.replaces_id == Some(transaction.id) && attempt.status != TransactionStatus::Failed
+    });
+
+    let bumps = attempts
+        .iter()
+        .filter(|attempt| attempt.replaces_id.is_some())
+        .count();
+
+    !replaced && bumps < max_bumps
+}
+
+/// Flags EVM transactions broadcast but still out of a block after `after`, and replaces
+/// them with a bumped gas price when their wallet's policy allows it
+pub struct StuckMonitor {
+    db: DatabaseConnection,
+    chains: Arc<ChainRegistry>,
+    participants: Arc<dyn ParticipantPool>,
+    after: Duration,
+    interval: Duration,
+    bump_percent: u64,
+    max_bumps: usize,
+}
+
+impl StuckMonitor {
+    pub fn new(
+        db: DatabaseConnection,
+        chains: Arc<ChainRegistry>,
+        participants: Arc<dyn ParticipantPool>,
+        after: Duration,
+        interval: Duration,
+        bump_percent: u64,
+        max_bumps: usize,
+    ) -> Self {
+        Self {
+            db,
+            chains,
+            participants,
+            after,
+            interval,
+            bump_percent,
+            max_bumps,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.check().await {
+                log::error!("Stuck transaction check failed: {err}");
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn check(&self) -> Result<()> {
+        let repository = TransactionRepository::new_with_connection(&self.db);
+
+        let cutoff = Utc::now() - chrono::Duration::from_std(self.after)?;
+
+        for transaction in repository
+            .find_by_status(TransactionStatus::Broadcast)
+            .await?
+        {
+            // Included transactions are only waiting for their confirmations
+            if transaction.block_number.is_some()
+                || transaction.updated_at.is_none_or(|at| at >= cutoff)
+            {
+                continue;
+            }
+
+            let id = transaction.id;
+
+            if let Err(err) = self.unstick(&repository, transaction).await {
+                log::error!("Failed to replace stuck transaction {id}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn unstick(
+        &self,
+        repository: &TransactionRepository<'_>,
+        transaction: TransactionModel,
+    ) -> Result<()> {
+        let Some(network) = transaction
+            .chain
+            .as_ref()
+            .filter(|chain| chain.is_evm())
+            .and_then(|chain| self.chains.get(chain))
+        else {
+            return Ok(());
+        };
+
+        let transaction = match transaction.stuck_at {
+            Some(_) => transaction,
+            None => {
+                log::warn!(
+                    "Transaction {} is still unconfirmed after {}s",
+                    transaction.id,
+                    self.after.as_secs()
+                );
+
+                repository.mark_stuck(&transaction).await?
+            }
+        };
+
+        let Some(nonce) = transaction.nonce else {
+            return Ok(());
+        };
+
+        let Some(wallet) = WalletRepository::new_with_connection(&self.db)
+            .find_by_id(transaction.wallet_id)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        if !wallet.auto_bump_gas || wallet.state.ensure_allows(WalletOperation::Sign).is_err() {
+            return Ok(());
+        }
+
+        let attempts = repository
+            .find_by_nonce(wallet.id, transaction.account_id, nonce)
+            .await?;
+
+        if !replacement_due(&transaction, &attempts, self.max_bumps) {
+            return Ok(());
+        }
+
+        replace_transaction(
+            &self.db,
+            self.participants.as_ref(),
+            &wallet,
+            network,
+            &transaction,
+            self.bump_percent,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(id: i32, status: TransactionStatus, replaces_id: Option<i32>) -> TransactionModel {
+        TransactionModel {
+            id,
+            user_id: 1,
+            wallet_id: 1,
+            created_at: None,
+            updated_at: None,
+            status,
+            tx_hash: None,
+            raw_tx: None,
+            signature_r: None,
+            signature_s: None,
+            signature_v: None,
+            chain: None,
+            value: None,
+            to_address: None,
+            contract_address: None,
+            token_contract: None,
+            token_id: None,
+            error: None,
+            block_number: None,
+            block_hash: None,
+            confirmations: None,
+            nonce: Some(0),
+            screening_verdict: None,
+            screening_reason: None,
+            stuck_at: None,
+            replaces_id,
+            account_id: None,
+            memo: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_replacement_due_waits_for_live_replacements() {
+        let original = attempt(1, TransactionStatus::Broadcast, None);
+
+        assert!(replacement_due(
+            &original,
+            std::slice::from_ref(&original),
+            3
+        ));
+
+        let live = attempt(2, TransactionStatus::Broadcast, Some(1));
+
+        assert!(!replacement_due(&original, &[original.clone(), live], 3));
+
+        // A failed replacement is retried from the transaction it replaced
+        let failed = attempt(2, TransactionStatus::Failed, Some(1));
+
+        assert!(replacement_due(
+            &original,
+            &[original.clone(), failed.clone()],
+            3
+        ));
+        assert!(!replacement_due(&original, &[original.clone(), failed], 1));
+    }
+}
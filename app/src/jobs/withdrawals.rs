@@ -0,0 +1,130 @@
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::release_withdrawal;
+use crate::chains::ChainRegistry;
+use crate::config::app_config::{QuotaConfig, WithdrawalConfig};
+use crate::db::models::WithdrawalModel;
+use crate::db::repositories::{WalletRepository, WithdrawalRepository};
+use crate::participants::ParticipantPool;
+use crate::screening::Screener;
+
+/// Most withdrawals released per poll, the rest wait for the next one
+const BATCH_SIZE: u64 = 100;
+
+/// Signs the withdrawals held by the delay policy of their wallet once their window closed
+/// without a cancellation. A withdrawal is claimed by releasing it, so it is sent once even
+/// with several instances polling
+pub struct WithdrawalReleaser {
+    db: DatabaseConnection,
+    chains: Arc<ChainRegistry>,
+    participants: Arc<dyn ParticipantPool>,
+    screener: Arc<Screener>,
+    quotas: QuotaConfig,
+    interval: Duration,
+}
+
+impl WithdrawalReleaser {
+    pub fn new(
+        db: DatabaseConnection,
+        chains: Arc<ChainRegistry>,
+        participants: Arc<dyn ParticipantPool>,
+        screener: Arc<Screener>,
+        quotas: QuotaConfig,
+        config: &WithdrawalConfig,
+    ) -> Self {
+        Self {
+            db,
+            chains,
+            participants,
+            screener,
+            quotas,
+            interval: Duration::from_secs(config.poll_interval),
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.release_due().await {
+                log::error!("Withdrawal release failed: {err}");
+            }
+
+            actix_web::rt::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn release_due(&self) -> Result<()> {
+        let repository = WithdrawalRepository::new_with_connection(&self.db);
+
+        for withdrawal in repository.find_due(Utc::now(), BATCH_SIZE).await? {
+            let id = withdrawal.id;
+
+            if let Err(err) = self.release(&repository, withdrawal).await {
+                log::error!("Failed to release withdrawal {id}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release(
+        &self,
+        repository: &WithdrawalRepository<'_>,
+        withdrawal: WithdrawalModel,
+    ) -> Result<()> {
+        let Some(withdrawal) = repository.release(&withdrawal).await? else {
+            return Ok(());
+        };
+
+        match self.send(&withdrawal).await {
+            Ok(transaction_id) => {
+                repository
+                    .record_transaction(&withdrawal, transaction_id)
+                    .await?;
+
+                log::info!(
+                    "Withdrawal {} sent transaction {transaction_id}",
+                    withdrawal.id
+                );
+            }
+            Err(err) => {
+                repository.fail(&withdrawal, &err).await?;
+
+                log::warn!("Withdrawal {} failed: {err}", withdrawal.id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Id of the transaction of the withdrawal, or why it wasn't sent
+    async fn send(&self, withdrawal: &WithdrawalModel) -> Result<i32, String> {
+        let wallet = WalletRepository::new_with_connection(&self.db)
+            .find_by_id(withdrawal.wallet_id)
+            .await
+            .map_err(|_| "Failed to retrieve the wallet".to_string())?
+            .ok_or_else(|| "Wallet not found".to_string())?;
+
+        let network = self
+            .chains
+            .get(&wallet.chain)
+            .ok_or_else(|| "Chain not configured".to_string())?;
+
+        let response = release_withdrawal(
+            &self.db,
+            self.participants.as_ref(),
+            &self.screener,
+            &self.quotas,
+            &wallet,
+            network,
+            withdrawal,
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+
+        Ok(response.id)
+    }
+}
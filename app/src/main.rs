@@ -1,24 +1,60 @@
 mod api;
 mod auth;
+mod chains;
 mod config;
 mod db;
+mod events;
+mod health;
+mod jobs;
 mod middleware;
+mod participants;
+mod prices;
+mod screening;
+mod siem;
+#[cfg(all(test, feature = "sqlite"))]
+mod testing;
 mod utils;
 
-use actix_web::{App, HttpServer, middleware::Logger};
-use alloy::providers::ProviderBuilder;
+use actix_web::{App, HttpServer, middleware::Logger, web};
 use anyhow::Result;
 use futures::future::join_all;
-use sea_orm::{Database, DbConn};
+use sea_orm::{ConnectOptions, Database, DbConn};
 use sea_orm_migration::MigratorTrait;
 use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Channel;
 
-use crate::config::app_config::AppConfig;
+use crate::api::status::StatusService;
+use crate::auth::OidcClient;
+use crate::chains::ChainRegistry;
+use crate::config::app_config::{
+    AppConfig, DatabaseConfig, ParticipantConfig, ParticipantDiscoveryConfig,
+};
+use crate::config::secrets::VaultSecrets;
 use crate::db::migrations::Migrator;
+use crate::events::EventBus;
+use crate::health::HealthChecker;
+use crate::jobs::{
+    EventRelay, JobPolicy, JobRunner, OutboxPublisher, ProviderMonitor, ReceiptPoller, Reconciler,
+    RecurringDispatcher, SecretRotator, StuckMonitor, WalletHealthMonitor, WalletPurger,
+    WithdrawalReleaser,
+};
+use crate::middleware::{RateLimiter, RequestIdMiddleware};
+use crate::participants::{
+    Discovery, GrpcParticipants, ParticipantPool, RetryPolicy, rooms, system_nameserver,
+};
+use crate::prices::Prices;
+use crate::screening::Screener;
 
-async fn connect_db(database_url: &str) -> Result<DbConn> {
-    let db: DbConn = Database::connect(database_url)
+async fn connect_db(config: &DatabaseConfig) -> Result<DbConn> {
+    let mut options = ConnectOptions::new(&config.url);
+    options
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .connect_timeout(Duration::from_secs(config.connect_timeout))
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout));
+
+    let db: DbConn = Database::connect(options)
         .await
         .expect("Error connecting to the database");
 
@@ -31,13 +67,69 @@ async fn connect_db(database_url: &str) -> Result<DbConn> {
     Ok(db)
 }
 
+/// Channel of a participant, discovered through its SRV record and following it when it has
+/// one
+async fn connect_participant(
+    participant: &ParticipantConfig,
+    discovery: &ParticipantDiscoveryConfig,
+) -> Result<Channel> {
+    let Some(srv) = participant.srv.as_deref() else {
+        return Ok(Channel::from_shared(participant.host.clone())?
+            .connect()
+            .await?);
+    };
+
+    let nameserver = match discovery.nameserver {
+        Some(nameserver) => nameserver,
+        None => system_nameserver()?,
+    };
+
+    let (channel, discovery) =
+        Discovery::new(srv, nameserver, Duration::from_secs(discovery.interval)).await?;
+
+    actix_web::rt::spawn(discovery.run());
+
+    Ok(channel)
+}
+
+/// Format of the access log, the default one with the request id. The line is written once the
+/// response was sent, outside of the log context of the request
+const ACCESS_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#;
+
 #[actix_web::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok();
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    logging::init("app");
 
-    let app_config = AppConfig::from_env()?;
+    let app_config = AppConfig::load().await?;
+
+    match &app_config.auth.jwt_signing_key {
+        Some(key) => auth::set_jwt_keys(key, &app_config.auth.jwt_previous_keys)?,
+        None => log::warn!("JWT_DEV_MODE is set, signing tokens with a key generated at startup"),
+    }
+    auth::set_jwt_issuer(
+        app_config.auth.jwt_issuer.clone(),
+        app_config.auth.jwt_audience.clone(),
+    );
+    auth::set_password_params(app_config.auth.argon2_params()?);
+
+    // Started first, events of the jobs and requests are only collected from then on
+    if let Some(exporter) = siem::exporter(&app_config.siem, &app_config.proxy)? {
+        log::info!("Exporting security events to {}", exporter.name());
+
+        actix_web::rt::spawn(exporter.run());
+    }
+
+    if let Some(vault) = &app_config.secrets.vault {
+        let rotator = SecretRotator::new(
+            VaultSecrets::new(vault)?,
+            Duration::from_secs(vault.refresh_interval),
+        );
+
+        actix_web::rt::spawn(rotator.run());
+    }
 
     log::info!(
         "Starting server at {}:{}",
@@ -45,32 +137,214 @@ async fn main() -> Result<()> {
         app_config.server.port
     );
 
-    let provider = Arc::new(ProviderBuilder::new().connect_http(
-        format!("{}:{}", app_config.provider.host, app_config.provider.port).parse()?,
-    ));
+    let chains = Arc::new(ChainRegistry::new(&app_config.chains, &app_config.proxy)?);
+    let screener = web::Data::new(Screener::new(&app_config.screening, &app_config.proxy)?);
+    let prices = web::Data::new(Prices::new(&app_config.prices, &app_config.proxy)?);
+
+    RetryPolicy::from(&app_config.participants.retry).install();
 
-    let p1 = Channel::from_shared(app_config.participants.participant_1.host.clone())?;
-    let p2 = Channel::from_shared(app_config.participants.participant_2.host.clone())?;
-    let p3 = Channel::from_shared(app_config.participants.participant_3.host.clone())?;
+    match &app_config.participants.room_token_secret {
+        Some(secret) => rooms::install(secret.clone()),
+        None => log::warn!("ROOM_TOKEN_SECRET is unset, relay rooms are left open"),
+    }
+
+    let primaries = [
+        &app_config.participants.participant_1,
+        &app_config.participants.participant_2,
+        &app_config.participants.participant_3,
+    ];
 
     let (db_result, channel_result) = futures::future::join(
-        connect_db(&app_config.database.url),
-        join_all([p1.connect(), p2.connect(), p3.connect()]),
+        connect_db(&app_config.database),
+        join_all(primaries.map(|participant| {
+            connect_participant(participant, &app_config.participants.discovery)
+        })),
     )
     .await;
 
-    let participants = channel_result
+    let channels = channel_result
         .into_iter()
-        .collect::<Result<Vec<Channel>, _>>()?;
+        .collect::<Result<Vec<Channel>>>()?;
+
+    // Standbys may be down at startup, they are connected once needed
+    let standbys = app_config
+        .participants
+        .standbys
+        .iter()
+        .map(|standby| Ok(Channel::from_shared(standby.host.clone())?.connect_lazy()))
+        .collect::<anyhow::Result<Vec<Channel>>>()?;
+
+    let attestation = primaries
+        .iter()
+        .map(|participant| participant.attestation.clone())
+        .collect();
+
+    let participants: Arc<dyn ParticipantPool> = Arc::new(
+        GrpcParticipants::new(channels)
+            .with_standbys(standbys)
+            .with_attestation(attestation),
+    );
 
     let db = db_result?;
 
+    // Shared across workers so the cache and the limits are global to the process
+    let status = web::Data::new(StatusService::new(
+        HealthChecker::new(db.clone(), chains.clone(), participants.clone()),
+        Duration::from_secs(app_config.status.cache_ttl),
+    ));
+    let status_limiter = Arc::new(RateLimiter::new(
+        app_config.status.rate_limit,
+        Duration::from_secs(60),
+    ));
+
+    let purger = WalletPurger::new(
+        db.clone(),
+        participants.clone(),
+        // In range, the configuration refuses a retention `chrono` can't hold
+        chrono::Duration::days(app_config.archive.retention_days as i64),
+        Duration::from_secs(app_config.archive.purge_interval),
+    );
+
+    actix_web::rt::spawn(purger.run());
+
+    let receipts = ReceiptPoller::new(
+        db.clone(),
+        chains.clone(),
+        Duration::from_secs(app_config.receipts.poll_interval),
+    );
+
+    actix_web::rt::spawn(receipts.run());
+
+    JobPolicy::from(&app_config.jobs).install();
+
+    let jobs = JobRunner::new(
+        db.clone(),
+        participants.clone(),
+        Duration::from_secs(app_config.jobs.poll_interval),
+        Duration::from_secs(app_config.jobs.lease),
+    );
+
+    actix_web::rt::spawn(jobs.run());
+
+    // Subscribers of `/api/events` only get the events of committed state changes, read
+    // back from the outbox
+    let events = web::Data::new(EventBus::new());
+
+    let relay = EventRelay::new(db.clone(), events.clone().into_inner(), &app_config.outbox);
+
+    actix_web::rt::spawn(relay.run());
+
+    let bus = jobs::message_bus(&app_config.outbox);
+
+    if let Some(bus) = &bus {
+        log::info!("Publishing the event outbox to {}", bus.name());
+    }
+
+    let publisher = OutboxPublisher::new(db.clone(), bus, &app_config.outbox);
+
+    actix_web::rt::spawn(publisher.run());
+
+    let monitor = ProviderMonitor::new(
+        chains.clone(),
+        Duration::from_secs(app_config.rpc.health_interval),
+    );
+
+    actix_web::rt::spawn(monitor.run());
+
+    let stuck = StuckMonitor::new(
+        db.clone(),
+        chains.clone(),
+        participants.clone(),
+        Duration::from_secs(app_config.stuck.after),
+        Duration::from_secs(app_config.stuck.interval),
+        app_config.stuck.bump_percent,
+        app_config.stuck.max_bumps,
+    );
+
+    actix_web::rt::spawn(stuck.run());
+
+    let recurring = RecurringDispatcher::new(
+        db.clone(),
+        chains.clone(),
+        participants.clone(),
+        screener.clone().into_inner(),
+        app_config.quotas.clone(),
+        &app_config.recurring,
+    );
+
+    actix_web::rt::spawn(recurring.run());
+
+    let releaser = WithdrawalReleaser::new(
+        db.clone(),
+        chains.clone(),
+        participants.clone(),
+        screener.clone().into_inner(),
+        app_config.quotas.clone(),
+        &app_config.withdrawals,
+    );
+
+    actix_web::rt::spawn(releaser.run());
+
+    let reconciler = web::Data::new(Reconciler::new(
+        db.clone(),
+        participants.clone(),
+        Duration::from_secs(app_config.reconcile.interval),
+        Duration::from_secs(app_config.reconcile.grace_period),
+        app_config.reconcile.retry_keygen,
+    ));
+
+    actix_web::rt::spawn(reconciler.clone().into_inner().run());
+
+    let wallet_health = web::Data::new(WalletHealthMonitor::new(
+        db.clone(),
+        participants.clone(),
+        Duration::from_secs(app_config.wallet_health.interval),
+        app_config.wallet_health.concurrency,
+    ));
+
+    actix_web::rt::spawn(wallet_health.clone().into_inner().run());
+
+    let import = web::Data::new(app_config.import.clone());
+    let export = web::Data::new(app_config.export.clone());
+    let cosigner = web::Data::new(app_config.cosigner.clone());
+    let quotas = web::Data::new(app_config.quotas.clone());
+    let withdrawals = web::Data::new(app_config.withdrawals.clone());
+    let login = web::Data::new(app_config.login.clone());
+    let oidc = web::Data::new(
+        app_config
+            .oidc
+            .as_ref()
+            .map(|config| OidcClient::new(config, &app_config.proxy))
+            .transpose()?,
+    );
+
     HttpServer::new(move || {
         App::new()
+            .app_data(events.clone())
+            .app_data(reconciler.clone())
+            .app_data(wallet_health.clone())
+            .app_data(import.clone())
+            .app_data(export.clone())
+            .app_data(cosigner.clone())
+            .app_data(quotas.clone())
+            .app_data(withdrawals.clone())
+            .app_data(login.clone())
+            .app_data(oidc.clone())
+            .app_data(screener.clone())
+            .app_data(prices.clone())
             .configure(|config| {
-                api::configure_routes(config, db.clone(), participants.clone(), provider.clone())
+                api::configure_routes(
+                    config,
+                    db.clone(),
+                    participants.clone(),
+                    chains.clone(),
+                    status.clone(),
+                    status_limiter.clone(),
+                    app_config.admin.api_key.clone(),
+                )
             })
-            .wrap(Logger::default())
+            .wrap(RequestIdMiddleware)
+            .wrap(Logger::new(ACCESS_LOG_FORMAT))
     })
     .bind(format!(
         "{}:{}",
@@ -0,0 +1,150 @@
+use actix_service::{Service, Transform};
+use actix_web::Error;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorForbidden, ErrorUnauthorized};
+use futures::future::{Ready, ready};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::siem::{self, Outcome, SecurityEvent};
+
+static ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// Guards operator endpoints with the static `ADMIN_API_KEY`, disabled when no key is configured
+pub struct AdminMiddleware {
+    api_key: Option<Arc<str>>,
+}
+
+impl AdminMiddleware {
+    pub fn new(api_key: Option<String>) -> Self {
+        AdminMiddleware {
+            api_key: api_key.map(Arc::from),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AdminMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AdminMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminMiddlewareService {
+            service: Arc::new(service),
+            api_key: self.api_key.clone(),
+        }))
+    }
+}
+
+pub struct AdminMiddlewareService<S> {
+    service: Arc<S>,
+    api_key: Option<Arc<str>>,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let Some(api_key) = self.api_key.as_deref() else {
+            return Box::pin(async { Err(ErrorForbidden("Admin API is disabled")) });
+        };
+
+        let provided = req
+            .headers()
+            .get(ADMIN_KEY_HEADER)
+            .and_then(|header| header.to_str().ok());
+
+        match provided {
+            Some(key) if tokens::constant_time_eq(key.as_bytes(), api_key.as_bytes()) => {}
+            _ => {
+                siem::emit(
+                    SecurityEvent::new("admin.key_rejected", Outcome::Failure)
+                        .source_ip(req.peer_addr().map(|addr| addr.ip()))
+                        .details(serde_json::json!({
+                            "reason": if provided.is_some() { "invalid_key" } else { "missing_key" },
+                            "method": req.method().as_str(),
+                            "path": req.path(),
+                        })),
+                );
+
+                return Box::pin(async { Err(ErrorUnauthorized("Invalid admin key")) });
+            }
+        }
+
+        let service = self.service.clone();
+
+        Box::pin(async move { service.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+
+    async fn send_req(api_key: Option<&str>, header: Option<&str>) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .wrap(AdminMiddleware::new(api_key.map(str::to_string)))
+                .route(
+                    "/admin",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+
+        let mut req = test::TestRequest::get().uri("/admin");
+
+        if let Some(header) = header {
+            req = req.insert_header((ADMIN_KEY_HEADER, header));
+        }
+
+        match test::try_call_service(&app, req.to_request()).await {
+            Ok(resp) => resp.status(),
+            Err(err) => err.error_response().status(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_disabled_without_key() {
+        assert_eq!(send_req(None, Some("key")).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_wrong_key() {
+        assert_eq!(
+            send_req(Some("secret"), Some("wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send_req(Some("secret"), None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_accepts_valid_key() {
+        assert_eq!(
+            send_req(Some("secret"), Some("secret")).await,
+            StatusCode::OK
+        );
+    }
+}
@@ -1,14 +1,37 @@
 use actix_service::{Service, Transform};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::error::ErrorUnauthorized;
-use actix_web::{Error, HttpMessage};
-use futures::future::{Ready, ready};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, ResponseError, web};
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{BoxFuture, Ready, ready};
+use sea_orm::DbConn;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use crate::auth::validate_token;
+use crate::api::error::ApiError;
+use crate::auth::{Claims, validate_token};
+use crate::db::models::SessionModel;
+use crate::db::repositories::SessionRepository;
+use crate::siem::{self, Outcome, SecurityEvent};
+
+/// Sessions the tokens are checked against, read by the middleware from the app data
+pub trait SessionStore: Send + Sync {
+    fn find<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<SessionModel>>>;
+
+    fn touch<'a>(&'a self, id: &'a str, now: DateTime<Utc>) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+impl SessionStore for DbConn {
+    fn find<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<SessionModel>>> {
+        Box::pin(async move { SessionRepository::new(self).find_by_id(id).await })
+    }
+
+    fn touch<'a>(&'a self, id: &'a str, now: DateTime<Utc>) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move { SessionRepository::new(self).touch(id, now).await })
+    }
+}
 
 pub struct AuthMiddleware;
 
@@ -57,40 +80,61 @@ where
         let auth_header = match req.headers().get("Authorization") {
             Some(header) => header,
             None => {
-                return Box::pin(async {
-                    Err(ErrorUnauthorized("Authorization header not found"))
-                });
+                let error = reject(
+                    &req,
+                    ApiError::unauthorized("Authorization header not found"),
+                );
+
+                return Box::pin(async { Err(error) });
             }
         };
 
         let auth_str = match auth_header.to_str() {
             Ok(s) => s,
             Err(_) => {
-                return Box::pin(async {
-                    Err(ErrorUnauthorized("Invalid authorization header format"))
-                });
+                let error = reject(
+                    &req,
+                    ApiError::unauthorized("Invalid authorization header format"),
+                );
+
+                return Box::pin(async { Err(error) });
             }
         };
 
         if !auth_str.starts_with("Bearer ") {
-            return Box::pin(async {
-                Err(ErrorUnauthorized("Invalid authorization header format"))
-            });
+            let error = reject(
+                &req,
+                ApiError::unauthorized("Invalid authorization header format"),
+            );
+
+            return Box::pin(async { Err(error) });
         }
 
         let token = auth_str.trim_start_matches("Bearer ").trim().to_string();
         let service = self.service.clone();
+        let sessions = req.app_data::<web::Data<dyn SessionStore>>().cloned();
 
         Box::pin(async move {
             match validate_token(&token).await {
                 Ok(token_data) => {
+                    let sessions =
+                        sessions.ok_or_else(|| ApiError::internal("Sessions are unavailable"))?;
+
+                    if let Err(err) = ensure_session(sessions.get_ref(), &token_data.claims).await {
+                        return Err(reject(&req, err));
+                    }
+
+                    logging::set_user_id(token_data.claims.user_id);
+
                     req.extensions_mut().insert(token_data.claims);
                     service.call(req).await
                 }
                 Err(err) => {
                     log::debug!("Token validation failed: {:?}", err);
-                    Err(ErrorUnauthorized(
-                        "Token validation failed. Please log in again.",
+                    Err(reject(
+                        &req,
+                        ApiError::unauthorized("Token validation failed. Please log in again.")
+                            .with_code("invalid_token"),
                     ))
                 }
             }
@@ -98,16 +142,74 @@ where
     }
 }
 
+/// Fails the request with `error`, reporting the refused credentials to the SIEM
+fn reject(req: &ServiceRequest, error: ApiError) -> Error {
+    if error.status_code() == StatusCode::UNAUTHORIZED {
+        siem::emit(
+            SecurityEvent::new("auth.token_rejected", Outcome::Failure)
+                .source_ip(req.peer_addr().map(|addr| addr.ip()))
+                .details(serde_json::json!({
+                    "reason": error.problem().code,
+                    "method": req.method().as_str(),
+                    "path": req.path(),
+                })),
+        );
+    }
+
+    error.into()
+}
+
+/// Refuses tokens whose session was revoked or never recorded
+async fn ensure_session(sessions: &dyn SessionStore, claims: &Claims) -> Result<(), ApiError> {
+    let now = Utc::now();
+
+    let session = sessions
+        .find(&claims.jti)
+        .await
+        .map_err(|_| ApiError::internal("Failed to check the session"))?;
+
+    match session {
+        Some(session) if session.user_id == claims.user_id && session.is_active(now) => {
+            // Not written on every request, a minute is precise enough for listing sessions
+            if now - session.last_used_at >= Duration::minutes(1)
+                && let Err(err) = sessions.touch(&session.id, now).await
+            {
+                log::error!("Failed to update session {}: {err}", session.id);
+            }
+
+            Ok(())
+        }
+        _ => Err(
+            ApiError::unauthorized("Session was revoked. Please log in again.")
+                .with_code("session_revoked"),
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::auth::generate_token;
     use crate::db::models::UserModel;
     use actix_web::{App, HttpResponse, http::StatusCode, test, web};
-    use chrono::DateTime;
+    use std::sync::Mutex;
 
     async fn send_req_with_header(name: &str, value: &str) -> StatusCode {
-        let app = test::init_service(App::new().wrap(AuthMiddleware::new()).route(
+        send_req(None, name, value).await
+    }
+
+    async fn send_req(
+        sessions: Option<Arc<dyn SessionStore>>,
+        name: &str,
+        value: &str,
+    ) -> StatusCode {
+        let mut app = App::new();
+
+        if let Some(sessions) = sessions {
+            app = app.app_data(web::Data::from(sessions));
+        }
+
+        let app = test::init_service(app.wrap(AuthMiddleware::new()).route(
             "/protected",
             web::get().to(|| async { HttpResponse::Ok().json("success") }),
         ))
@@ -127,17 +229,107 @@ mod tests {
         };
     }
 
-    fn jwt_token() -> String {
-        let claims = crate::auth::generate_claims(&UserModel {
+    fn jwt_claims() -> Claims {
+        crate::auth::generate_claims(&UserModel {
             id: 123,
             username: "testuser".to_string(),
             password: "hashed_password".to_string(),
             email: "test@example.com".to_string(),
             created_on: Some(DateTime::from_timestamp(1640995200, 0).unwrap()),
             updated_on: Some(DateTime::from_timestamp(1640995200, 0).unwrap()),
-        });
+        })
+    }
+
+    fn jwt_token() -> String {
+        generate_token(&jwt_claims()).unwrap()
+    }
+
+    /// Session recorded at login for the token of the claims, last used an hour ago
+    fn session(claims: &Claims) -> SessionModel {
+        let last_used_at = Utc::now() - Duration::hours(1);
+
+        SessionModel {
+            id: claims.jti.clone(),
+            user_id: claims.user_id,
+            device: None,
+            ip: None,
+            created_at: last_used_at,
+            last_used_at,
+            expires_at: DateTime::from_timestamp(claims.exp as i64, 0).unwrap(),
+            revoked_at: None,
+        }
+    }
 
-        generate_token(&claims).unwrap()
+    /// Sessions kept in memory, recording which were touched
+    #[derive(Default)]
+    struct MockSessions {
+        sessions: Vec<SessionModel>,
+        touched: Mutex<Vec<String>>,
+    }
+
+    impl SessionStore for MockSessions {
+        fn find<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<SessionModel>>> {
+            let session = self
+                .sessions
+                .iter()
+                .find(|session| session.id == id)
+                .cloned();
+
+            Box::pin(async move { Ok(session) })
+        }
+
+        fn touch<'a>(
+            &'a self,
+            id: &'a str,
+            _now: DateTime<Utc>,
+        ) -> BoxFuture<'a, anyhow::Result<()>> {
+            self.touched.lock().unwrap().push(id.to_string());
+
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn mock_sessions(sessions: Vec<SessionModel>) -> Arc<MockSessions> {
+        Arc::new(MockSessions {
+            sessions,
+            ..Default::default()
+        })
+    }
+
+    /// Database holding the user of the claims and a session of the token
+    #[cfg(feature = "sqlite")]
+    async fn session_db(claims: &Claims) -> DbConn {
+        use crate::db::models::{SessionActiveModel, UserActiveModel};
+        use crate::db::repositories::UserRepository;
+        use sea_orm::{ConnectOptions, Database, Set};
+        use sea_orm_migration::MigratorTrait;
+
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        UserRepository::new(&db)
+            .create(UserActiveModel {
+                id: Set(claims.user_id),
+                username: Set(claims.username.clone()),
+                password: Set(String::new()),
+                email: Set("test@example.com".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        SessionRepository::new(&db)
+            .create(SessionActiveModel::from(session(claims)))
+            .await
+            .unwrap();
+
+        db
     }
 
     #[actix_web::test]
@@ -163,20 +355,91 @@ mod tests {
 
     #[actix_web::test]
     async fn test_valid_token_success() {
+        let claims = jwt_claims();
+        let sessions = mock_sessions(vec![session(&claims)]);
+        let token = generate_token(&claims).unwrap();
+
         assert_eq!(
-            send_req_with_header("Authorization", &format!("Bearer {}", jwt_token())).await,
+            send_req(
+                Some(sessions.clone()),
+                "Authorization",
+                &format!("Bearer {token}")
+            )
+            .await,
             StatusCode::OK
         );
+
+        assert_eq!(*sessions.touched.lock().unwrap(), vec![claims.jti]);
     }
 
     #[actix_web::test]
     async fn test_bearer_token_with_extra_whitespace() {
+        let claims = jwt_claims();
+        let sessions = mock_sessions(vec![session(&claims)]);
+        let token = generate_token(&claims).unwrap();
+
         assert_eq!(
-            send_req_with_header("Authorization", &format!("Bearer   {}   ", jwt_token())).await,
+            send_req(
+                Some(sessions),
+                "Authorization",
+                &format!("Bearer   {token}   ")
+            )
+            .await,
             StatusCode::OK
         );
     }
 
+    #[actix_web::test]
+    async fn test_unknown_session_is_refused() {
+        assert_eq!(
+            send_req(
+                Some(mock_sessions(Vec::new())),
+                "Authorization",
+                &format!("Bearer {}", jwt_token())
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_web::test]
+    async fn test_revoked_or_unknown_session_is_refused() {
+        let claims = jwt_claims();
+        let db = session_db(&claims).await;
+        let token = generate_token(&claims).unwrap();
+
+        // Signed like any other token, but it was never recorded at login
+        let unknown = generate_token(&jwt_claims()).unwrap();
+
+        assert_eq!(
+            send_req(
+                Some(Arc::new(db.clone())),
+                "Authorization",
+                &format!("Bearer {unknown}")
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        assert!(
+            SessionRepository::new(&db)
+                .revoke(claims.user_id, &claims.jti, Utc::now())
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(
+            send_req(
+                Some(Arc::new(db)),
+                "Authorization",
+                &format!("Bearer {token}")
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
     #[actix_web::test]
     async fn test_case_sensitive_bearer() {
         assert_eq!(
@@ -1,3 +1,9 @@
+mod admin;
 mod auth;
+mod rate_limit;
+mod request_id;
 
-pub use auth::AuthMiddleware;
+pub use admin::AdminMiddleware;
+pub use auth::{AuthMiddleware, SessionStore};
+pub use rate_limit::{RateLimitMiddleware, RateLimiter};
+pub use request_id::RequestIdMiddleware;
@@ -0,0 +1,183 @@
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{Error, HttpResponse};
+use futures::future::{Ready, ready};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+// Stale windows are only pruned once the map grows past this size
+static MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+/// Fixed-window request limiter keyed by client IP address
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    clients: Mutex<HashMap<IpAddr, Window>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request from `client`, returning how long to wait when the limit is exceeded
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        if clients.len() >= MAX_TRACKED_CLIENTS {
+            clients.retain(|_, w| now.duration_since(w.started) < self.window);
+        }
+
+        let window = clients.entry(client).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+
+        if now.duration_since(window.started) >= self.window {
+            window.started = now;
+            window.count = 0;
+        }
+
+        if window.count >= self.max_requests {
+            return Err(self.window - now.duration_since(window.started));
+        }
+
+        window.count += 1;
+
+        Ok(())
+    }
+}
+
+pub struct RateLimitMiddleware {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        RateLimitMiddleware { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddlewareService {
+            service: Arc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddlewareService<S> {
+    service: Arc<S>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(addr) = req.peer_addr()
+            && let Err(retry_after) = self.limiter.check(addr.ip())
+        {
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.as_secs().max(1).to_string()))
+                .json(serde_json::json!({ "error": "Too many requests" }));
+
+            return Box::pin(async move {
+                Err(InternalError::from_response("Too many requests", response).into())
+            });
+        }
+
+        let service = self.service.clone();
+
+        Box::pin(async move { service.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(last: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last])
+    }
+
+    #[test]
+    fn test_rejects_after_limit() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), now).is_ok());
+        assert!(limiter.check_at(client(1), now).is_ok());
+        assert!(limiter.check_at(client(1), now).is_err());
+    }
+
+    #[test]
+    fn test_limits_are_per_client() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), now).is_ok());
+        assert!(limiter.check_at(client(2), now).is_ok());
+        assert!(limiter.check_at(client(1), now).is_err());
+    }
+
+    #[test]
+    fn test_window_resets() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), now).is_ok());
+
+        let retry_after = limiter
+            .check_at(client(1), now + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(40));
+
+        assert!(
+            limiter
+                .check_at(client(1), now + Duration::from_secs(60))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use actix_service::{Service, Transform};
+use actix_web::Error;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use futures::future::{Ready, ready};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+/// Longest client request id kept, longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Serves every request in a log context of its `X-Request-Id`, the one of the client when
+/// it sent a usable one and a new UUID otherwise, and returns the id in the same header of
+/// its response. The user and wallet ids are added to the context by the handlers once known.
+/// Errors middleware fail the request with are rendered by the server, without the header
+pub struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+/// Request id of the client, unless it's empty, too long or has characters unfit for logs
+fn client_request_id(req: &ServiceRequest) -> Option<String> {
+    let id = req
+        .headers()
+        .get(logging::REQUEST_ID_HEADER)?
+        .to_str()
+        .ok()?;
+
+    let usable = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+
+    usable.then(|| id.to_string())
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id =
+            client_request_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let service = self.service.clone();
+
+        let context = logging::Context::new(request_id.clone());
+
+        Box::pin(logging::scope(context, async move {
+            let mut res = service.call(req).await?;
+
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(logging::REQUEST_ID_HEADER), value);
+            }
+
+            Ok(res)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpResponse, test, web};
+
+    async fn request_id(header: Option<&str>) -> (String, String) {
+        let app = test::init_service(App::new().wrap(RequestIdMiddleware).route(
+            "/",
+            web::get().to(|| async {
+                HttpResponse::Ok().body(logging::request_id().unwrap_or_default())
+            }),
+        ))
+        .await;
+
+        let mut req = test::TestRequest::get().uri("/");
+
+        if let Some(header) = header {
+            req = req.insert_header((logging::REQUEST_ID_HEADER, header));
+        }
+
+        let res = test::call_service(&app, req.to_request()).await;
+
+        let returned = res
+            .headers()
+            .get(logging::REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let logged = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+        (returned, logged)
+    }
+
+    #[actix_web::test]
+    async fn test_requests_are_served_under_their_id() {
+        assert_eq!(
+            request_id(Some("req-1")).await,
+            ("req-1".to_string(), "req-1".to_string())
+        );
+
+        let (returned, logged) = request_id(None).await;
+
+        assert!(uuid::Uuid::parse_str(&returned).is_ok());
+        assert_eq!(returned, logged);
+
+        // Ids that could forge log lines are replaced
+        let (returned, _) = request_id(Some("req-1 INFO admin logged in")).await;
+
+        assert_ne!(returned, "req-1 INFO admin logged in");
+    }
+}
@@ -0,0 +1,358 @@
+//! Verification of the evidence participants attest the platform sealing their shares with,
+//! before they are included in a signing set. The evidence must be bound to a fresh nonce
+//! and carry one of the configured measurements. TPM quotes are verified against the pinned
+//! attestation key, the signatures of SGX quotes and SEV-SNP reports are not checked against
+//! the Intel and AMD certificate chains
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use proto::mpc::{AttestationResponse, SealingPlatform};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::app_config::{AttestationConfig, AttestationPlatform};
+
+static ATTESTATION_DOMAIN: &[u8] = b"mpc-waas-attestation-v1";
+
+/// Offsets of the report body of an SGX DCAP quote, after its 48-byte header
+const SGX_MRENCLAVE: usize = 48 + 64;
+const SGX_REPORT_DATA: usize = 48 + 320;
+const SGX_QUOTE_MIN_LEN: usize = 48 + 384;
+
+/// Offsets of an SEV-SNP attestation report
+const SNP_REPORT_DATA: usize = 0x50;
+const SNP_MEASUREMENT: usize = 0x90;
+const SNP_REPORT_LEN: usize = 0x4a0;
+
+/// `TPM_GENERATED_VALUE` and `TPM_ST_ATTEST_QUOTE` of a TPMS_ATTEST
+const TPM_GENERATED: u32 = 0xff54_4347;
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AttestationError {
+    #[error("participant attested {0:?}")]
+    Platform(SealingPlatform),
+    #[error("malformed evidence: {0}")]
+    Malformed(&'static str),
+    #[error("evidence is not bound to the nonce")]
+    Stale,
+    #[error("measurement {0} is not accepted")]
+    Measurement(String),
+    #[error("invalid TPM quote signature")]
+    Signature,
+}
+
+/// Fresh nonce of an attestation
+pub fn nonce() -> [u8; 32] {
+    let mut nonce = [0; 32];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// Report data the participant puts in its evidence for `nonce`, zero-padded to 64 bytes in
+/// SGX quotes and SEV-SNP reports
+pub fn report_data(nonce: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(ATTESTATION_DOMAIN)
+        .chain_update(nonce)
+        .finalize()
+        .into()
+}
+
+/// Checks the evidence the participant returned for `nonce` against its attestation policy
+pub fn verify(
+    config: &AttestationConfig,
+    nonce: &[u8],
+    response: &AttestationResponse,
+) -> Result<(), AttestationError> {
+    let expected = match config.platform {
+        AttestationPlatform::Tpm => SealingPlatform::Tpm,
+        AttestationPlatform::Sgx => SealingPlatform::Sgx,
+        AttestationPlatform::SevSnp => SealingPlatform::SevSnp,
+    };
+
+    if response.platform() != expected {
+        return Err(AttestationError::Platform(response.platform()));
+    }
+
+    let evidence = &response.evidence;
+
+    let (report, measurement) = match config.platform {
+        AttestationPlatform::Sgx => {
+            if evidence.len() < SGX_QUOTE_MIN_LEN || !matches!(evidence[0..2], [3 | 4, 0]) {
+                return Err(AttestationError::Malformed("not an SGX DCAP quote"));
+            }
+
+            (
+                &evidence[SGX_REPORT_DATA..SGX_REPORT_DATA + 64],
+                &evidence[SGX_MRENCLAVE..SGX_MRENCLAVE + 32],
+            )
+        }
+        AttestationPlatform::SevSnp => {
+            if evidence.len() < SNP_REPORT_LEN {
+                return Err(AttestationError::Malformed("not an SEV-SNP report"));
+            }
+
+            (
+                &evidence[SNP_REPORT_DATA..SNP_REPORT_DATA + 64],
+                &evidence[SNP_MEASUREMENT..SNP_MEASUREMENT + 48],
+            )
+        }
+        AttestationPlatform::Tpm => {
+            let key = config
+                .attestation_key
+                .as_deref()
+                .and_then(|key| VerifyingKey::from_sec1_bytes(key).ok())
+                .ok_or(AttestationError::Signature)?;
+
+            let signature = Signature::from_der(&response.signature)
+                .map_err(|_| AttestationError::Signature)?;
+
+            key.verify(evidence, &signature)
+                .map_err(|_| AttestationError::Signature)?;
+
+            tpm_quote(evidence)?
+        }
+    };
+
+    let expected = report_data(nonce);
+
+    let bound = report.len() >= expected.len()
+        && report[..expected.len()] == expected
+        && report[expected.len()..].iter().all(|byte| *byte == 0);
+
+    if !bound {
+        return Err(AttestationError::Stale);
+    }
+
+    if !config
+        .measurements
+        .iter()
+        .any(|accepted| accepted == measurement)
+    {
+        return Err(AttestationError::Measurement(hex::encode(measurement)));
+    }
+
+    Ok(())
+}
+
+/// Qualifying data and PCR digest of a TPMS_ATTEST quote
+fn tpm_quote(attest: &[u8]) -> Result<(&[u8], &[u8]), AttestationError> {
+    let malformed = || AttestationError::Malformed("not a TPM quote");
+
+    let mut reader = Reader(attest);
+
+    if reader.u32().ok_or_else(malformed)? != TPM_GENERATED
+        || reader.u16().ok_or_else(malformed)? != TPM_ST_ATTEST_QUOTE
+    {
+        return Err(malformed());
+    }
+
+    // qualifiedSigner
+    reader.sized().ok_or_else(malformed)?;
+
+    let extra_data = reader.sized().ok_or_else(malformed)?;
+
+    // clockInfo and firmwareVersion
+    reader.take(17 + 8).ok_or_else(malformed)?;
+
+    let selections = reader.u32().ok_or_else(malformed)?;
+
+    for _ in 0..selections {
+        reader.u16().ok_or_else(malformed)?;
+
+        let size = reader.take(1).ok_or_else(malformed)?[0];
+
+        reader.take(size as usize).ok_or_else(malformed)?;
+    }
+
+    let pcr_digest = reader.sized().ok_or_else(malformed)?;
+
+    Ok((extra_data, pcr_digest))
+}
+
+/// Big-endian fields of a TPM structure
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Some(taken)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    /// TPM2B buffer, prefixed with its size
+    fn sized(&mut self) -> Option<&'a [u8]> {
+        let size = self.u16()?;
+
+        self.take(size as usize)
+    }
+}
+
+/// DCAP quote of an enclave of `measurement` over the report data of `nonce`, unsigned
+#[cfg(test)]
+pub fn sgx_quote(measurement: &[u8; 32], nonce: &[u8]) -> Vec<u8> {
+    let mut quote = vec![0; SGX_QUOTE_MIN_LEN];
+
+    quote[0] = 3;
+    quote[SGX_MRENCLAVE..SGX_MRENCLAVE + 32].copy_from_slice(measurement);
+    quote[SGX_REPORT_DATA..SGX_REPORT_DATA + 32].copy_from_slice(&report_data(nonce));
+
+    quote
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::SigningKey;
+    use p256::ecdsa::signature::Signer;
+
+    fn config(platform: AttestationPlatform, measurement: &[u8]) -> AttestationConfig {
+        AttestationConfig {
+            platform,
+            measurements: vec![measurement.to_vec()],
+            attestation_key: None,
+        }
+    }
+
+    fn response(platform: SealingPlatform, evidence: Vec<u8>) -> AttestationResponse {
+        AttestationResponse {
+            platform: platform.into(),
+            evidence,
+            signature: Vec::new(),
+        }
+    }
+
+    /// TPMS_ATTEST of a quote of PCRs 0 to 7 with `pcr_digest`
+    fn tpm_attest(qualifying_data: &[u8], pcr_digest: &[u8]) -> Vec<u8> {
+        let mut attest = Vec::new();
+
+        attest.extend(TPM_GENERATED.to_be_bytes());
+        attest.extend(TPM_ST_ATTEST_QUOTE.to_be_bytes());
+        attest.extend(4u16.to_be_bytes());
+        attest.extend([0x00, 0x0b, 0xaa, 0xbb]);
+        attest.extend((qualifying_data.len() as u16).to_be_bytes());
+        attest.extend(qualifying_data);
+        attest.extend([0; 17 + 8]);
+        attest.extend(1u32.to_be_bytes());
+        attest.extend(0x000bu16.to_be_bytes());
+        attest.extend([3, 0xff, 0x00, 0x00]);
+        attest.extend((pcr_digest.len() as u16).to_be_bytes());
+        attest.extend(pcr_digest);
+
+        attest
+    }
+
+    #[test]
+    fn test_sgx_quote_bound_to_the_nonce() {
+        let nonce = nonce();
+        let config = config(AttestationPlatform::Sgx, &[7; 32]);
+
+        let quote = sgx_quote(&[7; 32], &nonce);
+        assert_eq!(
+            verify(&config, &nonce, &response(SealingPlatform::Sgx, quote)),
+            Ok(())
+        );
+
+        let replayed = sgx_quote(&[7; 32], b"an older nonce of another probe");
+        assert_eq!(
+            verify(&config, &nonce, &response(SealingPlatform::Sgx, replayed)),
+            Err(AttestationError::Stale)
+        );
+
+        let unsealed = response(SealingPlatform::Unsealed, Vec::new());
+        assert_eq!(
+            verify(&config, &nonce, &unsealed),
+            Err(AttestationError::Platform(SealingPlatform::Unsealed))
+        );
+    }
+
+    #[test]
+    fn test_snp_report_measurement_must_be_accepted() {
+        let nonce = nonce();
+
+        let mut report = vec![0; SNP_REPORT_LEN];
+        report[0] = 2;
+        report[SNP_REPORT_DATA..SNP_REPORT_DATA + 32].copy_from_slice(&report_data(&nonce));
+        report[SNP_MEASUREMENT..SNP_MEASUREMENT + 48].copy_from_slice(&[9; 48]);
+
+        let accepted = config(AttestationPlatform::SevSnp, &[9; 48]);
+        let response = response(SealingPlatform::SevSnp, report);
+        assert_eq!(verify(&accepted, &nonce, &response), Ok(()));
+
+        let other = config(AttestationPlatform::SevSnp, &[8; 48]);
+        assert_eq!(
+            verify(&other, &nonce, &response),
+            Err(AttestationError::Measurement(hex::encode([9; 48])))
+        );
+
+        let truncated = AttestationResponse {
+            evidence: response.evidence[..0x100].to_vec(),
+            ..response
+        };
+        assert!(matches!(
+            verify(&accepted, &nonce, &truncated),
+            Err(AttestationError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_tpm_quote_signed_by_the_attestation_key() {
+        let nonce = nonce();
+        let signing_key = SigningKey::random(&mut OsRng);
+
+        let config = AttestationConfig {
+            attestation_key: Some(
+                signing_key
+                    .verifying_key()
+                    .to_encoded_point(false)
+                    .as_bytes()
+                    .to_vec(),
+            ),
+            ..config(AttestationPlatform::Tpm, &[5; 32])
+        };
+
+        let attest = tpm_attest(&report_data(&nonce), &[5; 32]);
+        let signature: Signature = signing_key.sign(&attest);
+
+        let quote = AttestationResponse {
+            platform: SealingPlatform::Tpm.into(),
+            evidence: attest.clone(),
+            signature: signature.to_der().as_bytes().to_vec(),
+        };
+        assert_eq!(verify(&config, &nonce, &quote), Ok(()));
+
+        let other_key = SigningKey::random(&mut OsRng);
+        let forged: Signature = other_key.sign(&attest);
+        let forged = AttestationResponse {
+            signature: forged.to_der().as_bytes().to_vec(),
+            ..quote.clone()
+        };
+        assert_eq!(
+            verify(&config, &nonce, &forged),
+            Err(AttestationError::Signature)
+        );
+
+        let mut tampered = quote;
+        let last = tampered.evidence.len() - 1;
+        tampered.evidence[last] ^= 1;
+        assert_eq!(
+            verify(&config, &nonce, &tampered),
+            Err(AttestationError::Signature)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Ceremonies of co-signed wallets waiting for the user's device. The key of such a wallet
+//! is shared 2-of-2 between the co-signing participant and the device, which runs a
+//! participant of its own and joins each ceremony once with the message the co-signing
+//! participant was sent
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use prost::Message as _;
+use proto::mpc::{CreateWalletMessage, SignBatchMessage, SignMessage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Participant holding the server share of co-signed wallets
+pub const COSIGNING_PARTICIPANT: usize = 1;
+
+/// Parties of a co-signed key, the co-signing participant and the device
+pub const COSIGNED_PARTIES: u32 = 2;
+
+/// Ceremonies the devices may join, by execution id
+static CEREMONIES: Lazy<RwLock<HashMap<Uuid, Ceremony>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Request the device runs on its participant to take part in a ceremony
+#[derive(Debug, Clone, PartialEq)]
+pub enum CeremonyMessage {
+    Keygen(CreateWalletMessage),
+    Signing(SignMessage),
+    BatchSigning(SignBatchMessage),
+}
+
+impl CeremonyMessage {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CeremonyMessage::Keygen(_) => "keygen",
+            CeremonyMessage::Signing(_) => "signing",
+            CeremonyMessage::BatchSigning(_) => "batch_signing",
+        }
+    }
+
+    pub fn wallet_id(&self) -> i32 {
+        match self {
+            CeremonyMessage::Keygen(message) => message.wallet_id,
+            CeremonyMessage::Signing(message) => message.wallet_id,
+            CeremonyMessage::BatchSigning(message) => message.wallet_id,
+        }
+    }
+
+    /// Transactions the ceremony signs, none for keygens
+    fn transaction_ids(&self) -> Vec<i32> {
+        match self {
+            CeremonyMessage::Keygen(_) => Vec::new(),
+            CeremonyMessage::Signing(message) => vec![message.tx_id],
+            CeremonyMessage::BatchSigning(message) => message.tx_ids.clone(),
+        }
+    }
+
+    /// Protobuf `mpc.CreateWalletMessage`, `mpc.SignMessage` or `mpc.SignBatchMessage`
+    /// of the kind
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            CeremonyMessage::Keygen(message) => message.encode_to_vec(),
+            CeremonyMessage::Signing(message) => message.encode_to_vec(),
+            CeremonyMessage::BatchSigning(message) => message.encode_to_vec(),
+        }
+    }
+}
+
+struct Ceremony {
+    user_id: i32,
+    message: CeremonyMessage,
+    opened_at: DateTime<Utc>,
+    joined: bool,
+}
+
+/// Ceremony of a co-signed wallet as its owner sees it, without the room token
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingCeremony {
+    pub execution_id: Uuid,
+    pub wallet_id: i32,
+    pub kind: &'static str,
+    pub transaction_ids: Vec<i32>,
+    pub opened_at: DateTime<Utc>,
+    /// Whether a device already joined, a ceremony is only joined once
+    pub joined: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JoinError {
+    /// No ceremony of the user runs under the execution id
+    NotFound,
+    AlreadyJoined,
+}
+
+/// Keeps the ceremony open to the device until dropped
+#[must_use]
+pub struct OpenCeremony {
+    execution_id: Uuid,
+}
+
+impl Drop for OpenCeremony {
+    fn drop(&mut self) {
+        CEREMONIES
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.execution_id);
+    }
+}
+
+/// Opens the ceremony of a co-signed wallet of the user to its device
+pub fn open(user_id: i32, execution_id: Uuid, message: CeremonyMessage) -> OpenCeremony {
+    CEREMONIES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(
+            execution_id,
+            Ceremony {
+                user_id,
+                message,
+                opened_at: Utc::now(),
+                joined: false,
+            },
+        );
+
+    OpenCeremony { execution_id }
+}
+
+/// Open ceremonies of the wallets of the user, oldest first
+pub fn pending(user_id: i32) -> Vec<PendingCeremony> {
+    let mut pending = CEREMONIES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(_, ceremony)| ceremony.user_id == user_id)
+        .map(|(execution_id, ceremony)| PendingCeremony {
+            execution_id: *execution_id,
+            wallet_id: ceremony.message.wallet_id(),
+            kind: ceremony.message.kind(),
+            transaction_ids: ceremony.message.transaction_ids(),
+            opened_at: ceremony.opened_at,
+            joined: ceremony.joined,
+        })
+        .collect::<Vec<_>>();
+
+    pending.sort_by_key(|ceremony| ceremony.opened_at);
+
+    pending
+}
+
+/// Message of the ceremony for the device of the user, given out once so a second device
+/// can't take its place
+pub fn join(user_id: i32, execution_id: &Uuid) -> Result<CeremonyMessage, JoinError> {
+    let mut ceremonies = CEREMONIES.write().unwrap_or_else(|e| e.into_inner());
+
+    let ceremony = ceremonies
+        .get_mut(execution_id)
+        .filter(|ceremony| ceremony.user_id == user_id)
+        .ok_or(JoinError::NotFound)?;
+
+    if ceremony.joined {
+        return Err(JoinError::AlreadyJoined);
+    }
+
+    ceremony.joined = true;
+
+    Ok(ceremony.message.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keygen(wallet_id: i32) -> CeremonyMessage {
+        CeremonyMessage::Keygen(CreateWalletMessage {
+            wallet_id,
+            parties: COSIGNED_PARTIES,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_ceremony_is_joined_once() {
+        let execution_id = Uuid::new_v4();
+        let _ceremony = open(-1, execution_id, keygen(7));
+
+        assert_eq!(join(-1, &execution_id), Ok(keygen(7)));
+        assert_eq!(join(-1, &execution_id), Err(JoinError::AlreadyJoined));
+
+        let pending = pending(-1);
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].kind, pending[0].joined), ("keygen", true));
+    }
+
+    #[test]
+    fn test_ceremonies_are_private_to_their_user() {
+        let execution_id = Uuid::new_v4();
+        let _ceremony = open(-2, execution_id, keygen(8));
+
+        assert!(pending(-3).is_empty());
+        assert_eq!(join(-3, &execution_id), Err(JoinError::NotFound));
+    }
+
+    #[test]
+    fn test_dropped_ceremony_closes() {
+        let execution_id = Uuid::new_v4();
+        let ceremony = open(-4, execution_id, keygen(9));
+
+        drop(ceremony);
+
+        assert!(pending(-4).is_empty());
+        assert_eq!(join(-4, &execution_id), Err(JoinError::NotFound));
+    }
+}
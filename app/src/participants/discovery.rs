@@ -0,0 +1,482 @@
+//! Participants discovered through DNS SRV records, e.g. the
+//! `_grpc._tcp.participant-1.mpc.svc.cluster.local` record of a Kubernetes headless service.
+//! The records are resolved again periodically and the channel of the participant moves to
+//! the new target, so rescheduled pods are followed without restarting the app
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{sleep, timeout};
+use tonic::transport::channel::Change;
+use tonic::transport::{Channel, Endpoint};
+
+/// Time the nameserver has to answer a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bytes of the UDP answers accepted, advertised with EDNS
+const UDP_PAYLOAD_SIZE: u16 = 4096;
+
+/// Endpoint changes the channel holds. The channel only takes them in once called, moves of
+/// an idle participant wait for its next call past this
+const PENDING_CHANGES: usize = 16;
+
+const TYPE_SRV: u16 = 33;
+const TYPE_OPT: u16 = 41;
+const CLASS_IN: u16 = 1;
+
+/// Target of an SRV record
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    host: String,
+}
+
+impl Target {
+    fn uri(&self) -> String {
+        format!("http://{}:{}", self.host, self.port)
+    }
+}
+
+/// Target a participant is reached at, of the lowest priority and then the highest weight.
+/// A participant is a single party, its records are never balanced
+fn preferred(targets: &[Target]) -> Option<&Target> {
+    targets.iter().min_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then(b.weight.cmp(&a.weight))
+            .then(a.host.cmp(&b.host))
+            .then(a.port.cmp(&b.port))
+    })
+}
+
+/// First nameserver of `/etc/resolv.conf`, on port 53
+pub fn system_nameserver() -> Result<SocketAddr> {
+    let conf =
+        std::fs::read_to_string("/etc/resolv.conf").context("Failed to read /etc/resolv.conf")?;
+
+    let address = conf
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(str::trim)
+        .find_map(|address| address.parse::<std::net::IpAddr>().ok())
+        .ok_or_else(|| anyhow!("No nameserver in /etc/resolv.conf"))?;
+
+    Ok(SocketAddr::new(address, 53))
+}
+
+/// Channel of the participant the SRV record `name` points to, following the record as
+/// it changes once `run`
+pub struct Discovery {
+    name: String,
+    nameserver: SocketAddr,
+    interval: Duration,
+    changes: Sender<Change<String, Endpoint>>,
+    current: String,
+}
+
+impl Discovery {
+    /// Resolves the record once, failing when it has no target so a participant is never
+    /// left without an endpoint
+    pub async fn new(
+        name: &str,
+        nameserver: SocketAddr,
+        interval: Duration,
+    ) -> Result<(Channel, Self)> {
+        let target = resolve(name, nameserver).await?;
+        let uri = target.uri();
+
+        let (channel, changes) = Channel::balance_channel(PENDING_CHANGES);
+        changes
+            .send(Change::Insert(
+                uri.clone(),
+                Endpoint::from_shared(uri.clone())?,
+            ))
+            .await?;
+
+        log::info!("Discovered participant {name} at {uri}");
+
+        Ok((
+            channel,
+            Self {
+                name: name.to_string(),
+                nameserver,
+                interval,
+                changes,
+                current: uri,
+            },
+        ))
+    }
+
+    /// Resolves the record every interval, a failed resolution keeps the current target
+    pub async fn run(mut self) {
+        loop {
+            sleep(self.interval).await;
+
+            match resolve(&self.name, self.nameserver).await {
+                Ok(target) => {
+                    if let Err(err) = self.follow(target.uri()).await {
+                        log::error!("Failed to move participant {}: {err}", self.name);
+                    }
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Failed to resolve participant {}, staying at {}: {err}",
+                        self.name,
+                        self.current
+                    );
+                }
+            }
+        }
+    }
+
+    async fn follow(&mut self, uri: String) -> Result<()> {
+        if uri == self.current {
+            return Ok(());
+        }
+
+        // Inserted first so calls always have an endpoint to go to
+        self.changes
+            .send(Change::Insert(
+                uri.clone(),
+                Endpoint::from_shared(uri.clone())?,
+            ))
+            .await?;
+        self.changes
+            .send(Change::Remove(self.current.clone()))
+            .await?;
+
+        log::info!(
+            "Participant {} moved from {} to {uri}",
+            self.name,
+            self.current
+        );
+
+        self.current = uri;
+
+        Ok(())
+    }
+}
+
+/// Preferred target of the SRV record
+async fn resolve(name: &str, nameserver: SocketAddr) -> Result<Target> {
+    let targets = query_srv(name, nameserver).await?;
+
+    preferred(&targets)
+        .cloned()
+        .ok_or_else(|| anyhow!("SRV record {name} has no target"))
+}
+
+async fn query_srv(name: &str, nameserver: SocketAddr) -> Result<Vec<Target>> {
+    let bind = match nameserver {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(nameserver).await?;
+
+    let id = rand_id();
+    socket.send(&query(id, name)?).await?;
+
+    let mut buffer = vec![0; usize::from(UDP_PAYLOAD_SIZE)];
+
+    loop {
+        let len = timeout(QUERY_TIMEOUT, socket.recv(&mut buffer))
+            .await
+            .map_err(|_| anyhow!("Nameserver {nameserver} didn't answer"))??;
+
+        // Late answers of other queries are skipped
+        if len >= 2 && u16::from_be_bytes([buffer[0], buffer[1]]) == id {
+            return parse_srv(&buffer[..len], id);
+        }
+    }
+}
+
+fn rand_id() -> u16 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// Recursive SRV query of `name`, advertising the UDP payload size accepted
+fn query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut message = Vec::with_capacity(64);
+
+    message.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired
+    message.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question and the OPT record
+    for count in [1u16, 0, 0, 1] {
+        message.extend_from_slice(&count.to_be_bytes());
+    }
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("Invalid SRV name {name}");
+        }
+
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    // OPT record of the root name, its class is the payload size
+    message.push(0);
+    message.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    message.extend_from_slice(&UDP_PAYLOAD_SIZE.to_be_bytes());
+    message.extend_from_slice(&[0; 6]);
+
+    Ok(message)
+}
+
+/// Reads a DNS message, failing on truncation past its end
+struct Reader<'a> {
+    message: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .message
+            .get(self.position..self.position + len)
+            .ok_or_else(|| anyhow!("Truncated DNS answer"))?;
+
+        self.position += len;
+
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Name at the position, following compression pointers
+    fn name(&mut self) -> Result<String> {
+        let mut labels = Vec::new();
+        let mut position = self.position;
+        let mut end = None;
+
+        // Each pointer goes back, more of them than bytes is a loop
+        for _ in 0..self.message.len() {
+            let len = *self
+                .message
+                .get(position)
+                .ok_or_else(|| anyhow!("Truncated DNS name"))?;
+
+            match len {
+                0 => {
+                    self.position = end.unwrap_or(position + 1);
+                    return Ok(labels.join("."));
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let low = *self
+                        .message
+                        .get(position + 1)
+                        .ok_or_else(|| anyhow!("Truncated DNS name"))?;
+
+                    end.get_or_insert(position + 2);
+                    position = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+                }
+                len => {
+                    let label = self
+                        .message
+                        .get(position + 1..position + 1 + usize::from(len))
+                        .ok_or_else(|| anyhow!("Truncated DNS name"))?;
+
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    position += 1 + usize::from(len);
+                }
+            }
+        }
+
+        Err(anyhow!("DNS name compression loops"))
+    }
+}
+
+/// SRV targets of the answer to query `id`, none when the name doesn't exist
+fn parse_srv(message: &[u8], id: u16) -> Result<Vec<Target>> {
+    let mut reader = Reader {
+        message,
+        position: 0,
+    };
+
+    if reader.u16()? != id {
+        bail!("DNS answer of another query");
+    }
+
+    let flags = reader.u16()?;
+
+    if flags & 0x8000 == 0 {
+        bail!("DNS message isn't an answer");
+    }
+
+    if flags & 0x0200 != 0 {
+        bail!("DNS answer is truncated");
+    }
+
+    match flags & 0x000f {
+        0 => {}
+        // The name doesn't exist
+        3 => return Ok(Vec::new()),
+        rcode => bail!("Nameserver failed with code {rcode}"),
+    }
+
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    reader.bytes(4)?;
+
+    for _ in 0..questions {
+        reader.name()?;
+        reader.bytes(4)?;
+    }
+
+    let mut targets = Vec::new();
+
+    for _ in 0..answers {
+        reader.name()?;
+        let kind = reader.u16()?;
+        reader.bytes(6)?;
+        let len = usize::from(reader.u16()?);
+        let end = reader.position + len;
+
+        // CNAMEs of the name come along with the records
+        if kind == TYPE_SRV {
+            let priority = reader.u16()?;
+            let weight = reader.u16()?;
+            let port = reader.u16()?;
+            let host = reader.name()?;
+
+            targets.push(Target {
+                priority,
+                weight,
+                port,
+                host,
+            });
+        }
+
+        reader.position = end;
+    }
+
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer to `query` with the SRV records of `(priority, weight, port, host)`, their
+    /// names compressed to the question
+    fn answer(query: &[u8], records: &[(u16, u16, u16, &str)]) -> Vec<u8> {
+        // Header and question, without the OPT record
+        let question_end = query.len() - 11;
+        let mut message = query[..question_end].to_vec();
+
+        message[2] = 0x81;
+        message[3] = 0x80;
+        message[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        message[10..12].copy_from_slice(&0u16.to_be_bytes());
+
+        for (priority, weight, port, host) in records {
+            // Pointer to the name of the question
+            message.extend_from_slice(&[0xc0, 12]);
+            message.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            message.extend_from_slice(&CLASS_IN.to_be_bytes());
+            message.extend_from_slice(&30u32.to_be_bytes());
+
+            let mut rdata = Vec::new();
+            rdata.extend_from_slice(&priority.to_be_bytes());
+            rdata.extend_from_slice(&weight.to_be_bytes());
+            rdata.extend_from_slice(&port.to_be_bytes());
+            for label in host.split('.') {
+                rdata.push(label.len() as u8);
+                rdata.extend_from_slice(label.as_bytes());
+            }
+            rdata.push(0);
+
+            message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            message.extend_from_slice(&rdata);
+        }
+
+        message
+    }
+
+    #[test]
+    fn test_parse_srv_answer() {
+        let query = query(7, "_grpc._tcp.participant-1.mpc.svc.cluster.local.").unwrap();
+        let message = answer(
+            &query,
+            &[
+                (10, 5, 50051, "pod-a.participant-1.mpc.svc.cluster.local"),
+                (0, 1, 50052, "pod-b.participant-1.mpc.svc.cluster.local"),
+            ],
+        );
+
+        let targets = parse_srv(&message, 7).unwrap();
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(
+            preferred(&targets).unwrap().uri(),
+            "http://pod-b.participant-1.mpc.svc.cluster.local:50052"
+        );
+
+        assert!(parse_srv(&message, 8).is_err());
+        assert!(parse_srv(&message[..message.len() - 3], 7).is_err());
+    }
+
+    #[test]
+    fn test_missing_name_has_no_targets() {
+        let query = query(7, "_grpc._tcp.missing.local").unwrap();
+        let mut message = answer(&query, &[]);
+        message[3] = 0x83;
+
+        assert!(parse_srv(&message, 7).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compression_loops_fail() {
+        let mut message = vec![0, 7, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(&[0xc0, 12]);
+
+        assert!(parse_srv(&message, 7).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_discovery_follows_the_record() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = server.local_addr().unwrap();
+        let name = "_grpc._tcp.participant-1.local";
+
+        let serve = |port: u16| {
+            let server = &server;
+
+            async move {
+                let mut buffer = [0; 512];
+                let (len, peer) = server.recv_from(&mut buffer).await.unwrap();
+                let message = answer(&buffer[..len], &[(0, 0, port, "localhost")]);
+                server.send_to(&message, peer).await.unwrap();
+            }
+        };
+
+        let (discovered, _) = futures::future::join(
+            Discovery::new(name, nameserver, Duration::from_secs(30)),
+            serve(50051),
+        )
+        .await;
+        let (_channel, mut discovery) = discovered.unwrap();
+
+        assert_eq!(discovery.current, "http://localhost:50051");
+
+        let (target, _) = futures::future::join(resolve(name, nameserver), serve(50052)).await;
+        discovery.follow(target.unwrap().uri()).await.unwrap();
+
+        assert_eq!(discovery.current, "http://localhost:50052");
+    }
+}
@@ -0,0 +1,319 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use proto::mpc::{
+    AttestationResponse, CreateWalletMessage, ExportShareBackupMessage, HasShareResponse,
+    ImportWalletMessage, KeygenEvent, KeygenProgress, KeygenStage, SealingPlatform,
+    ShareBackupMessage, SignBatchMessage, SignMessage, SignatureBatchMessage, SignatureMessage,
+    WalletCreatedMessage, WalletInfoMessage, WalletInfoResponse, keygen_event,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tonic::Status;
+use tonic_health::pb::health_check_response::ServingStatus;
+
+use super::attestation::sgx_quote;
+use super::pool::{KeygenEvents, ParticipantPool};
+use crate::config::app_config::AttestationConfig;
+
+/// Public key every mock keygen returns, the one of the secp256k1 private key 1
+pub const PUBLIC_KEY: &str = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+                              483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+
+/// Child key every mock derivation returns whatever the path, the one of the private key 2
+pub const CHILD_PUBLIC_KEY: &str = "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+                                    1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a";
+
+#[derive(Default)]
+struct Participant {
+    down: bool,
+    /// Error every ceremony call fails with
+    error: Option<Status>,
+    /// Keygen index of each wallet share held
+    shares: HashMap<i32, u32>,
+    /// Measurement of the enclave the participant runs in, unsealed when unset
+    enclave: Option<[u8; 32]>,
+}
+
+/// In-process participants holding shares in memory, for testing the code running
+/// ceremonies without gRPC servers
+pub struct MockParticipants {
+    participants: Mutex<Vec<Participant>>,
+    primaries: usize,
+    attestation: HashMap<usize, AttestationConfig>,
+}
+
+impl MockParticipants {
+    pub fn new(count: usize) -> Self {
+        Self {
+            participants: Mutex::new((0..count).map(|_| Participant::default()).collect()),
+            primaries: count,
+            attestation: HashMap::new(),
+        }
+    }
+
+    /// Adds `count` standbys, numbered after the participants
+    pub fn with_standbys(self, count: usize) -> Self {
+        self.participants
+            .lock()
+            .unwrap()
+            .extend((0..count).map(|_| Participant::default()));
+        self
+    }
+
+    /// Gives the participant a share of the wallet generated under `keygen_index`
+    pub fn with_share(self, participant: usize, wallet_id: i32, keygen_index: u32) -> Self {
+        self.update(participant, |p| {
+            p.shares.insert(wallet_id, keygen_index);
+        });
+        self
+    }
+
+    /// Attests the participant with `config` before it signs
+    pub fn attested(mut self, participant: usize, config: AttestationConfig) -> Self {
+        self.attestation.insert(participant, config);
+        self
+    }
+
+    /// Runs the participant in an SGX enclave of `measurement`
+    pub fn in_enclave(self, participant: usize, measurement: [u8; 32]) -> Self {
+        self.update(participant, |p| p.enclave = Some(measurement));
+        self
+    }
+
+    /// Makes the participant report it isn't serving
+    pub fn down(self, participant: usize) -> Self {
+        self.update(participant, |p| p.down = true);
+        self
+    }
+
+    /// Makes every ceremony call of the participant fail with `error`
+    pub fn failing(self, participant: usize, error: Status) -> Self {
+        self.update(participant, |p| p.error = Some(error));
+        self
+    }
+
+    pub fn has_share_of(&self, participant: usize, wallet_id: i32) -> bool {
+        self.read(participant, |p| p.shares.contains_key(&wallet_id))
+    }
+
+    fn update(&self, participant: usize, update: impl FnOnce(&mut Participant)) {
+        update(&mut self.participants.lock().unwrap()[participant - 1]);
+    }
+
+    fn read<T>(&self, participant: usize, read: impl FnOnce(&Participant) -> T) -> T {
+        read(&self.participants.lock().unwrap()[participant - 1])
+    }
+
+    /// Runs a ceremony call, failing with the configured error
+    fn call<T>(
+        &self,
+        participant: usize,
+        call: impl FnOnce(&mut Participant) -> Result<T, Status>,
+    ) -> Result<T, Status> {
+        let mut participants = self.participants.lock().unwrap();
+
+        let participant = participants
+            .get_mut(participant.wrapping_sub(1))
+            .ok_or_else(|| Status::not_found(format!("No participant {participant}")))?;
+
+        if participant.down {
+            return Err(Status::unavailable("Participant is down"));
+        }
+
+        if let Some(error) = &participant.error {
+            return Err(error.clone());
+        }
+
+        call(participant)
+    }
+}
+
+#[async_trait]
+impl ParticipantPool for MockParticipants {
+    fn count(&self) -> usize {
+        self.participants.lock().unwrap().len()
+    }
+
+    fn primaries(&self) -> usize {
+        self.primaries
+    }
+
+    async fn health(&self, participant: usize) -> Result<ServingStatus, Status> {
+        match self.read(participant, |p| p.down) {
+            true => Ok(ServingStatus::NotServing),
+            false => Ok(ServingStatus::Serving),
+        }
+    }
+
+    fn attestation(&self, participant: usize) -> Option<&AttestationConfig> {
+        self.attestation.get(&participant)
+    }
+
+    async fn attest(
+        &self,
+        participant: usize,
+        nonce: Vec<u8>,
+    ) -> Result<AttestationResponse, Status> {
+        self.call(participant, |p| {
+            let measurement = p
+                .enclave
+                .ok_or_else(|| Status::failed_precondition("Participant runs without sealing"))?;
+
+            Ok(AttestationResponse {
+                platform: SealingPlatform::Sgx.into(),
+                evidence: sgx_quote(&measurement, &nonce),
+                signature: Vec::new(),
+            })
+        })
+    }
+
+    async fn create_wallet(
+        &self,
+        participant: usize,
+        message: CreateWalletMessage,
+    ) -> Result<KeygenEvents, Status> {
+        self.call(participant, |p| {
+            p.shares.insert(message.wallet_id, participant as u32 - 1);
+
+            let progress = KeygenProgress {
+                stage: KeygenStage::ThresholdKeygen.into(),
+                round: 1,
+            };
+
+            let created = WalletCreatedMessage {
+                public_key: hex::decode(PUBLIC_KEY).unwrap(),
+            };
+
+            let events = [
+                keygen_event::Event::Progress(progress),
+                keygen_event::Event::Created(created),
+            ]
+            .map(|event| Ok(KeygenEvent { event: Some(event) }));
+
+            Ok(futures::stream::iter(events).boxed())
+        })
+    }
+
+    async fn import_wallet(
+        &self,
+        participant: usize,
+        message: ImportWalletMessage,
+    ) -> Result<WalletCreatedMessage, Status> {
+        self.call(participant, |p| {
+            p.shares.insert(message.wallet_id, participant as u32 - 1);
+
+            Ok(WalletCreatedMessage {
+                public_key: hex::decode(PUBLIC_KEY).unwrap(),
+            })
+        })
+    }
+
+    async fn delete_wallet(&self, participant: usize, wallet_id: i32) -> Result<(), Status> {
+        self.call(participant, |p| {
+            p.shares.remove(&wallet_id);
+            Ok(())
+        })
+    }
+
+    async fn export_share(
+        &self,
+        participant: usize,
+        message: ExportShareBackupMessage,
+    ) -> Result<ShareBackupMessage, Status> {
+        self.call(participant, |p| {
+            if !p.shares.contains_key(&message.wallet_id) {
+                return Err(Status::not_found("Wallet not found"));
+            }
+
+            // Not encrypted, the mock holds no actual share
+            Ok(ShareBackupMessage {
+                index: participant as u32 - 1,
+                ephemeral_key: message.public_key,
+                nonce: vec![0; 12],
+                ciphertext: message.wallet_id.to_be_bytes().to_vec(),
+            })
+        })
+    }
+
+    async fn has_share(
+        &self,
+        participant: usize,
+        wallet_id: i32,
+    ) -> Result<HasShareResponse, Status> {
+        self.call(participant, |p| {
+            let keygen_index = p.shares.get(&wallet_id).copied();
+
+            Ok(HasShareResponse {
+                present: keygen_index.is_some(),
+                keygen_index: keygen_index.unwrap_or_default(),
+            })
+        })
+    }
+
+    async fn wallet_info(
+        &self,
+        participant: usize,
+        message: WalletInfoMessage,
+    ) -> Result<WalletInfoResponse, Status> {
+        self.call(participant, |p| {
+            let keygen_index = p
+                .shares
+                .get(&message.wallet_id)
+                .copied()
+                .ok_or_else(|| Status::not_found("Wallet not found"))?;
+
+            let public_key = match message.derivation_path.is_empty() {
+                true => PUBLIC_KEY,
+                false => CHILD_PUBLIC_KEY,
+            };
+
+            Ok(WalletInfoResponse {
+                public_key: hex::decode(public_key).unwrap(),
+                curve: "secp256k1".to_string(),
+                threshold: 2,
+                parties: vec![0, 1, 2],
+                keygen_index,
+                chain_code: Vec::new(),
+            })
+        })
+    }
+
+    async fn sign(
+        &self,
+        participant: usize,
+        message: SignMessage,
+    ) -> Result<SignatureMessage, Status> {
+        self.call(participant, |p| {
+            if !p.shares.contains_key(&message.wallet_id) {
+                return Err(Status::not_found("Wallet not found"));
+            }
+
+            Ok(signature(message.tx_id))
+        })
+    }
+
+    async fn sign_batch(
+        &self,
+        participant: usize,
+        message: SignBatchMessage,
+    ) -> Result<SignatureBatchMessage, Status> {
+        self.call(participant, |p| {
+            if !p.shares.contains_key(&message.wallet_id) {
+                return Err(Status::not_found("Wallet not found"));
+            }
+
+            Ok(SignatureBatchMessage {
+                signatures: message.tx_ids.iter().copied().map(signature).collect(),
+            })
+        })
+    }
+}
+
+/// Signature the participants agree on for the transaction, not a valid one
+fn signature(tx_id: i32) -> SignatureMessage {
+    SignatureMessage {
+        r: vec![1; 32],
+        s: tx_id.to_be_bytes().repeat(8),
+        v: 0,
+    }
+}
@@ -0,0 +1,1048 @@
+use actix_web::rt::time::timeout;
+use alloy::primitives::Address;
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use futures::future::join_all;
+use prost::Message;
+use proto::mpc::{
+    CreateWalletMessage, ErrorCode, ErrorDetail, ExportApproval, ExportShareBackupMessage,
+    ImportWalletMessage, ShareBackupMessage, WalletCreatedMessage, WalletInfoMessage,
+    WalletInfoResponse, keygen_event,
+};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use tonic::{Code, Status};
+use tonic_health::pb::health_check_response::ServingStatus;
+use uuid::Uuid;
+
+use crate::chains::{encode_base58, taproot_address};
+use crate::config::app_config::AttestationConfig;
+use crate::db::models::{AddressType, Chain, WalletModel};
+use crate::siem::{self, Outcome, SecurityEvent};
+use cosigner::{COSIGNED_PARTIES, COSIGNING_PARTICIPANT, CeremonyMessage};
+
+pub mod attestation;
+pub mod cosigner;
+mod discovery;
+#[cfg(test)]
+pub mod mock;
+mod pool;
+pub mod progress;
+mod retry;
+pub mod rooms;
+
+pub use discovery::{Discovery, system_nameserver};
+pub use pool::{GrpcParticipants, ParticipantPool};
+pub use retry::{RetryPolicy, is_transient};
+
+/// Participants holding a share of the wallet, standbys aside. Co-signed wallets have a
+/// single one, the device holds the other share
+pub fn share_holders(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+) -> RangeInclusive<usize> {
+    match wallet.cosigner {
+        true => COSIGNING_PARTICIPANT..=COSIGNING_PARTICIPANT,
+        false => 1..=participants.primaries(),
+    }
+}
+
+/// Shares of the wallet that must agree on its key before it is trusted, the single one the
+/// server holds of a co-signed wallet
+pub fn key_quorum(wallet: &WalletModel) -> usize {
+    match wallet.cosigner {
+        true => 1,
+        false => SIGNING_THRESHOLD,
+    }
+}
+
+/// Follows the events a participant streams for a keygen, recording its progress, until
+/// the created wallet
+async fn follow_keygen(
+    participants: &dyn ParticipantPool,
+    message: CreateWalletMessage,
+    participant: usize,
+) -> Result<WalletCreatedMessage, Status> {
+    let wallet_id = message.wallet_id;
+    let mut events = participants.create_wallet(participant, message).await?;
+
+    while let Some(event) = events.next().await.transpose()? {
+        match event.event {
+            Some(keygen_event::Event::Progress(update)) => {
+                progress::record(wallet_id, participant, &update);
+            }
+            Some(keygen_event::Event::Created(created)) => return Ok(created),
+            None => {}
+        }
+    }
+
+    Err(Status::internal("Keygen stream ended without a wallet"))
+}
+
+/// Runs a keygen ceremony for the wallet on every participant but the standbys, its progress can be read with
+/// `progress::keygen_progress` until it ends. The keygen of a co-signed wallet runs on the
+/// co-signing participant and is open to the user's device meanwhile
+pub async fn run_keygen(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+) -> Vec<Result<WalletCreatedMessage, Status>> {
+    // Must be unique for all participants
+    let execution_id = Uuid::new_v4();
+    let room_token = rooms::room_token(&execution_id);
+
+    let message = CreateWalletMessage {
+        wallet_id: wallet.id,
+        chain: wallet.chain.clone().into(),
+        execution_id: execution_id.as_bytes().to_vec(),
+        namespace: wallet.namespace.clone(),
+        room_token: room_token.clone(),
+        scheme: wallet.signature_scheme().into(),
+        overwrite: false,
+        parties: match wallet.cosigner {
+            true => COSIGNED_PARTIES,
+            false => 0,
+        },
+    };
+
+    let _ceremony = wallet.cosigner.then(|| {
+        cosigner::open(
+            wallet.user_id,
+            execution_id,
+            CeremonyMessage::Keygen(message.clone()),
+        )
+    });
+
+    let policy = RetryPolicy::current();
+
+    let futures = share_holders(participants, wallet).map(|participant| {
+        let message = &message;
+
+        async move {
+            policy
+                .run(|| follow_keygen(participants, message.clone(), participant))
+                .await
+                .inspect_err(|err| {
+                    log::error!("Failed to create wallet on participant: {err}");
+                })
+        }
+    });
+
+    let results = join_all(futures).await;
+
+    progress::finish(wallet.id);
+
+    results
+}
+
+/// Participant given the private key of an import, which deals the shares of the others
+const IMPORT_DEALER: usize = 1;
+
+/// Shares the private key of the wallet between the participants, dealt by the first one.
+/// Only the dealer's message holds the key, results are returned like keygens
+pub async fn run_import(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    private_key: &[u8],
+) -> Vec<Result<WalletCreatedMessage, Status>> {
+    let execution_id = Uuid::new_v4();
+    let room_token = rooms::room_token(&execution_id);
+
+    let message = ImportWalletMessage {
+        wallet_id: wallet.id,
+        chain: wallet.chain.clone().into(),
+        execution_id: execution_id.as_bytes().to_vec(),
+        namespace: wallet.namespace.clone(),
+        room_token,
+        scheme: wallet.signature_scheme().into(),
+        private_key: Vec::new(),
+    };
+
+    let policy = RetryPolicy::current();
+
+    let futures = (1..=participants.primaries()).map(|participant| {
+        let message = &message;
+
+        async move {
+            let message = || {
+                let mut message = message.clone();
+
+                if participant == IMPORT_DEALER {
+                    message.private_key = private_key.to_vec();
+                }
+
+                message
+            };
+
+            policy
+                .run(|| participants.import_wallet(participant, message()))
+                .await
+                .inspect_err(|err| {
+                    log::error!("Failed to import wallet on participant: {err}");
+                })
+        }
+    });
+
+    join_all(futures).await
+}
+
+/// Collects the share of the wallet of every participant, each encrypted to `public_key`
+/// once it verified the approvals of the export. Standbys hold copies of the same shares
+pub async fn run_export(
+    participants: &dyn ParticipantPool,
+    wallet_id: i32,
+    export_id: i32,
+    public_key: &[u8],
+    approvals: Vec<ExportApproval>,
+) -> Vec<Result<ShareBackupMessage, Status>> {
+    let message = ExportShareBackupMessage {
+        wallet_id,
+        public_key: public_key.to_vec(),
+        export_id,
+        approvals,
+    };
+
+    let policy = RetryPolicy::current();
+
+    let futures = (1..=participants.primaries()).map(|participant| {
+        let message = &message;
+
+        async move {
+            policy
+                .run(|| participants.export_share(participant, message.clone()))
+                .await
+                .inspect_err(|err| {
+                    log::error!("Failed to export share of participant {participant}: {err}");
+                })
+        }
+    });
+
+    join_all(futures).await
+}
+
+/// Whether a participant may hold a share after its keygen call, only calls it never
+/// started are known to have left nothing behind
+pub fn may_hold_share<T>(result: &Result<T, Status>) -> bool {
+    !matches!(result, Err(status) if is_transient(status))
+}
+
+/// Address of the key returned by the successful keygens, `None` for P2WPKH wallets
+///
+/// Fails when the participants disagree on the key, their shares could never sign together.
+pub fn keygen_address(
+    chain: &Chain,
+    address_type: Option<AddressType>,
+    results: &[Result<WalletCreatedMessage, Status>],
+) -> Result<Option<String>> {
+    let mut keys = results
+        .iter()
+        .filter_map(|res| res.as_ref().ok())
+        .map(|res| res.public_key.as_slice());
+
+    let public_key = keys
+        .next()
+        .ok_or_else(|| anyhow!("No participant returned a public key"))?;
+
+    if keys.any(|key| key != public_key) {
+        return Err(anyhow!("Participants returned different public keys"));
+    }
+
+    key_address(chain, address_type, public_key)
+}
+
+/// Address of a public key encoded like keygens return it, `None` for P2WPKH wallets
+fn key_address(
+    chain: &Chain,
+    address_type: Option<AddressType>,
+    public_key: &[u8],
+) -> Result<Option<String>> {
+    match chain {
+        // Uncompressed SEC1 keys are the 0x04 tag followed by both coordinates
+        Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon => {
+            match public_key {
+                [0x04, coordinates @ ..] if coordinates.len() == 64 => {
+                    Ok(Some(Address::from_raw_public_key(coordinates).to_string()))
+                }
+                _ => Err(anyhow!("Invalid public key returned by keygen")),
+            }
+        }
+        // Solana addresses are the base58 Ed25519 public key
+        Chain::Solana if public_key.len() == 32 => Ok(Some(encode_base58(public_key))),
+        Chain::Solana => Err(anyhow!("Invalid public key returned by keygen")),
+        // Taproot keygens return the x-only output key
+        Chain::Bitcoin => match (address_type, <&[u8; 32]>::try_from(public_key)) {
+            (Some(AddressType::P2tr), Ok(output_key)) => Ok(Some(taproot_address(output_key))),
+            (Some(AddressType::P2tr), Err(_)) => {
+                Err(anyhow!("Invalid public key returned by keygen"))
+            }
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Reads the key of the wallet share from every participant, standbys aside, in participant
+/// order, the shares are compared without running a signing round. A `derivation_path` reads the
+/// child key at the path instead
+pub async fn wallet_infos(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+    derivation_path: &[u32],
+) -> Vec<Result<WalletInfoResponse, Status>> {
+    let message = WalletInfoMessage {
+        wallet_id: wallet.id,
+        chain: wallet.chain.clone().into(),
+        scheme: wallet.signature_scheme().into(),
+        derivation_path: derivation_path.to_vec(),
+    };
+
+    let futures = share_holders(participants, wallet).map(|participant| {
+        let message = &message;
+
+        async move {
+            participants
+                .wallet_info(participant, message.clone())
+                .await
+                .inspect_err(|err| {
+                    log::error!(
+                        "Failed to read wallet {} info on participant: {err}",
+                        message.wallet_id
+                    );
+                })
+        }
+    });
+
+    join_all(futures).await
+}
+
+/// Master public key, SEC1 compressed, and chain code of an HD key
+pub struct ExtendedKey {
+    pub public_key: [u8; 33],
+    pub chain_code: [u8; 32],
+}
+
+/// Extended key the participants read the shares of, `None` for keys without a chain code
+/// or on another curve than secp256k1, the only one with BIP-32 derivation
+///
+/// Fails when the participants disagree on the key.
+pub fn extended_key(infos: &[WalletInfoResponse]) -> Result<Option<ExtendedKey>> {
+    let first = infos
+        .first()
+        .ok_or_else(|| anyhow!("No participant holds a share"))?;
+
+    if infos.iter().any(|info| {
+        info.public_key != first.public_key
+            || info.curve != first.curve
+            || info.chain_code != first.chain_code
+    }) {
+        return Err(anyhow!("Participants hold shares of different keys"));
+    }
+
+    if first.curve != "secp256k1" || first.chain_code.is_empty() {
+        return Ok(None);
+    }
+
+    let chain_code = <[u8; 32]>::try_from(first.chain_code.as_slice())
+        .map_err(|_| anyhow!("Invalid chain code"))?;
+
+    let public_key = match first.public_key.as_slice() {
+        [0x04, x @ .., last] if x.len() == 63 => {
+            let mut compressed = [0u8; 33];
+            compressed[0] = 0x02 | (last & 1);
+            compressed[1..].copy_from_slice(&x[..32]);
+            compressed
+        }
+        _ => return Err(anyhow!("Invalid public key returned by participant")),
+    };
+
+    Ok(Some(ExtendedKey {
+        public_key,
+        chain_code,
+    }))
+}
+
+/// Address of the child key the participants read at the derivation path of an account
+///
+/// Fails when the participants disagree on the key, or for keys without addresses.
+pub fn account_address(wallet: &WalletModel, infos: &[WalletInfoResponse]) -> Result<String> {
+    let first = infos
+        .first()
+        .ok_or_else(|| anyhow!("No participant holds a share"))?;
+
+    if infos.iter().any(|info| info.public_key != first.public_key) {
+        return Err(anyhow!("Participants derived different child keys"));
+    }
+
+    key_address(&wallet.chain, wallet.address_type, &first.public_key)?
+        .ok_or_else(|| anyhow!("Child keys of the wallet have no address"))
+}
+
+/// Checks the participants hold shares of one key, each under its own keygen index, and
+/// that the key is the one of the wallet address
+pub fn verify_shares(wallet: &WalletModel, infos: &[WalletInfoResponse]) -> Result<()> {
+    let first = infos
+        .first()
+        .ok_or_else(|| anyhow!("No participant holds a share"))?;
+
+    if infos.iter().any(|info| {
+        info.public_key != first.public_key
+            || info.curve != first.curve
+            || info.threshold != first.threshold
+            || info.parties != first.parties
+            || info.chain_code != first.chain_code
+    }) {
+        return Err(anyhow!("Participants hold shares of different keys"));
+    }
+
+    let mut indexes = infos
+        .iter()
+        .map(|info| info.keygen_index)
+        .collect::<Vec<_>>();
+
+    indexes.sort_unstable();
+    indexes.dedup();
+
+    if indexes.len() != infos.len() {
+        return Err(anyhow!(
+            "Participants hold shares under the same keygen index"
+        ));
+    }
+
+    if indexes.iter().any(|index| !first.parties.contains(index)) {
+        return Err(anyhow!(
+            "A share has a keygen index outside of the key parties"
+        ));
+    }
+
+    let address = key_address(&wallet.chain, wallet.address_type, &first.public_key)?;
+
+    if address.is_some() && address != wallet.address {
+        return Err(anyhow!("Shares don't match the wallet address"));
+    }
+
+    Ok(())
+}
+
+/// Deletes the wallet share on every participant and standby, true only if all of them
+/// succeeded. The deletion is reported to the SIEM
+pub async fn purge_shares(participants: &dyn ParticipantPool, wallet_id: i32) -> bool {
+    let policy = RetryPolicy::current();
+
+    let futures = (1..=participants.count()).map(|participant| async move {
+        policy
+            .run(|| participants.delete_wallet(participant, wallet_id))
+            .await
+            .inspect_err(|err| {
+                log::error!("Failed to delete wallet {wallet_id} on participant: {err}");
+            })
+    });
+
+    let purged = join_all(futures).await.iter().all(|res| res.is_ok());
+
+    siem::emit(
+        SecurityEvent::new(
+            "wallet.shares_deleted",
+            match purged {
+                true => Outcome::Success,
+                false => Outcome::Failure,
+            },
+        )
+        .wallet(wallet_id),
+    );
+
+    purged
+}
+
+/// Asks every participant holding a share of the wallet whether it still does, in
+/// participant order, standbys aside
+pub async fn has_shares(
+    participants: &dyn ParticipantPool,
+    wallet: &WalletModel,
+) -> Vec<Result<bool, Status>> {
+    let wallet_id = wallet.id;
+
+    let futures = share_holders(participants, wallet).map(|participant| async move {
+        participants
+            .has_share(participant, wallet_id)
+            .await
+            .map(|res| res.present)
+            .inspect_err(|err| {
+                log::error!("Failed to look up wallet {wallet_id} on participant: {err}");
+            })
+    });
+
+    join_all(futures).await
+}
+
+/// Parties needed to sign, the threshold of every keygen
+pub const SIGNING_THRESHOLD: usize = 2;
+
+/// Time a participant has to answer the health and share probes before signing
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Participant able to take part in a signing round of a wallet
+#[derive(Debug, Clone)]
+pub struct Signer {
+    /// Position of the participant in the configuration, starting at 1
+    pub participant: usize,
+    pub keygen_index: u32,
+}
+
+/// Serving participants holding a share of the wallet, in participant order, with the
+/// keygen index each share was generated under. Standbys come last, so they only sign for
+/// the participants that are down
+pub async fn signing_parties(participants: &dyn ParticipantPool, wallet_id: i32) -> Vec<Signer> {
+    let futures = (1..=participants.count())
+        .map(|participant| probe_signer(participants, participant, wallet_id));
+
+    join_all(futures).await.into_iter().flatten().collect()
+}
+
+/// Co-signing participant of a co-signed wallet when it serves and holds the share, and
+/// the keygen index of the device, the other one of the key
+pub async fn cosigning_party(
+    participants: &dyn ParticipantPool,
+    wallet_id: i32,
+) -> Option<(Signer, u32)> {
+    let signer = probe_signer(participants, COSIGNING_PARTICIPANT, wallet_id).await?;
+
+    let device = match signer.keygen_index {
+        0 => 1,
+        _ => 0,
+    };
+
+    Some((signer, device))
+}
+
+async fn probe_signer(
+    participants: &dyn ParticipantPool,
+    participant: usize,
+    wallet_id: i32,
+) -> Option<Signer> {
+    let serving = timeout(PROBE_TIMEOUT, participants.health(participant)).await;
+
+    if !matches!(serving, Ok(Ok(ServingStatus::Serving))) {
+        log::warn!("Participant {participant} is not serving, skipped for signing");
+        return None;
+    }
+
+    if let Some(config) = participants.attestation(participant)
+        && let Err(err) = attest(participants, participant, config).await
+    {
+        log::warn!("Participant {participant} failed its attestation, skipped for signing: {err}");
+
+        siem::emit(
+            SecurityEvent::new("participant.attestation", Outcome::Failure)
+                .resource("participant", participant)
+                .details(serde_json::json!({ "error": err.to_string() })),
+        );
+
+        return None;
+    }
+
+    match timeout(
+        PROBE_TIMEOUT,
+        participants.has_share(participant, wallet_id),
+    )
+    .await
+    {
+        Ok(Ok(res)) if res.present => Some(Signer {
+            participant,
+            keygen_index: res.keygen_index,
+        }),
+        Ok(Ok(_)) => {
+            log::warn!("Participant {participant} has no share of wallet {wallet_id}");
+            None
+        }
+        _ => {
+            log::warn!("Participant {participant} could not look up wallet {wallet_id}");
+            None
+        }
+    }
+}
+
+/// Checks the participant runs on the platform and measurement of its attestation policy,
+/// with evidence of a fresh nonce so an earlier quote can't be replayed
+async fn attest(
+    participants: &dyn ParticipantPool,
+    participant: usize,
+    config: &AttestationConfig,
+) -> Result<()> {
+    let nonce = attestation::nonce();
+
+    let response = timeout(
+        PROBE_TIMEOUT,
+        participants.attest(participant, nonce.to_vec()),
+    )
+    .await
+    .map_err(|_| anyhow!("attestation timed out"))??;
+
+    attestation::verify(config, &nonce, &response)?;
+
+    Ok(())
+}
+
+/// First `threshold` of the available signers with distinct keygen indexes, `None` when
+/// too few are available
+pub fn select_signers(available: Vec<Signer>, threshold: usize) -> Option<Vec<Signer>> {
+    let mut selected: Vec<Signer> = Vec::new();
+
+    for signer in available {
+        if selected.len() == threshold {
+            break;
+        }
+
+        // Two shares under one index, e.g. a backup restored on the wrong participant
+        if selected
+            .iter()
+            .any(|other| other.keygen_index == signer.keygen_index)
+        {
+            log::warn!(
+                "Participant {} holds the keygen index {} of another participant",
+                signer.participant,
+                signer.keygen_index
+            );
+            continue;
+        }
+
+        selected.push(signer);
+    }
+
+    (selected.len() == threshold).then_some(selected)
+}
+
+/// Details a participant attached to its failed call, `None` for transport failures
+pub fn error_detail(status: &Status) -> Option<ErrorDetail> {
+    if status.details().is_empty() {
+        return None;
+    }
+
+    ErrorDetail::decode(status.details())
+        .inspect_err(|err| log::warn!("Invalid participant error details: {err}"))
+        .ok()
+}
+
+/// Reason of a failed participant call, inferred from the gRPC code when the call carries
+/// no details
+pub fn error_code(status: &Status) -> ErrorCode {
+    if let Some(detail) = error_detail(status) {
+        return detail.code();
+    }
+
+    match status.code() {
+        Code::ResourceExhausted => ErrorCode::Busy,
+        Code::Unavailable => ErrorCode::Unavailable,
+        Code::DeadlineExceeded => ErrorCode::Timeout,
+        Code::Aborted => ErrorCode::Aborted,
+        Code::NotFound => ErrorCode::ShareNotFound,
+        Code::InvalidArgument => ErrorCode::InvalidRequest,
+        Code::PermissionDenied => ErrorCode::PolicyViolation,
+        Code::AlreadyExists => ErrorCode::Replayed,
+        _ => ErrorCode::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(participant: usize, keygen_index: u32) -> Signer {
+        Signer {
+            participant,
+            keygen_index,
+        }
+    }
+
+    #[test]
+    fn test_may_hold_share_unless_never_started() {
+        assert!(may_hold_share(&created(&[4])));
+        assert!(may_hold_share::<()>(&Err(Status::deadline_exceeded(
+            "timed out"
+        ))));
+        assert!(!may_hold_share::<()>(&Err(Status::unavailable("down"))));
+        assert!(!may_hold_share::<()>(&Err(Status::resource_exhausted(
+            "busy"
+        ))));
+    }
+
+    #[test]
+    fn test_error_code_prefers_details() {
+        let detail = ErrorDetail {
+            code: ErrorCode::StorageFailed.into(),
+            retryable: true,
+            ..Default::default()
+        };
+
+        let status = Status::with_details(
+            Code::Internal,
+            "Failed to store new wallet",
+            detail.encode_to_vec().into(),
+        );
+
+        assert_eq!(error_code(&status), ErrorCode::StorageFailed);
+        assert_eq!(
+            error_code(&Status::resource_exhausted("busy")),
+            ErrorCode::Busy
+        );
+        assert_eq!(error_code(&Status::internal("failed")), ErrorCode::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_select_signers_skips_duplicate_indexes() {
+        let selected = select_signers(vec![signer(2, 0), signer(3, 2)], 2).unwrap();
+
+        assert_eq!(
+            selected.iter().map(|s| s.participant).collect::<Vec<_>>(),
+            [2, 3]
+        );
+
+        let selected = select_signers(vec![signer(1, 1), signer(2, 1), signer(3, 0)], 2).unwrap();
+
+        assert_eq!(
+            selected.iter().map(|s| s.keygen_index).collect::<Vec<_>>(),
+            [1, 0]
+        );
+
+        assert!(select_signers(vec![signer(1, 0), signer(2, 0)], 2).is_none());
+        assert!(select_signers(vec![signer(3, 2)], 2).is_none());
+    }
+
+    fn created(public_key: &[u8]) -> Result<WalletCreatedMessage, Status> {
+        Ok(WalletCreatedMessage {
+            public_key: public_key.to_vec(),
+        })
+    }
+
+    #[test]
+    fn test_verify_shares() {
+        // Public key of the private key 1, the secp256k1 generator
+        let public_key = hex::decode(
+            "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+             483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+        )
+        .unwrap();
+
+        let wallet = WalletModel {
+            id: 1,
+            user_id: 1,
+            name: "wallet".to_string(),
+            created_at: None,
+            updated_at: None,
+            chain: Chain::Ethereum,
+            namespace: String::new(),
+            namespace_rotated_at: None,
+            state: crate::db::models::WalletState::Active,
+            archived_at: None,
+            address: Some("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".to_string()),
+            whitelist_only: false,
+            auto_bump_gas: false,
+            withdrawal_threshold: None,
+            withdrawal_delay: 0,
+            max_signatures_per_hour: None,
+            max_signatures_per_day: None,
+            cosigner: false,
+            address_type: None,
+        };
+
+        let info = |keygen_index| WalletInfoResponse {
+            public_key: public_key.clone(),
+            curve: "secp256k1".to_string(),
+            threshold: 2,
+            parties: vec![0, 1, 2],
+            keygen_index,
+            chain_code: vec![7; 32],
+        };
+
+        assert!(verify_shares(&wallet, &[info(0), info(1), info(2)]).is_ok());
+        assert!(verify_shares(&wallet, &[info(0), info(0)]).is_err());
+        assert!(verify_shares(&wallet, &[info(0), info(3)]).is_err());
+        assert!(verify_shares(&wallet, &[]).is_err());
+
+        let other = WalletInfoResponse {
+            threshold: 3,
+            ..info(2)
+        };
+
+        assert!(verify_shares(&wallet, &[info(0), other]).is_err());
+
+        let other = WalletInfoResponse {
+            chain_code: vec![8; 32],
+            ..info(2)
+        };
+
+        assert!(verify_shares(&wallet, &[info(0), other]).is_err());
+
+        let moved = WalletModel {
+            address: Some("0x0000000000000000000000000000000000000001".to_string()),
+            ..wallet
+        };
+
+        assert!(verify_shares(&moved, &[info(0), info(1)]).is_err());
+    }
+
+    #[test]
+    fn test_extended_key_compresses_the_public_key() {
+        let info = WalletInfoResponse {
+            public_key: hex::decode(mock::PUBLIC_KEY).unwrap(),
+            curve: "secp256k1".to_string(),
+            threshold: 2,
+            parties: vec![0, 1, 2],
+            keygen_index: 0,
+            chain_code: vec![7; 32],
+        };
+
+        let key = extended_key(&[info.clone(), info.clone()])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            hex::encode(key.public_key),
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        assert_eq!(key.chain_code, [7; 32]);
+
+        let without_chain_code = WalletInfoResponse {
+            chain_code: Vec::new(),
+            ..info.clone()
+        };
+
+        assert!(extended_key(&[without_chain_code]).unwrap().is_none());
+
+        let other = WalletInfoResponse {
+            chain_code: vec![8; 32],
+            ..info.clone()
+        };
+
+        assert!(extended_key(&[info, other]).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_account_address_of_the_child_key() {
+        let participants = mock::MockParticipants::new(3)
+            .with_share(1, 7, 0)
+            .with_share(2, 7, 1)
+            .with_share(3, 7, 2);
+
+        let wallet = WalletModel {
+            id: 7,
+            user_id: 1,
+            name: "wallet".to_string(),
+            created_at: None,
+            updated_at: None,
+            chain: Chain::Ethereum,
+            namespace: String::new(),
+            namespace_rotated_at: None,
+            state: crate::db::models::WalletState::Active,
+            archived_at: None,
+            address: Some("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".to_string()),
+            whitelist_only: false,
+            auto_bump_gas: false,
+            withdrawal_threshold: None,
+            withdrawal_delay: 0,
+            max_signatures_per_hour: None,
+            max_signatures_per_day: None,
+            cosigner: false,
+            address_type: None,
+        };
+
+        let infos = wallet_infos(&participants, &wallet, &[0])
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // The mock derives the key of the private key 2
+        assert_eq!(
+            account_address(&wallet, &infos).unwrap(),
+            "0x2B5AD5c4795c026514f8317c7a215E218DcCD6cF"
+        );
+
+        let mut infos = infos;
+        infos[2].public_key = hex::decode(mock::PUBLIC_KEY).unwrap();
+
+        assert!(account_address(&wallet, &infos).is_err());
+        assert!(account_address(&wallet, &[]).is_err());
+    }
+
+    #[test]
+    fn test_keygen_address() {
+        // Public key of the private key 1, the secp256k1 generator
+        let public_key = hex::decode(
+            "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+             483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+        )
+        .unwrap();
+
+        let results = [created(&public_key), created(&public_key)];
+
+        assert_eq!(
+            keygen_address(&Chain::Ethereum, None, &results)
+                .unwrap()
+                .as_deref(),
+            Some("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf")
+        );
+        assert_eq!(
+            keygen_address(&Chain::Base, None, &results).unwrap(),
+            keygen_address(&Chain::Ethereum, None, &results).unwrap()
+        );
+        assert_eq!(
+            keygen_address(&Chain::Bitcoin, Some(AddressType::P2wpkh), &results).unwrap(),
+            None
+        );
+        assert!(keygen_address(&Chain::Solana, None, &results).is_err());
+
+        let results = [created(&[0; 32])];
+
+        assert_eq!(
+            keygen_address(&Chain::Solana, None, &results)
+                .unwrap()
+                .as_deref(),
+            Some("11111111111111111111111111111111")
+        );
+        assert!(
+            keygen_address(&Chain::Bitcoin, Some(AddressType::P2tr), &results)
+                .unwrap()
+                .is_some_and(|address| address.starts_with("bc1p"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_signing_parties_skip_unavailable_participants() {
+        let participants = mock::MockParticipants::new(3)
+            .with_share(1, 7, 0)
+            .down(1)
+            .with_share(2, 7, 1);
+
+        let parties = signing_parties(&participants, 7).await;
+
+        assert_eq!(
+            parties
+                .iter()
+                .map(|s| (s.participant, s.keygen_index))
+                .collect::<Vec<_>>(),
+            [(2, 1)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_signing_parties_substitute_standbys() {
+        let participants = mock::MockParticipants::new(3)
+            .with_standbys(1)
+            .with_share(1, 7, 0)
+            .down(1)
+            .with_share(2, 7, 1)
+            .with_share(3, 7, 2)
+            .with_share(4, 7, 0);
+
+        let parties = signing_parties(&participants, 7).await;
+        let selected = select_signers(parties, SIGNING_THRESHOLD).unwrap();
+
+        assert_eq!(
+            selected
+                .iter()
+                .map(|s| (s.participant, s.keygen_index))
+                .collect::<Vec<_>>(),
+            [(2, 1), (3, 2)]
+        );
+
+        let participants = mock::MockParticipants::new(3)
+            .with_standbys(1)
+            .with_share(1, 7, 0)
+            .down(1)
+            .with_share(2, 7, 1)
+            .down(2)
+            .with_share(3, 7, 2)
+            .with_share(4, 7, 1);
+
+        let parties = signing_parties(&participants, 7).await;
+        let selected = select_signers(parties, SIGNING_THRESHOLD).unwrap();
+
+        assert_eq!(
+            selected
+                .iter()
+                .map(|s| (s.participant, s.keygen_index))
+                .collect::<Vec<_>>(),
+            [(3, 2), (4, 1)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_signing_parties_skip_participants_failing_attestation() {
+        let enclave = |measurement: [u8; 32]| AttestationConfig {
+            platform: crate::config::app_config::AttestationPlatform::Sgx,
+            measurements: vec![measurement.to_vec()],
+            attestation_key: None,
+        };
+
+        let participants = mock::MockParticipants::new(3)
+            .with_share(1, 7, 0)
+            .with_share(2, 7, 1)
+            .with_share(3, 7, 2)
+            .attested(1, enclave([1; 32]))
+            .in_enclave(1, [1; 32])
+            .attested(2, enclave([1; 32]))
+            .in_enclave(2, [2; 32])
+            .attested(3, enclave([1; 32]));
+
+        let parties = signing_parties(&participants, 7).await;
+
+        assert_eq!(
+            parties.iter().map(|s| s.participant).collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_keygen_skips_standbys() {
+        let participants = mock::MockParticipants::new(3).with_standbys(1);
+        let wallet = WalletModel {
+            id: 7,
+            user_id: 1,
+            name: "wallet".to_string(),
+            created_at: None,
+            updated_at: None,
+            chain: Chain::Ethereum,
+            namespace: String::new(),
+            namespace_rotated_at: None,
+            state: crate::db::models::WalletState::Creating,
+            archived_at: None,
+            address: None,
+            whitelist_only: false,
+            auto_bump_gas: false,
+            withdrawal_threshold: None,
+            withdrawal_delay: 0,
+            max_signatures_per_hour: None,
+            max_signatures_per_day: None,
+            cosigner: false,
+            address_type: None,
+        };
+
+        let results = run_keygen(&participants, &wallet).await;
+
+        assert_eq!(results.len(), 3);
+        assert!(participants.has_share_of(3, 7));
+        assert!(!participants.has_share_of(4, 7));
+    }
+
+    #[actix_web::test]
+    async fn test_purge_shares_fails_while_a_share_is_left() {
+        let participants = mock::MockParticipants::new(3)
+            .with_share(1, 7, 0)
+            .with_share(2, 7, 1)
+            .failing(2, Status::internal("vault sealed"));
+
+        assert!(!purge_shares(&participants, 7).await);
+        assert!(!participants.has_share_of(1, 7));
+        assert!(participants.has_share_of(2, 7));
+
+        let participants = mock::MockParticipants::new(3).with_share(3, 7, 2);
+
+        assert!(purge_shares(&participants, 7).await);
+        assert!(!participants.has_share_of(3, 7));
+    }
+
+    #[test]
+    fn test_keygen_address_rejects_different_keys() {
+        let results = [created(&[0x04; 65]), created(&[0x05; 65])];
+
+        assert!(keygen_address(&Chain::Ethereum, None, &results).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use proto::mpc::participant_client::ParticipantClient;
+use proto::mpc::{
+    AttestMessage, AttestationResponse, CreateWalletMessage, DeleteWalletMessage,
+    ExportShareBackupMessage, HasShareMessage, HasShareResponse, ImportWalletMessage, KeygenEvent,
+    ShareBackupMessage, SignBatchMessage, SignMessage, SignatureBatchMessage, SignatureMessage,
+    WalletCreatedMessage, WalletInfoMessage, WalletInfoResponse,
+};
+use tonic::Status;
+use tonic::transport::Channel;
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+
+use super::error_code;
+use crate::config::app_config::AttestationConfig;
+use crate::siem::{self, Outcome, SecurityEvent};
+
+/// Events a participant streams while it runs a keygen, ending with the created wallet
+pub type KeygenEvents = BoxStream<'static, Result<KeygenEvent, Status>>;
+
+/// The MPC participants, addressed by their position in the configuration starting at 1.
+/// Ceremonies and retries are run on top of it by the functions of this module
+#[async_trait]
+pub trait ParticipantPool: Send + Sync {
+    /// Participants in the pool, numbered from 1 to this count
+    fn count(&self) -> usize;
+
+    /// Participants running keygens, numbered from 1 to this count. The others are standbys
+    /// holding copies of their shares, signing in their stead when they are down
+    fn primaries(&self) -> usize {
+        self.count()
+    }
+
+    /// Serving status reported by the `grpc.health.v1` service of the participant
+    async fn health(&self, participant: usize) -> Result<ServingStatus, Status>;
+
+    /// Attestation the participant must pass before it is included in a signing set
+    fn attestation(&self, _participant: usize) -> Option<&AttestationConfig> {
+        None
+    }
+
+    /// Evidence of the platform sealing the shares of the participant, bound to `nonce`
+    async fn attest(
+        &self,
+        participant: usize,
+        nonce: Vec<u8>,
+    ) -> Result<AttestationResponse, Status>;
+
+    async fn create_wallet(
+        &self,
+        participant: usize,
+        message: CreateWalletMessage,
+    ) -> Result<KeygenEvents, Status>;
+
+    /// Shares an existing key, the participant whose message holds it deals the others
+    async fn import_wallet(
+        &self,
+        participant: usize,
+        message: ImportWalletMessage,
+    ) -> Result<WalletCreatedMessage, Status>;
+
+    async fn delete_wallet(&self, participant: usize, wallet_id: i32) -> Result<(), Status>;
+
+    /// Share of the participant encrypted to the public key of the message
+    async fn export_share(
+        &self,
+        participant: usize,
+        message: ExportShareBackupMessage,
+    ) -> Result<ShareBackupMessage, Status>;
+
+    async fn has_share(
+        &self,
+        participant: usize,
+        wallet_id: i32,
+    ) -> Result<HasShareResponse, Status>;
+
+    async fn wallet_info(
+        &self,
+        participant: usize,
+        message: WalletInfoMessage,
+    ) -> Result<WalletInfoResponse, Status>;
+
+    async fn sign(
+        &self,
+        participant: usize,
+        message: SignMessage,
+    ) -> Result<SignatureMessage, Status>;
+
+    async fn sign_batch(
+        &self,
+        participant: usize,
+        message: SignBatchMessage,
+    ) -> Result<SignatureBatchMessage, Status>;
+}
+
+/// Request of `message` carrying the log context of the caller in its metadata, so the
+/// participant logs under the same request, user and wallet ids
+fn request<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+
+    for (header, value) in logging::headers() {
+        if let Ok(value) = value.parse() {
+            request.metadata_mut().insert(header, value);
+        }
+    }
+
+    request
+}
+
+/// Reports the failed call of `participant` to the SIEM
+fn report(participant: usize, call: &'static str, status: &Status) {
+    siem::emit(
+        SecurityEvent::new("participant.error", Outcome::Failure)
+            .resource("participant", participant)
+            .details(serde_json::json!({
+                "call": call,
+                "status": format!("{:?}", status.code()),
+                "error": error_code(status).as_str_name(),
+                "message": logging::redact(status.message()),
+            })),
+    );
+}
+
+/// Participants reached over gRPC, in configuration order, followed by the standbys
+pub struct GrpcParticipants {
+    channels: Vec<Channel>,
+    primaries: usize,
+    attestation: Vec<Option<AttestationConfig>>,
+}
+
+impl GrpcParticipants {
+    pub fn new(channels: Vec<Channel>) -> Self {
+        Self {
+            primaries: channels.len(),
+            channels,
+            attestation: Vec::new(),
+        }
+    }
+
+    /// Same participants, attested with the policy at their position before they sign
+    pub fn with_attestation(mut self, attestation: Vec<Option<AttestationConfig>>) -> Self {
+        self.attestation = attestation;
+        self
+    }
+
+    /// Same participants, with `standbys` numbered after them
+    pub fn with_standbys(mut self, standbys: Vec<Channel>) -> Self {
+        self.channels.extend(standbys);
+        self
+    }
+
+    fn client(&self, participant: usize) -> Result<ParticipantClient<Channel>, Status> {
+        Ok(ParticipantClient::new(self.channel(participant)?.clone()))
+    }
+
+    fn channel(&self, participant: usize) -> Result<&Channel, Status> {
+        participant
+            .checked_sub(1)
+            .and_then(|index| self.channels.get(index))
+            .ok_or_else(|| Status::not_found(format!("No participant {participant}")))
+    }
+}
+
+#[async_trait]
+impl ParticipantPool for GrpcParticipants {
+    fn count(&self) -> usize {
+        self.channels.len()
+    }
+
+    fn primaries(&self) -> usize {
+        self.primaries
+    }
+
+    async fn health(&self, participant: usize) -> Result<ServingStatus, Status> {
+        let mut client = HealthClient::new(self.channel(participant)?.clone());
+
+        let response = client
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await?;
+
+        Ok(response.get_ref().status())
+    }
+
+    fn attestation(&self, participant: usize) -> Option<&AttestationConfig> {
+        self.attestation.get(participant.checked_sub(1)?)?.as_ref()
+    }
+
+    async fn attest(
+        &self,
+        participant: usize,
+        nonce: Vec<u8>,
+    ) -> Result<AttestationResponse, Status> {
+        let response = self
+            .client(participant)?
+            .attest(request(AttestMessage { nonce }))
+            .await
+            .inspect_err(|status| report(participant, "attest", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn create_wallet(
+        &self,
+        participant: usize,
+        message: CreateWalletMessage,
+    ) -> Result<KeygenEvents, Status> {
+        let events = self
+            .client(participant)?
+            .new_wallet(request(message))
+            .await
+            .inspect_err(|status| report(participant, "new_wallet", status))?
+            .into_inner();
+
+        Ok(events.boxed())
+    }
+
+    async fn import_wallet(
+        &self,
+        participant: usize,
+        message: ImportWalletMessage,
+    ) -> Result<WalletCreatedMessage, Status> {
+        let response = self
+            .client(participant)?
+            .import_wallet(request(message))
+            .await
+            .inspect_err(|status| report(participant, "import_wallet", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn delete_wallet(&self, participant: usize, wallet_id: i32) -> Result<(), Status> {
+        self.client(participant)?
+            .delete_wallet(request(DeleteWalletMessage { wallet_id }))
+            .await
+            .inspect_err(|status| report(participant, "delete_wallet", status))?;
+
+        Ok(())
+    }
+
+    async fn export_share(
+        &self,
+        participant: usize,
+        message: ExportShareBackupMessage,
+    ) -> Result<ShareBackupMessage, Status> {
+        let response = self
+            .client(participant)?
+            .export_share_backup(request(message))
+            .await
+            .inspect_err(|status| report(participant, "export_share_backup", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn has_share(
+        &self,
+        participant: usize,
+        wallet_id: i32,
+    ) -> Result<HasShareResponse, Status> {
+        let response = self
+            .client(participant)?
+            .has_share(request(HasShareMessage { wallet_id }))
+            .await
+            .inspect_err(|status| report(participant, "has_share", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn wallet_info(
+        &self,
+        participant: usize,
+        message: WalletInfoMessage,
+    ) -> Result<WalletInfoResponse, Status> {
+        let response = self
+            .client(participant)?
+            .get_wallet_info(request(message))
+            .await
+            .inspect_err(|status| report(participant, "get_wallet_info", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn sign(
+        &self,
+        participant: usize,
+        message: SignMessage,
+    ) -> Result<SignatureMessage, Status> {
+        let response = self
+            .client(participant)?
+            .sign_tx(request(message))
+            .await
+            .inspect_err(|status| report(participant, "sign_tx", status))?;
+
+        Ok(response.into_inner())
+    }
+
+    async fn sign_batch(
+        &self,
+        participant: usize,
+        message: SignBatchMessage,
+    ) -> Result<SignatureBatchMessage, Status> {
+        let response = self
+            .client(participant)?
+            .sign_batch(request(message))
+            .await
+            .inspect_err(|status| report(participant, "sign_batch", status))?;
+
+        Ok(response.into_inner())
+    }
+}
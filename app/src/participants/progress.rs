@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use proto::mpc::{KeygenProgress, KeygenStage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Latest progress of every participant of the running keygens, by wallet id
+static KEYGENS: Lazy<RwLock<HashMap<i32, Vec<ParticipantProgress>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Last step a participant reported in the keygen of a wallet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParticipantProgress {
+    /// Position of the participant in the configuration, starting at 1
+    pub participant: usize,
+    pub stage: &'static str,
+    /// Protocol round of the stage, 0 when the stage began
+    pub round: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn stage_name(stage: KeygenStage) -> &'static str {
+    match stage {
+        KeygenStage::Queued => "queued",
+        KeygenStage::Joining => "joining",
+        KeygenStage::GeneratingPrimes => "generating_primes",
+        KeygenStage::ThresholdKeygen => "keygen",
+        KeygenStage::AuxInfo => "aux_info",
+        KeygenStage::Storing => "storing",
+    }
+}
+
+/// Records the progress a participant streamed for the keygen of the wallet
+pub fn record(wallet_id: i32, participant: usize, progress: &KeygenProgress) {
+    let update = ParticipantProgress {
+        participant,
+        stage: stage_name(progress.stage()),
+        round: progress.round,
+        updated_at: Utc::now(),
+    };
+
+    let mut keygens = KEYGENS.write().unwrap_or_else(|e| e.into_inner());
+    let participants = keygens.entry(wallet_id).or_default();
+
+    match participants
+        .iter_mut()
+        .find(|other| other.participant == participant)
+    {
+        Some(other) => *other = update,
+        None => {
+            participants.push(update);
+            participants.sort_by_key(|other| other.participant);
+        }
+    }
+}
+
+/// Progress of the running keygen of the wallet, in participant order, empty when none runs
+pub fn keygen_progress(wallet_id: i32) -> Vec<ParticipantProgress> {
+    KEYGENS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&wallet_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Forgets the progress of a keygen that ended
+pub fn finish(wallet_id: i32) {
+    KEYGENS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&wallet_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(stage: KeygenStage, round: u32) -> KeygenProgress {
+        KeygenProgress {
+            stage: stage.into(),
+            round,
+        }
+    }
+
+    #[test]
+    fn test_keygen_progress_keeps_latest_per_participant() {
+        record(-1, 2, &progress(KeygenStage::Queued, 0));
+        record(-1, 1, &progress(KeygenStage::ThresholdKeygen, 1));
+        record(-1, 2, &progress(KeygenStage::AuxInfo, 3));
+
+        let participants = keygen_progress(-1)
+            .into_iter()
+            .map(|p| (p.participant, p.stage, p.round))
+            .collect::<Vec<_>>();
+
+        assert_eq!(participants, [(1, "keygen", 1), (2, "aux_info", 3)]);
+
+        finish(-1);
+
+        assert!(keygen_progress(-1).is_empty());
+    }
+}
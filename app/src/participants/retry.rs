@@ -0,0 +1,165 @@
+use once_cell::sync::OnceCell;
+use std::future::Future;
+use std::time::Duration;
+use tonic::{Code, Status};
+use uuid::Uuid;
+
+use crate::config::app_config::ParticipantRetryConfig;
+
+static POLICY: OnceCell<RetryPolicy> = OnceCell::new();
+
+/// Retries of participant calls that failed before the participant joined the ceremony,
+/// the other parties wait in the relay room so the retried call can still take part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl From<&ParticipantRetryConfig> for RetryPolicy {
+    fn from(config: &ParticipantRetryConfig) -> Self {
+        Self {
+            attempts: config.attempts.max(1),
+            backoff: Duration::from_millis(config.backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Sets the policy of every participant call, the default one applies until then
+    pub fn install(self) {
+        if POLICY.set(self).is_err() {
+            log::warn!("Participant retry policy is already installed");
+        }
+    }
+
+    pub fn current() -> Self {
+        POLICY.get().copied().unwrap_or_default()
+    }
+
+    /// Delay before the retry following `attempt`, `jitter` in `[0, 1)` adds up to half of it
+    fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        delay + delay.mul_f64(jitter / 2.0)
+    }
+
+    /// Runs `call` until it succeeds, fails with a code that isn't transient or runs out
+    /// of attempts, returning its last result
+    pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, Status>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match call().await {
+                Err(status) if is_transient(&status) && attempt < self.attempts => {
+                    let jitter = (Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+                    let delay = self.delay(attempt, jitter);
+
+                    log::warn!(
+                        "Participant call failed with {:?}, retrying in {delay:?}: {}",
+                        status.code(),
+                        status.message()
+                    );
+
+                    actix_web::rt::time::sleep(delay).await;
+
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Codes of calls the participant never started on, e.g. unreachable or at its limit
+pub fn is_transient(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::ResourceExhausted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        }
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_millis(800));
+        assert_eq!(policy.delay(10, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.delay(1, 0.5), Duration::from_millis(250));
+    }
+
+    #[actix_web::test]
+    async fn test_run_retries_transient_codes_only() {
+        let calls = Cell::new(0);
+
+        let result = policy(3)
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err::<(), _>(Status::unavailable("down")) }
+            })
+            .await;
+
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+
+        let result = policy(3)
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err::<(), _>(Status::aborted("faulty party")) }
+            })
+            .await;
+
+        assert_eq!(result.unwrap_err().code(), Code::Aborted);
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+
+        let result = policy(3)
+            .run(|| {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move {
+                    if attempt < 2 {
+                        Err(Status::resource_exhausted("busy"))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 2);
+    }
+}
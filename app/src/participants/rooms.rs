@@ -0,0 +1,21 @@
+//! Tokens of the relay rooms of the ceremonies, signed with the secret shared with the relay
+
+use once_cell::sync::OnceCell;
+use uuid::Uuid;
+
+static SECRET: OnceCell<Vec<u8>> = OnceCell::new();
+
+/// Sets the secret room tokens are signed with, ceremonies run with empty tokens until then
+pub fn install(secret: Vec<u8>) {
+    if SECRET.set(secret).is_err() {
+        log::warn!("Room token secret is already installed");
+    }
+}
+
+/// Token the participants of the execution present to the relay, opening its rooms only
+pub fn room_token(execution_id: &Uuid) -> String {
+    SECRET
+        .get()
+        .map(|secret| tokens::room::sign(secret, execution_id.as_bytes()))
+        .unwrap_or_default()
+}
@@ -0,0 +1,222 @@
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{Asset, PriceFeed};
+use crate::config::app_config::{CoinGeckoConfig, ProxyConfig};
+use crate::db::models::Chain;
+use crate::utils::http::http_client;
+
+/// Prices by coin id or contract address, then by currency
+type PriceResponse = HashMap<String, HashMap<String, f64>>;
+
+/// CoinGecko prices, the native coins by coin id through `simple/price` and the tokens by
+/// contract address through `simple/token_price/{platform}`
+pub struct CoinGeckoFeed {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    timeout: Duration,
+}
+
+/// Coin id of the native coin of the chain
+fn coin_id(chain: &Chain) -> &'static str {
+    match chain {
+        Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base => "ethereum",
+        Chain::Bitcoin => "bitcoin",
+        Chain::Solana => "solana",
+        Chain::Polygon => "polygon-ecosystem-token",
+    }
+}
+
+/// Asset platform the tokens of the chain are listed under
+fn platform(chain: &Chain) -> Option<&'static str> {
+    match chain {
+        Chain::Ethereum => Some("ethereum"),
+        Chain::Optimism => Some("optimistic-ethereum"),
+        Chain::Arbitrum => Some("arbitrum-one"),
+        Chain::Base => Some("base"),
+        Chain::Polygon => Some("polygon-pos"),
+        Chain::Bitcoin | Chain::Solana => None,
+    }
+}
+
+/// Key the response lists the asset under, addresses are lowercase
+fn key(asset: &Asset) -> String {
+    match asset {
+        Asset::Native(chain) => coin_id(chain).to_string(),
+        Asset::Token(_, address) => format!("{address:#x}"),
+    }
+}
+
+/// Prices of the assets the response quotes in `currency`
+fn select(assets: &[&Asset], response: &PriceResponse, currency: &str) -> HashMap<Asset, f64> {
+    assets
+        .iter()
+        .filter_map(|asset| {
+            let price = response.get(&key(asset))?.get(currency)?;
+            Some(((*asset).clone(), *price))
+        })
+        .collect()
+}
+
+impl CoinGeckoFeed {
+    pub fn new(config: &CoinGeckoConfig, proxy: &ProxyConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client(proxy)?,
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            timeout: Duration::from_secs(config.timeout),
+        })
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, String)]) -> Result<PriceResponse> {
+        let mut request = self
+            .client
+            .get(format!("{}{path}", self.url))
+            .query(query)
+            .timeout(self.timeout);
+
+        if let Some(api_key) = &self.api_key {
+            let header = if self.url.contains("pro-api") {
+                "x-cg-pro-api-key"
+            } else {
+                "x-cg-demo-api-key"
+            };
+
+            request = request.header(header, api_key);
+        }
+
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("CoinGecko returned {}", response.status()));
+        }
+
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+
+    async fn native_prices(
+        &self,
+        assets: &[&Asset],
+        currency: &str,
+    ) -> Result<HashMap<Asset, f64>> {
+        let mut ids: Vec<String> = assets.iter().map(|asset| key(asset)).collect();
+        ids.sort();
+        ids.dedup();
+
+        let response = self
+            .get(
+                "/simple/price",
+                &[
+                    ("ids", ids.join(",")),
+                    ("vs_currencies", currency.to_string()),
+                ],
+            )
+            .await?;
+
+        Ok(select(assets, &response, currency))
+    }
+
+    async fn token_prices(
+        &self,
+        platform: &str,
+        assets: &[&Asset],
+        currency: &str,
+    ) -> Result<HashMap<Asset, f64>> {
+        let addresses: Vec<String> = assets.iter().map(|asset| key(asset)).collect();
+
+        let response = self
+            .get(
+                &format!("/simple/token_price/{platform}"),
+                &[
+                    ("contract_addresses", addresses.join(",")),
+                    ("vs_currencies", currency.to_string()),
+                ],
+            )
+            .await?;
+
+        Ok(select(assets, &response, currency))
+    }
+}
+
+impl PriceFeed for CoinGeckoFeed {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn prices<'a>(
+        &'a self,
+        assets: &'a [Asset],
+        currency: &'a str,
+    ) -> BoxFuture<'a, Result<HashMap<Asset, f64>>> {
+        async move {
+            let mut natives = Vec::new();
+            let mut tokens: HashMap<&'static str, Vec<&Asset>> = HashMap::new();
+
+            for asset in assets {
+                match asset {
+                    Asset::Native(_) => natives.push(asset),
+                    Asset::Token(chain, _) => {
+                        if let Some(platform) = platform(chain) {
+                            tokens.entry(platform).or_default().push(asset);
+                        }
+                    }
+                }
+            }
+
+            let mut prices = HashMap::new();
+
+            if !natives.is_empty() {
+                prices.extend(self.native_prices(&natives, currency).await?);
+            }
+
+            for (platform, assets) in tokens {
+                prices.extend(self.token_prices(platform, &assets, currency).await?);
+            }
+
+            Ok(prices)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+
+    #[test]
+    fn test_select_prices_by_coin_id_and_address() {
+        let usdc = Asset::Token(
+            Chain::Ethereum,
+            address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+        );
+        let assets = [
+            Asset::Native(Chain::Ethereum),
+            Asset::Native(Chain::Base),
+            Asset::Native(Chain::Solana),
+            usdc.clone(),
+        ];
+
+        let response: PriceResponse = serde_json::from_value(serde_json::json!({
+            "ethereum": { "usd": 2000.5 },
+            "solana": { "eur": 150.0 },
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48": { "usd": 1.0 },
+        }))
+        .unwrap();
+
+        let prices = select(&assets.iter().collect::<Vec<_>>(), &response, "usd");
+
+        assert_eq!(
+            prices,
+            HashMap::from([
+                (Asset::Native(Chain::Ethereum), 2000.5),
+                (Asset::Native(Chain::Base), 2000.5),
+                (usdc, 1.0),
+            ])
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use alloy::primitives::utils::format_units;
+use alloy::primitives::{Address, U256};
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::app_config::{PricesConfig, ProxyConfig};
+use crate::db::models::Chain;
+
+mod coingecko;
+
+pub use coingecko::CoinGeckoFeed;
+
+/// Asset a price feed quotes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Asset {
+    /// Native coin of the chain, ether on the EVM rollups
+    Native(Chain),
+    /// ERC-20 token of an EVM chain
+    Token(Chain, Address),
+}
+
+/// Source of the fiat prices of assets
+///
+/// Assets the feed doesn't quote are left out of the prices rather than failing the others.
+pub trait PriceFeed: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn prices<'a>(
+        &'a self,
+        assets: &'a [Asset],
+        currency: &'a str,
+    ) -> BoxFuture<'a, Result<HashMap<Asset, f64>>>;
+}
+
+/// Value of an amount in the configured currency at the cached price, indicative only
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FiatValue {
+    pub currency: String,
+    /// Price of one whole unit of the asset
+    pub price: f64,
+    pub value: f64,
+}
+
+/// Decimals of the smallest unit of the native coin of the chain, satoshis and lamports
+/// outside of the EVM chains
+pub fn native_decimals(chain: &Chain) -> u8 {
+    match chain {
+        Chain::Bitcoin => 8,
+        Chain::Solana => 9,
+        _ => 18,
+    }
+}
+
+/// Prices of the configured feed, each cached for the TTL and served stale while the feed
+/// fails. Without a feed no asset has a price
+pub struct Prices {
+    feed: Option<Box<dyn PriceFeed>>,
+    currency: String,
+    ttl: Duration,
+    cache: Mutex<HashMap<Asset, (Instant, f64)>>,
+}
+
+impl Prices {
+    pub fn new(config: &PricesConfig, proxy: &ProxyConfig) -> Result<Self> {
+        let feed: Option<Box<dyn PriceFeed>> = match &config.coingecko {
+            Some(coingecko) => Some(Box::new(CoinGeckoFeed::new(coingecko, proxy)?)),
+            None => None,
+        };
+
+        Ok(Self::with_feed(feed, config))
+    }
+
+    fn with_feed(feed: Option<Box<dyn PriceFeed>>, config: &PricesConfig) -> Self {
+        Self {
+            feed,
+            currency: config.currency.clone(),
+            ttl: Duration::from_secs(config.cache_ttl),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Prices of the assets, asking the feed once for those not cached
+    pub async fn prices(&self, assets: &[Asset]) -> HashMap<Asset, f64> {
+        let Some(feed) = &self.feed else {
+            return HashMap::new();
+        };
+
+        let now = Instant::now();
+        let mut prices = HashMap::new();
+        let mut missing = Vec::new();
+
+        {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+
+            for asset in assets {
+                match cache.get(asset) {
+                    Some((at, price)) if now.duration_since(*at) < self.ttl => {
+                        prices.insert(asset.clone(), *price);
+                    }
+                    _ if !missing.contains(asset) => missing.push(asset.clone()),
+                    _ => {}
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            return prices;
+        }
+
+        let fetched = match feed.prices(&missing, &self.currency).await {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                log::warn!("Price feed {} failed: {err}", feed.name());
+                HashMap::new()
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        for asset in missing {
+            match fetched.get(&asset) {
+                Some(price) => {
+                    cache.insert(asset.clone(), (now, *price));
+                    prices.insert(asset, *price);
+                }
+                None => {
+                    if let Some((_, price)) = cache.get(&asset) {
+                        prices.insert(asset, *price);
+                    }
+                }
+            }
+        }
+
+        prices
+    }
+
+    pub async fn price(&self, asset: &Asset) -> Option<f64> {
+        self.prices(std::slice::from_ref(asset))
+            .await
+            .get(asset)
+            .copied()
+    }
+
+    /// Value of `amount` smallest units of an asset of `decimals` at `price`
+    pub fn value(&self, price: Option<f64>, amount: U256, decimals: u8) -> Option<FiatValue> {
+        let price = price?;
+        let units: f64 = format_units(amount, decimals).ok()?.parse().ok()?;
+
+        Some(FiatValue {
+            currency: self.currency.clone(),
+            price,
+            value: units * price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Quotes ether at 2000 and counts the assets it was asked for
+    struct FakeFeed(&'static AtomicUsize);
+
+    impl PriceFeed for FakeFeed {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn prices<'a>(
+            &'a self,
+            assets: &'a [Asset],
+            _currency: &'a str,
+        ) -> BoxFuture<'a, Result<HashMap<Asset, f64>>> {
+            async move {
+                self.0.fetch_add(assets.len(), Ordering::SeqCst);
+
+                Ok(assets
+                    .iter()
+                    .filter(|asset| **asset == Asset::Native(Chain::Ethereum))
+                    .map(|asset| (asset.clone(), 2000.0))
+                    .collect())
+            }
+            .boxed()
+        }
+    }
+
+    fn config() -> PricesConfig {
+        PricesConfig {
+            currency: "usd".to_string(),
+            cache_ttl: 60,
+            coingecko: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prices_are_cached() {
+        static ASKED: AtomicUsize = AtomicUsize::new(0);
+
+        let prices = Prices::with_feed(Some(Box::new(FakeFeed(&ASKED))), &config());
+        let assets = [Asset::Native(Chain::Ethereum), Asset::Native(Chain::Solana)];
+
+        assert_eq!(prices.prices(&assets).await.len(), 1);
+        assert_eq!(prices.price(&assets[0]).await, Some(2000.0));
+        assert_eq!(ASKED.load(Ordering::SeqCst), 2);
+
+        // Assets without a price are asked for again
+        assert_eq!(prices.price(&assets[1]).await, None);
+        assert_eq!(ASKED.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_value_of_smallest_units() {
+        let prices = Prices::with_feed(None, &config());
+
+        assert_eq!(prices.price(&Asset::Native(Chain::Ethereum)).await, None);
+        assert_eq!(
+            prices.value(Some(2000.0), U256::from(1_500_000_000_000_000_000u64), 18),
+            Some(FiatValue {
+                currency: "usd".to_string(),
+                price: 2000.0,
+                value: 3000.0,
+            })
+        );
+        assert_eq!(prices.value(None, U256::from(1), 18), None);
+    }
+}
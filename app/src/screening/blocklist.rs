@@ -0,0 +1,74 @@
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::HashSet;
+
+use super::{Screening, ScreeningProvider};
+use crate::db::models::Chain;
+
+/// Static list of blocked recipients, e.g. addresses from a sanctions list export
+pub struct BlocklistScreening {
+    addresses: HashSet<String>,
+}
+
+impl BlocklistScreening {
+    pub fn new(addresses: &[String]) -> Self {
+        Self {
+            addresses: addresses
+                .iter()
+                .map(|address| address.trim().to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+impl ScreeningProvider for BlocklistScreening {
+    fn name(&self) -> &'static str {
+        "blocklist"
+    }
+
+    fn screen<'a>(
+        &'a self,
+        _chain: &'a Chain,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Screening>> {
+        // Hex addresses differ only by their checksum casing
+        let screening = if self.addresses.contains(&address.to_lowercase()) {
+            Screening::blocked("Recipient is on the blocklist")
+        } else {
+            Screening::clear()
+        };
+
+        async move { Ok(screening) }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blocklist_ignores_checksum_casing() {
+        let blocklist =
+            BlocklistScreening::new(&["0x7e5f4552091a69125d5dfcb7b8c2659029395bdf".to_string()]);
+
+        let screening = blocklist
+            .screen(
+                &Chain::Ethereum,
+                "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf",
+            )
+            .await
+            .unwrap();
+
+        assert!(screening.is_blocked());
+
+        let screening = blocklist
+            .screen(
+                &Chain::Ethereum,
+                "0x2B5AD5c4795c026514f8317c7a215E218DcCD6cF",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(screening, Screening::clear());
+    }
+}
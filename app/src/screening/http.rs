@@ -0,0 +1,116 @@
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, FutureExt};
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::{Screening, ScreeningProvider};
+use crate::config::app_config::{HttpScreeningConfig, ProxyConfig};
+use crate::db::models::Chain;
+use crate::utils::http::http_client;
+
+#[derive(Debug, Deserialize)]
+struct AddressResponse {
+    identifications: Vec<Identification>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identification {
+    category: Option<String>,
+    name: Option<String>,
+}
+
+/// Sanctions screening API answering `GET {url}/{address}` with the identifications of
+/// the address, any identification blocks it
+pub struct HttpScreening {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    timeout: Duration,
+}
+
+impl HttpScreening {
+    pub fn new(config: &HttpScreeningConfig, proxy: &ProxyConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client(proxy)?,
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            timeout: Duration::from_secs(config.timeout),
+        })
+    }
+
+    async fn lookup(&self, address: &str) -> Result<AddressResponse> {
+        let mut request = self
+            .client
+            .get(format!("{}/{address}", self.url))
+            .timeout(self.timeout);
+
+        if let Some(api_key) = &self.api_key {
+            request = request.header("X-API-Key", api_key);
+        }
+
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Screening API returned {}", response.status()));
+        }
+
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+}
+
+fn verdict(response: AddressResponse) -> Screening {
+    let Some(identification) = response.identifications.into_iter().next() else {
+        return Screening::clear();
+    };
+
+    let reason = match (identification.category, identification.name) {
+        (Some(category), Some(name)) => format!("{category} ({name})"),
+        (Some(label), None) | (None, Some(label)) => label,
+        (None, None) => "Recipient is identified by the screening API".to_string(),
+    };
+
+    Screening::blocked(reason)
+}
+
+impl ScreeningProvider for HttpScreening {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn screen<'a>(
+        &'a self,
+        _chain: &'a Chain,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Screening>> {
+        async move { Ok(verdict(self.lookup(address).await?)) }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict_blocks_identified_addresses() {
+        let response: AddressResponse = serde_json::from_value(serde_json::json!({
+            "identifications": [{
+                "category": "sanctions",
+                "name": "SANCTIONS: OFAC SDN Example",
+                "description": "",
+                "url": null,
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            verdict(response),
+            Screening::blocked("sanctions (SANCTIONS: OFAC SDN Example)")
+        );
+
+        let response: AddressResponse =
+            serde_json::from_value(serde_json::json!({ "identifications": [] })).unwrap();
+
+        assert_eq!(verdict(response), Screening::clear());
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+use crate::config::app_config::{ProxyConfig, ScreeningConfig};
+use crate::db::models::{Chain, ScreeningVerdict};
+
+mod blocklist;
+mod http;
+
+pub use blocklist::BlocklistScreening;
+pub use http::HttpScreening;
+
+/// Verdict of a provider on a recipient, the reason explains a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screening {
+    pub verdict: ScreeningVerdict,
+    pub reason: Option<String>,
+}
+
+impl Screening {
+    pub fn clear() -> Self {
+        Self {
+            verdict: ScreeningVerdict::Clear,
+            reason: None,
+        }
+    }
+
+    pub fn blocked(reason: impl Into<String>) -> Self {
+        Self {
+            verdict: ScreeningVerdict::Blocked,
+            reason: Some(reason.into()),
+        }
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.verdict == ScreeningVerdict::Blocked
+    }
+}
+
+/// Source of screening verdicts for transaction recipients
+///
+/// Errors mean no verdict could be reached, the transaction is then rejected rather than
+/// signed unscreened.
+pub trait ScreeningProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn screen<'a>(&'a self, chain: &'a Chain, address: &'a str)
+    -> BoxFuture<'a, Result<Screening>>;
+}
+
+/// Runs every configured provider, a recipient must be cleared by all of them
+pub struct Screener {
+    providers: Vec<Box<dyn ScreeningProvider>>,
+}
+
+impl Screener {
+    pub fn new(config: &ScreeningConfig, proxy: &ProxyConfig) -> Result<Self> {
+        let mut providers: Vec<Box<dyn ScreeningProvider>> = Vec::new();
+
+        if !config.blocklist.is_empty() {
+            providers.push(Box::new(BlocklistScreening::new(&config.blocklist)));
+        }
+
+        if let Some(http) = &config.http {
+            providers.push(Box::new(HttpScreening::new(http, proxy)?));
+        }
+
+        Ok(Self { providers })
+    }
+
+    /// First block reported by a provider, prefixed with the provider name
+    pub async fn screen(&self, chain: &Chain, address: &str) -> Result<Screening> {
+        for provider in &self.providers {
+            let screening = provider.screen(chain, address).await?;
+
+            if screening.is_blocked() {
+                let reason = screening.reason.unwrap_or_default();
+
+                return Ok(Screening::blocked(format!("{}: {reason}", provider.name())));
+            }
+        }
+
+        Ok(Screening::clear())
+    }
+}
@@ -0,0 +1,99 @@
+use alloy::transports::http::reqwest::Client;
+use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, FutureExt};
+use std::time::Duration;
+
+use super::{EventSink, SecurityEvent};
+use crate::config::app_config::ProxyConfig;
+use crate::utils::http::http_client;
+
+/// Posts every batch to a collector as newline-delimited JSON, one event per line
+pub struct HttpSink {
+    client: Client,
+    url: String,
+    authorization: Option<String>,
+    timeout: Duration,
+}
+
+impl HttpSink {
+    pub fn new(
+        url: String,
+        authorization: Option<String>,
+        timeout: Duration,
+        proxy: &ProxyConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: http_client(proxy)?,
+            url,
+            authorization,
+            timeout,
+        })
+    }
+}
+
+fn body(events: &[SecurityEvent]) -> Result<String> {
+    let mut body = String::new();
+
+    for event in events {
+        body.push_str(&serde_json::to_string(event)?);
+        body.push('\n');
+    }
+
+    Ok(body)
+}
+
+impl EventSink for HttpSink {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn send<'a>(&'a self, events: &'a [SecurityEvent]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut request = self
+                .client
+                .post(&self.url)
+                .timeout(self.timeout)
+                .header("Content-Type", "application/x-ndjson")
+                .body(body(events)?);
+
+            if let Some(authorization) = &self.authorization {
+                request = request.header("Authorization", authorization);
+            }
+
+            let response = request.send().await?;
+
+            if !response.status().is_success() {
+                return Err(anyhow!("SIEM collector returned {}", response.status()));
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::siem::Outcome;
+
+    #[test]
+    fn test_events_are_sent_one_per_line() {
+        let events = [
+            SecurityEvent::new("auth.login", Outcome::Success).user(3),
+            SecurityEvent::new("auth.token_rejected", Outcome::Failure),
+        ];
+
+        let body = body(&events).unwrap();
+        let lines = body
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(body.ends_with('\n'));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["user_id"], 3);
+        assert_eq!(lines[1]["event"], "auth.token_rejected");
+        assert_eq!(lines[1]["service"], "mpc-waas");
+    }
+}
@@ -0,0 +1,361 @@
+//! Security-relevant events exported to a SIEM as JSON: logins, rejected credentials,
+//! signing rounds, share deletions, participant errors and every entry of the audit log.
+//! Events are queued by `emit` and sent by the exporter in the background, a sink that is
+//! down never slows the requests down, its events are dropped once the queue is full
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::config::app_config::{ProxyConfig, SiemConfig, SiemSink};
+use crate::db::models::AuditLogModel;
+
+mod http;
+mod syslog;
+
+pub use http::HttpSink;
+pub use syslog::SyslogSink;
+
+/// Service the events are reported by, and the syslog `APP-NAME`
+const SERVICE: &str = "mpc-waas";
+
+/// Attempts of a batch before its events are dropped
+const ATTEMPTS: u32 = 3;
+
+/// Queue of the exporter, events are only collected once it was started
+static EVENTS: OnceCell<mpsc::Sender<SecurityEvent>> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Outcome {
+    pub fn of<T, E>(result: &std::result::Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Outcome::Success,
+            Err(_) => Outcome::Failure,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SecurityEvent {
+    pub timestamp: DateTime<Utc>,
+    pub service: &'static str,
+    /// Name of the event, the action for entries of the audit log (e.g., `auth.login_failed`)
+    pub event: String,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wallet_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl SecurityEvent {
+    /// Event of the request being served, with its request, user and wallet ids
+    pub fn new(event: impl Into<String>, outcome: Outcome) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            service: SERVICE,
+            event: event.into(),
+            outcome,
+            actor: None,
+            user_id: logging::user_id(),
+            wallet_id: logging::wallet_id(),
+            resource_type: None,
+            resource_id: None,
+            source_ip: None,
+            request_id: logging::request_id(),
+            details: None,
+        }
+    }
+
+    /// Entry of the audit log, a failure when its action is one (`*_failed`) or a lockout
+    pub fn audit(entry: &AuditLogModel) -> Self {
+        let outcome = match entry.action.ends_with("_failed") || entry.action.ends_with("_locked") {
+            true => Outcome::Failure,
+            false => Outcome::Success,
+        };
+
+        Self {
+            timestamp: entry.created_at.unwrap_or_else(Utc::now),
+            actor: Some(entry.actor.clone()),
+            resource_type: Some(entry.resource_type.clone()),
+            resource_id: entry.resource_id.clone(),
+            details: entry.details.clone(),
+            ..Self::new(entry.action.clone(), outcome)
+        }
+    }
+
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn user(mut self, user_id: i32) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn wallet(mut self, wallet_id: i32) -> Self {
+        self.wallet_id = Some(wallet_id);
+        self
+    }
+
+    pub fn resource(mut self, resource_type: &str, resource_id: impl ToString) -> Self {
+        self.resource_type = Some(resource_type.to_string());
+        self.resource_id = Some(resource_id.to_string());
+        self
+    }
+
+    pub fn source_ip(mut self, ip: Option<IpAddr>) -> Self {
+        self.source_ip = ip.map(|ip| ip.to_string());
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Queues `event` for the SIEM, a no-op when no sink is configured
+pub fn emit(event: SecurityEvent) {
+    let Some(events) = EVENTS.get() else {
+        return;
+    };
+
+    if let Err(TrySendError::Full(event)) = events.try_send(event) {
+        log::warn!("SIEM queue is full, dropped {} event", event.event);
+    }
+}
+
+/// Destination of the security events, a batch is delivered once the call succeeded
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn send<'a>(&'a self, events: &'a [SecurityEvent]) -> BoxFuture<'a, Result<()>>;
+}
+
+fn sink(config: &SiemConfig, proxy: &ProxyConfig) -> Result<Option<Box<dyn EventSink>>> {
+    let timeout = Duration::from_secs(config.timeout);
+
+    Ok(match config.sink.clone() {
+        None => None,
+        Some(SiemSink::Syslog { address, protocol }) => {
+            Some(Box::new(SyslogSink::new(address, protocol)))
+        }
+        Some(SiemSink::Http { url, authorization }) => {
+            Some(Box::new(HttpSink::new(url, authorization, timeout, proxy)?))
+        }
+    })
+}
+
+/// Exporter of the configured sink, `None` when there is none. Events are collected from
+/// then on, to be sent once the exporter runs
+pub fn exporter(config: &SiemConfig, proxy: &ProxyConfig) -> Result<Option<SiemExporter>> {
+    let Some(sink) = sink(config, proxy)? else {
+        return Ok(None);
+    };
+
+    let (sender, receiver) = mpsc::channel(config.queue_size);
+
+    if EVENTS.set(sender).is_err() {
+        bail!("The SIEM exporter was already started");
+    }
+
+    Ok(Some(SiemExporter::new(sink, receiver, config)))
+}
+
+/// Sends the queued events to the sink in batches, retrying a failed batch before its
+/// events are dropped
+pub struct SiemExporter {
+    sink: Box<dyn EventSink>,
+    receiver: mpsc::Receiver<SecurityEvent>,
+    batch_size: usize,
+    timeout: Duration,
+    backoff: Duration,
+}
+
+impl SiemExporter {
+    fn new(
+        sink: Box<dyn EventSink>,
+        receiver: mpsc::Receiver<SecurityEvent>,
+        config: &SiemConfig,
+    ) -> Self {
+        Self {
+            sink,
+            receiver,
+            batch_size: config.batch_size,
+            timeout: Duration::from_secs(config.timeout),
+            backoff: Duration::from_secs(1),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    pub async fn run(mut self) {
+        let mut batch = Vec::with_capacity(self.batch_size);
+
+        while self.receiver.recv_many(&mut batch, self.batch_size).await > 0 {
+            self.export(&batch).await;
+            batch.clear();
+        }
+    }
+
+    async fn export(&self, batch: &[SecurityEvent]) {
+        let mut backoff = self.backoff;
+
+        for attempt in 1..=ATTEMPTS {
+            let result = tokio::time::timeout(self.timeout, self.sink.send(batch))
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out")));
+
+            match result {
+                Ok(()) => return,
+                Err(err) if attempt == ATTEMPTS => log::error!(
+                    "{} refused {} security events, dropping them: {err}",
+                    self.sink.name(),
+                    batch.len()
+                ),
+                Err(err) => {
+                    log::warn!("{} refused security events: {err}", self.sink.name());
+
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the batches it accepts, after refusing the first `failures` calls
+    struct RecordingSink {
+        batches: Arc<Mutex<Vec<Vec<String>>>>,
+        failures: Mutex<u32>,
+    }
+
+    impl EventSink for RecordingSink {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn send<'a>(&'a self, events: &'a [SecurityEvent]) -> BoxFuture<'a, Result<()>> {
+            async move {
+                let mut failures = self.failures.lock().unwrap();
+
+                if *failures > 0 {
+                    *failures -= 1;
+                    bail!("Unavailable");
+                }
+
+                self.batches
+                    .lock()
+                    .unwrap()
+                    .push(events.iter().map(|event| event.event.clone()).collect());
+
+                Ok(())
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_carry_the_request_context() {
+        let event = logging::scope(logging::Context::new("req-1"), async {
+            logging::set_user_id(3);
+
+            SecurityEvent::new("wallet.signed", Outcome::Success).wallet(7)
+        })
+        .await;
+
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["event"], "wallet.signed");
+        assert_eq!(json["outcome"], "success");
+        assert_eq!(json["request_id"], "req-1");
+        assert_eq!(json["user_id"], 3);
+        assert_eq!(json["wallet_id"], 7);
+        assert!(json.get("details").is_none());
+
+        let audit = SecurityEvent::audit(&AuditLogModel {
+            id: 1,
+            actor: "ip:10.0.0.1".to_string(),
+            action: "auth.login_failed".to_string(),
+            resource_type: "user".to_string(),
+            resource_id: Some("alice".to_string()),
+            details: None,
+            created_at: None,
+        });
+
+        assert_eq!(audit.outcome, Outcome::Failure);
+        assert_eq!(audit.actor.as_deref(), Some("ip:10.0.0.1"));
+        assert_eq!(audit.resource_id.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_exporter_retries_failed_batches() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let (sender, receiver) = mpsc::channel(10);
+
+        let config = SiemConfig {
+            sink: None,
+            queue_size: 10,
+            batch_size: 2,
+            timeout: 10,
+        };
+        let sink = RecordingSink {
+            batches: batches.clone(),
+            failures: Mutex::new(1),
+        };
+
+        for event in ["auth.login", "auth.login_failed", "wallet.signed"] {
+            sender
+                .send(SecurityEvent::new(event, Outcome::Success))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+
+        let mut exporter = SiemExporter::new(Box::new(sink), receiver, &config);
+        exporter.backoff = Duration::ZERO;
+
+        exporter.run().await;
+
+        assert_eq!(
+            *batches.lock().unwrap(),
+            vec![
+                vec!["auth.login".to_string(), "auth.login_failed".to_string()],
+                vec!["wallet.signed".to_string()],
+            ]
+        );
+    }
+}
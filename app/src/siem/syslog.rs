@@ -0,0 +1,153 @@
+use anyhow::{Result, anyhow};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+
+use super::{EventSink, Outcome, SERVICE, SecurityEvent};
+use crate::config::app_config::SyslogProtocol;
+
+/// `authpriv`, the facility of security and authorization messages
+const FACILITY: u8 = 10;
+
+/// Longest `MSGID` RFC 5424 allows
+const MAX_MSGID_LEN: usize = 32;
+
+/// Sends every event as an RFC 5424 message whose `MSGID` is the event name and whose
+/// message is the event as JSON, failures at the `warning` severity and the rest at `info`
+pub struct SyslogSink {
+    address: String,
+    protocol: SyslogProtocol,
+    hostname: String,
+    /// Connection of the TCP transport, reopened after a failed write
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl SyslogSink {
+    pub fn new(address: String, protocol: SyslogProtocol) -> Self {
+        Self {
+            address,
+            protocol,
+            hostname: std::env::var("HOSTNAME")
+                .ok()
+                .filter(|hostname| !hostname.is_empty())
+                .unwrap_or_else(|| "-".to_string()),
+            stream: Mutex::new(None),
+        }
+    }
+
+    async fn send_udp(&self, messages: &[String]) -> Result<()> {
+        let address = tokio::net::lookup_host(&self.address)
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("{} has no address", self.address))?;
+
+        let socket = match address.is_ipv4() {
+            true => UdpSocket::bind("0.0.0.0:0").await?,
+            false => UdpSocket::bind("[::]:0").await?,
+        };
+        socket.connect(address).await?;
+
+        for message in messages {
+            socket.send(message.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_tcp(&self, messages: &[String]) -> Result<()> {
+        let mut stream = self.stream.lock().await;
+
+        let connection = match stream.as_mut() {
+            Some(connection) => connection,
+            None => stream.insert(TcpStream::connect(&self.address).await?),
+        };
+
+        let framed = messages
+            .iter()
+            .map(|message| format!("{} {message}", message.len()))
+            .collect::<String>();
+
+        if let Err(err) = connection.write_all(framed.as_bytes()).await {
+            *stream = None;
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// RFC 5424 message of `event` from `hostname`
+fn message(event: &SecurityEvent, hostname: &str) -> Result<String> {
+    let severity = match event.outcome {
+        Outcome::Failure => 4,
+        Outcome::Success => 6,
+    };
+
+    let msgid = event
+        .event
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(MAX_MSGID_LEN)
+        .collect::<String>();
+
+    Ok(format!(
+        "<{}>1 {} {hostname} {SERVICE} {} {msgid} - {}",
+        FACILITY * 8 + severity,
+        event
+            .timestamp
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        std::process::id(),
+        serde_json::to_string(event)?
+    ))
+}
+
+impl EventSink for SyslogSink {
+    fn name(&self) -> &'static str {
+        "syslog"
+    }
+
+    fn send<'a>(&'a self, events: &'a [SecurityEvent]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let messages = events
+                .iter()
+                .map(|event| message(event, &self.hostname))
+                .collect::<Result<Vec<_>>>()?;
+
+            match self.protocol {
+                SyslogProtocol::Udp => self.send_udp(&messages).await,
+                SyslogProtocol::Tcp => self.send_tcp(&messages).await,
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_messages_follow_rfc_5424() {
+        let mut event = SecurityEvent::new("auth.login_failed", Outcome::Failure);
+        event.timestamp = Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+
+        let message = message(&event, "app-1").unwrap();
+        let (header, json) = message.split_once(" - ").unwrap();
+
+        assert_eq!(
+            header,
+            format!(
+                "<84>1 2025-06-01T12:00:00.000Z app-1 mpc-waas {} auth.login_failed",
+                std::process::id()
+            )
+        );
+
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        assert_eq!(json["event"], "auth.login_failed");
+        assert_eq!(json["outcome"], "failure");
+    }
+}
@@ -0,0 +1,222 @@
+//! The whole MPC deployment in one process for end-to-end tests: the SSE relay, three
+//! participants holding their shares in memory and the app database on SQLite, and the
+//! participants of user devices co-signing wallets
+
+use actix_web::{App, HttpServer, web};
+use participant::ParticipantHandler;
+use participant::aux_info::AuxInfoCache;
+use participant::client::Client;
+use participant::config::{BackupConfig, LimitsConfig, PolicyConfig, ProxyConfig, TimeoutConfig};
+use participant::log_context::LogContextLayer;
+use participant::policy::SigningPolicy;
+use participant::primes::{self, PrimePool};
+use participant::store::Store;
+use proto::mpc::participant_client::ParticipantClient;
+use proto::mpc::participant_server::ParticipantServer;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, Set};
+use sea_orm_migration::MigratorTrait;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Channel, Server};
+use uuid::Uuid;
+
+use crate::db::models::{
+    Chain, UserActiveModel, UserModel, WalletActiveModel, WalletModel, WalletState,
+};
+use crate::db::repositories::{UserRepository, WalletRepository};
+use crate::participants::{GrpcParticipants, ParticipantPool, rooms};
+
+const PARTICIPANTS: u16 = 3;
+
+/// Secret the app signs the room tokens with and the relay checks them against
+const ROOM_TOKEN_SECRET: [u8; 32] = [7; 32];
+
+/// Import key of the participant of the keygen `index`, the shares of imported keys are
+/// dealt to it
+fn import_key(index: u16) -> Vec<u8> {
+    vec![index as u8 + 1; 32]
+}
+
+/// Import keys the participants deal shares with, each pinning those of the others
+fn backup(index: u16) -> BackupConfig {
+    BackupConfig {
+        import_key: Some(import_key(index)),
+        peer_keys: (0..PARTICIPANTS)
+            .filter(|peer| *peer != index)
+            .map(|peer| participant::backup::public_key(&import_key(peer)).unwrap())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Relay and participants serving for the rest of the test, and the app database.
+/// The servers run on runtimes of their own so ceremonies progress while the test awaits
+pub struct Harness {
+    pub db: DatabaseConnection,
+    pub participants: Arc<dyn ParticipantPool>,
+    relay: SocketAddr,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        rooms::install(ROOM_TOKEN_SECRET.to_vec());
+
+        let relay = start_relay();
+
+        let addresses = (0..PARTICIPANTS)
+            .map(|index| start_participant(index, relay))
+            .collect::<Vec<_>>();
+
+        let channels = addresses
+            .iter()
+            .map(|address| {
+                Channel::from_shared(format!("http://{address}"))
+                    .unwrap()
+                    .connect_lazy()
+            })
+            .collect();
+
+        // Each connection of `sqlite::memory:` opens a database of its own
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+
+        let db = Database::connect(options).await.unwrap();
+
+        crate::db::migrations::Migrator::up(&db, None)
+            .await
+            .unwrap();
+
+        Self {
+            db,
+            participants: Arc::new(GrpcParticipants::new(channels)),
+            relay,
+        }
+    }
+
+    /// Participant of a user's device, which the app never calls, joining the ceremonies of
+    /// co-signed wallets through the relay
+    pub fn device(&self) -> ParticipantClient<Channel> {
+        let address = start_participant(PARTICIPANTS, self.relay);
+
+        ParticipantClient::new(
+            Channel::from_shared(format!("http://{address}"))
+                .unwrap()
+                .connect_lazy(),
+        )
+    }
+
+    pub async fn user(&self, username: &str) -> UserModel {
+        UserRepository::new(&self.db)
+            .create(UserActiveModel {
+                username: Set(username.to_string()),
+                password: Set(String::new()),
+                email: Set(format!("{username}@example.com")),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Wallet of the user as `create_wallet` leaves it before the keygen
+    pub async fn creating_wallet(&self, user: &UserModel, chain: Chain) -> WalletModel {
+        WalletRepository::new_with_connection(&self.db)
+            .create(WalletActiveModel {
+                user_id: Set(user.id),
+                name: Set("wallet".to_string()),
+                chain: Set(chain),
+                namespace: Set(Uuid::new_v4().simple().to_string()),
+                state: Set(WalletState::Creating),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+}
+
+/// Serves the relay on a free port with a single worker
+fn start_relay() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let db = web::Data::new(sse::Db::empty().authenticated(Some(ROOM_TOKEN_SECRET.to_vec())));
+
+    let server = HttpServer::new(move || App::new().app_data(db.clone()).configure(sse::configure))
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+
+    std::thread::spawn(move || actix_web::rt::System::new().block_on(server));
+
+    address
+}
+
+/// Serves the participant of the keygen `index` on a free port, talking to the relay
+fn start_participant(index: u16, relay: SocketAddr) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    listener.set_nonblocking(true).unwrap();
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async move {
+            let client = Client::new(
+                format!("http://{relay}").parse().unwrap(),
+                &ProxyConfig::default(),
+            )
+            .unwrap();
+
+            let store = Arc::new(Store::in_memory());
+
+            // A pregenerated pair each for the aux info of the ECDSA keygens, which would
+            // spend minutes generating their own
+            let primes = PrimePool::seeded(
+                store.clone(),
+                vec![primes::pregenerated().swap_remove(index as usize)],
+            );
+
+            let aux = AuxInfoCache::new(store.clone(), Arc::new(primes), None);
+
+            let handler = ParticipantHandler::new(
+                client,
+                store,
+                Arc::new(aux),
+                index,
+                &LimitsConfig {
+                    max_keygens: 4,
+                    max_signings: 4,
+                    max_queued: 16,
+                },
+                &TimeoutConfig {
+                    keygen: 60,
+                    signing: 60,
+                },
+                SigningPolicy::new(PolicyConfig::default()),
+            )
+            .with_backup(backup(index));
+
+            // Reports serving, the monitor checking Vault doesn't run in memory
+            let (_reporter, health) = tonic_health::server::health_reporter();
+
+            let incoming = TcpIncoming::from(tokio::net::TcpListener::from_std(listener).unwrap());
+
+            Server::builder()
+                .layer(LogContextLayer)
+                .add_service(health)
+                .add_service(ParticipantServer::new(handler))
+                .serve_with_incoming(incoming)
+                .await
+                .unwrap();
+        })
+    });
+
+    address
+}
@@ -0,0 +1,20 @@
+use crate::config::app_config::ProxyConfig;
+use alloy::transports::http::reqwest::{Client, NoProxy, Proxy};
+use anyhow::Result;
+
+/// Builds the HTTP client shared by every outbound integration, honoring the proxy settings
+pub fn http_client(proxy: &ProxyConfig) -> Result<Client> {
+    let no_proxy = NoProxy::from_string(&proxy.no_proxy.join(","));
+
+    let mut builder = Client::builder();
+
+    if let Some(url) = &proxy.http {
+        builder = builder.proxy(Proxy::http(url)?.no_proxy(no_proxy.clone()));
+    }
+
+    if let Some(url) = &proxy.https {
+        builder = builder.proxy(Proxy::https(url)?.no_proxy(no_proxy));
+    }
+
+    Ok(builder.build()?)
+}
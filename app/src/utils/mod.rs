@@ -1,3 +1,4 @@
+pub mod http;
 pub mod request;
 pub mod validate;
 pub mod validators;
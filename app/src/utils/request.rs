@@ -1,12 +1,24 @@
+use crate::api::error::ApiError;
 use crate::auth::Claims;
 use actix_web::{HttpMessage, HttpRequest};
 
-pub fn request_user_id(req: &HttpRequest) -> Result<i32, actix_web::Error> {
+pub fn request_user_id(req: &HttpRequest) -> Result<i32, ApiError> {
     let ext = req.extensions();
 
     let claims = &ext
         .get::<Claims>()
-        .ok_or(actix_web::error::ErrorUnauthorized("User not authorized"))?;
+        .ok_or_else(|| ApiError::unauthorized("User not authorized"))?;
 
     Ok(claims.user_id)
 }
+
+/// Session of the token the request was authenticated with
+pub fn request_session_id(req: &HttpRequest) -> Result<String, ApiError> {
+    let ext = req.extensions();
+
+    let claims = &ext
+        .get::<Claims>()
+        .ok_or_else(|| ApiError::unauthorized("User not authorized"))?;
+
+    Ok(claims.jti.clone())
+}
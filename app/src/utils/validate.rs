@@ -1,15 +1,16 @@
-use actix_web::{Error, error::ErrorUnprocessableEntity, web};
+use crate::api::error::ApiError;
+use actix_web::web;
 use validator::{Validate, ValidationErrors};
 
-pub fn validate_item<T: Validate>(item: &T) -> Result<(), Error> {
+pub fn validate_item<T: Validate>(item: &T) -> Result<(), ApiError> {
     if let Err(err) = item.validate() {
         let error_messages = format_err(err);
-        return Err(ErrorUnprocessableEntity(error_messages));
+        return Err(ApiError::unprocessable("validation_failed", error_messages));
     }
     Ok(())
 }
 
-pub fn validate_req<T: Validate>(json: &web::Json<T>) -> Result<(), Error> {
+pub fn validate_req<T: Validate>(json: &web::Json<T>) -> Result<(), ApiError> {
     validate_item(&json.0)
 }
 
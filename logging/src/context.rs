@@ -0,0 +1,90 @@
+use std::cell::Cell;
+use std::future::Future;
+use tokio::task::futures::TaskLocalFuture;
+
+/// Header carrying the request id between the services, `X-Request-Id` on HTTP and gRPC
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// gRPC metadata carrying the user the app calls a participant for
+pub const USER_ID_HEADER: &str = "x-user-id";
+/// gRPC metadata carrying the wallet the app calls a participant for
+pub const WALLET_ID_HEADER: &str = "x-wallet-id";
+
+tokio::task_local! {
+    static CONTEXT: Context;
+}
+
+/// Ids of the request being served, added to every JSON log line written while serving it.
+/// The user and the wallet are set once known, e.g. after authentication
+#[derive(Debug, Default)]
+pub struct Context {
+    request_id: Option<String>,
+    user_id: Cell<Option<i32>>,
+    wallet_id: Cell<Option<i32>>,
+}
+
+impl Context {
+    pub fn new(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: Some(request_id.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Context of the headers `header` looks up, ids that don't parse are left out
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Self {
+        Self {
+            request_id: header(REQUEST_ID_HEADER).map(str::to_string),
+            user_id: Cell::new(header(USER_ID_HEADER).and_then(|id| id.parse().ok())),
+            wallet_id: Cell::new(header(WALLET_ID_HEADER).and_then(|id| id.parse().ok())),
+        }
+    }
+}
+
+/// Runs `future` with `context` as the log context
+pub fn scope<F: Future>(context: Context, future: F) -> TaskLocalFuture<Context, F> {
+    CONTEXT.scope(context, future)
+}
+
+/// Request id of the current context
+pub fn request_id() -> Option<String> {
+    CONTEXT
+        .try_with(|context| context.request_id.clone())
+        .ok()
+        .flatten()
+}
+
+pub fn user_id() -> Option<i32> {
+    CONTEXT
+        .try_with(|context| context.user_id.get())
+        .ok()
+        .flatten()
+}
+
+pub fn wallet_id() -> Option<i32> {
+    CONTEXT
+        .try_with(|context| context.wallet_id.get())
+        .ok()
+        .flatten()
+}
+
+/// Sets the user of the current context, a no-op outside of one
+pub fn set_user_id(user_id: i32) {
+    let _ = CONTEXT.try_with(|context| context.user_id.set(Some(user_id)));
+}
+
+/// Sets the wallet of the current context, a no-op outside of one
+pub fn set_wallet_id(wallet_id: i32) {
+    let _ = CONTEXT.try_with(|context| context.wallet_id.set(Some(wallet_id)));
+}
+
+/// Headers of the ids of the current context, to pass it on to another service
+pub fn headers() -> Vec<(&'static str, String)> {
+    [
+        (REQUEST_ID_HEADER, request_id()),
+        (USER_ID_HEADER, user_id().map(|id| id.to_string())),
+        (WALLET_ID_HEADER, wallet_id().map(|id| id.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(header, value)| Some((header, value?)))
+    .collect()
+}
@@ -0,0 +1,159 @@
+//! Logging shared by the services: `env_logger` filtered by `RUST_LOG`, writing plain text or,
+//! with `LOG_FORMAT=json`, one JSON object per line carrying the service and the ids of the
+//! request being served. Both formats redact credentials and signing payloads from messages.
+
+mod context;
+mod redact;
+
+pub use context::{
+    Context, REQUEST_ID_HEADER, USER_ID_HEADER, WALLET_ID_HEADER, headers, request_id, scope,
+    set_user_id, set_wallet_id, user_id, wallet_id,
+};
+pub use redact::{REDACTED, redact};
+
+use serde_json::{Map, Value};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    /// Format of `LOG_FORMAT`, text when unset
+    fn from_env() -> Result<Self, String> {
+        match std::env::var("LOG_FORMAT").as_deref() {
+            Err(_) | Ok("") | Ok("text") => Ok(Format::Text),
+            Ok("json") => Ok(Format::Json),
+            Ok(other) => Err(other.to_string()),
+        }
+    }
+}
+
+/// JSON log line of a record of `service`
+fn json_line(service: &str, timestamp: String, record: &log::Record) -> Value {
+    let mut line = Map::new();
+
+    line.insert("timestamp".to_string(), timestamp.into());
+    line.insert("level".to_string(), record.level().as_str().into());
+    line.insert("service".to_string(), service.into());
+    line.insert("target".to_string(), record.target().into());
+    line.insert(
+        "message".to_string(),
+        redact(&record.args().to_string()).into(),
+    );
+
+    if let Some(request_id) = request_id() {
+        line.insert("request_id".to_string(), request_id.into());
+    }
+    if let Some(user_id) = user_id() {
+        line.insert("user_id".to_string(), user_id.into());
+    }
+    if let Some(wallet_id) = wallet_id() {
+        line.insert("wallet_id".to_string(), wallet_id.into());
+    }
+
+    Value::Object(line)
+}
+
+/// Installs the logger of `service`, at the `info` level unless `RUST_LOG` says otherwise
+pub fn init(service: &'static str) {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let format = Format::from_env();
+
+    match format {
+        Ok(Format::Json) => builder.format(move |buf, record| {
+            let line = json_line(service, buf.timestamp().to_string(), record);
+
+            writeln!(buf, "{line}")
+        }),
+        _ => builder.format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact(&record.args().to_string())
+            )
+        }),
+    };
+
+    builder.init();
+
+    if let Err(other) = format {
+        log::warn!("Unknown LOG_FORMAT {other}, logging text");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_json_lines_carry_the_request_context() {
+        let record = |message: &str| {
+            json_line(
+                "app",
+                "2025-06-01T00:00:00Z".to_string(),
+                &log::Record::builder()
+                    .args(format_args!("{message}"))
+                    .level(log::Level::Info)
+                    .target("app::api")
+                    .build(),
+            )
+        };
+
+        let line = record("Started");
+
+        assert_eq!(line["service"], "app");
+        assert_eq!(line["level"], "INFO");
+        assert!(line.get("request_id").is_none());
+
+        let line = scope(Context::new("req-1"), async {
+            set_user_id(3);
+            set_wallet_id(7);
+
+            record("Signing with Authorization: Bearer abc")
+        })
+        .await;
+
+        assert_eq!(line["request_id"], "req-1");
+        assert_eq!(line["user_id"], 3);
+        assert_eq!(line["wallet_id"], 7);
+        assert_eq!(line["message"], "Signing with Authorization: [REDACTED]");
+    }
+
+    #[tokio::test]
+    async fn test_context_is_passed_on_in_headers() {
+        let headers = scope(Context::new("req-1"), async {
+            set_wallet_id(7);
+
+            headers()
+        })
+        .await;
+
+        assert_eq!(
+            headers,
+            vec![
+                (REQUEST_ID_HEADER, "req-1".to_string()),
+                (WALLET_ID_HEADER, "7".to_string()),
+            ]
+        );
+
+        let context = Context::from_headers(|name| {
+            headers
+                .iter()
+                .find(|(header, _)| *header == name)
+                .map(|(_, value)| value.as_str())
+        });
+
+        let wallet_id = scope(context, async { wallet_id() }).await;
+
+        assert_eq!(wallet_id, Some(7));
+        assert!(super::headers().is_empty());
+    }
+}
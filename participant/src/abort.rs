@@ -0,0 +1,194 @@
+use std::error::Error;
+
+use proto::mpc::AbortDetails;
+
+use crate::frost::FrostError;
+
+// cggmp21 keeps its abort reasons private, so they are recognised from the `Debug`
+// output of the error chain. Longer names first, `InvalidPsi` prefixes `InvalidPsiPrimePrime`
+static SIGNING_ABORTS: &[(&str, &str)] = &[
+    ("Round1aNotReliable", "round1a"),
+    ("EncProofOfK", "round1b"),
+    ("InvalidPsiPrimePrime", "round3"),
+    ("InvalidPsi", "round2"),
+    ("MismatchedDelta", "round3"),
+    ("SignatureInvalid", "round4"),
+];
+
+/// Extracts the culprits of an identifiable abort from a failed signing
+pub fn identify(err: &anyhow::Error) -> Option<AbortDetails> {
+    if let Some(FrostError::InvalidShares { parties }) = err.downcast_ref::<FrostError>() {
+        return Some(AbortDetails {
+            faulty_parties: parties.iter().copied().map(u32::from).collect(),
+            round: "frost_round2".to_string(),
+            reason: err.to_string(),
+        });
+    }
+
+    let mut source: Option<&(dyn Error + 'static)> = Some(err.as_ref());
+
+    while let Some(err) = source {
+        let debug = format!("{err:?}");
+
+        for (variant, round) in SIGNING_ABORTS {
+            if let Some(rest) = debug.strip_prefix(variant) {
+                return Some(AbortDetails {
+                    faulty_parties: faulty_parties(rest),
+                    round: round.to_string(),
+                    reason: err.to_string(),
+                });
+            }
+        }
+
+        source = err.source();
+    }
+
+    None
+}
+
+// Aborts list one tuple per faulty party, starting with its index: `([(1, 0, 3), ...])`
+fn faulty_parties(debug: &str) -> Vec<u32> {
+    let Some((_, list)) = debug.split_once('[') else {
+        return Vec::new();
+    };
+
+    let mut parties = Vec::new();
+    let mut depth = 0;
+
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => {
+                if depth == 0 {
+                    let digits: String = list[i + 1..]
+                        .chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect();
+
+                    if let Ok(party) = digits.parse() {
+                        parties.push(party);
+                    }
+                }
+                depth += 1;
+            }
+            ')' => depth -= 1,
+            ']' if depth == 0 => break,
+            _ => {}
+        }
+    }
+
+    parties.sort_unstable();
+    parties.dedup();
+
+    parties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mirrors the error chain of cggmp21 0.6.2: `SigningError` wraps a private `Reason`,
+    // whose `Aborted` variant holds the private `SigningAborted`
+    #[derive(Debug, thiserror::Error)]
+    #[error("signing protocol failed")]
+    struct SigningError(#[source] Reason);
+
+    #[derive(Debug, thiserror::Error)]
+    enum Reason {
+        #[error("protocol was maliciously aborted by another party")]
+        Aborted(#[source] SigningAborted),
+    }
+
+    #[derive(Debug)]
+    struct InvalidProof;
+
+    #[allow(clippy::type_complexity, dead_code)]
+    #[derive(Debug, thiserror::Error)]
+    enum SigningAborted {
+        #[error("pi_enc::verify(K) failed")]
+        EncProofOfK(Vec<(u16, u64, u64)>),
+        #[error("ψ, ψˆ, or ψ' proofs are invalid")]
+        InvalidPsi(
+            Vec<(
+                u16,
+                u64,
+                u64,
+                (
+                    Option<InvalidProof>,
+                    Option<InvalidProof>,
+                    Option<InvalidProof>,
+                ),
+            )>,
+        ),
+        #[error("ψ'' proof is invalid")]
+        InvalidPsiPrimePrime(Vec<(u16, u64, u64)>),
+        #[error("Delta != G * delta")]
+        MismatchedDelta,
+        #[error("resulting signature is not valid")]
+        SignatureInvalid,
+        #[error("other parties received different broadcast messages at round1a")]
+        Round1aNotReliable(Vec<(u16, u64)>),
+    }
+
+    fn aborted(reason: SigningAborted) -> anyhow::Error {
+        anyhow::Error::new(SigningError(Reason::Aborted(reason))).context("Signing failed")
+    }
+
+    #[test]
+    fn test_identify_enc_proof_of_k() {
+        let details = identify(&aborted(SigningAborted::EncProofOfK(vec![
+            (2, 4, 5),
+            (0, 1, 5),
+        ])))
+        .unwrap();
+
+        assert_eq!(details.faulty_parties, vec![0, 2]);
+        assert_eq!(details.round, "round1b");
+        assert_eq!(details.reason, "pi_enc::verify(K) failed");
+    }
+
+    #[test]
+    fn test_identify_invalid_psi_skips_nested_proofs() {
+        let details = identify(&aborted(SigningAborted::InvalidPsi(vec![(
+            1,
+            3,
+            4,
+            (Some(InvalidProof), None, Some(InvalidProof)),
+        )])))
+        .unwrap();
+
+        assert_eq!(details.faulty_parties, vec![1]);
+        assert_eq!(details.round, "round2");
+    }
+
+    #[test]
+    fn test_identify_invalid_psi_prime_prime() {
+        let details = identify(&aborted(SigningAborted::InvalidPsiPrimePrime(vec![(
+            2, 7, 9,
+        )])))
+        .unwrap();
+
+        assert_eq!(details.faulty_parties, vec![2]);
+        assert_eq!(details.round, "round3");
+    }
+
+    #[test]
+    fn test_identify_round1a_not_reliable() {
+        let details = identify(&aborted(SigningAborted::Round1aNotReliable(vec![(3, 0)]))).unwrap();
+
+        assert_eq!(details.faulty_parties, vec![3]);
+        assert_eq!(details.round, "round1a");
+    }
+
+    #[test]
+    fn test_identify_abort_without_culprits() {
+        let details = identify(&aborted(SigningAborted::SignatureInvalid)).unwrap();
+
+        assert!(details.faulty_parties.is_empty());
+        assert_eq!(details.round, "round4");
+    }
+
+    #[test]
+    fn test_identify_ignores_other_failures() {
+        assert!(identify(&anyhow::anyhow!("Signing timed out")).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use alloy::primitives::hex;
+use cggmp21::PregeneratedPrimes;
+use cggmp21::key_share::{DirtyAuxInfo, Valid};
+use cggmp21::security_level::SecurityLevel128;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use vaultrs::error::ClientError;
+
+use crate::primes::PrimePool;
+use crate::store::Store;
+
+/// Aux info of a keygen kept for the next ones, with the keygen index it was generated
+/// under since `parties` is ordered by it
+#[derive(Serialize, Deserialize)]
+pub struct CachedAuxInfo {
+    pub index: u16,
+    pub aux_info: Valid<DirtyAuxInfo>,
+}
+
+impl CachedAuxInfo {
+    /// Digest of the public data of every party, the same for all the parties that
+    /// generated the aux info together
+    pub fn fingerprint(&self) -> String {
+        let parties = serde_json::to_vec(&self.aux_info.parties).unwrap_or_default();
+
+        hex::encode(Sha256::digest(parties))
+    }
+}
+
+/// Where keygens get their aux info from. Aux info isn't specific to a wallet, with an
+/// epoch configured the aux info of a keygen is stored and reused by the next keygens of
+/// the epoch whose parties all hold the same. Without one every keygen generates its own
+pub struct AuxInfoCache {
+    store: Arc<Store>,
+    primes: Arc<PrimePool>,
+    epoch: Option<String>,
+}
+
+impl AuxInfoCache {
+    pub fn new(store: Arc<Store>, primes: Arc<PrimePool>, epoch: Option<String>) -> Self {
+        Self {
+            store,
+            primes,
+            epoch,
+        }
+    }
+
+    fn key(epoch: &str) -> String {
+        format!("aux_info/{epoch}")
+    }
+
+    /// Primes for a fresh aux info
+    pub async fn primes(&self) -> PregeneratedPrimes<SecurityLevel128> {
+        self.primes.take().await
+    }
+
+    /// Aux info reusable in the current epoch, `None` without an epoch or when none was
+    /// stored yet
+    pub async fn load(&self) -> Option<CachedAuxInfo> {
+        let epoch = self.epoch.as_deref()?;
+
+        match self.store.read(&Self::key(epoch)).await {
+            Ok(cached) => Some(cached),
+            Err(ClientError::APIError { code: 404, .. }) => None,
+            Err(err) => {
+                warn!("Failed to read the aux info of epoch {epoch}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Keeps a freshly generated aux info for the next keygens of the epoch, replacing the
+    /// previous one
+    pub async fn save(&self, index: u16, aux_info: &Valid<DirtyAuxInfo>) {
+        let Some(epoch) = self.epoch.as_deref() else {
+            return;
+        };
+
+        let cached = CachedAuxInfo {
+            index,
+            aux_info: aux_info.clone(),
+        };
+
+        if let Err(err) = self.store.set(&Self::key(epoch), &cached).await {
+            warn!("Failed to store the aux info of epoch {epoch}: {err}");
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{Result, anyhow};
+use cggmp21::supported_curves::Secp256k1;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use generic_ec::{Point, Scalar, SecretScalar};
+use hkdf::Hkdf;
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use proto::mpc::ExportApproval;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+static BACKUP_INFO: &[u8] = b"mpc-waas-share-backup-v1";
+
+static APPROVAL_DOMAIN: &[u8] = b"mpc-waas-export-approval-v1";
+
+#[derive(Deserialize, Serialize)]
+pub struct ShareSecret {
+    pub index: u16,
+    pub share: String,
+}
+
+pub struct EncryptedBackup {
+    pub ephemeral_key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+// ECIES over secp256k1: the shared point between an ephemeral key and the
+// operator key is expanded with HKDF into a ChaCha20-Poly1305 key
+fn derive_key(shared: Point<Secp256k1>, ephemeral: Point<Secp256k1>) -> Key {
+    let ikm = [
+        shared.to_bytes(true).as_bytes(),
+        ephemeral.to_bytes(true).as_bytes(),
+    ]
+    .concat();
+
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(None, &ikm)
+        .expand(BACKUP_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    key
+}
+
+pub fn encrypt(secret: &ShareSecret, public_key: &[u8], aad: &[u8]) -> Result<EncryptedBackup> {
+    let public_key = Point::<Secp256k1>::from_bytes(public_key)
+        .map_err(|_| anyhow!("Invalid backup public key"))?;
+
+    let ephemeral_secret = SecretScalar::<Secp256k1>::random(&mut rand::rngs::OsRng);
+    let ephemeral = Point::generator() * &ephemeral_secret;
+    let shared = public_key * &ephemeral_secret;
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(shared, ephemeral));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut rand::rngs::OsRng);
+
+    let plaintext = serde_json::to_vec(secret)?;
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt share backup"))?;
+
+    Ok(EncryptedBackup {
+        ephemeral_key: ephemeral.to_bytes(true).to_vec(),
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+pub fn decrypt(backup: &EncryptedBackup, private_key: &[u8], aad: &[u8]) -> Result<ShareSecret> {
+    let mut private_key = Scalar::<Secp256k1>::from_be_bytes(private_key)
+        .map_err(|_| anyhow!("Invalid backup private key"))?;
+    let private_key = SecretScalar::new(&mut private_key);
+
+    let ephemeral = Point::<Secp256k1>::from_bytes(&backup.ephemeral_key)
+        .map_err(|_| anyhow!("Invalid backup ephemeral key"))?;
+
+    if backup.nonce.len() != 12 {
+        return Err(anyhow!("Invalid backup nonce"));
+    }
+
+    let shared = ephemeral * &private_key;
+    let cipher = ChaCha20Poly1305::new(&derive_key(shared, ephemeral));
+
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&backup.nonce),
+            Payload {
+                msg: &backup.ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("Failed to decrypt share backup"))?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// SEC1 public key of a big-endian private key
+pub fn public_key(private_key: &[u8]) -> Result<Vec<u8>> {
+    let private_key =
+        k256::SecretKey::from_slice(private_key).map_err(|_| anyhow!("Invalid private key"))?;
+
+    Ok(private_key.public_key().to_sec1_bytes().to_vec())
+}
+
+/// Message an approver signs to approve the export of a wallet's shares to `public_key`
+pub fn approval_message(export_id: i32, wallet_id: i32, public_key: &[u8]) -> Vec<u8> {
+    [
+        APPROVAL_DOMAIN,
+        &export_id.to_be_bytes(),
+        &wallet_id.to_be_bytes(),
+        public_key,
+    ]
+    .concat()
+}
+
+/// Whether `public_key` is one of the pinned keys, whatever the SEC1 encoding of either
+pub fn is_pinned(pinned: &[Vec<u8>], public_key: &[u8]) -> bool {
+    let Ok(public_key) = k256::PublicKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+
+    pinned
+        .iter()
+        .any(|key| k256::PublicKey::from_sec1_bytes(key).is_ok_and(|key| key == public_key))
+}
+
+/// Pinned approvers with a valid signature of `message` among the approvals, each counted
+/// once however many approvals it signed
+pub fn approvers(approver_keys: &[Vec<u8>], message: &[u8], approvals: &[ExportApproval]) -> usize {
+    approver_keys
+        .iter()
+        .filter_map(|key| VerifyingKey::from_sec1_bytes(key).ok())
+        .filter(|approver| {
+            approvals.iter().any(|approval| {
+                let signed_by = VerifyingKey::from_sec1_bytes(&approval.approver_key)
+                    .is_ok_and(|key| key == *approver);
+
+                signed_by
+                    && Signature::from_slice(&approval.signature)
+                        .is_ok_and(|signature| approver.verify(message, &signature).is_ok())
+            })
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+    use k256::ecdsa::signature::Signer;
+
+    fn approver(seed: u8) -> SigningKey {
+        SigningKey::from_slice(&[seed; 32]).unwrap()
+    }
+
+    fn approval(key: &SigningKey, message: &[u8]) -> ExportApproval {
+        let signature: Signature = key.sign(message);
+
+        ExportApproval {
+            approver_key: key.verifying_key().to_sec1_bytes().to_vec(),
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_approvers_count_distinct_pinned_signatures() {
+        let (bob, carol, mallory) = (approver(1), approver(2), approver(3));
+
+        let pinned = [&bob, &carol].map(|key| {
+            key.verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec()
+        });
+
+        let message = approval_message(1, 7, &[2; 33]);
+
+        assert_eq!(
+            approvers(
+                &pinned,
+                &message,
+                &[approval(&bob, &message), approval(&carol, &message)]
+            ),
+            2
+        );
+        assert_eq!(
+            approvers(
+                &pinned,
+                &message,
+                &[approval(&bob, &message), approval(&bob, &message)]
+            ),
+            1
+        );
+        assert_eq!(
+            approvers(&pinned, &message, &[approval(&mallory, &message)]),
+            0
+        );
+
+        // Signed for another export key
+        let other = approval_message(1, 7, &[3; 33]);
+
+        assert_eq!(approvers(&pinned, &message, &[approval(&bob, &other)]), 0);
+    }
+
+    #[test]
+    fn test_is_pinned_compares_points() {
+        let key = *approver(1).verifying_key();
+
+        let pinned = [key.to_encoded_point(false).as_bytes().to_vec()];
+
+        assert!(is_pinned(&pinned, &key.to_sec1_bytes()));
+        assert!(!is_pinned(
+            &pinned,
+            &approver(2).verifying_key().to_sec1_bytes()
+        ));
+        assert!(!is_pinned(&pinned, &[2; 33]));
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let operator = approver(4);
+        let public_key = operator.verifying_key().to_sec1_bytes();
+
+        let secret = ShareSecret {
+            index: 1,
+            share: "{}".to_string(),
+        };
+
+        let backup = encrypt(&secret, &public_key, b"7").unwrap();
+
+        let decrypted = decrypt(&backup, &operator.to_bytes(), b"7").unwrap();
+
+        assert_eq!((decrypted.index, decrypted.share.as_str()), (1, "{}"));
+        assert!(decrypt(&backup, &operator.to_bytes(), b"8").is_err());
+    }
+}
@@ -1,25 +1,156 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use futures::{Sink, Stream, StreamExt, TryStreamExt};
-use log::{debug, error, info};
+use http_client::isahc::IsahcClient;
+use isahc::config::Configurable;
+use log::{debug, error, info, warn};
+use prost::Message as _;
 use round_based::{Incoming, Outgoing};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use thiserror::Error;
 
+use crate::config::ProxyConfig;
+use crate::metrics::metrics;
+
+static ENVELOPE_VERSION: u8 = 1;
+
 #[derive(Deserialize, Debug)]
-#[allow(dead_code)]
 struct IssuedUniqueIdx {
     unique_idx: u16,
 }
 
+/// Acknowledgement of a broadcast by the relay, a duplicate is a retry of a message the
+/// relay already published
+#[derive(Deserialize, Debug, Default)]
+struct BroadcastAck {
+    message_id: Option<u64>,
+    duplicate: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
-struct Msg<M> {
+struct Envelope<M> {
+    version: u8,
     sender: u16,
     receiver: Option<u16>,
+    // Distinguishes executions sharing the same room
+    epoch: u64,
+    // Monotonic per sender and epoch, starting at 0
+    sequence: u64,
     body: M,
 }
 
+impl<M: Serialize> Envelope<M> {
+    /// `relay.Envelope` of the message, its body serialized as JSON
+    fn encode(&self) -> Result<Vec<u8>, TransportError> {
+        let envelope = proto::relay::Envelope {
+            version: u32::from(self.version),
+            sender: u32::from(self.sender),
+            receiver: self.receiver.map(u32::from),
+            epoch: self.epoch,
+            sequence: self.sequence,
+            payload: serde_json::to_vec(&self.body)?,
+        };
+
+        Ok(envelope.encode_to_vec())
+    }
+}
+
+impl<M: DeserializeOwned> Envelope<M> {
+    fn decode(frame: Frame) -> Result<Self, TransportError> {
+        let data = match frame {
+            Frame::Json(data) => return Ok(serde_json::from_str(&data)?),
+            Frame::Protobuf(data) => data,
+        };
+
+        let invalid = |err: &dyn std::fmt::Display| {
+            error!("Invalid relay envelope: {err}");
+            TransportError::InvalidMessage
+        };
+
+        let bytes = BASE64.decode(data).map_err(|err| invalid(&err))?;
+        let envelope =
+            proto::relay::Envelope::decode(bytes.as_slice()).map_err(|err| invalid(&err))?;
+        let index = |index: u32| u16::try_from(index).map_err(|err| invalid(&err));
+
+        Ok(Self {
+            version: u8::try_from(envelope.version).map_err(|err| invalid(&err))?,
+            sender: index(envelope.sender)?,
+            receiver: envelope.receiver.map(index).transpose()?,
+            epoch: envelope.epoch,
+            sequence: envelope.sequence,
+            body: serde_json::from_slice(&envelope.payload)?,
+        })
+    }
+}
+
+/// Message of a room as the relay delivers it
+enum Frame {
+    /// JSON envelope, as sent by participants from before protobuf envelopes
+    Json(String),
+    /// `relay.Envelope`, base64-encoded
+    Protobuf(String),
+}
+
+static ROOM_TOKEN_HEADER: &str = "X-Room-Token";
+
+static PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Reconnections of a dropped subscription before its stream fails
+const RESUBSCRIBE_ATTEMPTS: u32 = 5;
+
+/// Attempts at delivering an outgoing message before the round fails
+const BROADCAST_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a relay request, doubled up to `RETRY_MAX_BACKOFF`
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+const RETRY_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Messages longer than this many bytes are broadcast zstd-compressed
+const COMPRESSION_THRESHOLD: usize = 16 * 1024;
+
+/// Body of a broadcast, compressed when the message is large
+struct Body {
+    data: Vec<u8>,
+    encoding: Option<&'static str>,
+}
+
+impl Body {
+    fn new(message: &[u8]) -> Self {
+        if message.len() > COMPRESSION_THRESHOLD {
+            match zstd::encode_all(message, zstd::DEFAULT_COMPRESSION_LEVEL) {
+                Ok(data) => {
+                    return Self {
+                        data,
+                        encoding: Some("zstd"),
+                    };
+                }
+                Err(err) => warn!("Failed to compress message, sending it as is: {err}"),
+            }
+        }
+
+        Self {
+            data: message.to_vec(),
+            encoding: None,
+        }
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = surf::Result<async_sse::Event>> + Send>>;
+
+/// Parameters shared by every room of a single keygen or signing execution
+pub struct Ceremony<'a> {
+    pub namespace: &'a str,
+    pub execution_id: &'a [u8],
+    pub room_token: &'a str,
+}
+
 #[derive(Error, Debug)]
 pub enum TransportError {
     #[error("Failed to serialize/deserialize message: {0}")]
@@ -46,8 +177,14 @@ pub enum TransportError {
     #[error("Invalid message format received")]
     InvalidMessage,
 
+    #[error("Relay no longer holds the messages the round resumes from")]
+    Resync,
+
     #[error("Connection to room '{room_id}' failed")]
     ConnectionFailed { room_id: String },
+
+    #[error("Room '{room_id}' holds messages of another execution")]
+    RoomCollision { room_id: String },
 }
 
 #[derive(Clone, Debug)]
@@ -56,18 +193,75 @@ pub struct Client {
 }
 
 impl Client {
-    pub fn new(address: surf::Url) -> Result<Self> {
+    pub fn new(address: surf::Url, proxy: &ProxyConfig) -> Result<Self> {
         info!("Creating new client for address: {}", address);
+
+        let config = surf::Config::new()
+            .set_base_url(address.clone())
+            .set_timeout(None);
+
+        let config = match proxy.for_url(&address) {
+            Some(proxy_url) => {
+                info!("Routing relay traffic through proxy: {}", proxy_url);
+                let client = isahc::HttpClient::builder()
+                    .proxy(Some(proxy_url.parse::<isahc::http::Uri>()?))
+                    .proxy_blacklist(proxy.no_proxy.clone())
+                    .build()?;
+                config.set_http_client(IsahcClient::from_client(client))
+            }
+            None => config,
+        };
+
         Ok(Self {
-            client: surf::Config::new()
-                .set_base_url(address)
-                .set_timeout(None)
-                .try_into()?,
+            client: config.try_into()?,
         })
     }
 
-    pub fn room(&self, room: &str) -> Room {
-        Room::new(self.client.clone(), room.to_string())
+    pub async fn ping(&self) -> Result<(), TransportError> {
+        let response =
+            self.client.get("health").await.map_err(|e| {
+                TransportError::Http(format!("Relay unreachable: {}", e.into_inner()))
+            })?;
+
+        if !response.status().is_success() {
+            return Err(TransportError::Http(format!(
+                "Relay unhealthy: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Room of the execution, named after its epoch so a retried execution never joins the
+    /// room of a failed attempt
+    pub fn room(&self, ceremony: &Ceremony, room: &str) -> Room {
+        let kind = room_kind(room);
+        let epoch = tokens::room::epoch(ceremony.execution_id);
+
+        // Wallets created before namespacing keep the legacy room names
+        let room = if ceremony.namespace.is_empty() {
+            format!("{room}_{epoch:016x}")
+        } else {
+            format!("{}_{room}_{epoch:016x}", ceremony.namespace)
+        };
+
+        Room::new(
+            self.client.clone(),
+            room,
+            kind,
+            epoch,
+            ceremony.room_token.to_string(),
+        )
+    }
+}
+
+/// Room name without the id of the wallet or the ceremony, e.g. `keygen` of `keygen_12`, the
+/// label of its metrics
+fn room_kind(room: &str) -> String {
+    match room.rsplit_once('_') {
+        Some((kind, id)) if id.chars().all(|c| c.is_ascii_digit()) => kind.to_string(),
+        _ => room.to_string(),
     }
 }
 
@@ -75,13 +269,25 @@ impl Client {
 pub struct Room {
     client: surf::Client,
     room: String,
+    kind: String,
+    epoch: u64,
+    token: String,
 }
 
 impl Room {
-    pub fn new(client: surf::Client, room: String) -> Self {
+    pub fn new(
+        client: surf::Client,
+        room: String,
+        kind: String,
+        epoch: u64,
+        token: String,
+    ) -> Self {
         Room {
             client,
             room: format!("rooms/{}", room),
+            kind,
+            epoch,
+            token,
         }
     }
 
@@ -89,13 +295,20 @@ impl Room {
         format!("{}/{}", self.room, endpoint)
     }
 
-    #[allow(dead_code)]
-    async fn issue_index(&self) -> Result<u16, TransportError> {
-        let endpoint = self.endpoint("issue_unique_idx");
+    fn authorize(&self, request: surf::RequestBuilder) -> surf::RequestBuilder {
+        if self.token.is_empty() {
+            request
+        } else {
+            request.header(ROOM_TOKEN_HEADER, self.token.as_str())
+        }
+    }
+
+    /// Issues an index unique among the parties of this execution, starting at 0
+    pub async fn issue_index(&self) -> Result<u16, TransportError> {
+        let endpoint = format!("{}?epoch={}", self.endpoint("issue_unique_idx"), self.epoch);
         debug!("Requesting unique index from endpoint: {}", endpoint);
         let response = self
-            .client
-            .post(endpoint)
+            .authorize(self.client.post(endpoint))
             .recv_json::<IssuedUniqueIdx>()
             .await
             .map_err(|e| {
@@ -108,62 +321,194 @@ impl Room {
         Ok(response.unique_idx)
     }
 
-    async fn broadcast(&self, message: &str) -> Result<(), TransportError> {
+    /// Posts a message once, failures the relay won't recover from are not retried
+    async fn post(&self, message: &Body) -> Result<BroadcastAck, (TransportError, bool)> {
         let endpoint = self.endpoint("broadcast");
         debug!("Broadcasting message to endpoint: {}", endpoint);
-        self.client
-            .post(endpoint)
-            .body(message)
-            .await
-            .map_err(|e| {
-                let err = TransportError::Http(format!(
-                    "Failed to broadcast message: {}",
-                    e.into_inner()
-                ));
-                error!("Failed to broadcast message: {}", err);
-                err
-            })?;
-        debug!("Message broadcast successful");
-        Ok(())
+
+        let request = self
+            .authorize(self.client.post(endpoint))
+            .content_type(PROTOBUF_CONTENT_TYPE);
+        let request = match message.encoding {
+            Some(encoding) => request.header("Content-Encoding", encoding),
+            None => request,
+        };
+
+        let mut response = request.body(message.data.clone()).await.map_err(|e| {
+            let err =
+                TransportError::Http(format!("Failed to broadcast message: {}", e.into_inner()));
+            (err, true)
+        })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response.body_string().await.unwrap_or_default();
+            let err =
+                TransportError::Http(format!("Failed to broadcast message: {status} {reason}"));
+            return Err((err, status.is_server_error()));
+        }
+
+        // Relays from before acknowledgements answer with an empty body
+        let body = response.body_string().await.unwrap_or_default();
+        if body.is_empty() {
+            return Ok(BroadcastAck::default());
+        }
+
+        serde_json::from_str(&body).map_err(|err| (TransportError::Serialization(err), false))
     }
 
-    async fn subscribe(
-        &self,
-    ) -> Result<
-        std::pin::Pin<Box<dyn Stream<Item = Result<String, anyhow::Error>> + Send>>,
-        TransportError,
-    > {
+    /// Delivers a message to the relay, retrying with exponential backoff until it is
+    /// acknowledged. Retries carry the same sequence so the relay publishes the message once
+    async fn broadcast(&self, message: &[u8]) -> Result<(), TransportError> {
+        let mut delay = RETRY_BACKOFF;
+        let started = Instant::now();
+        let body = Body::new(message);
+
+        for attempt in 1..=BROADCAST_ATTEMPTS {
+            match self.post(&body).await {
+                Ok(ack) => {
+                    metrics().message_sent(&self.kind);
+                    metrics().relay_broadcast(&self.kind, started.elapsed());
+
+                    debug!(
+                        "Message broadcast acknowledged as event {:?}{}",
+                        ack.message_id,
+                        if ack.duplicate { " (duplicate)" } else { "" }
+                    );
+                    return Ok(());
+                }
+                Err((err, retry)) if retry && attempt < BROADCAST_ATTEMPTS => {
+                    warn!("Broadcast attempt {attempt}/{BROADCAST_ATTEMPTS} failed: {err}");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RETRY_MAX_BACKOFF);
+                }
+                Err((err, _)) => {
+                    error!("Failed to broadcast message: {}", err);
+                    return Err(err);
+                }
+            }
+        }
+
+        Err(TransportError::Broadcast)
+    }
+
+    /// Opens the SSE stream of the room, resuming after `last_event_id` when set
+    async fn open(&self, last_event_id: Option<&str>) -> Result<EventStream, TransportError> {
         let endpoint = self.endpoint("subscribe");
         debug!("Subscribing to SSE stream at endpoint: {}", endpoint);
-        let response = self.client.get(endpoint).await.map_err(|e| {
+
+        let request = self.authorize(self.client.get(endpoint));
+        let request = match last_event_id {
+            Some(id) => request.header("Last-Event-ID", id),
+            None => request,
+        };
+
+        let response = request.await.map_err(|e| {
             let err =
                 TransportError::Http(format!("Failed to subscribe to stream: {}", e.into_inner()));
             error!("Failed to subscribe to stream: {}", err);
             err
         })?;
-        let events = async_sse::decode(response);
-        let stream = events.filter_map(|msg| {
-            Box::pin(async {
-                match msg {
-                    Ok(async_sse::Event::Message(msg)) => Some(
-                        String::from_utf8(msg.into_bytes())
-                            .context("Received invalid UTF-8 in SSE message"),
-                    ),
-                    Ok(_) => {
+
+        if !response.status().is_success() {
+            return Err(TransportError::Http(format!(
+                "Failed to subscribe to stream: {}",
+                response.status()
+            )));
+        }
+
+        Ok(Box::pin(async_sse::decode(response)))
+    }
+
+    /// Reopens a dropped SSE stream with exponential backoff, the relay replays the events
+    /// after `last_event_id` from the room history
+    async fn resubscribe(
+        &self,
+        last_event_id: Option<&str>,
+    ) -> Result<EventStream, TransportError> {
+        let mut delay = RETRY_BACKOFF;
+
+        for attempt in 1..=RESUBSCRIBE_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+
+            match self.open(last_event_id).await {
+                Ok(events) => {
+                    info!(
+                        "Resubscribed to '{}' after event {:?}",
+                        self.room, last_event_id
+                    );
+                    return Ok(events);
+                }
+                Err(err) => {
+                    warn!(
+                        "Resubscription {attempt}/{RESUBSCRIBE_ATTEMPTS} to '{}' failed: {err}",
+                        self.room
+                    );
+                    delay = (delay * 2).min(RETRY_MAX_BACKOFF);
+                }
+            }
+        }
+
+        Err(TransportError::Subscription)
+    }
+
+    /// Messages of the room, the subscription survives dropped connections by resuming
+    /// from the id of the last event received
+    async fn subscribe(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Frame, anyhow::Error>> + Send>>, TransportError>
+    {
+        let events = self.open(None).await?;
+
+        let stream = futures::stream::unfold(
+            Some((self.clone(), events, None::<String>)),
+            |state| async move {
+                let (room, mut events, mut last_event_id) = state?;
+
+                loop {
+                    match events.next().await {
+                        // Messages were lost, the round can't go on without them
+                        Some(Ok(async_sse::Event::Message(msg))) if msg.name() == "resync" => {
+                            error!(
+                                "Relay dropped the messages of '{}' after event {:?}",
+                                room.room, last_event_id
+                            );
+                            return Some((Err(anyhow::Error::new(TransportError::Resync)), None));
+                        }
+                        Some(Ok(async_sse::Event::Message(msg))) => {
+                            if let Some(id) = msg.id() {
+                                last_event_id = Some(id.clone());
+                            }
+
+                            let protobuf = msg.name() == "new-envelope";
+                            let frame = String::from_utf8(msg.into_bytes())
+                                .context("Received invalid UTF-8 in SSE message")
+                                .map(|data| match protobuf {
+                                    true => Frame::Protobuf(data),
+                                    false => Frame::Json(data),
+                                });
+
+                            return Some((frame, Some((room, events, last_event_id))));
+                        }
                         // ignore other types of SSE events (like comments, etc.)
-                        None
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            warn!("SSE stream of '{}' failed: {}", room.room, e.into_inner());
+                        }
+                        None => warn!("SSE stream of '{}' closed", room.room),
                     }
-                    Err(e) => {
-                        let err = anyhow::Error::new(TransportError::Sse(format!(
-                            "SSE stream error: {}",
-                            e.into_inner()
-                        )));
-                        error!("SSE stream error: {}", err);
-                        Some(Err(err))
+
+                    match room.resubscribe(last_event_id.as_deref()).await {
+                        Ok(resumed) => events = resumed,
+                        Err(err) => {
+                            error!("SSE stream error: {}", err);
+                            return Some((Err(anyhow::Error::new(err)), None));
+                        }
                     }
                 }
-            })
-        });
+            },
+        );
+
         Ok(Box::pin(stream))
     }
 
@@ -179,21 +524,40 @@ impl Room {
         M: Serialize + DeserializeOwned + Send + 'static,
     {
         let room = self.room.clone();
-
-        // Clone what we need for the closures
-        let outgoing_client = self.client.clone();
+        let epoch = self.epoch;
 
         // Construct channel of incoming messages
         let incoming = self
             .subscribe()
             .await?
             .map_err(TransportError::Network)
+            .and_then(|frame| futures::future::ready(Envelope::<M>::decode(frame)))
             .and_then(|msg| {
-                Box::pin(async move {
-                    serde_json::from_str::<Msg<M>>(&msg).map_err(TransportError::from)
+                futures::future::ready(if msg.version == ENVELOPE_VERSION {
+                    Ok(msg)
+                } else {
+                    error!("Unsupported envelope version {}", msg.version);
+                    Err(TransportError::InvalidMessage)
                 })
             });
 
+        // Rooms are named after the epoch, another one means the room is shared with another
+        // execution whose messages would be mistaken for ours
+        let room_id = self.room.clone();
+        let incoming = incoming.and_then(move |msg| {
+            futures::future::ready(if msg.epoch == epoch {
+                Ok(msg)
+            } else {
+                error!(
+                    "Room '{}' holds a message of epoch {}, expected {}",
+                    room_id, msg.epoch, epoch
+                );
+                Err(TransportError::RoomCollision {
+                    room_id: room_id.clone(),
+                })
+            })
+        });
+
         // Ignore incoming messages addressed to someone else
         let incoming = incoming.try_filter(move |msg| {
             let should_receive =
@@ -207,42 +571,71 @@ impl Room {
             futures::future::ready(should_receive)
         });
 
-        // Convert Msg<M> to Incoming<M>
-        let incoming = incoming.map_ok(|msg| Incoming {
-            id: 0,
-            sender: msg.sender,
-            msg_type: if msg.receiver.is_none() {
-                round_based::MessageType::Broadcast
+        // Drop duplicates and flag gaps in each sender's sequence
+        let mut next_sequence = HashMap::<u16, u64>::new();
+        let incoming = incoming.try_filter(move |msg| {
+            let expected = next_sequence.entry(msg.sender).or_insert(0);
+            let is_new = msg.sequence >= *expected;
+            if !is_new {
+                debug!(
+                    "Dropping duplicate message {} from sender {}",
+                    msg.sequence, msg.sender
+                );
             } else {
-                round_based::MessageType::P2P
-            },
-            msg: msg.body,
+                if msg.sequence > *expected {
+                    warn!(
+                        "Sender {} skipped messages {}..{}",
+                        msg.sender, *expected, msg.sequence
+                    );
+                }
+                *expected = msg.sequence + 1;
+            }
+            futures::future::ready(is_new)
+        });
+
+        // Convert Envelope<M> to Incoming<M>
+        let kind = self.kind.clone();
+        let incoming = incoming.map_ok(move |msg| {
+            metrics().message_received(&kind, msg.sender);
+
+            Incoming {
+                id: 0,
+                sender: msg.sender,
+                msg_type: if msg.receiver.is_none() {
+                    round_based::MessageType::Broadcast
+                } else {
+                    round_based::MessageType::P2P
+                },
+                msg: msg.body,
+            }
         });
 
         // Pin the incoming stream
         let incoming = Box::pin(incoming);
 
         // Construct channel of outgoing messages
-        let outgoing =
-            futures::sink::unfold(outgoing_client, move |client, message: Outgoing<M>| {
-                let room = self.clone();
-                Box::pin(async move {
-                    let msg = Msg {
-                        sender: index,
-                        receiver: match message.recipient {
-                            round_based::MessageDestination::AllParties => None,
-                            round_based::MessageDestination::OneParty(party_id) => Some(party_id),
-                        },
-                        body: message.msg,
-                    };
-                    let serialized = serde_json::to_string(&msg).map_err(TransportError::from)?;
-                    room.broadcast(&serialized).await.map_err(|e| {
-                        error!("Failed to broadcast outgoing message: {}", e);
-                        e
-                    })?;
-                    Ok::<_, TransportError>(client)
-                })
-            });
+        let outgoing = futures::sink::unfold(0u64, move |sequence, message: Outgoing<M>| {
+            let room = self.clone();
+            Box::pin(async move {
+                let msg = Envelope {
+                    version: ENVELOPE_VERSION,
+                    sender: index,
+                    receiver: match message.recipient {
+                        round_based::MessageDestination::AllParties => None,
+                        round_based::MessageDestination::OneParty(party_id) => Some(party_id),
+                    },
+                    epoch,
+                    sequence,
+                    body: message.msg,
+                };
+                let serialized = msg.encode()?;
+                room.broadcast(&serialized).await.map_err(|e| {
+                    error!("Failed to broadcast outgoing message: {}", e);
+                    e
+                })?;
+                Ok::<_, TransportError>(sequence + 1)
+            })
+        });
 
         // Pin the outgoing sink
         let outgoing = Box::pin(outgoing);
@@ -1,7 +1,9 @@
+use alloy::primitives::{Address, U256, hex};
 use anyhow::Result;
 use log::{debug, error, info};
 use serde::Deserialize;
-use std::env;
+use settings::ConfigSource;
+use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,6 +19,15 @@ pub struct AppConfig {
     pub sse: SSEConfig,
     pub participant: ParticipantConfig,
     pub vault: VaultConfig,
+    pub backup: BackupConfig,
+    pub proxy: ProxyConfig,
+    pub limits: LimitsConfig,
+    pub timeouts: TimeoutConfig,
+    pub policy: PolicyConfig,
+    pub primes: PrimesConfig,
+    pub aux_info: AuxInfoConfig,
+    pub sealing: SealingConfig,
+    pub share_mac: ShareMacConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -30,21 +41,247 @@ pub struct ParticipantConfig {
     pub host: String,
     pub port: u16,
     pub index: u16,
+    pub health_interval: u64,
+    /// Port of the `/metrics` endpoint, unset serves no metrics
+    pub metrics_port: Option<u16>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct VaultConfig {
     pub address: String,
     pub token: String,
+    /// Vault Enterprise namespace, unset on open-source Vault
+    pub namespace: Option<String>,
+    /// KV v2 mount of the secrets
+    pub mount: String,
+    /// Path every secret is stored under, empty for the root of the mount
+    pub prefix: String,
+}
+
+/// Share backup exports, refused unless to a pinned key and approved by enough of the
+/// pinned approvers
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BackupConfig {
+    /// SEC1 secp256k1 keys of the operators, shares are only exported to these
+    pub public_keys: Vec<Vec<u8>>,
+    /// SEC1 secp256k1 keys of the approvers signing the exports
+    pub approver_keys: Vec<Vec<u8>>,
+    /// Distinct approvers whose signature an export needs
+    pub approvals: usize,
+    /// Big-endian secp256k1 key of the participant the operators encrypt imported backups
+    /// to, imports are refused without it. The shares of key imports are dealt to it too
+    pub import_key: Option<Vec<u8>>,
+    /// SEC1 keys of the import keys of the other participants, the only keys the shares of
+    /// key imports this participant deals are encrypted to
+    pub peer_keys: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsConfig {
+    pub max_keygens: usize,
+    pub max_signings: usize,
+    pub max_queued: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeoutConfig {
+    pub keygen: u64,
+    pub signing: u64,
+}
+
+/// Pool of safe primes generated ahead of the ECDSA keygens
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrimesConfig {
+    /// Pairs kept ready, 0 generates them during each keygen
+    pub pool_size: usize,
+    /// Seconds between two pairs generated to refill the pool
+    pub refill_interval: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuxInfoConfig {
+    /// Keygens of the same epoch reuse their aux info, unset generates it for every wallet.
+    /// All participants must be given the same, changing it rotates the aux info
+    pub epoch: Option<String>,
+}
+
+/// Platform the stored secrets are sealed to, see `sealing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum SealingPlatform {
+    None,
+    Tpm,
+    Sgx,
+    SevSnp,
+}
+
+impl FromStr for SealingPlatform {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "tpm" => Ok(Self::Tpm),
+            "sgx" => Ok(Self::Sgx),
+            "sev-snp" => Ok(Self::SevSnp),
+            _ => Err(ConfigError::InvalidEnvVar(format!(
+                "Expected SEALING_PLATFORM to be none, tpm, sgx or sev-snp, got {s}"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SealingConfig {
+    pub platform: SealingPlatform,
+    /// Persistent handle of the TPM object holding the sealing key, e.g. `0x81000001`
+    pub tpm_key_handle: Option<String>,
+    /// Persistent handle of the ECDSA P-256 attestation key signing the TPM quotes
+    pub tpm_ak_handle: Option<String>,
+    /// PCRs the key is bound to and quoted, in `tpm2-tools` syntax
+    pub tpm_pcrs: String,
+    /// Gramine attestation pseudo-filesystem of the SGX enclave
+    pub attestation_dir: String,
+    /// configfs-tsm directory of the SEV-SNP attestation reports
+    pub tsm_dir: String,
+    /// File of the SEV-SNP sealing key, released into the guest after its attestation
+    pub key_path: Option<String>,
+}
+
+/// MAC of the stored shares, disabled without a key
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ShareMacConfig {
+    /// Secret of the participant, kept out of Vault
+    pub key: Option<Vec<u8>>,
+    /// Reads shares stored without a MAC, while those stored before are rewritten
+    pub accept_missing: bool,
+}
+
+/// Signing policy of the participant, disabled when no rule is set
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PolicyConfig {
+    /// Highest native value of a transaction, in wei
+    pub max_value: Option<U256>,
+    pub chain_ids: Vec<u64>,
+    pub recipients: Vec<Address>,
+    /// Signs data the policy can't decode, e.g. user operation digests or other chains
+    pub allow_opaque: bool,
+    /// Signs EVM calldata other than ERC-20 `transfer` and `approve`, e.g. deployments
+    pub allow_contract_calls: bool,
+    /// Signatures of a wallet in any hour and any 24 hours, 0 disables a limit
+    pub max_signatures_per_hour: u32,
+    pub max_signatures_per_day: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProxyConfig {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Proxy to use for the given destination, unless it is listed in `no_proxy`
+    pub fn for_url(&self, url: &surf::Url) -> Option<&str> {
+        let host = url.host_str()?;
+
+        let bypass = self.no_proxy.iter().any(|rule| {
+            let rule = rule.trim_start_matches('.');
+            rule == "*" || host == rule || host.ends_with(&format!(".{rule}"))
+        });
+
+        if bypass {
+            return None;
+        }
+
+        match url.scheme() {
+            "https" => self.https.as_deref(),
+            _ => self.http.as_deref(),
+        }
+    }
+}
+
+fn parse_env<T: FromStr>(
+    source: &ConfigSource,
+    name: &str,
+    default: &str,
+) -> Result<T, ConfigError> {
+    source
+        .var(name)
+        .unwrap_or_else(|| default.to_string())
+        .parse()
+        .map_err(|_| {
+            let err = ConfigError::InvalidEnvVar(format!("Expected {name} to be a number"));
+            error!("Invalid {} configuration: {}", name, err);
+            err
+        })
+}
+
+/// Number of operations of a limit, `0` would let none of them ever run
+fn parse_limit(source: &ConfigSource, name: &str, default: &str) -> Result<usize, ConfigError> {
+    match parse_env(source, name, default)? {
+        0 => {
+            let err = ConfigError::InvalidEnvVar(format!("Expected {name} to be at least 1"));
+            error!("Invalid {} configuration: {}", name, err);
+            Err(err)
+        }
+        limit => Ok(limit),
+    }
+}
+
+/// Comma-separated list of the variable, empty when unset
+fn parse_list<T: FromStr>(source: &ConfigSource, name: &str) -> Result<Vec<T>, ConfigError> {
+    source
+        .var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse().map_err(|_| {
+                let err = ConfigError::InvalidEnvVar(format!("Invalid {name} entry {v}"));
+                error!("Invalid {} configuration: {}", name, err);
+                err
+            })
+        })
+        .collect()
+}
+
+/// Comma-separated SEC1 secp256k1 public keys of the variable, empty when unset
+fn parse_public_keys(source: &ConfigSource, name: &str) -> Result<Vec<Vec<u8>>, ConfigError> {
+    source
+        .var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            hex::decode(v.trim_start_matches("0x"))
+                .ok()
+                .filter(|key| k256::PublicKey::from_sec1_bytes(key).is_ok())
+                .ok_or_else(|| {
+                    let err = ConfigError::InvalidEnvVar(format!(
+                        "Expected {name} entry {v} to be a hex SEC1 secp256k1 public key"
+                    ));
+                    error!("Invalid {} configuration: {}", name, err);
+                    err
+                })
+        })
+        .collect()
 }
 
 impl AppConfig {
-    pub fn from_env() -> Result<Self> {
+    /// Environment variables take precedence over the optional `APP_CONFIG` file
+    pub fn load() -> Result<Self> {
         debug!("Loading configuration from environment variables");
 
-        let sse_host = env::var("SSE_HOST").unwrap_or_else(|_| "localhost".to_string());
-        let sse_port = env::var("SSE_PORT")
-            .unwrap_or_else(|_| "8080".to_string())
+        let source = ConfigSource::load()?;
+
+        let sse_host = source
+            .var("SSE_HOST")
+            .unwrap_or_else(|| "localhost".to_string());
+        let sse_port = source
+            .var("SSE_PORT")
+            .unwrap_or_else(|| "8080".to_string())
             .parse()
             .map_err(|_| {
                 let err =
@@ -53,9 +290,12 @@ impl AppConfig {
                 err
             })?;
 
-        let participant_host = env::var("PARTICIPANT_HOST").unwrap_or_else(|_| "::1".to_string());
-        let participant_port = env::var("PARTICIPANT_PORT")
-            .unwrap_or_else(|_| "50051".to_string())
+        let participant_host = source
+            .var("PARTICIPANT_HOST")
+            .unwrap_or_else(|| "::1".to_string());
+        let participant_port = source
+            .var("PARTICIPANT_PORT")
+            .unwrap_or_else(|| "50051".to_string())
             .parse()
             .map_err(|_| {
                 let err = ConfigError::InvalidEnvVar(
@@ -64,8 +304,9 @@ impl AppConfig {
                 error!("Invalid PARTICIPANT_PORT configuration: {}", err);
                 err
             })?;
-        let participant_index = env::var("PARTICIPANT_INDEX")
-            .map_err(|_| {
+        let participant_index = source
+            .var("PARTICIPANT_INDEX")
+            .ok_or_else(|| {
                 let err = ConfigError::MissingEnvVar("PARTICIPANT_INDEX is required".to_string());
                 error!("Missing required environment variable: {}", err);
                 err
@@ -79,15 +320,209 @@ impl AppConfig {
                 err
             })?;
 
-        let vault_address =
-            env::var("VAULT_ADDRESS").unwrap_or_else(|_| "https://127.0.0.1:8200".to_string());
+        let backup = BackupConfig {
+            public_keys: parse_public_keys(&source, "BACKUP_PUBLIC_KEYS")?,
+            approver_keys: parse_public_keys(&source, "BACKUP_APPROVER_KEYS")?,
+            approvals: parse_env(&source, "BACKUP_APPROVALS", "2")?,
+            import_key: source
+                .var("BACKUP_IMPORT_KEY")
+                .filter(|v| !v.is_empty())
+                .map(|v| {
+                    hex::decode(v.trim_start_matches("0x"))
+                        .ok()
+                        .filter(|key| k256::SecretKey::from_slice(key).is_ok())
+                        .ok_or_else(|| {
+                            let err = ConfigError::InvalidEnvVar(
+                                "Expected BACKUP_IMPORT_KEY to be a hex secp256k1 private key"
+                                    .to_string(),
+                            );
+                            error!("Invalid BACKUP_IMPORT_KEY configuration: {}", err);
+                            err
+                        })
+                })
+                .transpose()?,
+            peer_keys: parse_public_keys(&source, "BACKUP_PEER_KEYS")?,
+        };
+
+        if backup.approvals == 0 {
+            let err = ConfigError::InvalidEnvVar(
+                "Expected BACKUP_APPROVALS to be at least 1".to_string(),
+            );
+            error!("Invalid BACKUP_APPROVALS configuration: {}", err);
+            return Err(err.into());
+        }
+
+        // Without approvers exports are refused, fewer than required could never approve
+        if !backup.approver_keys.is_empty() && backup.approver_keys.len() < backup.approvals {
+            let err = ConfigError::InvalidEnvVar(format!(
+                "Expected BACKUP_APPROVER_KEYS to list at least BACKUP_APPROVALS ({}) keys",
+                backup.approvals
+            ));
+            error!("Invalid BACKUP_APPROVER_KEYS configuration: {}", err);
+            return Err(err.into());
+        }
+
+        let health_interval = parse_env(&source, "HEALTH_CHECK_INTERVAL", "10")?;
+
+        let metrics_port = source
+            .var("METRICS_PORT")
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                v.parse().map_err(|_| {
+                    let err = ConfigError::InvalidEnvVar(
+                        "Expected METRICS_PORT to be a number".to_string(),
+                    );
+                    error!("Invalid METRICS_PORT configuration: {}", err);
+                    err
+                })
+            })
+            .transpose()?;
+
+        // Keygen generates safe primes and is far more CPU-heavy than signing
+        let limits = LimitsConfig {
+            max_keygens: parse_limit(&source, "MAX_CONCURRENT_KEYGENS", "2")?,
+            max_signings: parse_limit(&source, "MAX_CONCURRENT_SIGNINGS", "8")?,
+            max_queued: parse_limit(&source, "MAX_QUEUED_OPERATIONS", "16")?,
+        };
+
+        // In seconds, a stalled peer would otherwise keep the ceremony open forever
+        let timeouts = TimeoutConfig {
+            keygen: parse_env(&source, "KEYGEN_TIMEOUT", "600")?,
+            signing: parse_env(&source, "SIGNING_TIMEOUT", "120")?,
+        };
+
+        let primes = PrimesConfig {
+            pool_size: parse_env(&source, "PRIME_POOL_SIZE", "4")?,
+            refill_interval: parse_env(&source, "PRIME_POOL_REFILL_INTERVAL", "5")?,
+        };
+
+        let aux_info = AuxInfoConfig {
+            epoch: source.var("AUX_INFO_EPOCH").filter(|v| !v.is_empty()),
+        };
+
+        let policy = PolicyConfig {
+            max_value: source
+                .var("SIGNING_POLICY_MAX_VALUE")
+                .filter(|v| !v.is_empty())
+                .map(|v| {
+                    v.parse().map_err(|_| {
+                        let err = ConfigError::InvalidEnvVar(
+                            "Expected SIGNING_POLICY_MAX_VALUE to be an amount in wei".to_string(),
+                        );
+                        error!("Invalid SIGNING_POLICY_MAX_VALUE configuration: {}", err);
+                        err
+                    })
+                })
+                .transpose()?,
+            chain_ids: parse_list(&source, "SIGNING_POLICY_CHAIN_IDS")?,
+            recipients: parse_list(&source, "SIGNING_POLICY_RECIPIENTS")?,
+            allow_opaque: source
+                .var("SIGNING_POLICY_ALLOW_OPAQUE")
+                .is_some_and(|v| v == "true"),
+            allow_contract_calls: source
+                .var("SIGNING_POLICY_ALLOW_CONTRACT_CALLS")
+                .is_some_and(|v| v == "true"),
+            max_signatures_per_hour: parse_env(
+                &source,
+                "SIGNING_POLICY_MAX_SIGNATURES_PER_HOUR",
+                "0",
+            )?,
+            max_signatures_per_day: parse_env(
+                &source,
+                "SIGNING_POLICY_MAX_SIGNATURES_PER_DAY",
+                "0",
+            )?,
+        };
 
-        let vault_token = env::var("VAULT_TOKEN").map_err(|_| {
+        let sealing = SealingConfig {
+            platform: source
+                .var("SEALING_PLATFORM")
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse())
+                .transpose()
+                .inspect_err(|err| error!("Invalid SEALING_PLATFORM configuration: {}", err))?
+                .unwrap_or(SealingPlatform::None),
+            tpm_key_handle: source
+                .var("SEALING_TPM_KEY_HANDLE")
+                .filter(|v| !v.is_empty()),
+            tpm_ak_handle: source
+                .var("SEALING_TPM_AK_HANDLE")
+                .filter(|v| !v.is_empty()),
+            tpm_pcrs: source
+                .var("SEALING_TPM_PCRS")
+                .unwrap_or_else(|| "sha256:0,1,2,3,4,5,6,7".to_string()),
+            attestation_dir: source
+                .var("SEALING_ATTESTATION_DIR")
+                .unwrap_or_else(|| "/dev/attestation".to_string()),
+            tsm_dir: source
+                .var("SEALING_TSM_DIR")
+                .unwrap_or_else(|| "/sys/kernel/config/tsm/report".to_string()),
+            key_path: source.var("SEALING_KEY_PATH").filter(|v| !v.is_empty()),
+        };
+
+        let required = match sealing.platform {
+            SealingPlatform::Tpm if sealing.tpm_key_handle.is_none() => {
+                Some("SEALING_TPM_KEY_HANDLE")
+            }
+            SealingPlatform::Tpm if sealing.tpm_ak_handle.is_none() => {
+                Some("SEALING_TPM_AK_HANDLE")
+            }
+            SealingPlatform::SevSnp if sealing.key_path.is_none() => Some("SEALING_KEY_PATH"),
+            _ => None,
+        };
+
+        if let Some(name) = required {
+            let err =
+                ConfigError::MissingEnvVar(format!("{name} is required by the SEALING_PLATFORM"));
+            error!("Missing required environment variable: {}", err);
+            return Err(err.into());
+        }
+
+        let share_mac = ShareMacConfig {
+            key: source
+                .var("SHARE_MAC_KEY")
+                .filter(|v| !v.is_empty())
+                .map(|v| {
+                    hex::decode(v.trim_start_matches("0x"))
+                        .ok()
+                        .filter(|key| key.len() >= 32)
+                        .ok_or_else(|| {
+                            let err = ConfigError::InvalidEnvVar(
+                                "Expected SHARE_MAC_KEY to be at least 32 hex bytes".to_string(),
+                            );
+                            error!("Invalid SHARE_MAC_KEY configuration: {}", err);
+                            err
+                        })
+                })
+                .transpose()?,
+            accept_missing: source
+                .var("SHARE_MAC_ACCEPT_MISSING")
+                .is_some_and(|v| v == "true"),
+        };
+
+        let vault_address = source
+            .var("VAULT_ADDRESS")
+            .unwrap_or_else(|| "https://127.0.0.1:8200".to_string());
+
+        let vault_token = source.var("VAULT_TOKEN").ok_or_else(|| {
             let err = ConfigError::MissingEnvVar("VAULT_TOKEN is required".to_string());
             error!("Missing required environment variable: {}", err);
             err
         })?;
 
+        let proxy = ProxyConfig {
+            http: source.var("OUTBOUND_HTTP_PROXY").filter(|v| !v.is_empty()),
+            https: source.var("OUTBOUND_HTTPS_PROXY").filter(|v| !v.is_empty()),
+            no_proxy: source
+                .var("OUTBOUND_NO_PROXY")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+
         let config = AppConfig {
             sse: SSEConfig {
                 host: sse_host,
@@ -97,11 +532,27 @@ impl AppConfig {
                 host: participant_host,
                 port: participant_port,
                 index: participant_index,
+                health_interval,
+                metrics_port,
             },
             vault: VaultConfig {
                 address: vault_address,
                 token: vault_token,
+                namespace: source.var("VAULT_NAMESPACE").filter(|v| !v.is_empty()),
+                mount: source
+                    .var("VAULT_MOUNT")
+                    .unwrap_or_else(|| "secret".to_string()),
+                prefix: source.var("VAULT_PATH_PREFIX").unwrap_or_default(),
             },
+            backup,
+            proxy,
+            limits,
+            timeouts,
+            policy,
+            primes,
+            aux_info,
+            sealing,
+            share_mac,
         };
 
         info!(
@@ -119,4 +570,11 @@ impl AppConfig {
     pub fn participant_addr(&self) -> String {
         format!("{}:{}", self.participant.host, self.participant.port)
     }
+
+    /// Address of the `/metrics` endpoint, on the host of the gRPC server
+    pub fn metrics_addr(&self) -> Option<String> {
+        let port = self.participant.metrics_port?;
+
+        Some(format!("{}:{}", self.participant.host, port))
+    }
 }
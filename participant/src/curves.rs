@@ -0,0 +1,82 @@
+use cggmp21::hd_wallet::{self, HdWallet};
+use cggmp21::supported_curves::{Secp256k1, Secp256r1, Stark};
+use generic_ec::Curve;
+use proto::mpc::{Chain, ErrorCode, Phase, SignatureScheme};
+use tonic::{Code, Status};
+
+use crate::failure::failure;
+
+/// Curve of CGGMP21 ECDSA shares, keygen and signing are generic over it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcdsaCurve {
+    Secp256k1,
+    /// NIST P-256
+    Secp256r1,
+    Stark,
+}
+
+/// Derivation of the child keys of an HD share on the curve, SLIP-10 matches BIP-32 on
+/// secp256k1 for the non-hardened paths shares can derive
+pub trait HdCurve: Curve {
+    type Hd: HdWallet<Self>;
+}
+
+impl HdCurve for Secp256k1 {
+    type Hd = hd_wallet::Slip10;
+}
+
+impl HdCurve for Secp256r1 {
+    type Hd = hd_wallet::Slip10;
+}
+
+impl HdCurve for Stark {
+    type Hd = hd_wallet::Stark;
+}
+
+/// Share a participant holds for a wallet, adding a chain only maps it to one of these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletKey {
+    /// Full CGGMP21 share with auxiliary info
+    Ecdsa(EcdsaCurve),
+    /// Core secp256k1 share signed with FROST under the BIP-86 Taproot tweak
+    Taproot,
+    /// Core Ed25519 share signed with FROST
+    Ed25519,
+}
+
+impl WalletKey {
+    /// Name of the share type, stored in share envelopes
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ecdsa(EcdsaCurve::Secp256k1) => "ecdsa_secp256k1",
+            Self::Ecdsa(EcdsaCurve::Secp256r1) => "ecdsa_secp256r1",
+            Self::Ecdsa(EcdsaCurve::Stark) => "ecdsa_stark",
+            Self::Taproot => "taproot",
+            Self::Ed25519 => "ed25519",
+        }
+    }
+
+    pub fn of(chain: Chain, scheme: SignatureScheme) -> Result<Self, Status> {
+        match (chain, scheme) {
+            (
+                Chain::Ethereum
+                | Chain::Optimism
+                | Chain::Arbitrum
+                | Chain::Base
+                | Chain::Polygon
+                | Chain::Bitcoin,
+                SignatureScheme::Ecdsa,
+            ) => Ok(Self::Ecdsa(EcdsaCurve::Secp256k1)),
+            (Chain::Starknet, SignatureScheme::Ecdsa) => Ok(Self::Ecdsa(EcdsaCurve::Stark)),
+            (Chain::Flow, SignatureScheme::Ecdsa) => Ok(Self::Ecdsa(EcdsaCurve::Secp256r1)),
+            (Chain::Bitcoin, SignatureScheme::Schnorr) => Ok(Self::Taproot),
+            (Chain::Solana, _) => Ok(Self::Ed25519),
+            (_, SignatureScheme::Schnorr) => Err(failure(
+                Code::InvalidArgument,
+                ErrorCode::InvalidRequest,
+                Phase::Request,
+                "Schnorr signatures are only supported on Bitcoin",
+            )),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{Result, anyhow};
+use cggmp21::security_level::SecurityLevel128;
+use cggmp21::{IncompleteKeyShare, KeyShare, PregeneratedPrimes};
+use generic_ec::curves::Ed25519;
+use generic_ec::{Curve, NonZero, Scalar, SecretScalar};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
+
+use crate::keygen::{THRESHOLD, TOTAL_PARTIES};
+
+/// Secret scalar of a big-endian private key
+pub fn secret_key<E: Curve>(private_key: &[u8]) -> Result<NonZero<SecretScalar<E>>> {
+    let scalar = SecretScalar::<E>::from_be_bytes(private_key)
+        .map_err(|_| anyhow!("Invalid private key"))?;
+
+    NonZero::from_secret_scalar(scalar).ok_or_else(|| anyhow!("Invalid private key"))
+}
+
+/// Secret scalar of an Ed25519 seed, the clamped first half of its SHA-512 hash as in RFC 8032.
+/// FROST signs with the scalar alone, the second half only derives the nonces of plain Ed25519
+pub fn ed25519_secret_key(seed: &[u8]) -> Result<NonZero<SecretScalar<Ed25519>>> {
+    if seed.len() != 32 {
+        return Err(anyhow!("Invalid private key"));
+    }
+
+    let digest = Zeroizing::new(Sha512::digest(seed));
+
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    bytes.copy_from_slice(&digest[..32]);
+    bytes[0] &= 248;
+    bytes[31] &= 127;
+    bytes[31] |= 64;
+
+    let mut scalar = Scalar::<Ed25519>::from_le_bytes_mod_order(bytes.as_slice());
+
+    NonZero::from_secret_scalar(SecretScalar::new(&mut scalar))
+        .ok_or_else(|| anyhow!("Invalid private key"))
+}
+
+/// CGGMP21 shares of the key with the aux info of every party, one pair of primes each
+pub fn ecdsa_shares<E: Curve>(
+    secret_key: NonZero<SecretScalar<E>>,
+    primes: Vec<PregeneratedPrimes<SecurityLevel128>>,
+) -> Result<Vec<KeyShare<E, SecurityLevel128>>> {
+    let primes = primes.into_iter().map(PregeneratedPrimes::split).collect();
+
+    Ok(
+        cggmp21::trusted_dealer::builder::<E, SecurityLevel128>(TOTAL_PARTIES)
+            .set_threshold(Some(THRESHOLD))
+            .set_shared_secret_key(secret_key)
+            .set_pregenerated_primes(primes)
+            .hd_wallet(false)
+            .generate_shares(&mut rand::rngs::OsRng)?,
+    )
+}
+
+/// Core shares of the key signed with FROST
+pub fn core_shares<E: Curve>(
+    secret_key: NonZero<SecretScalar<E>>,
+) -> Result<Vec<IncompleteKeyShare<E>>> {
+    Ok(
+        cggmp21::trusted_dealer::builder::<E, SecurityLevel128>(TOTAL_PARTIES)
+            .set_threshold(Some(THRESHOLD))
+            .set_shared_secret_key(secret_key)
+            .hd_wallet(false)
+            .generate_core_shares(&mut rand::rngs::OsRng)?,
+    )
+}
@@ -0,0 +1,50 @@
+use crate::metrics::metrics;
+use prost::Message;
+use proto::mpc::{AbortDetails, ErrorCode, ErrorDetail, Phase};
+use tonic::{Code, Status};
+
+/// Errors a later request may get past, failed ceremonies are never resumed
+fn retryable(error: ErrorCode) -> bool {
+    matches!(
+        error,
+        ErrorCode::Busy | ErrorCode::Timeout | ErrorCode::StorageFailed | ErrorCode::Unavailable
+    )
+}
+
+fn detailed(code: Code, message: impl Into<String>, detail: ErrorDetail) -> Status {
+    Status::with_details(code, message, detail.encode_to_vec().into())
+}
+
+/// Status with the `ErrorDetail` of the failure, `code` stays the coarse gRPC code
+pub fn failure(code: Code, error: ErrorCode, phase: Phase, message: impl Into<String>) -> Status {
+    metrics().failure(phase, error);
+
+    detailed(
+        code,
+        message,
+        ErrorDetail {
+            code: error.into(),
+            retryable: retryable(error),
+            party: None,
+            phase: phase.into(),
+            abort: None,
+        },
+    )
+}
+
+/// `ABORTED` status naming the first culprit of an identifiable abort as the offending party
+pub fn aborted(phase: Phase, message: impl Into<String>, details: AbortDetails) -> Status {
+    metrics().failure(phase, ErrorCode::Aborted);
+
+    detailed(
+        Code::Aborted,
+        message,
+        ErrorDetail {
+            code: ErrorCode::Aborted.into(),
+            retryable: false,
+            party: details.faulty_parties.first().copied(),
+            phase: phase.into(),
+            abort: Some(details),
+        },
+    )
+}
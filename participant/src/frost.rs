@@ -0,0 +1,642 @@
+use crate::client::Room;
+use crate::metrics::RoundMetrics;
+use anyhow::{Result, anyhow};
+use cggmp21::IncompleteKeyShare;
+use cggmp21::progress::{Event, Tracer};
+use futures::{SinkExt, StreamExt};
+use generic_ec::curves::{Ed25519, Secp256k1};
+use generic_ec::{Curve, NonZero, Point, Scalar, SecretScalar};
+use rand::RngCore;
+use round_based::Outgoing;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Curve, hashes and signature encoding of a FROST variant, shares come from the cggmp21
+/// threshold keygen of the curve
+pub trait Ciphersuite {
+    type Curve: Curve;
+
+    /// Domain separation of the nonce and binding factor hashes
+    const CONTEXT: &'static [u8];
+
+    /// Key the signature verifies under, derived from the shared public key
+    fn group_key(public_key: Point<Self::Curve>) -> GroupKey<Self::Curve> {
+        GroupKey {
+            key: public_key,
+            factor: Scalar::one(),
+            tweak: Scalar::zero(),
+        }
+    }
+
+    /// Whether the signers negate their nonces, for group commitments the signature
+    /// encoding can't represent
+    fn negate_nonces(_group_commitment: &Point<Self::Curve>) -> bool {
+        false
+    }
+
+    fn challenge(
+        group_commitment: &Point<Self::Curve>,
+        key: &Point<Self::Curve>,
+        message: &[u8],
+    ) -> Scalar<Self::Curve>;
+
+    fn signature(group_commitment: &Point<Self::Curve>, z: &Scalar<Self::Curve>) -> [u8; 64];
+}
+
+/// Signing key of a ciphersuite, its secret is `factor * x + tweak` for the shared secret `x`
+pub struct GroupKey<E: Curve> {
+    pub key: Point<E>,
+    factor: Scalar<E>,
+    tweak: Scalar<E>,
+}
+
+/// FROST(Ed25519, SHA-512) from RFC 9591, signatures are plain Ed25519 signatures
+pub struct Ed25519Sha512;
+
+impl Ciphersuite for Ed25519Sha512 {
+    type Curve = Ed25519;
+
+    const CONTEXT: &'static [u8] = b"FROST-ED25519-SHA512-v1";
+
+    fn challenge(
+        group_commitment: &Point<Ed25519>,
+        key: &Point<Ed25519>,
+        message: &[u8],
+    ) -> Scalar<Ed25519> {
+        // Unprefixed SHA-512 keeps the signature a plain Ed25519 signature
+        hash(&[
+            &group_commitment.to_bytes(true),
+            &key.to_bytes(true),
+            message,
+        ])
+    }
+
+    fn signature(group_commitment: &Point<Ed25519>, z: &Scalar<Ed25519>) -> [u8; 64] {
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&group_commitment.to_bytes(true));
+        signature[32..].copy_from_slice(z.to_le_bytes().as_bytes());
+        signature
+    }
+}
+
+/// FROST over secp256k1 producing BIP-340 signatures for the BIP-86 Taproot output key,
+/// the internal key tweaked without a script tree
+pub struct Secp256k1Tr;
+
+impl Secp256k1Tr {
+    fn tagged_hash(tag: &[u8], parts: &[&[u8]]) -> Scalar<Secp256k1> {
+        let tag = Sha256::digest(tag);
+        let mut digest = Sha256::new().chain_update(tag).chain_update(tag);
+
+        for part in parts {
+            digest.update(part);
+        }
+
+        Scalar::from_be_bytes_mod_order(digest.finalize())
+    }
+
+    fn is_odd(point: &Point<Secp256k1>) -> bool {
+        point.to_bytes(true)[0] == 0x03
+    }
+
+    pub fn x_only(point: &Point<Secp256k1>) -> [u8; 32] {
+        let mut x = [0u8; 32];
+        x.copy_from_slice(&point.to_bytes(true)[1..]);
+        x
+    }
+}
+
+impl Ciphersuite for Secp256k1Tr {
+    type Curve = Secp256k1;
+
+    const CONTEXT: &'static [u8] = b"FROST-secp256k1-SHA256-TR-v1";
+
+    fn group_key(public_key: Point<Secp256k1>) -> GroupKey<Secp256k1> {
+        // BIP-340 keys are x-only, the internal and output keys are both taken with an even Y
+        let internal_sign = if Self::is_odd(&public_key) {
+            -Scalar::one()
+        } else {
+            Scalar::one()
+        };
+        let internal_key = public_key * internal_sign;
+
+        let tweak = Self::tagged_hash(b"TapTweak", &[&Self::x_only(&internal_key)]);
+        let output_key = internal_key + Point::generator() * tweak;
+
+        let output_sign = if Self::is_odd(&output_key) {
+            -Scalar::one()
+        } else {
+            Scalar::one()
+        };
+
+        GroupKey {
+            key: output_key * output_sign,
+            factor: output_sign * internal_sign,
+            tweak: output_sign * tweak,
+        }
+    }
+
+    fn negate_nonces(group_commitment: &Point<Secp256k1>) -> bool {
+        Self::is_odd(group_commitment)
+    }
+
+    fn challenge(
+        group_commitment: &Point<Secp256k1>,
+        key: &Point<Secp256k1>,
+        message: &[u8],
+    ) -> Scalar<Secp256k1> {
+        Self::tagged_hash(
+            b"BIP0340/challenge",
+            &[&Self::x_only(group_commitment), &Self::x_only(key), message],
+        )
+    }
+
+    fn signature(group_commitment: &Point<Secp256k1>, z: &Scalar<Secp256k1>) -> [u8; 64] {
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&Self::x_only(group_commitment));
+        signature[32..].copy_from_slice(z.to_be_bytes().as_bytes());
+        signature
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CommitmentsMsg {
+    hiding: Vec<u8>,
+    binding: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ShareMsg {
+    z: Vec<u8>,
+}
+
+#[derive(Debug, Error)]
+pub enum FrostError {
+    /// Signature shares that don't match the public share and commitments of their
+    /// signer, parties are signing indexes (positions among the sorted signers)
+    #[error("Invalid signature shares from parties {parties:?}")]
+    InvalidShares { parties: Vec<u16> },
+}
+
+struct Nonces<E: Curve> {
+    hiding: Scalar<E>,
+    binding: Scalar<E>,
+}
+
+struct Commitments<E: Curve> {
+    hiding: Point<E>,
+    binding: Point<E>,
+}
+
+fn hash<E: Curve>(parts: &[&[u8]]) -> Scalar<E> {
+    let mut digest = Sha512::new();
+
+    for part in parts {
+        digest.update(part);
+    }
+
+    Scalar::from_le_bytes_mod_order(digest.finalize())
+}
+
+fn point<E: Curve>(bytes: &[u8]) -> Result<Point<E>> {
+    Point::from_bytes(bytes).map_err(|_| anyhow!("Invalid point"))
+}
+
+/// Hedged nonce, fresh randomness mixed with the secret share
+fn nonce<C: Ciphersuite>(secret: &Scalar<C::Curve>) -> Scalar<C::Curve> {
+    let mut random = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut random);
+
+    hash(&[
+        C::CONTEXT,
+        b"nonce",
+        &random,
+        secret.to_le_bytes().as_bytes(),
+    ])
+}
+
+/// Interpolation coefficient of `identifier` at zero over all signer `identifiers`
+fn lagrange<E: Curve>(identifier: &Scalar<E>, identifiers: &[Scalar<E>]) -> Result<Scalar<E>> {
+    let mut numerator = Scalar::one();
+    let mut denominator = Scalar::one();
+
+    for other in identifiers.iter().filter(|other| *other != identifier) {
+        numerator *= other;
+        denominator *= other - identifier;
+    }
+
+    Ok(numerator
+        * denominator
+            .invert()
+            .ok_or_else(|| anyhow!("Duplicate signer identifiers"))?)
+}
+
+/// Public values every signer derives from the collected commitments
+struct SigningPackage<C: Ciphersuite> {
+    group_key: GroupKey<C::Curve>,
+    identifiers: BTreeMap<u16, Scalar<C::Curve>>,
+    binding_factors: BTreeMap<u16, Scalar<C::Curve>>,
+    /// `-1` when the nonces are negated, `1` otherwise
+    nonce_sign: Scalar<C::Curve>,
+    group_commitment: Point<C::Curve>,
+    challenge: Scalar<C::Curve>,
+}
+
+impl<C: Ciphersuite> SigningPackage<C> {
+    fn new(
+        share: &IncompleteKeyShare<C::Curve>,
+        commitments: &BTreeMap<u16, Commitments<C::Curve>>,
+        message: &[u8],
+    ) -> Result<Self> {
+        let group_key = C::group_key(*share.shared_public_key);
+        let public_key = group_key.key.to_bytes(true);
+
+        let mut identifiers = BTreeMap::new();
+        let mut encoded_commitments = Vec::new();
+
+        for (party, commitment) in commitments {
+            let identifier: Scalar<C::Curve> = share
+                .share_preimage(*party)
+                .map(NonZero::into_inner)
+                .ok_or_else(|| anyhow!("Unknown signer {party}"))?;
+
+            encoded_commitments.extend_from_slice(identifier.to_le_bytes().as_bytes());
+            encoded_commitments.extend_from_slice(&commitment.hiding.to_bytes(true));
+            encoded_commitments.extend_from_slice(&commitment.binding.to_bytes(true));
+
+            identifiers.insert(*party, identifier);
+        }
+
+        let message_hash = Sha512::new()
+            .chain_update(C::CONTEXT)
+            .chain_update(b"msg")
+            .chain_update(message)
+            .finalize();
+        let commitments_hash = Sha512::new()
+            .chain_update(C::CONTEXT)
+            .chain_update(b"com")
+            .chain_update(&encoded_commitments)
+            .finalize();
+
+        let mut binding_factors = BTreeMap::new();
+        let mut group_commitment = Point::zero();
+
+        for (party, commitment) in commitments {
+            let binding_factor = hash(&[
+                C::CONTEXT,
+                b"rho",
+                &public_key,
+                &message_hash,
+                &commitments_hash,
+                identifiers[party].to_le_bytes().as_bytes(),
+            ]);
+
+            group_commitment =
+                group_commitment + commitment.hiding + commitment.binding * binding_factor;
+            binding_factors.insert(*party, binding_factor);
+        }
+
+        let nonce_sign = if C::negate_nonces(&group_commitment) {
+            -Scalar::one()
+        } else {
+            Scalar::one()
+        };
+        let group_commitment = group_commitment * nonce_sign;
+
+        let challenge = C::challenge(&group_commitment, &group_key.key, message);
+
+        Ok(Self {
+            group_key,
+            identifiers,
+            binding_factors,
+            nonce_sign,
+            group_commitment,
+            challenge,
+        })
+    }
+
+    fn lagrange(&self, party: u16) -> Result<Scalar<C::Curve>> {
+        let identifiers: Vec<_> = self.identifiers.values().copied().collect();
+
+        lagrange(&self.identifiers[&party], &identifiers)
+    }
+}
+
+/// Nonces of a signer and the commitments to them it broadcasts in round 1
+fn commit<C: Ciphersuite>(secret: &Scalar<C::Curve>) -> (Nonces<C::Curve>, Commitments<C::Curve>) {
+    let nonces = Nonces {
+        hiding: nonce::<C>(secret),
+        binding: nonce::<C>(secret),
+    };
+
+    let commitments = Commitments {
+        hiding: Point::generator() * nonces.hiding,
+        binding: Point::generator() * nonces.binding,
+    };
+
+    (nonces, commitments)
+}
+
+/// Share of the signature of the signer `index`, broadcast in round 2
+fn signature_share<C: Ciphersuite>(
+    package: &SigningPackage<C>,
+    index: u16,
+    nonces: &Nonces<C::Curve>,
+    secret: &Scalar<C::Curve>,
+) -> Result<Scalar<C::Curve>> {
+    Ok(
+        package.nonce_sign * (nonces.hiding + nonces.binding * package.binding_factors[&index])
+            + package.lagrange(index)? * package.group_key.factor * secret * package.challenge,
+    )
+}
+
+/// Checks every signature share against the public share and commitments of its signer and
+/// returns the aggregated signature
+fn aggregate<C: Ciphersuite>(
+    share: &IncompleteKeyShare<C::Curve>,
+    package: &SigningPackage<C>,
+    commitments: &BTreeMap<u16, Commitments<C::Curve>>,
+    shares: &BTreeMap<u16, Scalar<C::Curve>>,
+    parties: &[u16],
+) -> Result<[u8; 64]> {
+    let mut faulty = Vec::new();
+
+    for (party, z) in shares {
+        let commitment = &commitments[party];
+        let public_share = share
+            .public_shares
+            .get(usize::from(*party))
+            .ok_or_else(|| anyhow!("Unknown signer {party}"))?;
+
+        let expected = (commitment.hiding + commitment.binding * package.binding_factors[party])
+            * package.nonce_sign
+            + **public_share
+                * (package.challenge * package.lagrange(*party)? * package.group_key.factor);
+
+        if Point::generator() * z != expected {
+            faulty.extend(parties.iter().position(|p| p == party).map(|p| p as u16));
+        }
+    }
+
+    if !faulty.is_empty() {
+        return Err(FrostError::InvalidShares { parties: faulty }.into());
+    }
+
+    // The tweak is known to every signer, it is added once to the aggregate
+    let z: Scalar<C::Curve> =
+        shares.values().sum::<Scalar<C::Curve>>() + package.challenge * package.group_key.tweak;
+
+    if Point::generator() * z
+        != package.group_commitment + package.group_key.key * package.challenge
+    {
+        return Err(anyhow!("Aggregated signature is invalid"));
+    }
+
+    Ok(C::signature(&package.group_commitment, &z))
+}
+
+/// Runs the two FROST rounds between the signing `parties`, identified by their keygen
+/// index, and returns the 64-byte signature of `message` in the ciphersuite encoding
+pub async fn sign<C: Ciphersuite>(
+    commitments_room: Room,
+    shares_room: Room,
+    share: &IncompleteKeyShare<C::Curve>,
+    parties: &[u16],
+    message: &[u8],
+) -> Result<[u8; 64]> {
+    let index = share.i;
+    let secret: &SecretScalar<C::Curve> = share.x.as_ref();
+    let secret: &Scalar<C::Curve> = secret.as_ref();
+
+    // The rounds are timed like the ones of the cggmp21 protocols
+    let mut tracer = RoundMetrics::new("frost_signing");
+
+    tracer.trace_event(Event::ProtocolBegins);
+
+    // Round 1, commit to a pair of nonces
+    tracer.trace_event(Event::RoundBegins { name: None });
+
+    let (nonces, own) = commit::<C>(secret);
+
+    let (_, mut incoming, mut outgoing) =
+        commitments_room.join_room::<CommitmentsMsg>(index).await?;
+
+    outgoing
+        .send(Outgoing::broadcast(CommitmentsMsg {
+            hiding: own.hiding.to_bytes(true).to_vec(),
+            binding: own.binding.to_bytes(true).to_vec(),
+        }))
+        .await?;
+
+    let mut commitments = BTreeMap::from([(index, own)]);
+
+    tracer.trace_event(Event::ReceiveMsgs);
+
+    while commitments.len() < parties.len() {
+        let msg = incoming
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("Commitments room closed"))??;
+
+        if parties.contains(&msg.sender) {
+            commitments.insert(
+                msg.sender,
+                Commitments {
+                    hiding: point(&msg.msg.hiding)?,
+                    binding: point(&msg.msg.binding)?,
+                },
+            );
+        }
+    }
+
+    tracer.trace_event(Event::MsgsReceived);
+
+    let package = SigningPackage::<C>::new(share, &commitments, message)?;
+
+    // Round 2, share of the signature
+    tracer.trace_event(Event::RoundBegins { name: None });
+
+    let z = signature_share(&package, index, &nonces, secret)?;
+
+    let (_, mut incoming, mut outgoing) = shares_room.join_room::<ShareMsg>(index).await?;
+
+    outgoing
+        .send(Outgoing::broadcast(ShareMsg {
+            z: z.to_le_bytes().to_vec(),
+        }))
+        .await?;
+
+    let mut shares = BTreeMap::from([(index, z)]);
+
+    tracer.trace_event(Event::ReceiveMsgs);
+
+    while shares.len() < parties.len() {
+        let msg = incoming
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("Signature shares room closed"))??;
+
+        if parties.contains(&msg.sender) {
+            // Unparseable shares are reported like invalid ones below
+            let z = Scalar::from_le_bytes(&msg.msg.z).unwrap_or_else(|_| Scalar::zero());
+
+            shares.insert(msg.sender, z);
+        }
+    }
+
+    tracer.trace_event(Event::MsgsReceived);
+
+    let signature = aggregate(share, &package, &commitments, &shares, parties)?;
+
+    tracer.trace_event(Event::ProtocolEnds);
+
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dealer;
+    use alloy::primitives::hex;
+
+    /// Runs both rounds in process between the shares of `signers`, `tamper` edits the
+    /// signature shares before they are aggregated
+    fn sign_in_process<C: Ciphersuite>(
+        shares: &[IncompleteKeyShare<C::Curve>],
+        signers: &[u16],
+        message: &[u8],
+        tamper: impl FnOnce(&mut BTreeMap<u16, Scalar<C::Curve>>),
+    ) -> Result<[u8; 64]> {
+        let secret = |index: u16| -> Scalar<C::Curve> {
+            let secret: &SecretScalar<C::Curve> = shares[usize::from(index)].x.as_ref();
+            *secret.as_ref()
+        };
+
+        let mut nonces = BTreeMap::new();
+        let mut commitments = BTreeMap::new();
+
+        for index in signers {
+            let (pair, commitment) = commit::<C>(&secret(*index));
+
+            nonces.insert(*index, pair);
+            commitments.insert(*index, commitment);
+        }
+
+        let package = SigningPackage::<C>::new(&shares[0], &commitments, message)?;
+
+        let mut signature_shares = BTreeMap::new();
+
+        for index in signers {
+            let z = signature_share(&package, *index, &nonces[index], &secret(*index))?;
+
+            signature_shares.insert(*index, z);
+        }
+
+        tamper(&mut signature_shares);
+
+        aggregate(
+            &shares[0],
+            &package,
+            &commitments,
+            &signature_shares,
+            signers,
+        )
+    }
+
+    #[test]
+    fn test_ed25519_signature_verifies_with_ed25519_dalek() {
+        let seed = [7u8; 32];
+        let shares = dealer::core_shares(dealer::ed25519_secret_key(&seed).unwrap()).unwrap();
+
+        // The shared key is the one plain Ed25519 derives from the seed
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&seed);
+        let verifying_key = signing_key.verifying_key();
+        assert_eq!(
+            shares[0].shared_public_key.to_bytes(true).as_bytes(),
+            verifying_key.as_bytes()
+        );
+
+        let message = b"FROST Ed25519 known answer";
+
+        for signers in [[0, 1], [0, 2], [1, 2]] {
+            let signature =
+                sign_in_process::<Ed25519Sha512>(&shares, &signers, message, |_| {}).unwrap();
+
+            verifying_key
+                .verify_strict(message, &ed25519_dalek::Signature::from_bytes(&signature))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_invalid_share_names_its_signing_index() {
+        let shares = dealer::core_shares(dealer::ed25519_secret_key(&[7u8; 32]).unwrap()).unwrap();
+
+        // Keygen index 2 is the second of the signers 0 and 2
+        let err = sign_in_process::<Ed25519Sha512>(&shares, &[0, 2], b"message", |shares| {
+            *shares.get_mut(&2).unwrap() += Scalar::one();
+        })
+        .unwrap_err();
+
+        match err.downcast_ref::<FrostError>() {
+            Some(FrostError::InvalidShares { parties }) => assert_eq!(parties, &[1]),
+            other => panic!("Unexpected error {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_bip86_output_key() {
+        // First receiving key of the BIP-86 test vectors
+        let internal_key =
+            hex::decode("02cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115")
+                .unwrap();
+
+        let group_key = Secp256k1Tr::group_key(point(&internal_key).unwrap());
+
+        assert_eq!(
+            hex::encode(Secp256k1Tr::x_only(&group_key.key)),
+            "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+    }
+
+    #[test]
+    fn test_taproot_signature_verifies_with_k256_schnorr() {
+        let message = b"FROST Taproot known answer";
+        let mut parities = std::collections::BTreeSet::new();
+
+        // Random keys until every parity of the internal and output keys has signed
+        while parities.len() < 4 {
+            let secret = NonZero::<SecretScalar<Secp256k1>>::random(&mut rand::rngs::OsRng);
+            let shares = dealer::core_shares(secret).unwrap();
+
+            let public_key = *shares[0].shared_public_key;
+            let internal_key = if Secp256k1Tr::is_odd(&public_key) {
+                -public_key
+            } else {
+                public_key
+            };
+            let output_key = internal_key
+                + Point::generator()
+                    * Secp256k1Tr::tagged_hash(b"TapTweak", &[&Secp256k1Tr::x_only(&internal_key)]);
+
+            parities.insert((
+                Secp256k1Tr::is_odd(&public_key),
+                Secp256k1Tr::is_odd(&output_key),
+            ));
+
+            let verifying_key =
+                k256::schnorr::VerifyingKey::from_bytes(&Secp256k1Tr::x_only(&output_key)).unwrap();
+            let signature =
+                sign_in_process::<Secp256k1Tr>(&shares, &[0, 2], message, |_| {}).unwrap();
+
+            verifying_key
+                .verify_raw(
+                    message,
+                    &k256::schnorr::Signature::try_from(signature.as_slice()).unwrap(),
+                )
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use proto::mpc::participant_server::ParticipantServer;
+use tonic::server::NamedService;
+use tonic_health::ServingStatus;
+use tonic_health::server::HealthReporter;
+use vaultrs::client::VaultClient;
+
+use crate::ParticipantHandler;
+use crate::client::Client;
+
+/// Reports the participant as serving only while Vault and the SSE relay are reachable
+pub struct HealthMonitor {
+    reporter: HealthReporter,
+    vault: VaultClient,
+    client: Client,
+    interval: Duration,
+}
+
+impl HealthMonitor {
+    pub fn new(
+        reporter: HealthReporter,
+        vault: VaultClient,
+        client: Client,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            reporter,
+            vault,
+            client,
+            interval,
+        }
+    }
+
+    pub async fn run(self) {
+        let mut serving = None;
+
+        loop {
+            let (vault, relay) =
+                futures::future::join(vaultrs::sys::health(&self.vault), self.client.ping()).await;
+
+            if let Err(err) = &vault {
+                warn!("Vault health check failed: {err}");
+            }
+
+            if let Err(err) = &relay {
+                warn!("SSE relay health check failed: {err}");
+            }
+
+            let healthy = vault.is_ok() && relay.is_ok();
+
+            if serving != Some(healthy) {
+                let status = if healthy {
+                    ServingStatus::Serving
+                } else {
+                    ServingStatus::NotServing
+                };
+
+                info!("Participant health changed to {:?}", status);
+
+                self.reporter.set_service_status("", status).await;
+                self.reporter
+                    .set_service_status(ParticipantServer::<ParticipantHandler>::NAME, status)
+                    .await;
+
+                serving = Some(healthy);
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
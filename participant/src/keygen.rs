@@ -1,44 +1,106 @@
-use crate::client::{Client, Room};
+use crate::aux_info::{AuxInfoCache, CachedAuxInfo};
+use crate::backup::{self, EncryptedBackup, ShareSecret};
+use crate::client::{Ceremony, Client, Room};
+use crate::progress::Progress;
 use generic_ec::Curve;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use cggmp21::ExecutionId;
+use cggmp21::IncompleteKeyShare;
 use cggmp21::KeyShare;
 use cggmp21::key_refresh::AuxOnlyMsg;
 use cggmp21::key_share::{DirtyAuxInfo, DirtyIncompleteKeyShare, Valid};
 use cggmp21::keygen::ThresholdMsg;
+use cggmp21::round_based::Outgoing;
 use cggmp21::security_level::SecurityLevel128;
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use log::info;
+use proto::mpc::KeygenStage;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::sync::Arc;
 
-static TOTAL_PARTIES: u16 = 3;
-static THRESHOLD: u16 = 2;
+pub static TOTAL_PARTIES: u16 = 3;
+pub static THRESHOLD: u16 = 2;
 
-#[derive(Deserialize, Serialize)]
-pub struct ShareSecret {
-    index: u16,
-    share: String,
+/// Cached aux info a party offers to reuse, `None` when it holds none
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct AuxOffer {
+    fingerprint: Option<String>,
+    index: Option<u16>,
+}
+
+/// Domain of the associated data of dealt shares, followed by the execution id
+static DEAL_DOMAIN: &[u8] = b"mpc-waas-import-deal-v1";
+
+/// Message of the deal room of an import, the relay only ever carries encrypted shares
+#[derive(Serialize, Deserialize)]
+enum DealMsg {
+    /// SEC1 public key of the import key of a receiving party
+    Key(Vec<u8>),
+    /// Share of the receiver encrypted to its import key
+    Share {
+        ephemeral_key: Vec<u8>,
+        nonce: Vec<u8>,
+        ciphertext: Vec<u8>,
+    },
+}
+
+/// Keys the shares of an import are dealt with
+pub struct DealKeys<'a> {
+    /// Big-endian secp256k1 key the share dealt to the party is encrypted to
+    pub import_key: Option<&'a [u8]>,
+    /// SEC1 keys the dealer accepts to encrypt the shares of the other parties to
+    pub peer_keys: &'a [Vec<u8>],
 }
 
 pub struct Keygen {
+    index_room: Room,
+    offer_room: Room,
     aux_room: Room,
     keygen_room: Room,
+    deal_room: Room,
+    progress: Progress,
+    aux: Arc<AuxInfoCache>,
+    parties: u16,
 }
 
 impl Keygen {
-    pub fn new(client: &Client, id: i32) -> Self {
+    pub fn new(
+        client: &Client,
+        ceremony: &Ceremony,
+        id: i32,
+        progress: Progress,
+        aux: Arc<AuxInfoCache>,
+    ) -> Self {
         Self {
-            aux_room: client.room(format!("aux_{id}").as_str()),
-            keygen_room: client.room(format!("keygen_{id}").as_str()),
+            index_room: client.room(ceremony, format!("index_{id}").as_str()),
+            offer_room: client.room(ceremony, format!("aux_offer_{id}").as_str()),
+            aux_room: client.room(ceremony, format!("aux_{id}").as_str()),
+            keygen_room: client.room(ceremony, format!("keygen_{id}").as_str()),
+            deal_room: client.room(ceremony, format!("deal_{id}").as_str()),
+            progress,
+            aux,
+            parties: TOTAL_PARTIES,
         }
     }
 
+    /// Shares the key between `parties` instead of the three participants, e.g. a single
+    /// participant and the device of the user co-signing the wallet
+    pub fn with_parties(mut self, parties: u16) -> Self {
+        self.parties = parties;
+        self
+    }
+
+    /// Threshold keygen, `hd_wallet` adds a chain code shared by the parties to the key
     async fn compute_keygen<T: Curve>(
         &self,
         index: u16,
         eid: ExecutionId<'_>,
+        hd_wallet: bool,
     ) -> Result<Valid<DirtyIncompleteKeyShare<T>>> {
         let (_, incoming, outgoing) = self
             .keygen_room
@@ -50,13 +112,15 @@ impl Keygen {
 
         info!(
             "Starting keygen phase with index: {}, total parties: {}, threshold: {}",
-            index, TOTAL_PARTIES, THRESHOLD
+            index, self.parties, THRESHOLD
         );
 
-        // TODO: Use HD Wallets
-        let key_share = cggmp21::keygen::<T>(eid, index, TOTAL_PARTIES)
+        let mut tracer = self.progress.tracer(KeygenStage::ThresholdKeygen, "keygen");
+
+        let key_share = cggmp21::keygen::<T>(eid, index, self.parties)
             .set_threshold(THRESHOLD)
-            .hd_wallet(false)
+            .hd_wallet(hd_wallet)
+            .set_progress_tracer(&mut tracer)
             .start(&mut rand::rngs::OsRng, party)
             .await?;
 
@@ -76,26 +140,270 @@ impl Keygen {
 
         info!("Starting Aux info phase with index: {}", index);
 
-        let pregenerated_primes = cggmp21::PregeneratedPrimes::generate(&mut rand::rngs::OsRng);
+        self.progress.stage(KeygenStage::GeneratingPrimes);
+
+        let pregenerated_primes = self.aux.primes().await;
 
         let party = cggmp21::round_based::MpcParty::connected((incoming, outgoing));
 
-        let aux_info = cggmp21::aux_info_gen(eid, index, TOTAL_PARTIES, pregenerated_primes)
+        let mut tracer = self.progress.tracer(KeygenStage::AuxInfo, "aux_info");
+
+        let aux_info = cggmp21::aux_info_gen(eid, index, self.parties, pregenerated_primes)
+            .set_progress_tracer(&mut tracer)
             .start(&mut rand::rngs::OsRng, party)
             .await?;
 
         Ok(aux_info)
     }
 
+    /// Exchanges the offers of the parties under their issued index, the cached aux info
+    /// is reused only when every party offers the same one
+    async fn agreed_aux_info(
+        &self,
+        index: u16,
+        cached: Option<CachedAuxInfo>,
+    ) -> Result<Option<CachedAuxInfo>> {
+        let offer = AuxOffer {
+            fingerprint: cached.as_ref().map(CachedAuxInfo::fingerprint),
+            index: cached.as_ref().map(|cached| cached.index),
+        };
+
+        let (_, incoming, mut outgoing) =
+            self.offer_room.clone().join_room::<AuxOffer>(index).await?;
+
+        outgoing.send(Outgoing::broadcast(offer.clone())).await?;
+
+        let offers = incoming
+            .take((self.parties - 1).into())
+            .map_ok(|incoming| incoming.msg)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut indexes = offers
+            .iter()
+            .chain([&offer])
+            .filter_map(|offer| offer.index)
+            .collect::<Vec<_>>();
+
+        indexes.sort_unstable();
+        indexes.dedup();
+
+        let agreed = offer.fingerprint.is_some()
+            && offers
+                .iter()
+                .all(|other| other.fingerprint == offer.fingerprint)
+            && indexes.len() == usize::from(self.parties)
+            && indexes.iter().all(|index| *index < self.parties);
+
+        Ok(cached.filter(|_| agreed))
+    }
+
+    async fn issue_index(&self) -> Result<u16> {
+        self.progress.stage(KeygenStage::Joining);
+
+        let index = self.index_room.issue_index().await?;
+
+        if index >= self.parties {
+            return Err(anyhow!(
+                "Issued index {index} exceeds the {} keygen parties",
+                self.parties
+            ));
+        }
+
+        Ok(index)
+    }
+
+    /// Runs only the threshold keygen, enough for FROST which needs no auxiliary info.
+    /// FROST keys are used as generated or under the Taproot tweak, without a chain code
+    pub async fn compute_core_share<T: Curve>(
+        self,
+        execution_id: &[u8],
+    ) -> Result<IncompleteKeyShare<T>> {
+        let eid = ExecutionId::new(execution_id);
+
+        let index = self.issue_index().await?;
+
+        self.compute_keygen::<T>(index, eid, false)
+            .await
+            .map_err(|err| {
+                log::error!("Keygen phase failed: {err}");
+                if let Some(source) = err.source() {
+                    log::error!("Caused by: {}", source);
+                }
+                err
+            })
+    }
+
+    /// Imports a key under an index issued by the relay for this execution. The dealer
+    /// is the party given `deal`, which splits the key into a share per index, keeps its own
+    /// and sends the others theirs, each encrypted to the import key the receiver announced
+    /// if it is one of the pinned peer keys. Returns the index with the share of the party
+    pub async fn import_share<S>(
+        self,
+        execution_id: &[u8],
+        keys: DealKeys<'_>,
+        deal: Option<impl FnOnce() -> Result<Vec<S>>>,
+    ) -> Result<(u16, S)>
+    where
+        S: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let index = self.issue_index().await?;
+
+        let (_, mut incoming, mut outgoing) =
+            self.deal_room.clone().join_room::<DealMsg>(index).await?;
+
+        let aad = [DEAL_DOMAIN, execution_id].concat();
+
+        let Some(deal) = deal else {
+            let import_key = keys
+                .import_key
+                .ok_or_else(|| anyhow!("No import key to receive the dealt share with"))?;
+
+            let public_key = backup::public_key(import_key)?;
+
+            outgoing
+                .send(Outgoing::broadcast(DealMsg::Key(public_key)))
+                .await?;
+
+            info!("Waiting for the dealt share of index {}", index);
+
+            loop {
+                let dealt = incoming
+                    .next()
+                    .await
+                    .ok_or_else(|| anyhow!("Relay closed before the share was dealt"))??;
+
+                let p2p = dealt.is_p2p();
+
+                let encrypted = match dealt.msg {
+                    // The other receivers announce their keys in the same room
+                    DealMsg::Key(_) => continue,
+                    DealMsg::Share { .. } if !p2p => {
+                        bail!("Share of party {} was broadcast", dealt.sender)
+                    }
+                    DealMsg::Share {
+                        ephemeral_key,
+                        nonce,
+                        ciphertext,
+                    } => EncryptedBackup {
+                        ephemeral_key,
+                        nonce,
+                        ciphertext,
+                    },
+                };
+
+                let secret = backup::decrypt(&encrypted, import_key, &aad)?;
+
+                if secret.index != index {
+                    bail!("Share of index {} was dealt to index {index}", secret.index);
+                }
+
+                return Ok((index, serde_json::from_str(&secret.share)?));
+            }
+        };
+
+        info!("Dealing the imported key from index {}", index);
+
+        let shares = deal()?;
+
+        let mut receiver_keys = BTreeMap::new();
+
+        while receiver_keys.len() + 1 < shares.len() {
+            let msg = incoming.next().await.ok_or_else(|| {
+                anyhow!("Relay closed before the receivers announced their keys")
+            })??;
+
+            let DealMsg::Key(key) = msg.msg else {
+                continue;
+            };
+
+            if msg.sender == index || usize::from(msg.sender) >= shares.len() {
+                continue;
+            }
+
+            if !backup::is_pinned(keys.peer_keys, &key) {
+                bail!(
+                    "Import key of party {} is not a pinned peer key",
+                    msg.sender
+                );
+            }
+
+            receiver_keys.insert(msg.sender, key);
+        }
+
+        let mut own = None;
+
+        for (receiver, share) in (0..).zip(shares) {
+            if receiver == index {
+                own = Some(share);
+                continue;
+            }
+
+            let key = receiver_keys
+                .get(&receiver)
+                .ok_or_else(|| anyhow!("No import key of party {receiver}"))?;
+
+            let secret = ShareSecret {
+                index: receiver,
+                share: serde_json::to_string(&share)?,
+            };
+            let encrypted = backup::encrypt(&secret, key, &aad)?;
+
+            outgoing
+                .send(Outgoing::p2p(
+                    receiver,
+                    DealMsg::Share {
+                        ephemeral_key: encrypted.ephemeral_key,
+                        nonce: encrypted.nonce,
+                        ciphertext: encrypted.ciphertext,
+                    },
+                ))
+                .await?;
+        }
+
+        let share = own.ok_or_else(|| anyhow!("No share was dealt for index {index}"))?;
+
+        Ok((index, share))
+    }
+
+    /// Runs keygen under an index issued by the relay for this execution, the index
+    /// is kept in the resulting share and identifies the party when signing. The key is an
+    /// HD key, signing without a derivation path signs with the key itself
     pub async fn compute_share<T: Curve>(
         self,
-        index: u16,
         execution_id: &[u8],
     ) -> Result<KeyShare<T, SecurityLevel128>> {
         let eid = ExecutionId::new(execution_id);
 
+        let index = self.issue_index().await?;
+
+        // The cached aux info is the one of the three participants, the parties of other
+        // keygens generate their own
+        let cached = match self.parties == TOTAL_PARTIES {
+            true => self.aux.load().await,
+            false => None,
+        };
+
+        if let Some(cached) = self.agreed_aux_info(index, cached).await? {
+            info!("Reusing the aux info of keygen index {}", cached.index);
+
+            // The parties are ordered as in the aux info
+            let keygen = self
+                .compute_keygen::<T>(cached.index, eid, true)
+                .await
+                .map_err(|err| {
+                    log::error!("Keygen phase failed: {err}");
+                    if let Some(source) = err.source() {
+                        log::error!("Caused by: {}", source);
+                    }
+                    err
+                })?;
+
+            return combine(keygen, cached.aux_info);
+        }
+
         let (keygen_result, aux_result) = futures::future::join(
-            self.compute_keygen::<T>(index, eid),
+            self.compute_keygen::<T>(index, eid, true),
             self.compute_aux_info(index, eid),
         )
         .await;
@@ -116,14 +424,25 @@ impl Keygen {
             err
         })?;
 
-        let share = KeyShare::from_parts((keygen, aux_info)).map_err(|err| {
-            log::error!("Key share phase failed: {err}");
-            if let Some(source) = err.source() {
-                log::error!("Caused by: {}", source);
-            }
-            err
-        })?;
+        if self.parties == TOTAL_PARTIES {
+            self.aux.save(index, &aux_info).await;
+        }
 
-        Ok(share)
+        combine(keygen, aux_info)
     }
 }
+
+fn combine<T: Curve>(
+    keygen: Valid<DirtyIncompleteKeyShare<T>>,
+    aux_info: Valid<DirtyAuxInfo>,
+) -> Result<KeyShare<T, SecurityLevel128>> {
+    let share = KeyShare::from_parts((keygen, aux_info)).map_err(|err| {
+        log::error!("Key share phase failed: {err}");
+        if let Some(source) = err.source() {
+            log::error!("Caused by: {}", source);
+        }
+        err
+    })?;
+
+    Ok(share)
+}
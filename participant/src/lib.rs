@@ -0,0 +1,1375 @@
+mod abort;
+pub mod aux_info;
+pub mod backup;
+pub mod client;
+pub mod config;
+mod curves;
+mod dealer;
+mod failure;
+mod frost;
+pub mod health;
+mod keygen;
+mod limiter;
+pub mod log_context;
+pub mod metrics;
+pub mod policy;
+pub mod primes;
+mod progress;
+mod replay;
+pub mod sealing;
+pub mod share;
+mod signing;
+pub mod store;
+
+use futures::FutureExt;
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+use log::info;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cggmp21::hd_wallet::ExtendedPublicKey;
+use cggmp21::key_share::DirtyIncompleteKeyShare;
+use cggmp21::security_level::SecurityLevel128;
+use cggmp21::supported_curves::{Secp256k1, Secp256r1, Stark};
+use cggmp21::{IncompleteKeyShare, KeyShare};
+use frost::{Ciphersuite, Ed25519Sha512, Secp256k1Tr};
+use generic_ec::coords::HasAffineX;
+use generic_ec::curves::Ed25519;
+use generic_ec::{Curve, NonZero, Point, SecretScalar};
+use proto::mpc::participant_server::Participant;
+use proto::mpc::{
+    AttestMessage, AttestationResponse, Chain, CreateWalletMessage, DeleteWalletMessage, Empty,
+    ErrorCode, ExportShareBackupMessage, HasShareMessage, HasShareResponse,
+    ImportShareBackupMessage, ImportWalletMessage, KeygenEvent, KeygenStage, Phase,
+    ShareBackupMessage, SignBatchMessage, SignMessage, SignatureBatchMessage, SignatureMessage,
+    SignatureScheme, WalletCreatedMessage, WalletInfoMessage, WalletInfoResponse, keygen_event,
+};
+use tokio::time::timeout;
+use tonic::{Code, Request, Response, Status};
+use vaultrs::error::ClientError;
+use zeroize::Zeroizing;
+
+use aux_info::AuxInfoCache;
+use backup::{EncryptedBackup, ShareSecret};
+use client::{Ceremony, Client};
+use config::{BackupConfig, LimitsConfig, TimeoutConfig};
+use curves::{EcdsaCurve, HdCurve, WalletKey};
+use failure::failure;
+use keygen::{DealKeys, Keygen, THRESHOLD, TOTAL_PARTIES};
+use limiter::OperationLimiter;
+use policy::SigningPolicy;
+use progress::Progress;
+use sealing::MIN_ATTESTATION_NONCE;
+use share::{ShareEnvelope, ShareMac};
+use signing::Signing;
+use store::Store;
+
+// Cloned into the keygens, which outlive the call returning their event stream
+#[derive(Clone)]
+pub struct ParticipantHandler {
+    client: Client,
+    store: Arc<Store>,
+    aux: Arc<AuxInfoCache>,
+    index: u16,
+    keygens: Arc<OperationLimiter>,
+    signings: Arc<OperationLimiter>,
+    keygen_timeout: Duration,
+    signing_timeout: Duration,
+    policy: Arc<SigningPolicy>,
+    share_mac: Option<Arc<ShareMac>>,
+    backup: Arc<BackupConfig>,
+}
+
+impl ParticipantHandler {
+    pub fn new(
+        client: Client,
+        store: Arc<Store>,
+        aux: Arc<AuxInfoCache>,
+        index: u16,
+        limits: &LimitsConfig,
+        timeouts: &TimeoutConfig,
+        policy: SigningPolicy,
+    ) -> Self {
+        Self {
+            client,
+            store,
+            aux,
+            index,
+            keygens: Arc::new(OperationLimiter::new(
+                "keygen",
+                Phase::Keygen,
+                limits.max_keygens,
+                limits.max_queued,
+            )),
+            signings: Arc::new(OperationLimiter::new(
+                "signing",
+                Phase::Signing,
+                limits.max_signings,
+                limits.max_queued,
+            )),
+            keygen_timeout: Duration::from_secs(timeouts.keygen),
+            signing_timeout: Duration::from_secs(timeouts.signing),
+            policy: Arc::new(policy),
+            share_mac: None,
+            backup: Arc::new(BackupConfig::default()),
+        }
+    }
+
+    /// Same handler, storing the shares with a MAC and checking it when they are read
+    pub fn with_share_mac(mut self, share_mac: ShareMac) -> Self {
+        self.share_mac = Some(Arc::new(share_mac));
+        self
+    }
+
+    /// Same handler, exporting shares to the pinned keys once the pinned approvers signed.
+    /// Exports are refused without it
+    pub fn with_backup(mut self, backup: BackupConfig) -> Self {
+        self.backup = Arc::new(backup);
+        self
+    }
+}
+
+impl ParticipantHandler {
+    /// Refuses exports to keys that aren't pinned and those lacking the signatures of
+    /// enough pinned approvers, whoever asks for them. Keys of wallet owners are pinned too,
+    /// the app could hand over a key of its own as the owner's
+    fn authorize_export(&self, req: &ExportShareBackupMessage) -> Result<(), Status> {
+        if !backup::is_pinned(&self.backup.public_keys, &req.public_key) {
+            log::warn!(
+                "Refused share backup export to an unpinned key - wallet_id: {}",
+                req.wallet_id
+            );
+
+            return Err(backup_rejected(
+                Code::PermissionDenied,
+                "Share backup key is not pinned",
+            ));
+        }
+
+        let message = backup::approval_message(req.export_id, req.wallet_id, &req.public_key);
+        let approvers = backup::approvers(&self.backup.approver_keys, &message, &req.approvals);
+
+        if self.backup.approver_keys.is_empty() || approvers < self.backup.approvals {
+            log::warn!(
+                "Refused share backup export with {approvers} valid approvals - wallet_id: {}",
+                req.wallet_id
+            );
+
+            return Err(backup_rejected(
+                Code::PermissionDenied,
+                "Share backup export is not approved",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl ParticipantHandler {
+    async fn with_keygen_timeout<S>(
+        &self,
+        wallet_id: i32,
+        share: impl Future<Output = anyhow::Result<S>>,
+    ) -> Result<S, Status> {
+        // Dropping the protocol on timeout also closes its relay subscriptions
+        timeout(self.keygen_timeout, share)
+            .await
+            .map_err(|_| {
+                log::error!("Keygen timed out - wallet_id: {}", wallet_id);
+                failure(
+                    Code::DeadlineExceeded,
+                    ErrorCode::Timeout,
+                    Phase::Keygen,
+                    "Keygen timed out",
+                )
+            })?
+            .map_err(|err| {
+                log::error!("Share computation failed: {err}");
+                failure(
+                    Code::Internal,
+                    ErrorCode::ProtocolFailed,
+                    Phase::Keygen,
+                    "Failed to create new wallet",
+                )
+            })
+    }
+
+    /// Runs a signing under the signing timeout, naming the faulty parties of an abort.
+    /// `subject` is the signed item in messages, e.g. `Transaction`, and `id` its log id
+    async fn with_signing_timeout<S>(
+        &self,
+        subject: &str,
+        id: String,
+        signature: impl Future<Output = anyhow::Result<S>>,
+    ) -> Result<S, Status> {
+        timeout(self.signing_timeout, signature)
+            .await
+            .map_err(|_| {
+                log::error!("Signing timed out - {}", id);
+                failure(
+                    Code::DeadlineExceeded,
+                    ErrorCode::Timeout,
+                    Phase::Signing,
+                    format!("{subject} signing timed out"),
+                )
+            })?
+            .map_err(|err| match abort::identify(&err) {
+                Some(details) => {
+                    log::error!(
+                        "Signing aborted by parties {:?} in {} - {}",
+                        details.faulty_parties,
+                        details.round,
+                        id
+                    );
+                    failure::aborted(
+                        Phase::Signing,
+                        format!("{subject} signing aborted by a faulty party"),
+                        details,
+                    )
+                }
+                None => failure(
+                    Code::Internal,
+                    ErrorCode::ProtocolFailed,
+                    Phase::Signing,
+                    format!("{subject} signing failed"),
+                ),
+            })
+    }
+
+    /// Runs a full CGGMP21 keygen on the curve, returning the share and its key info with the
+    /// SEC1 uncompressed public key
+    async fn ecdsa_keygen<E>(
+        &self,
+        wallet_id: i32,
+        keygen: Keygen,
+        execution_id: &[u8],
+    ) -> Result<(serde_json::Result<serde_json::Value>, WalletInfoResponse), Status>
+    where
+        E: Curve,
+    {
+        let share = self
+            .with_keygen_timeout(wallet_id, keygen.compute_share::<E>(execution_id))
+            .await?;
+
+        let public_key = share.shared_public_key.into_inner().to_bytes(false);
+
+        Ok((
+            serde_json::to_value(&share),
+            wallet_info(&share.core, public_key.to_vec()),
+        ))
+    }
+
+    /// Reads the CGGMP21 share of the wallet on the curve and starts signing the request
+    /// data with it
+    async fn ecdsa_signature<'a, E>(
+        &self,
+        signing: Signing,
+        req: &'a SignMessage,
+        chain: Chain,
+        key: WalletKey,
+    ) -> Result<BoxFuture<'a, anyhow::Result<(Vec<u8>, Vec<u8>, u32)>>, Status>
+    where
+        E: HdCurve,
+        Point<E>: HasAffineX<E>,
+    {
+        let key = self
+            .read_share::<KeyShare<E, SecurityLevel128>>(&req.wallet_id.to_string(), key)
+            .await?;
+
+        // Rejected before joining the ceremony rather than failing it
+        if !req.derivation_path.is_empty() {
+            child_key(&key, &req.derivation_path)?;
+        }
+
+        Ok(signing
+            .derived(req.derivation_path.clone())
+            .sign_tx(
+                &req.execution_id,
+                &req.data,
+                key,
+                chain,
+                req.chain_id,
+                req.prehashed,
+            )
+            .boxed())
+    }
+
+    /// Reads the CGGMP21 share of the wallet on the curve and starts signing every item of
+    /// the batch with it
+    async fn ecdsa_batch_signature<'a, E>(
+        &self,
+        signing: Signing,
+        req: &'a SignBatchMessage,
+        chain: Chain,
+        key: WalletKey,
+    ) -> Result<BoxFuture<'a, anyhow::Result<Vec<(Vec<u8>, Vec<u8>, u32)>>>, Status>
+    where
+        E: Curve,
+        Point<E>: HasAffineX<E>,
+    {
+        let key = self
+            .read_share::<KeyShare<E, SecurityLevel128>>(&req.wallet_id.to_string(), key)
+            .await?;
+
+        Ok(signing
+            .sign_batch(
+                &req.execution_id,
+                &req.data,
+                key,
+                chain,
+                req.chain_id,
+                req.prehashed,
+            )
+            .boxed())
+    }
+
+    /// Key info of the stored CGGMP21 share of the wallet on the curve, of its child key at
+    /// `derivation_path` unless empty
+    async fn ecdsa_info<E: HdCurve>(
+        &self,
+        wallet_id: &str,
+        key: WalletKey,
+        derivation_path: &[u32],
+    ) -> Result<WalletInfoResponse, Status> {
+        let share = self
+            .read_share::<KeyShare<E, SecurityLevel128>>(wallet_id, key)
+            .await?;
+
+        if derivation_path.is_empty() {
+            let public_key = share.shared_public_key.into_inner().to_bytes(false);
+
+            return Ok(wallet_info(&share.core, public_key.to_vec()));
+        }
+
+        let child = child_key(&share, derivation_path)?;
+
+        Ok(WalletInfoResponse {
+            public_key: child.public_key.to_bytes(false).to_vec(),
+            chain_code: child.chain_code.to_vec(),
+            ..wallet_info(&share.core, Vec::new())
+        })
+    }
+
+    /// Runs the keygen of a `NewWallet` request and stores the resulting share
+    async fn keygen(
+        &self,
+        req: CreateWalletMessage,
+        chain: Chain,
+        key: WalletKey,
+        parties: u16,
+        progress: Progress,
+    ) -> Result<WalletCreatedMessage, Status> {
+        let wallet_id = req.wallet_id;
+        let execution_id = req.execution_id;
+
+        progress.stage(KeygenStage::Queued);
+
+        let _permit = self.keygens.acquire().await?;
+
+        // Claimed once the request holds a permit, a busy participant can still be retried
+        replay::claim_execution(&self.store, &execution_id, "keygen").await?;
+
+        let ceremony = Ceremony {
+            namespace: &req.namespace,
+            execution_id: &execution_id,
+            room_token: &req.room_token,
+        };
+
+        let keygen = Keygen::new(
+            &self.client,
+            &ceremony,
+            wallet_id,
+            progress.clone(),
+            self.aux.clone(),
+        )
+        .with_parties(parties);
+
+        // Stored as JSON, the chain and scheme decide which share type is read back
+        let (share, info) = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                self.ecdsa_keygen::<Secp256k1>(wallet_id, keygen, &execution_id)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                self.ecdsa_keygen::<Secp256r1>(wallet_id, keygen, &execution_id)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                self.ecdsa_keygen::<Stark>(wallet_id, keygen, &execution_id)
+                    .await?
+            }
+            WalletKey::Taproot => {
+                let share = self
+                    .with_keygen_timeout(
+                        wallet_id,
+                        keygen.compute_core_share::<Secp256k1>(&execution_id),
+                    )
+                    .await?;
+
+                let output_key = Secp256k1Tr::group_key(*share.shared_public_key).key;
+
+                (
+                    serde_json::to_value(&share),
+                    wallet_info(&share, Secp256k1Tr::x_only(&output_key).to_vec()),
+                )
+            }
+            WalletKey::Ed25519 => {
+                let share = self
+                    .with_keygen_timeout(
+                        wallet_id,
+                        keygen.compute_core_share::<Ed25519>(&execution_id),
+                    )
+                    .await?;
+
+                let public_key = share.shared_public_key.into_inner().to_bytes(true);
+
+                (
+                    serde_json::to_value(&share),
+                    wallet_info(&share, public_key.to_vec()),
+                )
+            }
+        };
+
+        progress.stage(KeygenStage::Storing);
+
+        let share = share
+            .and_then(|share| {
+                serde_json::to_value(ShareEnvelope::new(chain, key, &info, &execution_id, share))
+            })
+            .map_err(|_| storage_failed("Failed to store new wallet"))?;
+        let share = self.protect(&wallet_id.to_string(), share);
+
+        // Without `overwrite`, the write fails if a share was stored during the keygen
+        let stored = if req.overwrite {
+            self.store.set(&wallet_id.to_string(), &share).await
+        } else {
+            self.store.create(&wallet_id.to_string(), &share).await
+        };
+
+        stored.map_err(|err| match err {
+            ClientError::APIError { code: 400, .. } => share_exists(),
+            _ => storage_failed("Failed to store new wallet"),
+        })?;
+
+        Ok(WalletCreatedMessage {
+            public_key: info.public_key,
+        })
+    }
+
+    /// Runs the import of an `ImportWallet` request and stores the resulting share, the
+    /// participant given the private key deals it
+    async fn import(
+        &self,
+        req: ImportWalletMessage,
+        chain: Chain,
+        key: WalletKey,
+    ) -> Result<WalletCreatedMessage, Status> {
+        let wallet_id = req.wallet_id;
+        let execution_id = req.execution_id;
+
+        // Scrubbed once dealt, the key itself is never stored
+        let private_key = Zeroizing::new(req.private_key);
+        let private_key = (!private_key.is_empty()).then_some(private_key.as_slice());
+
+        let _permit = self.keygens.acquire().await?;
+
+        replay::claim_execution(&self.store, &execution_id, "import").await?;
+
+        let ceremony = Ceremony {
+            namespace: &req.namespace,
+            execution_id: &execution_id,
+            room_token: &req.room_token,
+        };
+
+        // Imports are unary calls, there is no stream to report their progress on
+        let (events, _) = mpsc::unbounded();
+
+        let keygen = Keygen::new(
+            &self.client,
+            &ceremony,
+            wallet_id,
+            Progress::new(events),
+            self.aux.clone(),
+        );
+
+        let (share, info) = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                self.ecdsa_import::<Secp256k1>(wallet_id, keygen, &execution_id, private_key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                self.ecdsa_import::<Secp256r1>(wallet_id, keygen, &execution_id, private_key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                self.ecdsa_import::<Stark>(wallet_id, keygen, &execution_id, private_key)
+                    .await?
+            }
+            WalletKey::Taproot => {
+                let secret_key = private_key
+                    .map(dealer::secret_key::<Secp256k1>)
+                    .transpose()
+                    .map_err(|_| invalid_request("Invalid private key"))?;
+
+                let share = self
+                    .core_import(wallet_id, keygen, &execution_id, secret_key)
+                    .await?;
+
+                let output_key = Secp256k1Tr::group_key(*share.shared_public_key).key;
+
+                (
+                    serde_json::to_value(&share),
+                    wallet_info(&share, Secp256k1Tr::x_only(&output_key).to_vec()),
+                )
+            }
+            WalletKey::Ed25519 => {
+                let secret_key = private_key
+                    .map(dealer::ed25519_secret_key)
+                    .transpose()
+                    .map_err(|_| invalid_request("Invalid private key"))?;
+
+                let share = self
+                    .core_import(wallet_id, keygen, &execution_id, secret_key)
+                    .await?;
+
+                let public_key = share.shared_public_key.into_inner().to_bytes(true);
+
+                (
+                    serde_json::to_value(&share),
+                    wallet_info(&share, public_key.to_vec()),
+                )
+            }
+        };
+
+        let share = share
+            .and_then(|share| {
+                serde_json::to_value(ShareEnvelope::new(chain, key, &info, &execution_id, share))
+            })
+            .map_err(|_| storage_failed("Failed to store imported wallet"))?;
+        let share = self.protect(&wallet_id.to_string(), share);
+
+        self.store
+            .create(&wallet_id.to_string(), &share)
+            .await
+            .map_err(|err| match err {
+                ClientError::APIError { code: 400, .. } => share_exists(),
+                _ => storage_failed("Failed to store imported wallet"),
+            })?;
+
+        Ok(WalletCreatedMessage {
+            public_key: info.public_key,
+        })
+    }
+
+    /// Imports a CGGMP21 share of the key on the curve, the dealer draws the primes of the
+    /// aux info of every party from the pool
+    async fn ecdsa_import<E: Curve>(
+        &self,
+        wallet_id: i32,
+        keygen: Keygen,
+        execution_id: &[u8],
+        private_key: Option<&[u8]>,
+    ) -> Result<(serde_json::Result<serde_json::Value>, WalletInfoResponse), Status> {
+        let deal = match private_key {
+            Some(private_key) => {
+                let secret_key = dealer::secret_key::<E>(private_key)
+                    .map_err(|_| invalid_request("Invalid private key"))?;
+
+                let mut primes = Vec::new();
+
+                for _ in 0..TOTAL_PARTIES {
+                    primes.push(self.aux.primes().await);
+                }
+
+                Some(move || dealer::ecdsa_shares(secret_key, primes))
+            }
+            None => None,
+        };
+
+        let share = self
+            .dealt_share(
+                wallet_id,
+                keygen,
+                execution_id,
+                deal,
+                |share: &KeyShare<E, SecurityLevel128>| share.core.i,
+            )
+            .await?;
+
+        let public_key = share.shared_public_key.into_inner().to_bytes(false);
+
+        Ok((
+            serde_json::to_value(&share),
+            wallet_info(&share.core, public_key.to_vec()),
+        ))
+    }
+
+    /// Imports a core share of the key on the curve, signed with FROST
+    async fn core_import<E: Curve>(
+        &self,
+        wallet_id: i32,
+        keygen: Keygen,
+        execution_id: &[u8],
+        secret_key: Option<NonZero<SecretScalar<E>>>,
+    ) -> Result<IncompleteKeyShare<E>, Status> {
+        let deal = secret_key.map(|secret_key| move || dealer::core_shares(secret_key));
+
+        self.dealt_share(
+            wallet_id,
+            keygen,
+            execution_id,
+            deal,
+            |share: &IncompleteKeyShare<E>| share.i,
+        )
+        .await
+    }
+
+    /// Runs the import under the keygen timeout, checking the share is the one of the index
+    /// issued to the participant
+    async fn dealt_share<S>(
+        &self,
+        wallet_id: i32,
+        keygen: Keygen,
+        execution_id: &[u8],
+        deal: Option<impl FnOnce() -> anyhow::Result<Vec<S>>>,
+        index_of: impl Fn(&S) -> u16,
+    ) -> Result<S, Status>
+    where
+        S: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let keys = DealKeys {
+            import_key: self.backup.import_key.as_deref(),
+            peer_keys: &self.backup.peer_keys,
+        };
+
+        let (index, share) = self
+            .with_keygen_timeout(wallet_id, keygen.import_share(execution_id, keys, deal))
+            .await?;
+
+        if index_of(&share) != index {
+            log::error!("Dealt share of another index - wallet_id: {}", wallet_id);
+            return Err(failure(
+                Code::Internal,
+                ErrorCode::ProtocolFailed,
+                Phase::Keygen,
+                "Failed to import wallet",
+            ));
+        }
+
+        Ok(share)
+    }
+
+    /// Fails with `ALREADY_EXISTS` when a share of the wallet is stored
+    async fn ensure_no_share(&self, wallet_id: i32) -> Result<(), Status> {
+        match self
+            .store
+            .read::<serde_json::Value>(&wallet_id.to_string())
+            .await
+        {
+            Ok(_) => {
+                log::warn!(
+                    "Refused to replace a stored wallet share - wallet_id: {}",
+                    wallet_id
+                );
+                Err(share_exists())
+            }
+            Err(ClientError::APIError { code: 404, .. }) => Ok(()),
+            Err(_) => Err(storage_failed("Failed to read stored wallet")),
+        }
+    }
+
+    /// Value to store for the share of the wallet, with its MAC when the participant has a key
+    fn protect(&self, wallet_id: &str, stored: serde_json::Value) -> serde_json::Value {
+        match &self.share_mac {
+            Some(share_mac) => share_mac.sign(wallet_id, stored),
+            None => stored,
+        }
+    }
+
+    /// Checks the MAC of a share read from Vault, when the participant has a key
+    fn verify_integrity(&self, wallet_id: &str, stored: &serde_json::Value) -> Result<(), Status> {
+        match &self.share_mac {
+            Some(share_mac) => share_mac.verify(wallet_id, stored),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the share of the wallet, checking its integrity and that its envelope holds a
+    /// share of `key`
+    async fn read_share<S: DeserializeOwned>(
+        &self,
+        wallet_id: &str,
+        key: WalletKey,
+    ) -> Result<S, Status> {
+        let stored = self
+            .store
+            .read::<serde_json::Value>(wallet_id)
+            .await
+            .map_err(|err| match err {
+                ClientError::APIError { code: 404, .. } => failure(
+                    Code::NotFound,
+                    ErrorCode::ShareNotFound,
+                    Phase::Storage,
+                    "Wallet not found",
+                ),
+                _ => failure(
+                    Code::Internal,
+                    ErrorCode::StorageFailed,
+                    Phase::Storage,
+                    "Wallet not found",
+                ),
+            })?;
+
+        self.verify_integrity(wallet_id, &stored)?;
+
+        serde_json::from_value(share::open(stored, key)?).map_err(|_| {
+            failure(
+                Code::FailedPrecondition,
+                ErrorCode::ShareMismatch,
+                Phase::Storage,
+                "Stored share has an unexpected format",
+            )
+        })
+    }
+}
+
+// Invalid chains and schemes were always reported as `INTERNAL`, kept for older apps
+fn invalid_request(message: &str) -> Status {
+    failure(
+        Code::Internal,
+        ErrorCode::InvalidRequest,
+        Phase::Request,
+        message,
+    )
+}
+
+/// Keygen indexes of the signers the app selected
+fn signer_indexes(signers: &[u32]) -> Result<Vec<u16>, Status> {
+    if signers.is_empty() {
+        return Err(failure(
+            Code::InvalidArgument,
+            ErrorCode::InvalidRequest,
+            Phase::Request,
+            "The request names no signers",
+        ));
+    }
+
+    signers
+        .iter()
+        .map(|index| u16::try_from(*index))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| {
+            failure(
+                Code::InvalidArgument,
+                ErrorCode::InvalidRequest,
+                Phase::Request,
+                "Invalid signer index",
+            )
+        })
+}
+
+/// Parties of a keygen, the three participants when the request leaves them unset
+fn keygen_parties(parties: u32) -> Result<u16, Status> {
+    match u16::try_from(parties) {
+        Ok(0) => Ok(TOTAL_PARTIES),
+        Ok(parties) if (THRESHOLD..=TOTAL_PARTIES).contains(&parties) => Ok(parties),
+        _ => Err(failure(
+            Code::InvalidArgument,
+            ErrorCode::InvalidRequest,
+            Phase::Request,
+            format!("Keygens share keys between {THRESHOLD} and {TOTAL_PARTIES} parties"),
+        )),
+    }
+}
+
+fn share_exists() -> Status {
+    failure(
+        Code::AlreadyExists,
+        ErrorCode::ShareExists,
+        Phase::Storage,
+        "A share of the wallet is already stored",
+    )
+}
+
+fn storage_failed(message: &str) -> Status {
+    failure(
+        Code::Internal,
+        ErrorCode::StorageFailed,
+        Phase::Storage,
+        message,
+    )
+}
+
+fn backup_rejected(code: Code, message: &str) -> Status {
+    failure(code, ErrorCode::BackupRejected, Phase::Backup, message)
+}
+
+/// Key info of a share, `public_key` is encoded like its keygen returned it
+fn wallet_info<E: Curve>(
+    share: &DirtyIncompleteKeyShare<E>,
+    public_key: Vec<u8>,
+) -> WalletInfoResponse {
+    let parties = share.public_shares.len() as u32;
+
+    WalletInfoResponse {
+        public_key,
+        curve: E::CURVE_NAME.to_string(),
+        // Keys generated without VSS need every party
+        threshold: share
+            .vss_setup
+            .as_ref()
+            .map_or(parties, |setup| setup.min_signers.into()),
+        parties: (0..parties).collect(),
+        keygen_index: share.i.into(),
+        chain_code: share.chain_code.map(Vec::from).unwrap_or_default(),
+    }
+}
+
+/// Only CGGMP21 shares are generated with a chain code, FROST keys have no child keys
+fn ensure_derivable(key: WalletKey, derivation_path: &[u32]) -> Result<(), Status> {
+    match (key, derivation_path) {
+        (_, []) | (WalletKey::Ecdsa(_), _) => Ok(()),
+        _ => Err(invalid_request(
+            "Child keys are only derived from ECDSA wallets",
+        )),
+    }
+}
+
+/// Extended public key of the child of an HD share at the non-hardened `derivation_path`
+fn child_key<E: HdCurve>(
+    share: &KeyShare<E, SecurityLevel128>,
+    derivation_path: &[u32],
+) -> Result<ExtendedPublicKey<E>, Status> {
+    share
+        .derive_child_public_key::<E::Hd, _>(derivation_path.iter().copied())
+        .map_err(|err| invalid_request(&format!("Cannot derive the child key: {err}")))
+}
+
+/// Keygen index of a stored share, CGGMP21 shares keep it in their core share and the
+/// FROST ones are a core share on their own
+fn keygen_index(stored: &serde_json::Value) -> Option<u32> {
+    let share = share::inner(stored);
+    let core = share.get("core").unwrap_or(share);
+
+    core.get("i")?.as_u64()?.try_into().ok()
+}
+
+/// Round trips the share through its type, which validates it
+fn validate_share<S: Serialize + DeserializeOwned>(
+    share: serde_json::Value,
+) -> serde_json::Result<serde_json::Value> {
+    serde_json::from_value::<S>(share).and_then(|share| serde_json::to_value(&share))
+}
+
+#[tonic::async_trait]
+impl Participant for ParticipantHandler {
+    type NewWalletStream = Pin<Box<dyn Stream<Item = Result<KeygenEvent, Status>> + Send>>;
+
+    async fn new_wallet(
+        &self,
+        request: Request<CreateWalletMessage>,
+    ) -> Result<Response<Self::NewWalletStream>, Status> {
+        let req = request.into_inner();
+
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+        let key = WalletKey::of(chain, scheme)?;
+        let parties = keygen_parties(req.parties)?;
+
+        // Refused before the ceremony, a second keygen would lose the stored key
+        if !req.overwrite {
+            self.ensure_no_share(req.wallet_id).await?;
+        }
+
+        let (events, received) = mpsc::unbounded();
+        let progress = Progress::new(events.clone());
+        let handler = self.clone();
+
+        // Polled with the events, dropping the stream cancels the keygen like a dropped call
+        let keygen = async move {
+            let created = handler.keygen(req, chain, key, parties, progress).await;
+
+            let _ = events.unbounded_send(created.map(|created| KeygenEvent {
+                event: Some(keygen_event::Event::Created(created)),
+            }));
+        };
+
+        // The events end once the keygen dropped its senders, after its result
+        let stream = futures::stream::select(
+            received,
+            keygen
+                .into_stream()
+                .filter_map(|()| async { None::<Result<KeygenEvent, Status>> }),
+        );
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn delete_wallet(
+        &self,
+        request: Request<DeleteWalletMessage>,
+    ) -> Result<Response<Empty>, Status> {
+        let wallet_id = request.into_inner().wallet_id;
+
+        info!("Deleting wallet - wallet_id: {}", wallet_id);
+
+        self.store
+            .delete(&wallet_id.to_string())
+            .await
+            .map_err(|_| storage_failed("Failed to delete wallet"))?;
+
+        info!("Wallet deleted successfully - wallet_id: {}", wallet_id);
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn sign_tx(
+        &self,
+        request: Request<SignMessage>,
+    ) -> Result<Response<SignatureMessage>, Status> {
+        let req = request.into_inner();
+
+        let tx_id = req.tx_id;
+        let wallet_id = req.wallet_id.to_string();
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+        let tx = &req.data;
+
+        self.policy
+            .check(chain, req.chain_id, tx, req.prehashed, &req.payload)?;
+
+        let _permit = self.signings.acquire().await?;
+
+        replay::claim_execution(&self.store, &req.execution_id, "signing").await?;
+        replay::claim_signing(&self.store, req.wallet_id, tx_id, req.item).await?;
+
+        // Counted once claimed, a replayed request doesn't use up the limits
+        self.policy.throttle(req.wallet_id, 1).await?;
+
+        let ceremony = Ceremony {
+            namespace: &req.namespace,
+            execution_id: &req.execution_id,
+            room_token: &req.room_token,
+        };
+
+        let signers = signer_indexes(&req.signers)?;
+
+        let signign = Signing::new(&self.client, &ceremony, tx_id, signers);
+
+        let key = WalletKey::of(chain, scheme)?;
+
+        ensure_derivable(key, &req.derivation_path)?;
+
+        let signature = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                self.ecdsa_signature::<Secp256k1>(signign, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                self.ecdsa_signature::<Secp256r1>(signign, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                self.ecdsa_signature::<Stark>(signign, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Taproot => {
+                let key = self
+                    .read_share::<IncompleteKeyShare<Secp256k1>>(&wallet_id, WalletKey::Taproot)
+                    .await?;
+
+                signign.sign_frost::<Secp256k1Tr>(tx, key).boxed()
+            }
+            WalletKey::Ed25519 => {
+                let key = self
+                    .read_share::<IncompleteKeyShare<Ed25519>>(&wallet_id, WalletKey::Ed25519)
+                    .await?;
+
+                signign.sign_frost::<Ed25519Sha512>(tx, key).boxed()
+            }
+        };
+
+        let (r, s, v) = self
+            .with_signing_timeout("Transaction", format!("tx_id: {tx_id}"), signature)
+            .await?;
+
+        Ok(Response::new(SignatureMessage { r, s, v }))
+    }
+
+    async fn sign_batch(
+        &self,
+        request: Request<SignBatchMessage>,
+    ) -> Result<Response<SignatureBatchMessage>, Status> {
+        let req = request.into_inner();
+
+        let batch_id = req.batch_id;
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+
+        if req.data.is_empty() {
+            return Err(failure(
+                Code::InvalidArgument,
+                ErrorCode::InvalidRequest,
+                Phase::Request,
+                "Empty signing batch",
+            ));
+        }
+
+        for (item, data) in req.data.iter().enumerate() {
+            let payload = req.payloads.get(item).map_or(&[][..], Vec::as_slice);
+
+            self.policy
+                .check(chain, req.chain_id, data, req.prehashed, payload)?;
+        }
+
+        if req.tx_ids.len() != req.data.len() {
+            return Err(failure(
+                Code::InvalidArgument,
+                ErrorCode::InvalidRequest,
+                Phase::Request,
+                "Every item of the batch needs a transaction",
+            ));
+        }
+
+        // The whole batch holds one signing permit, its items share the session
+        let _permit = self.signings.acquire().await?;
+
+        replay::claim_execution(&self.store, &req.execution_id, "signing").await?;
+
+        for tx_id in &req.tx_ids {
+            replay::claim_signing(&self.store, req.wallet_id, *tx_id, 0).await?;
+        }
+
+        self.policy
+            .throttle(req.wallet_id, req.tx_ids.len())
+            .await?;
+
+        let ceremony = Ceremony {
+            namespace: &req.namespace,
+            execution_id: &req.execution_id,
+            room_token: &req.room_token,
+        };
+
+        let signers = signer_indexes(&req.signers)?;
+
+        let signing = Signing::batch(&self.client, &ceremony, batch_id, signers);
+
+        let key = WalletKey::of(chain, scheme)?;
+
+        let signatures = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                self.ecdsa_batch_signature::<Secp256k1>(signing, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                self.ecdsa_batch_signature::<Secp256r1>(signing, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                self.ecdsa_batch_signature::<Stark>(signing, &req, chain, key)
+                    .await?
+            }
+            WalletKey::Taproot | WalletKey::Ed25519 => {
+                return Err(failure(
+                    Code::InvalidArgument,
+                    ErrorCode::InvalidRequest,
+                    Phase::Request,
+                    "Batch signing only supports ECDSA wallets",
+                ));
+            }
+        };
+
+        let signatures = self
+            .with_signing_timeout("Batch", format!("batch_id: {batch_id}"), signatures)
+            .await?;
+
+        Ok(Response::new(SignatureBatchMessage {
+            signatures: signatures
+                .into_iter()
+                .map(|(r, s, v)| SignatureMessage { r, s, v })
+                .collect(),
+        }))
+    }
+
+    async fn export_share_backup(
+        &self,
+        request: Request<ExportShareBackupMessage>,
+    ) -> Result<Response<ShareBackupMessage>, Status> {
+        let req = request.into_inner();
+
+        let wallet_id = req.wallet_id;
+
+        info!("Exporting share backup - wallet_id: {}", wallet_id);
+
+        self.authorize_export(&req)?;
+
+        replay::claim_export(&self.store, req.export_id).await?;
+
+        let share = self
+            .store
+            .read::<serde_json::Value>(&wallet_id.to_string())
+            .await
+            .map_err(|_| {
+                failure(
+                    Code::NotFound,
+                    ErrorCode::ShareNotFound,
+                    Phase::Storage,
+                    "Wallet not found",
+                )
+            })?;
+
+        self.verify_integrity(&wallet_id.to_string(), &share)?;
+
+        let secret = ShareSecret {
+            index: self.index,
+            share: share.to_string(),
+        };
+
+        let backup =
+            backup::encrypt(&secret, &req.public_key, &wallet_id.to_be_bytes()).map_err(|err| {
+                log::error!("Share backup encryption failed: {err}");
+                backup_rejected(Code::InvalidArgument, "Failed to encrypt share backup")
+            })?;
+
+        Ok(Response::new(ShareBackupMessage {
+            index: self.index.into(),
+            ephemeral_key: backup.ephemeral_key,
+            nonce: backup.nonce,
+            ciphertext: backup.ciphertext,
+        }))
+    }
+
+    async fn import_share_backup(
+        &self,
+        request: Request<ImportShareBackupMessage>,
+    ) -> Result<Response<Empty>, Status> {
+        let req = request.into_inner();
+
+        let wallet_id = req.wallet_id;
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+        let backup = req.backup.ok_or_else(|| {
+            failure(
+                Code::InvalidArgument,
+                ErrorCode::InvalidRequest,
+                Phase::Request,
+                "Missing share backup",
+            )
+        })?;
+
+        info!("Importing share backup - wallet_id: {}", wallet_id);
+
+        let import_key = self.backup.import_key.as_deref().ok_or_else(|| {
+            backup_rejected(
+                Code::FailedPrecondition,
+                "Share backup imports are disabled, no import key is set",
+            )
+        })?;
+
+        // Refused before decrypting, an import would lose the stored share
+        if !req.overwrite {
+            self.ensure_no_share(wallet_id).await?;
+        }
+
+        let backup = EncryptedBackup {
+            ephemeral_key: backup.ephemeral_key,
+            nonce: backup.nonce,
+            ciphertext: backup.ciphertext,
+        };
+
+        let secret =
+            backup::decrypt(&backup, import_key, &wallet_id.to_be_bytes()).map_err(|err| {
+                log::error!("Share backup decryption failed: {err}");
+                backup_rejected(Code::InvalidArgument, "Failed to decrypt share backup")
+            })?;
+
+        if secret.index != self.index {
+            return Err(backup_rejected(
+                Code::FailedPrecondition,
+                "Share backup belongs to another participant",
+            ));
+        }
+
+        let key = WalletKey::of(chain, scheme)?;
+
+        let stored = serde_json::from_str::<serde_json::Value>(&secret.share)
+            .map_err(|_| backup_rejected(Code::InvalidArgument, "Invalid key share in backup"))?;
+
+        // Backups hold the stored value, the envelope is checked like on reads
+        let share = share::open(stored.clone(), key)?;
+
+        // Deserializing validates the share before it replaces anything in Vault
+        let share = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                validate_share::<KeyShare<Secp256k1, SecurityLevel128>>(share)
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                validate_share::<KeyShare<Secp256r1, SecurityLevel128>>(share)
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                validate_share::<KeyShare<Stark, SecurityLevel128>>(share)
+            }
+            WalletKey::Taproot => validate_share::<IncompleteKeyShare<Secp256k1>>(share),
+            WalletKey::Ed25519 => validate_share::<IncompleteKeyShare<Ed25519>>(share),
+        }
+        .map_err(|_| backup_rejected(Code::InvalidArgument, "Invalid key share in backup"))?;
+
+        // Signed again with the key of this participant, the backup may come from another host
+        let share = self.protect(&wallet_id.to_string(), share::reseal(stored, share));
+
+        // Without `overwrite`, the write fails if a share was stored since the check
+        let stored = if req.overwrite {
+            self.store.set(&wallet_id.to_string(), &share).await
+        } else {
+            self.store.create(&wallet_id.to_string(), &share).await
+        };
+
+        stored.map_err(|err| match err {
+            ClientError::APIError { code: 400, .. } => share_exists(),
+            _ => storage_failed("Failed to store imported wallet"),
+        })?;
+
+        info!("Share backup imported - wallet_id: {}", wallet_id);
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn has_share(
+        &self,
+        request: Request<HasShareMessage>,
+    ) -> Result<Response<HasShareResponse>, Status> {
+        let wallet_id = request.into_inner().wallet_id;
+
+        let share = match self
+            .store
+            .read::<serde_json::Value>(&wallet_id.to_string())
+            .await
+        {
+            Ok(share) => Some(share),
+            Err(ClientError::APIError { code: 404, .. }) => None,
+            Err(err) => {
+                log::error!("Failed to look up wallet share: {err}");
+                return Err(failure(
+                    Code::Unavailable,
+                    ErrorCode::StorageFailed,
+                    Phase::Storage,
+                    "Failed to look up wallet share",
+                ));
+            }
+        };
+
+        Ok(Response::new(HasShareResponse {
+            present: share.is_some(),
+            keygen_index: share.as_ref().and_then(keygen_index).unwrap_or_default(),
+        }))
+    }
+
+    async fn import_wallet(
+        &self,
+        request: Request<ImportWalletMessage>,
+    ) -> Result<Response<WalletCreatedMessage>, Status> {
+        let req = request.into_inner();
+
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+        let key = WalletKey::of(chain, scheme)?;
+
+        info!(
+            "Importing wallet - wallet_id: {}, dealer: {}",
+            req.wallet_id,
+            !req.private_key.is_empty()
+        );
+
+        self.ensure_no_share(req.wallet_id).await?;
+
+        let created = self.import(req, chain, key).await?;
+
+        Ok(Response::new(created))
+    }
+
+    async fn get_wallet_info(
+        &self,
+        request: Request<WalletInfoMessage>,
+    ) -> Result<Response<WalletInfoResponse>, Status> {
+        let req = request.into_inner();
+
+        let wallet_id = req.wallet_id.to_string();
+        let chain = Chain::try_from(req.chain).map_err(|_| invalid_request("Invalid chain"))?;
+        let scheme = SignatureScheme::try_from(req.scheme)
+            .map_err(|_| invalid_request("Invalid signature scheme"))?;
+
+        // Public keys are encoded like their keygen returned them so they can be compared
+        let key = WalletKey::of(chain, scheme)?;
+
+        ensure_derivable(key, &req.derivation_path)?;
+
+        let path = &req.derivation_path;
+
+        let info = match key {
+            WalletKey::Ecdsa(EcdsaCurve::Secp256k1) => {
+                self.ecdsa_info::<Secp256k1>(&wallet_id, key, path).await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1) => {
+                self.ecdsa_info::<Secp256r1>(&wallet_id, key, path).await?
+            }
+            WalletKey::Ecdsa(EcdsaCurve::Stark) => {
+                self.ecdsa_info::<Stark>(&wallet_id, key, path).await?
+            }
+            WalletKey::Taproot => {
+                let share = self
+                    .read_share::<IncompleteKeyShare<Secp256k1>>(&wallet_id, WalletKey::Taproot)
+                    .await?;
+
+                let output_key = Secp256k1Tr::group_key(*share.shared_public_key).key;
+
+                wallet_info(&share, Secp256k1Tr::x_only(&output_key).to_vec())
+            }
+            WalletKey::Ed25519 => {
+                let share = self
+                    .read_share::<IncompleteKeyShare<Ed25519>>(&wallet_id, WalletKey::Ed25519)
+                    .await?;
+
+                let public_key = share.shared_public_key.into_inner().to_bytes(true);
+
+                wallet_info(&share, public_key.to_vec())
+            }
+        };
+
+        Ok(Response::new(info))
+    }
+
+    async fn attest(
+        &self,
+        request: Request<AttestMessage>,
+    ) -> Result<Response<AttestationResponse>, Status> {
+        let nonce = request.into_inner().nonce;
+
+        if nonce.len() < MIN_ATTESTATION_NONCE {
+            return Err(invalid_request("Attestation nonce is too short"));
+        }
+
+        let response = self.store.sealer().attest(&nonce).await.map_err(|err| {
+            log::error!("Failed to attest the participant: {err:#}");
+
+            failure(
+                Code::FailedPrecondition,
+                ErrorCode::AttestationFailed,
+                Phase::Attestation,
+                "Failed to attest the participant",
+            )
+        })?;
+
+        Ok(Response::new(response))
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use proto::mpc::{ErrorCode, Phase};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tonic::{Code, Status};
+
+use crate::failure::failure;
+
+/// Bounds how many operations of one kind run at once, with a bounded queue of waiters
+pub struct OperationLimiter {
+    operation: &'static str,
+    phase: Phase,
+    permits: Semaphore,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+// Leaves the queue when the waiting future completes or is dropped by a cancelled request
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl OperationLimiter {
+    pub fn new(
+        operation: &'static str,
+        phase: Phase,
+        max_concurrent: usize,
+        max_queued: usize,
+    ) -> Self {
+        Self {
+            operation,
+            phase,
+            permits: Semaphore::new(max_concurrent),
+            queued: AtomicUsize::new(0),
+            max_queued,
+        }
+    }
+
+    /// Waits for a free slot, failing with `RESOURCE_EXHAUSTED` when the queue is already full
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, Status> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+
+            log::warn!("Rejecting {}: queue is full", self.operation);
+
+            return Err(failure(
+                Code::ResourceExhausted,
+                ErrorCode::Busy,
+                self.phase,
+                format!("Too many concurrent {} operations", self.operation),
+            ));
+        }
+
+        let _slot = QueueSlot(&self.queued);
+
+        self.permits.acquire().await.map_err(|_| {
+            failure(
+                Code::Unavailable,
+                ErrorCode::Unavailable,
+                self.phase,
+                "Participant is shutting down",
+            )
+        })
+    }
+}
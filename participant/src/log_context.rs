@@ -0,0 +1,45 @@
+use std::task::{Context, Poll};
+use tokio::task::futures::TaskLocalFuture;
+use tower::{Layer, Service};
+
+/// Serves every gRPC call in the log context of the request, user and wallet ids the app sent
+/// in its metadata. Logs of work spawned off the call, e.g. the rounds of a streamed keygen,
+/// are written without it
+#[derive(Clone, Copy, Default)]
+pub struct LogContextLayer;
+
+impl<S> Layer<S> for LogContextLayer {
+    type Service = LogContext<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LogContext { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct LogContext<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for LogContext<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TaskLocalFuture<logging::Context, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let context = logging::Context::from_headers(|name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        });
+
+        logging::scope(context, self.inner.call(req))
+    }
+}
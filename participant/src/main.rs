@@ -1,160 +1,126 @@
-mod client;
-mod config;
-mod keygen;
-mod signing;
-
 use log::info;
+use proto::mpc::participant_server::ParticipantServer;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::transport::Server;
+
+use participant::ParticipantHandler;
+use participant::aux_info::AuxInfoCache;
+use participant::client::Client;
+use participant::config::AppConfig;
+use participant::health::HealthMonitor;
+use participant::log_context::LogContextLayer;
+use participant::metrics;
+use participant::policy::SigningPolicy;
+use participant::primes::PrimePool;
+use participant::sealing::Sealer;
+use participant::share::ShareMac;
+use participant::store::{Store, vault_client};
 
-use cggmp21::KeyShare;
-use cggmp21::security_level::SecurityLevel128;
-use cggmp21::supported_curves::Secp256k1;
-use proto::mpc::participant_server::{Participant, ParticipantServer};
-use proto::mpc::{
-    Chain, CreateWalletMessage, DeleteWalletMessage, Empty, SignMessage, SignatureMessage,
-};
-use tonic::{Request, Response, Status, transport::Server};
-use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
-use vaultrs::kv2;
-
-use client::Client;
-use config::AppConfig;
-use keygen::Keygen;
-use signing::Signing;
-
-pub struct ParticipantHandler {
-    client: Client,
-    vault: VaultClient,
-    index: u16,
-}
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
 
-impl ParticipantHandler {
-    pub fn new(client: Client, vault: VaultClient, index: u16) -> Self {
-        Self {
-            client,
-            vault,
-            index,
-        }
-    }
-}
+    logging::init("participant");
 
-#[tonic::async_trait]
-impl Participant for ParticipantHandler {
-    async fn new_wallet(
-        &self,
-        request: Request<CreateWalletMessage>,
-    ) -> Result<Response<Empty>, Status> {
-        let req = request.into_inner();
-
-        let wallet_id = req.wallet_id;
-        let execution_id = req.execution_id;
-        let chain = Chain::try_from(req.chain).map_err(|_| Status::internal("Invalid chain"))?;
-
-        let keygen = Keygen::new(&self.client, wallet_id);
-
-        let share = match chain {
-            Chain::Ethereum => keygen.compute_share::<Secp256k1>(self.index, &execution_id),
-            Chain::Bitcoin => keygen.compute_share::<Secp256k1>(self.index, &execution_id),
-        }
-        .await
-        .map_err(|err| {
-            log::error!("Share computation failed: {err}");
-            Status::internal("Failed to create new wallet")
-        })?;
-
-        kv2::set(&self.vault, "secret", &wallet_id.to_string(), &share)
-            .await
-            .map_err(|_| Status::internal("Failed to store new wallet"))?;
-
-        Ok(Response::new(Empty {}))
-    }
+    info!("Starting MPC participant service");
 
-    async fn delete_wallet(
-        &self,
-        request: Request<DeleteWalletMessage>,
-    ) -> Result<Response<Empty>, Status> {
-        let wallet_id = request.into_inner().wallet_id;
+    let config = AppConfig::load()?;
 
-        info!("Deleting wallet - wallet_id: {}", wallet_id);
+    let server_url = surf::Url::parse(&config.sse_url())?;
 
-        kv2::delete_metadata(&self.vault, "secret", &wallet_id.to_string())
-            .await
-            .map_err(|_| Status::internal("Failed to delete wallet"))?;
+    let client = Client::new(server_url, &config.proxy)?;
 
-        info!("Wallet deleted successfully - wallet_id: {}", wallet_id);
+    info!("Connecting to Vault at: {}", config.vault.address);
 
-        Ok(Response::new(Empty {}))
-    }
+    let vault = vault_client(&config.vault)?;
 
-    async fn sign_tx(
-        &self,
-        request: Request<SignMessage>,
-    ) -> Result<Response<SignatureMessage>, Status> {
-        let req = request.into_inner();
-
-        let tx_id = req.tx_id;
-        let wallet_id = req.wallet_id.to_string();
-        let execution_id = req.execution_id;
-        let chain = Chain::try_from(req.chain).map_err(|_| Status::internal("Invalid chain"))?;
-        let tx = req.data;
-
-        let signign = Signing::new(&self.client, tx_id);
-
-        let key = match chain {
-            Chain::Ethereum => kv2::read::<KeyShare<Secp256k1, SecurityLevel128>>(
-                &self.vault,
-                "secret",
-                &wallet_id,
-            ),
-            Chain::Bitcoin => kv2::read::<KeyShare<Secp256k1, SecurityLevel128>>(
-                &self.vault,
-                "secret",
-                &wallet_id,
-            ),
-        }
-        .await
-        .map_err(|_| Status::internal("Wallet not found"))?;
-
-        let (r, s, v) = signign
-            .sign_tx(self.index, &execution_id, &tx, key, chain)
-            .await
-            .map_err(|_| Status::internal("Transaction signing failed"))?;
-
-        Ok(Response::new(SignatureMessage { r, s, v }))
+    info!("Successfully connected to Vault");
+
+    // Not serving until the monitor has reached Vault and the relay at least once
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    reporter
+        .set_service_status("", tonic_health::ServingStatus::NotServing)
+        .await;
+    reporter
+        .set_not_serving::<ParticipantServer<ParticipantHandler>>()
+        .await;
+
+    let monitor = HealthMonitor::new(
+        reporter,
+        vault_client(&config.vault)?,
+        client.clone(),
+        Duration::from_secs(config.participant.health_interval),
+    );
+
+    tokio::spawn(monitor.run());
+
+    if let Some(metrics_addr) = config.metrics_addr() {
+        let metrics_addr = metrics_addr.parse()?;
+
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(metrics_addr).await {
+                log::error!("Metrics endpoint failed: {err}");
+            }
+        });
     }
-}
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    dotenv::dotenv().ok();
+    let sealer = Sealer::load(&config.sealing).await?;
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    info!("Sealing secrets to platform {:?}", sealer.platform());
 
-    info!("Starting MPC participant service");
+    let store = Arc::new(Store::new(vault, &config.vault).sealed_with(Arc::new(sealer)));
 
-    let config = AppConfig::from_env()?;
+    let primes = Arc::new(PrimePool::new(store.clone(), &config.primes));
 
-    let server_url = surf::Url::parse(&config.sse_url())?;
+    tokio::spawn(primes.clone().run());
 
-    let client = Client::new(server_url)?;
+    let aux = AuxInfoCache::new(store.clone(), primes, config.aux_info.epoch.clone());
 
-    info!("Connecting to Vault at: {}", config.vault.address);
+    let addr = config.participant_addr().parse()?;
 
-    let vault = VaultClient::new(
-        VaultClientSettingsBuilder::default()
-            .address(&config.vault.address)
-            .token(&config.vault.token)
-            .build()?,
-    )?;
+    let mut p = ParticipantHandler::new(
+        client,
+        store,
+        Arc::new(aux),
+        config.participant.index,
+        &config.limits,
+        &config.timeouts,
+        SigningPolicy::new(config.policy.clone()),
+    );
 
-    info!("Successfully connected to Vault");
+    if let Some(key) = &config.share_mac.key {
+        info!("Checking the integrity of the stored shares");
 
-    let addr = config.participant_addr().parse()?;
+        p = p.with_share_mac(ShareMac::new(key.clone(), config.share_mac.accept_missing));
+    }
+
+    if !config.backup.approver_keys.is_empty() {
+        info!(
+            "Exporting share backups to {} pinned keys with {} of {} approvals",
+            config.backup.public_keys.len(),
+            config.backup.approvals,
+            config.backup.approver_keys.len()
+        );
+    }
+
+    if let Some(key) = &config.backup.import_key {
+        let key = k256::SecretKey::from_slice(key)?;
+
+        // Operators re-encrypt the backups they import to this key
+        info!(
+            "Importing share backups encrypted to {}",
+            alloy::primitives::hex::encode(key.public_key().to_sec1_bytes())
+        );
+    }
 
-    let p = ParticipantHandler::new(client, vault, config.participant.index);
+    p = p.with_backup(config.backup.clone());
 
     info!("Starting gRPC server on address: {}", addr);
 
     Server::builder()
+        .layer(LogContextLayer)
+        .add_service(health_service)
         .add_service(ParticipantServer::new(p))
         .serve(addr)
         .await?;
@@ -0,0 +1,307 @@
+use cggmp21::progress::{Event, Tracer};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{debug, info};
+use proto::mpc::{ErrorCode, Phase};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+/// Upper bounds of the duration buckets, in seconds, from a relay round trip to a keygen
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+const PROTOCOL_DURATION: &str = "mpc_protocol_duration_seconds";
+const ROUND_DURATION: &str = "mpc_round_duration_seconds";
+const ROUND_WAIT: &str = "mpc_round_wait_seconds";
+const MESSAGES_SENT: &str = "mpc_messages_sent_total";
+const MESSAGES_RECEIVED: &str = "mpc_messages_received_total";
+const RELAY_BROADCAST: &str = "mpc_relay_broadcast_seconds";
+const FAILURES: &str = "mpc_failures_total";
+
+/// Help of every metric
+const HELP: [(&str, &str); 7] = [
+    (FAILURES, "Failed participant calls by phase and error code"),
+    (
+        MESSAGES_RECEIVED,
+        "Messages received from each sender through the relay",
+    ),
+    (MESSAGES_SENT, "Messages sent through the relay"),
+    (PROTOCOL_DURATION, "Duration of completed MPC protocols"),
+    (
+        RELAY_BROADCAST,
+        "Time until the relay acknowledged a message, retries included",
+    ),
+    (
+        ROUND_DURATION,
+        "Duration of each round of the MPC protocols",
+    ),
+    (
+        ROUND_WAIT,
+        "Time a round waited for the messages of the other parties",
+    ),
+];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Metrics of the participant, served in the Prometheus text format
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Histogram {
+    /// Observations at or below each bound of `BUCKETS`
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<(&'static str, Labels), u64>>,
+    histograms: Mutex<BTreeMap<(&'static str, Labels), Histogram>>,
+}
+
+fn labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect()
+}
+
+/// `{name="value",...}` of the labels, with `extra` appended
+fn label_set(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let pairs = labels
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .chain(extra)
+        .map(|(name, value)| {
+            format!(
+                "{name}=\"{}\"",
+                value.replace('\\', r"\\").replace('"', "\\\"")
+            )
+        })
+        .collect::<Vec<_>>();
+
+    match pairs.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", pairs.join(",")),
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str) {
+    if let Some((_, help)) = HELP.iter().find(|(metric, _)| *metric == name) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+    }
+
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+impl Metrics {
+    fn increment(&self, name: &'static str, labels: Labels) {
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry((name, labels))
+            .or_default() += 1;
+    }
+
+    fn observe(&self, name: &'static str, labels: Labels, duration: Duration) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry((name, labels))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn message_sent(&self, room: &str) {
+        self.increment(MESSAGES_SENT, labels(&[("room", room)]));
+    }
+
+    pub fn message_received(&self, room: &str, sender: u16) {
+        self.increment(
+            MESSAGES_RECEIVED,
+            labels(&[("room", room), ("sender", &sender.to_string())]),
+        );
+    }
+
+    pub fn relay_broadcast(&self, room: &str, duration: Duration) {
+        self.observe(RELAY_BROADCAST, labels(&[("room", room)]), duration);
+    }
+
+    pub fn failure(&self, phase: Phase, error: ErrorCode) {
+        self.increment(
+            FAILURES,
+            labels(&[
+                ("phase", phase.as_str_name()),
+                ("error", error.as_str_name()),
+            ]),
+        );
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut last = None;
+
+        for ((name, labels), value) in self.counters.lock().unwrap().iter() {
+            if last != Some(*name) {
+                header(&mut out, name, "counter");
+                last = Some(*name);
+            }
+
+            let _ = writeln!(out, "{name}{} {value}", label_set(labels, None));
+        }
+
+        for ((name, labels), histogram) in self.histograms.lock().unwrap().iter() {
+            if last != Some(*name) {
+                header(&mut out, name, "histogram");
+                last = Some(*name);
+            }
+
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                let le = bound.to_string();
+                let labels = label_set(labels, Some(("le", &le)));
+
+                let _ = writeln!(out, "{name}_bucket{labels} {count}");
+            }
+
+            let inf = label_set(labels, Some(("le", "+Inf")));
+            let labels = label_set(labels, None);
+
+            let _ = writeln!(out, "{name}_bucket{inf} {}", histogram.count);
+            let _ = writeln!(out, "{name}_sum{labels} {}", histogram.sum);
+            let _ = writeln!(out, "{name}_count{labels} {}", histogram.count);
+        }
+
+        out
+    }
+}
+
+/// Times the rounds of a protocol run by cggmp21, or by hand for FROST. Protocols that fail
+/// are left out of the durations, their failure is counted instead
+pub struct RoundMetrics {
+    protocol: &'static str,
+    round: u32,
+    started: Option<Instant>,
+    round_started: Option<Instant>,
+    waiting: Option<Instant>,
+}
+
+impl RoundMetrics {
+    pub fn new(protocol: &'static str) -> Self {
+        Self {
+            protocol,
+            round: 0,
+            started: None,
+            round_started: None,
+            waiting: None,
+        }
+    }
+
+    fn round_labels(&self) -> Labels {
+        labels(&[
+            ("protocol", self.protocol),
+            ("round", &self.round.to_string()),
+        ])
+    }
+
+    fn end_round(&mut self, now: Instant) {
+        if let Some(started) = self.round_started.take() {
+            metrics().observe(ROUND_DURATION, self.round_labels(), now - started);
+        }
+    }
+}
+
+impl Tracer for RoundMetrics {
+    fn trace_event(&mut self, event: Event) {
+        let now = Instant::now();
+
+        match event {
+            Event::ProtocolBegins => self.started = Some(now),
+            Event::RoundBegins { .. } => {
+                self.end_round(now);
+                self.round += 1;
+                self.round_started = Some(now);
+            }
+            Event::ReceiveMsgs => self.waiting = Some(now),
+            Event::MsgsReceived => {
+                if let Some(waiting) = self.waiting.take() {
+                    metrics().observe(ROUND_WAIT, self.round_labels(), now - waiting);
+                }
+            }
+            Event::ProtocolEnds => {
+                self.end_round(now);
+
+                if let Some(started) = self.started.take() {
+                    metrics().observe(
+                        PROTOCOL_DURATION,
+                        labels(&[("protocol", self.protocol)]),
+                        now - started,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn respond(
+    request: Request<hyper::body::Incoming>,
+) -> Result<Response<Full<Bytes>>, hyper::http::Error> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Full::new(Bytes::from(metrics().render()))),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Full::new(Bytes::new())),
+    }
+}
+
+/// Serves `GET /metrics` on `addr` over plain HTTP/1.1, apart from the gRPC port so the
+/// scraper needs no access to the signing API
+pub async fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    info!("Serving metrics on http://{addr}/metrics");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        tokio::spawn(async move {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service_fn(respond))
+                .await
+            {
+                debug!("Metrics connection failed: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use alloy::primitives::{Address, Bytes, TxKind, U256, keccak256};
+use alloy_rlp::{Decodable, RlpDecodable};
+use log::warn;
+use proto::mpc::{Chain, ErrorCode, Phase};
+use tokio::sync::Mutex;
+use tonic::{Code, Status};
+
+use crate::config::PolicyConfig;
+use crate::failure::failure;
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+/// Selectors of ERC-20 `transfer(address,uint256)` and `approve(address,uint256)`
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+
+/// Unsigned EIP-155 transaction, the transaction fields followed by the chain id and two
+/// empty values, only the fields the rules look at are read
+#[allow(dead_code)]
+#[derive(Debug, RlpDecodable)]
+#[cfg_attr(test, derive(alloy_rlp::RlpEncodable))]
+struct SigningPayload {
+    nonce: u64,
+    gas_price: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    data: Bytes,
+    chain_id: u64,
+    r: u8,
+    s: u8,
+}
+
+/// Account an ERC-20 `transfer` sends tokens to or an `approve` lets spend them, `None` for
+/// any other calldata
+fn token_beneficiary(data: &[u8]) -> Option<Address> {
+    let (selector, args) = data.split_first_chunk::<4>()?;
+
+    if *selector != TRANSFER_SELECTOR && *selector != APPROVE_SELECTOR {
+        return None;
+    }
+
+    // The address and amount words, the address left-padded with zeros
+    if args.len() != 64 || args[..12].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+
+    Some(Address::from_slice(&args[12..32]))
+}
+
+fn violation(message: &str) -> Status {
+    warn!("Signing refused by policy: {message}");
+
+    failure(
+        Code::PermissionDenied,
+        ErrorCode::PolicyViolation,
+        Phase::Request,
+        message,
+    )
+}
+
+/// Rules the participant checks on its own before signing, so a compromised app can't get
+/// transactions out of it that break them
+pub struct SigningPolicy {
+    config: PolicyConfig,
+    /// When the transactions of each wallet signed in the last 24 hours were, oldest first
+    signings: Mutex<HashMap<i32, VecDeque<Instant>>>,
+}
+
+impl SigningPolicy {
+    pub fn new(config: PolicyConfig) -> Self {
+        Self {
+            config,
+            signings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts `count` transactions of the wallet against the signing rate limits, refusing
+    /// them all when they'd exceed one. The counts are kept in memory and start over when the
+    /// participant restarts
+    pub async fn throttle(&self, wallet_id: i32, count: usize) -> Result<(), Status> {
+        let hourly = self.config.max_signatures_per_hour as usize;
+        let daily = self.config.max_signatures_per_day as usize;
+
+        if hourly == 0 && daily == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+
+        let mut signings = self.signings.lock().await;
+
+        // Wallets that signed nothing for a day are dropped as their history expires
+        signings.retain(|_, signed| {
+            while signed
+                .front()
+                .is_some_and(|at| now.duration_since(*at) >= DAY)
+            {
+                signed.pop_front();
+            }
+
+            !signed.is_empty()
+        });
+
+        let signed = signings.entry(wallet_id).or_default();
+
+        let last_hour = signed
+            .iter()
+            .rev()
+            .take_while(|at| now.duration_since(**at) < HOUR)
+            .count();
+
+        if hourly > 0 && last_hour + count > hourly {
+            return Err(violation("Hourly signing limit of the wallet reached"));
+        }
+
+        if daily > 0 && signed.len() + count > daily {
+            return Err(violation("Daily signing limit of the wallet reached"));
+        }
+
+        signed.extend(std::iter::repeat_n(now, count));
+
+        Ok(())
+    }
+
+    fn enabled(&self) -> bool {
+        self.config.max_value.is_some()
+            || !self.config.chain_ids.is_empty()
+            || !self.config.recipients.is_empty()
+    }
+
+    /// Checks the data of a signing request, anything is signed when no rule is configured
+    ///
+    /// EVM transactions are checked on the RLP `payload` whose keccak hash is the prehashed
+    /// `data`, or on `data` itself when it is hashed by the participant. Other data, e.g.
+    /// user operation digests or other chains, is refused unless `allow_opaque` is set.
+    /// The recipients of ERC-20 transfers and the spenders of approvals must be allowed like
+    /// the transaction recipient, other calldata is refused unless `allow_contract_calls`
+    /// is set.
+    pub fn check(
+        &self,
+        chain: Chain,
+        chain_id: u64,
+        data: &[u8],
+        prehashed: bool,
+        payload: &[u8],
+    ) -> Result<(), Status> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let evm = matches!(
+            chain,
+            Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon
+        );
+
+        let payload = match (evm, prehashed) {
+            (true, true) if !payload.is_empty() => {
+                if keccak256(payload).as_slice() != data {
+                    return Err(violation("Payload does not match the signed digest"));
+                }
+
+                payload
+            }
+            (true, false) => data,
+            _ if self.config.allow_opaque => return Ok(()),
+            _ => return Err(violation("Data the signing policy can't check is refused")),
+        };
+
+        let tx = SigningPayload::decode(&mut &payload[..])
+            .map_err(|_| violation("Payload is not an EIP-155 transaction"))?;
+
+        if tx.chain_id != chain_id {
+            return Err(violation("Transaction chain id does not match the request"));
+        }
+
+        if !self.config.chain_ids.is_empty() && !self.config.chain_ids.contains(&tx.chain_id) {
+            return Err(violation("Chain id not allowed by the signing policy"));
+        }
+
+        if self.config.max_value.is_some_and(|max| tx.value > max) {
+            return Err(violation("Value above the signing policy maximum"));
+        }
+
+        // Deployments have no recipient and are refused by an allowlist
+        if !self.config.recipients.is_empty()
+            && !matches!(tx.to, TxKind::Call(to) if self.config.recipients.contains(&to))
+        {
+            return Err(violation("Recipient not allowed by the signing policy"));
+        }
+
+        if tx.data.is_empty() {
+            return Ok(());
+        }
+
+        match (tx.to, token_beneficiary(&tx.data)) {
+            (TxKind::Call(_), Some(beneficiary)) => {
+                if !self.config.recipients.is_empty()
+                    && !self.config.recipients.contains(&beneficiary)
+                {
+                    return Err(violation(
+                        "Token recipient not allowed by the signing policy",
+                    ));
+                }
+            }
+            _ if self.config.allow_contract_calls => {}
+            _ => {
+                return Err(violation(
+                    "Contract calls are refused by the signing policy",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+    use alloy_rlp::Encodable;
+
+    const TOKEN: Address = address!("0x1111111111111111111111111111111111111111");
+    const RECIPIENT: Address = address!("0x2222222222222222222222222222222222222222");
+    const STRANGER: Address = address!("0x3333333333333333333333333333333333333333");
+
+    fn policy(config: PolicyConfig) -> SigningPolicy {
+        SigningPolicy::new(PolicyConfig {
+            recipients: vec![TOKEN, RECIPIENT],
+            ..config
+        })
+    }
+
+    fn payload(chain_id: u64, to: TxKind, value: u64, data: Vec<u8>) -> Vec<u8> {
+        let mut payload = Vec::new();
+
+        SigningPayload {
+            nonce: 0,
+            gas_price: 1,
+            gas_limit: 21_000,
+            to,
+            value: U256::from(value),
+            data: data.into(),
+            chain_id,
+            r: 0,
+            s: 0,
+        }
+        .encode(&mut payload);
+
+        payload
+    }
+
+    fn token_call(selector: [u8; 4], beneficiary: Address) -> Vec<u8> {
+        let mut data = selector.to_vec();
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(beneficiary.as_slice());
+        data.extend_from_slice(&U256::from(1).to_be_bytes::<32>());
+        data
+    }
+
+    fn refusal(result: Result<(), Status>) -> String {
+        let status = result.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        status.message().to_string()
+    }
+
+    #[test]
+    fn test_transfer_to_allowed_recipient_is_signed() {
+        let policy = policy(PolicyConfig::default());
+        let tx = payload(1, TxKind::Call(RECIPIENT), 5, vec![]);
+
+        policy.check(Chain::Ethereum, 1, &tx, false, &[]).unwrap();
+        policy
+            .check(Chain::Ethereum, 1, keccak256(&tx).as_slice(), true, &tx)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_payload_not_matching_digest_is_refused() {
+        let policy = policy(PolicyConfig::default());
+        let tx = payload(1, TxKind::Call(RECIPIENT), 5, vec![]);
+        let other = payload(1, TxKind::Call(STRANGER), 5, vec![]);
+
+        let message =
+            refusal(policy.check(Chain::Ethereum, 1, keccak256(&other).as_slice(), true, &tx));
+        assert_eq!(message, "Payload does not match the signed digest");
+    }
+
+    #[test]
+    fn test_opaque_data_is_refused_unless_allowed() {
+        let policy = policy(PolicyConfig::default());
+
+        let message = refusal(policy.check(Chain::Solana, 0, b"message", false, &[]));
+        assert_eq!(message, "Data the signing policy can't check is refused");
+
+        let message = refusal(policy.check(Chain::Ethereum, 1, &[0; 32], true, &[]));
+        assert_eq!(message, "Data the signing policy can't check is refused");
+
+        let policy = self::policy(PolicyConfig {
+            allow_opaque: true,
+            ..Default::default()
+        });
+        policy
+            .check(Chain::Ethereum, 1, &[0; 32], true, &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_undecodable_payload_is_refused() {
+        let policy = policy(PolicyConfig::default());
+
+        let message = refusal(policy.check(Chain::Ethereum, 1, b"not rlp", false, &[]));
+        assert_eq!(message, "Payload is not an EIP-155 transaction");
+    }
+
+    #[test]
+    fn test_chain_id_rules() {
+        let policy = policy(PolicyConfig {
+            chain_ids: vec![1],
+            ..Default::default()
+        });
+        let tx = payload(10, TxKind::Call(RECIPIENT), 5, vec![]);
+
+        let message = refusal(policy.check(Chain::Optimism, 1, &tx, false, &[]));
+        assert_eq!(message, "Transaction chain id does not match the request");
+
+        let message = refusal(policy.check(Chain::Optimism, 10, &tx, false, &[]));
+        assert_eq!(message, "Chain id not allowed by the signing policy");
+    }
+
+    #[test]
+    fn test_value_above_maximum_is_refused() {
+        let policy = policy(PolicyConfig {
+            max_value: Some(U256::from(5)),
+            ..Default::default()
+        });
+
+        let tx = payload(1, TxKind::Call(RECIPIENT), 5, vec![]);
+        policy.check(Chain::Ethereum, 1, &tx, false, &[]).unwrap();
+
+        let tx = payload(1, TxKind::Call(RECIPIENT), 6, vec![]);
+        let message = refusal(policy.check(Chain::Ethereum, 1, &tx, false, &[]));
+        assert_eq!(message, "Value above the signing policy maximum");
+    }
+
+    #[test]
+    fn test_recipient_not_allowed_is_refused() {
+        let policy = policy(PolicyConfig::default());
+
+        let tx = payload(1, TxKind::Call(STRANGER), 5, vec![]);
+        let message = refusal(policy.check(Chain::Ethereum, 1, &tx, false, &[]));
+        assert_eq!(message, "Recipient not allowed by the signing policy");
+
+        let tx = payload(1, TxKind::Create, 0, vec![0x60, 0x00]);
+        let message = refusal(policy.check(Chain::Ethereum, 1, &tx, false, &[]));
+        assert_eq!(message, "Recipient not allowed by the signing policy");
+    }
+
+    #[test]
+    fn test_token_beneficiary_must_be_allowed() {
+        let policy = policy(PolicyConfig::default());
+
+        for selector in [TRANSFER_SELECTOR, APPROVE_SELECTOR] {
+            let tx = payload(1, TxKind::Call(TOKEN), 0, token_call(selector, RECIPIENT));
+            policy.check(Chain::Ethereum, 1, &tx, false, &[]).unwrap();
+
+            let tx = payload(1, TxKind::Call(TOKEN), 0, token_call(selector, STRANGER));
+            let message = refusal(policy.check(Chain::Ethereum, 1, &tx, false, &[]));
+            assert_eq!(message, "Token recipient not allowed by the signing policy");
+        }
+    }
+
+    #[test]
+    fn test_other_calldata_is_refused_unless_allowed() {
+        let mut call = token_call(TRANSFER_SELECTOR, RECIPIENT);
+        // `transferFrom(address,address,uint256)`
+        call[..4].copy_from_slice(&[0x23, 0xb8, 0x72, 0xdd]);
+
+        let tx = payload(1, TxKind::Call(TOKEN), 0, call);
+
+        let policy = policy(PolicyConfig {
+            chain_ids: vec![1],
+            ..Default::default()
+        });
+        let message = refusal(policy.check(Chain::Ethereum, 1, &tx, false, &[]));
+        assert_eq!(message, "Contract calls are refused by the signing policy");
+
+        let policy = self::policy(PolicyConfig {
+            allow_contract_calls: true,
+            ..Default::default()
+        });
+        policy.check(Chain::Ethereum, 1, &tx, false, &[]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signing_limits() {
+        let policy = policy(PolicyConfig {
+            max_signatures_per_hour: 2,
+            ..Default::default()
+        });
+
+        policy.throttle(1, 2).await.unwrap();
+        let message = refusal(policy.throttle(1, 1).await);
+        assert_eq!(message, "Hourly signing limit of the wallet reached");
+
+        // Other wallets are counted apart
+        policy.throttle(2, 2).await.unwrap();
+
+        let policy = self::policy(PolicyConfig {
+            max_signatures_per_day: 3,
+            ..Default::default()
+        });
+
+        policy.throttle(1, 3).await.unwrap();
+        let message = refusal(policy.throttle(1, 1).await);
+        assert_eq!(message, "Daily signing limit of the wallet reached");
+    }
+}
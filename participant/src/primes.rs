@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cggmp21::PregeneratedPrimes;
+use cggmp21::security_level::SecurityLevel128;
+use log::{info, warn};
+use tokio::sync::Mutex;
+use vaultrs::error::ClientError;
+
+use crate::config::PrimesConfig;
+use crate::store::Store;
+
+type Primes = PregeneratedPrimes<SecurityLevel128>;
+
+/// Store key of the cached primes, apart from the wallet shares keyed by wallet id
+const KEY: &str = "primes";
+
+/// Safe primes of the aux-info phase generated ahead of the keygens, which would otherwise
+/// wait for them. The pool is cached in the store so a restart doesn't empty it, a pair
+/// leaves the cache before a keygen gets it and is never handed out twice
+pub struct PrimePool {
+    store: Arc<Store>,
+    primes: Mutex<VecDeque<Primes>>,
+    size: usize,
+    refill_interval: Duration,
+}
+
+impl PrimePool {
+    pub fn new(store: Arc<Store>, config: &PrimesConfig) -> Self {
+        Self {
+            store,
+            primes: Mutex::new(VecDeque::new()),
+            size: config.pool_size,
+            refill_interval: Duration::from_secs(config.refill_interval),
+        }
+    }
+
+    /// Pool handing out `primes` before generating any, never refilled. Lets the tests skip
+    /// the minutes of generating primes
+    #[cfg(any(test, feature = "testing"))]
+    pub fn seeded(store: Arc<Store>, primes: Vec<Primes>) -> Self {
+        Self {
+            store,
+            primes: Mutex::new(primes.into()),
+            size: 0,
+            refill_interval: Duration::ZERO,
+        }
+    }
+
+    /// Loads the cached primes, then keeps the pool full generating a pair every refill
+    /// interval. Does nothing when the pool is disabled
+    pub async fn run(self: Arc<Self>) {
+        if self.size == 0 {
+            return;
+        }
+
+        self.load().await;
+
+        loop {
+            if self.primes.lock().await.len() < self.size {
+                let primes = generate().await;
+
+                let mut pool = self.primes.lock().await;
+                pool.push_back(primes);
+
+                if let Err(err) = self.persist(&pool).await {
+                    warn!("Failed to cache pregenerated primes: {err}");
+                }
+
+                info!("Prime pool holds {}/{} pairs", pool.len(), self.size);
+            }
+
+            tokio::time::sleep(self.refill_interval).await;
+        }
+    }
+
+    /// Primes for one aux-info phase, from the pool when it has some, generated on the spot
+    /// otherwise
+    pub async fn take(&self) -> Primes {
+        let mut pool = self.primes.lock().await;
+
+        if let Some(primes) = pool.pop_front() {
+            match self.persist(&pool).await {
+                Ok(()) => return primes,
+                // Still in the cache, it would be handed out again after a restart
+                Err(err) => warn!("Failed to remove pregenerated primes from the cache: {err}"),
+            }
+        }
+
+        drop(pool);
+
+        generate().await
+    }
+
+    async fn load(&self) {
+        let cached = match self.store.read::<Vec<Primes>>(KEY).await {
+            Ok(cached) => cached,
+            Err(ClientError::APIError { code: 404, .. }) => Vec::new(),
+            Err(err) => {
+                warn!("Failed to read the cached primes: {err}");
+                return;
+            }
+        };
+
+        info!("Loaded {} cached pairs of primes", cached.len());
+
+        self.primes.lock().await.extend(cached);
+    }
+
+    async fn persist(&self, pool: &VecDeque<Primes>) -> Result<(), ClientError> {
+        self.store.set(KEY, &pool.iter().collect::<Vec<_>>()).await
+    }
+}
+
+/// Generates a pair off the async workers, it takes seconds of CPU
+async fn generate() -> Primes {
+    tokio::task::spawn_blocking(|| Primes::generate(&mut rand::rngs::OsRng))
+        .await
+        .expect("Prime generation panicked")
+}
+
+/// Pairs generated once for the tests, which would spend minutes generating their own.
+/// They are public, never use them for a real key
+#[cfg(any(test, feature = "testing"))]
+pub fn pregenerated() -> Vec<Primes> {
+    serde_json::from_str(include_str!("../testdata/primes.json")).unwrap()
+}
@@ -0,0 +1,68 @@
+use crate::metrics::RoundMetrics;
+use cggmp21::progress::{Event, Tracer};
+use futures::channel::mpsc::UnboundedSender;
+use proto::mpc::{KeygenEvent, KeygenProgress, KeygenStage, keygen_event};
+use tonic::Status;
+
+/// Events of the `NewWallet` response stream
+pub type KeygenEvents = UnboundedSender<Result<KeygenEvent, Status>>;
+
+/// Reports the progress of a keygen to the app, events are dropped once it stopped listening
+#[derive(Clone)]
+pub struct Progress {
+    events: KeygenEvents,
+}
+
+impl Progress {
+    pub fn new(events: KeygenEvents) -> Self {
+        Self { events }
+    }
+
+    pub fn stage(&self, stage: KeygenStage) {
+        self.round(stage, 0);
+    }
+
+    fn round(&self, stage: KeygenStage, round: u32) {
+        let progress = KeygenProgress {
+            stage: stage.into(),
+            round,
+        };
+
+        let _ = self.events.unbounded_send(Ok(KeygenEvent {
+            event: Some(keygen_event::Event::Progress(progress)),
+        }));
+    }
+
+    /// Tracer of the `protocol` run by the stage, reporting each round as it begins and
+    /// timing it in the metrics
+    pub fn tracer(&self, stage: KeygenStage, protocol: &'static str) -> RoundTracer {
+        RoundTracer {
+            progress: self.clone(),
+            stage,
+            round: 0,
+            metrics: RoundMetrics::new(protocol),
+        }
+    }
+}
+
+pub struct RoundTracer {
+    progress: Progress,
+    stage: KeygenStage,
+    round: u32,
+    metrics: RoundMetrics,
+}
+
+impl Tracer for RoundTracer {
+    fn trace_event(&mut self, event: Event) {
+        self.metrics.trace_event(event);
+
+        match event {
+            Event::ProtocolBegins => self.progress.stage(self.stage),
+            Event::RoundBegins { .. } => {
+                self.round += 1;
+                self.progress.round(self.stage, self.round);
+            }
+            _ => {}
+        }
+    }
+}
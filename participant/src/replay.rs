@@ -0,0 +1,108 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use alloy::primitives::hex;
+use log::warn;
+use proto::mpc::{ErrorCode, Phase};
+use serde::Serialize;
+use tonic::{Code, Status};
+use vaultrs::error::ClientError;
+
+use crate::failure::failure;
+use crate::store::Store;
+
+/// Marker of a used id, kept next to the shares in Vault
+#[derive(Serialize)]
+struct Claim {
+    operation: &'static str,
+    claimed_at: u64,
+}
+
+/// Vault path of the execution id of a keygen or signing
+fn execution_path(execution_id: &[u8]) -> String {
+    format!("executions/{}", hex::encode(execution_id))
+}
+
+/// Vault path of an item of a transaction of the wallet, e.g. one input of a PSBT
+fn signing_path(wallet_id: i32, tx_id: i32, item: u32) -> String {
+    format!("signings/{wallet_id}/{tx_id}/{item}")
+}
+
+/// Vault path of an approved export, its approvals are good for a single export
+fn export_path(export_id: i32) -> String {
+    format!("exports/{export_id}")
+}
+
+/// Records `path` as used, failing when it already was. The write only succeeds on a path
+/// that doesn't exist yet, so concurrent requests with the same id can't both pass
+async fn claim(store: &Store, path: &str, operation: &'static str) -> Result<(), Status> {
+    let claim = Claim {
+        operation,
+        claimed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+    };
+
+    store.create(path, &claim).await.map_err(|err| match err {
+        // Vault refuses the check-and-set write of an existing path
+        ClientError::APIError { code: 400, .. } => {
+            warn!("Rejected replayed {operation} request - {path}");
+
+            failure(
+                Code::AlreadyExists,
+                ErrorCode::Replayed,
+                Phase::Request,
+                format!("The {operation} request was already received"),
+            )
+        }
+        _ => failure(
+            Code::Internal,
+            ErrorCode::StorageFailed,
+            Phase::Storage,
+            "Failed to record the execution id",
+        ),
+    })
+}
+
+/// Claims the execution id of a ceremony, a keygen or signing runs once per execution id
+pub async fn claim_execution(
+    store: &Store,
+    execution_id: &[u8],
+    operation: &'static str,
+) -> Result<(), Status> {
+    claim(store, &execution_path(execution_id), operation).await
+}
+
+/// Claims an item of a transaction of the wallet, each of them is signed once whatever the
+/// execution id of the request
+pub async fn claim_signing(
+    store: &Store,
+    wallet_id: i32,
+    tx_id: i32,
+    item: u32,
+) -> Result<(), Status> {
+    claim(store, &signing_path(wallet_id, tx_id, item), "signing").await
+}
+
+/// Claims the id of an approved export, replaying its approvals doesn't export the share again
+pub async fn claim_export(store: &Store, export_id: i32) -> Result<(), Status> {
+    claim(store, &export_path(export_id), "export").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_export_is_claimed_once() {
+        let store = Store::in_memory();
+
+        claim_export(&store, 7).await.unwrap();
+
+        let replayed = claim_export(&store, 7).await.unwrap_err();
+
+        assert_eq!(replayed.code(), Code::AlreadyExists);
+
+        // Another export of the same wallet has approvals of its own
+        claim_export(&store, 8).await.unwrap();
+    }
+}
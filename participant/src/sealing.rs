@@ -0,0 +1,343 @@
+//! Sealing of the stored secrets to the platform the participant runs on. Secrets are
+//! encrypted under a key only the platform releases, a TPM object bound to PCRs, the
+//! sealing key of the SGX enclave measurement or a key provisioned into an SEV-SNP guest,
+//! so the Vault token alone doesn't read the shares. The platform also produces the
+//! evidence the app checks before including the participant in a signing set
+
+use alloy::primitives::hex;
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use proto::mpc::AttestationResponse;
+use rand::RngCore;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::fs;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+use crate::config::{SealingConfig, SealingPlatform};
+
+static SEALING_INFO: &[u8] = b"mpc-waas-sealing-v1";
+
+static ATTESTATION_DOMAIN: &[u8] = b"mpc-waas-attestation-v1";
+
+/// Shortest nonce an attestation is produced for
+pub const MIN_ATTESTATION_NONCE: usize = 16;
+
+const NONCE_LEN: usize = 12;
+
+/// Report data of the evidence produced for `nonce`
+pub fn report_data(nonce: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(ATTESTATION_DOMAIN)
+        .chain_update(nonce)
+        .finalize()
+        .into()
+}
+
+/// Report data padded to the 64 bytes of SGX and SEV-SNP reports
+fn padded_report_data(nonce: &[u8]) -> [u8; 64] {
+    let mut padded = [0; 64];
+    padded[..32].copy_from_slice(&report_data(nonce));
+    padded
+}
+
+pub struct Sealer {
+    config: SealingConfig,
+    key: Option<Zeroizing<[u8; 32]>>,
+    /// The TPM and the attestation pseudo-files produce one quote at a time
+    quoting: Mutex<()>,
+}
+
+impl Sealer {
+    /// Stores secrets as they are, for participants without a sealing platform
+    pub fn unsealed() -> Self {
+        Self {
+            config: SealingConfig {
+                platform: SealingPlatform::None,
+                tpm_key_handle: None,
+                tpm_ak_handle: None,
+                tpm_pcrs: String::new(),
+                attestation_dir: String::new(),
+                tsm_dir: String::new(),
+                key_path: None,
+            },
+            key: None,
+            quoting: Mutex::new(()),
+        }
+    }
+
+    /// Sealer of the platform, failing when the platform doesn't release its key, e.g. a TPM
+    /// whose PCRs changed since the key was sealed
+    pub async fn load(config: &SealingConfig) -> Result<Self> {
+        let secret = match config.platform {
+            SealingPlatform::None => None,
+            SealingPlatform::Tpm => {
+                let handle = config
+                    .tpm_key_handle
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("No TPM sealing key handle"))?;
+
+                let pcrs = format!("pcr:{}", config.tpm_pcrs);
+
+                Some(run("tpm2_unseal", &["-c", handle, "-p", &pcrs]).await?)
+            }
+            SealingPlatform::Sgx => {
+                let path = Path::new(&config.attestation_dir).join("keys/_sgx_mrenclave");
+
+                Some(read(&path).await?)
+            }
+            SealingPlatform::SevSnp => {
+                let path = config
+                    .key_path
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("No SEV-SNP sealing key path"))?;
+
+                Some(read(Path::new(path)).await?)
+            }
+        };
+
+        let key = match secret {
+            Some(secret) if secret.is_empty() => {
+                return Err(anyhow!("The sealing platform released an empty key"));
+            }
+            Some(secret) => {
+                let mut key = Zeroizing::new([0; 32]);
+
+                Hkdf::<Sha256>::new(None, &secret)
+                    .expand(SEALING_INFO, key.as_mut())
+                    .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+                Some(key)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            key,
+            quoting: Mutex::new(()),
+        })
+    }
+
+    pub fn platform(&self) -> SealingPlatform {
+        self.config.platform
+    }
+
+    fn cipher(&self) -> Option<ChaCha20Poly1305> {
+        let key = self.key.as_ref()?;
+
+        Some(ChaCha20Poly1305::new(Key::from_slice(key.as_ref())))
+    }
+
+    /// Value to store at `path`, bound to it so sealed secrets can't be moved to another path
+    pub fn seal(&self, path: &str, value: Value) -> Result<Value> {
+        let Some(cipher) = self.cipher() else {
+            return Ok(value);
+        };
+
+        let plaintext = Zeroizing::new(serde_json::to_vec(&value)?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut rand::rngs::OsRng);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("Failed to seal secret"))?;
+
+        Ok(serde_json::json!({
+            "sealed": BASE64.encode([nonce.as_slice(), &ciphertext].concat()),
+        }))
+    }
+
+    /// Secret stored at `path`. Secrets stored before sealing was enabled are returned as
+    /// they are and sealed when they are written again
+    pub fn unseal(&self, path: &str, stored: Value) -> Result<Value> {
+        let Some(sealed) = sealed(&stored) else {
+            return Ok(stored);
+        };
+
+        let cipher = self
+            .cipher()
+            .ok_or_else(|| anyhow!("Secret is sealed but the participant runs without sealing"))?;
+
+        let sealed = BASE64.decode(sealed).context("Invalid sealed secret")?;
+
+        if sealed.len() < NONCE_LEN {
+            return Err(anyhow!("Invalid sealed secret"));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("Failed to unseal secret, it was sealed on another platform"))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Evidence of the platform over the report data of `nonce`
+    pub async fn attest(&self, nonce: &[u8]) -> Result<AttestationResponse> {
+        let _quoting = self.quoting.lock().await;
+
+        let (evidence, signature) = match self.config.platform {
+            SealingPlatform::None => return Err(anyhow!("Participant runs without sealing")),
+            SealingPlatform::Tpm => self.tpm_quote(nonce).await?,
+            SealingPlatform::Sgx => (self.sgx_quote(nonce).await?, Vec::new()),
+            SealingPlatform::SevSnp => (self.snp_report(nonce).await?, Vec::new()),
+        };
+
+        Ok(AttestationResponse {
+            platform: platform(self.config.platform).into(),
+            evidence,
+            signature,
+        })
+    }
+
+    /// TPMS_ATTEST of a quote of the PCRs the key is bound to, and its DER signature
+    async fn tpm_quote(&self, nonce: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let handle = self
+            .config
+            .tpm_ak_handle
+            .as_deref()
+            .ok_or_else(|| anyhow!("No TPM attestation key handle"))?;
+
+        let dir = std::env::temp_dir().join(format!("mpc-quote-{}", random_hex()));
+        fs::create_dir(&dir).await?;
+
+        let message = dir.join("quote.msg");
+        let signature = dir.join("quote.sig");
+
+        let quoted = run(
+            "tpm2_quote",
+            &[
+                "-c",
+                handle,
+                "-l",
+                &self.config.tpm_pcrs,
+                "-q",
+                &hex::encode(report_data(nonce)),
+                "-g",
+                "sha256",
+                "-f",
+                "plain",
+                "-m",
+                &message.to_string_lossy(),
+                "-s",
+                &signature.to_string_lossy(),
+            ],
+        )
+        .await;
+
+        let quote = match quoted {
+            Ok(_) => Ok((
+                read(&message).await?.to_vec(),
+                read(&signature).await?.to_vec(),
+            )),
+            Err(err) => Err(err),
+        };
+
+        fs::remove_dir_all(&dir).await.ok();
+
+        quote
+    }
+
+    /// DCAP quote of the enclave produced by Gramine for the report data
+    async fn sgx_quote(&self, nonce: &[u8]) -> Result<Vec<u8>> {
+        let dir = Path::new(&self.config.attestation_dir);
+
+        fs::write(dir.join("user_report_data"), padded_report_data(nonce))
+            .await
+            .context("Failed to write the SGX report data")?;
+
+        Ok(read(&dir.join("quote")).await?.to_vec())
+    }
+
+    /// SEV-SNP attestation report produced through a configfs-tsm entry of its own
+    async fn snp_report(&self, nonce: &[u8]) -> Result<Vec<u8>> {
+        let entry = Path::new(&self.config.tsm_dir).join(format!("mpc-{}", random_hex()));
+
+        fs::create_dir(&entry)
+            .await
+            .context("Failed to create the configfs-tsm report")?;
+
+        let report = async {
+            fs::write(entry.join("inblob"), padded_report_data(nonce)).await?;
+
+            Ok(read(&entry.join("outblob")).await?.to_vec())
+        }
+        .await;
+
+        fs::remove_dir(&entry).await.ok();
+
+        report
+    }
+}
+
+fn platform(platform: SealingPlatform) -> proto::mpc::SealingPlatform {
+    match platform {
+        SealingPlatform::None => proto::mpc::SealingPlatform::Unsealed,
+        SealingPlatform::Tpm => proto::mpc::SealingPlatform::Tpm,
+        SealingPlatform::Sgx => proto::mpc::SealingPlatform::Sgx,
+        SealingPlatform::SevSnp => proto::mpc::SealingPlatform::SevSnp,
+    }
+}
+
+/// Sealed secret of a stored value, `None` for values stored as they are
+fn sealed(stored: &Value) -> Option<&str> {
+    let object = stored.as_object()?;
+
+    if object.len() != 1 {
+        return None;
+    }
+
+    object.get("sealed")?.as_str()
+}
+
+fn random_hex() -> String {
+    let mut bytes = [0; 8];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+async fn read(path: &Path) -> Result<Zeroizing<Vec<u8>>> {
+    fs::read(path)
+        .await
+        .map(Zeroizing::new)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Standard output of a `tpm2-tools` command
+async fn run(program: &str, args: &[&str]) -> Result<Zeroizing<Vec<u8>>> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {program}"))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(Zeroizing::new(output.stdout))
+}
@@ -0,0 +1,205 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use alloy::primitives::hex;
+use hmac::{Hmac, Mac};
+use proto::mpc::{Chain, ErrorCode, Phase, WalletInfoResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use tonic::{Code, Status};
+use zeroize::Zeroizing;
+
+use crate::curves::WalletKey;
+use crate::failure::failure;
+
+/// Version of the envelope and of the share format it holds, bumped by migrations
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Stored share of a wallet with what it was generated for, checked before the share
+/// itself is deserialized
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareEnvelope {
+    pub version: u32,
+    pub chain: String,
+    pub curve: String,
+    /// Share type, see `WalletKey::name`
+    pub key: String,
+    pub threshold: u32,
+    pub parties: u32,
+    /// Unix time of the keygen, in seconds
+    pub created_at: u64,
+    /// Hex execution id of the keygen
+    pub execution_id: String,
+    pub share: Value,
+    /// Hex HMAC-SHA256 of the envelope by the participant, see `ShareMac`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
+
+fn mismatch(message: &str) -> Status {
+    failure(
+        Code::FailedPrecondition,
+        ErrorCode::ShareMismatch,
+        Phase::Storage,
+        message,
+    )
+}
+
+impl ShareEnvelope {
+    pub fn new(
+        chain: Chain,
+        key: WalletKey,
+        info: &WalletInfoResponse,
+        execution_id: &[u8],
+        share: Value,
+    ) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            chain: chain.as_str_name().to_string(),
+            curve: info.curve.clone(),
+            key: key.name().to_string(),
+            threshold: info.threshold,
+            parties: info.parties.len() as u32,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            execution_id: hex::encode(execution_id),
+            share,
+            mac: None,
+        }
+    }
+}
+
+/// Envelopes are told from shares stored before them by their version
+fn is_envelope(stored: &Value) -> bool {
+    stored.get("version").is_some() && stored.get("share").is_some()
+}
+
+/// Share of a stored value, after checking its envelope holds a share of `key` in a format
+/// this participant reads. Shares stored before envelopes are returned as is
+pub fn open(stored: Value, key: WalletKey) -> Result<Value, Status> {
+    if !is_envelope(&stored) {
+        return Ok(stored);
+    }
+
+    let envelope = serde_json::from_value::<ShareEnvelope>(stored)
+        .map_err(|_| mismatch("Invalid share envelope"))?;
+
+    if envelope.version > SCHEMA_VERSION {
+        return Err(mismatch("Share stored in a newer format"));
+    }
+
+    if envelope.key != key.name() {
+        return Err(mismatch("Stored share is of another key type"));
+    }
+
+    Ok(envelope.share)
+}
+
+/// Share of a stored value without checking its envelope
+pub fn inner(stored: &Value) -> &Value {
+    match stored.get("share") {
+        Some(share) if is_envelope(stored) => share,
+        _ => stored,
+    }
+}
+
+/// Value to store for a share checked by `open`, in the envelope it was read from if any
+pub fn reseal(mut stored: Value, share: Value) -> Value {
+    if is_envelope(&stored) {
+        stored["share"] = share;
+        stored
+    } else {
+        share
+    }
+}
+
+fn tampered(message: &str) -> Status {
+    failure(
+        Code::DataLoss,
+        ErrorCode::ShareTampered,
+        Phase::Storage,
+        message,
+    )
+}
+
+/// MAC of the stored shares, keyed with a secret of the participant kept out of Vault so a
+/// share corrupted or written to Vault by anyone else fails its check
+pub struct ShareMac {
+    key: Zeroizing<Vec<u8>>,
+    /// Reads shares stored without a MAC, while the shares of a participant enabling MACs
+    /// are rewritten
+    accept_missing: bool,
+}
+
+impl ShareMac {
+    pub fn new(key: Vec<u8>, accept_missing: bool) -> Self {
+        Self {
+            key: Zeroizing::new(key),
+            accept_missing,
+        }
+    }
+
+    /// Tag of the envelope without its MAC, bound to the wallet so shares can't be swapped.
+    /// Objects of `serde_json` serialize with sorted keys, so the bytes are canonical
+    fn tag(&self, wallet_id: &str, envelope: &Value) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key)
+            .expect("HMAC-SHA256 takes keys of any length");
+
+        mac.update(wallet_id.as_bytes());
+        mac.update(&[0]);
+        mac.update(envelope.to_string().as_bytes());
+
+        mac
+    }
+
+    /// Stored value with the MAC of the wallet's share, replacing any earlier one. Shares
+    /// stored before envelopes are left as they are
+    pub fn sign(&self, wallet_id: &str, mut stored: Value) -> Value {
+        if !is_envelope(&stored) {
+            return stored;
+        }
+
+        if let Some(envelope) = stored.as_object_mut() {
+            envelope.remove("mac");
+        }
+
+        let tag = self.tag(wallet_id, &stored).finalize().into_bytes();
+
+        stored["mac"] = Value::String(hex::encode(tag));
+
+        stored
+    }
+
+    /// Checks the MAC of the stored share of the wallet
+    pub fn verify(&self, wallet_id: &str, stored: &Value) -> Result<(), Status> {
+        let mac = stored
+            .get("mac")
+            .filter(|_| is_envelope(stored))
+            .and_then(Value::as_str);
+
+        let Some(mac) = mac else {
+            if self.accept_missing {
+                log::warn!("Share of wallet {wallet_id} has no MAC, accepted until rewritten");
+                return Ok(());
+            }
+
+            return Err(tampered("Stored share has no MAC"));
+        };
+
+        let tag = hex::decode(mac).map_err(|_| tampered("Stored share has an invalid MAC"))?;
+
+        let mut envelope = stored.clone();
+
+        if let Some(envelope) = envelope.as_object_mut() {
+            envelope.remove("mac");
+        }
+
+        self.tag(wallet_id, &envelope)
+            .verify_slice(&tag)
+            .map_err(|_| {
+                log::error!("Share of wallet {wallet_id} failed its integrity check");
+                tampered("Stored share failed its integrity check")
+            })
+    }
+}
@@ -1,56 +1,227 @@
-use crate::client::{Client, Room};
+use crate::client::{Ceremony, Client, Room};
+use crate::curves::HdCurve;
+use crate::frost::{self, Ciphersuite};
+use crate::metrics::RoundMetrics;
+use alloy::primitives::keccak256;
 use alloy::signers::k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use cggmp21::DataToSign;
 use cggmp21::ExecutionId;
+use cggmp21::IncompleteKeyShare;
 use cggmp21::KeyShare;
-use generic_ec::{Curve, Point, coords::HasAffineX};
+use cggmp21::key_share::AnyKeyShare;
+use futures::channel::mpsc;
+use futures::future::{ready, try_join_all};
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use generic_ec::{Curve, Point, Scalar, coords::HasAffineX};
 use proto::mpc::Chain;
 
-use cggmp21::hd_wallet::slip10::SupportedCurve;
-use cggmp21::round_based::MpcParty;
+use cggmp21::round_based::{Incoming, MpcParty, Outgoing};
 use cggmp21::security_level::SecurityLevel128;
+use cggmp21::signing::Signature as EcdsaSignature;
 use cggmp21::signing::msg::Msg;
-use sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Sha3_256;
 use std::error::Error;
 
+use crate::client::TransportError;
+
+/// Message of one signing of a batch, all of them share the batch room
+#[derive(Serialize, Deserialize)]
+struct BatchMsg<M> {
+    item: usize,
+    msg: M,
+}
+
+/// Data the CGGMP21 signing signs for the chain, hashing `tx` unless it is `prehashed`
+fn data_to_sign<T: Curve>(chain: Chain, tx: &[u8], prehashed: bool) -> Result<DataToSign<T>> {
+    Ok(match chain {
+        _ if prehashed => {
+            if tx.len() != 32 {
+                return Err(anyhow!("Prehashed data must be a 32-byte digest"));
+            }
+
+            DataToSign::from_scalar(Scalar::from_be_bytes_mod_order(tx))
+        }
+        // EVM chains sign the keccak hash of the transaction
+        Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon => {
+            DataToSign::from_scalar(Scalar::from_be_bytes_mod_order(keccak256(tx)))
+        }
+        Chain::Bitcoin => DataToSign::digest::<Sha256>(tx),
+        // The transaction hash is already a field element
+        Chain::Starknet => DataToSign::from_scalar(Scalar::from_be_bytes_mod_order(tx)),
+        Chain::Flow => DataToSign::digest::<Sha3_256>(tx),
+        Chain::Solana => return Err(anyhow!("Solana transactions are signed with FROST")),
+    })
+}
+
+/// `(r, s, v)` of a combined signature by `public_key`, `v` is the EIP-155 v of the chain
+/// id on EVM chains
+fn signature_parts<T>(
+    signature: EcdsaSignature<T>,
+    data: DataToSign<T>,
+    public_key: Point<T>,
+    chain: Chain,
+    chain_id: u64,
+) -> Result<(Vec<u8>, Vec<u8>, u32)>
+where
+    T: Curve,
+{
+    let r = signature.r.into_inner().to_be_bytes();
+    let r_bytes = r.as_bytes();
+    let s = signature.s.into_inner().to_be_bytes();
+    let s_bytes = s.as_bytes();
+
+    // EIP-155 v of the chain id the app signs for
+    let v = match chain {
+        Chain::Ethereum | Chain::Optimism | Chain::Arbitrum | Chain::Base | Chain::Polygon => {
+            let pub_key = public_key.to_bytes(false);
+            let v_key = VerifyingKey::from_sec1_bytes(&pub_key).map_err(|err| {
+                log::error!("Verifying key failed: {err}");
+                if let Some(source) = err.source() {
+                    log::error!("Caused by: {}", source);
+                }
+                err
+            })?;
+            let s = Signature::from_slice(&[r_bytes, s_bytes].concat()).map_err(|err| {
+                log::error!("Signature failed: {err}");
+                if let Some(source) = err.source() {
+                    log::error!("Caused by: {}", source);
+                }
+                err
+            })?;
+
+            let reid = RecoveryId::trial_recovery_from_prehash(
+                &v_key,
+                &data.to_scalar().to_be_bytes(),
+                &s,
+            );
+
+            // https://medium.com/@LucasJennings/a-step-by-step-guide-to-generating-raw-ethereum-transactions-c3292ad36ab4
+            let recovery = match reid {
+                Err(_) => r.last().unwrap() % 2,
+                Ok(id) => id.to_byte(),
+            };
+
+            eip155_v(chain_id, recovery)
+                .ok_or_else(|| anyhow!("Chain id {chain_id} is too large"))?
+        }
+        Chain::Bitcoin | Chain::Solana | Chain::Starknet | Chain::Flow => 0,
+    };
+
+    Ok((r_bytes.to_vec(), s_bytes.to_vec(), v))
+}
+
+/// EIP-155 `v` of the recovery id, `chain_id * 2 + 35 + recovery`, unless it overflows
+fn eip155_v(chain_id: u64, recovery: u8) -> Option<u32> {
+    chain_id
+        .checked_mul(2)?
+        .checked_add(35 + u64::from(recovery))
+        .and_then(|v| u32::try_from(v).ok())
+}
+
 pub struct Signing {
     room: Room,
+    // Second FROST round, the first one runs in `room`
+    shares_room: Room,
+    // Keygen indexes the app selected to sign
+    signers: Vec<u16>,
+    // Path of the child key signing single transactions, empty for the key of the share
+    derivation_path: Vec<u32>,
 }
 
 impl Signing {
-    pub fn new(client: &Client, id: i32) -> Self {
+    pub fn new(client: &Client, ceremony: &Ceremony, id: i32, signers: Vec<u16>) -> Self {
+        Self {
+            room: client.room(ceremony, format!("signing_{id}").as_str()),
+            shares_room: client.room(ceremony, format!("shares_{id}").as_str()),
+            signers,
+            derivation_path: Vec::new(),
+        }
+    }
+
+    /// Signing of a batch, its rooms are apart from the ones of single transactions
+    pub fn batch(client: &Client, ceremony: &Ceremony, id: i32, signers: Vec<u16>) -> Self {
         Self {
-            room: client.room(format!("signing_{id}").as_str()),
+            room: client.room(ceremony, format!("batch_signing_{id}").as_str()),
+            shares_room: client.room(ceremony, format!("batch_shares_{id}").as_str()),
+            signers,
+            derivation_path: Vec::new(),
+        }
+    }
+
+    /// Signs with the child key of an HD share at the non-hardened `derivation_path`
+    pub fn derived(self, derivation_path: Vec<u32>) -> Self {
+        Self {
+            derivation_path,
+            ..self
+        }
+    }
+
+    /// Keygen indexes of the signers the app selected, sorted so every signer derives the
+    /// same `parties_indexes_at_keygen`, and the signing index of the local party among them
+    fn parties(&self, keygen_index: u16, signers: u16) -> Result<(Vec<u16>, u16)> {
+        let mut parties = self.signers.clone();
+
+        parties.sort_unstable();
+        parties.dedup();
+
+        if parties.len() != usize::from(signers) {
+            return Err(anyhow!(
+                "Expected {signers} signers, the app selected {parties:?}"
+            ));
         }
+
+        let index = parties
+            .iter()
+            .position(|i| *i == keygen_index)
+            .ok_or_else(|| {
+                anyhow!("Keygen index {keygen_index} is not among the selected signers {parties:?}")
+            })?;
+
+        Ok((parties, index as u16))
     }
 
     pub async fn sign_tx<T>(
         self,
-        index: u16,
         execution_id: &[u8],
         tx: &[u8],
         key_share: KeyShare<T, SecurityLevel128>,
         chain: Chain,
+        chain_id: u64,
+        prehashed: bool,
     ) -> Result<(Vec<u8>, Vec<u8>, u32)>
     where
-        T: Curve + SupportedCurve,
+        T: HdCurve,
         Point<T>: HasAffineX<T>,
     {
         let eid = ExecutionId::new(execution_id);
 
+        let (parties, index) = self.parties(key_share.core.i, key_share.min_signers())?;
+
         let (_, incoming, outgoing) = self.room.join_room::<Msg<T, Sha256>>(index).await?;
 
         let party = MpcParty::connected((incoming, outgoing));
 
-        let data = match chain {
-            Chain::Ethereum => DataToSign::digest::<Sha256>(tx),
-            Chain::Bitcoin => DataToSign::digest::<Sha256>(tx),
+        let data = data_to_sign(chain, tx, prehashed)?;
+
+        let signing = cggmp21::signing(eid, index, &parties, &key_share);
+
+        let (signing, public_key) = match self.derivation_path.as_slice() {
+            [] => (signing, key_share.shared_public_key.into_inner()),
+            path => (
+                signing.set_derivation_path_with_algo::<T::Hd, _>(path.iter().copied())?,
+                key_share
+                    .derive_child_public_key::<T::Hd, _>(path.iter().copied())?
+                    .public_key,
+            ),
         };
 
-        // TODO: Harcoded parties_indexes_at_keygen. Participants has a harcoded index.
-        // Indexes must be issued on room creation and stored in DB.
-        let signature = cggmp21::signing(eid, index, &[0, 1], &key_share)
+        let mut tracer = RoundMetrics::new("signing");
+
+        let signature = signing
+            .set_progress_tracer(&mut tracer)
             .sign(&mut rand::rngs::OsRng, party, data)
             .await
             .map_err(|err| {
@@ -61,53 +232,236 @@ impl Signing {
                 err
             })?;
 
-        let r = signature.r.into_inner().to_be_bytes();
-        let r_bytes = r.as_bytes();
-        let s = signature.s.into_inner().to_be_bytes();
-        let s_bytes = s.as_bytes();
-
-        let v = match chain {
-            Chain::Ethereum => {
-                let pub_key = key_share.shared_public_key.into_inner().to_bytes(false);
-                let v_key = VerifyingKey::from_sec1_bytes(&pub_key).map_err(|err| {
-                    log::error!("Verifying key failed: {err}");
-                    if let Some(source) = err.source() {
-                        log::error!("Caused by: {}", source);
-                    }
-                    err
-                })?;
-                let s = Signature::from_slice(&[r_bytes, s_bytes].concat()).map_err(|err| {
-                    log::error!("Signature failed: {err}");
-                    if let Some(source) = err.source() {
-                        log::error!("Caused by: {}", source);
-                    }
+        signature_parts(signature, data, public_key, chain, chain_id)
+    }
+
+    /// Signs every item of `data` like `sign_tx` does, the signings run at once over a
+    /// single subscription of the batch room
+    pub async fn sign_batch<T>(
+        self,
+        execution_id: &[u8],
+        data: &[Vec<u8>],
+        key_share: KeyShare<T, SecurityLevel128>,
+        chain: Chain,
+        chain_id: u64,
+        prehashed: bool,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>, u32)>>
+    where
+        T: Curve,
+        Point<T>: HasAffineX<T>,
+    {
+        let data = data
+            .iter()
+            .map(|tx| data_to_sign::<T>(chain, tx, prehashed))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (parties, index) = self.parties(key_share.core.i, key_share.min_signers())?;
+
+        let (_, incoming, outgoing) = self
+            .room
+            .join_room::<BatchMsg<Msg<T, Sha256>>>(index)
+            .await?;
+
+        // Every signing needs its own execution id, derived from the batch one
+        let execution_ids = (0..data.len())
+            .map(|item| {
+                Sha256::new()
+                    .chain_update(execution_id)
+                    .chain_update((item as u64).to_be_bytes())
+                    .finalize()
+            })
+            .collect::<Vec<_>>();
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..data.len())
+            .map(|_| mpsc::unbounded::<Result<Incoming<Msg<T, Sha256>>, TransportError>>())
+            .unzip();
+
+        // Routes the messages of the room to the signing of their item
+        let demux = tokio::spawn(incoming.try_for_each(move |msg| {
+            match senders.get(msg.msg.item) {
+                // Signings that already finished don't read their messages anymore
+                Some(sender) => {
+                    let _ = sender.unbounded_send(Ok(Incoming {
+                        id: msg.id,
+                        sender: msg.sender,
+                        msg_type: msg.msg_type,
+                        msg: msg.msg.msg,
+                    }));
+                }
+                None => log::warn!("Dropping message of unknown batch item {}", msg.msg.item),
+            }
+
+            ready(Ok(()))
+        }));
+
+        // Sends the messages of every signing through the one outgoing sink, in order
+        let (outbox, outbox_rx) = mpsc::unbounded::<Outgoing<BatchMsg<Msg<T, Sha256>>>>();
+        let sender = tokio::spawn(outbox_rx.map(Ok).forward(outgoing));
+
+        let signings = receivers
+            .into_iter()
+            .zip(&data)
+            .zip(&execution_ids)
+            .enumerate()
+            .map(|(item, ((incoming, data), execution_id))| {
+                let outgoing = outbox
+                    .clone()
+                    .sink_map_err(|_| TransportError::Broadcast)
+                    .with(move |msg: Outgoing<Msg<T, Sha256>>| {
+                        ready(Ok::<_, TransportError>(Outgoing {
+                            recipient: msg.recipient,
+                            msg: BatchMsg { item, msg: msg.msg },
+                        }))
+                    });
+
+                let party = MpcParty::connected((incoming, Box::pin(outgoing)));
+                let (parties, key_share) = (&parties, &key_share);
+
+                async move {
+                    let mut tracer = RoundMetrics::new("batch_signing");
+
+                    cggmp21::signing(ExecutionId::new(execution_id), index, parties, key_share)
+                        .set_progress_tracer(&mut tracer)
+                        .sign(&mut rand::rngs::OsRng, party, *data)
+                        .await
+                }
+            });
+
+        let signatures = try_join_all(signings).await;
+
+        demux.abort();
+
+        // Dropping the last sender lets the outbox flush the final messages
+        drop(outbox);
+
+        if let Ok(Err(err)) = sender.await {
+            log::warn!("Failed to send batch messages: {err}");
+        }
+
+        let signatures = signatures.map_err(|err| {
+            log::error!("Batch signing failed: {err}");
+            if let Some(source) = err.source() {
+                log::error!("Caused by: {}", source);
+            }
+            err
+        })?;
+
+        signatures
+            .into_iter()
+            .zip(data)
+            .map(|(signature, data)| {
+                let public_key = key_share.shared_public_key.into_inner();
+
+                signature_parts(signature, data, public_key, chain, chain_id)
+            })
+            .collect()
+    }
+
+    /// Signs `message` as is with FROST, returning the two halves of the 64-byte signature of
+    /// the ciphersuite as `r` and `s`
+    pub async fn sign_frost<C: Ciphersuite>(
+        self,
+        message: &[u8],
+        key_share: IncompleteKeyShare<C::Curve>,
+    ) -> Result<(Vec<u8>, Vec<u8>, u32)> {
+        let (parties, _) = self.parties(key_share.i, key_share.min_signers())?;
+
+        let signature =
+            frost::sign::<C>(self.room, self.shares_room, &key_share, &parties, message)
+                .await
+                .map_err(|err| {
+                    log::error!("FROST signing failed: {err}");
                     err
                 })?;
 
-                let reid = RecoveryId::trial_recovery_from_msg(
-                    &v_key,
-                    &data.to_scalar().to_be_bytes(),
-                    &s,
-                );
-
-                // TODO: Harcoded! Use an input from the request or a config value
-                let chain_id = 1;
-
-                // https://medium.com/@LucasJennings/a-step-by-step-guide-to-generating-raw-ethereum-transactions-c3292ad36ab4
-                match reid {
-                    Err(_) => {
-                        if r.last().unwrap() % 2 == 0 {
-                            37
-                        } else {
-                            38
-                        }
-                    }
-                    Ok(id) => chain_id * 2 + 35 + id.to_byte(),
-                }
+        Ok((signature[..32].to_vec(), signature[32..].to_vec(), 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curves::{EcdsaCurve, WalletKey};
+    use crate::keygen::{THRESHOLD, TOTAL_PARTIES};
+    use cggmp21::supported_curves::Secp256r1;
+    use p256::ecdsa::signature::hazmat::PrehashVerifier;
+    use proto::mpc::SignatureScheme;
+
+    #[test]
+    fn test_eip155_v() {
+        assert_eq!(eip155_v(1, 0), Some(37));
+        assert_eq!(eip155_v(11155111, 1), Some(22310258));
+        assert_eq!(eip155_v(u64::from(u32::MAX), 0), None);
+        assert_eq!(eip155_v(u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn test_flow_keygen_and_signing_round_trip() {
+        assert_eq!(
+            WalletKey::of(Chain::Flow, SignatureScheme::Ecdsa).unwrap(),
+            WalletKey::Ecdsa(EcdsaCurve::Secp256r1)
+        );
+
+        let eid = ExecutionId::new(b"flow round trip");
+
+        let keygens = round_based::sim::run(TOTAL_PARTIES, |i, party| async move {
+            cggmp21::keygen::<Secp256r1>(eid, i, TOTAL_PARTIES)
+                .set_threshold(THRESHOLD)
+                .start(&mut rand::rngs::OsRng, party)
+                .await
+        })
+        .unwrap()
+        .expect_ok()
+        .into_vec();
+
+        let aux_infos = round_based::sim::run_with_setup(
+            crate::primes::pregenerated()
+                .into_iter()
+                .take(TOTAL_PARTIES.into()),
+            |i, party, primes| async move {
+                cggmp21::aux_info_gen(eid, i, TOTAL_PARTIES, primes)
+                    .start(&mut rand::rngs::OsRng, party)
+                    .await
+            },
+        )
+        .unwrap()
+        .expect_ok()
+        .into_vec();
+
+        let shares = keygens
+            .into_iter()
+            .zip(aux_infos)
+            .map(|parts| KeyShare::<Secp256r1, SecurityLevel128>::from_parts(parts).unwrap())
+            .collect::<Vec<_>>();
+
+        let tx = b"Flow transaction";
+        let data = data_to_sign::<Secp256r1>(Chain::Flow, tx, false).unwrap();
+
+        // Keygen indexes 0 and 2 sign
+        let parties = [0, 2];
+        let signature = round_based::sim::run(2, |i, party| {
+            let share = &shares[usize::from(parties[usize::from(i)])];
+
+            async move {
+                cggmp21::signing(eid, i, &parties, share)
+                    .sign(&mut rand::rngs::OsRng, party, data)
+                    .await
             }
-            Chain::Bitcoin => 0,
-        };
+        })
+        .unwrap()
+        .expect_ok()
+        .expect_eq();
+
+        let public_key = shares[0].shared_public_key.into_inner();
+        let (r, s, v) = signature_parts(signature, data, public_key, Chain::Flow, 0).unwrap();
+        assert_eq!(v, 0);
+
+        let verifying_key =
+            p256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key.to_bytes(false)).unwrap();
+        let signature = p256::ecdsa::Signature::from_slice(&[r, s].concat()).unwrap();
 
-        Ok((r_bytes.to_vec(), s_bytes.to_vec(), v.into()))
+        verifying_key
+            .verify_prehash(&Sha3_256::digest(tx), &signature)
+            .unwrap();
     }
 }
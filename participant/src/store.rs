@@ -0,0 +1,179 @@
+use serde::Serialize;
+use serde::de::{DeserializeOwned, Error as _};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, RwLock};
+use vaultrs::api::kv2::requests::SetSecretRequestOptions;
+use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
+use vaultrs::error::ClientError;
+use vaultrs::kv2;
+
+use crate::config::VaultConfig;
+use crate::sealing::Sealer;
+
+pub fn vault_client(config: &VaultConfig) -> anyhow::Result<VaultClient> {
+    let mut settings = VaultClientSettingsBuilder::default();
+
+    settings.address(&config.address).token(&config.token);
+
+    if let Some(namespace) = &config.namespace {
+        settings.set_namespace(namespace.clone());
+    }
+
+    Ok(VaultClient::new(settings.build()?)?)
+}
+
+enum Backend {
+    Vault {
+        client: Box<VaultClient>,
+        mount: String,
+    },
+    /// Secrets lost with the process, answering like Vault does
+    Memory(RwLock<HashMap<String, serde_json::Value>>),
+}
+
+/// Secrets of the participant, kept under the path prefix of the KV v2 mount so several
+/// environments can share a Vault cluster, and sealed to the platform of the participant
+/// when it has one
+pub struct Store {
+    backend: Backend,
+    prefix: String,
+    sealer: Arc<Sealer>,
+}
+
+impl Store {
+    pub fn new(client: VaultClient, config: &VaultConfig) -> Self {
+        Self {
+            backend: Backend::Vault {
+                client: Box::new(client),
+                mount: config.mount.clone(),
+            },
+            prefix: config.prefix.trim_matches('/').to_string(),
+            sealer: Arc::new(Sealer::unsealed()),
+        }
+    }
+
+    /// Same store, sealing the secrets it writes with `sealer`
+    pub fn sealed_with(mut self, sealer: Arc<Sealer>) -> Self {
+        self.sealer = sealer;
+        self
+    }
+
+    pub fn sealer(&self) -> &Sealer {
+        &self.sealer
+    }
+
+    /// Store without Vault for in-process tests, nothing outlives it
+    pub fn in_memory() -> Self {
+        Self {
+            backend: Backend::Memory(RwLock::new(HashMap::new())),
+            prefix: String::new(),
+            sealer: Arc::new(Sealer::unsealed()),
+        }
+    }
+
+    fn path(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{key}", self.prefix)
+        }
+    }
+
+    pub async fn read<T: DeserializeOwned>(&self, key: &str) -> Result<T, ClientError> {
+        let path = self.path(key);
+
+        let stored = match &self.backend {
+            Backend::Vault { client, mount } => kv2::read(&**client, mount, &path).await?,
+            Backend::Memory(secrets) => secrets
+                .read()
+                .unwrap()
+                .get(&path)
+                .cloned()
+                .ok_or_else(|| api_error(404))?,
+        };
+
+        let secret = self.sealer.unseal(&path, stored).map_err(sealing_error)?;
+
+        serde_json::from_value(secret).map_err(|source| ClientError::JsonParseError { source })
+    }
+
+    fn seal<T: Serialize>(&self, path: &str, data: &T) -> Result<serde_json::Value, ClientError> {
+        self.sealer
+            .seal(path, to_value(data)?)
+            .map_err(sealing_error)
+    }
+
+    pub async fn set<T: Serialize>(&self, key: &str, data: &T) -> Result<(), ClientError> {
+        let path = self.path(key);
+        let secret = self.seal(&path, data)?;
+
+        match &self.backend {
+            Backend::Vault { client, mount } => {
+                kv2::set(&**client, mount, &path, &secret).await.map(|_| ())
+            }
+            Backend::Memory(secrets) => {
+                secrets.write().unwrap().insert(path, secret);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes `key` only if it doesn't exist yet, Vault refuses the write with a `400` otherwise
+    pub async fn create<T: Serialize>(&self, key: &str, data: &T) -> Result<(), ClientError> {
+        let path = self.path(key);
+        let secret = self.seal(&path, data)?;
+
+        match &self.backend {
+            Backend::Vault { client, mount } => kv2::set_with_options(
+                &**client,
+                mount,
+                &path,
+                &secret,
+                SetSecretRequestOptions { cas: 0 },
+            )
+            .await
+            .map(|_| ()),
+            Backend::Memory(secrets) => match secrets.write().unwrap().entry(path) {
+                Entry::Occupied(_) => Err(api_error(400)),
+                Entry::Vacant(entry) => {
+                    entry.insert(secret);
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Deletes every version of `key`
+    pub async fn delete(&self, key: &str) -> Result<(), ClientError> {
+        match &self.backend {
+            Backend::Vault { client, mount } => {
+                kv2::delete_metadata(&**client, mount, &self.path(key)).await
+            }
+            // Vault deletes missing paths without an error as well
+            Backend::Memory(secrets) => {
+                secrets.write().unwrap().remove(&self.path(key));
+                Ok(())
+            }
+        }
+    }
+}
+
+fn to_value<T: Serialize>(data: &T) -> Result<serde_json::Value, ClientError> {
+    serde_json::to_value(data).map_err(|source| ClientError::JsonParseError { source })
+}
+
+/// Secrets failing to seal or unseal are reported like unreadable ones
+fn sealing_error(err: anyhow::Error) -> ClientError {
+    ClientError::JsonParseError {
+        source: serde_json::Error::custom(format!("{err:#}")),
+    }
+}
+
+/// Error Vault answers a request with `code`
+fn api_error(code: u16) -> ClientError {
+    ClientError::APIError {
+        code,
+        errors: Vec::new(),
+    }
+}
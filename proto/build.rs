@@ -2,6 +2,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_prost_build::configure()
         .build_server(true)
         .build_client(true)
-        .compile_protos(&["proto/mpc.proto"], &["proto"])?;
+        .compile_protos(&["proto/mpc.proto", "proto/relay.proto"], &["proto"])?;
     Ok(())
 }
@@ -1,3 +1,7 @@
 pub mod mpc {
     tonic::include_proto!("mpc");
 }
+
+pub mod relay {
+    tonic::include_proto!("relay");
+}
@@ -0,0 +1,130 @@
+//! Operator endpoints of the relay, guarded by the static `SSE_ADMIN_KEY` and disabled when
+//! no key is configured: rooms with their subscribers, messages and ages, and closing the
+//! room of a stuck execution
+
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, web};
+use log::{error, info, warn};
+use serde::Serialize;
+
+use crate::Db;
+
+static ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// State of a room as operators see it
+#[derive(Serialize, Debug)]
+pub struct RoomStatus {
+    pub room_id: String,
+    /// Open subscriptions, of this replica only with Redis
+    pub subscribers: usize,
+    /// Messages the room retains
+    pub messages: usize,
+    /// Event id of the next message published to the room
+    pub next_event: u64,
+    /// Bytes of the retained messages, only known in memory
+    pub bytes: Option<usize>,
+    /// Seconds since the room was created, only known in memory
+    pub age_secs: Option<u64>,
+    /// Seconds since the last message, or subscription change in memory. Unknown for rooms
+    /// without messages in Redis
+    pub idle_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct RoomList {
+    rooms: Vec<RoomStatus>,
+}
+
+/// Response refusing the request, `None` when it presents the admin key
+fn reject(db: &Db, req: &HttpRequest) -> Option<HttpResponse> {
+    let Some(admin_key) = db.admin_key.as_deref() else {
+        return Some(HttpResponse::Forbidden().body("Admin API is disabled"));
+    };
+
+    let provided = req
+        .headers()
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|header| header.to_str().ok());
+
+    match provided {
+        Some(key) if tokens::constant_time_eq(key.as_bytes(), admin_key.as_bytes()) => None,
+        _ => {
+            warn!(
+                "Rejecting admin request {} {}: invalid admin key",
+                req.method(),
+                req.path()
+            );
+
+            Some(HttpResponse::Unauthorized().body("Invalid admin key"))
+        }
+    }
+}
+
+fn unavailable(err: anyhow::Error) -> HttpResponse {
+    error!("Room state is unavailable to the admin API: {}", err);
+
+    HttpResponse::ServiceUnavailable().body("Room state is unavailable")
+}
+
+async fn list_rooms(db: web::Data<Db>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    if let Some(rejected) = reject(&db, &req) {
+        return Ok(rejected);
+    }
+
+    match db.room_statuses().await {
+        Ok(rooms) => Ok(HttpResponse::Ok().json(RoomList { rooms })),
+        Err(err) => Ok(unavailable(err)),
+    }
+}
+
+async fn get_room(
+    db: web::Data<Db>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    if let Some(rejected) = reject(&db, &req) {
+        return Ok(rejected);
+    }
+
+    match db.room_status(&path.into_inner()).await {
+        Ok(Some(room)) => Ok(HttpResponse::Ok().json(room)),
+        Ok(None) => Ok(HttpResponse::NotFound().body("Room not found")),
+        Err(err) => Ok(unavailable(err)),
+    }
+}
+
+/// Drops the room and ends the streams of its subscribers, answered with the state the room
+/// was in
+async fn close_room(
+    db: web::Data<Db>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    if let Some(rejected) = reject(&db, &req) {
+        return Ok(rejected);
+    }
+
+    let room_id = path.into_inner();
+
+    let room = match db.room_status(&room_id).await {
+        Ok(Some(room)) => room,
+        Ok(None) => return Ok(HttpResponse::NotFound().body("Room not found")),
+        Err(err) => return Ok(unavailable(err)),
+    };
+
+    if let Err(err) = db.close_room(&room_id).await {
+        return Ok(unavailable(err));
+    }
+
+    info!(
+        "Closed room '{}' with {} subscribers and {} messages",
+        room_id, room.subscribers, room.messages
+    );
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/admin/rooms", web::get().to(list_rooms))
+        .route("/admin/rooms/{room_id}", web::get().to(get_room))
+        .route("/admin/rooms/{room_id}", web::delete().to(close_room));
+}
@@ -0,0 +1,671 @@
+//! Rooms kept in Redis so that several relay replicas behind a load balancer serve them
+//! consistently: the messages of a room are a stream, its indexes and sequences are
+//! keys next to it, and every publication is announced on a pub/sub channel to wake the
+//! subscribers of each replica. Closing a room is announced on another one
+//!
+//! The keys of a room share a hash slot, so its scripts run on one node of a Redis Cluster.
+//! Rooms are listed in a set of their own, a keyspace scan would only cover one node
+
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use log::{debug, info, warn};
+use redis::streams::{StreamId, StreamRangeReply};
+use redis::{Script, cmd};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::admin::RoomStatus;
+use crate::config::RedisConfig;
+use crate::envelope::{EnvelopeHeader, Format, Message};
+use crate::redis::Client;
+use crate::{BroadcastAck, ENTRY_BYTES, Event, IndexesExhausted, SequenceOverflow};
+
+/// Channel the rooms receiving a message are published on
+const CHANNEL: &str = "sse:published";
+
+/// Channel the rooms closed by an operator are published on
+const CLOSED_CHANNEL: &str = "sse:closed";
+
+/// Keys of the state of a room
+const ROOM_KEYS: [&str; 6] = [
+    "indexes",
+    "sequences",
+    "published",
+    "messages",
+    "next",
+    "bytes",
+];
+
+/// Set of the ids of the rooms, pruned of the expired ones as they are listed
+const ROOMS_KEY: &str = "sse:rooms";
+
+/// Messages read from a stream at once
+const READ_BATCH: usize = 256;
+
+/// Subscribers look for messages they may have missed a notification of this often
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Delay before the channel subscription is attempted again
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The backlog of every room is measured this often
+const BACKLOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Publishes an envelope unless its sequence was already seen, in one step so replicas
+/// receiving retries of the same envelope agree on its event id. Event ids are counted by
+/// the next id key, stream entries are `0-<event id + 1>` and the oldest are trimmed past
+/// the retained messages, along with their published event id and, when the sender
+/// published nothing since, its sequence. Returns whether the envelope is a duplicate, its
+/// event id and the change of the bytes of the room
+///
+/// `KEYS`: sequences, published event ids, messages, next event id, bytes
+/// `ARGV`: `sender:epoch`, `sender:epoch:sequence`, sequence, message, TTL, room, retained
+/// messages, message format, bytes of an entry besides its message
+static PUBLISH_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local next = tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0')
+if tonumber(ARGV[3]) < next then
+  local id = redis.call('HGET', KEYS[2], ARGV[2])
+  return {1, id and tonumber(id) or -1, 0}
+end
+redis.call('HSET', KEYS[1], ARGV[1], tonumber(ARGV[3]) + 1)
+local id = redis.call('INCR', KEYS[4]) - 1
+redis.call('XADD', KEYS[3], string.format('0-%d', id + 1), 'message', ARGV[4], 'format', ARGV[8], 'sender', ARGV[1], 'sequence', ARGV[3])
+redis.call('HSET', KEYS[2], ARGV[2], string.format('%d', id))
+local bytes = #ARGV[4] + tonumber(ARGV[9])
+local excess = redis.call('XLEN', KEYS[3]) - tonumber(ARGV[7])
+if excess > 0 then
+  for _, entry in ipairs(redis.call('XRANGE', KEYS[3], '-', '+', 'COUNT', excess)) do
+    local fields = {}
+    for i = 1, #entry[2], 2 do
+      fields[entry[2][i]] = entry[2][i + 1]
+    end
+    bytes = bytes - #(fields['message'] or '') - tonumber(ARGV[9])
+    if fields['sender'] then
+      redis.call('HDEL', KEYS[2], fields['sender'] .. ':' .. fields['sequence'])
+      if tonumber(redis.call('HGET', KEYS[1], fields['sender']) or '-1') == tonumber(fields['sequence']) + 1 then
+        redis.call('HDEL', KEYS[1], fields['sender'])
+      end
+    end
+    redis.call('XDEL', KEYS[3], entry[1])
+  end
+end
+redis.call('INCRBY', KEYS[5], bytes)
+for _, key in ipairs(KEYS) do
+  redis.call('EXPIRE', key, ARGV[5])
+end
+redis.call('PUBLISH', 'sse:published', ARGV[6])
+return {0, id, bytes}
+"#,
+    )
+});
+
+/// Key of a room, its id is a hash tag so every key of the room lives on the same slot
+fn key(room_id: &str, name: &str) -> String {
+    format!("sse:{{{room_id}}}:{name}")
+}
+
+/// Subscribers of a room on this replica
+#[derive(Default)]
+struct Waiters {
+    notify: Notify,
+    closed: AtomicBool,
+    /// Changed with the rooms locked
+    subscribers: AtomicUsize,
+}
+
+pub struct Cluster {
+    client: Client,
+    /// Wakes the local subscribers of each room with one
+    rooms: Mutex<HashMap<String, Arc<Waiters>>>,
+    room_ttl: u64,
+    /// Bytes of the messages of every room as last measured, with the ones published by
+    /// this replica since
+    backlog: AtomicI64,
+}
+
+impl Cluster {
+    pub async fn connect(config: &RedisConfig) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            client: Client::connect(config).await?,
+            room_ttl: config.room_ttl,
+            rooms: Mutex::new(HashMap::new()),
+            backlog: AtomicI64::new(0),
+        }))
+    }
+
+    /// Wakes the subscribers of the rooms published to, and ends the subscriptions of the
+    /// rooms closed, on every replica including this one
+    pub async fn listen(self: Arc<Self>) {
+        loop {
+            match self.client.subscribe(&[CHANNEL, CLOSED_CHANNEL]).await {
+                Ok(mut subscription) => {
+                    info!(
+                        "Listening for room messages on Redis channels {CHANNEL} and {CLOSED_CHANNEL}"
+                    );
+
+                    // Messages published while disconnected were not announced
+                    self.wake_all();
+
+                    while let Some((channel, room_id)) = subscription.next().await {
+                        let room_id = String::from_utf8_lossy(&room_id);
+
+                        match channel == CLOSED_CHANNEL {
+                            true => self.end_subscriptions(&room_id),
+                            false => self.wake(&room_id),
+                        }
+                    }
+
+                    warn!("Lost the Redis channel subscription");
+                }
+                Err(err) => warn!("Failed to subscribe to Redis channel {CHANNEL}: {err}"),
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    /// Measures the bytes of the messages of every room now and then, the other replicas
+    /// publish too
+    pub async fn measure_backlog(self: Arc<Self>) {
+        loop {
+            match self.measure().await {
+                Ok(bytes) => {
+                    debug!("Rooms in Redis hold {bytes} bytes");
+                    self.backlog.store(bytes, Ordering::SeqCst);
+                }
+                Err(err) => warn!("Failed to measure the backlog of the rooms: {err}"),
+            }
+
+            tokio::time::sleep(BACKLOG_INTERVAL).await;
+        }
+    }
+
+    async fn measure(&self) -> Result<i64> {
+        let mut connection = self.client.connection();
+
+        let room_ids: Vec<String> = cmd("SMEMBERS")
+            .arg(ROOMS_KEY)
+            .query_async(&mut connection)
+            .await?;
+
+        let mut pipeline = redis::pipe();
+        for room_id in &room_ids {
+            pipeline.get(key(room_id, "bytes"));
+        }
+
+        let bytes: Vec<Option<i64>> = pipeline.query_async(&mut connection).await?;
+
+        Ok(bytes.into_iter().flatten().sum())
+    }
+
+    /// Bytes of the messages of every room, as last measured
+    pub fn backlog(&self) -> usize {
+        usize::try_from(self.backlog.load(Ordering::SeqCst)).unwrap_or(0)
+    }
+
+    fn wake(&self, room_id: &str) {
+        let rooms = self.rooms.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(waiters) = rooms.get(room_id) {
+            waiters.notify.notify_waiters();
+        }
+    }
+
+    /// Ends the local subscriptions of a closed room
+    fn end_subscriptions(&self, room_id: &str) {
+        let waiters = self
+            .rooms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(room_id);
+
+        if let Some(waiters) = waiters {
+            waiters.closed.store(true, Ordering::SeqCst);
+            waiters.notify.notify_waiters();
+        }
+    }
+
+    fn wake_all(&self) {
+        for waiters in self
+            .rooms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+        {
+            waiters.notify.notify_waiters();
+        }
+    }
+
+    pub async fn issue_unique_idx(&self, room_id: &str, epoch: u64) -> Result<u16> {
+        let key = key(room_id, "indexes");
+        let mut connection = self.client.connection();
+
+        let next: i64 = cmd("HINCRBY")
+            .arg(&key)
+            .arg(epoch)
+            .arg(1)
+            .query_async(&mut connection)
+            .await?;
+
+        cmd("EXPIRE")
+            .arg(&key)
+            .arg(self.room_ttl)
+            .exec_async(&mut connection)
+            .await?;
+
+        if next == 1 {
+            self.register(room_id).await?;
+        }
+
+        // Counted on past the last index like the local rooms, which never issue u16::MAX
+        u16::try_from(next - 1)
+            .ok()
+            .filter(|&idx| idx < u16::MAX)
+            .ok_or_else(|| IndexesExhausted(epoch).into())
+    }
+
+    pub async fn publish_envelope(
+        &self,
+        room_id: &str,
+        header: &EnvelopeHeader,
+        message: Message,
+        retained: usize,
+    ) -> Result<BroadcastAck> {
+        // The script stores the sequence after it as the next one
+        if header.sequence == u64::MAX {
+            return Err(SequenceOverflow(header.sequence).into());
+        }
+
+        let sequences = key(room_id, "sequences");
+        let published = key(room_id, "published");
+        let messages = key(room_id, "messages");
+        let next = key(room_id, "next");
+        let bytes = key(room_id, "bytes");
+        let sender = format!("{}:{}", header.sender, header.epoch);
+        let envelope = format!("{sender}:{}", header.sequence);
+
+        let (duplicate, message_id, bytes): (i64, i64, i64) = PUBLISH_SCRIPT
+            .key(sequences)
+            .key(published)
+            .key(messages)
+            .key(next)
+            .key(bytes)
+            .arg(sender)
+            .arg(envelope)
+            .arg(header.sequence)
+            .arg(&message.data)
+            .arg(self.room_ttl)
+            .arg(room_id)
+            .arg(retained)
+            .arg(message.format.as_str())
+            .arg(ENTRY_BYTES)
+            .invoke_async(&mut self.client.connection())
+            .await?;
+
+        self.backlog.fetch_add(bytes, Ordering::SeqCst);
+
+        let duplicate = duplicate == 1;
+        let message_id = u64::try_from(message_id).ok();
+
+        if message_id == Some(0) && !duplicate {
+            self.register(room_id).await?;
+        }
+
+        debug!("Published to room '{room_id}' as event {message_id:?}, duplicate: {duplicate}");
+
+        Ok(BroadcastAck {
+            message_id,
+            duplicate,
+        })
+    }
+
+    /// Subscription of the room, `None` when it has `max_subscribers` on this replica
+    pub fn subscribe(
+        self: Arc<Self>,
+        room_id: &str,
+        next_event: u64,
+        max_subscribers: u16,
+    ) -> Option<Subscription> {
+        let waiters = {
+            let mut rooms = self.rooms.lock().unwrap_or_else(|e| e.into_inner());
+            let waiters = rooms.entry(room_id.to_string()).or_default();
+
+            if waiters.subscribers.load(Ordering::SeqCst) >= usize::from(max_subscribers) {
+                return None;
+            }
+
+            waiters.subscribers.fetch_add(1, Ordering::SeqCst);
+            waiters.clone()
+        };
+
+        Some(Subscription {
+            waiters,
+            cluster: self,
+            room_id: room_id.to_string(),
+            next_event,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Messages of the room from event `from` on, the oldest retained ones when it was
+    /// trimmed
+    async fn read(&self, room_id: &str, from: u64) -> Result<Vec<(u64, Message)>> {
+        let start = format!("0-{}", u128::from(from) + 1);
+
+        let reply: StreamRangeReply = cmd("XRANGE")
+            .arg(key(room_id, "messages"))
+            .arg(start)
+            .arg("+")
+            .arg("COUNT")
+            .arg(READ_BATCH)
+            .query_async(&mut self.client.connection())
+            .await?;
+
+        reply.ids.iter().map(entry).collect()
+    }
+
+    /// Lists the room, from its first index or message on
+    async fn register(&self, room_id: &str) -> Result<()> {
+        cmd("SADD")
+            .arg(ROOMS_KEY)
+            .arg(room_id)
+            .exec_async(&mut self.client.connection())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Rooms with any state in Redis, the subscribers are the ones of this replica
+    pub async fn room_statuses(&self) -> Result<Vec<RoomStatus>> {
+        let mut room_ids: Vec<String> = cmd("SMEMBERS")
+            .arg(ROOMS_KEY)
+            .query_async(&mut self.client.connection())
+            .await?;
+        room_ids.sort();
+
+        let mut statuses = Vec::with_capacity(room_ids.len());
+        for room_id in room_ids {
+            match self.room_status(&room_id).await? {
+                Some(status) => statuses.push(status),
+                None => {
+                    cmd("SREM")
+                        .arg(ROOMS_KEY)
+                        .arg(&room_id)
+                        .exec_async(&mut self.client.connection())
+                        .await?
+                }
+            }
+        }
+
+        Ok(statuses)
+    }
+
+    pub async fn room_status(&self, room_id: &str) -> Result<Option<RoomStatus>> {
+        let mut connection = self.client.connection();
+        let keys = ROOM_KEYS.map(|name| key(room_id, name));
+
+        let exists: i64 = cmd("EXISTS")
+            .arg(&keys)
+            .query_async(&mut connection)
+            .await?;
+
+        if exists == 0 {
+            return Ok(None);
+        }
+
+        let messages = key(room_id, "messages");
+        let count: u64 = cmd("XLEN")
+            .arg(&messages)
+            .query_async(&mut connection)
+            .await?;
+
+        // Every message sets the TTL of the stream again
+        let ttl: i64 = cmd("TTL")
+            .arg(&messages)
+            .query_async(&mut connection)
+            .await?;
+
+        let bytes: Option<usize> = cmd("GET")
+            .arg(key(room_id, "bytes"))
+            .query_async(&mut connection)
+            .await?;
+
+        let subscribers = self
+            .rooms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(room_id)
+            .map_or(0, |waiters| waiters.subscribers.load(Ordering::SeqCst));
+
+        Ok(Some(RoomStatus {
+            room_id: room_id.to_string(),
+            subscribers,
+            messages: usize::try_from(count)?,
+            next_event: self.end(room_id).await?,
+            bytes: Some(bytes.unwrap_or(0)),
+            age_secs: None,
+            idle_secs: u64::try_from(ttl)
+                .ok()
+                .map(|ttl| self.room_ttl.saturating_sub(ttl)),
+        }))
+    }
+
+    /// Deletes the state of the room and ends its subscriptions on every replica
+    pub async fn close_room(&self, room_id: &str) -> Result<()> {
+        let mut connection = self.client.connection();
+        let keys = ROOM_KEYS.map(|name| key(room_id, name));
+
+        cmd("DEL").arg(&keys).exec_async(&mut connection).await?;
+        cmd("SREM")
+            .arg(ROOMS_KEY)
+            .arg(room_id)
+            .exec_async(&mut connection)
+            .await?;
+        cmd("PUBLISH")
+            .arg(CLOSED_CHANNEL)
+            .arg(room_id)
+            .exec_async(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Event id of the next message published to the room
+    async fn end(&self, room_id: &str) -> Result<u64> {
+        let next: Option<u64> = cmd("GET")
+            .arg(key(room_id, "next"))
+            .query_async(&mut self.client.connection())
+            .await?;
+
+        Ok(next.unwrap_or(0))
+    }
+}
+
+/// Event id and message of a stream entry
+fn entry(entry: &StreamId) -> Result<(u64, Message)> {
+    let malformed = || anyhow!("Malformed room message {}", entry.id);
+
+    let sequence = entry
+        .id
+        .strip_prefix("0-")
+        .and_then(|sequence| sequence.parse::<u64>().ok())
+        .and_then(|sequence| sequence.checked_sub(1))
+        .ok_or_else(malformed)?;
+
+    let message = Message {
+        format: Format::parse(entry.get::<String>("format").as_deref()),
+        data: entry.get("message").ok_or_else(malformed)?,
+    };
+
+    Ok((sequence, message))
+}
+
+pub struct Subscription {
+    cluster: Arc<Cluster>,
+    waiters: Arc<Waiters>,
+    room_id: String,
+    next_event: u64,
+    pending: VecDeque<(u64, Message)>,
+}
+
+impl Subscription {
+    pub async fn next(&mut self) -> Result<Event> {
+        loop {
+            if self.waiters.closed.load(Ordering::SeqCst) {
+                return Ok(Event::Closed);
+            }
+
+            if let Some((event_id, message)) = self.pending.pop_front() {
+                // Events before the oldest retained one were trimmed
+                if event_id != self.next_event {
+                    self.pending.push_front((event_id, message));
+                    return Ok(self.resync(event_id));
+                }
+
+                self.next_event = event_id + 1;
+                debug!("Delivering event {} to subscriber", event_id);
+                return Ok(Event::Message(event_id, message));
+            }
+
+            // Registered before reading, so a message published meanwhile wakes it
+            let waiters = self.waiters.clone();
+            let notification = waiters.notify.notified();
+            tokio::pin!(notification);
+            notification.as_mut().enable();
+
+            // Closed before it was registered
+            if self.waiters.closed.load(Ordering::SeqCst) {
+                return Ok(Event::Closed);
+            }
+
+            self.pending = self
+                .cluster
+                .read(&self.room_id, self.next_event)
+                .await?
+                .into();
+
+            if self.pending.is_empty() {
+                // Events after the last one belong to an expired room of the same id
+                let end = self.cluster.end(&self.room_id).await?;
+                if self.next_event > end {
+                    return Ok(self.resync(end));
+                }
+
+                let _ = tokio::time::timeout(POLL_INTERVAL, notification).await;
+            }
+        }
+    }
+
+    fn resync(&mut self, event_id: u64) -> Event {
+        warn!(
+            "Event {} of room '{}' is out of its history, resyncing from {}",
+            self.next_event, self.room_id, event_id
+        );
+        self.next_event = event_id;
+
+        Event::Resync(event_id)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut rooms = self.cluster.rooms.lock().unwrap_or_else(|e| e.into_inner());
+
+        // No one else waits on the room. Closed rooms were removed already, possibly replaced
+        // by a room of the same id
+        if self.waiters.subscribers.fetch_sub(1, Ordering::SeqCst) == 1
+            && rooms
+                .get(&self.room_id)
+                .is_some_and(|waiters| Arc::ptr_eq(waiters, &self.waiters))
+        {
+            rooms.remove(&self.room_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::cluster_routing::Slot;
+    use redis::{FromRedisValue, Value};
+
+    fn bulk(data: &str) -> Value {
+        Value::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn xrange(entries: &[(&str, &[(&str, &str)])]) -> Vec<Result<(u64, Message)>> {
+        let reply = Value::Array(
+            entries
+                .iter()
+                .map(|(id, fields)| {
+                    Value::Array(vec![
+                        bulk(id),
+                        Value::Array(
+                            fields
+                                .iter()
+                                .flat_map(|(field, value)| [bulk(field), bulk(value)])
+                                .collect(),
+                        ),
+                    ])
+                })
+                .collect(),
+        );
+
+        StreamRangeReply::from_redis_value(reply)
+            .unwrap()
+            .ids
+            .iter()
+            .map(entry)
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_are_read_with_their_event_ids() {
+        let entries = xrange(&[
+            ("0-1", &[("message", "{}"), ("format", "json")]),
+            ("0-6", &[("message", "CgE="), ("format", "protobuf")]),
+            // Stored before there were formats
+            ("0-7", &[("message", "[]")]),
+        ]);
+
+        let entries = entries
+            .into_iter()
+            .map(|entry| {
+                let (id, message) = entry.unwrap();
+                (id, message.format, message.data)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            [
+                (0, Format::Json, "{}".to_string()),
+                (5, Format::Protobuf, "CgE=".to_string()),
+                (6, Format::Json, "[]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_entries_are_refused() {
+        let entries = xrange(&[
+            ("0-0", &[("message", "{}")]),
+            ("1700000000000-0", &[("message", "{}")]),
+            ("0-3", &[("format", "json")]),
+        ]);
+
+        assert!(entries.iter().all(Result::is_err));
+    }
+
+    #[test]
+    fn test_room_keys_share_a_hash_slot() {
+        let slots = ROOM_KEYS.map(|name| Slot::for_key(key("room_1", name)));
+
+        assert!(slots.iter().all(|&slot| slot == slots[0]));
+    }
+}
@@ -0,0 +1,192 @@
+//! Compression of the messages exchanged with the relay: broadcast bodies are decoded from
+//! their `Content-Encoding` within the payload limits, and SSE streams are encoded with the
+//! zstd or gzip encoding the subscriber accepts, flushed after every event so compression
+//! never holds one back
+
+use std::io::{self, Write};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::Decompress;
+use actix_web::error::PayloadError;
+use actix_web::http::header::{
+    self, AcceptEncoding, CacheControl, CacheDirective, ContentEncoding, Encoding, Header,
+};
+use actix_web::web::{Bytes, BytesMut, Payload};
+use actix_web::{HttpRequest, HttpResponse, mime};
+use flate2::write::GzEncoder;
+use futures_util::StreamExt;
+
+/// Encodings of the SSE streams, most preferred first when the subscriber ranks them alike
+const SUPPORTED: [Encoding; 3] = [Encoding::zstd(), Encoding::gzip(), Encoding::identity()];
+
+/// Reasons a broadcast body is refused
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body sent is larger than the limit, in bytes
+    PayloadTooLarge(usize),
+    /// The decoded message is larger than the limit, in bytes
+    MessageTooLarge(usize),
+    /// The body couldn't be read or decoded
+    Malformed(PayloadError),
+}
+
+/// Broadcast body, of at most `max_payload` bytes as sent and `max_message`
+/// bytes once decoded
+pub async fn read_message(
+    req: &HttpRequest,
+    payload: Payload,
+    max_payload: usize,
+    max_message: usize,
+) -> Result<Bytes, BodyError> {
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+
+    if declared.is_some_and(|length| length > max_payload) {
+        return Err(BodyError::PayloadTooLarge(max_payload));
+    }
+
+    let mut received = 0;
+    let payload = payload.map(move |chunk| {
+        let chunk = chunk?;
+        received += chunk.len();
+
+        match received > max_payload {
+            true => Err(PayloadError::Overflow),
+            false => Ok(chunk),
+        }
+    });
+
+    let mut body = Decompress::from_headers(payload, req.headers());
+    let mut message = BytesMut::new();
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|err| match err {
+            PayloadError::Overflow => BodyError::PayloadTooLarge(max_payload),
+            err => BodyError::Malformed(err),
+        })?;
+
+        if message.len() + chunk.len() > max_message {
+            return Err(BodyError::MessageTooLarge(max_message));
+        }
+
+        message.extend_from_slice(&chunk);
+    }
+
+    Ok(message.freeze())
+}
+
+/// Response of an SSE stream, encoded with the most preferred encoding of the subscriber
+pub fn event_stream<B>(req: &HttpRequest, events: B) -> HttpResponse
+where
+    B: MessageBody + 'static,
+{
+    let encoding = AcceptEncoding::parse(req)
+        .ok()
+        .and_then(|accepted| accepted.negotiate(SUPPORTED.iter()));
+
+    let encoder = match encoding {
+        Some(Encoding::Known(ContentEncoding::Zstd)) => StreamEncoder::zstd(),
+        Some(Encoding::Known(ContentEncoding::Gzip)) => Some(StreamEncoder::gzip()),
+        _ => None,
+    };
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(mime::TEXT_EVENT_STREAM)
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .insert_header((header::VARY, "accept-encoding"));
+
+    let Some(encoder) = encoder else {
+        return response
+            .insert_header(ContentEncoding::Identity)
+            .body(BoxBody::new(events));
+    };
+
+    response
+        .insert_header(encoder.encoding())
+        .streaming(encode(events, encoder))
+}
+
+/// Chunks of `body`, compressed and flushed one by one
+fn encode<B>(
+    body: B,
+    mut encoder: StreamEncoder,
+) -> impl futures_util::Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    B: MessageBody + 'static,
+{
+    async_stream::stream! {
+        let mut body = Box::pin(body);
+
+        while let Some(chunk) = futures_util::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await {
+            let Ok(chunk) = chunk else {
+                break;
+            };
+
+            match encoder.encode(&chunk) {
+                Ok(encoded) => yield Ok(encoded),
+                Err(err) => {
+                    yield Err(err.into());
+                    return;
+                }
+            }
+        }
+
+        yield encoder.finish().map_err(Into::into);
+    }
+}
+
+enum StreamEncoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl StreamEncoder {
+    fn gzip() -> Self {
+        StreamEncoder::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::default()))
+    }
+
+    /// `None` when the zstd context can't be allocated, the stream is sent unencoded then
+    fn zstd() -> Option<Self> {
+        zstd::stream::write::Encoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)
+            .ok()
+            .map(StreamEncoder::Zstd)
+    }
+
+    fn encoding(&self) -> ContentEncoding {
+        match self {
+            StreamEncoder::Gzip(_) => ContentEncoding::Gzip,
+            StreamEncoder::Zstd(_) => ContentEncoding::Zstd,
+        }
+    }
+
+    /// `chunk` compressed and flushed, so the subscriber decodes it whole
+    fn encode(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        let buffer = match self {
+            StreamEncoder::Gzip(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            StreamEncoder::Zstd(encoder) => {
+                encoder.write_all(chunk)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+
+        Ok(Bytes::from(std::mem::take(buffer)))
+    }
+
+    fn finish(self) -> io::Result<Bytes> {
+        let buffer = match self {
+            StreamEncoder::Gzip(encoder) => encoder.finish()?,
+            StreamEncoder::Zstd(encoder) => encoder.finish()?,
+        };
+
+        Ok(Bytes::from(buffer))
+    }
+}
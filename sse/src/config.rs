@@ -1,6 +1,7 @@
+use anyhow::Result;
 use log::{debug, error};
 use serde::Deserialize;
-use std::env;
+use settings::ConfigSource;
 use std::fmt;
 
 #[derive(Debug)]
@@ -27,15 +28,76 @@ pub struct AppConfig {
 pub struct SSEConfig {
     pub host: String,
     pub port: u16,
+    /// Redis the rooms are kept in so several replicas serve them, in memory when unset
+    pub redis: Option<RedisConfig>,
+    pub limits: LimitsConfig,
+    /// Key required in the `X-Admin-Key` header, admin endpoints are disabled when unset
+    pub admin_key: Option<String>,
+    /// Secret shared with the app, which signs the room token of every execution with it.
+    /// Rooms are open to anyone reaching the relay when unset
+    pub room_token_secret: Option<Vec<u8>>,
+}
+
+/// Bounds of the subscribers and messages the relay holds, requests beyond them are
+/// answered with `503` so participants retry them later
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsConfig {
+    /// Open subscriptions of the instance
+    pub max_subscribers: usize,
+    /// Open subscriptions of a room on the instance
+    pub max_room_subscribers: u16,
+    /// Messages a room retains, older ones are dropped and subscribers resuming before them
+    /// get a `resync` event
+    pub room_retained_messages: usize,
+    /// Bytes of the messages kept, with their sequence and event id entries. In memory,
+    /// freed by dropping the least recently active rooms without subscribers. In Redis,
+    /// rooms expire instead and the bytes of every room are measured now and then
+    pub max_backlog_bytes: usize,
+    /// Bytes of a request body as sent, compressed or not
+    pub max_payload_bytes: usize,
+    /// Bytes of a broadcast message once its body is decoded
+    pub max_message_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_subscribers: 10000,
+            max_room_subscribers: 32,
+            room_retained_messages: 10000,
+            max_backlog_bytes: 1024 * 1024 * 1024,
+            max_payload_bytes: 100 * 1024 * 1024,
+            max_message_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedisConfig {
+    /// `redis://` or, over TLS, `rediss://[[username]:password@]host[:port][/database]`.
+    /// Several with `cluster`, the nodes the cluster is discovered from
+    pub urls: Vec<String>,
+    /// The URLs are nodes of a Redis Cluster
+    pub cluster: bool,
+    /// Seconds the state of a room is kept after its last message
+    pub room_ttl: u64,
+    /// Seconds to connect or to get a reply before the request fails
+    pub timeout: u64,
 }
 
 impl AppConfig {
-    pub fn from_env() -> Result<Self, ConfigError> {
+    /// Environment variables take precedence over the optional `APP_CONFIG` file
+    pub fn load() -> Result<Self> {
         debug!("Loading SSE configuration from environment variables");
 
-        let sse_host = env::var("SSE_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-        let sse_port = env::var("SSE_PORT")
-            .unwrap_or_else(|_| "8080".to_string())
+        let source = ConfigSource::load()?;
+
+        let sse_host = source
+            .var("SSE_HOST")
+            .unwrap_or_else(|| "127.0.0.1".to_string());
+        let sse_port = source
+            .var("SSE_PORT")
+            .unwrap_or_else(|| "8080".to_string())
             .parse()
             .map_err(|_| {
                 let err =
@@ -44,13 +106,89 @@ impl AppConfig {
                 err
             })?;
 
+        let redis = match source.var("SSE_REDIS_URL").filter(|url| !url.is_empty()) {
+            Some(urls) => Some(RedisConfig {
+                urls: urls.split(',').map(|url| url.trim().to_string()).collect(),
+                cluster: source.var("SSE_REDIS_CLUSTER").is_some_and(|v| v == "true"),
+                timeout: limit(&source, "SSE_REDIS_TIMEOUT", 5)?,
+                room_ttl: source
+                    .var("SSE_REDIS_ROOM_TTL")
+                    .unwrap_or_else(|| "86400".to_string())
+                    .parse()
+                    .map_err(|_| {
+                        let err = ConfigError::InvalidEnvVar(
+                            "Expected SSE_REDIS_ROOM_TTL to be a number of seconds".to_string(),
+                        );
+                        error!("Invalid SSE_REDIS_ROOM_TTL configuration: {}", err);
+                        err
+                    })?,
+            }),
+            None => None,
+        };
+
         let config = AppConfig {
             sse: SSEConfig {
                 host: sse_host,
                 port: sse_port,
+                redis,
+                limits: load_limits(&source)?,
+                admin_key: source.var("SSE_ADMIN_KEY").filter(|key| !key.is_empty()),
+                room_token_secret: load_room_token_secret(&source)?,
             },
         };
 
         Ok(config)
     }
 }
+
+fn load_room_token_secret(source: &ConfigSource) -> Result<Option<Vec<u8>>> {
+    let Some(secret) = source
+        .var("SSE_ROOM_TOKEN_SECRET")
+        .filter(|secret| !secret.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    match hex::decode(&secret) {
+        Ok(secret) if secret.len() >= 32 => Ok(Some(secret)),
+        _ => {
+            let err = ConfigError::InvalidEnvVar(
+                "Expected SSE_ROOM_TOKEN_SECRET to be at least 32 hex bytes".to_string(),
+            );
+            error!("Invalid SSE_ROOM_TOKEN_SECRET configuration: {}", err);
+            Err(err.into())
+        }
+    }
+}
+
+fn load_limits(source: &ConfigSource) -> Result<LimitsConfig> {
+    let defaults = LimitsConfig::default();
+
+    Ok(LimitsConfig {
+        max_subscribers: limit(source, "SSE_MAX_SUBSCRIBERS", defaults.max_subscribers)?,
+        max_room_subscribers: limit(
+            source,
+            "SSE_MAX_ROOM_SUBSCRIBERS",
+            defaults.max_room_subscribers,
+        )?,
+        room_retained_messages: limit(
+            source,
+            "SSE_ROOM_RETAINED_MESSAGES",
+            defaults.room_retained_messages,
+        )?,
+        max_backlog_bytes: limit(source, "SSE_MAX_BACKLOG_BYTES", defaults.max_backlog_bytes)?,
+        max_payload_bytes: limit(source, "SSE_MAX_PAYLOAD_BYTES", defaults.max_payload_bytes)?,
+        max_message_bytes: limit(source, "SSE_MAX_MESSAGE_BYTES", defaults.max_message_bytes)?,
+    })
+}
+
+fn limit<T: std::str::FromStr>(source: &ConfigSource, name: &str, default: T) -> Result<T> {
+    match source.var(name) {
+        Some(value) => value.parse().map_err(|_| {
+            let err = ConfigError::InvalidEnvVar(format!("Expected {name} to be a number"));
+            error!("Invalid {} configuration: {}", name, err);
+            err.into()
+        }),
+        None => Ok(default),
+    }
+}
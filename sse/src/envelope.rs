@@ -0,0 +1,156 @@
+//! Envelopes of the participant messages, either JSON or the protobuf `relay.Envelope`
+//! sent as `application/x-protobuf`. The relay reads their routing fields and delivers them
+//! as they were sent, protobuf envelopes base64-encoded as SSE data is text
+
+use actix_web::HttpRequest;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use prost::Message as _;
+use serde::{Deserialize, de::IgnoredAny};
+
+use proto::relay::Envelope;
+
+const ENVELOPE_VERSION: u32 = 1;
+
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Routing fields of an envelope, the body stays opaque to the relay
+#[derive(Debug)]
+pub struct EnvelopeHeader {
+    pub sender: u16,
+    pub receiver: Option<u16>,
+    pub epoch: u64,
+    pub sequence: u64,
+}
+
+#[derive(Deserialize)]
+struct JsonEnvelope {
+    version: u32,
+    sender: u16,
+    receiver: Option<u16>,
+    epoch: u64,
+    sequence: u64,
+    #[allow(dead_code)]
+    body: IgnoredAny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Protobuf,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Protobuf => "protobuf",
+        }
+    }
+
+    /// Format of a stored message, messages stored before there were formats are JSON
+    pub fn parse(format: Option<&str>) -> Self {
+        match format {
+            Some("protobuf") => Format::Protobuf,
+            _ => Format::Json,
+        }
+    }
+
+    /// Name of the SSE events delivering the messages
+    pub fn event(self) -> &'static str {
+        match self {
+            Format::Json => "new-message",
+            Format::Protobuf => "new-envelope",
+        }
+    }
+}
+
+/// Message of a room as its subscribers receive it
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub format: Format,
+    pub data: String,
+}
+
+impl Message {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+#[derive(Debug)]
+pub enum EnvelopeError {
+    UnsupportedVersion(u32),
+    Malformed(String),
+}
+
+/// Header and message of a broadcast body, protobuf when its content type says so
+pub fn parse(req: &HttpRequest, body: Bytes) -> Result<(EnvelopeHeader, Message), EnvelopeError> {
+    let protobuf = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with(PROTOBUF_CONTENT_TYPE));
+
+    match protobuf {
+        true => parse_protobuf(body),
+        false => parse_json(body),
+    }
+}
+
+fn parse_json(body: Bytes) -> Result<(EnvelopeHeader, Message), EnvelopeError> {
+    let data = String::from_utf8(body.to_vec())
+        .map_err(|err| EnvelopeError::Malformed(err.to_string()))?;
+
+    let envelope = serde_json::from_str::<JsonEnvelope>(&data)
+        .map_err(|err| EnvelopeError::Malformed(err.to_string()))?;
+
+    if envelope.version != ENVELOPE_VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(envelope.version));
+    }
+
+    let header = EnvelopeHeader {
+        sender: envelope.sender,
+        receiver: envelope.receiver,
+        epoch: envelope.epoch,
+        sequence: envelope.sequence,
+    };
+
+    Ok((
+        header,
+        Message {
+            format: Format::Json,
+            data,
+        },
+    ))
+}
+
+fn parse_protobuf(body: Bytes) -> Result<(EnvelopeHeader, Message), EnvelopeError> {
+    let envelope =
+        Envelope::decode(body.clone()).map_err(|err| EnvelopeError::Malformed(err.to_string()))?;
+
+    if envelope.version != ENVELOPE_VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(envelope.version));
+    }
+
+    let index = |index: u32| {
+        u16::try_from(index).map_err(|_| EnvelopeError::Malformed(format!("Invalid index {index}")))
+    };
+
+    let header = EnvelopeHeader {
+        sender: index(envelope.sender)?,
+        receiver: envelope.receiver.map(index).transpose()?,
+        epoch: envelope.epoch,
+        sequence: envelope.sequence,
+    };
+
+    Ok((
+        header,
+        Message {
+            format: Format::Protobuf,
+            data: BASE64.encode(&body),
+        },
+    ))
+}
@@ -0,0 +1,1277 @@
+mod admin;
+mod cluster;
+mod compression;
+pub mod config;
+mod envelope;
+mod redis;
+
+use std::collections::VecDeque;
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering},
+};
+use std::time::Instant;
+
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, http::header, web};
+use actix_web_lab::sse::{self, Sse};
+use futures_util::Stream;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+
+use crate::admin::RoomStatus;
+use crate::cluster::Cluster;
+use crate::compression::BodyError;
+use crate::config::{LimitsConfig, RedisConfig};
+use crate::envelope::{EnvelopeError, EnvelopeHeader, Message};
+
+static ROOM_TOKEN_HEADER: &str = "X-Room-Token";
+/// Bytes counted in the backlog for each message besides its data, for its history, sequence
+/// and event id entries
+const ENTRY_BYTES: usize = 128;
+// Seconds clients are asked to wait before retrying a request beyond the limits
+static RETRY_AFTER_SECS: u64 = 5;
+
+async fn subscribe(
+    db: web::Data<Db>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let room_id = path.into_inner();
+    let Some(next_event) = extract_next_event(&req) else {
+        warn!(
+            "Rejecting subscription to room '{}': invalid Last-Event-ID",
+            room_id
+        );
+        return Ok(HttpResponse::BadRequest().body("Invalid Last-Event-ID"));
+    };
+
+    info!(
+        "New subscription to room '{}' starting from event {}",
+        room_id, next_event
+    );
+
+    if !db.authorize(&room_id, extract_room_token(&req)) {
+        warn!(
+            "Rejecting subscription to room '{}': invalid room token",
+            room_id
+        );
+        return Ok(HttpResponse::Forbidden().body("Invalid room token"));
+    }
+
+    let Some(subscription) = db.subscribe(&room_id, next_event).await else {
+        return Ok(overloaded(&room_id, "Too many subscribers"));
+    };
+
+    debug!("Created subscription for room '{}'", room_id);
+
+    let stream = subscription_to_stream(subscription);
+
+    Ok(compression::event_stream(
+        &req,
+        Sse::from_stream(stream)
+            .with_retry_duration(std::time::Duration::from_secs(RETRY_AFTER_SECS)),
+    ))
+}
+
+/// Response of a request the shared room state couldn't serve, clients retry it
+fn unavailable(room_id: &str, err: anyhow::Error) -> HttpResponse {
+    error!("Room state of '{}' is unavailable: {}", room_id, err);
+
+    HttpResponse::ServiceUnavailable().body("Room state is unavailable")
+}
+
+/// Response of a broadcast whose body was refused
+fn refused(room_id: &str, err: BodyError) -> HttpResponse {
+    let (mut response, reason) = match err {
+        BodyError::PayloadTooLarge(limit) => (
+            HttpResponse::PayloadTooLarge(),
+            format!("Broadcast body exceeds the limit of {limit} bytes"),
+        ),
+        BodyError::MessageTooLarge(limit) => (
+            HttpResponse::PayloadTooLarge(),
+            format!("Message exceeds the limit of {limit} bytes once decoded"),
+        ),
+        BodyError::Malformed(err) => (
+            HttpResponse::BadRequest(),
+            format!("Malformed broadcast body: {err}"),
+        ),
+    };
+
+    warn!("Rejecting broadcast to room '{}': {}", room_id, reason);
+
+    response.body(reason)
+}
+
+/// Response of a request beyond the limits of the relay, clients retry it later
+fn overloaded(room_id: &str, reason: &'static str) -> HttpResponse {
+    warn!("Shedding request for room '{}': {}", room_id, reason);
+
+    HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS.to_string()))
+        .body(reason)
+}
+
+async fn health_check() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "UP",
+        "message": "Service is running"
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct IssueIdxQuery {
+    // Indexes restart from 0 for every execution sharing the room
+    epoch: Option<u64>,
+}
+
+async fn issue_idx(
+    db: web::Data<Db>,
+    path: web::Path<String>,
+    query: web::Query<IssueIdxQuery>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let room_id = path.into_inner();
+
+    if !db.authorize(&room_id, extract_room_token(&req)) {
+        warn!(
+            "Rejecting index issuance for room '{}': invalid room token",
+            room_id
+        );
+        return Ok(HttpResponse::Forbidden().body("Invalid room token"));
+    }
+
+    let epoch = query.epoch.unwrap_or_default();
+    let idx = match db.issue_unique_idx(&room_id, epoch).await {
+        Ok(idx) => idx,
+        Err(err) if err.is::<IndexesExhausted>() => {
+            warn!("Rejecting index issuance for room '{}': {}", room_id, err);
+            return Ok(HttpResponse::Conflict().body(err.to_string()));
+        }
+        Err(err) => return Ok(unavailable(&room_id, err)),
+    };
+
+    info!(
+        "Issued unique index {} for room '{}' (epoch {})",
+        idx, room_id, epoch
+    );
+
+    Ok(HttpResponse::Ok().json(IssuedUniqueIdx { unique_idx: idx }))
+}
+
+async fn broadcast(
+    db: web::Data<Db>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> ActixResult<HttpResponse> {
+    let room_id = path.into_inner();
+
+    let limits = &db.limits;
+    let body = match compression::read_message(
+        &req,
+        payload,
+        limits.max_payload_bytes,
+        limits.max_message_bytes,
+    )
+    .await
+    {
+        Ok(body) => body,
+        Err(err) => return Ok(refused(&room_id, err)),
+    };
+
+    let (header, message) = match envelope::parse(&req, body) {
+        Ok(envelope) => envelope,
+        Err(EnvelopeError::UnsupportedVersion(version)) => {
+            warn!(
+                "Rejecting message with unsupported envelope version {} for room '{}'",
+                version, room_id
+            );
+            return Ok(HttpResponse::BadRequest().body("Unsupported envelope version"));
+        }
+        Err(EnvelopeError::Malformed(err)) => {
+            warn!(
+                "Rejecting malformed envelope for room '{}': {}",
+                room_id, err
+            );
+            return Ok(HttpResponse::BadRequest().body("Malformed message envelope"));
+        }
+    };
+
+    if !db.authorize(&room_id, extract_room_token(&req)) {
+        warn!(
+            "Rejecting broadcast to room '{}': invalid room token",
+            room_id
+        );
+        return Ok(HttpResponse::Forbidden().body("Invalid room token"));
+    }
+
+    debug!(
+        "Broadcasting message {} from sender {} to {:?} in room '{}' (epoch {}), message length: {} bytes",
+        header.sequence,
+        header.sender,
+        header.receiver,
+        room_id,
+        header.epoch,
+        message.len()
+    );
+
+    let ack = match db.publish_envelope(&room_id, &header, message).await {
+        Ok(Some(ack)) => ack,
+        Ok(None) => return Ok(overloaded(&room_id, "Message backlog is full")),
+        Err(err) if err.is::<SequenceOverflow>() => {
+            warn!(
+                "Rejecting message {} from sender {} for room '{}': {}",
+                header.sequence, header.sender, room_id, err
+            );
+            return Ok(HttpResponse::BadRequest().body(err.to_string()));
+        }
+        Err(err) => return Ok(unavailable(&room_id, err)),
+    };
+
+    if ack.duplicate {
+        debug!(
+            "Ignoring duplicate message {} from sender {} in room '{}'",
+            header.sequence, header.sender, room_id
+        );
+    } else {
+        debug!("Message broadcast complete for room '{}'", room_id);
+    }
+
+    Ok(HttpResponse::Ok().json(ack))
+}
+
+/// Event the subscription starts from, the one after `Last-Event-ID` or the first one
+/// without it. `None` when the header isn't an event id of the relay
+fn extract_next_event(req: &HttpRequest) -> Option<u64> {
+    match req.headers().get("Last-Event-ID") {
+        Some(header) => header.to_str().ok()?.parse::<u64>().ok()?.checked_add(1),
+        None => Some(0),
+    }
+}
+
+fn extract_room_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(ROOM_TOKEN_HEADER)
+        .and_then(|header| header.to_str().ok())
+}
+
+fn subscription_to_stream(
+    mut subscription: Subscription,
+) -> impl Stream<Item = Result<sse::Event, actix_web::Error>> {
+    async_stream::stream! {
+        loop {
+            // Check if the client has disconnected by yielding a test event
+            // If the client is gone, this will cause the stream to be dropped
+            // The client reconnects from its last event when the room state is unavailable
+            let event = match subscription.next().await {
+                Ok(Event::Message(id, msg)) => sse::Event::Data(
+                    sse::Data::new(msg.data)
+                        .event(msg.format.event())
+                        .id(id.to_string())
+                ),
+                Ok(Event::Resync(next_event)) => sse::Event::Data(
+                    sse::Data::new(serde_json::json!({ "next_event": next_event }).to_string())
+                        .event("resync")
+                ),
+                Ok(Event::Closed) => {
+                    info!("Ending subscription of a closed room");
+                    break;
+                }
+                Err(err) => {
+                    error!("Subscription failed: {}", err);
+                    break;
+                }
+            };
+            yield Ok(event);
+        }
+    }
+}
+
+/// Rooms of the relay, shared by every worker
+pub struct Db {
+    rooms: RwLock<HashMap<String, Arc<Room>>>,
+    /// Rooms shared with the other replicas through Redis, used instead of the local ones
+    cluster: Option<Arc<Cluster>>,
+    limits: LimitsConfig,
+    /// Open subscriptions, of every room
+    subscribers: Arc<AtomicUsize>,
+    /// Bytes of the messages of the local rooms, released as rooms are dropped
+    backlog: Arc<AtomicUsize>,
+    /// Key of the admin API, disabled when unset
+    admin_key: Option<String>,
+    /// Secret the room tokens are signed with, rooms are open to anyone when unset
+    room_token_secret: Option<Vec<u8>>,
+}
+
+/// Sender, epoch and sequence of an envelope
+type EnvelopeKey = (u16, u64, u64);
+
+struct Room {
+    history: RwLock<History>,
+    // Next expected sequence per (sender, epoch), dropped with the last retained message of
+    // the sender
+    sequences: RwLock<HashMap<(u16, u64), u64>>,
+    // Event id of each retained envelope, returned to retries
+    published: RwLock<HashMap<EnvelopeKey, u64>>,
+    message_appeared: Notify,
+    subscribers: AtomicU16,
+    // Next index to issue per epoch
+    next_idx: Mutex<HashMap<u64, u16>>,
+    // Bytes of the messages, counted in the backlog of the relay until the room is dropped
+    size: AtomicUsize,
+    backlog: Arc<AtomicUsize>,
+    // Last message or subscription change, rooms are shed least recently active first
+    last_active: Mutex<Instant>,
+    created: Instant,
+    // Set when an operator closes the room, its subscriptions end
+    closed: AtomicBool,
+}
+
+impl Db {
+    pub fn empty() -> Self {
+        Self {
+            rooms: RwLock::new(HashMap::new()),
+            cluster: None,
+            limits: LimitsConfig::default(),
+            subscribers: Arc::new(AtomicUsize::new(0)),
+            backlog: Arc::new(AtomicUsize::new(0)),
+            admin_key: None,
+            room_token_secret: None,
+        }
+    }
+
+    /// Same rooms, bounded by `limits` instead of the defaults
+    pub fn limited(self, limits: LimitsConfig) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Same rooms, with the admin API enabled when `admin_key` is set
+    pub fn administered(self, admin_key: Option<String>) -> Self {
+        Self { admin_key, ..self }
+    }
+
+    /// Same rooms, each open only to requests with the token of its execution signed with
+    /// `room_token_secret`
+    pub fn authenticated(self, room_token_secret: Option<Vec<u8>>) -> Self {
+        Self {
+            room_token_secret,
+            ..self
+        }
+    }
+
+    /// Rooms kept in Redis, served alike by every replica sharing it. Listens for the
+    /// messages published by the replicas on the runtime
+    pub async fn clustered(config: &RedisConfig) -> anyhow::Result<Self> {
+        let cluster = Cluster::connect(config).await?;
+
+        tokio::spawn(cluster.clone().listen());
+        tokio::spawn(cluster.clone().measure_backlog());
+
+        Ok(Self {
+            cluster: Some(cluster),
+            ..Self::empty()
+        })
+    }
+
+    /// Checks the room token against the secret, the same on every replica so nothing is
+    /// bound to a room on first sight
+    fn authorize(&self, room_id: &str, token: Option<&str>) -> bool {
+        match (&self.room_token_secret, token) {
+            (None, _) => true,
+            (Some(secret), Some(token)) => tokens::room::verify(secret, room_id, token),
+            (Some(_), None) => false,
+        }
+    }
+
+    async fn issue_unique_idx(&self, room_id: &str, epoch: u64) -> anyhow::Result<u16> {
+        match &self.cluster {
+            Some(cluster) => cluster.issue_unique_idx(room_id, epoch).await,
+            None => Ok(self
+                .get_room_or_create_for_index(room_id)
+                .await
+                .issue_unique_idx(epoch)?),
+        }
+    }
+
+    /// Acknowledgement of the envelope, `None` when the relay holds as many messages as it
+    /// may
+    async fn publish_envelope(
+        &self,
+        room_id: &str,
+        header: &EnvelopeHeader,
+        message: Message,
+    ) -> anyhow::Result<Option<BroadcastAck>> {
+        let retained = self.limits.room_retained_messages;
+        let size = message.len() + ENTRY_BYTES;
+
+        if let Some(cluster) = &self.cluster {
+            // Rooms expire rather than being shed. The backlog is measured now and then, it
+            // may go over by what the other replicas publish meanwhile
+            if cluster.backlog() + size > self.limits.max_backlog_bytes {
+                return Ok(None);
+            }
+
+            return Ok(Some(
+                cluster
+                    .publish_envelope(room_id, header, message, retained)
+                    .await?,
+            ));
+        }
+
+        if !self.reserve_backlog(size) {
+            self.shed(room_id, size).await;
+
+            if !self.reserve_backlog(size) {
+                return Ok(None);
+            }
+        }
+
+        let ack = self
+            .get_room_or_create_for_index(room_id)
+            .await
+            .publish_envelope(header, message, retained)
+            .await;
+
+        // Only published messages stay in the backlog
+        if !ack.as_ref().is_ok_and(|ack| !ack.duplicate) {
+            self.backlog.fetch_sub(size, Ordering::SeqCst);
+        }
+
+        Ok(Some(ack?))
+    }
+
+    fn reserve_backlog(&self, size: usize) -> bool {
+        self.backlog
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(size)
+                    .filter(|&used| used <= self.limits.max_backlog_bytes)
+            })
+            .is_ok()
+    }
+
+    /// Drops the least recently active rooms without subscribers, other than `room_id`,
+    /// until `size` more bytes fit in the backlog
+    async fn shed(&self, room_id: &str, size: usize) {
+        let mut rooms = self.rooms.write().await;
+
+        let mut abandoned = rooms
+            .iter()
+            .filter(|(id, room)| {
+                id.as_str() != room_id
+                    && room.subscribers.load(Ordering::SeqCst) == 0
+                    && room.size.load(Ordering::SeqCst) > 0
+            })
+            .map(|(id, room)| (room.last_active(), id.clone()))
+            .collect::<Vec<_>>();
+        abandoned.sort();
+
+        let mut excess = (self.backlog.load(Ordering::SeqCst) + size)
+            .saturating_sub(self.limits.max_backlog_bytes);
+
+        for (_, id) in abandoned {
+            if excess == 0 {
+                break;
+            }
+
+            if let Some(room) = rooms.remove(&id) {
+                let freed = room.size.load(Ordering::SeqCst);
+                excess = excess.saturating_sub(freed);
+
+                info!(
+                    "Dropped abandoned room '{}' to free {} bytes of backlog",
+                    id, freed
+                );
+            }
+        }
+
+        if excess > 0 {
+            warn!(
+                "Backlog is full, {} bytes are held by rooms with subscribers",
+                self.backlog.load(Ordering::SeqCst)
+            );
+        }
+    }
+
+    /// Subscription of the room, `None` when the room or the relay has as many
+    /// subscribers as it may
+    async fn subscribe(&self, room_id: &str, next_event: u64) -> Option<Subscription> {
+        let slot = SubscriberSlot::acquire(&self.subscribers, self.limits.max_subscribers)?;
+        let max_room_subscribers = self.limits.max_room_subscribers;
+
+        let source = match &self.cluster {
+            Some(cluster) => Source::Cluster(cluster.clone().subscribe(
+                room_id,
+                next_event,
+                max_room_subscribers,
+            )?),
+            None => Source::Local(
+                self.get_room_or_create_for_index(room_id)
+                    .await
+                    .subscribe(next_event, max_room_subscribers)?,
+            ),
+        };
+
+        Some(Subscription {
+            source,
+            _slot: slot,
+        })
+    }
+
+    async fn room_statuses(&self) -> anyhow::Result<Vec<RoomStatus>> {
+        if let Some(cluster) = &self.cluster {
+            return cluster.room_statuses().await;
+        }
+
+        let rooms = self
+            .rooms
+            .read()
+            .await
+            .iter()
+            .map(|(id, room)| (id.clone(), room.clone()))
+            .collect::<Vec<_>>();
+
+        let mut statuses = Vec::with_capacity(rooms.len());
+        for (id, room) in rooms {
+            statuses.push(room.status(id).await);
+        }
+        statuses.sort_by(|a, b| a.room_id.cmp(&b.room_id));
+
+        Ok(statuses)
+    }
+
+    async fn room_status(&self, room_id: &str) -> anyhow::Result<Option<RoomStatus>> {
+        if let Some(cluster) = &self.cluster {
+            return cluster.room_status(room_id).await;
+        }
+
+        let room = self.rooms.read().await.get(room_id).cloned();
+
+        Ok(match room {
+            Some(room) => Some(room.status(room_id.to_string()).await),
+            None => None,
+        })
+    }
+
+    /// Drops the room, its indexes and messages, and ends its subscriptions
+    async fn close_room(&self, room_id: &str) -> anyhow::Result<()> {
+        if let Some(cluster) = &self.cluster {
+            return cluster.close_room(room_id).await;
+        }
+
+        if let Some(room) = self.rooms.write().await.remove(room_id) {
+            room.close().await;
+        }
+
+        Ok(())
+    }
+
+    async fn get_room_or_create_for_index(&self, room_id: &str) -> Arc<Room> {
+        let rooms = self.rooms.read().await;
+        if let Some(room) = rooms.get(room_id) {
+            debug!("Found existing room '{}'", room_id);
+            return room.clone();
+        }
+        drop(rooms);
+
+        let mut rooms = self.rooms.write().await;
+        match rooms.entry(room_id.to_owned()) {
+            Entry::Occupied(entry) => {
+                debug!("Room '{}' was created by another thread", room_id);
+                entry.get().clone()
+            }
+            Entry::Vacant(entry) => {
+                info!("Creating new room '{}'", room_id);
+                entry
+                    .insert(Arc::new(Room::empty(self.backlog.clone())))
+                    .clone()
+            }
+        }
+    }
+}
+
+impl Room {
+    pub fn empty(backlog: Arc<AtomicUsize>) -> Self {
+        Self {
+            history: RwLock::new(History::default()),
+            sequences: RwLock::new(HashMap::new()),
+            published: RwLock::new(HashMap::new()),
+            message_appeared: Notify::new(),
+            subscribers: AtomicU16::new(0),
+            next_idx: Mutex::new(HashMap::new()),
+            size: AtomicUsize::new(0),
+            backlog,
+            last_active: Mutex::new(Instant::now()),
+            created: Instant::now(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    async fn status(&self, room_id: String) -> RoomStatus {
+        let history = self.history.read().await;
+
+        RoomStatus {
+            room_id,
+            subscribers: usize::from(self.subscribers.load(Ordering::SeqCst)),
+            messages: history.messages.len(),
+            next_event: history.end(),
+            bytes: Some(self.size.load(Ordering::SeqCst)),
+            age_secs: Some(self.created.elapsed().as_secs()),
+            idle_secs: Some(self.last_active().elapsed().as_secs()),
+        }
+    }
+
+    /// Ends the subscriptions, under the history lock so none misses it between checking and
+    /// waiting for a message
+    async fn close(&self) {
+        let _history = self.history.write().await;
+        self.closed.store(true, Ordering::SeqCst);
+        self.message_appeared.notify_waiters();
+    }
+
+    fn last_active(&self) -> Instant {
+        *self.last_active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    /// Returns the event id of the message, the id subscribers receive it with, and the
+    /// envelopes of the oldest messages beyond the `retained` ones, which are dropped
+    async fn publish(
+        self: &Arc<Self>,
+        envelope: EnvelopeKey,
+        message: Message,
+        retained: usize,
+    ) -> (u64, Vec<EnvelopeKey>) {
+        let mut history = self.history.write().await;
+        let message_id = history.end();
+        self.size
+            .fetch_add(message.len() + ENTRY_BYTES, Ordering::SeqCst);
+        history.messages.push_back((envelope, message));
+
+        let mut dropped = Vec::new();
+        while history.messages.len() > retained {
+            let Some((envelope, message)) = history.messages.pop_front() else {
+                break;
+            };
+            history.first += 1;
+            dropped.push(envelope);
+
+            let size = message.len() + ENTRY_BYTES;
+            self.size.fetch_sub(size, Ordering::SeqCst);
+            self.backlog.fetch_sub(size, Ordering::SeqCst);
+        }
+
+        self.touch();
+        let subscriber_count = self.subscribers.load(Ordering::SeqCst);
+
+        debug!(
+            "Published message {} to {} subscribers",
+            message_id, subscriber_count
+        );
+
+        self.message_appeared.notify_waiters();
+
+        (message_id, dropped)
+    }
+
+    /// Publishes an envelope unless it already was, acknowledging it either way. The
+    /// sequences stay locked until the envelope is published so a concurrent retry gets
+    /// its event id
+    ///
+    /// Envelopes leave the sequences and event ids with their messages, so both stay within
+    /// the retained window. A retry of an envelope that left it is published again, and
+    /// dropped by the receivers that got it by its sequence
+    pub async fn publish_envelope(
+        self: &Arc<Self>,
+        header: &EnvelopeHeader,
+        message: Message,
+        retained: usize,
+    ) -> Result<BroadcastAck, SequenceOverflow> {
+        let mut sequences = self.sequences.write().await;
+        let key = (header.sender, header.epoch, header.sequence);
+
+        if !accept_sequence(&mut sequences, header)? {
+            return Ok(BroadcastAck {
+                message_id: self.published.read().await.get(&key).copied(),
+                duplicate: true,
+            });
+        }
+
+        let (message_id, dropped) = self.publish(key, message, retained).await;
+
+        let mut published = self.published.write().await;
+        published.insert(key, message_id);
+
+        for (sender, epoch, sequence) in dropped {
+            published.remove(&(sender, epoch, sequence));
+
+            // The sender published nothing since, its next sequence leaves with the message
+            if sequences.get(&(sender, epoch)) == Some(&(sequence + 1)) {
+                sequences.remove(&(sender, epoch));
+            }
+        }
+
+        Ok(BroadcastAck {
+            message_id: Some(message_id),
+            duplicate: false,
+        })
+    }
+
+    /// Subscription of the room, `None` when it has `max_subscribers` already
+    pub fn subscribe(
+        self: Arc<Self>,
+        next_event: u64,
+        max_subscribers: u16,
+    ) -> Option<RoomSubscription> {
+        let new_count = self
+            .subscribers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max_subscribers).then_some(count + 1)
+            })
+            .ok()?
+            + 1;
+        self.touch();
+
+        debug!(
+            "New subscription created, subscribers: {}, starting from event: {}",
+            new_count, next_event
+        );
+
+        Some(RoomSubscription {
+            room: self,
+            next_event,
+        })
+    }
+
+    pub fn issue_unique_idx(&self, epoch: u64) -> Result<u16, IndexesExhausted> {
+        let mut next_idx = self.next_idx.lock().unwrap_or_else(|e| e.into_inner());
+        let idx = next_idx.entry(epoch).or_insert(0);
+
+        let issued = *idx;
+        *idx = idx.checked_add(1).ok_or(IndexesExhausted(epoch))?;
+
+        Ok(issued)
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        self.backlog
+            .fetch_sub(self.size.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+}
+
+/// Subscription to a room of the relay, or of the cluster, counted in the subscribers of
+/// the relay while open
+struct Subscription {
+    source: Source,
+    _slot: SubscriberSlot,
+}
+
+enum Source {
+    Local(RoomSubscription),
+    Cluster(cluster::Subscription),
+}
+
+impl Subscription {
+    async fn next(&mut self) -> anyhow::Result<Event> {
+        match &mut self.source {
+            Source::Local(subscription) => Ok(subscription.next().await),
+            Source::Cluster(subscription) => subscription.next().await,
+        }
+    }
+}
+
+/// Delivery of a subscription
+enum Event {
+    /// Message of the room and its event id
+    Message(u64, Message),
+    /// The events the subscriber expected next are not in the history of the room, they
+    /// were dropped or belong to a room that expired. Events go on from the one given
+    Resync(u64),
+    /// An operator closed the room, the subscription ends
+    Closed,
+}
+
+/// Messages a room retains with their envelopes, and the event id of the oldest one
+#[derive(Default)]
+struct History {
+    first: u64,
+    messages: VecDeque<(EnvelopeKey, Message)>,
+}
+
+impl History {
+    /// Event id of the next message published
+    fn end(&self) -> u64 {
+        self.first + self.messages.len() as u64
+    }
+}
+
+/// One of the subscribers of the relay, released when dropped
+struct SubscriberSlot(Arc<AtomicUsize>);
+
+impl SubscriberSlot {
+    fn acquire(subscribers: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        subscribers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .ok()?;
+
+        Some(Self(subscribers.clone()))
+    }
+}
+
+impl Drop for SubscriberSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct RoomSubscription {
+    room: Arc<Room>,
+    next_event: u64,
+}
+
+impl RoomSubscription {
+    pub async fn next(&mut self) -> Event {
+        loop {
+            let history = self.room.history.read().await;
+
+            if self.room.closed.load(Ordering::SeqCst) {
+                return Event::Closed;
+            }
+
+            if !(history.first..=history.end()).contains(&self.next_event) {
+                let resumed = self.next_event.clamp(history.first, history.end());
+                warn!(
+                    "Event {} is out of the room history {}..{}, resyncing from {}",
+                    self.next_event,
+                    history.first,
+                    history.end(),
+                    resumed
+                );
+                self.next_event = resumed;
+                return Event::Resync(resumed);
+            }
+
+            if let Some((_, msg)) = history
+                .messages
+                .get((self.next_event - history.first) as usize)
+            {
+                let event_id = self.next_event;
+                self.next_event = event_id + 1;
+                debug!("Delivering event {} to subscriber", event_id);
+                return Event::Message(event_id, msg.clone());
+            }
+            debug!(
+                "No new messages, waiting for notification (current event: {})",
+                self.next_event
+            );
+            let notification = self.room.message_appeared.notified();
+            drop(history);
+            notification.await;
+        }
+    }
+}
+
+impl Drop for RoomSubscription {
+    fn drop(&mut self) {
+        let remaining = self.room.subscribers.fetch_sub(1, Ordering::SeqCst) - 1;
+        self.room.touch();
+        debug!("Subscription dropped, remaining subscribers: {}", remaining);
+
+        if remaining == 0 {
+            info!("Last subscriber left the room, room is now abandoned");
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct IssuedUniqueIdx {
+    unique_idx: u16,
+}
+
+/// Acknowledgement of a broadcast, `message_id` is the event id the envelope was published
+/// with. Retries of a published envelope are acknowledged as duplicates, with the id of the
+/// first publication unless it was skipped over by a later sequence or left the retained
+/// messages
+#[derive(Serialize, Deserialize, Debug)]
+struct BroadcastAck {
+    message_id: Option<u64>,
+    duplicate: bool,
+}
+
+/// Every index of the epoch was issued
+#[derive(Debug)]
+struct IndexesExhausted(u64);
+
+impl std::fmt::Display for IndexesExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No index is left to issue in epoch {}", self.0)
+    }
+}
+
+impl std::error::Error for IndexesExhausted {}
+
+/// Sequence of an envelope leaving no sequence for the next one of its sender
+#[derive(Debug)]
+struct SequenceOverflow(u64);
+
+impl std::fmt::Display for SequenceOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sequence {} is out of range", self.0)
+    }
+}
+
+impl std::error::Error for SequenceOverflow {}
+
+/// Returns false when the envelope was already published, e.g. on a retried broadcast
+fn accept_sequence(
+    sequences: &mut HashMap<(u16, u64), u64>,
+    header: &EnvelopeHeader,
+) -> Result<bool, SequenceOverflow> {
+    let next = sequences.entry((header.sender, header.epoch)).or_insert(0);
+
+    if header.sequence < *next {
+        return Ok(false);
+    }
+
+    *next = header
+        .sequence
+        .checked_add(1)
+        .ok_or(SequenceOverflow(header.sequence))?;
+    Ok(true)
+}
+
+/// Routes of the relay, serving the rooms of the `Db` in the app data
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/health", web::get().to(health_check))
+        .route("/rooms/{room_id}/subscribe", web::get().to(subscribe))
+        .route(
+            "/rooms/{room_id}/issue_unique_idx",
+            web::post().to(issue_idx),
+        )
+        .route("/rooms/{room_id}/broadcast", web::post().to(broadcast))
+        .configure(admin::configure);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::App;
+    use actix_web::body::MessageBody;
+    use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body, read_body_json};
+    use std::time::Duration;
+
+    /// Relay of the tests, with `limits` and without authentication
+    fn relay(
+        limits: LimitsConfig,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(web::Data::new(Db::empty().limited(limits)))
+            .configure(configure)
+    }
+
+    fn envelope(sender: u16, sequence: u64) -> String {
+        serde_json::json!({
+            "version": 1,
+            "sender": sender,
+            "receiver": null,
+            "epoch": 0,
+            "sequence": sequence,
+            "body": { "round": 1 }
+        })
+        .to_string()
+    }
+
+    fn broadcast_request(room_id: &str, envelope: String) -> TestRequest {
+        TestRequest::post()
+            .uri(&format!("/rooms/{room_id}/broadcast"))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(envelope)
+    }
+
+    fn subscribe_request(room_id: &str, last_event_id: Option<u64>) -> TestRequest {
+        let mut req = TestRequest::get().uri(&format!("/rooms/{room_id}/subscribe"));
+        if let Some(id) = last_event_id {
+            req = req.insert_header(("Last-Event-ID", id.to_string()));
+        }
+        req
+    }
+
+    /// Text of the event stream up to the first chunk containing `needle`, the stream
+    /// doesn't end while the room is open
+    async fn read_until(response: ServiceResponse, needle: &str) -> String {
+        let mut body = Box::pin(response.into_body());
+        let mut text = String::new();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !text.contains(needle) {
+                let chunk = std::future::poll_fn(|cx| body.as_mut().poll_next(cx))
+                    .await
+                    .expect("Event stream ended")
+                    .unwrap();
+                text.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("No '{needle}' in the event stream: {text}"));
+
+        text
+    }
+
+    fn header(sequence: u64) -> EnvelopeHeader {
+        sender_header(1, sequence)
+    }
+
+    fn sender_header(sender: u16, sequence: u64) -> EnvelopeHeader {
+        EnvelopeHeader {
+            sender,
+            receiver: None,
+            epoch: 0,
+            sequence,
+        }
+    }
+
+    fn message(data: &str) -> Message {
+        Message {
+            format: envelope::Format::Json,
+            data: data.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sequences_and_event_ids_leave_with_the_retained_messages() {
+        let backlog = Arc::new(AtomicUsize::new(0));
+        let room = Arc::new(Room::empty(backlog.clone()));
+
+        for sequence in 0..3 {
+            room.publish_envelope(&sender_header(1, sequence), message("a"), 2)
+                .await
+                .unwrap();
+        }
+        for sequence in 0..3 {
+            room.publish_envelope(&sender_header(2, sequence), message("b"), 2)
+                .await
+                .unwrap();
+        }
+
+        // Sender 1 has no message left in the room, sender 2 its last two
+        assert_eq!(*room.sequences.read().await, HashMap::from([((2, 0), 3)]));
+        assert_eq!(
+            *room.published.read().await,
+            HashMap::from([((2, 0, 1), 4), ((2, 0, 2), 5)])
+        );
+        assert_eq!(room.size.load(Ordering::SeqCst), 2 * (1 + ENTRY_BYTES));
+
+        // Retries get the event id while the message is retained
+        let ack = room
+            .publish_envelope(&sender_header(2, 2), message("b"), 2)
+            .await
+            .unwrap();
+        assert!(ack.duplicate);
+        assert_eq!(ack.message_id, Some(5));
+
+        let ack = room
+            .publish_envelope(&sender_header(2, 0), message("b"), 2)
+            .await
+            .unwrap();
+        assert!(ack.duplicate);
+        assert_eq!(ack.message_id, None);
+    }
+
+    #[test]
+    fn test_issue_unique_idx_stops_at_the_last_index() {
+        let room = Room::empty(Arc::new(AtomicUsize::new(0)));
+        room.next_idx.lock().unwrap().insert(3, u16::MAX - 1);
+
+        assert_eq!(room.issue_unique_idx(3).unwrap(), u16::MAX - 1);
+        assert!(room.issue_unique_idx(3).is_err());
+        assert!(room.issue_unique_idx(3).is_err());
+
+        // Other epochs have their own indexes
+        assert_eq!(room.issue_unique_idx(4).unwrap(), 0);
+        assert_eq!(room.issue_unique_idx(4).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_accept_sequence_skips_published_sequences() {
+        let mut sequences = HashMap::new();
+
+        assert!(accept_sequence(&mut sequences, &header(0)).unwrap());
+        assert!(accept_sequence(&mut sequences, &header(2)).unwrap());
+        assert!(!accept_sequence(&mut sequences, &header(1)).unwrap());
+        assert!(!accept_sequence(&mut sequences, &header(2)).unwrap());
+    }
+
+    #[test]
+    fn test_accept_sequence_refuses_the_last_sequence() {
+        let mut sequences = HashMap::new();
+
+        assert!(accept_sequence(&mut sequences, &header(u64::MAX)).is_err());
+
+        // Earlier sequences are not reopened
+        assert!(accept_sequence(&mut sequences, &header(u64::MAX - 1)).unwrap());
+        assert!(accept_sequence(&mut sequences, &header(u64::MAX)).is_err());
+        assert!(!accept_sequence(&mut sequences, &header(0)).unwrap());
+    }
+
+    #[actix_web::test]
+    async fn test_subscription_resyncs_past_the_dropped_events() {
+        let app = init_service(relay(LimitsConfig {
+            room_retained_messages: 2,
+            ..LimitsConfig::default()
+        }))
+        .await;
+
+        for sequence in 0..4 {
+            let resp = call_service(
+                &app,
+                broadcast_request("room", envelope(1, sequence)).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        // Events 0 and 1 left the window, the subscriber resumes from 2
+        let resp = call_service(&app, subscribe_request("room", Some(0)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let text = read_until(resp, "id: 3").await;
+        let resync = text.find("event: resync").expect("No resync event");
+        assert!(text[resync..].contains(r#"data: {"next_event":2}"#));
+        assert!(text.find("id: 2").unwrap() > resync);
+        assert!(!text.contains("id: 1\n"));
+    }
+
+    #[actix_web::test]
+    async fn test_duplicate_envelope_returns_the_original_id() {
+        let app = init_service(relay(LimitsConfig::default())).await;
+
+        for sequence in 0..2 {
+            let resp = call_service(
+                &app,
+                broadcast_request("room", envelope(1, sequence)).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let resp = call_service(&app, broadcast_request("room", envelope(1, 0)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ack: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(
+            ack,
+            serde_json::json!({ "message_id": 0, "duplicate": true })
+        );
+
+        // The retry isn't delivered again
+        let resp = call_service(&app, subscribe_request("room", None).to_request()).await;
+        let text = read_until(resp, "id: 1").await;
+        assert_eq!(text.matches("event: new-message").count(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_broadcast_is_refused() {
+        let app = init_service(relay(LimitsConfig {
+            max_message_bytes: 64,
+            ..LimitsConfig::default()
+        }))
+        .await;
+
+        let resp = call_service(&app, broadcast_request("room", envelope(1, 0)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let app = init_service(relay(LimitsConfig {
+            max_payload_bytes: 64,
+            ..LimitsConfig::default()
+        }))
+        .await;
+
+        let resp = call_service(&app, broadcast_request("room", envelope(1, 0)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn test_subscribers_beyond_the_limits_are_shed() {
+        let app = init_service(relay(LimitsConfig {
+            max_subscribers: 2,
+            max_room_subscribers: 1,
+            ..LimitsConfig::default()
+        }))
+        .await;
+
+        let first = call_service(&app, subscribe_request("a", None).to_request()).await;
+        assert_eq!(first.status(), StatusCode::OK);
+
+        let resp = call_service(&app, subscribe_request("a", None).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "5");
+
+        let second = call_service(&app, subscribe_request("b", None).to_request()).await;
+        assert_eq!(second.status(), StatusCode::OK);
+
+        let resp = call_service(&app, subscribe_request("c", None).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Closing a subscription frees its slots
+        drop(first);
+        let resp = call_service(&app, subscribe_request("a", None).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        drop(second);
+    }
+
+    #[actix_web::test]
+    async fn test_broadcasts_beyond_the_backlog_are_shed() {
+        let size = envelope(1, 0).len() + ENTRY_BYTES;
+        let app = init_service(relay(LimitsConfig {
+            max_backlog_bytes: 2 * size,
+            ..LimitsConfig::default()
+        }))
+        .await;
+
+        for sequence in 0..2 {
+            let resp = call_service(
+                &app,
+                broadcast_request("a", envelope(1, sequence)).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        // Room a has no subscribers, it's dropped to make room for b
+        let subscription = call_service(&app, subscribe_request("b", None).to_request()).await;
+        for sequence in 0..2 {
+            let resp = call_service(
+                &app,
+                broadcast_request("b", envelope(1, sequence)).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let resp = call_service(&app, broadcast_request("b", envelope(1, 2)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "5");
+        assert_eq!(
+            read_body(resp).await,
+            web::Bytes::from_static(b"Message backlog is full")
+        );
+        drop(subscription);
+    }
+}
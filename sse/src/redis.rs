@@ -0,0 +1,131 @@
+//! Connections of the cluster mode to Redis, a single server or a Redis Cluster, with every
+//! connection attempt and reply bounded by the configured timeout
+
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use futures_util::stream::BoxStream;
+use redis::aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig};
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+use std::time::Duration;
+
+use crate::config::RedisConfig;
+
+/// Connection shared by the commands of every request, reconnected as needed
+#[derive(Clone)]
+pub enum Connection {
+    Server(ConnectionManager),
+    Cluster(ClusterConnection),
+}
+
+impl ConnectionLike for Connection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            Connection::Server(connection) => connection.req_packed_command(cmd),
+            Connection::Cluster(connection) => connection.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            Connection::Server(connection) => {
+                connection.req_packed_commands(pipeline, offset, count)
+            }
+            Connection::Cluster(connection) => {
+                connection.req_packed_commands(pipeline, offset, count)
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Connection::Server(connection) => connection.get_db(),
+            Connection::Cluster(connection) => connection.get_db(),
+        }
+    }
+}
+
+pub struct Client {
+    connection: Connection,
+    /// Client of the first node, the channel subscriptions are made on. Messages published
+    /// on any node of a Redis Cluster reach the subscribers of every node
+    pubsub: redis::Client,
+    timeout: Duration,
+}
+
+impl Client {
+    /// Connects to the server, or discovers the cluster from its nodes
+    pub async fn connect(config: &RedisConfig) -> Result<Self> {
+        let timeout = Duration::from_secs(config.timeout);
+        let first = config.urls.first().context("No Redis URL is configured")?;
+        let pubsub = redis::Client::open(first.as_str())
+            .with_context(|| format!("Invalid Redis URL {first}"))?;
+
+        let connection = if config.cluster {
+            let client = ClusterClient::builder(config.urls.clone())
+                .connection_timeout(timeout)
+                .response_timeout(timeout)
+                .build()
+                .context("Invalid Redis Cluster nodes")?;
+
+            Connection::Cluster(
+                client
+                    .get_async_connection()
+                    .await
+                    .context("Failed to connect to the Redis Cluster")?,
+            )
+        } else {
+            let config = ConnectionManagerConfig::new()
+                .set_connection_timeout(Some(timeout))
+                .set_response_timeout(Some(timeout));
+
+            Connection::Server(
+                pubsub
+                    .get_connection_manager_with_config(config)
+                    .await
+                    .context("Failed to connect to Redis")?,
+            )
+        };
+
+        Ok(Self {
+            connection,
+            pubsub,
+            timeout,
+        })
+    }
+
+    pub fn connection(&self) -> Connection {
+        self.connection.clone()
+    }
+
+    /// Channel and payload of the messages published to `channels`, ending when the
+    /// connection is lost
+    pub async fn subscribe(
+        &self,
+        channels: &[&str],
+    ) -> Result<BoxStream<'static, (String, Vec<u8>)>> {
+        let mut pubsub = tokio::time::timeout(self.timeout, self.pubsub.get_async_pubsub())
+            .await
+            .context("Timed out connecting to Redis")??;
+
+        tokio::time::timeout(self.timeout, pubsub.subscribe(channels))
+            .await
+            .context("Timed out subscribing to the Redis channels")??;
+
+        Ok(pubsub
+            .into_on_message()
+            .map(|msg| {
+                (
+                    msg.get_channel_name().to_string(),
+                    msg.get_payload_bytes().to_vec(),
+                )
+            })
+            .boxed())
+    }
+}
@@ -0,0 +1,81 @@
+//! Tokens of the relay rooms, signed by the app with a secret it shares with the relay. A
+//! token opens every room of one execution, the rooms named after the epoch of its execution
+//! id, so the relay checks it on every request without being told about the ceremony first
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+static DOMAIN: &[u8] = b"mpc-waas-room-token-v1";
+
+/// Derives the room epoch from the execution id shared by all parties
+pub fn epoch(execution_id: &[u8]) -> u64 {
+    let digest = Sha256::digest(execution_id);
+    u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+}
+
+/// Epoch a room is named after, the hex suffix of e.g. `keygen_12_3f9a0c1d2e4b5a67`
+fn room_epoch(room_id: &str) -> Option<u64> {
+    let (_, epoch) = room_id.rsplit_once('_')?;
+
+    match epoch.len() {
+        16 => u64::from_str_radix(epoch, 16).ok(),
+        _ => None,
+    }
+}
+
+fn mac(secret: &[u8], epoch: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(DOMAIN);
+    mac.update(&epoch.to_be_bytes());
+    mac
+}
+
+/// Hex token of the rooms of the execution
+pub fn sign(secret: &[u8], execution_id: &[u8]) -> String {
+    hex::encode(mac(secret, epoch(execution_id)).finalize().into_bytes())
+}
+
+/// Whether `token` was signed with `secret` for the execution the room belongs to
+pub fn verify(secret: &[u8], room_id: &str, token: &str) -> bool {
+    let (Some(epoch), Ok(tag)) = (room_epoch(room_id), hex::decode(token)) else {
+        return false;
+    };
+
+    mac(secret, epoch).verify_slice(&tag).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = &[7; 32];
+
+    fn room(execution_id: &[u8]) -> String {
+        format!("ns_keygen_12_{:016x}", epoch(execution_id))
+    }
+
+    #[test]
+    fn test_token_opens_the_rooms_of_its_execution() {
+        let token = sign(SECRET, b"execution");
+
+        assert!(verify(SECRET, &room(b"execution"), &token));
+        assert!(verify(
+            SECRET,
+            &format!("aux_12_{:016x}", epoch(b"execution")),
+            &token
+        ));
+
+        assert!(!verify(SECRET, &room(b"other execution"), &token));
+        assert!(!verify(&[8; 32], &room(b"execution"), &token));
+    }
+
+    #[test]
+    fn test_rejects_malformed_tokens_and_rooms() {
+        let token = sign(SECRET, b"execution");
+
+        assert!(!verify(SECRET, "keygen_12", &token));
+        assert!(!verify(SECRET, &room(b"execution"), "not hex"));
+        assert!(!verify(SECRET, &room(b"execution"), ""));
+        assert!(!verify(SECRET, &room(b"execution"), &token[..32]));
+    }
+}